@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+
+use glam::vec2;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use shin_core::layout::fixture::{FixtureChar, LayoutFixture};
+
+// converts a dump of the game's layouter commands into a layout fixture
+// the dump is printed by a debugger script, one command per line as `key=value` pairs, for example:
+// [12] type=0 codepoint=3042 time=12.5 x=48.49 y=90.625 width=46.55 height=47.5
+// only the char commands (type 0) are kept, the other ones don't have positions
+static COMMAND_FIELD_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"([a-z_]+)=([0-9a-fA-F.\-]+)").unwrap());
+
+const CHAR_COMMAND_TYPE: &str = "0";
+
+fn parse_command(line: &str) -> Option<FixtureChar> {
+    let fields = COMMAND_FIELD_REGEX
+        .captures_iter(line)
+        .map(|c| (c.get(1).unwrap().as_str(), c.get(2).unwrap().as_str()))
+        .collect::<HashMap<_, _>>();
+
+    if fields.get("type").copied() != Some(CHAR_COMMAND_TYPE) {
+        return None;
+    }
+
+    let float = |name: &str| -> f32 {
+        fields
+            .get(name)
+            .unwrap_or_else(|| panic!("Missing {} in {:?}", name, line))
+            .parse()
+            .unwrap_or_else(|_| panic!("Invalid {} in {:?}", name, line))
+    };
+
+    Some(FixtureChar {
+        codepoint: u16::from_str_radix(fields["codepoint"], 16).unwrap(),
+        time: float("time"),
+        position: vec2(float("x"), float("y")),
+        size: vec2(float("width"), float("height")),
+    })
+}
+
+pub fn main(dump_path: String, message: String, output_path: String) {
+    let dump = std::fs::read_to_string(dump_path).unwrap();
+
+    let mut fixture = LayoutFixture::new(message);
+    fixture.chars = dump.lines().filter_map(parse_command).collect();
+
+    println!(
+        "{} chars in the layout of {:?}",
+        fixture.chars.len(),
+        fixture.message
+    );
+
+    let mut output = Vec::new();
+    fixture.write(&mut output).unwrap();
+    std::fs::write(output_path, output).unwrap();
+}
@@ -5,7 +5,9 @@ use clap::Parser;
 mod buffer_parser;
 mod check_info_uniqueness;
 mod debug_tex_parser;
+mod layout_fixture;
 mod mask_visualize_vertices;
+mod pipeline_audit;
 
 // use clap to select what to do
 #[derive(Parser)]
@@ -18,8 +20,21 @@ struct Opts {
 enum JunkAction {
     BufferParser,
     DebugTexParser,
-    CheckInfoUniqueness { snr_path: String },
-    MaskVisualizeVertices { msk_path: String },
+    CheckInfoUniqueness {
+        snr_path: String,
+    },
+    MaskVisualizeVertices {
+        msk_path: String,
+    },
+    /// Convert a dump of the game's layouter commands into a layout fixture
+    LayoutFixture {
+        dump_path: String,
+        /// The message that was laid out, in the layouter markup
+        message: String,
+        output_path: String,
+    },
+    /// Build all the render pipelines on a headless device and report how long they take
+    PipelineAudit,
 }
 
 fn main() {
@@ -29,5 +44,11 @@ fn main() {
         JunkAction::DebugTexParser => debug_tex_parser::main(),
         JunkAction::CheckInfoUniqueness { snr_path } => check_info_uniqueness::main(snr_path),
         JunkAction::MaskVisualizeVertices { msk_path } => mask_visualize_vertices::main(msk_path),
+        JunkAction::LayoutFixture {
+            dump_path,
+            message,
+            output_path,
+        } => layout_fixture::main(dump_path, message, output_path),
+        JunkAction::PipelineAudit => pipeline_audit::main(),
     }
 }
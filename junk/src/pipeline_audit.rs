@@ -0,0 +1,53 @@
+use std::time::Duration;
+
+use owo_colors::OwoColorize;
+use shin_render::{BindGroupLayouts, PipelineStats, Pipelines, SRGB_TEXTURE_FORMAT};
+
+// builds all the render pipelines on a headless device and reports how long each of them took
+// the pipelines are built twice: the first round is what the player sees on the first start,
+// the second one shows how much the driver caches (the pipeline cache prewarm list should target the slow cold ones)
+
+fn print_round(title: &str, stats: &[PipelineStats]) {
+    let total = stats.iter().map(|s| s.build_time).sum::<Duration>();
+    println!(
+        "{} ({} pipelines, {:?} total)",
+        title.blue().bold(),
+        stats.len(),
+        total
+    );
+
+    let mut stats = stats.to_vec();
+    stats.sort_by_key(|s| std::cmp::Reverse(s.build_time));
+    for s in stats {
+        println!(
+            "  {:<16} {:<24} {:>12?}",
+            s.name,
+            format!("{:?}", s.texture_format),
+            s.build_time
+        );
+    }
+}
+
+pub fn main() {
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends: wgpu::util::backend_bits_from_env().unwrap_or(wgpu::Backends::all()),
+        ..Default::default()
+    });
+    let adapter = pollster::block_on(wgpu::util::initialize_adapter_from_env_or_default(
+        &instance, None,
+    ))
+    .expect("Failed to find an adapter");
+    println!("Adapter: {:?}", adapter.get_info());
+
+    let (device, _queue) =
+        pollster::block_on(adapter.request_device(&shin_render::init::device_descriptor(), None))
+            .expect("Failed to create a device");
+
+    let bind_group_layouts = BindGroupLayouts::new(&device);
+
+    // the window uses the surface format for the final pass, which is usually the same sRGB format
+    let (_, cold) = Pipelines::new_with_stats(&device, &bind_group_layouts, SRGB_TEXTURE_FORMAT);
+    print_round("Cold", &cold);
+    let (_, warm) = Pipelines::new_with_stats(&device, &bind_group_layouts, SRGB_TEXTURE_FORMAT);
+    print_round("Warm", &warm);
+}
@@ -1,14 +1,27 @@
-use std::io::{Read, Seek, SeekFrom};
+use std::io::{IsTerminal, Read, Seek, SeekFrom};
 
 use anyhow::{Context, Result};
 use binrw::BinRead;
 use camino::Utf8PathBuf;
-use shin_asm::compile::{
-    diagnostics::{AriadneDbCache, HirDiagnosticAccumulator, SourceDiagnosticAccumulator},
-    hir, File, Program,
+use serde::Serialize;
+use shin_asm::{
+    compile::{
+        db::Database,
+        diagnostics::{render_diagnostics, Diagnostic, Span},
+        hir, Db, File, Program,
+    },
+    ide,
 };
 use shin_core::format::scenario::ScenarioHeader;
 
+#[derive(clap::ValueEnum, Debug, Clone, Copy)]
+pub enum DiagnosticsFormat {
+    /// Annotated source snippets
+    Human,
+    /// A JSON array, for editors and other tools
+    Json,
+}
+
 #[derive(clap::Subcommand, Debug)]
 pub enum AssemblerCommand {
     /// Lex the input file and dump the tokens
@@ -30,6 +43,130 @@ pub enum AssemblerCommand {
         #[clap(short, long, default_value = "main.snr")]
         output: Utf8PathBuf,
     },
+    /// Compile the source files without producing an SNR file, only reporting the problems
+    Check {
+        /// List of input `.sal` files
+        inputs: Vec<Utf8PathBuf>,
+        /// How to print the diagnostics
+        #[clap(long, value_enum, default_value_t = DiagnosticsFormat::Human)]
+        format: DiagnosticsFormat,
+    },
+}
+
+/// A location in a source file. Offsets are in bytes, lines and columns (in characters) start from 1
+#[derive(Serialize)]
+struct JsonSpan {
+    file: String,
+    start: usize,
+    end: usize,
+    line_start: usize,
+    column_start: usize,
+    line_end: usize,
+    column_end: usize,
+}
+
+impl JsonSpan {
+    fn new(db: &dyn Db, span: Span) -> Self {
+        let contents = span.file().contents(db);
+        let line_column = |offset: usize| {
+            let before = &contents[..offset];
+            let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+            (
+                before.matches('\n').count() + 1,
+                before[line_start..].chars().count() + 1,
+            )
+        };
+
+        let (start, end) = (span.range().start().into(), span.range().end().into());
+        let (line_start, column_start) = line_column(start);
+        let (line_end, column_end) = line_column(end);
+        Self {
+            file: span.file().path(db),
+            start,
+            end,
+            line_start,
+            column_start,
+            line_end,
+            column_end,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonLabel {
+    message: String,
+    span: JsonSpan,
+}
+
+#[derive(Serialize)]
+struct JsonDiagnostic {
+    severity: &'static str,
+    message: String,
+    span: JsonSpan,
+    labels: Vec<JsonLabel>,
+    notes: Vec<String>,
+}
+
+impl JsonDiagnostic {
+    fn new(db: &dyn Db, diagnostic: Diagnostic<Span>) -> Self {
+        Self {
+            severity: diagnostic.severity.name(),
+            message: diagnostic.message,
+            span: JsonSpan::new(db, diagnostic.location),
+            labels: diagnostic
+                .additional_labels
+                .into_iter()
+                .map(|(message, span)| JsonLabel {
+                    message,
+                    span: JsonSpan::new(db, span),
+                })
+                .collect(),
+            notes: diagnostic.notes,
+        }
+    }
+}
+
+fn load_program(db: &dyn Db, inputs: Vec<Utf8PathBuf>) -> Result<Program> {
+    let inputs = inputs
+        .into_iter()
+        .map(|path| {
+            let contents = std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read file {:?}", path))?;
+            let path = path.as_str();
+            Ok(File::new(db, path.to_string(), contents))
+        })
+        .collect::<Result<Vec<_>>>()
+        .context("Failed to read input files")?;
+
+    // files pulled in by `include` directives are loaded relative to the including file
+    Ok(Program::load_with_includes(db, inputs, |path| {
+        std::fs::read_to_string(path).ok()
+    }))
+}
+
+/// Prints the diagnostics of the program, failing if any of them is an error
+fn report_diagnostics(db: &dyn Db, program: Program, format: DiagnosticsFormat) -> Result<()> {
+    let diagnostics = ide::diagnostics(db, program);
+    let has_errors = diagnostics.iter().any(|d| d.is_error());
+
+    match format {
+        DiagnosticsFormat::Human => {
+            let color = std::io::stderr().is_terminal();
+            eprint!("{}", render_diagnostics(db, diagnostics, color));
+        }
+        DiagnosticsFormat::Json => {
+            let diagnostics = diagnostics
+                .into_iter()
+                .map(|d| JsonDiagnostic::new(db, d))
+                .collect::<Vec<_>>();
+            println!("{}", serde_json::to_string_pretty(&diagnostics)?);
+        }
+    }
+
+    if has_errors {
+        return Err(anyhow::anyhow!("Compilation failed"));
+    }
+    Ok(())
 }
 
 pub fn assembler_command(command: AssemblerCommand) -> Result<()> {
@@ -73,45 +210,16 @@ pub fn assembler_command(command: AssemblerCommand) -> Result<()> {
             headers_from.read_exact(&mut head_data)?;
             drop(headers_from);
 
-            let db = shin_asm::compile::db::Database::default();
+            let db = Database::default();
             let db = &db;
 
             let donor_headers =
                 shin_asm::compile::generate_snr::DonorHeaders::new(db, head_data, snr_header);
 
-            let inputs = inputs
-                .into_iter()
-                .map(|path| {
-                    let contents = std::fs::read_to_string(&path)
-                        .with_context(|| format!("Failed to read file {:?}", path))?;
-                    let path = path.as_str();
-                    Ok(File::new(db, path.to_string(), contents))
-                })
-                .collect::<Result<Vec<_>>>()
-                .context("Failed to read input files")?;
-
-            let program = Program::new(db, inputs);
+            let program = load_program(db, inputs)?;
+            report_diagnostics(db, program, DiagnosticsFormat::Human)?;
 
             let lowered_program = hir::lower::lower_program(db, program);
-
-            let hir_errors =
-                hir::lower::lower_program::accumulated::<HirDiagnosticAccumulator>(db, program);
-            let source_errors =
-                hir::lower::lower_program::accumulated::<SourceDiagnosticAccumulator>(db, program);
-
-            let mut ariadne_errors = Vec::new();
-            ariadne_errors.extend(source_errors.into_iter().map(|e| e.into_ariadne(db)));
-            ariadne_errors.extend(hir_errors.into_iter().map(|e| e.into_ariadne(db)));
-
-            if !ariadne_errors.is_empty() {
-                let mut cache = AriadneDbCache::new(db);
-
-                for error in ariadne_errors {
-                    error.eprint(&mut cache).context("Failed to print error")?;
-                }
-                return Err(anyhow::anyhow!("Compilation failed"));
-            }
-
             let output_bytes =
                 shin_asm::compile::generate_snr::generate_snr(db, donor_headers, lowered_program);
 
@@ -119,5 +227,12 @@ pub fn assembler_command(command: AssemblerCommand) -> Result<()> {
 
             Ok(())
         }
+        AssemblerCommand::Check { inputs, format } => {
+            let db = Database::default();
+            let db = &db;
+
+            let program = load_program(db, inputs)?;
+            report_diagnostics(db, program, format)
+        }
     }
 }
@@ -1,18 +1,62 @@
 use std::{
     borrow::Cow,
-    fs::File,
+    collections::HashSet,
+    fs::{File, OpenOptions},
     io,
-    io::{BufWriter, Cursor},
+    io::{BufReader, BufWriter, Cursor, Seek, SeekFrom, Write},
+    path::Path,
 };
 
-use anyhow::Context;
+use anyhow::{bail, Context};
 use binrw::BinWrite;
+use bytes::Bytes;
 use hound::WavSpec;
 use ogg::PacketWriteEndInfo;
-use shin_core::format::audio::{AudioInfo, AudioSource};
+use shin_core::format::{
+    audio::{AudioDecoder, AudioFile, AudioInfo, AudioSource},
+    scenario::{info::BgmInfoItem, Scenario},
+};
 
 use crate::AudioCommand;
 
+#[derive(clap::ValueEnum, Debug, Clone, Copy)]
+pub enum ExportFormat {
+    /// Decoded 32-bit float WAV, with a `smpl` chunk for the loop points
+    Wav,
+    /// The original opus packets in an Ogg container, with `LOOPSTART`/`LOOPLENGTH` comments
+    Opus,
+}
+
+impl ExportFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Wav => "wav",
+            ExportFormat::Opus => "opus",
+        }
+    }
+}
+
+/// How `remux` should re-encode the audio instead of copying the opus packets
+#[derive(Debug, Clone, Copy)]
+pub struct EncodeOptions {
+    /// In kbit/s, `None` lets the encoder choose
+    pub bitrate: Option<u32>,
+    /// `None` keeps the original sample rate
+    pub sample_rate: Option<u32>,
+    pub mono: bool,
+}
+
+impl EncodeOptions {
+    /// Returns `None` if nothing asks for re-encoding
+    fn new(bitrate: Option<u32>, sample_rate: Option<u32>, mono: bool) -> Option<Self> {
+        (bitrate.is_some() || sample_rate.is_some() || mono).then_some(Self {
+            bitrate,
+            sample_rate,
+            mono,
+        })
+    }
+}
+
 #[derive(BinWrite)]
 #[brw(magic(b"OpusHead"))]
 struct OpusIdHeader {
@@ -24,7 +68,7 @@ struct OpusIdHeader {
     pub mapping_family: u8,
 }
 
-struct OpusOggWriter<'writer, W: io::Write> {
+pub struct OpusOggWriter<'writer, W: io::Write> {
     inner: ogg::writing::PacketWriter<'writer, W>,
     frame_idx: usize,
     frame_samples: usize,
@@ -33,7 +77,7 @@ struct OpusOggWriter<'writer, W: io::Write> {
 impl<'writer, W: io::Write> OpusOggWriter<'writer, W> {
     const SERIAL: u32 = 42;
 
-    pub fn new(inner: W, audio_info: &AudioInfo) -> io::Result<Self> {
+    pub fn new(inner: W, audio_info: &AudioInfo, comments: &[String]) -> io::Result<Self> {
         let mut inner = ogg::writing::PacketWriter::new(inner);
 
         // write the Opus Identification Header
@@ -60,8 +104,16 @@ impl<'writer, W: io::Write> OpusOggWriter<'writer, W> {
         )?;
 
         // write the Opus Comment Header
-        // vendor string length + vendor string + user comment count (always 0)
-        let opus_comment_header_bytes = b"OpusTags\x11\x00\x00\x00sdu remuxer\x00\x00\x00\x00";
+        // vendor string length + vendor string + user comment count + (comment length + comment)*
+        const VENDOR: &[u8] = b"sdu remuxer";
+        let mut opus_comment_header_bytes = b"OpusTags".to_vec();
+        opus_comment_header_bytes.extend_from_slice(&(VENDOR.len() as u32).to_le_bytes());
+        opus_comment_header_bytes.extend_from_slice(VENDOR);
+        opus_comment_header_bytes.extend_from_slice(&(comments.len() as u32).to_le_bytes());
+        for comment in comments {
+            opus_comment_header_bytes.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+            opus_comment_header_bytes.extend_from_slice(comment.as_bytes());
+        }
 
         inner.write_packet(
             opus_comment_header_bytes,
@@ -95,61 +147,470 @@ impl<'writer, W: io::Write> OpusOggWriter<'writer, W> {
     }
 }
 
+/// The `smpl` chunk of a WAV file with a single forward loop, understood by most audio editors and samplers
+#[derive(BinWrite)]
+#[brw(magic(b"smpl"))]
+struct SamplerChunk {
+    pub chunk_size: u32,
+    pub manufacturer: u32,
+    pub product: u32,
+    /// Duration of a sample in nanoseconds
+    pub sample_period: u32,
+    pub midi_unity_note: u32,
+    pub midi_pitch_fraction: u32,
+    pub smpte_format: u32,
+    pub smpte_offset: u32,
+    pub sample_loop_count: u32,
+    pub sampler_data_size: u32,
+    pub cue_point_id: u32,
+    /// 0 for a forward loop
+    pub loop_type: u32,
+    pub loop_start: u32,
+    /// The last sample of the loop (inclusive)
+    pub loop_end: u32,
+    pub loop_fraction: u32,
+    /// 0 for an infinite loop
+    pub loop_play_count: u32,
+}
+
+impl SamplerChunk {
+    const SIZE: u32 = 60;
+
+    pub fn new(sample_rate: u32, loop_start: u32, loop_end: u32) -> Self {
+        Self {
+            chunk_size: Self::SIZE,
+            manufacturer: 0,
+            product: 0,
+            sample_period: 1_000_000_000 / sample_rate,
+            midi_unity_note: 60,
+            midi_pitch_fraction: 0,
+            smpte_format: 0,
+            smpte_offset: 0,
+            sample_loop_count: 1,
+            sampler_data_size: 0,
+            cue_point_id: 0,
+            loop_type: 0,
+            loop_start,
+            loop_end: loop_end - 1,
+            loop_fraction: 0,
+            loop_play_count: 0,
+        }
+    }
+}
+
+fn write_riff_chunk(output: &mut Vec<u8>, id: &[u8; 4], data: &[u8]) {
+    output.extend_from_slice(id);
+    output.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    output.extend_from_slice(data);
+    // the chunks are aligned to 2 bytes, the padding is not counted in the size
+    if data.len() % 2 == 1 {
+        output.push(0);
+    }
+}
+
+/// The metadata written into the converted files
+#[derive(Debug)]
+struct AudioTags {
+    /// The track name from the scenario
+    title: Option<String>,
+    /// The looped region, in samples. `None` if the file doesn't specify one
+    loop_points: Option<(u32, u32)>,
+}
+
+impl AudioTags {
+    fn new(info: &AudioInfo, title: Option<String>) -> Self {
+        Self {
+            title,
+            loop_points: (info.loop_start < info.loop_end)
+                .then_some((info.loop_start, info.loop_end)),
+        }
+    }
+
+    /// The tags as Vorbis comments, with the loop points as `LOOPSTART`/`LOOPLENGTH` understood by many players and game engines
+    ///
+    /// The players always decode Opus at 48 kHz, so the loop points are scaled to it.
+    fn vorbis_comments(&self, sample_rate: u32) -> Vec<String> {
+        let mut comments = Vec::new();
+        if let Some(title) = &self.title {
+            comments.push(format!("TITLE={}", title));
+        }
+        if let Some((start, end)) = self.loop_points {
+            let scale = |samples: u32| samples as u64 * 48000 / sample_rate as u64;
+            comments.push(format!("LOOPSTART={}", scale(start)));
+            comments.push(format!("LOOPLENGTH={}", scale(end) - scale(start)));
+        }
+        comments
+    }
+}
+
+/// Appends the title (as a `LIST`/`INFO` chunk) and the loop points (as a `smpl` chunk) to a WAV file written by hound, which can't write them by itself
+fn append_wav_metadata(path: &Path, tags: &AudioTags, sample_rate: u32) -> anyhow::Result<()> {
+    let mut chunks = Vec::new();
+
+    if let Some(title) = &tags.title {
+        let mut name = title.as_bytes().to_vec();
+        name.push(0);
+        let mut info = b"INFO".to_vec();
+        write_riff_chunk(&mut info, b"INAM", &name);
+        write_riff_chunk(&mut chunks, b"LIST", &info);
+    }
+
+    if let Some((loop_start, loop_end)) = tags.loop_points {
+        let mut sampler = Cursor::new(Vec::new());
+        SamplerChunk::new(sample_rate, loop_start, loop_end)
+            .write_le(&mut sampler)
+            .context("Writing the smpl chunk")?;
+        chunks.extend_from_slice(sampler.get_ref());
+    }
+
+    if chunks.is_empty() {
+        return Ok(());
+    }
+
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+        .context("Opening the WAV file")?;
+    let end = file.seek(SeekFrom::End(0))?;
+    file.write_all(&chunks)?;
+    // patch the RIFF chunk size, it covers everything after itself
+    file.seek(SeekFrom::Start(4))?;
+    file.write_all(&((end + chunks.len() as u64 - 8) as u32).to_le_bytes())?;
+
+    Ok(())
+}
+
+/// Decodes the audio into a 32-bit float WAV file
+fn decode_to_wav(
+    source: impl Iterator<Item = (f32, f32)>,
+    info: &AudioInfo,
+    output_path: &Path,
+) -> anyhow::Result<()> {
+    let writer = File::create(output_path).context("Creating output file")?;
+    let writer = BufWriter::new(writer);
+    let mut writer = hound::WavWriter::new(
+        writer,
+        WavSpec {
+            channels: info.channel_count,
+            sample_rate: info.sample_rate,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        },
+    )
+    .context("Creating WAV writer")?;
+
+    for (left, right) in source {
+        writer.write_sample(left).context("Writing sample")?;
+        writer.write_sample(right).context("Writing sample")?;
+    }
+
+    writer.finalize().context("Finalizing the WAV file")?;
+
+    Ok(())
+}
+
+/// Decodes the NXA file into a WAV file with the tags
+fn decode_file(input_path: &Path, output_path: &Path, title: Option<String>) -> anyhow::Result<()> {
+    // stream the file, so that the long tracks don't have to be loaded into memory
+    let reader = File::open(input_path).context("Opening input file")?;
+    let decoder = AudioDecoder::from_reader(BufReader::new(reader)).context("Creating decoder")?;
+    let info = decoder.audio_info().clone();
+
+    decode_to_wav(AudioSource::new(decoder), &info, output_path)?;
+    append_wav_metadata(output_path, &AudioTags::new(&info, title), info.sample_rate)
+}
+
+/// Remuxes the opus packets of the NXA file into an Ogg Opus file with the tags, or re-encodes them if `encode` is set
+fn remux_file(
+    input_path: &Path,
+    output_path: &Path,
+    title: Option<String>,
+    encode: Option<EncodeOptions>,
+) -> anyhow::Result<()> {
+    let audio = std::fs::read(input_path).context("Reading input file")?;
+    let audio = shin_core::format::audio::read_audio(&audio)?;
+
+    let info = audio.info().clone();
+    // the loop points are stored at 48 kHz, so they stay valid after resampling
+    let comments = AudioTags::new(&info, title).vorbis_comments(info.sample_rate);
+
+    if let Some(encode) = encode {
+        #[cfg(feature = "opus-encode")]
+        return crate::opus_encode::reencode(&audio, encode, &comments, output_path);
+        #[cfg(not(feature = "opus-encode"))]
+        bail!(
+            "Can't re-encode with {:?}: sdu was built without the `opus-encode` feature",
+            encode
+        );
+    }
+
+    let mut frame_reader = audio.read_frames();
+
+    let writer = File::create(output_path).context("Creating output file")?;
+    let writer = BufWriter::new(writer);
+    let mut writer = OpusOggWriter::new(writer, &info, &comments).context("Creating OGG writer")?;
+
+    while let Some(frame) = frame_reader.get_next_frame() {
+        // allocating here to make life easier
+        writer
+            .write_frame(Vec::from(frame), !frame_reader.has_next_frame())
+            .context("Writing frame")?;
+    }
+
+    Ok(())
+}
+
+fn read_scenario(path: &Path) -> anyhow::Result<Scenario> {
+    let scenario = std::fs::read(path).context("Reading the scenario")?;
+    Scenario::new(Bytes::from(scenario)).context("Parsing the scenario")
+}
+
+/// The human-readable name of the track, falling back to the file name if it doesn't have one
+fn bgm_title(bgm: &BgmInfoItem) -> &str {
+    match bgm.display_name.as_str() {
+        "" => bgm.name.as_str(),
+        display_name => display_name,
+    }
+}
+
+/// Finds the name of the track in the `audio_path` file in the BGM info table of the scenario
+fn find_bgm_title(
+    scenario_path: Option<&Path>,
+    audio_path: &Path,
+) -> anyhow::Result<Option<String>> {
+    let Some(scenario_path) = scenario_path else {
+        return Ok(None);
+    };
+    let scenario = read_scenario(scenario_path)?;
+    let stem = audio_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_lowercase());
+
+    let title = scenario
+        .info_tables()
+        .bgm_info
+        .iter()
+        .find(|bgm| Some(bgm.name.as_str().to_lowercase()) == stem)
+        .map(|bgm| bgm_title(bgm).to_string());
+    if title.is_none() {
+        eprintln!(
+            "{} is not in the BGM info table of the scenario",
+            audio_path.display()
+        );
+    }
+    Ok(title)
+}
+
+/// Replaces the characters that can't be used in the file names on some platforms
+fn sanitize_file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+/// Converts all the BGM tracks referenced by the scenario into files named after the tracks, with the tags
+fn export_all_bgm(
+    scenario_path: &Path,
+    rom_dir: &Path,
+    output_dir: &Path,
+    format: ExportFormat,
+) -> anyhow::Result<()> {
+    let scenario = read_scenario(scenario_path)?;
+    std::fs::create_dir_all(output_dir).context("Creating the output directory")?;
+
+    let mut exported = HashSet::new();
+    let mut failed = 0;
+    for (id, bgm) in scenario.info_tables().bgm_info.iter().enumerate() {
+        let path = bgm.path();
+        // several entries can refer to the same file
+        if !exported.insert(path.clone()) {
+            continue;
+        }
+
+        let title = bgm_title(bgm);
+        let input_path = rom_dir.join(path.trim_start_matches('/'));
+        let output_path = output_dir.join(format!(
+            "{:03} {}.{}",
+            id,
+            sanitize_file_name(title),
+            format.extension()
+        ));
+        println!("{} -> {}", input_path.display(), output_path.display());
+
+        let title = Some(title.to_string());
+        let result = match format {
+            ExportFormat::Wav => decode_file(&input_path, &output_path, title),
+            ExportFormat::Opus => remux_file(&input_path, &output_path, title, None),
+        };
+        if let Err(e) = result {
+            eprintln!("Failed to convert {}: {:?}", input_path.display(), e);
+            failed += 1;
+        }
+    }
+
+    if failed > 0 {
+        bail!("Failed to convert {} tracks", failed);
+    }
+
+    Ok(())
+}
+
+type Sample = (f32, f32);
+
+fn read_samples(audio: &AudioFile, position: u32, count: u32) -> anyhow::Result<Vec<Sample>> {
+    let mut source = AudioSource::new(AudioDecoder::new(audio).context("Creating decoder")?);
+    source.samples_seek(position).context("Seeking")?;
+
+    let mut result = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let Some(sample) = source.read_sample() else {
+            bail!(
+                "Unexpected end of file at sample {}",
+                position + result.len() as u32
+            );
+        };
+        result.push(sample);
+    }
+
+    Ok(result)
+}
+
+fn sample_distance(a: Sample, b: Sample) -> f32 {
+    f32::max((a.0 - b.0).abs(), (a.1 - b.1).abs())
+}
+
+/// Average distance between adjacent samples, used as a baseline for "normal" signal movement
+fn mean_delta(samples: &[Sample]) -> f32 {
+    if samples.len() < 2 {
+        return 0.0;
+    }
+
+    samples
+        .windows(2)
+        .map(|w| sample_distance(w[0], w[1]))
+        .sum::<f32>()
+        / (samples.len() - 1) as f32
+}
+
+fn loop_check(audio: &AudioFile, window: u32, threshold: f32) -> anyhow::Result<bool> {
+    let info = audio.info();
+    let Some(region) = info.loop_region() else {
+        bail!("The file has no samples to loop");
+    };
+
+    println!(
+        "Loop region: {}..{} (intro {} samples, loop {} samples, tail {} samples)",
+        region.start,
+        region.end,
+        region.start,
+        region.len(),
+        info.num_samples - region.end
+    );
+
+    let window = window.min(region.len());
+    if window == 0 {
+        bail!("The loop region is empty");
+    }
+    let before_start = region.end - window;
+
+    // what is heard right before the jump and right after it when decoding linearly
+    let before = read_samples(audio, before_start, window)?;
+    let after = read_samples(audio, region.start, window)?;
+
+    // what is actually produced by the looping source
+    let mut source = AudioSource::new(AudioDecoder::new(audio).context("Creating decoder")?);
+    source.set_loop_region(Some(region))?;
+    source.samples_seek(before_start).context("Seeking")?;
+    let looped = (0..window * 2)
+        .map(|_| source.read_sample().context("Looping source ended"))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let (looped_before, looped_after) = looped.split_at(window as usize);
+
+    let mismatch = looped_before
+        .iter()
+        .chain(looped_after)
+        .zip(before.iter().chain(&after))
+        .map(|(&a, &b)| sample_distance(a, b))
+        .fold(0.0, f32::max);
+
+    let jump = sample_distance(*looped_before.last().unwrap(), looped_after[0]);
+    let baseline = f32::max(mean_delta(&before), mean_delta(&after));
+    let ratio = if baseline > 0.0 {
+        jump / baseline
+    } else if jump > 0.0 {
+        f32::INFINITY
+    } else {
+        0.0
+    };
+
+    println!("Max deviation from linear decode: {:.6}", mismatch);
+    println!(
+        "Jump at the boundary: {:.6} (average delta {:.6}, ratio {:.2})",
+        jump, baseline, ratio
+    );
+
+    let mut ok = true;
+    // the decoder is pre-rolled after seeking, so the output should be (almost) bit-exact
+    if mismatch > 1e-3 {
+        println!("WARNING: looped output differs from the linear decode, the seek is not seamless");
+        ok = false;
+    }
+    if ratio > threshold {
+        println!("WARNING: discontinuity detected at the loop boundary");
+        ok = false;
+    }
+    if ok {
+        println!("OK: the loop is seamless");
+    }
+
+    Ok(ok)
+}
+
 pub fn audio_command(command: AudioCommand) -> anyhow::Result<()> {
     match command {
         AudioCommand::Decode {
             audio_path,
             output_path,
+            from_scenario,
         } => {
-            let audio = std::fs::read(audio_path).context("Reading input file")?;
-            let audio = shin_core::format::audio::read_audio(&audio)?;
-
-            let info = audio.info().clone();
-
-            let writer = File::create(output_path).context("Creating output file")?;
-            let writer = BufWriter::new(writer);
-            let mut writer = hound::WavWriter::new(
-                writer,
-                WavSpec {
-                    channels: info.channel_count,
-                    sample_rate: info.sample_rate,
-                    bits_per_sample: 32,
-                    sample_format: hound::SampleFormat::Float,
-                },
-            )
-            .context("Creating WAV writer")?;
-
-            let mut audio_source = AudioSource::new(audio.decode().context("Creating decoder")?);
-
-            while let Some((left, right)) = audio_source.read_sample() {
-                writer.write_sample(left).context("Writing sample")?;
-                writer.write_sample(right).context("Writing sample")?;
-            }
-
-            writer.finalize().context("Finalizing the WAV file")?;
-
-            Ok(())
+            let title = find_bgm_title(from_scenario.as_deref(), &audio_path)?;
+            decode_file(&audio_path, &output_path, title)
         }
+        AudioCommand::DecodeAll {
+            from_scenario,
+            rom_dir,
+            output_dir,
+            format,
+        } => export_all_bgm(&from_scenario, &rom_dir, &output_dir, format),
         AudioCommand::Remux {
             audio_path,
             output_path,
+            from_scenario,
+            bitrate,
+            sample_rate,
+            mono,
+        } => {
+            let title = find_bgm_title(from_scenario.as_deref(), &audio_path)?;
+            let encode = EncodeOptions::new(bitrate, sample_rate, mono);
+            remux_file(&audio_path, &output_path, title, encode)
+        }
+        AudioCommand::LoopCheck {
+            audio_path,
+            window,
+            threshold,
         } => {
             let audio = std::fs::read(audio_path).context("Reading input file")?;
             let audio = shin_core::format::audio::read_audio(&audio)?;
 
-            let info = audio.info().clone();
-
-            let mut frame_reader = audio.read_frames();
-
-            let writer = File::create(output_path).context("Creating output file")?;
-            let writer = BufWriter::new(writer);
-            let mut writer = OpusOggWriter::new(writer, &info).context("Creating OGG writer")?;
-
-            while let Some(frame) = frame_reader.get_next_frame() {
-                // allocating here to make life easier
-                writer
-                    .write_frame(Vec::from(frame), !frame_reader.has_next_frame())
-                    .context("Writing frame")?;
+            if !loop_check(&audio, window, threshold)? {
+                bail!("Loop check failed");
             }
 
             Ok(())
@@ -0,0 +1,148 @@
+//! Contact sheets: all the expression and mouth combinations of a bustup composited into a single labeled image
+
+use anyhow::{Context, Result};
+use image::{imageops, GenericImageView, Rgba, RgbaImage};
+use itertools::Itertools;
+use shin_core::format::{
+    bustup::Bustup,
+    font::{GlyphMipLevel, GlyphTrait, LazyFont},
+    picture::PictureChunk,
+};
+
+const BACKGROUND: Rgba<u8> = Rgba([255, 255, 255, 255]);
+const CAPTION_COLOR: [u8; 3] = [0, 0, 0];
+/// Space between the cells and around the sheet, in pixels
+const PADDING: u32 = 16;
+
+/// Draws the `text` with the top-left corner at (`x`, `y`), clipping it to `max_width`
+fn draw_text(
+    image: &mut RgbaImage,
+    font: &LazyFont,
+    text: &str,
+    x: u32,
+    y: u32,
+    max_width: u32,
+) -> Result<()> {
+    let ascent = font.get_ascent() as i32;
+    let mut pen_x = 0i32;
+
+    for character in text.chars() {
+        let Ok(codepoint) = u16::try_from(character as u32) else {
+            continue;
+        };
+        let glyph = font.get_glyph_for_character(codepoint);
+        let info = glyph.get_info();
+        if pen_x + info.advance_width as i32 > max_width as i32 {
+            break;
+        }
+
+        let glyph = glyph
+            .decompress()
+            .with_context(|| format!("Decompressing the glyph for {:?}", character))?;
+        let (width, height) = info.actual_size();
+        let glyph_image = glyph
+            .get_image(GlyphMipLevel::Level0)
+            .view(0, 0, width, height);
+
+        let left = x as i32 + pen_x + info.bearing_x as i32;
+        let top = y as i32 + ascent - info.bearing_y as i32;
+        for (gx, gy, coverage) in glyph_image.pixels() {
+            let (px, py) = (left + gx as i32, top + gy as i32);
+            if px < 0 || py < 0 || px >= image.width() as i32 || py >= image.height() as i32 {
+                continue;
+            }
+
+            let alpha = coverage[0] as u32;
+            let pixel = image.get_pixel_mut(px as u32, py as u32);
+            for (channel, &color) in pixel.0.iter_mut().zip(CAPTION_COLOR.iter()) {
+                *channel = ((*channel as u32 * (255 - alpha) + color as u32 * alpha) / 255) as u8;
+            }
+        }
+
+        pen_x += info.advance_width as i32;
+    }
+
+    Ok(())
+}
+
+/// Composites the expression over the base image, with the given mouth (if any)
+fn composite(bustup: &Bustup, expression_name: &str, mouth: Option<usize>) -> RgbaImage {
+    let expression = &bustup.expressions[expression_name];
+    let mut image = bustup.base_image.clone();
+
+    // the chunk offsets are relative to the base image
+    let mut overlay = |chunk: &PictureChunk| {
+        if !chunk.is_empty() {
+            imageops::overlay(
+                &mut image,
+                &chunk.data,
+                chunk.offset_x as i64,
+                chunk.offset_y as i64,
+            );
+        }
+    };
+    overlay(&expression.face_chunk);
+    if let Some(mouth) = mouth {
+        overlay(&expression.mouth_chunks[mouth]);
+    }
+
+    image
+}
+
+/// Builds a grid with a row per expression and a column per mouth position, each cell captioned with the expression name and the mouth index
+///
+/// The bustups are scaled by `scale` to keep the sheet size manageable.
+pub fn build_contact_sheet(bustup: &Bustup, font: &LazyFont, scale: f32) -> Result<RgbaImage> {
+    let expression_names = bustup.expressions.keys().sorted().collect::<Vec<_>>();
+    let columns = bustup
+        .expressions
+        .values()
+        .map(|e| e.mouth_chunks.len())
+        .max()
+        .unwrap_or(0)
+        .max(1) as u32;
+    let rows = expression_names.len().max(1) as u32;
+
+    let cell_width = ((bustup.base_image.width() as f32 * scale).round() as u32).max(1);
+    let cell_height = ((bustup.base_image.height() as f32 * scale).round() as u32).max(1);
+    let caption_height = font.get_line_height() as u32;
+
+    let column_stride = cell_width + PADDING;
+    let row_stride = cell_height + caption_height + PADDING;
+    let mut sheet = RgbaImage::from_pixel(
+        PADDING + columns * column_stride,
+        PADDING + rows * row_stride,
+        BACKGROUND,
+    );
+
+    for (row, &expression_name) in expression_names.iter().enumerate() {
+        let expression = &bustup.expressions[expression_name];
+        let mouths = if expression.mouth_chunks.is_empty() {
+            vec![None]
+        } else {
+            (0..expression.mouth_chunks.len()).map(Some).collect()
+        };
+
+        for (column, mouth) in mouths.into_iter().enumerate() {
+            let x = PADDING + column as u32 * column_stride;
+            let y = PADDING + row as u32 * row_stride;
+
+            let image = composite(bustup, expression_name, mouth);
+            let image = imageops::resize(
+                &image,
+                cell_width,
+                cell_height,
+                imageops::FilterType::Triangle,
+            );
+            imageops::overlay(&mut sheet, &image, x as i64, y as i64);
+
+            let caption = match mouth {
+                Some(mouth) => format!("{} / mouth {}", expression_name, mouth),
+                None => expression_name.to_string(),
+            };
+            draw_text(&mut sheet, font, &caption, x, y + cell_height, cell_width)?;
+        }
+    }
+
+    Ok(sheet)
+}
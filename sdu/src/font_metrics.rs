@@ -0,0 +1,112 @@
+//! Font metrics as JSON: the character mapping and the glyph placement of a FNT file in a form external font tools can edit
+
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use shin_core::format::font::{GlyphId, GlyphTrait, LazyFont};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FontMetrics {
+    /// Distance between the baseline and the top of the font
+    pub ascent: u16,
+    /// Distance between the baseline and the bottom of the font
+    pub descent: u16,
+    /// Glyph ids of the characters, by their hex codes (like `"3042"`)
+    ///
+    /// The characters missing on import keep their glyphs.
+    pub characters: BTreeMap<String, u32>,
+    /// Placement of the glyphs, by their ids
+    pub glyphs: BTreeMap<u32, GlyphMetrics>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GlyphMetrics {
+    /// Distance between the pen position and the left of the glyph bitmap
+    pub bearing_x: i8,
+    /// Distance between the baseline and the top of the glyph bitmap
+    pub bearing_y: i8,
+    /// Horizontal pen movement after drawing the glyph
+    pub advance_width: u8,
+    /// Width of the glyph bitmap, for reference only: the bitmaps are not changed on import
+    #[serde(default)]
+    pub width: u8,
+    /// Height of the glyph bitmap, for reference only
+    #[serde(default)]
+    pub height: u8,
+}
+
+pub fn export_metrics(font: &LazyFont) -> FontMetrics {
+    let characters = font
+        .get_character_mapping()
+        .iter()
+        .enumerate()
+        .map(|(character, glyph)| (format!("{:04x}", character), glyph.0))
+        .collect();
+    let glyphs = font
+        .get_glyphs()
+        .iter()
+        .map(|(glyph, data)| {
+            let info = data.get_info();
+            (
+                glyph.0,
+                GlyphMetrics {
+                    bearing_x: info.bearing_x,
+                    bearing_y: info.bearing_y,
+                    advance_width: info.advance_width,
+                    width: info.actual_width,
+                    height: info.actual_height,
+                },
+            )
+        })
+        .collect();
+
+    FontMetrics {
+        ascent: font.get_ascent(),
+        descent: font.get_descent(),
+        characters,
+        glyphs,
+    }
+}
+
+/// Applies the `metrics` to the font, everything is checked before the font is changed
+pub fn import_metrics(font: &mut LazyFont, metrics: &FontMetrics) -> Result<()> {
+    let characters = metrics
+        .characters
+        .iter()
+        .map(|(character, &glyph)| {
+            let character = u16::from_str_radix(character, 16)
+                .with_context(|| format!("Invalid character code {:?}", character))?;
+            let glyph = GlyphId(glyph);
+            if font.get_glyph(glyph).is_none() {
+                bail!(
+                    "Character {:04x} is mapped to glyph {}, which is not in the font",
+                    character,
+                    glyph.0
+                );
+            }
+            Ok((character, glyph))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if let Some(glyph) = metrics
+        .glyphs
+        .keys()
+        .find(|&&glyph| font.get_glyph(GlyphId(glyph)).is_none())
+    {
+        bail!("Glyph {} is not in the font", glyph);
+    }
+
+    font.set_ascent_descent(metrics.ascent, metrics.descent);
+    for (character, glyph) in characters {
+        font.set_character_glyph(character, glyph);
+    }
+    for (&glyph, glyph_metrics) in &metrics.glyphs {
+        font.get_glyph_mut(GlyphId(glyph)).unwrap().set_metrics(
+            glyph_metrics.bearing_x,
+            glyph_metrics.bearing_y,
+            glyph_metrics.advance_width,
+        );
+    }
+
+    Ok(())
+}
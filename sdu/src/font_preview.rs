@@ -0,0 +1,116 @@
+//! Font previews: a sample text laid out with the game's layouter and rasterized with the FNT glyphs
+
+use anyhow::{bail, Context, Result};
+use image::{imageops, GenericImageView, Rgba, RgbaImage};
+use shin_core::{
+    format::font::{GlyphMipLevel, GlyphTrait, LazyFont},
+    layout::{
+        layout_text, LayoutParams, LayouterState, LayoutingMode, LineBreaking, TextDirection,
+    },
+    vm::command::types::MessageTextLayout,
+};
+
+/// The game shows the text over the darkened message window, so a dark background shows the colors as they are in the game
+const BACKGROUND: Rgba<u8> = Rgba([32, 32, 32, 255]);
+/// Space around the text, in pixels
+const PADDING: u32 = 16;
+
+/// Lays out the `text` (in the layouter markup, `@r` breaks the lines) with `font_size` pixel high lines wrapped at `width` and draws it
+pub fn render_preview(
+    font: &LazyFont,
+    text: &str,
+    font_size: f32,
+    width: f32,
+) -> Result<RgbaImage> {
+    let params = LayoutParams {
+        font,
+        layout_width: width,
+        character_name_layout_width: 0.0,
+        base_font_height: font_size,
+        furigana_font_height: font_size * 0.4,
+        // the same as the engine UI labels
+        font_horizontal_base_scale: 0.9697,
+        text_layout: MessageTextLayout::Left,
+        default_state: LayouterState {
+            instant: true,
+            ..Default::default()
+        },
+        has_character_name: false,
+        mode: LayoutingMode::GenericText,
+        line_breaking: LineBreaking::default(),
+        ruby: Default::default(),
+        direction: TextDirection::Horizontal,
+    };
+    let chars = layout_text(params, text).chars;
+    if chars.is_empty() {
+        bail!("The text has no characters to draw");
+    }
+
+    // the char positions are the pen positions on the baselines, the glyphs are offset from them by the bearings
+    let quads = chars
+        .iter()
+        .map(|char| {
+            let info = font.get_glyph_for_character(char.codepoint).get_info();
+            let left = char.position.x + info.bearing_x as f32 * char.size.horizontal_scale;
+            let top = char.position.y - info.bearing_y as f32 * char.size.scale;
+            (char, left, top)
+        })
+        .collect::<Vec<_>>();
+
+    let min_x = quads.iter().map(|&(_, left, _)| left).fold(0.0, f32::min);
+    let min_y = quads.iter().map(|&(_, _, top)| top).fold(0.0, f32::min);
+    let max_x = quads
+        .iter()
+        .map(|&(char, left, _)| left + char.size.width)
+        .fold(0.0, f32::max);
+    let max_y = quads
+        .iter()
+        .map(|&(char, _, top)| top + char.size.height)
+        .fold(0.0, f32::max);
+
+    let mut image = RgbaImage::from_pixel(
+        (max_x - min_x).ceil() as u32 + 2 * PADDING,
+        (max_y - min_y).ceil() as u32 + 2 * PADDING,
+        BACKGROUND,
+    );
+
+    for (char, left, top) in quads {
+        let glyph = font
+            .get_glyph_for_character(char.codepoint)
+            .decompress()
+            .with_context(|| format!("Decompressing the glyph for {:#06x}", char.codepoint))?;
+        let (glyph_width, glyph_height) = glyph.get_info().actual_size();
+        if glyph_width == 0 || glyph_height == 0 {
+            continue;
+        }
+        let glyph_image = glyph
+            .get_image(GlyphMipLevel::Level0)
+            .view(0, 0, glyph_width, glyph_height)
+            .to_image();
+
+        let (width, height) = (
+            (char.size.width.round() as u32).max(1),
+            (char.size.height.round() as u32).max(1),
+        );
+        let glyph_image =
+            imageops::resize(&glyph_image, width, height, imageops::FilterType::Triangle);
+
+        let x = (left - min_x).round() as i64 + PADDING as i64;
+        let y = (top - min_y).round() as i64 + PADDING as i64;
+        let color = [char.color.x, char.color.y, char.color.z].map(|c| (c * 255.0) as u32);
+        for (gx, gy, coverage) in glyph_image.enumerate_pixels() {
+            let (px, py) = (x + gx as i64, y + gy as i64);
+            if px < 0 || py < 0 || px >= image.width() as i64 || py >= image.height() as i64 {
+                continue;
+            }
+
+            let alpha = coverage[0] as u32;
+            let pixel = image.get_pixel_mut(px as u32, py as u32);
+            for (channel, &color) in pixel.0.iter_mut().zip(color.iter()) {
+                *channel = ((*channel as u32 * (255 - alpha) + color * alpha) / 255) as u8;
+            }
+        }
+    }
+
+    Ok(image)
+}
@@ -2,7 +2,13 @@
 
 mod assembler;
 mod audio;
+mod contact_sheet;
+mod font_metrics;
+mod font_preview;
+#[cfg(feature = "opus-encode")]
+mod opus_encode;
 mod rom;
+mod rom_diff;
 mod savedata;
 mod scenario;
 
@@ -10,6 +16,7 @@ use std::{fs::File, io::BufReader, path::PathBuf};
 
 use anyhow::{Context, Result};
 use assembler::{assembler_command, AssemblerCommand};
+use audio::ExportFormat;
 use clap::{CommandFactory, Parser};
 use clap_complete::{generate, Shell};
 use image::{GenericImageView, Rgba, RgbaImage};
@@ -17,7 +24,7 @@ use itertools::Itertools;
 use rom::{rom_command, RomCommand};
 use savedata::{savedata_command, SavedataCommand};
 use scenario::{scenario_command, ScenarioCommand};
-use shin_core::format::picture::SimpleMergedPicture;
+use shin_core::format::picture::{premultiply_alpha, SimpleMergedPicture};
 use tracing_subscriber::EnvFilter;
 
 #[derive(clap::Parser, Debug)]
@@ -79,6 +86,9 @@ enum PictureCommand {
         picture_path: PathBuf,
         /// Path to the output PNG file
         output_path: PathBuf,
+        /// Write the images with premultiplied alpha instead of the straight alpha PNG expects
+        #[clap(long)]
+        premultiplied_alpha: bool,
     },
 }
 
@@ -95,13 +105,45 @@ enum MaskCommand {
 
 #[derive(clap::Subcommand, Debug)]
 enum FontCommand {
-    /// Convert a FNT file into a metadata.txt file and a bunch of PNG files (one per glyph)
+    /// Convert a FNT file into a metadata.txt file, a metrics.json file (see `export-metrics`) and a bunch of PNG files (one per glyph)
     Decode {
         /// Path to the FNT file
         font_path: PathBuf,
         /// Path to the output directory
         output_path: PathBuf,
     },
+    /// Render a sample text with the game's layouter into a PNG, to check a font or the layouter changes without running the game
+    Preview {
+        /// Path to the FNT file
+        font_path: PathBuf,
+        /// The text to render, in the layouter markup (`@r` breaks the line, `@c900.` changes the color, and so on)
+        #[clap(long)]
+        text: String,
+        /// Height of the lines, in pixels
+        #[clap(long, default_value_t = 50.0)]
+        size: f32,
+        /// Width the lines are wrapped at, in pixels
+        #[clap(long, default_value_t = 1500.0)]
+        width: f32,
+        /// Path to the output PNG file
+        output_path: PathBuf,
+    },
+    /// Write the ascent, descent, character mapping and glyph placement of a FNT file to a JSON file
+    ExportMetrics {
+        /// Path to the FNT file
+        font_path: PathBuf,
+        /// Path to the output JSON file
+        output_path: PathBuf,
+    },
+    /// Apply the metrics from a JSON file (in the `export-metrics` format) to a FNT file, keeping the glyph bitmaps
+    ImportMetrics {
+        /// Path to the FNT file
+        font_path: PathBuf,
+        /// Path to the JSON file with the metrics
+        metrics_path: PathBuf,
+        /// Path to the output FNT file
+        output_path: PathBuf,
+    },
 }
 
 #[derive(clap::Subcommand, Debug)]
@@ -112,6 +154,21 @@ enum BustupCommand {
         bustup_path: PathBuf,
         /// Path to the output directory
         output_path: PathBuf,
+        /// Write the images with premultiplied alpha instead of the straight alpha PNG expects
+        #[clap(long)]
+        premultiplied_alpha: bool,
+    },
+    /// Composite every expression and mouth combination into a single PNG, with a row per expression and a column per mouth position
+    ContactSheet {
+        /// Path to the BUP file
+        bustup_path: PathBuf,
+        /// Path to the FNT file used to draw the captions
+        font_path: PathBuf,
+        /// Path to the output PNG file
+        output_path: PathBuf,
+        /// Scale of the bustups in the sheet
+        #[clap(long, default_value_t = 0.25)]
+        scale: f32,
     },
 }
 
@@ -123,24 +180,73 @@ enum TextureArchiveCommand {
         texture_archive_path: PathBuf,
         /// Path to the output directory
         output_path: PathBuf,
+        /// Write the images with premultiplied alpha instead of the straight alpha PNG expects
+        #[clap(long)]
+        premultiplied_alpha: bool,
     },
 }
 
 #[derive(clap::Subcommand, Debug)]
 enum AudioCommand {
     /// Convert a NXA file into a WAV file
+    ///
+    /// The loop points are written as a `smpl` chunk.
     Decode {
         /// Path to the NXA file
         audio_path: PathBuf,
         /// Path to the output WAV file
         output_path: PathBuf,
+        /// Take the track name from the BGM info table of this SNR file
+        #[clap(long)]
+        from_scenario: Option<PathBuf>,
+    },
+    /// Convert all the BGM tracks of the game into audio files, named after the tracks
+    ///
+    /// The track names and the files come from the BGM info table of the scenario. The names are written into the files too, with the loop points.
+    DecodeAll {
+        /// Path to the SNR file
+        #[clap(long)]
+        from_scenario: PathBuf,
+        /// Path to the directory with the extracted ROM contents
+        rom_dir: PathBuf,
+        /// Directory to write the files into
+        output_dir: PathBuf,
+        #[clap(long, value_enum, default_value_t = ExportFormat::Wav)]
+        format: ExportFormat,
     },
     /// Convert an NXA file into an OPUS file losslessly (it simply remuxes the opus packets)
+    ///
+    /// The loop points are written as `LOOPSTART`/`LOOPLENGTH` comments.
+    ///
+    /// With `--bitrate`, `--sample-rate` or `--mono` the audio is decoded and encoded again instead, which needs sdu to be built with the `opus-encode` feature.
     Remux {
         /// Path to the NXA file
         audio_path: PathBuf,
         /// Path to the output OPUS file
         output_path: PathBuf,
+        /// Take the track name from the BGM info table of this SNR file
+        #[clap(long)]
+        from_scenario: Option<PathBuf>,
+        /// Re-encode at this bitrate, in kbit/s
+        #[clap(long)]
+        bitrate: Option<u32>,
+        /// Re-encode at this sample rate (8000, 12000, 16000, 24000 or 48000 Hz)
+        #[clap(long)]
+        sample_rate: Option<u32>,
+        /// Re-encode downmixed to mono
+        #[clap(long)]
+        mono: bool,
+    },
+    /// Decode an NXA file across its loop boundary and report discontinuities
+    LoopCheck {
+        /// Path to the NXA file
+        audio_path: PathBuf,
+        /// Number of samples to analyze on each side of the loop boundary
+        #[clap(long, default_value_t = 2048)]
+        window: u32,
+        /// How many times larger than the average the jump at the boundary should be to be reported as a discontinuity
+        #[clap(long, default_value_t = 8.0)]
+        threshold: f32,
     },
 }
 
@@ -164,10 +270,14 @@ fn picture_command(command: PictureCommand) -> Result<()> {
         PictureCommand::Decode {
             picture_path: path,
             output_path,
+            premultiplied_alpha,
         } => {
             let picture = std::fs::read(path)?;
-            let picture =
+            let mut picture =
                 shin_core::format::picture::read_picture::<SimpleMergedPicture>(&picture, ())?;
+            if premultiplied_alpha {
+                premultiply_alpha(&mut picture.image);
+            }
             picture.image.save(output_path)?;
             Ok(())
         }
@@ -262,10 +372,16 @@ fn font_command(command: FontCommand) -> Result<()> {
                 writeln!(metadata, "    advance  : {}", info.advance_width)?;
             }
             std::fs::write(output_path.join("metadata.txt"), metadata)?;
+            std::fs::write(
+                output_path.join("metrics.json"),
+                serde_json::to_string_pretty(&font_metrics::export_metrics(&font))?,
+            )?;
 
             // then, write each glyph to a separate file
             for (&glyph_id, glyph_data) in font.get_glyphs().iter() {
-                let glyph_data = glyph_data.decompress();
+                let glyph_data = glyph_data
+                    .decompress()
+                    .with_context(|| format!("Decompressing glyph {}", glyph_id.0))?;
 
                 let size = glyph_data.get_info().actual_size();
                 let glyph_pic = glyph_data
@@ -284,6 +400,54 @@ fn font_command(command: FontCommand) -> Result<()> {
             }
             Ok(())
         }
+        FontCommand::Preview {
+            font_path,
+            text,
+            size,
+            width,
+            output_path,
+        } => {
+            use shin_core::format::font::read_lazy_font;
+
+            let font = File::open(font_path)?;
+            let font = read_lazy_font(&mut BufReader::new(font))?;
+
+            let image = font_preview::render_preview(&font, &text, size, width)?;
+            image.save(output_path)?;
+            Ok(())
+        }
+        FontCommand::ExportMetrics {
+            font_path,
+            output_path,
+        } => {
+            use shin_core::format::font::read_lazy_font;
+
+            let font = File::open(font_path)?;
+            let font = read_lazy_font(&mut BufReader::new(font))?;
+
+            let metrics = font_metrics::export_metrics(&font);
+            std::fs::write(output_path, serde_json::to_string_pretty(&metrics)?)?;
+            Ok(())
+        }
+        FontCommand::ImportMetrics {
+            font_path,
+            metrics_path,
+            output_path,
+        } => {
+            use shin_core::format::font::{read_lazy_font, write_lazy_font};
+
+            let font = File::open(font_path)?;
+            let mut font = read_lazy_font(&mut BufReader::new(font))?;
+
+            let metrics = std::fs::read_to_string(&metrics_path)?;
+            let metrics = serde_json::from_str(&metrics)
+                .with_context(|| format!("Parsing {}", metrics_path.display()))?;
+            font_metrics::import_metrics(&mut font, &metrics)?;
+
+            let mut output = std::io::BufWriter::new(File::create(output_path)?);
+            write_lazy_font(&font, &mut output)?;
+            Ok(())
+        }
     }
 }
 
@@ -292,11 +456,21 @@ fn bustup_command(command: BustupCommand) -> Result<()> {
         BustupCommand::Decode {
             bustup_path,
             output_path,
+            premultiplied_alpha,
         } => {
             use std::fmt::Write;
 
             let bustup = std::fs::read(bustup_path)?;
-            let bustup = shin_core::format::bustup::read_bustup(&bustup)?;
+            let mut bustup = shin_core::format::bustup::read_bustup(&bustup)?;
+            if premultiplied_alpha {
+                premultiply_alpha(&mut bustup.base_image);
+                for expression in bustup.expressions.values_mut() {
+                    premultiply_alpha(&mut expression.face_chunk.data);
+                    for mouth in &mut expression.mouth_chunks {
+                        premultiply_alpha(&mut mouth.data);
+                    }
+                }
+            }
 
             std::fs::create_dir_all(&output_path)?;
 
@@ -343,6 +517,25 @@ fn bustup_command(command: BustupCommand) -> Result<()> {
                 }
             }
 
+            Ok(())
+        }
+        BustupCommand::ContactSheet {
+            bustup_path,
+            font_path,
+            output_path,
+            scale,
+        } => {
+            use shin_core::format::font::read_lazy_font;
+
+            let bustup = std::fs::read(bustup_path)?;
+            let bustup = shin_core::format::bustup::read_bustup(&bustup)?;
+
+            let font = File::open(font_path)?;
+            let font = read_lazy_font(&mut BufReader::new(font))?;
+
+            let sheet = contact_sheet::build_contact_sheet(&bustup, &font, scale)?;
+            sheet.save(output_path)?;
+
             Ok(())
         }
     }
@@ -353,12 +546,18 @@ fn texture_archive_command(command: TextureArchiveCommand) -> Result<()> {
         TextureArchiveCommand::Decode {
             texture_archive_path,
             output_path,
+            premultiplied_alpha,
         } => {
             // use std::fmt::Write;
 
             let texture_archive = std::fs::read(texture_archive_path)?;
-            let texture_archive =
+            let mut texture_archive =
                 shin_core::format::texture_archive::read_texture_archive(&texture_archive)?;
+            if premultiplied_alpha {
+                for texture in &mut texture_archive.textures {
+                    premultiply_alpha(texture);
+                }
+            }
 
             std::fs::create_dir_all(&output_path)?;
 
@@ -0,0 +1,264 @@
+//! Re-encoding the NXA audio into Ogg Opus at another bitrate or sample rate, for `sdu audio remux --bitrate`
+//!
+//! The audio is decoded, optionally downmixed to mono and resampled, and encoded again with the (pure Rust) libopus port used by the decoder.
+
+use std::{fs::File, io, io::BufWriter, path::Path};
+
+use anyhow::{bail, Context};
+use opus::{Application, Bitrate, Channels, Encoder};
+use shin_core::format::audio::{AudioDecoder, AudioFile, AudioInfo, AudioSource};
+
+use crate::audio::{EncodeOptions, OpusOggWriter};
+
+/// The sample rates supported by the opus encoder
+pub const SUPPORTED_SAMPLE_RATES: &[u32] = &[8000, 12000, 16000, 24000, 48000];
+
+/// The granule positions and the pre-skip of Ogg Opus are always in 48 kHz samples
+const GRANULE_RATE: u32 = 48000;
+/// 20 ms, the default frame duration of the opus encoder
+const FRAMES_PER_SECOND: u32 = 50;
+/// The largest packet the encoder is allowed to produce, as recommended by libopus
+const MAX_PACKET_SIZE: usize = 4000;
+
+/// How many input samples are decoded and resampled at once
+const BLOCK_SAMPLES: usize = 4096;
+/// Half of the resampling filter length, in the input samples (at the lower of the two sample rates)
+const RESAMPLE_HALF_TAPS: f64 = 16.0;
+
+fn sinc(x: f64) -> f64 {
+    if x == 0.0 {
+        1.0
+    } else {
+        let x = x * std::f64::consts::PI;
+        x.sin() / x
+    }
+}
+
+/// Resamples a single channel with a Lanczos-windowed sinc filter, fed with the input block by block
+struct Resampler {
+    ratio: f64,
+    cutoff: f64,
+    half_width: f64,
+    /// The input samples still needed by the filter
+    input: Vec<f32>,
+    /// Index of the first sample in `input`
+    input_offset: usize,
+    /// Index of the next output sample
+    next_output: usize,
+}
+
+impl Resampler {
+    fn new(from: u32, to: u32) -> Self {
+        let ratio = to as f64 / from as f64;
+        // when downsampling, the cutoff is lowered to the new Nyquist frequency, so that nothing is aliased
+        let cutoff = ratio.min(1.0);
+        Self {
+            ratio,
+            cutoff,
+            half_width: RESAMPLE_HALF_TAPS / cutoff,
+            input: Vec::new(),
+            input_offset: 0,
+            next_output: 0,
+        }
+    }
+
+    fn input_end(&self) -> usize {
+        self.input_offset + self.input.len()
+    }
+
+    /// The input samples contributing to the output sample `index`, not clipped to the input
+    fn window(&self, index: usize) -> (f64, i64, i64) {
+        let center = index as f64 / self.ratio;
+        (
+            center,
+            (center - self.half_width).ceil() as i64,
+            (center + self.half_width).floor() as i64,
+        )
+    }
+
+    fn output_sample(&self, index: usize) -> f32 {
+        let (center, first, last) = self.window(index);
+        let first = first.max(0) as usize;
+        let last = (last.max(-1) + 1).min(self.input_end() as i64) as usize;
+
+        let (mut sum, mut weight_sum) = (0.0, 0.0);
+        for j in first..last {
+            let x = j as f64 - center;
+            let weight = sinc(x * self.cutoff) * sinc(x / self.half_width);
+            sum += self.input[j - self.input_offset] as f64 * weight;
+            weight_sum += weight;
+        }
+        // normalizing keeps the gain right at the edges, where the filter is cut off
+        if weight_sum != 0.0 {
+            (sum / weight_sum) as f32
+        } else {
+            0.0
+        }
+    }
+
+    /// Appends the output samples that can be computed with the input received so far
+    fn process(&mut self, block: &[f32], output: &mut Vec<f32>) {
+        if self.ratio == 1.0 {
+            output.extend_from_slice(block);
+            self.input_offset += block.len();
+            self.next_output += block.len();
+            return;
+        }
+
+        self.input.extend_from_slice(block);
+        while self.window(self.next_output).2 < self.input_end() as i64 {
+            output.push(self.output_sample(self.next_output));
+            self.next_output += 1;
+        }
+
+        // drop the input no longer needed by the next output samples
+        let needed = (self.window(self.next_output).1.max(0) as usize).min(self.input_end());
+        if needed > self.input_offset {
+            self.input.drain(..needed - self.input_offset);
+            self.input_offset = needed;
+        }
+    }
+
+    /// Appends the rest of the output, the input ends at the last block
+    fn finish(&mut self, output: &mut Vec<f32>) {
+        let output_len = (self.input_end() as f64 * self.ratio).round() as usize;
+        while self.next_output < output_len {
+            output.push(self.output_sample(self.next_output));
+            self.next_output += 1;
+        }
+    }
+}
+
+/// Encodes the frames from the per-channel `pending` samples, keeping more than a frame of them unless `is_end` is set
+///
+/// At the end, all the samples are encoded and the last frame is padded with silence.
+fn encode_frames<W: io::Write>(
+    encoder: &mut Encoder,
+    writer: &mut OpusOggWriter<W>,
+    pending: &mut [Vec<f32>],
+    frame_samples: usize,
+    is_end: bool,
+) -> anyhow::Result<()> {
+    let mut frame = Vec::with_capacity(frame_samples * pending.len());
+    loop {
+        let available = pending[0].len();
+        let is_last = is_end && available <= frame_samples;
+        // the last frame is only known once the input has ended
+        if !is_end && available <= frame_samples {
+            return Ok(());
+        }
+
+        frame.clear();
+        for i in 0..frame_samples {
+            frame.extend(pending.iter().map(|c| c.get(i).copied().unwrap_or(0.0)));
+        }
+        for channel in pending.iter_mut() {
+            channel.drain(..frame_samples.min(available));
+        }
+
+        let packet = encoder
+            .encode_vec_float(&frame, MAX_PACKET_SIZE)
+            .context("Encoding a frame")?;
+        writer
+            .write_frame(packet, is_last)
+            .context("Writing frame")?;
+        if is_last {
+            return Ok(());
+        }
+    }
+}
+
+/// Decodes the audio and encodes it again into an Ogg Opus file
+///
+/// The audio is processed in blocks, so that the long tracks don't have to be decoded into memory.
+pub fn reencode(
+    audio: &AudioFile,
+    options: EncodeOptions,
+    comments: &[String],
+    output_path: &Path,
+) -> anyhow::Result<()> {
+    let info = audio.info();
+    let sample_rate = options.sample_rate.unwrap_or(info.sample_rate);
+    if !SUPPORTED_SAMPLE_RATES.contains(&sample_rate) {
+        bail!(
+            "Opus can't encode at {} Hz, the supported sample rates are {:?}",
+            sample_rate,
+            SUPPORTED_SAMPLE_RATES
+        );
+    }
+    let channel_count = if options.mono { 1 } else { 2 };
+
+    let mut encoder = Encoder::new(
+        sample_rate,
+        if options.mono {
+            Channels::Mono
+        } else {
+            Channels::Stereo
+        },
+        Application::Audio,
+    )
+    .context("Creating encoder")?;
+    if let Some(bitrate) = options.bitrate {
+        encoder
+            .set_bitrate(Bitrate::Bits(bitrate as i32 * 1000))
+            .context("Setting the bitrate")?;
+    }
+    let lookahead = encoder.get_lookahead().context("Getting the lookahead")? as u32;
+
+    let frame_samples = (sample_rate / FRAMES_PER_SECOND) as usize;
+    let output_info = AudioInfo {
+        sample_rate,
+        channel_count,
+        frame_size: 0,
+        frame_samples: (GRANULE_RATE / FRAMES_PER_SECOND) as u16,
+        pre_skip: (lookahead * GRANULE_RATE / sample_rate) as u16,
+        num_samples: (info.num_samples as u64 * sample_rate as u64 / info.sample_rate as u64)
+            as u32,
+        loop_start: 0,
+        loop_end: 0,
+    };
+
+    let writer = File::create(output_path).context("Creating output file")?;
+    let writer = BufWriter::new(writer);
+    let mut writer =
+        OpusOggWriter::new(writer, &output_info, comments).context("Creating OGG writer")?;
+
+    let mut source = AudioSource::new(AudioDecoder::new(audio).context("Creating decoder")?);
+    let mut resamplers = (0..channel_count)
+        .map(|_| Resampler::new(info.sample_rate, sample_rate))
+        .collect::<Vec<_>>();
+    let mut blocks = vec![Vec::with_capacity(BLOCK_SAMPLES); channel_count as usize];
+    let mut pending = vec![Vec::new(); channel_count as usize];
+    loop {
+        for block in &mut blocks {
+            block.clear();
+        }
+        for (left, right) in source.by_ref().take(BLOCK_SAMPLES) {
+            if options.mono {
+                blocks[0].push((left + right) / 2.0);
+            } else {
+                blocks[0].push(left);
+                blocks[1].push(right);
+            }
+        }
+        let is_end = blocks[0].len() < BLOCK_SAMPLES;
+
+        for ((resampler, block), pending) in resamplers.iter_mut().zip(&blocks).zip(&mut pending) {
+            resampler.process(block, pending);
+            if is_end {
+                resampler.finish(pending);
+            }
+        }
+        encode_frames(
+            &mut encoder,
+            &mut writer,
+            &mut pending,
+            frame_samples,
+            is_end,
+        )?;
+
+        if is_end {
+            return Ok(());
+        }
+    }
+}
@@ -1,7 +1,12 @@
-use std::{fs::File, io::BufReader, path::PathBuf};
+use std::{
+    fs::File,
+    io::{BufReader, Read},
+    ops::Deref,
+    path::{Path, PathBuf},
+};
 
 use anyhow::{Context, Result};
-use shin_core::format::rom::{IndexEntry, IndexFile};
+use shin_core::format::rom::{IndexEntry, IndexFile, MappedRomReader, RomFileData, RomReader};
 
 #[derive(clap::Subcommand, Debug)]
 pub enum RomCommand {
@@ -17,6 +22,9 @@ pub enum RomCommand {
         rom_filename: String,
         /// Path to the output file
         output_path: PathBuf,
+        /// Memory map the ROM instead of reading it through a buffer
+        #[clap(long)]
+        mmap: bool,
     },
     /// Extract multiple files from the archive, creating a directory tree
     Extract {
@@ -26,7 +34,85 @@ pub enum RomCommand {
         output_dir: PathBuf,
         /// Names of specific files to be extracted. If none are specified, all files in the ROM will be extracted.
         file_names: Vec<String>,
+        /// Memory map the ROM instead of reading it through a buffer
+        #[clap(long)]
+        mmap: bool,
     },
+    /// List the files added, removed and changed between two ROMs
+    Diff {
+        /// Path to the old ROM file
+        old_rom_path: PathBuf,
+        /// Path to the new ROM file
+        new_rom_path: PathBuf,
+        /// Decode the changed files in the known formats (scenarios, pictures, bustups and texture archives) to tell what changed in them
+        #[clap(long)]
+        deep: bool,
+    },
+}
+
+/// A ROM opened either with buffered reads or memory mapped
+enum Rom {
+    Buffered(RomReader<BufReader<File>>),
+    Mapped(MappedRomReader),
+}
+
+impl Rom {
+    fn open(path: &Path, mmap: bool) -> Result<Self> {
+        Ok(if mmap {
+            Rom::Mapped(MappedRomReader::open_mapped(path).context("Parsing ROM")?)
+        } else {
+            let rom = File::open(path).context("Opening rom file")?;
+            Rom::Buffered(RomReader::new(BufReader::new(rom)).context("Parsing ROM")?)
+        })
+    }
+
+    fn find_file(&self, path: &str) -> Result<IndexFile> {
+        match self {
+            Rom::Buffered(reader) => reader.find_file(path),
+            Rom::Mapped(reader) => reader.find_file(path),
+        }
+    }
+
+    fn files(&self) -> Vec<(String, IndexFile)> {
+        match self {
+            Rom::Buffered(reader) => reader.files().collect(),
+            Rom::Mapped(reader) => reader.files().collect(),
+        }
+    }
+
+    /// Reads the file data, it's not copied when the ROM is memory mapped
+    fn read(&mut self, file: IndexFile) -> Result<FileData> {
+        match self {
+            Rom::Buffered(reader) => {
+                let mut file = reader.open_file(file).context("Opening file in rom")?;
+                let mut buf = Vec::new();
+                file.read_to_end(&mut buf)
+                    .context("Reading file data from rom")?;
+                Ok(FileData::Buffered(buf))
+            }
+            Rom::Mapped(reader) => Ok(FileData::Mapped(
+                reader
+                    .file_data(file)
+                    .context("Reading file data from rom")?,
+            )),
+        }
+    }
+}
+
+enum FileData {
+    Buffered(Vec<u8>),
+    Mapped(RomFileData),
+}
+
+impl Deref for FileData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            FileData::Buffered(data) => data,
+            FileData::Mapped(data) => data,
+        }
+    }
 }
 
 pub fn rom_command(command: RomCommand) -> Result<()> {
@@ -48,43 +134,29 @@ pub fn rom_command(command: RomCommand) -> Result<()> {
             rom_path,
             rom_filename,
             output_path,
+            mmap,
         } => {
-            use std::io::Read;
-            let rom = File::open(rom_path).context("Opening rom file")?;
-            let rom = BufReader::new(rom);
-            let mut reader = shin_core::format::rom::RomReader::new(rom).context("Parsing ROM")?;
-            let file = reader
+            let mut rom = Rom::open(&rom_path, mmap)?;
+            let file = rom
                 .find_file(&rom_filename)
                 .context("Searching for file in ROM")?;
-            let mut file = reader.open_file(file).context("Opening file in rom")?;
-            let mut buf = Vec::new();
-            file.read_to_end(&mut buf)?;
-            std::fs::write(output_path, buf).context("Writing file")?;
+            let data = rom.read(file)?;
+            std::fs::write(output_path, &*data).context("Writing file")?;
             Ok(())
         }
         RomCommand::Extract {
             rom_path,
             output_dir,
             file_names,
+            mmap,
         } => {
-            use std::io::Read;
-            let rom = File::open(rom_path).context("Opening rom file")?;
-            let rom = BufReader::new(rom);
-            let mut reader = shin_core::format::rom::RomReader::new(rom).context("Parsing ROM")?;
+            let mut rom = Rom::open(&rom_path, mmap)?;
 
             // First, make a list of all the files in the rom
-            let files: Vec<(String, IndexFile)> = reader
-                .traverse()
-                .filter_map(|(name, entry)| match entry {
-                    IndexEntry::File(file_entry) => {
-                        if file_names.is_empty() || file_names.contains(&name) {
-                            Some((name, *file_entry))
-                        } else {
-                            None
-                        }
-                    }
-                    IndexEntry::Directory(_) => None,
-                })
+            let files: Vec<(String, IndexFile)> = rom
+                .files()
+                .into_iter()
+                .filter(|(name, _)| file_names.is_empty() || file_names.contains(name))
                 .collect();
 
             // Then go through the files, read each one from the rom, and write it to the filesystem
@@ -93,22 +165,29 @@ pub fn rom_command(command: RomCommand) -> Result<()> {
                 let mut output_path = output_dir.clone();
                 output_path.extend(name.split('/'));
 
-                let mut file = reader
-                    .open_file(file_entry)
-                    .context("Opening file in rom")?;
-                let mut buf = Vec::new();
-                let len = file
-                    .read_to_end(&mut buf)
-                    .context("Reading file data from rom")?;
+                let data = rom.read(file_entry)?;
                 if let Some(parent) = output_path.parent() {
                     std::fs::create_dir_all(parent)
                         .context("Creating directory to write file in")?
                 }
-                std::fs::write(output_path.as_path(), buf).context("Writing file")?;
+                std::fs::write(output_path.as_path(), &*data).context("Writing file")?;
 
-                println!("Wrote file {} ({} bytes)", output_path.display(), len);
+                println!(
+                    "Wrote file {} ({} bytes)",
+                    output_path.display(),
+                    data.len()
+                );
             }
             Ok(())
         }
+        RomCommand::Diff {
+            old_rom_path,
+            new_rom_path,
+            deep,
+        } => {
+            let old_rom = MappedRomReader::open_mapped(&old_rom_path).context("Parsing old ROM")?;
+            let new_rom = MappedRomReader::open_mapped(&new_rom_path).context("Parsing new ROM")?;
+            crate::rom_diff::diff_roms(&old_rom, &new_rom, deep)
+        }
     }
 }
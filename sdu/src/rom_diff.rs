@@ -0,0 +1,329 @@
+//! Comparison of two ROMs: lists the added, removed and changed files
+//!
+//! The files are compared by their size and checksum. With the deep comparison, the changed files in the known formats are decoded to tell what actually changed (the text of a scenario, the pixels of a picture, etc.)
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::{Context, Result};
+use bytes::Bytes;
+use image::RgbaImage;
+use shin_core::{
+    format::{
+        bustup::read_bustup,
+        picture::{read_picture, PictureChunk, SimpleMergedPicture},
+        rom::{FileDigest, MappedRomReader},
+        scenario::{info::ScenarioInfoTables, instructions::Instruction, Scenario},
+        texture_archive::read_texture_archive,
+    },
+    vm::command::CompiletimeCommand,
+};
+
+use crate::scenario::analysis::ScenarioAnalysis;
+
+fn describe_images(old: &RgbaImage, new: &RgbaImage) -> Option<String> {
+    if old.dimensions() != new.dimensions() {
+        return Some(format!(
+            "size changed {}x{} -> {}x{}",
+            old.width(),
+            old.height(),
+            new.width(),
+            new.height()
+        ));
+    }
+
+    let differing = old
+        .pixels()
+        .zip(new.pixels())
+        .filter(|(old, new)| old != new)
+        .count();
+    (differing > 0).then(|| format!("{} pixels differ", differing))
+}
+
+fn describe_chunks(old: &PictureChunk, new: &PictureChunk) -> Option<String> {
+    if (old.offset_x, old.offset_y) != (new.offset_x, new.offset_y) {
+        return Some(format!(
+            "moved ({}, {}) -> ({}, {})",
+            old.offset_x, old.offset_y, new.offset_x, new.offset_y
+        ));
+    }
+    describe_images(&old.data, &new.data)
+}
+
+/// Describes the changes in the set of names, returning the names present in both
+fn describe_names<'a>(
+    what: &str,
+    old: impl Iterator<Item = &'a String>,
+    new: impl Iterator<Item = &'a String>,
+    changes: &mut Vec<String>,
+) -> Vec<&'a String> {
+    let old = old.collect::<BTreeSet<_>>();
+    let new = new.collect::<BTreeSet<_>>();
+    let added = new.difference(&old).count();
+    let removed = old.difference(&new).count();
+    if added > 0 {
+        changes.push(format!("{} {} added", added, what));
+    }
+    if removed > 0 {
+        changes.push(format!("{} {} removed", removed, what));
+    }
+    old.intersection(&new).copied().collect()
+}
+
+fn describe_picture(old: &[u8], new: &[u8]) -> Result<Vec<String>> {
+    let old = read_picture::<SimpleMergedPicture>(old, ()).context("Decoding old picture")?;
+    let new = read_picture::<SimpleMergedPicture>(new, ()).context("Decoding new picture")?;
+
+    let mut changes = Vec::new();
+    if (old.origin_x, old.origin_y) != (new.origin_x, new.origin_y) {
+        changes.push("origin changed".to_string());
+    }
+    changes.extend(describe_images(&old.image, &new.image));
+    Ok(changes)
+}
+
+fn describe_bustup(old: &[u8], new: &[u8]) -> Result<Vec<String>> {
+    let old = read_bustup(old).context("Decoding old bustup")?;
+    let new = read_bustup(new).context("Decoding new bustup")?;
+
+    let mut changes = Vec::new();
+    if old.origin != new.origin {
+        changes.push("origin changed".to_string());
+    }
+    if let Some(change) = describe_images(&old.base_image, &new.base_image) {
+        changes.push(format!("base image: {}", change));
+    }
+
+    let common = describe_names(
+        "expressions",
+        old.expressions.keys(),
+        new.expressions.keys(),
+        &mut changes,
+    );
+    for name in common {
+        let (old, new) = (&old.expressions[name], &new.expressions[name]);
+        if let Some(change) = describe_chunks(&old.face_chunk, &new.face_chunk) {
+            changes.push(format!("expression {:?} face: {}", name, change));
+        }
+        if old.mouth_chunks.len() != new.mouth_chunks.len() {
+            changes.push(format!(
+                "expression {:?}: {} -> {} mouths",
+                name,
+                old.mouth_chunks.len(),
+                new.mouth_chunks.len()
+            ));
+        }
+        for (index, (old, new)) in old.mouth_chunks.iter().zip(&new.mouth_chunks).enumerate() {
+            if let Some(change) = describe_chunks(old, new) {
+                changes.push(format!("expression {:?} mouth {}: {}", name, index, change));
+            }
+        }
+    }
+    Ok(changes)
+}
+
+fn describe_texture_archive(old: &[u8], new: &[u8]) -> Result<Vec<String>> {
+    let old = read_texture_archive(old).context("Decoding old texture archive")?;
+    let new = read_texture_archive(new).context("Decoding new texture archive")?;
+
+    let mut changes = Vec::new();
+    let common = describe_names(
+        "textures",
+        old.name_to_index.keys(),
+        new.name_to_index.keys(),
+        &mut changes,
+    );
+    for name in common {
+        let old = &old.textures[old.name_to_index[name]];
+        let new = &new.textures[new.name_to_index[name]];
+        if let Some(change) = describe_images(old, new) {
+            changes.push(format!("texture {:?}: {}", name, change));
+        }
+    }
+    Ok(changes)
+}
+
+/// Collects the message texts, by their ids
+fn scenario_messages(analysis: &ScenarioAnalysis) -> BTreeMap<u32, &str> {
+    analysis
+        .instructions
+        .iter()
+        .filter_map(|(_, instruction)| match instruction {
+            Instruction::Command(CompiletimeCommand::MSGSET(msgset)) => {
+                Some((msgset.msg_id.0, msgset.text.0.as_str()))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Names the info tables that differ
+fn changed_info_tables(old: &ScenarioInfoTables, new: &ScenarioInfoTables) -> Vec<&'static str> {
+    // destructuring makes sure the tables added later are not forgotten here
+    let ScenarioInfoTables {
+        mask_info,
+        picture_info,
+        bustup_info,
+        bgm_info,
+        se_info,
+        movie_info,
+        voice_mapping_info,
+        picture_box_info,
+        music_box_info,
+        character_box_info,
+        chars_sprite_info,
+        chars_grid_info,
+        tips_info,
+    } = old;
+
+    [
+        ("masks", *mask_info == new.mask_info),
+        ("pictures", *picture_info == new.picture_info),
+        ("bustups", *bustup_info == new.bustup_info),
+        ("BGM", *bgm_info == new.bgm_info),
+        ("SE", *se_info == new.se_info),
+        ("movies", *movie_info == new.movie_info),
+        (
+            "voice mapping",
+            *voice_mapping_info == new.voice_mapping_info,
+        ),
+        ("picture box", *picture_box_info == new.picture_box_info),
+        ("music box", *music_box_info == new.music_box_info),
+        (
+            "character box",
+            *character_box_info == new.character_box_info,
+        ),
+        ("chars sprites", *chars_sprite_info == new.chars_sprite_info),
+        ("chars grids", *chars_grid_info == new.chars_grid_info),
+        ("tips", *tips_info == new.tips_info),
+    ]
+    .into_iter()
+    .filter(|&(_, same)| !same)
+    .map(|(name, _)| name)
+    .collect()
+}
+
+fn describe_scenario(old: &[u8], new: &[u8]) -> Result<Vec<String>> {
+    let old = Scenario::new(Bytes::copy_from_slice(old)).context("Parsing old scenario")?;
+    let new = Scenario::new(Bytes::copy_from_slice(new)).context("Parsing new scenario")?;
+
+    let mut changes = Vec::new();
+    if old.info_tables() != new.info_tables() {
+        changes.push(format!(
+            "info tables changed: {}",
+            changed_info_tables(old.info_tables(), new.info_tables()).join(", ")
+        ));
+    }
+
+    let old = ScenarioAnalysis::new(&old).context("Analyzing old scenario")?;
+    let new = ScenarioAnalysis::new(&new).context("Analyzing new scenario")?;
+
+    let old_messages = scenario_messages(&old);
+    let new_messages = scenario_messages(&new);
+    let added = new_messages
+        .keys()
+        .filter(|id| !old_messages.contains_key(*id))
+        .count();
+    let removed = old_messages
+        .keys()
+        .filter(|id| !new_messages.contains_key(*id))
+        .count();
+    let changed = old_messages
+        .iter()
+        .filter(|(id, text)| new_messages.get(*id).is_some_and(|new| new != *text))
+        .count();
+    if changed > 0 {
+        changes.push(format!("text of {} messages changed", changed));
+    }
+    if added > 0 {
+        changes.push(format!("{} messages added", added));
+    }
+    if removed > 0 {
+        changes.push(format!("{} messages removed", removed));
+    }
+
+    // the addresses shift with any change in the text, so only the instruction counts are compared
+    let old_code = old
+        .instructions
+        .iter()
+        .filter(|(_, i)| !matches!(i, Instruction::Command(CompiletimeCommand::MSGSET(_))))
+        .count();
+    let new_code = new
+        .instructions
+        .iter()
+        .filter(|(_, i)| !matches!(i, Instruction::Command(CompiletimeCommand::MSGSET(_))))
+        .count();
+    if old_code != new_code {
+        changes.push(format!(
+            "{} -> {} non-message instructions",
+            old_code, new_code
+        ));
+    }
+
+    Ok(changes)
+}
+
+/// Decodes the file in a known format to describe what changed, returns `None` for unknown formats
+fn describe_changes(name: &str, old: &[u8], new: &[u8]) -> Option<Result<Vec<String>>> {
+    let extension = name.rsplit_once('.')?.1.to_ascii_lowercase();
+    Some(match extension.as_str() {
+        "snr" => describe_scenario(old, new),
+        "pic" => describe_picture(old, new),
+        "bup" => describe_bustup(old, new),
+        "txa" => describe_texture_archive(old, new),
+        _ => return None,
+    })
+}
+
+fn describe_change(
+    old_rom: &MappedRomReader,
+    new_rom: &MappedRomReader,
+    name: &str,
+) -> Result<String> {
+    let old = old_rom.file_data(old_rom.find_file(name)?)?;
+    let new = new_rom.file_data(new_rom.find_file(name)?)?;
+
+    Ok(match describe_changes(name, &old, &new) {
+        None => "binary changed".to_string(),
+        Some(Ok(changes)) if changes.is_empty() => {
+            "encoding changed, decoded contents identical".to_string()
+        }
+        Some(Ok(changes)) => changes.join(", "),
+        Some(Err(e)) => format!("could not be compared: {:#}", e),
+    })
+}
+
+/// Prints the differences between the ROMs, the `deep` comparison decodes the changed files in the known formats
+pub fn diff_roms(old_rom: &MappedRomReader, new_rom: &MappedRomReader, deep: bool) -> Result<()> {
+    let old = old_rom.par_digests().context("Hashing old ROM")?;
+    let new = new_rom.par_digests().context("Hashing new ROM")?;
+
+    let names = old.keys().chain(new.keys()).collect::<BTreeSet<_>>();
+    let (mut added, mut removed, mut changed) = (0, 0, 0);
+    for name in names {
+        match (old.get(name), new.get(name)) {
+            (None, Some(FileDigest { size, .. })) => {
+                added += 1;
+                println!("A {} ({} bytes)", name, size);
+            }
+            (Some(_), None) => {
+                removed += 1;
+                println!("D {}", name);
+            }
+            (Some(old), Some(new)) if old != new => {
+                changed += 1;
+                if deep {
+                    let description = describe_change(old_rom, new_rom, name)
+                        .with_context(|| format!("Comparing {}", name))?;
+                    println!("M {}: {}", name, description);
+                } else {
+                    println!("M {} ({} -> {} bytes)", name, old.size, new.size);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    println!("{} added, {} removed, {} changed", added, removed, changed);
+
+    Ok(())
+}
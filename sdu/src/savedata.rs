@@ -1,7 +1,20 @@
-use std::{fs::File, path::PathBuf};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    path::{Path, PathBuf},
+};
 
-use anyhow::{Context, Result};
-use shin_core::format::save::Savedata;
+use anyhow::{bail, Context, Result};
+use bytes::Bytes;
+use shin_core::{
+    format::{
+        save::{SaveBitVector, SaveChange, SaveDescriptor, Savedata},
+        scenario::{instructions::Instruction, Scenario},
+    },
+    vm::command::CompiletimeCommand,
+};
+
+use crate::scenario::analysis::ScenarioAnalysis;
 
 #[derive(clap::Subcommand, Debug)]
 pub enum SavedataCommand {
@@ -18,6 +31,9 @@ pub enum SavedataCommand {
         /// It is run through a hash function to produce the actual key
         #[clap(long)]
         key_seed: Option<String>,
+        /// The game the save belongs to (detected by default)
+        #[clap(long)]
+        game: Option<String>,
     },
     /// Obfuscate the save file
     Obfuscate {
@@ -32,16 +48,186 @@ pub enum SavedataCommand {
         /// It is run through a hash function to produce the actual key
         #[clap(long)]
         key_seed: Option<String>,
+        /// The game the save belongs to
+        #[clap(long, default_value = "umineko")]
+        game: String,
     },
     /// Decode the save file into a human-readable format
     Decode {
         /// Path to the save file
         save_path: PathBuf,
-        /// Path to the output yaml file
+        /// Path to the output ron file
+        output_path: PathBuf,
+        /// The game the save belongs to (detected by default)
+        #[clap(long)]
+        game: Option<String>,
+    },
+    /// Encode the human-readable save produced by `decode` back into a save file
+    Encode {
+        /// Path to the ron file
+        input_path: PathBuf,
+        /// Path to the output save file
+        output_path: PathBuf,
+        /// The game the save belongs to
+        #[clap(long, default_value = "umineko")]
+        game: String,
+    },
+    /// Show what changed between two saves: the persistent flags, the read messages, the unlocks and the save slots
+    Diff {
+        /// Path to the old save file
+        old_path: PathBuf,
+        /// Path to the new save file
+        new_path: PathBuf,
+        /// The game the saves belong to (detected by default)
+        #[clap(long)]
+        game: Option<String>,
+        /// The scenario of the game, to show the message texts and the names of the unlocked pictures, BGMs and tips
+        #[clap(long)]
+        scenario: Option<PathBuf>,
+    },
+    /// Combine the read state of two saves: the read messages and choices, and the unlocked pictures, BGMs and tips
+    ///
+    /// Everything else, including the persistent flags and the save slots, is taken from the base save.
+    Merge {
+        /// Path to the base save file
+        base_path: PathBuf,
+        /// Path to the save file to take the read state from
+        other_path: PathBuf,
+        /// Path to the output save file
         output_path: PathBuf,
+        /// The game the saves belong to (detected by default)
+        #[clap(long)]
+        game: Option<String>,
     },
 }
 
+/// Reads and decodes a save file, detecting the game if it's not given
+fn read_save(path: &Path, game: Option<&str>) -> Result<(&'static SaveDescriptor, Savedata)> {
+    let savedata = std::fs::read(path).with_context(|| format!("Reading {}", path.display()))?;
+    match game {
+        None => Savedata::decode(&savedata),
+        Some(game) => {
+            let descriptor = SaveDescriptor::by_game(game)?;
+            Ok((descriptor, Savedata::decode_as(&savedata, descriptor)?))
+        }
+    }
+    .with_context(|| format!("Decoding {}", path.display()))
+}
+
+/// The names of the things referenced by the save, taken from the scenario
+#[derive(Default)]
+struct SaveNames {
+    messages: BTreeMap<u32, String>,
+    pictures: Vec<String>,
+    bgms: Vec<String>,
+    tips: Vec<String>,
+}
+
+/// How much of a message text is shown
+const MESSAGE_PREVIEW_CHARS: usize = 40;
+
+impl SaveNames {
+    fn from_scenario(path: &Path) -> Result<Self> {
+        let scenario = Bytes::from(std::fs::read(path)?);
+        let scenario = Scenario::new(scenario).context("Parsing the scenario")?;
+        let analysis = ScenarioAnalysis::new(&scenario).context("Analyzing the scenario")?;
+        let info = scenario.info_tables();
+
+        Ok(Self {
+            messages: analysis
+                .instructions
+                .iter()
+                .filter_map(|(_, instruction)| match instruction {
+                    Instruction::Command(CompiletimeCommand::MSGSET(msgset)) => Some((
+                        msgset.msg_id.0,
+                        msgset
+                            .text
+                            .0
+                            .as_str()
+                            .chars()
+                            .take(MESSAGE_PREVIEW_CHARS)
+                            .collect(),
+                    )),
+                    _ => None,
+                })
+                .collect(),
+            pictures: info
+                .picture_info
+                .iter()
+                .map(|p| p.name.as_str().to_string())
+                .collect(),
+            bgms: info
+                .bgm_info
+                .iter()
+                .map(|b| b.display_name.as_str().to_string())
+                .collect(),
+            tips: info
+                .tips_info
+                .iter()
+                .map(|t| t.title.as_str().to_string())
+                .collect(),
+        })
+    }
+
+    fn bit_name(&self, vector: SaveBitVector, index: usize) -> Option<&str> {
+        match vector {
+            SaveBitVector::SeenMessages => self.messages.get(&(index as u32)).map(String::as_str),
+            SaveBitVector::SeenChoices => None,
+            SaveBitVector::UnlockedPictures => self.pictures.get(index).map(String::as_str),
+            SaveBitVector::UnlockedBgms => self.bgms.get(index).map(String::as_str),
+            SaveBitVector::UnlockedTips => self.tips.get(index).map(String::as_str),
+        }
+    }
+
+    fn describe(&self, change: &SaveChange) -> String {
+        let slot_name = |slot: Option<usize>| match slot {
+            None => "auto save slot".to_string(),
+            Some(slot) => format!("save slot {}", slot),
+        };
+
+        match change {
+            SaveChange::PlaySeconds { old, new } => {
+                format!("play time: {}s -> {}s", old, new)
+            }
+            SaveChange::PersistFlag { index, old, new } => {
+                format!("persistent flag {}: {} -> {}", index, old, new)
+            }
+            &SaveChange::Bit { vector, index, set } => {
+                let what = match vector {
+                    SaveBitVector::SeenMessages => "message",
+                    SaveBitVector::SeenChoices => "choice",
+                    SaveBitVector::UnlockedPictures => "picture",
+                    SaveBitVector::UnlockedBgms => "BGM",
+                    SaveBitVector::UnlockedTips => "tip",
+                };
+                let state = match (vector, set) {
+                    (SaveBitVector::SeenMessages | SaveBitVector::SeenChoices, true) => "read",
+                    (SaveBitVector::SeenMessages | SaveBitVector::SeenChoices, false) => "unread",
+                    (_, true) => "unlocked",
+                    (_, false) => "locked",
+                };
+                match self.bit_name(vector, index) {
+                    Some(name) => format!("{} {} {:?}: {}", what, index, name, state),
+                    None => format!("{} {}: {}", what, index, state),
+                }
+            }
+            SaveChange::Vec3 { index, old, new } => {
+                format!("vec3[{}]: {:?} -> {:?}", index, old, new)
+            }
+            SaveChange::Settings => "settings changed".to_string(),
+            SaveChange::Slot { slot, old: _, new } => match new {
+                Some(new) => format!(
+                    "{}: saved at {} (position 0x{:x})",
+                    slot_name(*slot),
+                    new.date_time(),
+                    new.entry().save_position
+                ),
+                None => format!("{}: cleared", slot_name(*slot)),
+            },
+        }
+    }
+}
+
 pub fn savedata_command(command: SavedataCommand) -> Result<()> {
     match command {
         SavedataCommand::Deobfuscate {
@@ -49,15 +235,24 @@ pub fn savedata_command(command: SavedataCommand) -> Result<()> {
             output_path,
             key,
             key_seed,
+            game,
         } => {
             let savedata = std::fs::read(save_path)?;
 
             let key = key.or_else(|| key_seed.as_deref().map(Savedata::obfuscation_key_from_seed));
 
-            let savedata = match key {
-                None => Savedata::deobfuscate(&savedata),
-                Some(key) => Savedata::deobfuscate_with_key(&savedata, key),
-            }?;
+            let savedata = match (game, key) {
+                (None, None) => {
+                    let (descriptor, savedata) = SaveDescriptor::detect(&savedata)?;
+                    println!("Detected a {} save", descriptor.game);
+                    savedata
+                }
+                (None, Some(key)) => Savedata::deobfuscate_with_key(&savedata, key)?,
+                (Some(game), None) => SaveDescriptor::by_game(&game)?.deobfuscate(&savedata)?,
+                (Some(game), Some(key)) => {
+                    SaveDescriptor::by_game(&game)?.deobfuscate_with_key(&savedata, key)?
+                }
+            };
 
             std::fs::write(output_path, savedata)?;
 
@@ -68,14 +263,16 @@ pub fn savedata_command(command: SavedataCommand) -> Result<()> {
             output_path,
             key,
             key_seed,
+            game,
         } => {
             let savedata = std::fs::read(save_path)?;
+            let descriptor = SaveDescriptor::by_game(&game)?;
 
             let key = key.or_else(|| key_seed.as_deref().map(Savedata::obfuscation_key_from_seed));
 
             let savedata = match key {
-                None => Savedata::obfuscate(&savedata),
-                Some(key) => Savedata::obfuscate_with_key(&savedata, key),
+                None => descriptor.obfuscate(&savedata),
+                Some(key) => descriptor.obfuscate_with_key(&savedata, key),
             };
 
             std::fs::write(output_path, savedata)?;
@@ -86,9 +283,17 @@ pub fn savedata_command(command: SavedataCommand) -> Result<()> {
         SavedataCommand::Decode {
             save_path,
             output_path,
+            game,
         } => {
             let savedata = std::fs::read(save_path)?;
-            let savedata = Savedata::decode(&savedata)?;
+            let savedata = match game {
+                None => {
+                    let (descriptor, savedata) = Savedata::decode(&savedata)?;
+                    println!("Detected a {} save", descriptor.game);
+                    savedata
+                }
+                Some(game) => Savedata::decode_as(&savedata, SaveDescriptor::by_game(&game)?)?,
+            };
 
             ron::ser::to_writer_pretty(
                 File::create(output_path).context("Creating output file")?,
@@ -97,6 +302,73 @@ pub fn savedata_command(command: SavedataCommand) -> Result<()> {
             )
             .context("Writing human-readable savedata")?;
 
+            Ok(())
+        }
+        SavedataCommand::Encode {
+            input_path,
+            output_path,
+            game,
+        } => {
+            let savedata = std::fs::read_to_string(input_path)?;
+            let savedata: Savedata =
+                ron::from_str(&savedata).context("Parsing human-readable savedata")?;
+
+            let savedata = savedata.encode_as(SaveDescriptor::by_game(&game)?)?;
+            std::fs::write(output_path, savedata)?;
+
+            Ok(())
+        }
+        SavedataCommand::Diff {
+            old_path,
+            new_path,
+            game,
+            scenario,
+        } => {
+            let (_, old) = read_save(&old_path, game.as_deref())?;
+            let (_, new) = read_save(&new_path, game.as_deref())?;
+            let names = match scenario {
+                Some(scenario) => SaveNames::from_scenario(&scenario)?,
+                None => SaveNames::default(),
+            };
+
+            let changes = old.diff(&new);
+            if changes.is_empty() {
+                println!("The saves are the same");
+            }
+            for change in &changes {
+                println!("{}", names.describe(change));
+            }
+
+            Ok(())
+        }
+        SavedataCommand::Merge {
+            base_path,
+            other_path,
+            output_path,
+            game,
+        } => {
+            let (descriptor, base) = read_save(&base_path, game.as_deref())?;
+            let (other_descriptor, other) = read_save(&other_path, game.as_deref())?;
+            if descriptor.game != other_descriptor.game {
+                bail!(
+                    "Can't merge a {} save with a {} save",
+                    descriptor.game,
+                    other_descriptor.game
+                );
+            }
+
+            let mut merged = base.clone();
+            merged.merge_read_state(&other);
+
+            let names = SaveNames::default();
+            let changes = base.diff(&merged);
+            println!("Added {} items of the read state:", changes.len());
+            for change in &changes {
+                println!("  {}", names.describe(change));
+            }
+
+            std::fs::write(output_path, merged.encode_as(descriptor)?)?;
+
             Ok(())
         }
     }
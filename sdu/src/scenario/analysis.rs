@@ -0,0 +1,349 @@
+//! Simple control-flow analysis of a scenario, used to make the disassembly readable.
+//!
+//! This is purely static: the targets are collected from the instructions operands, so anything computed at runtime is not visible here.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+    io::Write,
+};
+
+use anyhow::{Context, Result};
+use shin_core::{
+    format::scenario::{instruction_elements::CodeAddress, instructions::Instruction, Scenario},
+    vm::{command::CompiletimeCommand, coverage::CoverageLog},
+};
+
+use super::symbols::InfoSymbols;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum XrefKind {
+    Jump,
+    ConditionalJump,
+    JumpTable { case: usize },
+    Gosub,
+    Call,
+}
+
+impl XrefKind {
+    fn is_function_call(self) -> bool {
+        matches!(self, XrefKind::Gosub | XrefKind::Call)
+    }
+}
+
+impl Display for XrefKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            XrefKind::Jump => write!(f, "j"),
+            XrefKind::ConditionalJump => write!(f, "jc"),
+            XrefKind::JumpTable { case } => write!(f, "jt case {}", case),
+            XrefKind::Gosub => write!(f, "gosub"),
+            XrefKind::Call => write!(f, "call"),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Xref {
+    pub from: CodeAddress,
+    pub kind: XrefKind,
+}
+
+/// Lists the code addresses an instruction can transfer control to (not including the fallthrough)
+fn instruction_targets(instruction: &Instruction) -> Vec<(CodeAddress, XrefKind)> {
+    match instruction {
+        Instruction::jc { target, .. } => vec![(*target, XrefKind::ConditionalJump)],
+        Instruction::j { target } => vec![(*target, XrefKind::Jump)],
+        Instruction::gosub { target } => vec![(*target, XrefKind::Gosub)],
+        Instruction::call { target, .. } => vec![(*target, XrefKind::Call)],
+        Instruction::jt { table, .. } => table
+            .0
+            .iter()
+            .enumerate()
+            .map(|(case, &target)| (target, XrefKind::JumpTable { case }))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+pub struct ScenarioAnalysis {
+    pub entrypoint: CodeAddress,
+    pub instructions: Vec<(CodeAddress, Instruction)>,
+    pub xrefs: BTreeMap<CodeAddress, Vec<Xref>>,
+    /// Start addresses of the detected functions (the entrypoint and all the `gosub`/`call` targets)
+    pub functions: BTreeSet<CodeAddress>,
+}
+
+impl ScenarioAnalysis {
+    pub fn new(scenario: &Scenario) -> Result<Self> {
+        let entrypoint = scenario.entrypoint_address();
+        let mut reader = scenario.instruction_reader(entrypoint);
+
+        let end_position = scenario.code_end();
+
+        let mut instructions = Vec::new();
+        while reader.position() < end_position {
+            let position = reader.position();
+
+            let instruction = reader
+                .read()
+                .with_context(|| format!("Reading instruction at {}", position))?;
+            instructions.push((position, instruction));
+        }
+
+        let mut xrefs = BTreeMap::<_, Vec<_>>::new();
+        let mut functions = BTreeSet::from([entrypoint]);
+        for (position, instruction) in &instructions {
+            for (target, kind) in instruction_targets(instruction) {
+                if kind.is_function_call() {
+                    functions.insert(target);
+                }
+                xrefs.entry(target).or_default().push(Xref {
+                    from: *position,
+                    kind,
+                });
+            }
+        }
+
+        Ok(Self {
+            entrypoint,
+            instructions,
+            xrefs,
+            functions,
+        })
+    }
+
+    /// Finds the function containing the address
+    ///
+    /// Functions are assumed to be contiguous and to span until the start of the next function.
+    pub fn function_of(&self, address: CodeAddress) -> Option<CodeAddress> {
+        self.functions.range(..=address).next_back().copied()
+    }
+
+    pub fn label_name(&self, address: CodeAddress) -> String {
+        if address == self.entrypoint {
+            "ENTRY".to_string()
+        } else if self.functions.contains(&address) {
+            format!("FUN_{:08x}", address.0)
+        } else {
+            format!("LAB_{:08x}", address.0)
+        }
+    }
+
+    /// Lists the call graph edges (caller function -> callee function), without duplicates
+    pub fn call_graph(&self) -> BTreeSet<(CodeAddress, CodeAddress)> {
+        let mut edges = BTreeSet::new();
+        for (&target, xrefs) in &self.xrefs {
+            for xref in xrefs.iter().filter(|xref| xref.kind.is_function_call()) {
+                if let Some(caller) = self.function_of(xref.from) {
+                    edges.insert((caller, target));
+                }
+            }
+        }
+        edges
+    }
+
+    /// Writes the disassembly listing, with the info tables items referenced by the commands resolved with `symbols`
+    pub fn write_disassembly(&self, symbols: &InfoSymbols, output: &mut dyn Write) -> Result<()> {
+        for (position, instruction) in &self.instructions {
+            let position = *position;
+
+            if self.functions.contains(&position) {
+                let callers = self.xrefs.get(&position).map_or(0, |v| v.len());
+                writeln!(output)?;
+                writeln!(
+                    output,
+                    "; ==== function {} ({} call sites) ====",
+                    self.label_name(position),
+                    callers
+                )?;
+            }
+
+            if let Some(xrefs) = self.xrefs.get(&position) {
+                writeln!(
+                    output,
+                    "; xrefs: {}",
+                    xrefs
+                        .iter()
+                        .map(|xref| format!("{} ({})", xref.from, xref.kind))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )?;
+            }
+            if self.xrefs.contains_key(&position) || position == self.entrypoint {
+                writeln!(output, "{}:", self.label_name(position))?;
+            }
+
+            let targets = instruction_targets(instruction);
+            let mut comments = Vec::new();
+            if let [(target, _)] = targets.as_slice() {
+                if !matches!(instruction, Instruction::jt { .. }) {
+                    comments.push(format!("-> {}", self.label_name(*target)));
+                }
+            }
+            if let Instruction::Command(command) = instruction {
+                if let Some((table, id)) = InfoSymbols::reference(command) {
+                    comments.push(symbols.describe(table, id));
+                }
+            }
+
+            if comments.is_empty() {
+                writeln!(output, "{:08x?} {:?}", position.0, instruction)?;
+            } else {
+                writeln!(
+                    output,
+                    "{:08x?} {:?} ; {}",
+                    position.0,
+                    instruction,
+                    comments.join("; ")
+                )?;
+            }
+            if let Instruction::jt { .. } = instruction {
+                for (target, kind) in targets {
+                    writeln!(output, ";   {} -> {}", kind, self.label_name(target))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn write_call_graph_dot(&self, output: &mut dyn Write) -> Result<()> {
+        writeln!(output, "digraph calls {{")?;
+        writeln!(output, "    node [shape=box, fontname=monospace];")?;
+        for &function in &self.functions {
+            writeln!(output, "    \"{}\";", self.label_name(function))?;
+        }
+        for (caller, callee) in self.call_graph() {
+            writeln!(
+                output,
+                "    \"{}\" -> \"{}\";",
+                self.label_name(caller),
+                self.label_name(callee)
+            )?;
+        }
+        writeln!(output, "}}")?;
+
+        Ok(())
+    }
+
+    /// Lists outcomes of the executed branch instructions that were never taken
+    ///
+    /// Branches that were never executed themselves are not listed: they are inside code that was not reached at all.
+    fn untaken_branches(&self, coverage: &CoverageLog) -> Vec<(CodeAddress, String, CodeAddress)> {
+        let mut result = Vec::new();
+        for (index, (position, instruction)) in self.instructions.iter().enumerate() {
+            let position = *position;
+            if !coverage.is_executed(position) {
+                continue;
+            }
+
+            let mut outcomes = instruction_targets(instruction)
+                .into_iter()
+                .filter(|(_, kind)| {
+                    matches!(kind, XrefKind::ConditionalJump | XrefKind::JumpTable { .. })
+                })
+                .map(|(target, kind)| (kind.to_string(), target))
+                .collect::<Vec<_>>();
+            if let (Instruction::jc { .. }, Some(&(next, _))) =
+                (instruction, self.instructions.get(index + 1))
+            {
+                outcomes.push(("jc fallthrough".to_string(), next));
+            }
+
+            for (kind, target) in outcomes {
+                if !coverage.is_branch_taken(position, target) {
+                    result.push((position, kind, target));
+                }
+            }
+        }
+        result
+    }
+
+    pub fn write_coverage_report(
+        &self,
+        coverage: &CoverageLog,
+        output: &mut dyn Write,
+    ) -> Result<()> {
+        fn percent(part: usize, total: usize) -> f64 {
+            if total == 0 {
+                100.0
+            } else {
+                part as f64 * 100.0 / total as f64
+            }
+        }
+
+        let executed = self
+            .instructions
+            .iter()
+            .filter(|(position, _)| coverage.is_executed(*position))
+            .count();
+        let total = self.instructions.len();
+        writeln!(
+            output,
+            "Instructions: {}/{} ({:.1}%)",
+            executed,
+            total,
+            percent(executed, total)
+        )?;
+
+        let messages = self
+            .instructions
+            .iter()
+            .filter(|(_, instruction)| {
+                matches!(
+                    instruction,
+                    Instruction::Command(CompiletimeCommand::MSGSET(_))
+                )
+            })
+            .collect::<Vec<_>>();
+        let unvisited_messages = messages
+            .iter()
+            .filter(|(position, _)| !coverage.is_executed(*position))
+            .collect::<Vec<_>>();
+        writeln!(
+            output,
+            "Messages: {}/{} ({:.1}%)",
+            messages.len() - unvisited_messages.len(),
+            messages.len(),
+            percent(messages.len() - unvisited_messages.len(), messages.len())
+        )?;
+
+        let functions = self
+            .functions
+            .iter()
+            .filter(|&&function| coverage.is_executed(function))
+            .count();
+        writeln!(
+            output,
+            "Functions: {}/{} ({:.1}%)",
+            functions,
+            self.functions.len(),
+            percent(functions, self.functions.len())
+        )?;
+
+        writeln!(output)?;
+        writeln!(output, "Untaken branches:")?;
+        for (position, kind, target) in self.untaken_branches(coverage) {
+            let function = self
+                .function_of(position)
+                .map_or_else(|| "?".to_string(), |f| self.label_name(f));
+            writeln!(
+                output,
+                "  {} (in {}): {} -> {}",
+                position, function, kind, target
+            )?;
+        }
+
+        writeln!(output)?;
+        writeln!(output, "Unvisited messages:")?;
+        for (position, instruction) in unvisited_messages {
+            let Instruction::Command(CompiletimeCommand::MSGSET(msgset)) = instruction else {
+                unreachable!()
+            };
+            writeln!(output, "  {} {:?}", position, msgset.msg_id)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,74 @@
+//! The JSON form of the scenario info tables, for `dump-info --format json`
+//!
+//! The asset tables are written as arrays of objects indexed by the asset ids, with the asset paths resolved.
+//! The segment lists of the character box and the `chars` screen are written in their debug form, as they don't have a stable structure yet.
+
+use serde_json::{json, Value};
+use shin_core::format::scenario::info::ScenarioInfoTables;
+
+pub fn info_tables_json(tables: &ScenarioInfoTables) -> Value {
+    json!({
+        "masks": tables.mask_info.iter().map(|mask| json!({
+            "name": mask.name.as_str(),
+            "path": mask.path(),
+        })).collect::<Vec<_>>(),
+        "pictures": tables.picture_info.iter().map(|picture| json!({
+            "name": picture.name.as_str(),
+            "path": picture.path(),
+            "linked_cg_id": picture.linked_cg_id,
+        })).collect::<Vec<_>>(),
+        "bustups": tables.bustup_info.iter().map(|bustup| json!({
+            "name": bustup.name.as_str(),
+            "path": bustup.path(),
+            "emotion": bustup.emotion.as_str(),
+            "lipsync_character_id": bustup.lipsync_character_id,
+        })).collect::<Vec<_>>(),
+        "bgms": tables.bgm_info.iter().map(|bgm| json!({
+            "name": bgm.name.as_str(),
+            "path": bgm.path(),
+            "display_name": bgm.display_name.as_str(),
+            "linked_bgm_id": bgm.linked_bgm_id,
+        })).collect::<Vec<_>>(),
+        "ses": tables.se_info.iter().map(|se| json!({
+            "name": se.name.as_str(),
+            "path": se.path(),
+        })).collect::<Vec<_>>(),
+        "movies": tables.movie_info.iter().map(|movie| json!({
+            "name": movie.name.as_str(),
+            "path": movie.path(),
+            "subtitles_path": movie.subtitles_path(),
+            "linked_picture_id": movie.linked_picture_id,
+            "flags": movie.flags,
+            "linked_bgm_id": movie.linked_bgm_id,
+        })).collect::<Vec<_>>(),
+        "voice_mappings": tables.voice_mapping_info.iter().map(|mapping| json!({
+            "name_pattern": mapping.name_pattern.as_str(),
+            "lipsync_character_ids": mapping.lipsync_character_ids.0.to_vec(),
+        })).collect::<Vec<_>>(),
+        "picture_box": tables.picture_box_info.iter().map(|item| json!({
+            "name": item.name.as_str(),
+            "picture_ids": item.picture_ids.0.to_vec(),
+        })).collect::<Vec<_>>(),
+        "music_box": tables.music_box_info.iter().map(|item| json!({
+            "bgm_id": item.bgm_id,
+            "name_index": item.name_index,
+            "once": item.once_flag != 0,
+        })).collect::<Vec<_>>(),
+        "character_box": tables.character_box_info.iter()
+            .map(|segment| format!("{:?}", segment))
+            .collect::<Vec<_>>(),
+        "chars_sprites": tables.chars_sprite_info.iter().map(|item| json!({
+            "episode": item.episode,
+            "segments": item.segments.iter().map(|segment| format!("{:?}", segment)).collect::<Vec<_>>(),
+        })).collect::<Vec<_>>(),
+        "chars_grids": tables.chars_grid_info.iter().map(|item| json!({
+            "segments": item.segments.iter().map(|segment| format!("{:?}", segment)).collect::<Vec<_>>(),
+        })).collect::<Vec<_>>(),
+        "tips": tables.tips_info.iter().map(|tip| json!({
+            "episode": tip.episode,
+            "title_index": tip.title_index,
+            "title": tip.title.as_str(),
+            "content": tip.content.as_str(),
+        })).collect::<Vec<_>>(),
+    })
+}
@@ -1,3 +1,7 @@
+pub mod analysis;
+mod info_json;
+mod symbols;
+
 use std::{fs::File, path::PathBuf};
 
 use anyhow::{Context, Result};
@@ -5,21 +9,55 @@ use bytes::Bytes;
 use itertools::Itertools;
 use shin_core::{
     format::scenario::instruction_elements::CodeAddress,
-    vm::command::{CommandResult, RuntimeCommand},
+    vm::{
+        command::{CommandResult, RuntimeCommand},
+        coverage::CoverageLog,
+    },
 };
 
+#[derive(clap::ValueEnum, Debug, Clone, Copy)]
+pub enum InfoFormat {
+    /// A plain listing, one item per line
+    Text,
+    /// A JSON object with an array per table, for scripts and other tools
+    Json,
+}
+
 #[derive(clap::Subcommand, Debug)]
 pub enum ScenarioCommand {
     /// Run a scenario in VM, printing all the commands executed
     ///
+    /// The commands are "executed" with dummy implementations, so the output can be diffed against other implementations.
+    ///
     /// NOTE: this doesn't work too well with SELECT: it always selects the first option
-    Trace {
+    #[clap(alias = "trace")]
+    Run {
         /// Path to the SNR file
         scenario_path: PathBuf,
         /// Initial value of the memory cell "0", usually selecting the episode or smth
         #[clap(default_value = "0")]
         init_val: i32,
         output_filename: Option<PathBuf>,
+        /// Write the coverage log of the run to this file (see `coverage-report`)
+        #[clap(long)]
+        coverage: Option<PathBuf>,
+        /// Initial value of the PRNG state
+        #[clap(long, default_value = "42")]
+        seed: u32,
+        /// Only print commands with these names (comma-separated, case-insensitive)
+        #[clap(long, value_delimiter = ',')]
+        filter: Vec<String>,
+        /// Stop after the execution reaches this address (decimal or 0x-prefixed hex)
+        ///
+        /// The VM is only stopped at commands, so this stops at the first command executed after reaching the address.
+        #[clap(long, value_parser = parse_address)]
+        until: Option<CodeAddress>,
+        /// Stop after this many messages (MSGSET commands) were shown
+        #[clap(long)]
+        max_messages: Option<usize>,
+        /// Stop after this many commands were executed (useful to break infinite loops)
+        #[clap(long)]
+        max_commands: Option<usize>,
     },
     /// Run a scenario in VM, parsing all the messages with layout parser (for testing)
     TestLayouter {
@@ -42,14 +80,45 @@ pub enum ScenarioCommand {
     DumpInfo {
         scenario_path: PathBuf,
         output_filename: Option<PathBuf>,
+        #[clap(long, value_enum, default_value_t = InfoFormat::Text)]
+        format: InfoFormat,
     },
     /// Disassemble a scenario into an assembly-like language
     ///
+    /// Function boundaries are detected from `gosub`/`call` targets, jump targets get labels and cross-reference comments.
+    /// The masks, pictures, bustups, BGMs, SEs and movies referenced by constant ids are named in comments.
+    ///
     /// NOTE: the format of the output is not stable yet
     Disassemble {
         scenario_path: PathBuf,
         output_filename: Option<PathBuf>,
+        /// Also write the call graph in graphviz format to this file
+        #[clap(long)]
+        call_graph: Option<PathBuf>,
+        /// Also write the names of the info tables items as `def` aliases to this `.sal` file, to be passed to the assembler along with the sources
+        #[clap(long)]
+        symbols: Option<PathBuf>,
     },
+    /// Merge coverage logs and report the parts of the scenario that were never executed
+    ///
+    /// The logs can be recorded with `run --coverage` or by the engine with `--coverage-log`.
+    CoverageReport {
+        scenario_path: PathBuf,
+        /// Coverage logs to merge
+        #[clap(required = true)]
+        logs: Vec<PathBuf>,
+        #[clap(short, long)]
+        output_filename: Option<PathBuf>,
+    },
+}
+
+fn parse_address(s: &str) -> Result<CodeAddress> {
+    let address = match s.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => s.parse(),
+    }
+    .with_context(|| format!("Invalid address: {:?}", s))?;
+    Ok(CodeAddress(address))
 }
 
 fn make_output(output_filename: Option<PathBuf>) -> Result<Box<dyn std::io::Write>> {
@@ -61,21 +130,62 @@ fn make_output(output_filename: Option<PathBuf>) -> Result<Box<dyn std::io::Writ
     }
 }
 
-fn trace(path: PathBuf, init_val: i32, output_filename: Option<PathBuf>) -> Result<()> {
+struct RunOptions {
+    init_val: i32,
+    seed: u32,
+    filter: Vec<String>,
+    until: Option<CodeAddress>,
+    max_messages: Option<usize>,
+    max_commands: Option<usize>,
+}
+
+fn run(
+    path: PathBuf,
+    options: RunOptions,
+    output_filename: Option<PathBuf>,
+    coverage: Option<PathBuf>,
+) -> Result<()> {
     let scenario = std::fs::read(path)?;
     let scenario = Bytes::from(scenario);
     let scenario = shin_core::format::scenario::Scenario::new(scenario)?;
 
     let mut output = make_output(output_filename)?;
 
-    let mut vm = shin_core::vm::Scripter::new(&scenario, init_val, 42);
+    let mut vm = shin_core::vm::Scripter::new(&scenario, options.init_val, options.seed);
+    if coverage.is_some() {
+        vm.enable_coverage();
+    }
+    let until = options.until.map(|address| vm.add_breakpoint(address));
+
+    let mut messages = 0;
+    let mut commands = 0;
     let mut result = CommandResult::None;
     loop {
         // NOTE: usually you would want to do something when the VM has returned "Pending"
         // stuff like running game loop to let the command progress...
         let command = vm.run(result)?;
-        writeln!(output, "{:08x} {}", vm.position().0, command)
-            .context("Writing to the output file")?;
+        commands += 1;
+        if matches!(command, RuntimeCommand::MSGSET(_)) {
+            messages += 1;
+        }
+
+        if options.filter.is_empty()
+            || options
+                .filter
+                .iter()
+                .any(|name| name.eq_ignore_ascii_case(command.name()))
+        {
+            writeln!(output, "{:08x} {}", vm.position().0, command)
+                .context("Writing to the output file")?;
+        }
+
+        if until.as_ref().is_some_and(|b| b.hit_count() > 0)
+            || options.max_messages.is_some_and(|max| messages >= max)
+            || options.max_commands.is_some_and(|max| commands >= max)
+        {
+            break;
+        }
+
         if let Some(new_result) = command.execute_dummy() {
             result = new_result
         } else {
@@ -83,7 +193,12 @@ fn trace(path: PathBuf, init_val: i32, output_filename: Option<PathBuf>) -> Resu
         }
     }
 
-    // println!("{:#?}", reader);
+    if let (Some(coverage), Some(log)) = (coverage, vm.coverage()) {
+        let file = File::create(coverage).context("Opening coverage log file")?;
+        log.write(std::io::BufWriter::new(file))
+            .context("Writing the coverage log")?;
+    }
+
     Ok(())
 }
 
@@ -161,7 +276,7 @@ fn char_frequency(path: PathBuf, init_val: i32, top_k: usize) -> Result<()> {
     Ok(())
 }
 
-fn dump_info(path: PathBuf, output_filename: Option<PathBuf>) -> Result<()> {
+fn dump_info(path: PathBuf, output_filename: Option<PathBuf>, format: InfoFormat) -> Result<()> {
     let scenario = std::fs::read(path)?;
     let scenario = Bytes::from(scenario);
     let scenario = shin_core::format::scenario::Scenario::new(scenario)?;
@@ -169,6 +284,12 @@ fn dump_info(path: PathBuf, output_filename: Option<PathBuf>) -> Result<()> {
     let mut output = make_output(output_filename)?;
 
     let tables = scenario.info_tables();
+    if let InfoFormat::Json = format {
+        serde_json::to_writer_pretty(&mut output, &info_json::info_tables_json(tables))
+            .context("Writing the JSON")?;
+        writeln!(output)?;
+        return Ok(());
+    }
     // I kinda hate it. Can we have a macro-based solution?
 
     writeln!(output, "Masks:")?;
@@ -255,43 +376,85 @@ fn dump_info(path: PathBuf, output_filename: Option<PathBuf>) -> Result<()> {
     Ok(())
 }
 
-fn disassemble(path: PathBuf, output_filename: Option<PathBuf>) -> Result<()> {
+fn disassemble(
+    path: PathBuf,
+    output_filename: Option<PathBuf>,
+    call_graph: Option<PathBuf>,
+    symbols_filename: Option<PathBuf>,
+) -> Result<()> {
     let scenario = std::fs::read(path)?;
     let scenario = Bytes::from(scenario);
     let scenario = shin_core::format::scenario::Scenario::new(scenario)?;
 
+    let analysis = analysis::ScenarioAnalysis::new(&scenario)?;
+    let symbols = symbols::InfoSymbols::new(scenario.info_tables());
+
     let mut output = make_output(output_filename)?;
+    analysis.write_disassembly(&symbols, &mut output)?;
 
-    let entry = scenario.entrypoint_address();
-    let mut reader = scenario.instruction_reader(entry);
+    if let Some(call_graph) = call_graph {
+        let mut output = make_output(Some(call_graph))?;
+        analysis.write_call_graph_dot(&mut output)?;
+    }
 
-    let mut end_position = scenario.raw().len();
-    // scenario file is aligned to 0x10 bytes, so there are some zeros at the end
-    // trim them
-    while end_position > 0 && scenario.raw()[end_position - 1] == 0 {
-        end_position -= 1;
+    if let Some(symbols_filename) = symbols_filename {
+        let mut output = make_output(Some(symbols_filename))?;
+        symbols.write_symbols(&mut output)?;
     }
-    let end_position = CodeAddress(end_position as u32);
 
-    while reader.position() < end_position {
-        let position = reader.position();
+    Ok(())
+}
 
-        let instruction = reader
-            .read()
-            .with_context(|| format!("Reading instruction at {}", position))?;
-        writeln!(output, "{:08x?} {:?}", position.0, instruction)?;
+fn coverage_report(
+    path: PathBuf,
+    logs: Vec<PathBuf>,
+    output_filename: Option<PathBuf>,
+) -> Result<()> {
+    let scenario = std::fs::read(path)?;
+    let scenario = Bytes::from(scenario);
+    let scenario = shin_core::format::scenario::Scenario::new(scenario)?;
+
+    let mut coverage = CoverageLog::new();
+    for log in logs {
+        let file = File::open(&log).with_context(|| format!("Opening {:?}", log))?;
+        let log = CoverageLog::read(std::io::BufReader::new(file))
+            .with_context(|| format!("Reading coverage log {:?}", log))?;
+        coverage.merge(&log);
     }
 
+    let analysis = analysis::ScenarioAnalysis::new(&scenario)?;
+
+    let mut output = make_output(output_filename)?;
+    analysis.write_coverage_report(&coverage, &mut output)?;
+
     Ok(())
 }
 
 pub fn scenario_command(command: ScenarioCommand) -> Result<()> {
     match command {
-        ScenarioCommand::Trace {
+        ScenarioCommand::Run {
             scenario_path,
             init_val,
             output_filename,
-        } => trace(scenario_path, init_val, output_filename),
+            coverage,
+            seed,
+            filter,
+            until,
+            max_messages,
+            max_commands,
+        } => run(
+            scenario_path,
+            RunOptions {
+                init_val,
+                seed,
+                filter,
+                until,
+                max_messages,
+                max_commands,
+            },
+            output_filename,
+            coverage,
+        ),
         ScenarioCommand::TestLayouter {
             scenario_path,
             init_val,
@@ -304,10 +467,18 @@ pub fn scenario_command(command: ScenarioCommand) -> Result<()> {
         ScenarioCommand::DumpInfo {
             scenario_path,
             output_filename,
-        } => dump_info(scenario_path, output_filename),
+            format,
+        } => dump_info(scenario_path, output_filename, format),
         ScenarioCommand::Disassemble {
             scenario_path,
             output_filename,
-        } => disassemble(scenario_path, output_filename),
+            call_graph,
+            symbols,
+        } => disassemble(scenario_path, output_filename, call_graph, symbols),
+        ScenarioCommand::CoverageReport {
+            scenario_path,
+            logs,
+            output_filename,
+        } => coverage_report(scenario_path, logs, output_filename),
     }
 }
@@ -0,0 +1,240 @@
+//! Symbolic names of the info tables items (masks, pictures, bustups, BGMs, SEs and movies), used to make the disassembly readable.
+//!
+//! The ids referenced by the commands are resolved to the item names in the disassembly comments.
+//! The names can also be written as `def` aliases in a `.sal` file, so the assembler sources can use them instead of the bare ids.
+
+use std::{collections::HashSet, io::Write};
+
+use anyhow::Result;
+use shin_core::{
+    format::scenario::{info::ScenarioInfoTables, instruction_elements::UntypedNumberSpec},
+    vm::command::{types::LayerType, CompiletimeCommand},
+};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum InfoTable {
+    Mask,
+    Picture,
+    Bustup,
+    Bgm,
+    Se,
+    Movie,
+}
+
+impl InfoTable {
+    const ALL: [InfoTable; 6] = [
+        InfoTable::Mask,
+        InfoTable::Picture,
+        InfoTable::Bustup,
+        InfoTable::Bgm,
+        InfoTable::Se,
+        InfoTable::Movie,
+    ];
+
+    /// The prefix of the alias names, keeping the items of different tables apart
+    fn symbol_prefix(self) -> &'static str {
+        match self {
+            InfoTable::Mask => "MASK",
+            InfoTable::Picture => "PIC",
+            InfoTable::Bustup => "BUP",
+            InfoTable::Bgm => "BGM",
+            InfoTable::Se => "SE",
+            InfoTable::Movie => "MOVIE",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            InfoTable::Mask => "mask",
+            InfoTable::Picture => "picture",
+            InfoTable::Bustup => "bustup",
+            InfoTable::Bgm => "bgm",
+            InfoTable::Se => "se",
+            InfoTable::Movie => "movie",
+        }
+    }
+}
+
+struct TableSymbols {
+    table: InfoTable,
+    /// The names shown in the disassembly comments, indexed by the item id
+    names: Vec<String>,
+    /// The alias names, indexed by the item id
+    symbols: Vec<String>,
+}
+
+impl TableSymbols {
+    /// `items` are the names of the items and the base names of their aliases
+    fn new(table: InfoTable, items: Vec<(String, String)>) -> Self {
+        let mut used = HashSet::new();
+        let mut names = Vec::with_capacity(items.len());
+        let mut symbols = Vec::with_capacity(items.len());
+
+        for (id, (name, symbol_base)) in items.into_iter().enumerate() {
+            let sanitized = sanitize_identifier(&symbol_base);
+            let mut symbol = if sanitized.is_empty() {
+                format!("{}_{}", table.symbol_prefix(), id)
+            } else {
+                format!("{}_{}", table.symbol_prefix(), sanitized)
+            };
+            // some tables have several items with the same name
+            if !used.insert(symbol.clone()) {
+                symbol = format!("{}_{}", symbol, id);
+                used.insert(symbol.clone());
+            }
+
+            names.push(name);
+            symbols.push(symbol);
+        }
+
+        Self {
+            table,
+            names,
+            symbols,
+        }
+    }
+}
+
+/// Makes an identifier out of an item name: upper-case ASCII letters, digits and underscores
+fn sanitize_identifier(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>()
+        .trim_matches('_')
+        .to_string()
+}
+
+pub struct InfoSymbols {
+    tables: Vec<TableSymbols>,
+}
+
+impl InfoSymbols {
+    pub fn new(tables: &ScenarioInfoTables) -> Self {
+        let plain = |name: &str| (name.to_string(), name.to_string());
+
+        let tables = InfoTable::ALL
+            .into_iter()
+            .map(|table| {
+                let items = match table {
+                    InfoTable::Mask => tables
+                        .mask_info
+                        .iter()
+                        .map(|item| plain(item.name.as_str()))
+                        .collect(),
+                    InfoTable::Picture => tables
+                        .picture_info
+                        .iter()
+                        .map(|item| plain(item.name.as_str()))
+                        .collect(),
+                    InfoTable::Bustup => tables
+                        .bustup_info
+                        .iter()
+                        .map(|item| {
+                            (
+                                format!("{} {}", item.name.as_str(), item.emotion.as_str()),
+                                format!("{}_{}", item.name.as_str(), item.emotion.as_str()),
+                            )
+                        })
+                        .collect(),
+                    InfoTable::Bgm => tables
+                        .bgm_info
+                        .iter()
+                        .map(|item| {
+                            (
+                                format!("{} {:?}", item.name.as_str(), item.display_name.as_str()),
+                                item.name.as_str().to_string(),
+                            )
+                        })
+                        .collect(),
+                    InfoTable::Se => tables
+                        .se_info
+                        .iter()
+                        .map(|item| plain(item.name.as_str()))
+                        .collect(),
+                    InfoTable::Movie => tables
+                        .movie_info
+                        .iter()
+                        .map(|item| plain(item.name.as_str()))
+                        .collect(),
+                };
+                TableSymbols::new(table, items)
+            })
+            .collect();
+
+        Self { tables }
+    }
+
+    fn table(&self, table: InfoTable) -> &TableSymbols {
+        self.tables
+            .iter()
+            .find(|symbols| symbols.table == table)
+            .expect("all the tables are collected")
+    }
+
+    /// Finds the info tables item referenced by the command
+    ///
+    /// Only the ids that are constants are resolved, the ones coming from registers are only known at runtime.
+    pub fn reference(command: &CompiletimeCommand) -> Option<(InfoTable, i32)> {
+        let constant = |spec: UntypedNumberSpec| match spec {
+            UntypedNumberSpec::Constant(value) => Some(value),
+            UntypedNumberSpec::Register(_) => None,
+        };
+
+        match command {
+            CompiletimeCommand::BGMPLAY(cmd) => {
+                constant(cmd.bgm_data_id.into_untyped()).map(|id| (InfoTable::Bgm, id))
+            }
+            CompiletimeCommand::SEPLAY(cmd) => {
+                constant(cmd.se_data_id.into_untyped()).map(|id| (InfoTable::Se, id))
+            }
+            CompiletimeCommand::MASKLOAD(cmd) => {
+                constant(cmd.mask_data_id.into_untyped()).map(|id| (InfoTable::Mask, id))
+            }
+            CompiletimeCommand::LAYERLOAD(cmd) => {
+                // the picture, bustup and movie layers take the item id as the first parameter
+                let table = match constant(cmd.layer_type.into_untyped()) {
+                    Some(ty) if ty == LayerType::Picture as i32 => Some(InfoTable::Picture),
+                    Some(ty) if ty == LayerType::Bustup as i32 => Some(InfoTable::Bustup),
+                    Some(ty) if ty == LayerType::Movie as i32 => Some(InfoTable::Movie),
+                    _ => None,
+                };
+                table.and_then(|table| constant(cmd.params.to_untyped()[0]).map(|id| (table, id)))
+            }
+            _ => None,
+        }
+    }
+
+    /// Describes the referenced item for a disassembly comment, like `bgm <name> "<display name>"`
+    pub fn describe(&self, table: InfoTable, id: i32) -> String {
+        let name = usize::try_from(id)
+            .ok()
+            .and_then(|id| self.table(table).names.get(id));
+        match name {
+            Some(name) => format!("{} {}", table.description(), name),
+            None => format!("{} {} (out of range)", table.description(), id),
+        }
+    }
+
+    /// Writes the `def` aliases for all the items, in a form the assembler accepts
+    pub fn write_symbols(&self, output: &mut dyn Write) -> Result<()> {
+        writeln!(
+            output,
+            "// Info tables items, generated by `sdu scenario disassemble`"
+        )?;
+        for table in &self.tables {
+            writeln!(output)?;
+            writeln!(output, "// {}", table.table.description())?;
+            for (id, symbol) in table.symbols.iter().enumerate() {
+                writeln!(output, "def {} = {}", symbol, id)?;
+            }
+        }
+
+        Ok(())
+    }
+}
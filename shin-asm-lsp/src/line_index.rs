@@ -0,0 +1,65 @@
+use lsp_types::{Position, Range};
+use shin_asm::syntax::{TextRange, TextSize};
+
+/// Converts between byte offsets used by the compiler and LSP positions (line + UTF-16 column)
+pub struct LineIndex {
+    text: String,
+    /// Byte offsets of the line starts
+    line_starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub fn new(text: &str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+
+        Self {
+            text: text.to_string(),
+            line_starts,
+        }
+    }
+
+    /// Converts the position to an offset, clamping it to the end of the line (or the file)
+    pub fn offset(&self, position: Position) -> TextSize {
+        let Some(&line_start) = self.line_starts.get(position.line as usize) else {
+            return self.text_size(self.text.len());
+        };
+        let line_end = self
+            .line_starts
+            .get(position.line as usize + 1)
+            .map_or(self.text.len(), |&next| next - 1);
+
+        let mut column = 0;
+        for (index, c) in self.text[line_start..line_end].char_indices() {
+            if column >= position.character {
+                return self.text_size(line_start + index);
+            }
+            column += c.len_utf16() as u32;
+        }
+
+        self.text_size(line_end)
+    }
+
+    pub fn position(&self, offset: TextSize) -> Position {
+        let offset = usize::from(offset).min(self.text.len());
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let line_start = self.line_starts[line];
+
+        Position {
+            line: line as u32,
+            character: self.text[line_start..offset].encode_utf16().count() as u32,
+        }
+    }
+
+    pub fn range(&self, range: TextRange) -> Range {
+        Range {
+            start: self.position(range.start()),
+            end: self.position(range.end()),
+        }
+    }
+
+    fn text_size(&self, offset: usize) -> TextSize {
+        TextSize::try_from(offset).expect("File is too large")
+    }
+}
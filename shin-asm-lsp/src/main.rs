@@ -0,0 +1,82 @@
+//! A language server for the shin assembly.
+//!
+//! Communicates over stdio, logs are written to stderr (use `RUST_LOG` to configure them).
+
+mod line_index;
+mod server;
+
+use anyhow::Result;
+use lsp_server::{Connection, Message, Notification};
+use lsp_types::{
+    notification::{Notification as _, PublishDiagnostics},
+    CompletionOptions, HoverProviderCapability, OneOf, ServerCapabilities,
+    TextDocumentSyncCapability, TextDocumentSyncKind,
+};
+use tracing::info;
+use tracing_subscriber::EnvFilter;
+
+use crate::server::Server;
+
+fn server_capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        definition_provider: Some(OneOf::Left(true)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec!["$".to_string()]),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+fn main_loop(connection: &Connection) -> Result<()> {
+    let mut server = Server::new();
+
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? {
+                    return Ok(());
+                }
+                let response = server.handle_request(request);
+                connection.sender.send(response.into())?;
+            }
+            Message::Notification(notification) => {
+                if server.handle_notification(notification) {
+                    for params in server.diagnostics() {
+                        connection.sender.send(
+                            Notification::new(PublishDiagnostics::METHOD.to_string(), params)
+                                .into(),
+                        )?;
+                    }
+                }
+            }
+            Message::Response(_) => {}
+        }
+    }
+
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        // stdout is used for the protocol
+        .with_writer(std::io::stderr)
+        .compact()
+        .init();
+
+    let (connection, io_threads) = Connection::stdio();
+
+    let capabilities = serde_json::to_value(server_capabilities())?;
+    connection.initialize(capabilities)?;
+    info!("shin-asm-lsp initialized");
+
+    main_loop(&connection)?;
+
+    drop(connection);
+    io_threads.join()?;
+
+    Ok(())
+}
@@ -0,0 +1,354 @@
+use std::{
+    collections::BTreeMap,
+    panic::{self, AssertUnwindSafe},
+};
+
+use anyhow::Result;
+use lsp_server::{ErrorCode, Notification, Request, Response};
+use lsp_types::{
+    notification::{DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument},
+    request::{Completion, GotoDefinition, HoverRequest},
+    CompletionItemKind, CompletionParams, CompletionResponse, Diagnostic,
+    DiagnosticRelatedInformation, DiagnosticSeverity, Documentation, GotoDefinitionParams,
+    GotoDefinitionResponse, Hover, HoverContents, HoverParams, Location, MarkupContent, MarkupKind,
+    PublishDiagnosticsParams, Url,
+};
+use shin_asm::{
+    compile::{
+        db::Database,
+        diagnostics::{Severity, Span},
+        Db, File, Program,
+    },
+    ide,
+};
+use tracing::{error, warn};
+
+use crate::line_index::LineIndex;
+
+struct Document {
+    file: File,
+    version: i32,
+    text: String,
+    line_index: LineIndex,
+}
+
+/// Keeps the open documents in the compilation database and answers the requests.
+///
+/// All the open documents are compiled together as a single program.
+pub struct Server {
+    db: Database,
+    program: Program,
+    documents: BTreeMap<Url, Document>,
+    /// Documents that were closed since the last diagnostics publish, their diagnostics need to be cleared
+    closed_documents: Vec<Url>,
+}
+
+impl Server {
+    pub fn new() -> Self {
+        let db = Database::default();
+        let program = Program::new(&db, Vec::new());
+
+        Self {
+            db,
+            program,
+            documents: BTreeMap::new(),
+            closed_documents: Vec::new(),
+        }
+    }
+
+    fn open(&mut self, uri: Url, version: i32, text: String) {
+        let file = File::new(&self.db, uri.to_string(), text.clone());
+        self.documents.insert(
+            uri,
+            Document {
+                file,
+                version,
+                line_index: LineIndex::new(&text),
+                text,
+            },
+        );
+        self.update_program();
+    }
+
+    fn change(&mut self, uri: &Url, version: i32, text: String) {
+        let Some(document) = self.documents.get_mut(uri) else {
+            warn!("Change for a document that is not open: {}", uri);
+            return;
+        };
+
+        document.file.set_contents(&mut self.db).to(text.clone());
+        document.version = version;
+        document.line_index = LineIndex::new(&text);
+        document.text = text;
+    }
+
+    fn close(&mut self, uri: &Url) {
+        if self.documents.remove(uri).is_some() {
+            self.closed_documents.push(uri.clone());
+            self.update_program();
+        }
+    }
+
+    fn update_program(&mut self) {
+        let files = self.documents.values().map(|d| d.file).collect();
+        self.program.set_files(&mut self.db).to(files);
+    }
+
+    /// Re-creates the database from the document contents.
+    ///
+    /// Used to recover after the compiler panics, as the database might be left in an inconsistent state.
+    fn reset_database(&mut self) {
+        self.db = Database::default();
+        for (uri, document) in self.documents.iter_mut() {
+            document.file = File::new(&self.db, uri.to_string(), document.text.clone());
+        }
+        let files = self.documents.values().map(|d| d.file).collect();
+        self.program = Program::new(&self.db, files);
+    }
+
+    /// Runs an analysis, recovering if the compiler panics (there are still some unimplemented paths in it)
+    fn analyze<T>(&mut self, f: impl FnOnce(&dyn Db, Program) -> T) -> Option<T> {
+        let db: &dyn Db = &self.db;
+        let program = self.program;
+        match panic::catch_unwind(AssertUnwindSafe(|| f(db, program))) {
+            Ok(result) => Some(result),
+            Err(_) => {
+                error!("The compiler panicked, resetting the database");
+                self.reset_database();
+                None
+            }
+        }
+    }
+
+    fn document_by_file(&self, file: File) -> Option<(&Url, &Document)> {
+        self.documents.iter().find(|(_, d)| d.file == file)
+    }
+
+    fn location(&self, span: Span) -> Option<Location> {
+        let (uri, document) = self.document_by_file(span.file())?;
+        Some(Location::new(
+            uri.clone(),
+            document.line_index.range(span.range()),
+        ))
+    }
+
+    /// Compiles the program and returns the diagnostics for all the open documents
+    pub fn diagnostics(&mut self) -> Vec<PublishDiagnosticsParams> {
+        let mut result = self
+            .closed_documents
+            .drain(..)
+            .map(|uri| PublishDiagnosticsParams::new(uri, Vec::new(), None))
+            .collect::<Vec<_>>();
+
+        let Some(diagnostics) = self.analyze(ide::diagnostics) else {
+            return result;
+        };
+
+        let mut per_document = self
+            .documents
+            .keys()
+            .map(|uri| (uri.clone(), Vec::new()))
+            .collect::<BTreeMap<_, _>>();
+        for diagnostic in diagnostics {
+            let Some(location) = self.location(diagnostic.location) else {
+                continue;
+            };
+            let related_information = diagnostic
+                .additional_labels
+                .into_iter()
+                .filter_map(|(message, span)| {
+                    Some(DiagnosticRelatedInformation {
+                        location: self.location(span)?,
+                        message,
+                    })
+                })
+                .collect::<Vec<_>>();
+            let severity = match diagnostic.severity {
+                Severity::Warning => DiagnosticSeverity::WARNING,
+                Severity::Error => DiagnosticSeverity::ERROR,
+            };
+            // LSP has no place for the notes, so they are appended to the message
+            let message = std::iter::once(diagnostic.message)
+                .chain(diagnostic.notes)
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            per_document
+                .get_mut(&location.uri)
+                .unwrap()
+                .push(Diagnostic::new(
+                    location.range,
+                    Some(severity),
+                    None,
+                    Some("shin-asm".to_string()),
+                    message,
+                    (!related_information.is_empty()).then_some(related_information),
+                    None,
+                ));
+        }
+
+        result.extend(per_document.into_iter().map(|(uri, diagnostics)| {
+            let version = self.documents[&uri].version;
+            PublishDiagnosticsParams::new(uri, diagnostics, Some(version))
+        }));
+        result
+    }
+
+    fn goto_definition(&mut self, params: GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+        let position = params.text_document_position_params;
+        let document = self.documents.get(&position.text_document.uri)?;
+        let file = document.file;
+        let offset = document.line_index.offset(position.position);
+
+        let target = self
+            .analyze(|db, program| ide::goto_definition(db, program, file, offset))
+            .flatten()?;
+
+        self.location(target.span)
+            .map(GotoDefinitionResponse::Scalar)
+    }
+
+    fn hover(&mut self, params: HoverParams) -> Option<Hover> {
+        let position = params.text_document_position_params;
+        let document = self.documents.get(&position.text_document.uri)?;
+        let file = document.file;
+        let offset = document.line_index.offset(position.position);
+
+        let hover = self
+            .analyze(|db, program| ide::hover(db, program, file, offset))
+            .flatten()?;
+
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: hover.markdown,
+            }),
+            range: Some(
+                self.documents[&position.text_document.uri]
+                    .line_index
+                    .range(hover.range),
+            ),
+        })
+    }
+
+    fn completion(&mut self, params: CompletionParams) -> Option<CompletionResponse> {
+        let position = params.text_document_position;
+        let document = self.documents.get(&position.text_document.uri)?;
+        let file = document.file;
+        let offset = document.line_index.offset(position.position);
+
+        let completions =
+            self.analyze(|db, program| ide::completions(db, program, file, offset))?;
+
+        Some(CompletionResponse::Array(
+            completions
+                .into_iter()
+                .map(|item| lsp_types::CompletionItem {
+                    label: item.label,
+                    kind: Some(match item.kind {
+                        ide::CompletionKind::Instruction => CompletionItemKind::KEYWORD,
+                        ide::CompletionKind::Item => CompletionItemKind::CONSTANT,
+                        ide::CompletionKind::Register => CompletionItemKind::VARIABLE,
+                    }),
+                    detail: item.detail,
+                    documentation: item.documentation.map(|value| {
+                        Documentation::MarkupContent(MarkupContent {
+                            kind: MarkupKind::Markdown,
+                            value,
+                        })
+                    }),
+                    ..Default::default()
+                })
+                .collect(),
+        ))
+    }
+
+    pub fn handle_request(&mut self, request: Request) -> Response {
+        let id = request.id.clone();
+        let method = request.method.clone();
+        let result = match method.as_str() {
+            <GotoDefinition as lsp_types::request::Request>::METHOD => {
+                self.dispatch::<GotoDefinition>(request, Self::goto_definition)
+            }
+            <HoverRequest as lsp_types::request::Request>::METHOD => {
+                self.dispatch::<HoverRequest>(request, Self::hover)
+            }
+            <Completion as lsp_types::request::Request>::METHOD => {
+                self.dispatch::<Completion>(request, Self::completion)
+            }
+            method => {
+                return Response::new_err(
+                    id,
+                    ErrorCode::MethodNotFound as i32,
+                    format!("Unsupported request: {}", method),
+                )
+            }
+        };
+
+        match result {
+            Ok(result) => Response::new_ok(id, result),
+            Err(err) => {
+                Response::new_err(id, ErrorCode::InvalidParams as i32, format!("{:#}", err))
+            }
+        }
+    }
+
+    fn dispatch<R: lsp_types::request::Request>(
+        &mut self,
+        request: Request,
+        handler: fn(&mut Self, R::Params) -> R::Result,
+    ) -> Result<serde_json::Value> {
+        let params = serde_json::from_value(request.params)?;
+        Ok(serde_json::to_value(handler(self, params))?)
+    }
+
+    /// Handles a notification, returns `true` if the diagnostics need to be re-published
+    pub fn handle_notification(&mut self, notification: Notification) -> bool {
+        let method = notification.method.clone();
+        match method.as_str() {
+            <DidOpenTextDocument as lsp_types::notification::Notification>::METHOD => {
+                let Some(params) = extract::<DidOpenTextDocument>(notification) else {
+                    return false;
+                };
+                let document = params.text_document;
+                self.open(document.uri, document.version, document.text);
+            }
+            <DidChangeTextDocument as lsp_types::notification::Notification>::METHOD => {
+                let Some(mut params) = extract::<DidChangeTextDocument>(notification) else {
+                    return false;
+                };
+                // we request full sync, so the last change contains the whole text
+                let Some(change) = params.content_changes.pop() else {
+                    return false;
+                };
+                self.change(
+                    &params.text_document.uri,
+                    params.text_document.version,
+                    change.text,
+                );
+            }
+            <DidCloseTextDocument as lsp_types::notification::Notification>::METHOD => {
+                let Some(params) = extract::<DidCloseTextDocument>(notification) else {
+                    return false;
+                };
+                self.close(&params.text_document.uri);
+            }
+            _ => return false,
+        }
+
+        true
+    }
+}
+
+fn extract<N: lsp_types::notification::Notification>(
+    notification: Notification,
+) -> Option<N::Params> {
+    let method = notification.method.clone();
+    match serde_json::from_value(notification.params) {
+        Ok(params) => Some(params),
+        Err(err) => {
+            warn!("Invalid parameters for {}: {}", method, err);
+            None
+        }
+    }
+}
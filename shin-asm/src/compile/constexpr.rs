@@ -34,17 +34,16 @@ impl std::fmt::Debug for ConstexprValue {
     }
 }
 
+#[derive(Clone)]
 pub enum ConstexprContextValue {
     Value(LowerResult<ConstexprValue>, Option<Span>),
     // This only exists to make the constexpr evaluator know that the value exists, but is of wrong type
     // we can't really meaningfully use a block reference in a constexpr expression, so there's no value associated with it
-    Block(Span),
+    Block(Option<Span>),
 }
 
-// NOTE: potentially we might want to add support for evaluating expressions in instruction arguments
-// however, it would require some design work to make sure it is sane (for example, we might want to do const fold instead of evaluating, think of `$v1 + 2 * 8`)
-// thus, for now, constexpr evaluation only happens in value alias context
-// (these different contexts will require some adapters)
+// Constexpr evaluation happens in two contexts: value aliases (`def A = ...`) and operator expressions in instruction arguments.
+// The latter are folded into constants as a whole, so registers can't participate in them (think of `$v1 + 2 * 8`): it would require emitting code.
 pub type ConstexprContext = FxHashMap<Name, ConstexprContextValue>;
 
 fn type_mismatch(
@@ -87,15 +86,25 @@ fn evaluate(ctx: &mut EvaluateContext, expr: hir::ExprId) -> LowerResult<Constex
         Expr::Literal(hir::Literal::String(_)) => {
             ctx.error(type_mismatch(Either::Left(expr), "int or float", "string"))
         }
-        Expr::NameRef(ref name) => match ctx.context[name] {
-            ConstexprContextValue::Value(ref value, _) => value.clone(),
-            ConstexprContextValue::Block(location) => ctx.error(
-                type_mismatch(Either::Left(expr), "int or float", "code reference")
-                    .with_additional_label(
+        Expr::NameRef(ref name) => match ctx.context.get(name) {
+            None => ctx.error(make_diagnostic!(
+                Either::Left(expr),
+                "Could not find the definition of `{}`",
+                name
+            )),
+            Some(ConstexprContextValue::Value(value, _)) => value.clone(),
+            Some(&ConstexprContextValue::Block(location)) => {
+                let diagnostic =
+                    type_mismatch(Either::Left(expr), "int or float", "code reference");
+                let diagnostic = match location {
+                    Some(location) => diagnostic.with_additional_label(
                         "Code reference defined at".to_string(),
                         Either::Right(location),
                     ),
-            ),
+                    None => diagnostic,
+                };
+                ctx.error(diagnostic)
+            }
         },
         Expr::RegisterRef(_) => ctx.error(make_diagnostic!(
             Either::Left(expr),
@@ -134,13 +143,33 @@ fn evaluate(ctx: &mut EvaluateContext, expr: hir::ExprId) -> LowerResult<Constex
                 ast::BinaryOp::Add => lhs.checked_add(rhs),
                 ast::BinaryOp::Subtract => lhs.checked_sub(rhs),
                 ast::BinaryOp::Multiply => lhs.checked_mul(rhs),
-                ast::BinaryOp::Divide => {
-                    if rhs == 0 {
-                        return ctx.error(make_diagnostic!(Either::Left(expr), "Division by zero"));
-                    }
-                    lhs.checked_div(rhs)
+                ast::BinaryOp::Divide | ast::BinaryOp::Modulo | ast::BinaryOp::DivideReal
+                    if rhs == 0 =>
+                {
+                    return ctx.error(make_diagnostic!(Either::Left(expr), "Division by zero"));
+                }
+                ast::BinaryOp::Divide => lhs.checked_div(rhs),
+                ast::BinaryOp::Modulo => lhs.checked_rem(rhs),
+                // real numbers are fixed-point with 3 decimal digits
+                ast::BinaryOp::MultiplyReal => i32::try_from(lhs as i64 * rhs as i64 / 1000).ok(),
+                ast::BinaryOp::DivideReal => i32::try_from(lhs as i64 * 1000 / rhs as i64).ok(),
+                ast::BinaryOp::BitwiseAnd => Some(lhs & rhs),
+                ast::BinaryOp::BitwiseOr => Some(lhs | rhs),
+                ast::BinaryOp::BitwiseXor => Some(lhs ^ rhs),
+                ast::BinaryOp::ShiftLeft => {
+                    u32::try_from(rhs).ok().and_then(|rhs| lhs.checked_shl(rhs))
+                }
+                ast::BinaryOp::ShiftRight => {
+                    u32::try_from(rhs).ok().and_then(|rhs| lhs.checked_shr(rhs))
                 }
-                op => todo!("constexpr evaluation of {:?}", op),
+                ast::BinaryOp::LogicalAnd => Some((lhs != 0 && rhs != 0) as i32),
+                ast::BinaryOp::LogicalOr => Some((lhs != 0 || rhs != 0) as i32),
+                ast::BinaryOp::Equal => Some((lhs == rhs) as i32),
+                ast::BinaryOp::NotEqual => Some((lhs != rhs) as i32),
+                ast::BinaryOp::LessThan => Some((lhs < rhs) as i32),
+                ast::BinaryOp::LessThanOrEqual => Some((lhs <= rhs) as i32),
+                ast::BinaryOp::GreaterThan => Some((lhs > rhs) as i32),
+                ast::BinaryOp::GreaterThanOrEqual => Some((lhs >= rhs) as i32),
             };
 
             match result {
@@ -151,9 +180,10 @@ fn evaluate(ctx: &mut EvaluateContext, expr: hir::ExprId) -> LowerResult<Constex
                 )),
             }
         }
-        Expr::Call { .. } => {
-            todo!()
-        }
+        Expr::Call { .. } => ctx.error(make_diagnostic!(
+            Either::Left(expr),
+            "Function calls cannot be used in const context"
+        )),
     }
 }
 
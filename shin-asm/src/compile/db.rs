@@ -6,7 +6,9 @@ use crate::compile::generate_snr;
 pub struct Jar(
     file::File,
     file::File_emit_diagnostics,
+    file::File_includes,
     file::Program,
+    file::check_includes,
     types::SalsaBlockIdWithFile,
     diagnostics::SourceDiagnosticAccumulator,
     diagnostics::HirDiagnosticAccumulator,
@@ -15,6 +17,7 @@ pub struct Jar(
     def_map::DefMap_global_register,
     def_map::DefMap_local_register,
     def_map::DefMap_resolve_item,
+    def_map::DefMap_resolve_macro,
     def_map::build_def_map,
     hir::HirBlockBodies,
     hir::HirBlockBodies_get_block,
@@ -23,6 +26,9 @@ pub struct Jar(
     hir::HirBlockBodySourceMaps_get_block,
     hir::collect_file_bodies_with_source_maps,
     hir::collect_file_bodies,
+    hir::macros::HirMacros,
+    hir::macros::HirMacros_get_macro,
+    hir::macros::collect_file_macros,
     hir::lower::lower_block,
     hir::lower::lower_file,
     hir::lower::LoweredFile,
@@ -194,9 +194,7 @@ pub fn resolve_item_defs(db: &dyn Db, def_map: &UnresolvedItems) -> ResolvedItem
                                     let (value, span) = self.resolve(name.clone(), Some(span));
 
                                     let value = match value {
-                                        DefValue::Block(_) => ConstexprContextValue::Block(
-                                            span.expect("BUG: block value without span"),
-                                        ),
+                                        DefValue::Block(_) => ConstexprContextValue::Block(span),
                                         DefValue::Value(value) => {
                                             ConstexprContextValue::Value(value, span)
                                         }
@@ -0,0 +1,73 @@
+use std::collections::hash_map::Entry;
+
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::{
+    compile::{
+        def_map::Name, diagnostics::Span, hir::lower::instruction_names, make_diagnostic, Db, File,
+        MakeWithFile, Program, WithFile,
+    },
+    syntax::{
+        ast::{self, visit, visit::ItemIndex},
+        AstSpanned, AstToken,
+    },
+};
+
+pub type MacroDefs = FxHashMap<Name, WithFile<ItemIndex>>;
+
+pub fn collect_macro_defs(db: &dyn Db, program: Program) -> MacroDefs {
+    struct MacroCollector<'a> {
+        db: &'a dyn Db,
+        instruction_names: FxHashSet<&'static str>,
+        macros: FxHashMap<Name, (WithFile<ItemIndex>, Span)>,
+    }
+
+    impl visit::Visitor for MacroCollector<'_> {
+        fn visit_macro_definition(
+            &mut self,
+            file: File,
+            item_index: ItemIndex,
+            macro_def: ast::MacroDefinition,
+        ) {
+            let Some(name_token) = macro_def.name().and_then(|v| v.token()) else {
+                return;
+            };
+            let span = name_token.span(file);
+            let name = Name(name_token.text().into());
+
+            // instructions always take precedence, so such a macro could never be invoked
+            if self.instruction_names.contains(name_token.text()) {
+                return make_diagnostic!(
+                    span,
+                    "Macro `{}` has the same name as an instruction",
+                    name
+                )
+                .emit(self.db);
+            }
+
+            match self.macros.entry(name) {
+                Entry::Occupied(o) => {
+                    make_diagnostic!(span, "Duplicate definition of macro `{}`", o.key())
+                        .with_additional_label("Previously defined here".to_string(), o.get().1)
+                        .emit(self.db);
+                }
+                Entry::Vacant(v) => {
+                    v.insert((item_index.in_file(file), span));
+                }
+            }
+        }
+    }
+
+    let mut visitor = MacroCollector {
+        db,
+        instruction_names: instruction_names().into_iter().collect(),
+        macros: FxHashMap::default(),
+    };
+    visit::visit_program(&mut visitor, db, program);
+
+    visitor
+        .macros
+        .into_iter()
+        .map(|(name, (item, _))| (name, item))
+        .collect()
+}
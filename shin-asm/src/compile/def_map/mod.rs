@@ -1,10 +1,12 @@
 mod collect;
 mod items;
+mod macros;
 mod registers;
 
 use std::{borrow::Cow, fmt::Display};
 
 pub use items::{DefValue, ResolvedItems};
+pub use macros::MacroDefs;
 pub use registers::{LocalRegisters, ResolvedGlobalRegisters};
 use rustc_hash::FxHashMap;
 use shin_core::format::scenario::instruction_elements::Register;
@@ -55,6 +57,8 @@ pub struct DefMap {
     local_registers: LocalRegisters,
     #[return_ref]
     block_names: FxHashMap<BlockIdWithFile, BlockName>,
+    #[return_ref]
+    macros: MacroDefs,
 }
 
 impl DefMap {
@@ -116,6 +120,12 @@ impl DefMap {
         self.items(db).get(&name).cloned()
     }
 
+    /// Finds the item that defines the macro with the specified name
+    #[salsa::tracked]
+    pub fn resolve_macro(self, db: &dyn Db, name: Name) -> Option<WithFile<ItemIndex>> {
+        self.macros(db).get(&name).copied()
+    }
+
     pub fn debug_dump(self, db: &dyn Db) -> String {
         use std::fmt::Write as _;
 
@@ -170,6 +180,21 @@ impl DefMap {
             writeln!(output, "  {:?} @ {}: {:?}", block_id, file_name, name).unwrap();
         }
 
+        let mut macros = self.macros(db).iter().collect::<Vec<_>>();
+        macros.sort_by_key(|&(name, _)| name);
+
+        writeln!(output, "macros:").unwrap();
+        for (name, item_index) in macros {
+            writeln!(
+                output,
+                "  {}: item {}@{}",
+                name,
+                item_index.value,
+                item_index.file.path(db)
+            )
+            .unwrap();
+        }
+
         output
     }
 }
@@ -183,8 +208,16 @@ pub fn build_def_map(db: &dyn Db, program: Program) -> DefMap {
     let global_registers = registers::collect_global_registers(db, program);
     let global_registers = registers::resolve_global_registers(db, &global_registers);
     let block_names = collect::collect_block_names(db, program);
-
-    DefMap::new(db, items, global_registers, local_registers, block_names)
+    let macros = macros::collect_macro_defs(db, program);
+
+    DefMap::new(
+        db,
+        items,
+        global_registers,
+        local_registers,
+        block_names,
+        macros,
+    )
 }
 
 #[cfg(test)]
@@ -265,6 +298,7 @@ LABEL2:
               BlockId { item_index: 5, block_index: Some(0) } @ test.sal: GlobalBlock(None)
               BlockId { item_index: 5, block_index: Some(1) } @ test.sal: GlobalBlock(Some(Name("LABEL1")))
               BlockId { item_index: 5, block_index: Some(2) } @ test.sal: GlobalBlock(Some(Name("LABEL2")))
+            macros:
         "#]].assert_eq(&def_map.debug_dump(&db));
     }
 
@@ -279,7 +313,7 @@ def $b = $a
 
         expect![[r#"
             building def map produced errors:
-            source-level: [Diagnostic { message: "Encountered a loop while resolving register $b", location: Span(WithFile { value: 10..12, file: File(Id { value: 1 }) }), additional_labels: [] }]
+            source-level: [Diagnostic { message: "Encountered a loop while resolving register $b", location: Span(WithFile { value: 10..12, file: File(Id { value: 1 }) }), additional_labels: [], severity: Error, notes: [] }]
             hir-level: []"#]]
             .assert_eq(errors.as_deref().unwrap());
 
@@ -291,6 +325,7 @@ def $b = $a
                 b: [ERROR]
               local:
             block names:
+            macros:
         "#]]
         .assert_eq(&def_map.debug_dump(&db));
     }
@@ -305,7 +340,7 @@ def A = 65536 * 65536
 
         expect![[r#"
             building def map produced errors:
-            source-level: [Diagnostic { message: "Overflow in constant expression", location: Span(WithFile { value: 9..22, file: File(Id { value: 1 }) }), additional_labels: [] }]
+            source-level: [Diagnostic { message: "Overflow in constant expression", location: Span(WithFile { value: 9..22, file: File(Id { value: 1 }) }), additional_labels: [], severity: Error, notes: [] }]
             hir-level: []"#]]
         .assert_eq(errors.as_deref().unwrap());
 
@@ -316,6 +351,37 @@ def A = 65536 * 65536
               global:
               local:
             block names:
+            macros:
+        "#]]
+        .assert_eq(&def_map.debug_dump(&db));
+    }
+
+    #[test]
+    fn macro_defs() {
+        let (db, def_map, errors) = parse_def_map(
+            r#"
+macro POSE($layer, pose)
+    LAYERCTRL $layer, 1, pose
+endmacro
+macro abs
+endmacro
+        "#,
+        );
+
+        expect![[r#"
+            building def map produced errors:
+            source-level: [Diagnostic { message: "Macro `abs` has the same name as an instruction", location: Span(WithFile { value: 71..74, file: File(Id { value: 1 }) }), additional_labels: [], severity: Error, notes: [] }]
+            hir-level: []"#]]
+        .assert_eq(errors.as_deref().unwrap());
+
+        expect![[r#"
+            items:
+            registers:
+              global:
+              local:
+            block names:
+            macros:
+              POSE: item #0@test.sal
         "#]]
         .assert_eq(&def_map.debug_dump(&db));
     }
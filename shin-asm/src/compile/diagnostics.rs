@@ -25,6 +25,10 @@ impl Span {
         self.0.file
     }
 
+    pub fn range(&self) -> TextRange {
+        self.0.value
+    }
+
     pub fn to_char_span(&self, db: &dyn Db) -> CharSpan {
         let file = self.file();
         let char_map = char_map(db, file);
@@ -98,11 +102,30 @@ impl DiagnosticLocation for HirLocation {
     }
 }
 
+/// How bad the diagnosed problem is. Only the errors prevent the program from being compiled
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl Severity {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Diagnostic<L> {
     pub message: String,
     pub location: L,
     pub additional_labels: Vec<(String, L)>,
+    pub severity: Severity,
+    /// Free-form explanations shown after the source snippet, not tied to any location
+    pub notes: Vec<String>,
 }
 
 impl<L> Diagnostic<L> {
@@ -111,6 +134,8 @@ impl<L> Diagnostic<L> {
             message,
             location,
             additional_labels: Vec::new(),
+            severity: Severity::Error,
+            notes: Vec::new(),
         }
     }
 
@@ -119,6 +144,20 @@ impl<L> Diagnostic<L> {
         self
     }
 
+    pub fn with_severity(mut self, severity: Severity) -> Self {
+        self.severity = severity;
+        self
+    }
+
+    pub fn with_note(mut self, note: String) -> Self {
+        self.notes.push(note);
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
     pub fn map_location<NewL, F: Fn(L) -> NewL>(self, f: F) -> Diagnostic<NewL> {
         Diagnostic {
             message: self.message,
@@ -128,6 +167,8 @@ impl<L> Diagnostic<L> {
                 .into_iter()
                 .map(|(m, l)| (m, f(l)))
                 .collect(),
+            severity: self.severity,
+            notes: self.notes,
         }
     }
 }
@@ -155,7 +196,7 @@ impl Diagnostic<TextRange> {
 
 impl Diagnostic<Span> {
     pub fn into_ariadne(self, db: &dyn Db) -> ariadne::Report<'static, CharSpan> {
-        lower_diagnostic_into_ariadne(db, self)
+        lower_diagnostic_into_ariadne(db, self, ariadne::Config::default())
     }
 }
 
@@ -178,18 +219,24 @@ impl Diagnostic<HirLocation> {
     }
 
     pub fn into_ariadne(self, db: &dyn Db) -> ariadne::Report<'static, CharSpan> {
-        lower_diagnostic_into_ariadne(db, self)
+        lower_diagnostic_into_ariadne(db, self, ariadne::Config::default())
     }
 }
 
 fn lower_diagnostic_into_ariadne<L: DiagnosticLocation>(
     db: &dyn Db,
     diagnostic: Diagnostic<L>,
+    config: ariadne::Config,
 ) -> ariadne::Report<'static, CharSpan> {
     let span = diagnostic.location.span(db).to_char_span(db);
+    let kind = match diagnostic.severity {
+        Severity::Warning => ariadne::ReportKind::Warning,
+        Severity::Error => ariadne::ReportKind::Error,
+    };
 
-    ariadne::Report::build(ariadne::ReportKind::Error, *span.source(), span.start())
+    let mut report = ariadne::Report::build(kind, *span.source(), span.start())
         .with_message(diagnostic.message)
+        .with_config(config)
         .with_label(ariadne::Label::new(span))
         .with_labels(
             diagnostic
@@ -199,8 +246,31 @@ fn lower_diagnostic_into_ariadne<L: DiagnosticLocation>(
                     let span = location.span(db).to_char_span(db);
                     ariadne::Label::new(span).with_message(message)
                 }),
-        )
-        .finish()
+        );
+    // ariadne can only show a single note
+    if !diagnostic.notes.is_empty() {
+        report = report.with_note(diagnostic.notes.join("\n"));
+    }
+
+    report.finish()
+}
+
+/// Renders the diagnostics as annotated source snippets, like the ones printed by `rustc`
+pub fn render_diagnostics(
+    db: &dyn Db,
+    diagnostics: impl IntoIterator<Item = Diagnostic<Span>>,
+    color: bool,
+) -> String {
+    let mut cache = AriadneDbCache::new(db);
+    let mut output = Vec::new();
+    for diagnostic in diagnostics {
+        let config = ariadne::Config::default().with_color(color);
+        lower_diagnostic_into_ariadne(db, diagnostic, config)
+            .write(&mut cache, &mut output)
+            .expect("writing to a Vec can't fail");
+    }
+
+    String::from_utf8(output).expect("ariadne produced invalid UTF-8")
 }
 
 pub struct AriadneDbCache<'db> {
@@ -1,5 +1,12 @@
+use std::path::{Component, Path, PathBuf};
+
+use rustc_hash::FxHashSet;
+
 use super::db::Db;
-use crate::syntax;
+use crate::{
+    compile::{diagnostics::Span, make_diagnostic},
+    syntax::{self, ast, AstSpanned},
+};
 
 #[salsa::input]
 pub struct File {
@@ -32,6 +39,57 @@ impl File {
 
         parse.debug_dump()
     }
+
+    /// Lists the files included with the `include` directive
+    #[salsa::tracked]
+    pub fn includes(self, db: &dyn Db) -> Vec<Include> {
+        let mut includes = Vec::new();
+        for item in self.parse(db).items() {
+            let ast::Item::IncludeDirective(include) = item else {
+                continue;
+            };
+            let Some(path) = include.path() else {
+                continue;
+            };
+
+            match path.value() {
+                Ok(value) => includes.push(Include {
+                    path: resolve_include_path(&self.path(db), &value),
+                    span: include.span(self),
+                }),
+                Err(e) => e.in_file(self).emit(db),
+            }
+        }
+        includes
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Include {
+    /// Path of the included file, relative to the including file's directory
+    pub path: String,
+    /// Span of the `include` directive
+    pub span: Span,
+}
+
+/// Resolves the path of an included file, normalizing it so that the same file included from different places gets the same path
+fn resolve_include_path(including_file: &str, include: &str) -> String {
+    let base = Path::new(including_file).parent().unwrap_or(Path::new(""));
+
+    let mut result = PathBuf::new();
+    for component in base.join(include).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(result.components().next_back(), Some(Component::Normal(_))) =>
+            {
+                result.pop();
+            }
+            component => result.push(component),
+        }
+    }
+
+    result.to_string_lossy().into_owned()
 }
 
 #[salsa::input]
@@ -42,6 +100,36 @@ pub struct Program {
 }
 
 impl Program {
+    /// Creates a program from the `roots`, adding all the files they include (transitively).
+    ///
+    /// The included files are read with `load`. If it returns `None`, the file is left out and the `include` directive is reported as an error.
+    pub fn load_with_includes(
+        db: &dyn Db,
+        roots: Vec<File>,
+        mut load: impl FnMut(&str) -> Option<String>,
+    ) -> Program {
+        let mut known_paths = roots
+            .iter()
+            .map(|file| file.path(db))
+            .collect::<FxHashSet<_>>();
+
+        let mut files = roots;
+        let mut index = 0;
+        while index < files.len() {
+            for include in files[index].includes(db) {
+                if !known_paths.insert(include.path.clone()) {
+                    continue;
+                }
+                if let Some(contents) = load(&include.path) {
+                    files.push(File::new(db, include.path, contents));
+                }
+            }
+            index += 1;
+        }
+
+        Program::new(db, files)
+    }
+
     pub fn parse_files(self, db: &dyn Db) -> impl Iterator<Item = (File, syntax::SourceFile)> + '_ {
         self.files(db)
             .iter()
@@ -49,3 +137,26 @@ impl Program {
             .map(|file| (file, file.parse(db)))
     }
 }
+
+/// Reports the `include` directives referring to files that are not part of the program
+#[salsa::tracked]
+pub fn check_includes(db: &dyn Db, program: Program) {
+    let paths = program
+        .files(db)
+        .iter()
+        .map(|file| file.path(db))
+        .collect::<FxHashSet<_>>();
+
+    for &file in program.files(db) {
+        for include in file.includes(db) {
+            if !paths.contains(&include.path) {
+                make_diagnostic!(
+                    include.span,
+                    "Included file `{}` is not part of the program",
+                    include.path
+                )
+                .emit(db);
+            }
+        }
+    }
+}
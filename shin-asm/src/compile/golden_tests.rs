@@ -0,0 +1,156 @@
+//! Round-trip tests over the scenarios of the real games.
+//!
+//! The game files can't be distributed, so these tests only run when `SHIN_ASM_CORPUS` points to a directory with `.snr` files (searched recursively).
+//! Without it they pass without checking anything.
+//!
+//! Each scenario is decoded into instructions, which are encoded back the same way [`generate_snr`](super::generate_snr::generate_snr) does it.
+//! The result is compared with the original byte for byte, and the instructions that encode differently are decoded again to tell the harmless differences (a non-canonical encoding of the same instruction) from the real ones.
+//!
+//! There is no decompiler producing `.sal` sources yet. When there is, the sources it produces should be assembled here as well, closing the loop through the text form.
+
+use std::{
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+
+use binrw::{BinRead, BinWrite};
+use bytes::Bytes;
+use shin_core::format::scenario::{
+    instruction_elements::CodeAddress, instructions::Instruction, Scenario,
+};
+
+const CORPUS_VAR: &str = "SHIN_ASM_CORPUS";
+
+fn collect_scenarios(dir: &Path, result: &mut Vec<PathBuf>) {
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            collect_scenarios(&path, result);
+        } else if path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("snr"))
+        {
+            result.push(path);
+        }
+    }
+}
+
+fn corpus() -> Vec<PathBuf> {
+    let Some(dir) = std::env::var_os(CORPUS_VAR) else {
+        eprintln!("{} is not set, skipping the golden tests", CORPUS_VAR);
+        return Vec::new();
+    };
+
+    let mut result = Vec::new();
+    collect_scenarios(Path::new(&dir), &mut result);
+    result.sort();
+    assert!(
+        !result.is_empty(),
+        "{} has no .snr files",
+        PathBuf::from(dir).display()
+    );
+    result
+}
+
+/// The problems found in a single scenario, as human-readable lines
+#[derive(Default)]
+struct RoundTripReport {
+    instructions: usize,
+    /// Encoded to different bytes, but decoded back to the same instruction
+    non_canonical: Vec<String>,
+    /// Encoded to something that means a different instruction
+    mismatches: Vec<String>,
+}
+
+fn round_trip(scenario: &Scenario) -> RoundTripReport {
+    let mut report = RoundTripReport::default();
+    let raw = scenario.raw();
+    let end = scenario.code_end();
+
+    let mut reader = scenario.instruction_reader(scenario.entrypoint_address());
+    while reader.position() < end {
+        let position = reader.position();
+        let instruction = reader
+            .read()
+            .unwrap_or_else(|e| panic!("Decoding the instruction at {}: {:?}", position, e));
+        let original = &raw[position.0 as usize..reader.position().0 as usize];
+        report.instructions += 1;
+
+        let mut encoded = Cursor::new(Vec::new());
+        instruction.write(&mut encoded).unwrap();
+        let encoded = encoded.into_inner();
+        if encoded == original {
+            continue;
+        }
+
+        let description = format!(
+            "{} {:?}\n  original: {:02x?}\n  encoded:  {:02x?}",
+            position, instruction, original, encoded
+        );
+        match Instruction::read(&mut Cursor::new(&encoded)) {
+            Ok(decoded) if decoded == instruction => report.non_canonical.push(description),
+            _ => report.mismatches.push(description),
+        }
+    }
+
+    report
+}
+
+#[test]
+fn instruction_round_trip() {
+    let mut failed = Vec::new();
+    for path in corpus() {
+        let data = Bytes::from(std::fs::read(&path).unwrap());
+        let scenario =
+            Scenario::new(data).unwrap_or_else(|e| panic!("Parsing {}: {:?}", path.display(), e));
+
+        let report = round_trip(&scenario);
+        eprintln!(
+            "{}: {} instructions, {} encoded differently, {} mismatched",
+            path.display(),
+            report.instructions,
+            report.non_canonical.len(),
+            report.mismatches.len()
+        );
+        for line in report.non_canonical.iter().take(5) {
+            eprintln!("  non-canonical: {}", line);
+        }
+        for line in report.mismatches.iter().take(20) {
+            eprintln!("  MISMATCH: {}", line);
+        }
+
+        if !report.mismatches.is_empty() {
+            failed.push(path);
+        }
+    }
+
+    assert!(
+        failed.is_empty(),
+        "The instructions didn't survive the round trip in {:?}",
+        failed
+    );
+}
+
+#[test]
+fn code_layout_round_trip() {
+    // the code re-encoded as a whole must have the same size, otherwise the jump targets would be off
+    for path in corpus() {
+        let data = Bytes::from(std::fs::read(&path).unwrap());
+        let scenario = Scenario::new(data).unwrap();
+        let end = scenario.code_end();
+
+        let mut reader = scenario.instruction_reader(scenario.entrypoint_address());
+        let mut encoded = Cursor::new(Vec::new());
+        while reader.position() < end {
+            reader.read().unwrap().write(&mut encoded).unwrap();
+        }
+
+        let start = scenario.entrypoint_address();
+        assert_eq!(
+            CodeAddress(start.0 + encoded.into_inner().len() as u32),
+            reader.position(),
+            "The re-encoded code of {} has a different size",
+            path.display()
+        );
+    }
+}
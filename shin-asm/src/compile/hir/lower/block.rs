@@ -81,11 +81,7 @@ impl LoweredBlock {
         };
 
         for (instr, _) in block.instructions.iter() {
-            instructions.push(super::instruction::instruction_from_hir(
-                &mut collectors,
-                &ctx,
-                instr,
-            ));
+            super::expand::lower_instruction(&mut collectors, &ctx, instr, &mut instructions);
         }
 
         Self {
@@ -5,10 +5,55 @@ use shin_core::format::scenario::instruction_elements::{NumberSpec, UntypedNumbe
 
 use super::prelude::*;
 use crate::compile::{
-    constexpr::ConstexprValue,
+    constexpr::{constexpr_evaluate, ConstexprContext, ConstexprContextValue, ConstexprValue},
     hir::lower::{LowerError, LowerResult},
 };
 
+fn collect_constexpr_context(ctx: &FromHirBlockCtx, expr: ExprId, context: &mut ConstexprContext) {
+    match *ctx.expr(expr) {
+        hir::Expr::NameRef(ref name) => {
+            if let Some(value) = ctx.resolve_item(name) {
+                let value = match value {
+                    DefValue::Block(_) => ConstexprContextValue::Block(None),
+                    DefValue::Value(value) => ConstexprContextValue::Value(value, None),
+                };
+                context.insert(name.clone(), value);
+            }
+        }
+        hir::Expr::UnaryOp { expr, .. } => collect_constexpr_context(ctx, expr, context),
+        hir::Expr::BinaryOp { lhs, rhs, .. } => {
+            collect_constexpr_context(ctx, lhs, context);
+            collect_constexpr_context(ctx, rhs, context);
+        }
+        _ => {}
+    }
+}
+
+/// Folds an operator expression into a constant at assembly time
+fn const_fold(
+    collectors: &mut FromHirCollectors,
+    ctx: &FromHirBlockCtx,
+    expr: ExprId,
+) -> LowerResult<ConstexprValue> {
+    let mut context = ConstexprContext::default();
+    collect_constexpr_context(ctx, expr, &mut context);
+
+    let (value, diagnostics) = constexpr_evaluate(&context, ctx.block, expr);
+
+    for diagnostic in diagnostics {
+        // hir diagnostics can only point into the block, so the labels pointing to the definitions are dropped
+        let location = diagnostic
+            .location
+            .left()
+            .expect("BUG: constexpr diagnostic is not located at an expression");
+        collectors
+            .diagnostics
+            .emit(location.into(), diagnostic.message);
+    }
+
+    value
+}
+
 fn try_lit_i32(
     collectors: &mut FromHirCollectors,
     ctx: &FromHirBlockCtx,
@@ -19,18 +64,9 @@ fn try_lit_i32(
         hir::Expr::Literal(hir::Literal::RationalNumber(lit)) => {
             Some(Ok(ConstexprValue::constant(lit.into_raw())))
         }
-        hir::Expr::UnaryOp {
-            op: ast::UnaryOp::Negate,
-            expr,
-        } => match *ctx.expr(expr) {
-            hir::Expr::Literal(hir::Literal::IntNumber(lit)) => {
-                Some(Ok(ConstexprValue::constant(-lit)))
-            }
-            hir::Expr::Literal(hir::Literal::RationalNumber(lit)) => {
-                Some(Ok(ConstexprValue::constant(-lit.into_raw())))
-            }
-            _ => None,
-        },
+        hir::Expr::UnaryOp { .. } | hir::Expr::BinaryOp { .. } => {
+            Some(const_fold(collectors, ctx, expr))
+        }
         hir::Expr::NameRef(ref name) => match ctx.resolve_item(name) {
             None => Some(collectors.emit_diagnostic(
                 expr.into(),
@@ -155,4 +191,22 @@ mod tests {
             ],
         );
     }
+
+    #[test]
+    fn number_spec_const_fold() {
+        check_from_hir_ok::<NumberSpec>(
+            indoc! {r"
+            def BASE_ALPHA = 100
+            def FLAG = 4
+
+            HELLO (BASE_ALPHA + 100), -BASE_ALPHA * 2, 1 << FLAG, 1.5 .* 2.0
+        "},
+            &[
+                NumberSpec::constant(200),
+                NumberSpec::constant(-200),
+                NumberSpec::constant(16),
+                NumberSpec::constant(3_000),
+            ],
+        );
+    }
 }
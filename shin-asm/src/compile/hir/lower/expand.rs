@@ -0,0 +1,175 @@
+//! Macro expansion.
+//!
+//! A macro invocation is expanded by copying the macro body into a fresh hir block,
+//! with the parameter references replaced by the invocation arguments, and lowering it in place of the invocation.
+//!
+//! The expansion is not hygienic: all the other names in the body are resolved at the invocation site.
+
+use la_arena::Arena;
+use rustc_hash::FxHashMap;
+use shin_core::format::scenario::instructions::Instruction;
+
+use crate::{
+    compile::{
+        def_map::{Name, RegisterName},
+        hir::{
+            self,
+            lower::{
+                from_hir::{FromHirBlockCtx, FromHirCollectors},
+                LowerResult,
+            },
+            HirBlockBody, HirMacro, MacroParam,
+        },
+    },
+    syntax::ast,
+};
+
+/// Lowers an instruction into the `output`, expanding it if it is a macro invocation
+pub fn lower_instruction(
+    collectors: &mut FromHirCollectors,
+    ctx: &FromHirBlockCtx,
+    instr: hir::InstructionId,
+    output: &mut Vec<LowerResult<Instruction>>,
+) {
+    let hir_macro = ctx
+        .instr(instr)
+        .name
+        .as_ref()
+        .and_then(|name| ctx.resolve_macro(&Name(name.clone())));
+
+    let Some(hir_macro) = hir_macro else {
+        output.push(super::instruction::instruction_from_hir(
+            collectors, ctx, instr,
+        ));
+        return;
+    };
+
+    if collectors.diagnostics.is_expanding_macro(&hir_macro.name) {
+        output.push(collectors.emit_diagnostic(
+            instr.into(),
+            format!("Recursive invocation of macro `{}`", hir_macro.name),
+        ));
+        return;
+    }
+
+    let args = &ctx.instr(instr).args;
+    if args.len() != hir_macro.params.len() {
+        output.push(collectors.emit_diagnostic(
+            instr.into(),
+            format!(
+                "Macro `{}` expects {} arguments, but {} were given",
+                hir_macro.name,
+                hir_macro.params.len(),
+                args.len()
+            ),
+        ));
+        return;
+    }
+
+    let expanded = expand_macro(ctx.block, args, &hir_macro);
+    let expanded_ctx = FromHirBlockCtx {
+        resolve_ctx: ctx.resolve_ctx,
+        block: &expanded,
+    };
+
+    collectors
+        .diagnostics
+        .enter_macro(instr.into(), hir_macro.name.clone());
+    for (instr, _) in expanded.instructions.iter() {
+        lower_instruction(collectors, &expanded_ctx, instr, output);
+    }
+    collectors.diagnostics.exit_macro();
+}
+
+/// Copies the macro body into a new block, substituting the parameters with the arguments (which live in the `invocation_block`)
+fn expand_macro(
+    invocation_block: &HirBlockBody,
+    args: &[hir::ExprId],
+    hir_macro: &HirMacro,
+) -> HirBlockBody {
+    let mut exprs = Arena::default();
+    let mut instructions = Arena::default();
+
+    let mut substitutions = FxHashMap::default();
+    for (param, &arg) in hir_macro.params.iter().zip(args) {
+        let arg = ExprCopier {
+            source: &invocation_block.exprs,
+            destination: &mut exprs,
+            substitutions: &FxHashMap::default(),
+        }
+        .copy(arg);
+        substitutions.insert(param.clone(), arg);
+    }
+
+    let mut copier = ExprCopier {
+        source: &hir_macro.body.exprs,
+        destination: &mut exprs,
+        substitutions: &substitutions,
+    };
+    for (_, instr) in hir_macro.body.instructions.iter() {
+        let args = instr.args.iter().map(|&arg| copier.copy(arg)).collect();
+        instructions.alloc(hir::Instruction {
+            name: instr.name.clone(),
+            args,
+        });
+    }
+
+    HirBlockBody {
+        exprs,
+        instructions,
+    }
+}
+
+struct ExprCopier<'a> {
+    source: &'a Arena<hir::Expr>,
+    destination: &'a mut Arena<hir::Expr>,
+    substitutions: &'a FxHashMap<MacroParam, hir::ExprId>,
+}
+
+impl ExprCopier<'_> {
+    fn copy(&mut self, id: hir::ExprId) -> hir::ExprId {
+        let source = self.source;
+        let expr = &source[id];
+
+        let param = match expr {
+            hir::Expr::NameRef(name) => Some(MacroParam::Value(name.clone())),
+            hir::Expr::RegisterRef(Ok(ast::RegisterIdentKind::Alias(name))) => {
+                Some(MacroParam::Register(RegisterName(name.clone())))
+            }
+            _ => None,
+        };
+        if let Some(&substitution) = param.and_then(|p| self.substitutions.get(&p)) {
+            return substitution;
+        }
+
+        let expr = match expr {
+            hir::Expr::Array(values) => {
+                hir::Expr::Array(values.iter().map(|&v| self.copy(v)).collect())
+            }
+            hir::Expr::Mapping(arms) => hir::Expr::Mapping(
+                arms.iter()
+                    .map(|&(key, body)| (key, self.copy(body)))
+                    .collect(),
+            ),
+            &hir::Expr::UnaryOp { expr, op } => hir::Expr::UnaryOp {
+                expr: self.copy(expr),
+                op,
+            },
+            &hir::Expr::BinaryOp { lhs, rhs, op } => hir::Expr::BinaryOp {
+                lhs: self.copy(lhs),
+                rhs: self.copy(rhs),
+                op,
+            },
+            hir::Expr::Call { target, args } => hir::Expr::Call {
+                target: target.clone(),
+                args: args.iter().map(|&v| self.copy(v)).collect(),
+            },
+            hir::Expr::Missing
+            | hir::Expr::Literal(_)
+            | hir::Expr::NameRef(_)
+            | hir::Expr::RegisterRef(_) => expr.clone(),
+        };
+
+        self.destination.alloc(expr)
+    }
+}
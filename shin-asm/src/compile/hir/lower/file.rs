@@ -33,6 +33,8 @@ impl LoweredFile {
 #[salsa::tracked]
 pub fn lower_file(db: &dyn Db, def_map: DefMap, file: File) -> LoweredFile {
     let block_bodies = hir::collect_file_bodies(db, file);
+    // macros are only lowered when invoked, but we still want the errors in their definitions to be reported
+    hir::collect_file_macros(db, file);
 
     let mut bodies = FxHashMap::default();
 
@@ -230,4 +232,100 @@ mod tests {
             "#]],
         )
     }
+
+    #[test]
+    fn check_macros() {
+        check_from_hir(
+            indoc! {"
+                macro ZERO_TWO($first, $second)
+                    zero $first
+                    zero $second
+                endmacro
+
+                macro ABS_OF($dst, value)
+                    abs $dst, value
+                endmacro
+
+                BLOCK1:
+                    ZERO_TWO $v0, $v1
+                    ABS_OF $v2, 42
+            "},
+            expect![[r#"
+                block Block { item_index: ItemIndex(2), block_index: BlockIndex(0) }:
+                instructions:
+                  uo(UnaryOperation { ty: Zero, destination: $v0, source: 0 })
+                  uo(UnaryOperation { ty: Zero, destination: $v1, source: 0 })
+                  uo(UnaryOperation { ty: Abs, destination: $v2, source: 42 })
+                code addresses:
+
+            "#]],
+        );
+    }
+
+    #[test]
+    fn check_macro_errors() {
+        check_from_hir(
+            indoc! {"
+                macro ONE($dst)
+                    zero $dst
+                endmacro
+                macro LOOP
+                    LOOP
+                endmacro
+                    ONE
+                    LOOP
+            "},
+            expect![[r#"
+                Diagnostics:
+                Error: Macro `ONE` expects 1 arguments, but 0 were given
+                   ╭─[test.sal:7:5]
+                   │
+                 7 │     ONE
+                   │     ────  
+                   │            
+                ───╯
+
+
+                Error: Recursive invocation of macro `LOOP` (in expansion of macro `LOOP`)
+                   ╭─[test.sal:8:5]
+                   │
+                 8 │     LOOP
+                   │     ─────  
+                   │             
+                ───╯
+
+                block Block { item_index: ItemIndex(2), block_index: BlockIndex(0) }:
+                instructions:
+                  <error>
+                  <error>
+                code addresses:
+
+            "#]],
+        )
+    }
+
+    #[test]
+    fn check_const_fold_overflow() {
+        check_from_hir(
+            indoc! {"
+                    abs $v0, 65536 * 65536
+            "},
+            expect![[r#"
+                Diagnostics:
+                Error: Overflow in constant expression
+                   ╭─[test.sal:1:14]
+                   │
+                 1 │     abs $v0, 65536 * 65536
+                   │              ─────────────  
+                   │                             
+                ───╯
+
+                block Block { item_index: ItemIndex(0), block_index: BlockIndex(0) }:
+                instructions:
+                  <error>
+                code addresses:
+
+            "#]],
+        )
+    }
 }
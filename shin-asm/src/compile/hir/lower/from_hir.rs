@@ -1,3 +1,5 @@
+use std::rc::Rc;
+
 use shin_core::format::scenario::instruction_elements::{CodeAddress, Register};
 
 use crate::{
@@ -68,6 +70,10 @@ impl<'a> HirDiagnosticCollectorWithFile<'a> {
 pub struct HirDiagnosticCollectorWithBlock<'a> {
     diagnostics: &'a mut HirDiagnosticCollectorWithFile<'a>,
     block: HirBlockId,
+    /// The macro invocations currently being expanded, outermost first.
+    ///
+    /// The expanded code does not exist in the source, so its diagnostics are reported at the outermost invocation.
+    expansion_stack: Vec<(HirId, Name)>,
 }
 
 impl<'a> HirDiagnosticCollectorWithBlock<'a> {
@@ -75,13 +81,38 @@ impl<'a> HirDiagnosticCollectorWithBlock<'a> {
         diagnostics: &'a mut HirDiagnosticCollectorWithFile<'a>,
         block: HirBlockId,
     ) -> HirDiagnosticCollectorWithBlock<'a> {
-        HirDiagnosticCollectorWithBlock { diagnostics, block }
+        HirDiagnosticCollectorWithBlock {
+            diagnostics,
+            block,
+            expansion_stack: Vec::new(),
+        }
     }
 
     pub fn emit(&mut self, location: HirId, message: String) {
+        let (location, message) = match (self.expansion_stack.first(), self.expansion_stack.last())
+        {
+            (Some(&(invocation, _)), Some((_, name))) => (
+                invocation,
+                format!("{} (in expansion of macro `{}`)", message, name),
+            ),
+            _ => (location, message),
+        };
+
         self.diagnostics
             .emit(HirIdWithBlock::new(location, self.block), message);
     }
+
+    pub fn enter_macro(&mut self, invocation: HirId, name: Name) {
+        self.expansion_stack.push((invocation, name));
+    }
+
+    pub fn exit_macro(&mut self) {
+        self.expansion_stack.pop();
+    }
+
+    pub fn is_expanding_macro(&self, name: &Name) -> bool {
+        self.expansion_stack.iter().any(|(_, n)| n == name)
+    }
 }
 
 pub struct CodeAddressCollector {
@@ -163,6 +194,11 @@ impl<'r, 'db, 'b> FromHirBlockCtx<'r, 'db, 'b> {
         self.resolve_ctx.resolve_item(name)
     }
 
+    #[inline]
+    pub fn resolve_macro(&self, name: &Name) -> Option<Rc<hir::HirMacro>> {
+        self.resolve_ctx.resolve_macro(name)
+    }
+
     #[inline]
     pub fn instr(&self, id: hir::InstructionId) -> &hir::Instruction {
         &self.block.instructions[id]
@@ -0,0 +1,95 @@
+//! Human-readable descriptions of the instructions and commands supported by the assembler.
+//!
+//! Used by IDE features (hover and completion).
+
+/// Describes an instruction or a command that can be used in the source
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InstructionDoc {
+    pub name: &'static str,
+    /// How the instruction is written, with the argument names
+    pub signature: &'static str,
+    /// Markdown description of the instruction
+    pub description: &'static str,
+}
+
+pub const INSTRUCTION_DOCS: &[InstructionDoc] = &[
+    InstructionDoc {
+        name: "zero",
+        signature: "zero $dest[, value]",
+        description: "Sets the `$dest` register to zero. The `value` operand is ignored by the VM and defaults to `0`.",
+    },
+    InstructionDoc {
+        name: "not16",
+        signature: "not16 $dest, value",
+        description: "Stores the bitwise negation of the lower 16 bits of `value` into `$dest`.",
+    },
+    InstructionDoc {
+        name: "neg",
+        signature: "neg $dest, value",
+        description: "Stores `-value` into `$dest`.",
+    },
+    InstructionDoc {
+        name: "abs",
+        signature: "abs $dest, value",
+        description: "Stores the absolute value of `value` into `$dest`.",
+    },
+    InstructionDoc {
+        name: "j",
+        signature: "j target",
+        description: "Unconditionally jumps to the `target` label.",
+    },
+    InstructionDoc {
+        name: "EXIT",
+        signature: "EXIT",
+        description: "Stops the execution of the scenario.",
+    },
+    InstructionDoc {
+        name: "WAIT",
+        signature: "WAIT ticks",
+        description: "Delays the execution for `ticks` ticks (1/60 of a second).",
+    },
+    InstructionDoc {
+        name: "MSGINIT",
+        signature: "MSGINIT messagebox_style",
+        description: "Sets the messagebox style & text layout.",
+    },
+    InstructionDoc {
+        name: "MSGSET",
+        signature: "MSGSET text",
+        description: "Shows the message and waits for it to finish.\n\nThe text may contain layouter commands (`@r`, `@k`, etc.) that are executed in parallel with the VM.",
+    },
+];
+
+/// Finds the documentation for an instruction by its name
+pub fn instruction_doc(name: &str) -> Option<&'static InstructionDoc> {
+    INSTRUCTION_DOCS.iter().find(|doc| doc.name == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{instruction_doc, INSTRUCTION_DOCS};
+    use crate::compile::hir::lower::instruction::instruction_names;
+
+    #[test]
+    fn all_instructions_documented() {
+        for name in instruction_names() {
+            assert!(
+                instruction_doc(name).is_some(),
+                "Instruction `{}` has no documentation",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn no_stale_docs() {
+        let names = instruction_names();
+        for doc in INSTRUCTION_DOCS {
+            assert!(
+                names.contains(&doc.name),
+                "Documented instruction `{}` is not supported by the assembler",
+                doc.name
+            );
+        }
+    }
+}
@@ -1,4 +1,5 @@
 mod commands;
+mod docs;
 mod from_instr_args;
 mod instr_lowerer;
 mod instructions;
@@ -7,6 +8,7 @@ mod router;
 
 use shin_core::format::scenario::instructions::Instruction;
 
+pub use self::docs::{instruction_doc, InstructionDoc, INSTRUCTION_DOCS};
 use self::router::{Router, RouterBuilder};
 use crate::compile::{
     hir,
@@ -16,6 +18,21 @@ use crate::compile::{
     },
 };
 
+fn router() -> impl Router {
+    let builder = RouterBuilder::new();
+    let builder = instructions::instructions(builder);
+    let builder = commands::commands(builder);
+    builder.build()
+}
+
+/// Returns the names of all the instructions and commands the assembler can lower
+pub fn instruction_names() -> Vec<&'static str> {
+    let mut names = Vec::new();
+    router().collect_names(&mut names);
+    names.reverse(); // the router is built as a cons list, so the names are in reverse order
+    names
+}
+
 pub fn instruction_from_hir(
     collectors: &mut FromHirCollectors,
     ctx: &FromHirBlockCtx,
@@ -25,12 +42,7 @@ pub fn instruction_from_hir(
         return Err(LowerError);
     };
 
-    let builder = RouterBuilder::new();
-    let builder = instructions::instructions(builder);
-    let builder = commands::commands(builder);
-    let router = builder.build();
-
-    return router.handle_instr(collectors, ctx, name, instr);
+    return router().handle_instr(collectors, ctx, name, instr);
 }
 
 #[cfg(test)]
@@ -20,6 +20,9 @@ pub trait Router {
         instr_name: &str,
         instr: hir::InstructionId,
     ) -> LowerResult<Instruction>;
+
+    /// Lists the names of all the instructions handled by this router
+    fn collect_names(&self, names: &mut Vec<&'static str>);
 }
 
 pub struct SentinelRouter;
@@ -38,6 +41,8 @@ impl Router for SentinelRouter {
             format!("Unrecognized instruction: `{}`", instr_name),
         )
     }
+
+    fn collect_names(&self, _names: &mut Vec<&'static str>) {}
 }
 
 pub struct ConsRouter<
@@ -76,6 +81,11 @@ impl<
             self.tail.handle_instr(collectors, ctx, instr_name, instr)
         }
     }
+
+    fn collect_names(&self, names: &mut Vec<&'static str>) {
+        names.push(self.name);
+        self.tail.collect_names(names);
+    }
 }
 
 pub struct RouterBuilder<S: Router = SentinelRouter> {
@@ -2,6 +2,7 @@
 
 mod block;
 mod elements;
+mod expand;
 mod file;
 mod instruction;
 mod program;
@@ -17,6 +18,7 @@ pub use from_hir::{
     CodeAddressCollector, FromHirExpr, HirDiagnosticCollector, HirDiagnosticCollectorWithBlock,
     HirDiagnosticCollectorWithFile,
 };
+pub use instruction::{instruction_doc, instruction_names, InstructionDoc, INSTRUCTION_DOCS};
 pub use program::{lower_program, LoweredProgram};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
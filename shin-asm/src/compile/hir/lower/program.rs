@@ -4,7 +4,7 @@ use itertools::Itertools;
 use rustc_hash::FxHashMap;
 
 use crate::compile::{
-    def_map,
+    def_map, file,
     hir::lower::{lower_file, LoweredBlock, LoweredFile},
     BlockIdWithFile, Db, File, Program,
 };
@@ -45,6 +45,7 @@ impl LoweredProgram {
 #[salsa::tracked]
 pub fn lower_program(db: &dyn Db, program: Program) -> LoweredProgram {
     let def_map = def_map::build_def_map(db, program);
+    file::check_includes(db, program);
 
     let mut files = FxHashMap::default();
     for &file in program.files(db) {
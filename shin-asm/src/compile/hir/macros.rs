@@ -0,0 +1,149 @@
+//! Collects macro definitions into hir.
+//!
+//! Macro bodies are stored as a single hir block, which gets copied into the invoking block during lowering (see `lower::expand`).
+
+use std::rc::Rc;
+
+use either::Either;
+use rustc_hash::FxHashMap;
+
+use crate::{
+    compile::{
+        def_map::{Name, RegisterName},
+        hir::{from_ast::HirBlockCollector, HirBlockBody},
+        make_diagnostic, Db, File,
+    },
+    syntax::{
+        ast::{self, visit, visit::ItemIndex},
+        AstToken,
+    },
+};
+
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub enum MacroParam {
+    /// A `name` parameter, substituted into name references
+    Value(Name),
+    /// A `$name` parameter, substituted into register alias references
+    Register(RegisterName),
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct HirMacro {
+    pub name: Name,
+    pub params: Box<[MacroParam]>,
+    /// All the instructions of the macro body, in order
+    pub body: HirBlockBody,
+}
+
+#[salsa::tracked]
+pub struct HirMacros {
+    #[return_ref]
+    macros: FxHashMap<ItemIndex, Rc<HirMacro>>,
+}
+
+#[salsa::tracked]
+impl HirMacros {
+    #[salsa::tracked]
+    pub fn get_macro(self, db: &dyn Db, item_index: ItemIndex) -> Option<Rc<HirMacro>> {
+        self.macros(db).get(&item_index).cloned()
+    }
+}
+
+#[salsa::tracked]
+pub fn collect_file_macros(db: &dyn Db, file: File) -> HirMacros {
+    struct FileMacrosCollector<'a> {
+        db: &'a dyn Db,
+        macros: FxHashMap<ItemIndex, Rc<HirMacro>>,
+    }
+
+    impl FileMacrosCollector<'_> {
+        fn collect_param(
+            &self,
+            file: File,
+            param: ast::MacroDefinitionParam,
+        ) -> Option<MacroParam> {
+            match param.value()? {
+                Either::Left(name) => Some(MacroParam::Value(Name(name.text().into()))),
+                Either::Right(register) => match register.kind() {
+                    Ok(ast::RegisterIdentKind::Alias(name)) => {
+                        Some(MacroParam::Register(RegisterName(name)))
+                    }
+                    Ok(ast::RegisterIdentKind::Register(_)) => {
+                        make_diagnostic!(
+                            register => file,
+                            "Cannot use a built-in register as a macro parameter"
+                        )
+                        .emit(self.db);
+                        None
+                    }
+                    Err(e) => {
+                        e.in_file(file).emit(self.db);
+                        None
+                    }
+                },
+            }
+        }
+    }
+
+    impl visit::Visitor for FileMacrosCollector<'_> {
+        fn visit_macro_definition(
+            &mut self,
+            file: File,
+            item_index: ItemIndex,
+            macro_def: ast::MacroDefinition,
+        ) {
+            let Some(name) = macro_def.name().and_then(|v| v.token()) else {
+                return;
+            };
+            let name = Name(name.text().into());
+
+            let mut params = Vec::new();
+            for param in macro_def.params().iter().flat_map(|v| v.params()) {
+                if let Some(param) = self.collect_param(file, param) {
+                    params.push(param);
+                }
+            }
+
+            let mut collector = HirBlockCollector::new();
+            for block in macro_def
+                .instruction_block_set()
+                .iter()
+                .flat_map(|v| v.blocks())
+            {
+                if let Some(labels) = block.labels() {
+                    // the body is pasted into the invoking block, so there is no block for the labels to refer to
+                    make_diagnostic!(labels => file, "Labels are not allowed in macro bodies")
+                        .emit(self.db);
+                }
+
+                if let Some(body) = block.body() {
+                    for instruction in body.instructions() {
+                        collector.collect_instruction(instruction);
+                    }
+                }
+            }
+
+            let (body, _, diagnostics) = collector.collect();
+            for e in diagnostics {
+                e.in_file(file).emit(self.db)
+            }
+
+            self.macros.insert(
+                item_index,
+                Rc::new(HirMacro {
+                    name,
+                    params: params.into_boxed_slice(),
+                    body,
+                }),
+            );
+        }
+    }
+
+    let mut visitor = FileMacrosCollector {
+        db,
+        macros: FxHashMap::default(),
+    };
+    visit::visit_file(&mut visitor, file, file.parse(db));
+
+    HirMacros::new(db, visitor.macros)
+}
@@ -1,5 +1,6 @@
 mod from_ast;
 pub mod lower;
+pub mod macros;
 #[cfg(test)]
 mod tests;
 
@@ -7,6 +8,7 @@ use std::rc::Rc;
 
 use from_ast::HirBlockCollector;
 use la_arena::{Arena, Idx};
+pub use macros::{collect_file_macros, HirMacro, HirMacros, MacroParam};
 use rustc_hash::FxHashMap;
 use shin_core::rational::Rational;
 use smol_str::SmolStr;
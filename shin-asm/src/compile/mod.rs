@@ -4,6 +4,8 @@ pub mod def_map;
 pub mod diagnostics;
 pub mod file;
 pub mod generate_snr;
+#[cfg(test)]
+mod golden_tests;
 pub mod hir;
 pub mod resolve;
 pub mod types;
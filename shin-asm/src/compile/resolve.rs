@@ -1,9 +1,11 @@
+use std::rc::Rc;
+
 use shin_core::format::scenario::instruction_elements::Register;
 
 use crate::{
     compile::{
         def_map::{DefValue, Name, RegisterName, ResolveKind},
-        Db, DefMap,
+        hir, Db, DefMap,
     },
     syntax::ast::{self},
 };
@@ -62,4 +64,17 @@ impl<'a> ResolveContext<'a> {
             } => def_map.resolve_item(self.db, name.clone()),
         }
     }
+
+    pub fn resolve_macro(&self, name: &Name) -> Option<Rc<hir::HirMacro>> {
+        match self.inner {
+            ResolveContextInner::Empty => None,
+            ResolveContextInner::Real {
+                def_map,
+                resolve_kind: _,
+            } => {
+                let item = def_map.resolve_macro(self.db, name.clone())?;
+                hir::collect_file_macros(self.db, item.file).get_macro(self.db, item.value)
+            }
+        }
+    }
 }
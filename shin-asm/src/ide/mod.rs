@@ -0,0 +1,265 @@
+//! Queries used to implement IDE features on top of the compilation database.
+//!
+//! Offsets and ranges are in UTF-8 bytes, converting them to the editor's representation is up to the caller.
+
+mod symbols;
+#[cfg(test)]
+mod tests;
+
+use crate::{
+    compile::{
+        def_map::{build_def_map, DefValue, Name, RegisterName},
+        diagnostics::{Diagnostic, HirDiagnosticAccumulator, SourceDiagnosticAccumulator, Span},
+        hir::lower::{instruction_doc, lower_program, INSTRUCTION_DOCS},
+        BlockIdRepr, Db, File, MakeWithFile, Program,
+    },
+    syntax::{ast, TextRange, TextSize},
+};
+
+/// Collects all the diagnostics produced when compiling the program
+pub fn diagnostics(db: &dyn Db, program: Program) -> Vec<Diagnostic<Span>> {
+    lower_program(db, program);
+
+    let mut result = lower_program::accumulated::<SourceDiagnosticAccumulator>(db, program);
+    result.extend(
+        lower_program::accumulated::<HirDiagnosticAccumulator>(db, program)
+            .into_iter()
+            .map(|e| e.into_source(db)),
+    );
+    result
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NavigationTarget {
+    pub name: String,
+    /// Span of the name in the definition
+    pub span: Span,
+}
+
+/// Finds where the label, function or register alias at the specified offset is defined
+pub fn goto_definition(
+    db: &dyn Db,
+    program: Program,
+    file: File,
+    offset: TextSize,
+) -> Option<NavigationTarget> {
+    let syntax = file.parse(db);
+    let (_, symbol) = symbols::symbol_at(&syntax, offset)?;
+
+    match symbol {
+        symbols::Symbol::Instruction(_) => None,
+        symbols::Symbol::Item { name, function } => {
+            let local = function.as_ref().and_then(|function| {
+                symbols::local_labels(function)
+                    .find(|(n, _)| n == &name)
+                    .map(|(_, range)| Span::new(file, range))
+            });
+            let span = match local {
+                Some(span) => span,
+                None => *symbols::collect_definitions(db, program).items.get(&name)?,
+            };
+
+            Some(NavigationTarget {
+                name: name.0.to_string(),
+                span,
+            })
+        }
+        symbols::Symbol::Register {
+            kind: ast::RegisterIdentKind::Alias(name),
+            function,
+        } => {
+            let name = RegisterName(name);
+            let local = function.as_ref().and_then(|function| {
+                symbols::local_registers(function)
+                    .find(|(_, n, _)| n == &name)
+                    .map(|(_, _, range)| Span::new(file, range))
+            });
+            let span = match local {
+                Some(span) => span,
+                None => *symbols::collect_definitions(db, program)
+                    .registers
+                    .get(&name)?,
+            };
+
+            Some(NavigationTarget {
+                name: format!("${}", name),
+                span,
+            })
+        }
+        // built-in registers are not defined anywhere
+        symbols::Symbol::Register {
+            kind: ast::RegisterIdentKind::Register(_),
+            ..
+        } => None,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HoverResult {
+    /// Range of the hovered identifier
+    pub range: TextRange,
+    pub markdown: String,
+}
+
+/// Describes the instruction, label, value alias or register at the specified offset
+pub fn hover(db: &dyn Db, program: Program, file: File, offset: TextSize) -> Option<HoverResult> {
+    let syntax = file.parse(db);
+    let (range, symbol) = symbols::symbol_at(&syntax, offset)?;
+
+    let markdown = match symbol {
+        symbols::Symbol::Instruction(name) => {
+            let doc = instruction_doc(&name)?;
+            format!("```\n{}\n```\n\n{}", doc.signature, doc.description)
+        }
+        symbols::Symbol::Item { name, function } => {
+            let is_local = function
+                .as_ref()
+                .is_some_and(|function| symbols::local_labels(function).any(|(n, _)| n == name));
+
+            if is_local {
+                format!("```\n{}:\n```\n\nLocal label", name)
+            } else {
+                let def_map = build_def_map(db, program);
+                match def_map.resolve_item(db, name.clone())? {
+                    DefValue::Block(block) => match block.value.repr() {
+                        BlockIdRepr::Function { .. } => format!("```\nfunction {}\n```", name),
+                        _ => format!("```\n{}:\n```", name),
+                    },
+                    DefValue::Value(Ok(value)) => format!("```\ndef {} = {:?}\n```", name, value),
+                    DefValue::Value(Err(_)) => format!("```\ndef {} = <error>\n```", name),
+                }
+            }
+        }
+        symbols::Symbol::Register {
+            kind: ast::RegisterIdentKind::Alias(name),
+            function,
+        } => {
+            let name = RegisterName(name);
+            let def_map = build_def_map(db, program);
+
+            let local = function.as_ref().and_then(|function| {
+                let item_index = symbols::function_item_index(&syntax, function)?;
+                def_map.local_register(db, item_index.in_file(file), name.clone())
+            });
+            let register = local.or_else(|| def_map.global_register(db, name.clone()))?;
+
+            format!("```\ndef ${} = {}\n```", name, register)
+        }
+        symbols::Symbol::Register {
+            kind: ast::RegisterIdentKind::Register(_),
+            ..
+        } => return None,
+    };
+
+    Some(HoverResult { range, markdown })
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CompletionKind {
+    Instruction,
+    /// A label, a function or a value alias
+    Item,
+    Register,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompletionItem {
+    pub label: String,
+    pub kind: CompletionKind,
+    pub detail: Option<String>,
+    /// Markdown documentation
+    pub documentation: Option<String>,
+}
+
+/// Lists the completions for the identifier being typed at the specified offset.
+///
+/// The completions are not filtered by the already typed prefix, the editor does that.
+pub fn completions(
+    db: &dyn Db,
+    program: Program,
+    file: File,
+    offset: TextSize,
+) -> Vec<CompletionItem> {
+    let contents = file.contents(db);
+    let Some(before_cursor) = contents.get(..usize::from(offset)) else {
+        return Vec::new();
+    };
+    let line = before_cursor
+        .rsplit_once('\n')
+        .map_or(before_cursor, |(_, line)| line);
+    let before_word = line.trim_end_matches(|c: char| c.is_alphanumeric() || c == '_');
+
+    if before_word.ends_with('$') {
+        let syntax = file.parse(db);
+        let mut registers = symbols::function_at(&syntax, offset)
+            .map(|function| {
+                symbols::local_registers(&function)
+                    .map(|(index, name, _)| (name, format!("$a{}", index)))
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        let def_map = build_def_map(db, program);
+        let mut global = symbols::collect_definitions(db, program)
+            .registers
+            .into_keys()
+            .filter(|name| registers.iter().all(|(n, _)| n != name))
+            .map(|name| {
+                let value = def_map
+                    .global_register(db, name.clone())
+                    .map_or_else(|| "<error>".to_string(), |r| r.to_string());
+                (name, value)
+            })
+            .collect::<Vec<_>>();
+        global.sort();
+        registers.extend(global);
+
+        registers
+            .into_iter()
+            .map(|(name, value)| CompletionItem {
+                label: name.0.to_string(),
+                kind: CompletionKind::Register,
+                detail: Some(format!("${} = {}", name, value)),
+                documentation: None,
+            })
+            .collect()
+    } else if before_word.trim().is_empty() {
+        // the start of the line can only be an instruction (or a label, but those are not worth completing)
+        INSTRUCTION_DOCS
+            .iter()
+            .map(|doc| CompletionItem {
+                label: doc.name.to_string(),
+                kind: CompletionKind::Instruction,
+                detail: Some(doc.signature.to_string()),
+                documentation: Some(doc.description.to_string()),
+            })
+            .collect()
+    } else {
+        let syntax = file.parse(db);
+        let mut names = symbols::function_at(&syntax, offset)
+            .map(|function| {
+                symbols::local_labels(&function)
+                    .map(|(name, _)| name)
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        let mut global = symbols::collect_definitions(db, program)
+            .items
+            .into_keys()
+            .filter(|name| !names.contains(name))
+            .collect::<Vec<Name>>();
+        global.sort();
+        names.extend(global);
+
+        names
+            .into_iter()
+            .map(|name| CompletionItem {
+                label: name.0.to_string(),
+                kind: CompletionKind::Item,
+                detail: None,
+                documentation: None,
+            })
+            .collect()
+    }
+}
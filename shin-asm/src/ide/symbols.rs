@@ -0,0 +1,201 @@
+//! Finding out what the identifier under the cursor refers to.
+
+use either::Either;
+use rustc_hash::FxHashMap;
+use smol_str::SmolStr;
+
+use crate::{
+    compile::{
+        def_map::{Name, RegisterName},
+        diagnostics::Span,
+        Db, File, Program,
+    },
+    parser::SyntaxKind,
+    syntax::{
+        ast::{
+            self, visit,
+            visit::{BlockIndex, ItemIndex},
+        },
+        AstNode, AstSpanned, AstToken, TextRange, TextSize,
+    },
+};
+
+#[derive(Debug, Clone)]
+pub enum Symbol {
+    Instruction(SmolStr),
+    /// A label, a function or a value alias
+    Item {
+        name: Name,
+        function: Option<ast::FunctionDefinition>,
+    },
+    Register {
+        kind: ast::RegisterIdentKind,
+        function: Option<ast::FunctionDefinition>,
+    },
+}
+
+/// Classifies the identifier at the specified offset, returning its range and what it refers to
+pub fn symbol_at(syntax: &ast::SourceFile, offset: TextSize) -> Option<(TextRange, Symbol)> {
+    // when the cursor is between two tokens, prefer the identifier
+    let token = syntax
+        .syntax()
+        .token_at_offset(offset)
+        .max_by_key(|t| matches!(t.kind(), SyntaxKind::IDENT | SyntaxKind::REGISTER_IDENT))?;
+    let parent = token.parent()?;
+    let function = parent.ancestors().find_map(ast::FunctionDefinition::cast);
+
+    let symbol = match token.kind() {
+        SyntaxKind::IDENT => match parent.kind() {
+            SyntaxKind::INSTRUCTION_NAME => Symbol::Instruction(token.text().into()),
+            SyntaxKind::NAME_REF_EXPR | SyntaxKind::LABEL | SyntaxKind::NAME_DEF => Symbol::Item {
+                name: Name(token.text().into()),
+                function,
+            },
+            _ => return None,
+        },
+        SyntaxKind::REGISTER_IDENT => Symbol::Register {
+            kind: ast::RegisterIdent::cast(token.clone())?.kind().ok()?,
+            function,
+        },
+        _ => return None,
+    };
+
+    Some((token.text_range(), symbol))
+}
+
+/// Finds the function that contains the specified offset
+pub fn function_at(syntax: &ast::SourceFile, offset: TextSize) -> Option<ast::FunctionDefinition> {
+    syntax
+        .syntax()
+        .token_at_offset(offset)
+        .left_biased()
+        .and_then(|t| t.parent())
+        .and_then(|p| p.ancestors().find_map(ast::FunctionDefinition::cast))
+}
+
+/// Returns the index of the function among the items of the file
+pub fn function_item_index(
+    syntax: &ast::SourceFile,
+    function: &ast::FunctionDefinition,
+) -> Option<ItemIndex> {
+    syntax
+        .items()
+        .position(|item| matches!(item, ast::Item::FunctionDefinition(f) if &f == function))
+        .map(|index| ItemIndex::from(u32::try_from(index).unwrap()))
+}
+
+pub fn local_labels(
+    function: &ast::FunctionDefinition,
+) -> impl Iterator<Item = (Name, TextRange)> + '_ {
+    function
+        .instruction_block_set()
+        .into_iter()
+        .flat_map(|v| v.blocks())
+        .flat_map(|block| block.labels())
+        .flat_map(|v| v.labels())
+        .filter_map(|label| label.name())
+        .map(|name| (Name(name.text().into()), name.text_range()))
+}
+
+/// Returns the parameter registers of the function, along with their index
+pub fn local_registers(
+    function: &ast::FunctionDefinition,
+) -> impl Iterator<Item = (usize, RegisterName, TextRange)> + '_ {
+    function
+        .params()
+        .into_iter()
+        .flat_map(|v| v.params())
+        .flat_map(|v| v.value())
+        .enumerate()
+        .filter_map(|(index, ident)| match ident.kind() {
+            Ok(ast::RegisterIdentKind::Alias(name)) => {
+                Some((index, RegisterName(name), ident.text_range()))
+            }
+            _ => None,
+        })
+}
+
+/// Locations of the global definitions in the program.
+///
+/// The [`DefMap`](crate::compile::DefMap) only stores the resolved values, so the definitions are collected separately.
+#[derive(Debug, Default)]
+pub struct Definitions {
+    pub items: FxHashMap<Name, Span>,
+    pub registers: FxHashMap<RegisterName, Span>,
+}
+
+pub fn collect_definitions(db: &dyn Db, program: Program) -> Definitions {
+    struct DefinitionCollector {
+        definitions: Definitions,
+    }
+
+    impl DefinitionCollector {
+        // duplicate definitions are reported by the compiler, here we just use the first one
+        fn define_item(&mut self, name: Name, span: Span) {
+            self.definitions.items.entry(name).or_insert(span);
+        }
+    }
+
+    impl visit::Visitor for DefinitionCollector {
+        fn visit_global_block(
+            &mut self,
+            file: File,
+            _item_index: ItemIndex,
+            _block_index: BlockIndex,
+            block: ast::InstructionsBlock,
+        ) {
+            for name in block
+                .labels()
+                .iter()
+                .flat_map(|v| v.labels())
+                .filter_map(|v| v.name())
+            {
+                self.define_item(Name(name.text().into()), name.span(file));
+            }
+        }
+
+        fn visit_function(
+            &mut self,
+            file: File,
+            _item_index: ItemIndex,
+            function: ast::FunctionDefinition,
+        ) {
+            if let Some(name) = function.name().and_then(|v| v.token()) {
+                self.define_item(Name(name.text().into()), name.span(file));
+            }
+        }
+
+        fn visit_alias_definition(
+            &mut self,
+            file: File,
+            _item_index: ItemIndex,
+            def: ast::AliasDefinition,
+        ) {
+            match def.name() {
+                Some(Either::Left(name)) => {
+                    if let Some(name) = name.token() {
+                        self.define_item(Name(name.text().into()), name.span(file));
+                    }
+                }
+                Some(Either::Right(name)) => {
+                    if let Some(name) = name.token() {
+                        if let Ok(ast::RegisterIdentKind::Alias(alias)) = name.kind() {
+                            self.definitions
+                                .registers
+                                .entry(RegisterName(alias))
+                                .or_insert(name.span(file));
+                        }
+                    }
+                }
+                None => {}
+            }
+        }
+    }
+
+    let mut visitor = DefinitionCollector {
+        definitions: Definitions::default(),
+    };
+    visit::visit_program(&mut visitor, db, program);
+
+    visitor.definitions
+}
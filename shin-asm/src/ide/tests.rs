@@ -0,0 +1,203 @@
+use expect_test::{expect, Expect};
+
+use crate::{
+    compile::{db::Database, File, Program},
+    ide,
+    syntax::TextSize,
+};
+
+const CURSOR: &str = "<|>";
+
+fn setup(code: &str) -> (Database, Program, File, TextSize) {
+    let offset = code.find(CURSOR).expect("no cursor marker in the code");
+    let code = code.replace(CURSOR, "");
+
+    let db = Database::default();
+    let file = File::new(&db, "test.sal".to_string(), code);
+    let program = Program::new(&db, vec![file]);
+
+    (db, program, file, TextSize::try_from(offset).unwrap())
+}
+
+fn check_goto_definition(code: &str, expected: Expect) {
+    let (db, program, file, offset) = setup(code);
+
+    let actual = match ide::goto_definition(&db, program, file, offset) {
+        Some(target) => {
+            let range = target.span.range();
+            let contents = target.span.file().contents(&db);
+            let line = contents[..usize::from(range.start())].matches('\n').count();
+            format!("{} at line {}: {:?}", target.name, line, &contents[range])
+        }
+        None => "<none>".to_string(),
+    };
+
+    expected.assert_eq(&actual);
+}
+
+fn check_hover(code: &str, expected: Expect) {
+    let (db, program, file, offset) = setup(code);
+
+    let actual = ide::hover(&db, program, file, offset)
+        .map_or_else(|| "<none>".to_string(), |hover| hover.markdown);
+
+    expected.assert_eq(&actual);
+}
+
+const PROGRAM: &str = r#"
+def ANSWER = 40 + 2
+def $counter = $v17
+
+function FUN($a0, $counter)
+    neg $counter, $a0
+LOOP:
+    j LOOP
+endfun
+
+LOOP:
+    neg $counter, ANSWER
+    j LOOP
+    j FUN
+"#;
+
+/// Puts the cursor after the first character of the specified occurrence of the pattern
+fn with_cursor_at(pattern: &str, occurrence: usize) -> String {
+    let (index, _) = PROGRAM
+        .match_indices(pattern)
+        .nth(occurrence)
+        .expect("pattern not found");
+    let mut code = PROGRAM.to_string();
+    code.insert_str(index + 1, CURSOR);
+    code
+}
+
+#[test]
+fn goto_local_label() {
+    check_goto_definition(
+        &with_cursor_at("LOOP", 1),
+        expect![[r#"LOOP at line 6: "LOOP""#]],
+    );
+}
+
+#[test]
+fn goto_global_label() {
+    check_goto_definition(
+        &with_cursor_at("LOOP", 3),
+        expect![[r#"LOOP at line 10: "LOOP""#]],
+    );
+}
+
+#[test]
+fn goto_function() {
+    check_goto_definition(
+        &with_cursor_at("FUN", 1),
+        expect![[r#"FUN at line 4: "FUN""#]],
+    );
+}
+
+#[test]
+fn goto_register() {
+    // the function parameter shadows the global alias
+    check_goto_definition(
+        &with_cursor_at("$counter", 2),
+        expect![[r#"$counter at line 4: "$counter""#]],
+    );
+    check_goto_definition(
+        &with_cursor_at("$counter", 3),
+        expect![[r#"$counter at line 2: "$counter""#]],
+    );
+}
+
+#[test]
+fn goto_builtin_register() {
+    check_goto_definition(&with_cursor_at("$a0", 1), expect!["<none>"]);
+}
+
+#[test]
+fn hover_instruction() {
+    check_hover(
+        &with_cursor_at("neg", 0),
+        expect![[r#"
+            ```
+            neg $dest, value
+            ```
+
+            Stores `-value` into `$dest`."#]],
+    );
+}
+
+#[test]
+fn hover_value() {
+    check_hover(
+        &with_cursor_at("ANSWER", 1),
+        expect![[r#"
+            ```
+            def ANSWER = 42
+            ```"#]],
+    );
+}
+
+#[test]
+fn hover_register() {
+    check_hover(
+        &with_cursor_at("$counter", 2),
+        expect![[r#"
+            ```
+            def $counter = $a1
+            ```"#]],
+    );
+    check_hover(
+        &with_cursor_at("$counter", 3),
+        expect![[r#"
+            ```
+            def $counter = $v17
+            ```"#]],
+    );
+}
+
+#[test]
+fn complete_instructions() {
+    let (db, program, file, offset) = setup("    MSG<|>");
+
+    let completions = ide::completions(&db, program, file, offset);
+
+    assert!(completions
+        .iter()
+        .all(|c| c.kind == ide::CompletionKind::Instruction));
+    assert!(completions.iter().any(|c| c.label == "MSGINIT"));
+    assert!(completions.iter().any(|c| c.label == "j"));
+}
+
+#[test]
+fn complete_registers() {
+    let (db, program, file, offset) = setup(
+        r#"
+def $global = $v1
+function FUN($local)
+    neg $<|>
+endfun
+"#,
+    );
+
+    let completions = ide::completions(&db, program, file, offset)
+        .into_iter()
+        .map(|c| format!("{:?} {} ({})", c.kind, c.label, c.detail.unwrap()))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    expect![[r#"
+        Register local ($local = $a0)
+        Register global ($global = $v1)"#]]
+    .assert_eq(&completions);
+}
+
+#[test]
+fn unrecognized_instruction_diagnostic() {
+    let (db, program, _, _) = setup("    aboba $v1<|>\n");
+
+    let diagnostics = ide::diagnostics(&db, program);
+
+    assert!(diagnostics
+        .iter()
+        .any(|d| d.message == "Unrecognized instruction: `aboba`"));
+}
@@ -10,6 +10,7 @@
 extern crate self as shin_asm;
 
 pub mod compile;
+pub mod ide;
 pub mod parser;
 pub mod syntax;
 
@@ -0,0 +1,14 @@
+use super::*;
+
+pub(super) fn include_directive(p: &mut Parser<'_>) {
+    assert!(p.at(T![include]));
+
+    let m = p.start();
+
+    p.bump(T![include]);
+    p.expect(STRING);
+
+    newline(p);
+
+    m.complete(p, INCLUDE_DIRECTIVE);
+}
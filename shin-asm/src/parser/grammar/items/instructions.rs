@@ -65,7 +65,7 @@ fn body(p: &mut Parser<'_>) {
     }
 }
 
-fn instruction(p: &mut Parser<'_>) {
+pub(super) fn instruction(p: &mut Parser<'_>) {
     assert!(!p.nth_at(1, T![:]));
 
     let m = p.start();
@@ -0,0 +1,65 @@
+use super::*;
+use crate::parser::grammar::items::instructions::instructions_block_set;
+
+const MACRO_PARAM_FIRST: TokenSet = TokenSet::new(&[IDENT, REGISTER_IDENT]);
+
+pub(super) fn macro_definition(p: &mut Parser<'_>) {
+    assert!(p.at(T![macro]));
+    let m = p.start();
+
+    p.bump(T![macro]);
+
+    name_def_r(p, TokenSet::EMPTY);
+
+    // the parameter list is optional, like for subroutines
+    if p.at(T!['(']) {
+        macro_definition_params(p);
+    }
+
+    newline(p);
+
+    if p.at(IDENT) {
+        instructions_block_set(p);
+    }
+
+    if !p.eat(T![endmacro]) {
+        p.err_and_bump("expected 'endmacro'");
+    }
+
+    newline(p);
+
+    m.complete(p, MACRO_DEFINITION);
+}
+
+fn macro_definition_params(p: &mut Parser<'_>) {
+    assert!(p.at(T!['(']));
+
+    let m = p.start();
+
+    p.bump(T!['(']);
+
+    delimited(
+        p,
+        EOL_SET.add(T![')']),
+        T![,],
+        MACRO_PARAM_FIRST,
+        |p: &mut Parser<'_>| {
+            macro_definition_param(p);
+            true
+        },
+    );
+
+    p.expect(T![')']);
+
+    m.complete(p, MACRO_DEFINITION_PARAMS);
+}
+
+fn macro_definition_param(p: &mut Parser<'_>) {
+    let m = p.start();
+    if p.at_ts(MACRO_PARAM_FIRST) {
+        p.bump_any();
+    } else {
+        p.error("expected a macro parameter");
+    }
+    m.complete(p, MACRO_DEFINITION_PARAM);
+}
@@ -1,6 +1,8 @@
 mod alias;
 mod functions;
+mod include;
 mod instructions;
+mod macros;
 
 use super::*;
 
@@ -11,6 +13,10 @@ pub(super) fn item(p: &mut Parser<'_>) {
         instructions::instructions_block_set(p);
     } else if p.at(DEF_KW) {
         alias::alias_definition(p);
+    } else if p.at(T![include]) {
+        include::include_directive(p);
+    } else if p.at(T![macro]) {
+        macros::macro_definition(p);
     } else if p.at_ts(EOL_SET) {
         p.bump_any();
         // empty items are allowed
@@ -18,3 +24,13 @@ pub(super) fn item(p: &mut Parser<'_>) {
         p.err_and_bump("expected an instruction or label");
     }
 }
+
+/// Returns the grammar entry point that can parse the node of the specified kind in isolation
+pub(super) fn reparser(node: SyntaxKind) -> Option<fn(&mut Parser<'_>)> {
+    let res: fn(&mut Parser<'_>) = match node {
+        INSTRUCTION => instructions::instruction,
+        ALIAS_DEFINITION => alias::alias_definition,
+        _ => return None,
+    };
+    Some(res)
+}
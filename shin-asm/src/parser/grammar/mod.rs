@@ -21,6 +21,10 @@ pub(crate) fn source_file(p: &mut Parser<'_>) {
     m.complete(p, SOURCE_FILE);
 }
 
+pub(crate) fn reparser(node: SyntaxKind) -> Option<fn(&mut Parser<'_>)> {
+    items::reparser(node)
+}
+
 /// The `parser` passed this is required to at least consume one token if it returns `true`.
 /// If the `parser` returns false, parsing will stop.
 fn delimited(
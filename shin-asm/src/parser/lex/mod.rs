@@ -72,23 +72,21 @@ impl<'a> LexedStr<'a> {
         conv.finalize_with_eof(err)
     }
 
-    // pub fn single_token(text: &'a str) -> Option<(SyntaxKind, Option<String>)> {
-    //     if text.is_empty() {
-    //         return None;
-    //     }
-    //
-    //     let token = rustc_lexer::tokenize(text).next()?;
-    //     if token.len as usize != text.len() {
-    //         return None;
-    //     }
-    //
-    //     let mut conv = Converter::new(text);
-    //     conv.extend_token(&token.kind, text);
-    //     match &*conv.res.kind {
-    //         [kind] => Some((*kind, conv.res.error.pop().map(|it| it.msg))),
-    //         _ => None,
-    //     }
-    // }
+    /// Lexes the text, returning its kind if it consists of exactly one token
+    pub fn single_token(text: &'a str) -> Option<(SyntaxKind, Option<String>)> {
+        if text.is_empty() {
+            return None;
+        }
+
+        let lexed = LexedStr::new(text);
+        if lexed.len() != 1 {
+            return None;
+        }
+
+        // errors can also be attached to the EOF token (like the unclosed bracket one)
+        let error = lexed.errors().next().map(|(_, msg)| msg.to_string());
+        Some((lexed.kind(0), error))
+    }
 
     pub fn as_str(&self) -> &str {
         self.text
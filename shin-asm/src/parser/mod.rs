@@ -21,6 +21,26 @@ pub use syntax_kind::SyntaxKind;
 pub(crate) use syntax_kind::T;
 pub(crate) use token_set::TokenSet;
 
+/// A grammar entry point that parses a single node in isolation, used for incremental reparsing.
+///
+/// Only the nodes that span a single line (instructions and alias definitions) can be reparsed this way.
+#[derive(Clone, Copy)]
+pub struct Reparser(fn(&mut parser::Parser<'_>));
+
+impl Reparser {
+    pub fn for_node(node: SyntaxKind) -> Option<Reparser> {
+        grammar::reparser(node).map(Reparser)
+    }
+
+    pub fn parse(self, input: &Input) -> Output {
+        let Reparser(r) = self;
+        let mut p = parser::Parser::new(input);
+        r(&mut p);
+        let events = p.finish();
+        event::process(events)
+    }
+}
+
 pub fn parse(input: &Input) -> Output {
     let mut p = parser::Parser::new(input);
     grammar::source_file(&mut p);
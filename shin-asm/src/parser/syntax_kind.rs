@@ -49,6 +49,9 @@ syntax_kind! {
         SUBROUTINE_KW => "subroutine",
         ENDSUB_KW => "endsub",
         DEF_KW => "def",
+        INCLUDE_KW => "include",
+        MACRO_KW => "macro",
+        ENDMACRO_KW => "endmacro",
     },
     literals: [
         INT_NUMBER,
@@ -84,6 +87,12 @@ syntax_kind! {
         INSTRUCTION_NAME,
         INSTR_ARG_LIST,
 
+        INCLUDE_DIRECTIVE,
+
+        MACRO_DEFINITION,
+        MACRO_DEFINITION_PARAMS,
+        MACRO_DEFINITION_PARAM,
+
         NAME_REF_EXPR,
         REGISTER_REF_EXPR,
 
@@ -0,0 +1,50 @@
+use either::Either;
+
+use super::*;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, AstNode)]
+#[ast(kind = MACRO_DEFINITION)]
+pub struct MacroDefinition {
+    pub(crate) syntax: SyntaxNode,
+}
+
+impl MacroDefinition {
+    pub fn name(&self) -> Option<NameDef> {
+        support::child(self.syntax())
+    }
+
+    pub fn params(&self) -> Option<MacroDefinitionParams> {
+        support::child(self.syntax())
+    }
+
+    pub fn instruction_block_set(&self) -> Option<InstructionsBlockSet> {
+        support::child(self.syntax())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, AstNode)]
+#[ast(kind = MACRO_DEFINITION_PARAMS)]
+pub struct MacroDefinitionParams {
+    pub(crate) syntax: SyntaxNode,
+}
+
+impl MacroDefinitionParams {
+    pub fn params(&self) -> AstChildren<MacroDefinitionParam> {
+        support::children(self.syntax())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, AstNode)]
+#[ast(kind = MACRO_DEFINITION_PARAM)]
+pub struct MacroDefinitionParam {
+    pub(crate) syntax: SyntaxNode,
+}
+
+impl MacroDefinitionParam {
+    /// Either a value parameter (`name`) or a register parameter (`$name`)
+    pub fn value(&self) -> Option<Either<Ident, RegisterIdent>> {
+        support::token(self.syntax())
+            .map(Either::Left)
+            .or_else(|| support::token(self.syntax()).map(Either::Right))
+    }
+}
@@ -1,7 +1,9 @@
 mod functions;
+mod macros;
 
 use either::Either;
 pub use functions::*;
+pub use macros::*;
 use smol_str::SmolStr;
 
 use super::*;
@@ -14,6 +16,10 @@ pub enum Item {
     FunctionDefinition(FunctionDefinition),
     #[ast(transparent)]
     AliasDefinition(AliasDefinition),
+    #[ast(transparent)]
+    IncludeDirective(IncludeDirective),
+    #[ast(transparent)]
+    MacroDefinition(MacroDefinition),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, AstNode)]
@@ -138,3 +144,15 @@ impl AliasDefinition {
         support::child(self.syntax())
     }
 }
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, AstNode)]
+#[ast(kind = INCLUDE_DIRECTIVE)]
+pub struct IncludeDirective {
+    pub(crate) syntax: SyntaxNode,
+}
+
+impl IncludeDirective {
+    pub fn path(&self) -> Option<String> {
+        support::token(self.syntax())
+    }
+}
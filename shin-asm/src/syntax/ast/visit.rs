@@ -73,6 +73,12 @@ pub fn visit_item<V: Visitor>(visitor: &mut V, file: File, item_index: ItemIndex
         ast::Item::AliasDefinition(alias) => {
             visitor.visit_alias_definition(file, item_index, alias)
         }
+        ast::Item::IncludeDirective(include) => {
+            visitor.visit_include_directive(file, item_index, include)
+        }
+        ast::Item::MacroDefinition(macro_def) => {
+            visitor.visit_macro_definition(file, item_index, macro_def)
+        }
     }
 }
 
@@ -133,6 +139,23 @@ pub fn visit_alias_definition<V: Visitor>(
 ) {
 }
 
+pub fn visit_include_directive<V: Visitor>(
+    _visitor: &mut V,
+    _file: File,
+    _item_index: ItemIndex,
+    _include: ast::IncludeDirective,
+) {
+}
+
+/// Macro bodies are not blocks on their own, so they are not visited by default
+pub fn visit_macro_definition<V: Visitor>(
+    _visitor: &mut V,
+    _file: File,
+    _item_index: ItemIndex,
+    _macro_def: ast::MacroDefinition,
+) {
+}
+
 pub trait Visitor: Sized {
     fn visit_file(&mut self, file: File, syntax: ast::SourceFile) {
         visit_file(self, file, syntax);
@@ -190,4 +213,20 @@ pub trait Visitor: Sized {
     ) {
         visit_alias_definition(self, file, item_index, def);
     }
+    fn visit_include_directive(
+        &mut self,
+        file: File,
+        item_index: ItemIndex,
+        include: ast::IncludeDirective,
+    ) {
+        visit_include_directive(self, file, item_index, include);
+    }
+    fn visit_macro_definition(
+        &mut self,
+        file: File,
+        item_index: ItemIndex,
+        macro_def: ast::MacroDefinition,
+    ) {
+        visit_macro_definition(self, file, item_index, macro_def);
+    }
 }
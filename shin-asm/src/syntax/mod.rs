@@ -110,6 +110,25 @@ impl Parse<SourceFile> {
         }
         buf
     }
+
+    /// Applies an edit replacing the `range` with `new_text`, reusing the unchanged parts of the tree when possible.
+    ///
+    /// Falls back to parsing the whole text again if the edit can't be handled incrementally.
+    pub fn reparse(&self, range: TextRange, new_text: &str) -> Parse<SourceFile> {
+        self.incremental_reparse(range, new_text)
+            .unwrap_or_else(|| self.full_reparse(range, new_text))
+    }
+
+    fn incremental_reparse(&self, range: TextRange, new_text: &str) -> Option<Parse<SourceFile>> {
+        parsing::incremental_reparse(self.tree().syntax(), range, new_text, self.errors.to_vec())
+            .map(|(green_node, errors, _reparsed_range)| Parse::new(green_node, errors))
+    }
+
+    fn full_reparse(&self, range: TextRange, new_text: &str) -> Parse<SourceFile> {
+        let mut text = self.tree().syntax().text().to_string();
+        text.replace_range(std::ops::Range::<usize>::from(range), new_text);
+        SourceFile::parse(&text)
+    }
 }
 
 /// `SourceFile` represents a parse tree for a single Rust file.
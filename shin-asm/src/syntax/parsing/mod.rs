@@ -1,6 +1,8 @@
 //! Lexing, bridging to parser (which does the actual parsing) and
 //! incremental reparsing.
 
+mod reparsing;
+
 use text_size::TextRange;
 
 use crate::{
@@ -8,6 +10,8 @@ use crate::{
     syntax::{syntax_node::GreenNode, SyntaxError, SyntaxTreeBuilder},
 };
 
+pub(crate) use self::reparsing::incremental_reparse;
+
 pub(crate) fn parse_text(text: &str) -> (GreenNode, Vec<SyntaxError>) {
     let lexed = parser::LexedStr::new(text);
     let parser_input = lexed.to_input();
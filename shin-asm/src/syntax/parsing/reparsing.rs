@@ -0,0 +1,458 @@
+//! Implementation of incremental re-parsing.
+//!
+//! We use two simple strategies for this:
+//!   - if the edit modifies only a single token (like changing an identifier's
+//!     letter), we replace only this token.
+//!   - otherwise, we search for the nearest node spanning a single line (an instruction
+//!     or an alias definition) that contains the edit, and reparse only it.
+//!
+//! The grammar is line-based, so a line that still forms a complete node after the edit
+//! can't affect how the rest of the file is parsed.
+//! The only exception are brackets: newlines inside them are demoted to whitespace by the lexer,
+//! so we bail if the brackets are not balanced within the line.
+
+use rowan::GreenToken;
+use text_size::{TextRange, TextSize};
+
+use crate::{
+    parser::{self, Reparser, SyntaxKind, SyntaxKind::*, T},
+    syntax::{
+        parsing::build_tree,
+        syntax_node::{GreenNode, SalLanguage, SyntaxElement, SyntaxNode},
+        SyntaxError,
+    },
+};
+
+pub(crate) fn incremental_reparse(
+    node: &SyntaxNode,
+    delete: TextRange,
+    insert: &str,
+    errors: Vec<SyntaxError>,
+) -> Option<(GreenNode, Vec<SyntaxError>, TextRange)> {
+    if let Some((green, new_errors, old_range)) = reparse_token(node, delete, insert) {
+        return Some((
+            green,
+            merge_errors(errors, new_errors, old_range, delete, insert),
+            old_range,
+        ));
+    }
+
+    if let Some((green, new_errors, old_range)) = reparse_block(node, delete, insert) {
+        return Some((
+            green,
+            merge_errors(errors, new_errors, old_range, delete, insert),
+            old_range,
+        ));
+    }
+    None
+}
+
+fn reparse_token(
+    root: &SyntaxNode,
+    delete: TextRange,
+    insert: &str,
+) -> Option<(GreenNode, Vec<SyntaxError>, TextRange)> {
+    let prev_token = root.covering_element(delete).as_token()?.clone();
+    match prev_token.kind() {
+        WHITESPACE | COMMENT | IDENT | REGISTER_IDENT => {
+            // newlines are significant, and whitespace tokens can contain the ones demoted inside brackets
+            if prev_token.text().contains('\n') || insert.contains('\n') {
+                return None;
+            }
+
+            let new_text = get_text_after_edit(prev_token.clone().into(), delete, insert);
+            let (new_token_kind, new_err) = parser::LexedStr::single_token(&new_text)?;
+
+            // an erroneous token (like an unterminated block comment) might extend further in the context of the file
+            if new_token_kind != prev_token.kind() || new_err.is_some() {
+                return None;
+            }
+
+            // Check that the edited token does not merge with its neighbours.
+            // E.g. if for source code `a /**/b` the user removed `/**/`, there's a single identifier now
+            if let Some(next_char) = prev_token
+                .next_token()
+                .and_then(|t| t.text().chars().next())
+            {
+                if parser::LexedStr::single_token(&format!("{}{}", new_text, next_char)).is_some() {
+                    return None;
+                }
+            }
+            if let Some(prev_char) = prev_token
+                .prev_token()
+                .and_then(|t| t.text().chars().next_back())
+            {
+                if parser::LexedStr::single_token(&format!("{}{}", prev_char, new_text)).is_some() {
+                    return None;
+                }
+            }
+
+            let new_token = GreenToken::new(SalLanguage::kind_to_raw(prev_token.kind()), &new_text);
+            Some((
+                prev_token.replace_with(new_token),
+                Vec::new(),
+                prev_token.text_range(),
+            ))
+        }
+        _ => None,
+    }
+}
+
+fn reparse_block(
+    root: &SyntaxNode,
+    delete: TextRange,
+    insert: &str,
+) -> Option<(GreenNode, Vec<SyntaxError>, TextRange)> {
+    let (node, reparser) = find_reparsable_node(root, delete)?;
+    let text = get_text_after_edit(node.clone().into(), delete, insert);
+
+    let lexed = parser::LexedStr::new(&text);
+    if !starts_after_line_break(&node) || !is_single_line(&lexed, &node) {
+        return None;
+    }
+
+    let parser_input = lexed.to_input();
+    let tree_traversal = reparser.parse(&parser_input);
+
+    let (green, new_parser_errors, is_eof) = build_tree(lexed, tree_traversal);
+    if !is_eof || green.kind() != SalLanguage::kind_to_raw(node.kind()) {
+        return None;
+    }
+
+    Some((
+        node.replace_with(green),
+        new_parser_errors,
+        node.text_range(),
+    ))
+}
+
+fn get_text_after_edit(element: SyntaxElement, delete: TextRange, insert: &str) -> String {
+    let edit_range = delete - element.text_range().start();
+
+    let mut text = match element {
+        rowan::NodeOrToken::Token(token) => token.text().to_string(),
+        rowan::NodeOrToken::Node(node) => node.text().to_string(),
+    };
+    text.replace_range(std::ops::Range::<usize>::from(edit_range), insert);
+    text
+}
+
+fn find_reparsable_node(node: &SyntaxNode, range: TextRange) -> Option<(SyntaxNode, Reparser)> {
+    let node = node.covering_element(range);
+
+    node.ancestors()
+        .find_map(|node| Reparser::for_node(node.kind()).map(|r| (node, r)))
+}
+
+fn is_bracket(kind: SyntaxKind) -> bool {
+    kind.is_any_opening_bracket() || kind.is_any_closing_bracket()
+}
+
+/// Checks that the lexer state (the bracket stack) is empty at the start of the node,
+/// by walking back to the previous line break
+fn starts_after_line_break(node: &SyntaxNode) -> bool {
+    let mut token = node.first_token().and_then(|t| t.prev_token());
+    while let Some(t) = token {
+        match t.kind() {
+            // a newline is demoted to whitespace when inside brackets, so getting a real one means the bracket stack is empty
+            NEWLINE => return true,
+            WHITESPACE if t.text().contains('\n') => return false,
+            kind if is_bracket(kind) => return false,
+            _ => {}
+        }
+        token = t.prev_token();
+    }
+
+    true
+}
+
+/// Checks that the edited text still forms a complete line that can't affect the parsing of the neighbouring lines
+fn is_single_line(lexed: &parser::LexedStr<'_>, old_node: &SyntaxNode) -> bool {
+    let expected_start = match old_node.kind() {
+        INSTRUCTION => IDENT,
+        ALIAS_DEFINITION => T![def],
+        _ => return false,
+    };
+    if lexed.is_empty() || lexed.kind(0) != expected_start {
+        return false;
+    }
+
+    // `IDENT :` is a label, not an instruction
+    if old_node.kind() == INSTRUCTION
+        && (1..lexed.len())
+            .map(|i| lexed.kind(i))
+            .find(|k| !k.is_trivia())
+            .is_some_and(|k| k == T![:])
+    {
+        return false;
+    }
+
+    let mut bracket_depth = 0usize;
+    for i in 0..lexed.len() {
+        let kind = lexed.kind(i);
+        if kind.is_any_opening_bracket() {
+            bracket_depth += 1;
+        } else if kind.is_any_closing_bracket() {
+            let Some(depth) = bracket_depth.checked_sub(1) else {
+                return false;
+            };
+            bracket_depth = depth;
+        }
+    }
+    if bracket_depth != 0 {
+        return false;
+    }
+
+    // the node should end with its (only) newline, unless it's the last line of the file
+    let newlines = (0..lexed.len())
+        .filter(|&i| lexed.kind(i) == NEWLINE)
+        .count();
+    let ends_with_newline = lexed.kind(lexed.len() - 1) == NEWLINE;
+    let old_ends_with_newline = old_node.last_token().is_some_and(|t| t.kind() == NEWLINE);
+    if old_ends_with_newline {
+        newlines == 1 && ends_with_newline
+    } else {
+        // otherwise the node is the last one in the file
+        newlines == 0
+    }
+}
+
+fn merge_errors(
+    old_errors: Vec<SyntaxError>,
+    new_errors: Vec<SyntaxError>,
+    range_before_reparse: TextRange,
+    delete: TextRange,
+    insert: &str,
+) -> Vec<SyntaxError> {
+    let mut res = Vec::new();
+
+    for old_err in old_errors {
+        let old_err_range = old_err.range();
+        if old_err_range.end() <= range_before_reparse.start() {
+            res.push(old_err);
+        } else if old_err_range.start() >= range_before_reparse.end() {
+            let inserted_len = TextSize::of(insert);
+            // Note: extra parens are intentional to prevent uint underflow
+            res.push(old_err.with_range((old_err_range + inserted_len) - delete.len()));
+        }
+    }
+    res.extend(new_errors.into_iter().map(|new_err| {
+        let offseted_range = new_err.range() + range_before_reparse.start();
+        new_err.with_range(offseted_range)
+    }));
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use text_size::{TextRange, TextSize};
+
+    use super::incremental_reparse;
+    use crate::syntax::{AstNode, Parse, SourceFile};
+
+    const MARKER: &str = "<|>";
+
+    /// Extracts the range between the two markers
+    fn extract_range(text: &str) -> (TextRange, String) {
+        let start = text.find(MARKER).expect("no start marker");
+        let text = text.replacen(MARKER, "", 1);
+        let end = text.find(MARKER).expect("no end marker");
+        let text = text.replacen(MARKER, "", 1);
+
+        let range = TextRange::new(
+            TextSize::try_from(start).unwrap(),
+            TextSize::try_from(end).unwrap(),
+        );
+        (range, text)
+    }
+
+    fn dump(parse: &Parse<SourceFile>) -> String {
+        let mut errors = parse
+            .errors()
+            .iter()
+            .map(|e| format!("{:?}", e))
+            .collect::<Vec<_>>();
+        // the order of errors is not preserved by the incremental reparsing
+        errors.sort();
+
+        format!("{:#?}\n{}", parse.tree().syntax(), errors.join("\n"))
+    }
+
+    fn do_check(before: &str, replace_with: &str, reparsed_len: u32) {
+        let (range, before) = extract_range(before);
+        let mut after = before.clone();
+        after.replace_range(std::ops::Range::<usize>::from(range), replace_with);
+
+        let fully_reparsed = SourceFile::parse(&after);
+        let incrementally_reparsed: Parse<SourceFile> = {
+            let before = SourceFile::parse(&before);
+            let (green, new_errors, range) = incremental_reparse(
+                before.tree().syntax(),
+                range,
+                replace_with,
+                before.errors.to_vec(),
+            )
+            .expect("cannot incrementally reparse");
+            assert_eq!(
+                range.len(),
+                reparsed_len.into(),
+                "reparsed fragment has wrong length"
+            );
+            Parse::new(green, new_errors)
+        };
+
+        assert_eq!(dump(&fully_reparsed), dump(&incrementally_reparsed));
+        // also check the public API
+        assert_eq!(
+            dump(&SourceFile::parse(&before).reparse(range, replace_with)),
+            dump(&fully_reparsed)
+        );
+    }
+
+    fn check_full_reparse(before: &str, replace_with: &str) {
+        let (range, before) = extract_range(before);
+        let mut after = before.clone();
+        after.replace_range(std::ops::Range::<usize>::from(range), replace_with);
+
+        let before = SourceFile::parse(&before);
+        assert!(
+            incremental_reparse(
+                before.tree().syntax(),
+                range,
+                replace_with,
+                before.errors.to_vec()
+            )
+            .is_none(),
+            "the edit should not be reparsed incrementally"
+        );
+        assert_eq!(
+            dump(&before.reparse(range, replace_with)),
+            dump(&SourceFile::parse(&after))
+        );
+    }
+
+    #[test]
+    fn reparse_token() {
+        do_check(
+            r#"
+    add $v<|>1<|>, 2
+    sub $v2, 3
+"#,
+            "17",
+            3,
+        );
+        do_check(
+            r#"
+    add $v1, 2 // <|>a comment<|>
+"#,
+            "another comment",
+            12,
+        );
+        do_check(
+            r#"
+LABEL:
+    j L<|>AB<|>
+"#,
+            "ABEL",
+            3,
+        );
+        do_check(
+            r#"
+    add<|> <|>$v1, 2
+"#,
+            "    ",
+            1,
+        );
+    }
+
+    #[test]
+    fn reparse_line() {
+        do_check(
+            r#"
+    add $v1, <|>2<|>
+    sub $v2, 3
+"#,
+            "(2 + 3) * 4",
+            11,
+        );
+        do_check(
+            r#"
+def ABOBA = <|>42<|>
+def BIBA = 1
+"#,
+            "ABOBA + 1",
+            15,
+        );
+        do_check(
+            r#"
+function FUN($a0)
+    add $v1, $a0<|><|>
+endfun
+"#,
+            ", 2",
+            13,
+        );
+        // the last line without a trailing newline
+        do_check(
+            r#"
+    add $v1, 2
+    sub $v2, <|>3<|>"#,
+            "4 5",
+            10,
+        );
+        // errors are reparsed too
+        do_check(
+            r#"
+    add $v1, 2
+    sub $v2, <|>3<|>
+    add $v3, (
+"#,
+            "3 +",
+            11,
+        );
+    }
+
+    #[test]
+    fn full_reparse() {
+        // the line becomes a label
+        check_full_reparse(
+            r#"
+    add<|><|> $v1
+    sub $v2
+"#,
+            ":",
+        );
+        // multiple lines are affected
+        check_full_reparse(
+            r#"
+    add $v1<|>
+    sub<|> $v2
+"#,
+            "",
+        );
+        // unbalanced brackets would affect the following lines
+        check_full_reparse(
+            r#"
+    add $v1, <|>2<|>
+    sub $v2
+"#,
+            "(2",
+        );
+        // unterminated block comment
+        check_full_reparse(
+            r#"
+    add $v1, 2 <|>// comment<|>
+    sub $v2
+"#,
+            "/* comment",
+        );
+        // keyword
+        check_full_reparse(
+            r#"
+    add $v1
+    de<|><|> $v2
+"#,
+            "f",
+        );
+    }
+}
@@ -0,0 +1,116 @@
+//! Metering of the tracks (the BGM, SE and voice buses and the main mix), shown in the debug overlay.
+//!
+//! Unlike the [`LevelMeter`](crate::LevelMeter), which only follows the loudness for the lip sync, this measures both channels separately,
+//! giving the RMS level (how loud it sounds) and the peak level (how close it is to clipping).
+//! The sounds reach the tracks with their volume and panning applied, so the meters show the effect of the Volume and Pan commands.
+
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc,
+};
+
+use kira::{
+    clock::clock_info::ClockInfoProvider,
+    modulator::value_provider::ModulatorValueProvider,
+    track::effect::{Effect, EffectBuilder},
+    Frame,
+};
+
+/// The time the RMS is averaged over, in seconds, close to the integration time of the VU meters
+const RMS_TIME: f64 = 0.3;
+/// How fast the peak level falls after the peak, in seconds to fall to ~37% of it
+const PEAK_RELEASE_TIME: f64 = 0.5;
+
+/// The levels of the two channels of a track, as linear amplitudes in `[0, 1]` (but they can be higher if the track clips)
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct BusLevels {
+    pub rms: [f32; 2],
+    pub peak: [f32; 2],
+}
+
+#[derive(Default)]
+struct BusMeterShared {
+    /// The bits of the `f32` levels, in the order of [`BusLevels::rms`] and then [`BusLevels::peak`]
+    levels: [AtomicU32; 4],
+}
+
+/// A handle to the levels measured by a [`BusMeterBuilder`] effect
+#[derive(Clone)]
+pub struct BusMeter {
+    shared: Arc<BusMeterShared>,
+}
+
+impl BusMeter {
+    pub fn levels(&self) -> BusLevels {
+        let level =
+            |index: usize| f32::from_bits(self.shared.levels[index].load(Ordering::Relaxed));
+
+        BusLevels {
+            rms: [level(0), level(1)],
+            peak: [level(2), level(3)],
+        }
+    }
+}
+
+struct BusMeterEffect {
+    shared: Arc<BusMeterShared>,
+    /// The running mean of the squared samples of each channel
+    mean_square: [f64; 2],
+    peak: [f64; 2],
+}
+
+impl Effect for BusMeterEffect {
+    fn process(
+        &mut self,
+        input: Frame,
+        dt: f64,
+        _clock_info_provider: &ClockInfoProvider,
+        _modulator_value_provider: &ModulatorValueProvider,
+    ) -> Frame {
+        let rms_factor = 1.0 - (-dt / RMS_TIME).exp();
+        let peak_factor = (-dt / PEAK_RELEASE_TIME).exp();
+
+        for (channel, sample) in [input.left, input.right].into_iter().enumerate() {
+            let sample = sample.abs() as f64;
+
+            self.mean_square[channel] += (sample * sample - self.mean_square[channel]) * rms_factor;
+            self.peak[channel] = sample.max(self.peak[channel] * peak_factor);
+
+            self.shared.levels[channel].store(
+                (self.mean_square[channel].sqrt() as f32).to_bits(),
+                Ordering::Relaxed,
+            );
+            self.shared.levels[2 + channel]
+                .store((self.peak[channel] as f32).to_bits(), Ordering::Relaxed);
+        }
+
+        input
+    }
+}
+
+/// Measures the levels of a track, without changing the audio passing through it
+#[derive(Default)]
+pub struct BusMeterBuilder;
+
+impl BusMeterBuilder {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl EffectBuilder for BusMeterBuilder {
+    type Handle = BusMeter;
+
+    fn build(self) -> (Box<dyn Effect>, Self::Handle) {
+        let shared = Arc::new(BusMeterShared::default());
+
+        (
+            Box::new(BusMeterEffect {
+                shared: shared.clone(),
+                mean_square: [0.0; 2],
+                peak: [0.0; 2],
+            }),
+            BusMeter { shared },
+        )
+    }
+}
@@ -0,0 +1,130 @@
+//! Capture of the final mix, used to record the game audio along with the video.
+//!
+//! An effect on the main track copies the frames to a ring buffer, which is drained by the recorder on the game thread.
+
+use std::sync::{
+    atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
+    Arc, Mutex,
+};
+
+use kira::{
+    clock::clock_info::ClockInfoProvider,
+    modulator::value_provider::ModulatorValueProvider,
+    track::effect::{Effect, EffectBuilder},
+    Frame,
+};
+use ringbuf::{
+    traits::{Consumer as _, Producer as _, Split as _},
+    HeapCons, HeapProd, HeapRb,
+};
+use tracing::warn;
+
+/// How many seconds of audio are buffered between the drains, at 48 kHz
+const BUFFER_SECONDS: usize = 2;
+const BUFFER_CAPACITY: usize = 48000 * BUFFER_SECONDS;
+
+struct MixCaptureShared {
+    recording: AtomicBool,
+    sample_rate: AtomicU32,
+    /// Frames that didn't fit in the ring buffer since the last drain
+    dropped: AtomicU64,
+}
+
+/// A handle to the capture of the final mix of an [`AudioManager`](crate::AudioManager)
+pub struct MixCapture {
+    shared: Arc<MixCaptureShared>,
+    consumer: Mutex<HeapCons<Frame>>,
+}
+
+impl MixCapture {
+    /// Starts copying the mix, discarding anything left from the previous recording
+    pub fn start(&self) {
+        self.consumer.lock().unwrap().clear();
+        self.shared.dropped.store(0, Ordering::SeqCst);
+        self.shared.recording.store(true, Ordering::SeqCst);
+    }
+
+    pub fn stop(&self) {
+        self.shared.recording.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.shared.recording.load(Ordering::SeqCst)
+    }
+
+    /// The sample rate of the captured frames, as chosen by the output device
+    pub fn sample_rate(&self) -> u32 {
+        self.shared.sample_rate.load(Ordering::SeqCst)
+    }
+
+    /// Moves the frames captured since the last call to `output`
+    ///
+    /// Must be called often enough for the frames to fit in the buffer (a couple of seconds), the overflowing frames are replaced with silence.
+    pub fn drain(&self, output: &mut Vec<Frame>) {
+        output.extend(self.consumer.lock().unwrap().pop_iter());
+
+        let dropped = self.shared.dropped.swap(0, Ordering::SeqCst);
+        if dropped > 0 {
+            warn!(
+                "The mix capture buffer overflowed, {} frames were lost",
+                dropped
+            );
+            output.extend(std::iter::repeat(Frame::ZERO).take(dropped as usize));
+        }
+    }
+}
+
+struct MixTap {
+    shared: Arc<MixCaptureShared>,
+    producer: HeapProd<Frame>,
+}
+
+impl Effect for MixTap {
+    fn init(&mut self, sample_rate: u32) {
+        self.shared.sample_rate.store(sample_rate, Ordering::SeqCst);
+    }
+
+    fn on_change_sample_rate(&mut self, sample_rate: u32) {
+        self.shared.sample_rate.store(sample_rate, Ordering::SeqCst);
+    }
+
+    fn process(
+        &mut self,
+        input: Frame,
+        _dt: f64,
+        _clock_info_provider: &ClockInfoProvider,
+        _modulator_value_provider: &ModulatorValueProvider,
+    ) -> Frame {
+        if self.shared.recording.load(Ordering::Relaxed) && self.producer.try_push(input).is_err() {
+            self.shared.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        input
+    }
+}
+
+/// Adds the capture to a track, without changing the audio passing through it
+pub(crate) struct MixTapBuilder;
+
+impl EffectBuilder for MixTapBuilder {
+    type Handle = MixCapture;
+
+    fn build(self) -> (Box<dyn Effect>, Self::Handle) {
+        let (producer, consumer) = HeapRb::new(BUFFER_CAPACITY).split();
+        let shared = Arc::new(MixCaptureShared {
+            recording: AtomicBool::new(false),
+            sample_rate: AtomicU32::new(0),
+            dropped: AtomicU64::new(0),
+        });
+
+        (
+            Box::new(MixTap {
+                shared: shared.clone(),
+                producer,
+            }),
+            MixCapture {
+                shared,
+                consumer: Mutex::new(consumer),
+            },
+        )
+    }
+}
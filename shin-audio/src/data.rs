@@ -1,11 +1,16 @@
 //! Implements the SoundData trait for the Kira audio library.
 
-use std::sync::Arc;
+use std::{
+    io::{Read, Seek},
+    sync::Arc,
+};
 
 use anyhow::Result;
 use kira::sound::{Sound, SoundData};
 use ringbuf::{traits::Split as _, HeapRb};
-use shin_core::format::audio::{AudioDecoder, AudioFile, AudioFrameSource};
+use shin_core::format::audio::{
+    AudioDecoder, AudioFile, AudioFileFrameReader, AudioFrameSource, AudioStreamReader,
+};
 
 use super::AudioSettings;
 use crate::{
@@ -18,7 +23,7 @@ pub struct AudioData<S: AudioFrameSource> {
     pub settings: AudioSettings,
 }
 
-impl AudioData<AudioDecoder<Arc<AudioFile>>> {
+impl AudioData<AudioDecoder<AudioFileFrameReader<Arc<AudioFile>>>> {
     pub fn from_audio_file(audio: Arc<AudioFile>, settings: AudioSettings) -> Self {
         Self {
             source: AudioDecoder::new(audio).expect("Failed to create audio decoder"),
@@ -27,6 +32,16 @@ impl AudioData<AudioDecoder<Arc<AudioFile>>> {
     }
 }
 
+impl<R: Read + Seek> AudioData<AudioDecoder<AudioStreamReader<R>>> {
+    /// Plays the audio streamed from the reader, without loading the whole file into memory
+    pub fn from_reader(reader: R, settings: AudioSettings) -> Result<Self> {
+        Ok(Self {
+            source: AudioDecoder::from_reader(reader)?,
+            settings,
+        })
+    }
+}
+
 impl<S: AudioFrameSource + Send + 'static> SoundData for AudioData<S> {
     type Error = anyhow::Error;
     type Handle = AudioHandle;
@@ -0,0 +1,156 @@
+//! Automatic lowering of the BGM volume while a voice is playing ("voice focus" in the original engine settings).
+//!
+//! The state is shared with the audio thread through atomics, so the decision to duck is made per sample.
+
+use std::sync::{
+    atomic::{AtomicBool, AtomicU32, Ordering},
+    Arc,
+};
+
+use shin_core::{
+    time::{Ticks, Tween},
+    vm::command::types::Volume,
+};
+
+/// Configures how the BGM is ducked when a voice is playing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DuckingSettings {
+    pub enabled: bool,
+    /// The volume multiplier applied to the ducked sounds while a voice is playing
+    pub volume: Volume,
+    /// The transition used to lower the volume when a voice starts
+    pub attack: Tween,
+    /// The transition used to restore the volume when the last voice stops
+    pub release: Tween,
+}
+
+impl Default for DuckingSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            volume: Volume(0.5),
+            attack: Tween::linear(Ticks::from_millis(100.0)),
+            release: Tween::linear(Ticks::from_millis(500.0)),
+        }
+    }
+}
+
+pub(crate) struct DuckerShared {
+    active_voices: AtomicU32,
+    enabled: AtomicBool,
+    volume: AtomicU32,
+    attack: AtomicU32,
+    release: AtomicU32,
+}
+
+impl DuckerShared {
+    fn new(settings: DuckingSettings) -> Self {
+        let result = Self {
+            active_voices: AtomicU32::new(0),
+            enabled: AtomicBool::new(false),
+            volume: AtomicU32::new(0),
+            attack: AtomicU32::new(0),
+            release: AtomicU32::new(0),
+        };
+        result.store_settings(settings);
+        result
+    }
+
+    fn store_settings(&self, settings: DuckingSettings) {
+        self.enabled.store(settings.enabled, Ordering::SeqCst);
+        self.volume
+            .store(settings.volume.0.to_bits(), Ordering::SeqCst);
+        self.attack.store(
+            settings.attack.duration.as_f32().to_bits(),
+            Ordering::SeqCst,
+        );
+        self.release.store(
+            settings.release.duration.as_f32().to_bits(),
+            Ordering::SeqCst,
+        );
+    }
+
+    /// Returns the volume the ducked sounds should be transitioning to, together with the tween to use
+    pub fn target(&self) -> (f32, Tween) {
+        let load = |v: &AtomicU32| f32::from_bits(v.load(Ordering::Relaxed));
+
+        if self.enabled.load(Ordering::Relaxed) && self.active_voices.load(Ordering::Relaxed) > 0 {
+            (
+                load(&self.volume),
+                Tween::linear(Ticks::from_f32(load(&self.attack))),
+            )
+        } else {
+            (1.0, Tween::linear(Ticks::from_f32(load(&self.release))))
+        }
+    }
+}
+
+/// A handle to the ducking state of an [`AudioManager`](crate::AudioManager)
+#[derive(Clone)]
+pub struct Ducker {
+    shared: Arc<DuckerShared>,
+}
+
+impl Ducker {
+    pub(crate) fn new(settings: DuckingSettings) -> Self {
+        Self {
+            shared: Arc::new(DuckerShared::new(settings)),
+        }
+    }
+
+    pub fn set_settings(&self, settings: DuckingSettings) {
+        self.shared.store_settings(settings);
+    }
+
+    /// Returns `true` if at least one voice is playing right now
+    pub fn is_voice_active(&self) -> bool {
+        self.shared.active_voices.load(Ordering::SeqCst) > 0
+    }
+
+    pub(crate) fn shared(&self) -> &DuckerShared {
+        &self.shared
+    }
+
+    pub(crate) fn voice_guard(&self) -> VoiceGuard {
+        self.shared.active_voices.fetch_add(1, Ordering::SeqCst);
+        VoiceGuard {
+            ducker: self.clone(),
+            active: true,
+        }
+    }
+}
+
+/// Keeps the BGM ducked while alive (or until [`VoiceGuard::release`] is called)
+pub(crate) struct VoiceGuard {
+    ducker: Ducker,
+    active: bool,
+}
+
+impl VoiceGuard {
+    pub fn release(&mut self) {
+        if std::mem::take(&mut self.active) {
+            self.ducker
+                .shared
+                .active_voices
+                .fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+}
+
+impl Drop for VoiceGuard {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+/// Specifies how a sound participates in the ducking
+#[derive(Clone, Default)]
+pub enum DuckingRole {
+    /// The sound is not affected by the ducking and does not cause it
+    #[default]
+    None,
+    /// The sound is a voice: while it's playing, the [`DuckingRole::Ducked`] sounds are lowered
+    Voice(Ducker),
+    /// The sound is lowered while a voice is playing (used for BGM)
+    Ducked(Ducker),
+}
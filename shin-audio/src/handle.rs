@@ -57,6 +57,30 @@ impl AudioHandle {
             .map_err(|_| anyhow!("Command queue full"))
     }
 
+    /// Fades out the sound to silence with the given tween and then
+    /// pauses playback, keeping the position.
+    pub fn pause(&mut self, tween: Tween) -> anyhow::Result<()> {
+        self.command_producer
+            .try_push(Command::Pause(tween))
+            .map_err(|_| anyhow!("Command queue full"))
+    }
+
+    /// Resumes a paused sound, fading it in with the given tween.
+    pub fn resume(&mut self, tween: Tween) -> anyhow::Result<()> {
+        self.command_producer
+            .try_push(Command::Resume(tween))
+            .map_err(|_| anyhow!("Command queue full"))
+    }
+
+    /// Moves the playback to the given position, relative to the start of the sound.
+    ///
+    /// The source has to support seeking. The position reported by [`AudioHandle::position`] is updated once the command is processed.
+    pub fn seek(&mut self, position: Ticks) -> anyhow::Result<()> {
+        self.command_producer
+            .try_push(Command::Seek(position))
+            .map_err(|_| anyhow!("Command queue full"))
+    }
+
     /// Returns the current playback position of the sound.
     pub fn position(&self) -> Ticks {
         Ticks::from_millis(
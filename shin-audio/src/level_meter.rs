@@ -0,0 +1,87 @@
+//! Measurement of the loudness of a track, used to animate the mouths of the characters when their voice is playing.
+//!
+//! An effect on the track follows the peak amplitude with a fast attack and a slow release, the game thread reads the current value through the handle.
+
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc,
+};
+
+use kira::{
+    clock::clock_info::ClockInfoProvider,
+    modulator::value_provider::ModulatorValueProvider,
+    track::effect::{Effect, EffectBuilder},
+    Frame,
+};
+
+/// How fast the level follows a louder signal, in seconds to reach ~63% of it
+const ATTACK_TIME: f64 = 0.01;
+/// How fast the level falls when the signal gets quieter
+const RELEASE_TIME: f64 = 0.08;
+
+/// A handle to the level measured by a [`LevelMeterBuilder`] effect
+#[derive(Clone)]
+pub struct LevelMeter {
+    /// The bits of an `f32` in `[0, 1]`
+    level: Arc<AtomicU32>,
+}
+
+impl LevelMeter {
+    /// The current peak amplitude of the track, `0.0` when it's silent
+    pub fn level(&self) -> f32 {
+        f32::from_bits(self.level.load(Ordering::Relaxed))
+    }
+}
+
+struct LevelMeterEffect {
+    level: Arc<AtomicU32>,
+    envelope: f64,
+}
+
+impl Effect for LevelMeterEffect {
+    fn process(
+        &mut self,
+        input: Frame,
+        dt: f64,
+        _clock_info_provider: &ClockInfoProvider,
+        _modulator_value_provider: &ModulatorValueProvider,
+    ) -> Frame {
+        let peak = input.left.abs().max(input.right.abs()).min(1.0) as f64;
+        let time = if peak > self.envelope {
+            ATTACK_TIME
+        } else {
+            RELEASE_TIME
+        };
+        self.envelope += (peak - self.envelope) * (1.0 - (-dt / time).exp());
+
+        self.level
+            .store((self.envelope as f32).to_bits(), Ordering::Relaxed);
+        input
+    }
+}
+
+/// Measures the level of a track, without changing the audio passing through it
+#[derive(Default)]
+pub struct LevelMeterBuilder;
+
+impl LevelMeterBuilder {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl EffectBuilder for LevelMeterBuilder {
+    type Handle = LevelMeter;
+
+    fn build(self) -> (Box<dyn Effect>, Self::Handle) {
+        let level = Arc::new(AtomicU32::new(0.0f32.to_bits()));
+
+        (
+            Box::new(LevelMeterEffect {
+                level: level.clone(),
+                envelope: 0.0,
+            }),
+            LevelMeter { level },
+        )
+    }
+}
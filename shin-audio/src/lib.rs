@@ -1,16 +1,25 @@
 //! Glue together `shin-core` and `kira` to provide an API to play NXA audio files.
 
+mod bus_meter;
+mod capture;
 mod data;
+mod ducking;
 mod handle;
+mod level_meter;
 mod manager;
+mod offline;
 mod resampler;
 mod sound;
 
+pub use bus_meter::{BusLevels, BusMeter, BusMeterBuilder};
+pub use capture::MixCapture;
 pub use data::AudioData;
+pub use ducking::{Ducker, DuckingRole, DuckingSettings};
 pub use handle::AudioHandle;
 use kira::track::TrackId;
+pub use level_meter::{LevelMeter, LevelMeterBuilder};
 pub use manager::AudioManager;
-pub use shin_core::format::audio::AudioFile;
+pub use shin_core::format::audio::{AudioFile, LoopRegion};
 use shin_core::{
     time::Tween,
     vm::command::types::{Pan, Volume},
@@ -19,8 +28,10 @@ use shin_core::{
 pub struct AudioSettings {
     pub track: TrackId,
     pub fade_in: Tween,
-    pub loop_start: Option<u32>,
+    /// If set, the sound plays the intro (everything before the region start) once and then repeats the region indefinitely
+    pub loop_region: Option<LoopRegion>,
     pub volume: Volume,
     pub pan: Pan,
+    pub ducking: DuckingRole,
     // TODO: support play speed (needs research)
 }
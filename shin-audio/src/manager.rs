@@ -1,34 +1,161 @@
-use std::sync::Mutex;
+use std::{path::Path, sync::Mutex, time::Duration};
 
-use kira::{manager::AudioManagerSettings, sound::SoundData};
+use anyhow::Result;
+use kira::{
+    manager::{
+        backend::{
+            cpal::CpalBackend,
+            mock::{MockBackend, MockBackendSettings},
+        },
+        AudioManagerSettings,
+    },
+    sound::SoundData,
+    track::{TrackBuilder, TrackHandle},
+};
 
-type Backend = kira::manager::backend::cpal::CpalBackend;
+use crate::{
+    bus_meter::{BusMeter, BusMeterBuilder},
+    capture::{MixCapture, MixTapBuilder},
+    offline::OfflineOutput,
+    Ducker, DuckingSettings,
+};
+
+enum Backend {
+    Device(kira::manager::AudioManager<CpalBackend>),
+    /// Renders to a file when the virtual clock is advanced, see [`AudioManager::new_offline`]
+    Offline {
+        manager: kira::manager::AudioManager<MockBackend>,
+        output: OfflineOutput,
+    },
+}
 
 pub struct AudioManager {
-    manager: Mutex<kira::manager::AudioManager<Backend>>,
+    backend: Mutex<Backend>,
+    ducker: Ducker,
+    mix_capture: MixCapture,
+    main_meter: BusMeter,
 }
 
 impl AudioManager {
     #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
-        let manager = kira::manager::AudioManager::new(AudioManagerSettings::default())
+        let mut settings = AudioManagerSettings::<CpalBackend>::default();
+        let mix_capture = settings.main_track_builder.add_effect(MixTapBuilder);
+        let main_meter = settings
+            .main_track_builder
+            .add_effect(BusMeterBuilder::new());
+
+        let manager = kira::manager::AudioManager::new(settings)
             .expect("Failed to create kira audio manager");
 
         Self {
-            manager: Mutex::new(manager),
+            backend: Mutex::new(Backend::Device(manager)),
+            ducker: Ducker::new(DuckingSettings::default()),
+            mix_capture,
+            main_meter,
         }
     }
 
+    /// Creates a manager that renders the mix to a WAV file instead of playing it
+    ///
+    /// Nothing is rendered until the virtual clock is advanced with [`AudioManager::advance`], which makes the timing of the sounds reproducible.
+    /// Call [`AudioManager::finish`] when done to finalize the file.
+    pub fn new_offline(output: &Path, sample_rate: u32) -> Result<Self> {
+        let mut settings = AudioManagerSettings::<MockBackend> {
+            backend_settings: MockBackendSettings { sample_rate },
+            ..Default::default()
+        };
+        let mix_capture = settings.main_track_builder.add_effect(MixTapBuilder);
+        let main_meter = settings
+            .main_track_builder
+            .add_effect(BusMeterBuilder::new());
+
+        let manager = kira::manager::AudioManager::new(settings)
+            .map_err(|_| anyhow::anyhow!("Failed to create offline kira audio manager"))?;
+
+        Ok(Self {
+            backend: Mutex::new(Backend::Offline {
+                manager,
+                output: OfflineOutput::new(output, sample_rate)?,
+            }),
+            ducker: Ducker::new(DuckingSettings::default()),
+            mix_capture,
+            main_meter,
+        })
+    }
+
     pub fn play<S: SoundData>(&self, data: S) -> S::Handle
     where
         S::Error: std::fmt::Debug,
     {
-        let mut manager = self.manager.lock().unwrap();
+        let mut backend = self.backend.lock().unwrap();
+
+        match &mut *backend {
+            Backend::Device(manager) => manager.play(data),
+            Backend::Offline { manager, .. } => manager.play(data),
+        }
+        .expect("Failed to start playing audio")
+    }
+
+    pub fn add_sub_track(&self, builder: TrackBuilder) -> TrackHandle {
+        let mut backend = self.backend.lock().unwrap();
+
+        match &mut *backend {
+            Backend::Device(manager) => manager.add_sub_track(builder),
+            Backend::Offline { manager, .. } => manager.add_sub_track(builder),
+        }
+        .expect("Failed to create a track")
+    }
+
+    /// Returns `true` if the mix is rendered to a file (see [`AudioManager::new_offline`])
+    pub fn is_offline(&self) -> bool {
+        matches!(*self.backend.lock().unwrap(), Backend::Offline { .. })
+    }
+
+    /// Advances the virtual clock of an offline manager, rendering the mix for this duration
+    ///
+    /// Does nothing when playing to the device, as the device drives the mix.
+    pub fn advance(&self, duration: Duration) -> Result<()> {
+        let mut backend = self.backend.lock().unwrap();
+
+        match &mut *backend {
+            Backend::Device(_) => Ok(()),
+            Backend::Offline { manager, output } => output.advance(manager.backend_mut(), duration),
+        }
+    }
+
+    /// The position of the virtual clock of an offline manager
+    pub fn offline_time(&self) -> Option<Duration> {
+        match &*self.backend.lock().unwrap() {
+            Backend::Device(_) => None,
+            Backend::Offline { output, .. } => Some(output.time()),
+        }
+    }
+
+    /// Finalizes the file of an offline manager, does nothing when playing to the device
+    pub fn finish(&self) -> Result<()> {
+        match &mut *self.backend.lock().unwrap() {
+            Backend::Device(_) => Ok(()),
+            Backend::Offline { output, .. } => output.finish(),
+        }
+    }
+
+    /// Returns the handle used to mark sounds as voices or as ducked by voices
+    pub fn ducker(&self) -> &Ducker {
+        &self.ducker
+    }
+
+    pub fn set_ducking_settings(&self, settings: DuckingSettings) {
+        self.ducker.set_settings(settings);
+    }
 
-        manager.play(data).expect("Failed to start playing audio")
+    /// Returns the handle used to record the final mix
+    pub fn mix_capture(&self) -> &MixCapture {
+        &self.mix_capture
     }
 
-    pub fn kira_manager(&self) -> &Mutex<kira::manager::AudioManager<Backend>> {
-        &self.manager
+    /// Returns the meter of the final mix
+    pub fn main_meter(&self) -> &BusMeter {
+        &self.main_meter
     }
 }
@@ -0,0 +1,77 @@
+//! Rendering of the mix to a WAV file instead of the audio device, for debugging the audio timing.
+//!
+//! The mix is advanced by a virtual clock controlled by the caller, so the result doesn't depend on the device latency or on the game running in real time.
+
+use std::{fs::File, io::BufWriter, path::Path, time::Duration};
+
+use anyhow::{Context, Result};
+use kira::manager::backend::mock::MockBackend;
+
+/// How many frames are rendered between the processing of the commands (like starting a sound), similar to the buffer of an audio device
+const BLOCK_SIZE: u64 = 256;
+
+pub(crate) struct OfflineOutput {
+    /// Taken when the output is finished
+    writer: Option<hound::WavWriter<BufWriter<File>>>,
+    sample_rate: u32,
+    clock: Duration,
+    rendered_frames: u64,
+}
+
+impl OfflineOutput {
+    pub fn new(path: &Path, sample_rate: u32) -> Result<Self> {
+        let file = File::create(path).with_context(|| format!("Creating {}", path.display()))?;
+        let writer = hound::WavWriter::new(
+            BufWriter::new(file),
+            hound::WavSpec {
+                channels: 2,
+                sample_rate,
+                bits_per_sample: 32,
+                sample_format: hound::SampleFormat::Float,
+            },
+        )?;
+
+        Ok(Self {
+            writer: Some(writer),
+            sample_rate,
+            clock: Duration::ZERO,
+            rendered_frames: 0,
+        })
+    }
+
+    /// The position of the virtual clock
+    pub fn time(&self) -> Duration {
+        self.clock
+    }
+
+    /// Advances the virtual clock, rendering the mix up to the new time
+    pub fn advance(&mut self, backend: &mut MockBackend, duration: Duration) -> Result<()> {
+        let Some(writer) = &mut self.writer else {
+            return Ok(());
+        };
+
+        self.clock += duration;
+        // computed from the clock and not accumulated, so that the rounding doesn't drift
+        let target_frames = (self.clock.as_secs_f64() * self.sample_rate as f64) as u64;
+        while self.rendered_frames < target_frames {
+            backend.on_start_processing();
+            let block = (target_frames - self.rendered_frames).min(BLOCK_SIZE);
+            for _ in 0..block {
+                let frame = backend.process();
+                writer.write_sample(frame.left)?;
+                writer.write_sample(frame.right)?;
+            }
+            self.rendered_frames += block;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the WAV header, nothing is rendered after this
+    pub fn finish(&mut self) -> Result<()> {
+        if let Some(writer) = self.writer.take() {
+            writer.finalize().context("Finalizing the WAV file")?;
+        }
+        Ok(())
+    }
+}
@@ -12,13 +12,17 @@ use kira::{
 };
 use ringbuf::{traits::Consumer as _, HeapCons};
 use shin_core::{
-    format::audio::{AudioFrameSource, AudioSource},
+    format::audio::{AudioFrameSource, AudioSource, LoopRegion},
     time::{Ticks, Tween, Tweener},
     vm::command::types::{AudioWaitStatus, Pan, Volume},
 };
 use tracing::{debug, warn};
 
-use crate::{resampler::Resampler, AudioData};
+use crate::{
+    ducking::{Ducker, DuckingRole, VoiceGuard},
+    resampler::Resampler,
+    AudioData,
+};
 
 pub const COMMAND_BUFFER_CAPACITY: usize = 8;
 
@@ -27,6 +31,9 @@ pub enum Command {
     SetVolume(Volume, Tween),
     SetPanning(Pan, Tween),
     Stop(Tween),
+    Pause(Tween),
+    Resume(Tween),
+    Seek(Ticks),
 }
 
 pub(crate) struct Shared {
@@ -54,6 +61,11 @@ pub enum PlaybackState {
     /// The sound is playing normally.
     Playing,
     /// The sound is fading out, and when the fade-out
+    /// is finished, playback will be paused.
+    Pausing,
+    /// The sound is paused and can be resumed.
+    Paused,
+    /// The sound is fading out, and when the fade-out
     /// is finished, playback will stop.
     Stopping,
     /// The sound has stopped and can no longer be resumed.
@@ -62,17 +74,21 @@ pub enum PlaybackState {
 
 pub struct SampleProvider<S: AudioFrameSource + Send> {
     source: AudioSource<S>,
-    loop_start: Option<u32>,
     resampler: Resampler,
     fractional_position: f64,
     reached_eof: bool,
 }
 
 impl<S: AudioFrameSource + Send> SampleProvider<S> {
-    fn new(audio: S, loop_start: Option<u32>) -> Self {
+    fn new(audio: S, loop_region: Option<LoopRegion>) -> Self {
+        let mut source = AudioSource::new(audio);
+        // the looping is handled by the source itself, so that the jump from the loop end to the loop start is sample-accurate
+        if let Err(e) = source.set_loop_region(loop_region) {
+            warn!("Playing the sound without looping: {:#}", e);
+        }
+
         Self {
-            source: AudioSource::new(audio),
-            loop_start,
+            source,
             resampler: Resampler::new(0),
             fractional_position: 0.0,
             reached_eof: false,
@@ -83,16 +99,8 @@ impl<S: AudioFrameSource + Send> SampleProvider<S> {
         let frame = match self.source.read_sample() {
             Some((left, right)) => Frame { left, right },
             None => {
-                if let Some(loop_start) = self.loop_start {
-                    self.source
-                        .samples_seek(loop_start)
-                        .expect("Could not seek to loop start");
-
-                    return self.push_frame_to_resampler();
-                } else {
-                    self.reached_eof = true;
-                    Frame::ZERO
-                }
+                self.reached_eof = true;
+                Frame::ZERO
             }
         };
 
@@ -100,6 +108,15 @@ impl<S: AudioFrameSource + Send> SampleProvider<S> {
         self.resampler.push_frame(frame, next_sample_index - 1);
     }
 
+    /// Seeks to the sample position (not taking the pre-skip into account), the loop region is still respected afterwards
+    fn seek(&mut self, sample_position: u32) -> anyhow::Result<()> {
+        self.source.samples_seek(sample_position)?;
+        self.resampler = Resampler::new(sample_position);
+        self.fractional_position = 0.0;
+        self.reached_eof = false;
+        Ok(())
+    }
+
     fn next(&mut self, dt: f64) -> Frame {
         let out = self.resampler.get(self.fractional_position as f32);
         self.fractional_position += dt * self.source.sample_rate() as f64;
@@ -121,6 +138,11 @@ pub struct AudioSound<S: AudioFrameSource + Send> {
     panning: Tweener,
     volume_fade: Tweener,
     sample_provider: SampleProvider<S>,
+    /// Present if this sound is a voice, keeps the ducked sounds lowered until released
+    voice_guard: Option<VoiceGuard>,
+    /// Present if this sound should be lowered while voices are playing
+    ducked_by: Option<Ducker>,
+    duck: Tweener,
 }
 
 impl<S: AudioFrameSource + Send> AudioSound<S> {
@@ -132,6 +154,12 @@ impl<S: AudioFrameSource + Send> AudioSound<S> {
 
         let shared = Arc::new(Shared::new());
 
+        let (voice_guard, ducked_by) = match data.settings.ducking {
+            DuckingRole::None => (None, None),
+            DuckingRole::Voice(ducker) => (Some(ducker.voice_guard()), None),
+            DuckingRole::Ducked(ducker) => (None, Some(ducker)),
+        };
+
         AudioSound {
             track_id: data.settings.track,
             command_consumer,
@@ -140,7 +168,10 @@ impl<S: AudioFrameSource + Send> AudioSound<S> {
             volume: Tweener::new(data.settings.volume.0),
             panning: Tweener::new(data.settings.pan.0),
             volume_fade,
-            sample_provider: SampleProvider::new(data.source, data.settings.loop_start),
+            sample_provider: SampleProvider::new(data.source, data.settings.loop_region),
+            voice_guard,
+            ducked_by,
+            duck: Tweener::new(1.0),
         }
     }
 
@@ -149,6 +180,29 @@ impl<S: AudioFrameSource + Send> AudioSound<S> {
         self.volume_fade.enqueue_now(0.0, fade_out_tween);
     }
 
+    fn pause(&mut self, fade_out_tween: Tween) {
+        if self.state == PlaybackState::Playing {
+            self.state = PlaybackState::Pausing;
+            self.volume_fade.enqueue_now(0.0, fade_out_tween);
+        }
+    }
+
+    fn resume(&mut self, fade_in_tween: Tween) {
+        if matches!(self.state, PlaybackState::Pausing | PlaybackState::Paused) {
+            self.state = PlaybackState::Playing;
+            self.volume_fade.enqueue_now(1.0, fade_in_tween);
+        }
+    }
+
+    fn seek(&mut self, position: Ticks) {
+        let sample_position = (position.as_seconds() as f64
+            * self.sample_provider.source.sample_rate() as f64)
+            as u32;
+        if let Err(e) = self.sample_provider.seek(sample_position) {
+            warn!("Could not seek the sound to {:?}: {:?}", position, e);
+        }
+    }
+
     fn wait_status(&self) -> AudioWaitStatus {
         let mut result = AudioWaitStatus::empty();
 
@@ -188,6 +242,9 @@ impl<S: AudioFrameSource + Send> Sound for AudioSound<S> {
                 Command::SetVolume(volume, tween) => self.volume.enqueue_now(volume.0, tween),
                 Command::SetPanning(panning, tween) => self.panning.enqueue_now(panning.0, tween),
                 Command::Stop(tween) => self.stop(tween),
+                Command::Pause(tween) => self.pause(tween),
+                Command::Resume(tween) => self.resume(tween),
+                Command::Seek(position) => self.seek(position),
             }
         }
 
@@ -217,9 +274,25 @@ impl<S: AudioFrameSource + Send> Sound for AudioSound<S> {
         self.panning.update(dt_ticks);
         self.volume_fade.update(dt_ticks);
 
+        // the ducking target is re-evaluated every sample, so that the transition starts exactly when the voice does
+        if let Some(ducker) = &self.ducked_by {
+            let (target, tween) = ducker.shared().target();
+            if target != self.duck.target_value() {
+                self.duck.enqueue_now(target, tween);
+            }
+        }
+        self.duck.update(dt_ticks);
+
         if self.state == PlaybackState::Stopping && self.volume_fade.is_idle() {
             self.state = PlaybackState::Stopped
         }
+        if self.state == PlaybackState::Pausing && self.volume_fade.is_idle() {
+            self.state = PlaybackState::Paused
+        }
+        // the position doesn't move while paused
+        if self.state == PlaybackState::Paused {
+            return Frame::ZERO;
+        }
 
         let mut f = self.sample_provider.next(dt);
 
@@ -227,8 +300,14 @@ impl<S: AudioFrameSource + Send> Sound for AudioSound<S> {
             self.state = PlaybackState::Stopped;
         }
 
+        if self.state == PlaybackState::Stopped {
+            if let Some(guard) = &mut self.voice_guard {
+                guard.release();
+            }
+        }
+
         let pan = self.panning.value();
-        let volume = self.volume_fade.value() * self.volume.value();
+        let volume = self.volume_fade.value() * self.volume.value() * self.duck.value();
 
         f *= volume;
         if pan != 0.0 {
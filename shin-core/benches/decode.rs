@@ -0,0 +1,158 @@
+//! Benchmarks of the format decoders
+//!
+//! The lz77 decompression and the dictionary decoding are benchmarked on synthetic data.
+//! The game files can't be distributed, so the benchmarks of the whole decoders are run only when the paths to the files are set in the environment:
+//!
+//! - `SHIN_BENCH_PICTURE`: a PIC file
+//! - `SHIN_BENCH_BUSTUP`: a BUP file
+//! - `SHIN_BENCH_AUDIO`: a NXA file
+//! - `SHIN_BENCH_FONT`: a FNT file
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use image::RgbaImage;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use shin_core::format::{
+    audio::{read_audio, AudioDecoder, AudioSource},
+    bustup::read_bustup,
+    font::{read_lazy_font, GlyphTrait},
+    lz77,
+    picture::{read_picture, read_texture, SimpleMergedPicture},
+};
+
+/// Reads the game file from the path in the environment `variable`, if it's set
+fn game_file(variable: &str) -> Option<Vec<u8>> {
+    let path = std::env::var_os(variable)?;
+    let data =
+        std::fs::read(&path).unwrap_or_else(|e| panic!("Reading {} ({:?}): {}", variable, path, e));
+    Some(data)
+}
+
+/// Generates a valid lz77 stream of about `size` decompressed bytes, with the mix of literals and references similar to the game pictures
+fn synthetic_lz77_stream(rng: &mut StdRng, size: usize) -> Vec<u8> {
+    let mut stream = Vec::new();
+    let mut decompressed_len = 0usize;
+    while decompressed_len < size {
+        let map_position = stream.len();
+        stream.push(0);
+        for i in 0..8 {
+            if decompressed_len == 0 || rng.gen_bool(0.4) {
+                stream.push(rng.gen());
+                decompressed_len += 1;
+            } else {
+                stream[map_position] |= 1 << i;
+                let back_offset = rng.gen_range(1..=decompressed_len.min(1 << 12));
+                let len = rng.gen_range(0..16u16);
+                let spec = (len << 12) | (back_offset - 1) as u16;
+                stream.extend_from_slice(&spec.to_be_bytes());
+                decompressed_len += len as usize + 3;
+            }
+        }
+    }
+    stream
+}
+
+/// Uncompressed dictionary encoded texture data with a separate alpha plane
+fn synthetic_dict_texture(rng: &mut StdRng, width: u32, height: u32) -> Vec<u8> {
+    let stride = ((width + 3) & !3) as usize;
+    let mut data = vec![0; 0x400 + stride * height as usize * 2];
+    rng.fill(&mut data[..0x400]);
+    for color in data[..0x400].chunks_exact_mut(4) {
+        color[3] = 0xff;
+    }
+    rng.fill(&mut data[0x400..]);
+    data
+}
+
+fn lz77(c: &mut Criterion) {
+    let mut rng = StdRng::seed_from_u64(42);
+    let mut group = c.benchmark_group("lz77");
+    for size in [64 * 1024, 4 * 1024 * 1024] {
+        let stream = synthetic_lz77_stream(&mut rng, size);
+        let mut output = Vec::new();
+        lz77::decompress::<12>(&stream, &mut output).unwrap();
+
+        group.throughput(Throughput::Bytes(output.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &stream, |b, stream| {
+            b.iter(|| {
+                output.clear();
+                lz77::decompress::<12>(stream, &mut output).unwrap();
+            })
+        });
+    }
+    group.finish();
+}
+
+fn decode_dict(c: &mut Criterion) {
+    let mut rng = StdRng::seed_from_u64(42);
+    let mut group = c.benchmark_group("decode_dict");
+    for (width, height) in [(256, 256), (1920, 1080)] {
+        let data = synthetic_dict_texture(&mut rng, width, height);
+        let mut image = RgbaImage::new(width, height);
+
+        group.throughput(Throughput::Elements(width as u64 * height as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(format!("{}x{}", width, height)),
+            &data,
+            |b, data| b.iter(|| read_texture(data, 0, &mut image, true, false).unwrap()),
+        );
+    }
+    group.finish();
+}
+
+fn picture(c: &mut Criterion) {
+    let Some(data) = game_file("SHIN_BENCH_PICTURE") else {
+        return;
+    };
+    shin_core::create_task_pools();
+
+    c.bench_function("read_picture", |b| {
+        b.iter(|| read_picture::<SimpleMergedPicture>(&data, ()).unwrap())
+    });
+}
+
+fn bustup(c: &mut Criterion) {
+    let Some(data) = game_file("SHIN_BENCH_BUSTUP") else {
+        return;
+    };
+    shin_core::create_task_pools();
+
+    c.bench_function("read_bustup", |b| b.iter(|| read_bustup(&data).unwrap()));
+}
+
+fn audio(c: &mut Criterion) {
+    let Some(data) = game_file("SHIN_BENCH_AUDIO") else {
+        return;
+    };
+    let audio = read_audio(&data).unwrap();
+
+    let mut group = c.benchmark_group("audio");
+    group.throughput(Throughput::Elements(audio.info().num_samples as u64));
+    group.bench_function("decode", |b| {
+        b.iter(|| {
+            let mut source = AudioSource::new(AudioDecoder::new(&audio).unwrap());
+            while source.read_sample().is_some() {}
+        })
+    });
+    group.finish();
+}
+
+fn font(c: &mut Criterion) {
+    let Some(data) = game_file("SHIN_BENCH_FONT") else {
+        return;
+    };
+    let font = read_lazy_font(&mut std::io::Cursor::new(data)).unwrap();
+
+    let mut group = c.benchmark_group("font");
+    group.throughput(Throughput::Elements(font.get_glyphs().len() as u64));
+    group.bench_function("decompress_glyphs", |b| {
+        b.iter(|| {
+            for glyph in font.get_glyphs().values() {
+                glyph.decompress().unwrap();
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, lz77, decode_dict, picture, bustup, audio, font);
+criterion_main!(benches);
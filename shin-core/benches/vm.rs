@@ -0,0 +1,121 @@
+//! Benchmark of the VM instruction throughput
+//!
+//! Runs a synthetic loop of arithmetic instructions, yielding a `WAIT` command on each iteration, the same way the game code yields commands between the computations.
+
+use std::io::Cursor;
+
+use binrw::BinWrite;
+use bytes::Bytes;
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use shin_core::{
+    format::scenario::{
+        instruction_elements::{CodeAddress, NumberSpec, Register, U8Bool, UntypedNumberSpec},
+        instructions::{BinaryOperation, BinaryOperationType, Instruction},
+        Scenario,
+    },
+    vm::{
+        command::{compiletime::WAIT, CommandResult, CompiletimeCommand},
+        Scripter,
+    },
+};
+
+/// Header and (empty) info tables of a scenario, the code follows right after
+const SCENARIO_HEADER: &[u8] =
+    b"SNR \xd8\x00\x00\x00\x00\x00\x00\x00\x06\x00\x00\x00\x13\x00\x00\x00\x00\x00\x00\
+    \x00\x00\x00\x00\x00\x00\x00\x00\x00\xbc\x00\x00\x00X\x00\x00\x00`\x00\x00\x00h\
+    \x00\x00\x00p\x00\x00\x00x\x00\x00\x00\x80\x00\x00\x00\x88\x00\x00\x00\x90\x00\
+    \x00\x00\x94\x00\x00\x00\x98\x00\x00\x00\x9c\x00\x00\x00\xa4\x00\x00\x00\xa8\x00\
+    \x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x04\x00\
+    \x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\
+    \x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x00\x00\
+    \x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x00\x00\
+    \x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\
+    \x00\x00";
+
+/// How many loop iterations (and, hence, commands) to run per benchmark iteration
+const ITERATIONS: usize = 1000;
+
+fn register(index: u16) -> NumberSpec {
+    NumberSpec::register(Register::from_regular_register(index))
+}
+
+fn bo(
+    ty: BinaryOperationType,
+    destination: u16,
+    left: NumberSpec,
+    right: NumberSpec,
+) -> Instruction {
+    Instruction::bo(BinaryOperation {
+        ty,
+        destination: Register::from_regular_register(destination),
+        left,
+        right,
+    })
+}
+
+/// Returns the scenario and the amount of instructions executed per loop iteration
+fn loop_scenario() -> (Scenario, usize) {
+    let loop_start = CodeAddress(SCENARIO_HEADER.len() as u32);
+    let instructions = [
+        bo(
+            BinaryOperationType::Add,
+            0,
+            register(0),
+            NumberSpec::constant(1),
+        ),
+        bo(
+            BinaryOperationType::Multiply,
+            1,
+            register(0),
+            NumberSpec::constant(3),
+        ),
+        bo(
+            BinaryOperationType::Modulo,
+            2,
+            register(1),
+            NumberSpec::constant(7),
+        ),
+        bo(BinaryOperationType::BitwiseXor, 3, register(3), register(2)),
+        Instruction::Command(CompiletimeCommand::WAIT(WAIT {
+            allow_interrupt: U8Bool(false),
+            wait_amount: NumberSpec::new(UntypedNumberSpec::Constant(0)),
+        })),
+        Instruction::j { target: loop_start },
+    ];
+
+    let mut cursor = Cursor::new(SCENARIO_HEADER.to_vec());
+    cursor.set_position(SCENARIO_HEADER.len() as u64);
+    for instruction in &instructions {
+        instruction.write(&mut cursor).unwrap();
+    }
+    let mut data = cursor.into_inner();
+    let size = data.len() as u32;
+    data[4..8].copy_from_slice(&size.to_le_bytes());
+
+    (
+        Scenario::new(Bytes::from(data)).unwrap(),
+        instructions.len(),
+    )
+}
+
+fn vm(c: &mut Criterion) {
+    let (scenario, loop_length) = loop_scenario();
+
+    let mut group = c.benchmark_group("vm");
+    group.throughput(Throughput::Elements((ITERATIONS * loop_length) as u64));
+    group.bench_function("arithmetic_loop", |b| {
+        b.iter(|| {
+            let mut scripter = Scripter::new(&scenario, 0, 42);
+            let mut result = CommandResult::None;
+            for _ in 0..ITERATIONS {
+                let command = scripter.run(result).unwrap();
+                result = command.execute_dummy().unwrap();
+            }
+            scripter
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, vm);
+criterion_main!(benches);
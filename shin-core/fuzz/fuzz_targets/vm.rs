@@ -0,0 +1,13 @@
+//! Runs random instruction sequences through the VM, checking that it doesn't panic.
+//!
+//! Malformed code (stack underflows, jumps to the end of the code, etc.) is allowed to fail with an error.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use shin_core_fuzz::{assemble, build_scenario, run, FuzzInstruction};
+
+fuzz_target!(|instructions: Vec<FuzzInstruction>| {
+    let scenario = build_scenario(&assemble(&instructions));
+    run(&scenario);
+});
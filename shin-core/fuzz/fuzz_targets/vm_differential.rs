@@ -0,0 +1,39 @@
+//! Runs random arithmetic instructions through the VM and the reference interpreter, checking that the registers end up the same.
+
+#![no_main]
+
+use binrw::BinWrite;
+use libfuzzer_sys::fuzz_target;
+use shin_core::format::scenario::instruction_elements::Register;
+use shin_core_fuzz::{
+    build_scenario,
+    reference::{ArithmeticInstruction, ReferenceInterpreter},
+    run, REGISTERS_COUNT,
+};
+
+fuzz_target!(|instructions: Vec<ArithmeticInstruction>| {
+    let mut code = std::io::Cursor::new(Vec::new());
+    // `run` starts the VM with a zero init value
+    let mut reference = ReferenceInterpreter::new(0);
+    for instruction in &instructions {
+        let Some(encoded) = instruction.to_instruction() else {
+            continue;
+        };
+        encoded.write(&mut code).unwrap();
+        reference.execute(instruction);
+    }
+
+    // no commands in the code, so the VM runs until the end of the code and fails to read the next instruction
+    let scripter = run(&build_scenario(&code.into_inner()));
+
+    for index in 0..REGISTERS_COUNT {
+        let register = Register::from_regular_register(index);
+        assert_eq!(
+            scripter.ctx().read_register(register),
+            reference.registers[index as usize],
+            "Register {:?} differs\n{:#?}",
+            register,
+            instructions
+        );
+    }
+});
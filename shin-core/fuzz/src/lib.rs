@@ -0,0 +1,428 @@
+//! Fuzzing harness for the scenario VM.
+//!
+//! The fuzz targets don't feed raw bytes to the VM: they generate instruction sequences, encode them with the binrw writers and wrap them into a minimal scenario.
+//! This way the fuzzer spends its time on the VM itself instead of the instruction decoder.
+//!
+//! See the `fuzz_targets` directory for the targets. Run them with `cargo +nightly fuzz run <target>` from the `shin-core` directory.
+
+pub mod reference;
+
+use std::io::Cursor;
+
+use arbitrary::Arbitrary;
+use binrw::BinWrite;
+use bytes::Bytes;
+use shin_core::{
+    format::scenario::{
+        instruction_elements::{CodeAddress, NumberSpec, Register, U8Bool, UntypedNumberSpec},
+        instructions::{
+            BinaryOperation, BinaryOperationType, Expression, ExpressionTerm, Instruction,
+            JumpCond, JumpCondType, UnaryOperation, UnaryOperationType,
+        },
+        types::Pad4,
+        Scenario,
+    },
+    vm::{
+        command::{compiletime::WAIT, CommandResult, CompiletimeCommand},
+        Scripter,
+    },
+};
+
+/// Header and (empty) info tables of a scenario, the code follows right after
+const SCENARIO_HEADER: &[u8] =
+    b"SNR \xd8\x00\x00\x00\x00\x00\x00\x00\x06\x00\x00\x00\x13\x00\x00\x00\x00\x00\x00\
+    \x00\x00\x00\x00\x00\x00\x00\x00\x00\xbc\x00\x00\x00X\x00\x00\x00`\x00\x00\x00h\
+    \x00\x00\x00p\x00\x00\x00x\x00\x00\x00\x80\x00\x00\x00\x88\x00\x00\x00\x90\x00\
+    \x00\x00\x94\x00\x00\x00\x98\x00\x00\x00\x9c\x00\x00\x00\xa4\x00\x00\x00\xa8\x00\
+    \x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x04\x00\
+    \x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\
+    \x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x00\x00\
+    \x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x00\x00\
+    \x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\
+    \x00\x00";
+
+/// How many commands to execute before giving up, the generated code can loop
+pub const MAX_COMMANDS: usize = 1000;
+
+/// Amount of registers the generated code uses, so that the operations have a chance to affect each other
+pub const REGISTERS_COUNT: u16 = 16;
+
+pub const UNARY_OPERATIONS: [UnaryOperationType; 4] = [
+    UnaryOperationType::Zero,
+    UnaryOperationType::Not16,
+    UnaryOperationType::Negate,
+    UnaryOperationType::Abs,
+];
+
+pub const BINARY_OPERATIONS: [BinaryOperationType; 18] = [
+    BinaryOperationType::MovRight,
+    BinaryOperationType::Zero,
+    BinaryOperationType::Add,
+    BinaryOperationType::Subtract,
+    BinaryOperationType::Multiply,
+    BinaryOperationType::Divide,
+    BinaryOperationType::Modulo,
+    BinaryOperationType::BitwiseAnd,
+    BinaryOperationType::BitwiseOr,
+    BinaryOperationType::BitwiseXor,
+    BinaryOperationType::LeftShift,
+    BinaryOperationType::RightShift,
+    BinaryOperationType::MultiplyReal,
+    BinaryOperationType::DivideReal,
+    BinaryOperationType::ATan2,
+    BinaryOperationType::SetBit,
+    BinaryOperationType::ClearBit,
+    BinaryOperationType::ACursedOperation,
+];
+
+pub const JUMP_CONDITIONS: [JumpCondType; 8] = [
+    JumpCondType::Equal,
+    JumpCondType::NotEqual,
+    JumpCondType::GreaterOrEqual,
+    JumpCondType::Greater,
+    JumpCondType::LessOrEqual,
+    JumpCondType::Less,
+    JumpCondType::BitwiseAndNotZero,
+    JumpCondType::BitSet,
+];
+
+/// All the expression terms, except for `Push`
+pub const EXPRESSION_OPERATIONS: [ExpressionTerm; 31] = [
+    ExpressionTerm::Add,
+    ExpressionTerm::Subtract,
+    ExpressionTerm::Multiply,
+    ExpressionTerm::Divide,
+    ExpressionTerm::Modulo,
+    ExpressionTerm::ShiftLeft,
+    ExpressionTerm::ShiftRight,
+    ExpressionTerm::BitwiseAnd,
+    ExpressionTerm::BitwiseOr,
+    ExpressionTerm::BitwiseXor,
+    ExpressionTerm::Negate,
+    ExpressionTerm::BitwiseNot,
+    ExpressionTerm::Abs,
+    ExpressionTerm::CmpEqual,
+    ExpressionTerm::CmpNotEqual,
+    ExpressionTerm::CmpGreaterOrEqual,
+    ExpressionTerm::CmpGreater,
+    ExpressionTerm::CmpLessOrEqual,
+    ExpressionTerm::CmpLess,
+    ExpressionTerm::CmpZero,
+    ExpressionTerm::CmpNotZero,
+    ExpressionTerm::LogicalAnd,
+    ExpressionTerm::LogicalOr,
+    ExpressionTerm::Select,
+    ExpressionTerm::MultiplyReal,
+    ExpressionTerm::DivideReal,
+    ExpressionTerm::Sin,
+    ExpressionTerm::Cos,
+    ExpressionTerm::Tan,
+    ExpressionTerm::Min,
+    ExpressionTerm::Max,
+];
+
+/// Picks an element of a non-empty slice using an arbitrary index
+pub fn pick<T: Copy>(items: &[T], index: u8) -> T {
+    items[index as usize % items.len()]
+}
+
+#[derive(Arbitrary, Debug, Copy, Clone)]
+pub enum FuzzNumber {
+    Constant(i32),
+    Register(u16),
+    Argument(u8),
+}
+
+impl FuzzNumber {
+    pub fn to_spec(self) -> NumberSpec {
+        match self {
+            FuzzNumber::Constant(value) => NumberSpec::constant(value),
+            FuzzNumber::Register(index) => NumberSpec::register(fuzz_register(index)),
+            FuzzNumber::Argument(index) => {
+                NumberSpec::register(Register::from_argument(index as u16 % 16))
+            }
+        }
+    }
+}
+
+pub fn fuzz_register(index: u16) -> Register {
+    Register::from_regular_register(index % REGISTERS_COUNT)
+}
+
+/// Builds a valid expression out of arbitrary terms
+///
+/// Operations without enough values on the stack get the missing ones from `fill`, and the leftover values are added together in the end.
+pub fn build_expression(terms: &[(u8, FuzzNumber)], fill: FuzzNumber) -> Expression {
+    let mut result = Vec::new();
+    let mut depth = 0;
+    // limit the size, so that the expression doesn't spill out of the inline storage all the time
+    for &(operation, number) in terms.iter().take(24) {
+        // use about half of the terms to push values
+        if operation & 1 == 0 {
+            result.push(ExpressionTerm::Push(number.to_spec()));
+            depth += 1;
+            continue;
+        }
+
+        let term = pick(&EXPRESSION_OPERATIONS, operation >> 1);
+        while depth < term.argument_count() {
+            result.push(ExpressionTerm::Push(fill.to_spec()));
+            depth += 1;
+        }
+        result.push(term);
+        depth = depth + 1 - term.argument_count();
+    }
+
+    if depth == 0 {
+        result.push(ExpressionTerm::Push(fill.to_spec()));
+        depth = 1;
+    }
+    while depth > 1 {
+        result.push(ExpressionTerm::Add);
+        depth -= 1;
+    }
+
+    Expression::new(result).expect("Generated an invalid expression")
+}
+
+/// A single generated instruction
+///
+/// Code addresses are represented as indices of the instructions, they are resolved when the code is assembled.
+#[derive(Arbitrary, Debug, Clone)]
+pub enum FuzzInstruction {
+    Uo {
+        ty: u8,
+        destination: u16,
+        source: FuzzNumber,
+    },
+    Bo {
+        ty: u8,
+        destination: u16,
+        left: FuzzNumber,
+        right: FuzzNumber,
+    },
+    Exp {
+        destination: u16,
+        terms: Vec<(u8, FuzzNumber)>,
+        fill: FuzzNumber,
+    },
+    Gt {
+        destination: u16,
+        index: FuzzNumber,
+        table: Vec<FuzzNumber>,
+    },
+    Jc {
+        condition: u8,
+        is_negated: bool,
+        left: FuzzNumber,
+        right: FuzzNumber,
+        target: u16,
+    },
+    J {
+        target: u16,
+    },
+    Gosub {
+        target: u16,
+    },
+    Retsub,
+    Jt {
+        index: FuzzNumber,
+        table: Vec<u16>,
+    },
+    Rnd {
+        destination: u16,
+        min: FuzzNumber,
+        max: FuzzNumber,
+    },
+    /// Pushes code addresses, the only thing the game uses the call stack for besides the return addresses
+    Push {
+        targets: Vec<u16>,
+    },
+    Pop {
+        destinations: Vec<u16>,
+    },
+    Call {
+        target: u16,
+        args: Vec<FuzzNumber>,
+    },
+    Return,
+}
+
+impl FuzzInstruction {
+    fn to_instruction(&self, resolve: impl Fn(u16) -> CodeAddress) -> Instruction {
+        // the list sizes are limited by the encoding
+        fn truncated<T: Clone>(items: &[T], limit: usize) -> &[T] {
+            &items[..items.len().min(limit)]
+        }
+
+        match self {
+            &FuzzInstruction::Uo {
+                ty,
+                destination,
+                source,
+            } => Instruction::uo(UnaryOperation {
+                ty: pick(&UNARY_OPERATIONS, ty),
+                destination: fuzz_register(destination),
+                source: source.to_spec(),
+            }),
+            &FuzzInstruction::Bo {
+                ty,
+                destination,
+                left,
+                right,
+            } => Instruction::bo(BinaryOperation {
+                ty: pick(&BINARY_OPERATIONS, ty),
+                destination: fuzz_register(destination),
+                left: left.to_spec(),
+                right: right.to_spec(),
+            }),
+            FuzzInstruction::Exp {
+                destination,
+                terms,
+                fill,
+            } => Instruction::exp {
+                dest: fuzz_register(*destination),
+                expr: build_expression(terms, *fill),
+            },
+            FuzzInstruction::Gt {
+                destination,
+                index,
+                table,
+            } => Instruction::gt {
+                dest: fuzz_register(*destination),
+                index: index.to_spec(),
+                table: truncated(table, 0xffff)
+                    .iter()
+                    .map(|v| Pad4(v.to_spec()))
+                    .collect(),
+            },
+            &FuzzInstruction::Jc {
+                condition,
+                is_negated,
+                left,
+                right,
+                target,
+            } => Instruction::jc {
+                cond: JumpCond {
+                    is_negated,
+                    condition: pick(&JUMP_CONDITIONS, condition),
+                },
+                left: left.to_spec(),
+                right: right.to_spec(),
+                target: resolve(target),
+            },
+            &FuzzInstruction::J { target } => Instruction::j {
+                target: resolve(target),
+            },
+            &FuzzInstruction::Gosub { target } => Instruction::gosub {
+                target: resolve(target),
+            },
+            FuzzInstruction::Retsub => Instruction::retsub {},
+            FuzzInstruction::Jt { index, table } => Instruction::jt {
+                index: index.to_spec(),
+                table: truncated(table, 0xffff)
+                    .iter()
+                    .map(|&target| resolve(target))
+                    .collect(),
+            },
+            &FuzzInstruction::Rnd {
+                destination,
+                min,
+                max,
+            } => Instruction::rnd {
+                dest: fuzz_register(destination),
+                min: min.to_spec(),
+                max: max.to_spec(),
+            },
+            FuzzInstruction::Push { targets } => Instruction::push {
+                values: truncated(targets, 0xff)
+                    .iter()
+                    .map(|&target| NumberSpec::constant(resolve(target).0 as i32))
+                    .collect(),
+            },
+            FuzzInstruction::Pop { destinations } => Instruction::pop {
+                dest: truncated(destinations, 0xff)
+                    .iter()
+                    .map(|&index| fuzz_register(index))
+                    .collect(),
+            },
+            FuzzInstruction::Call { target, args } => Instruction::call {
+                target: resolve(*target),
+                args: truncated(args, 0xff).iter().map(|v| v.to_spec()).collect(),
+            },
+            FuzzInstruction::Return => Instruction::r#return {},
+        }
+    }
+}
+
+/// A command that is executed after every generated instruction
+///
+/// This makes sure the [`Scripter::run`] returns regularly even if the generated code loops.
+fn yield_command() -> Instruction {
+    Instruction::Command(CompiletimeCommand::WAIT(WAIT {
+        allow_interrupt: U8Bool(false),
+        wait_amount: NumberSpec::new(UntypedNumberSpec::Constant(0)),
+    }))
+}
+
+fn encode(instructions: &[Instruction]) -> (Vec<u8>, Vec<CodeAddress>) {
+    let mut cursor = Cursor::new(Vec::new());
+    let mut positions = Vec::new();
+    for instruction in instructions {
+        positions.push(CodeAddress(
+            (SCENARIO_HEADER.len() as u64 + cursor.position()) as u32,
+        ));
+        instruction
+            .write(&mut cursor)
+            .expect("Failed to encode an instruction");
+    }
+    (cursor.into_inner(), positions)
+}
+
+/// Assembles the instructions into code, resolving the instruction indices to the code addresses
+///
+/// Indices past the end of the code point to the end of the code, executing it results in an error.
+pub fn assemble(instructions: &[FuzzInstruction]) -> Vec<u8> {
+    let lower = |resolve: &dyn Fn(u16) -> CodeAddress| {
+        instructions
+            .iter()
+            .flat_map(|instruction| [instruction.to_instruction(resolve), yield_command()])
+            .collect::<Vec<_>>()
+    };
+
+    // the code addresses are always encoded with 4 bytes, so the layout doesn't depend on the values
+    let (code, positions) = encode(&lower(&|_| CodeAddress(0)));
+    let end = CodeAddress((SCENARIO_HEADER.len() + code.len()) as u32);
+    let resolve = |index: u16| positions.get(index as usize * 2).copied().unwrap_or(end);
+
+    encode(&lower(&resolve)).0
+}
+
+/// Wraps the code into a scenario with empty info tables
+pub fn build_scenario(code: &[u8]) -> Scenario {
+    let mut data = SCENARIO_HEADER.to_vec();
+    data.extend_from_slice(code);
+    let size = data.len() as u32;
+    data[4..8].copy_from_slice(&size.to_le_bytes());
+
+    Scenario::new(Bytes::from(data)).expect("Failed to build a scenario")
+}
+
+/// Runs the scenario, executing the commands with [`RuntimeCommand::execute_dummy`](shin_core::vm::command::RuntimeCommand::execute_dummy)
+///
+/// Stops at an error, at the `EXIT` command or after [`MAX_COMMANDS`] commands.
+pub fn run(scenario: &Scenario) -> Scripter {
+    let mut scripter = Scripter::new(scenario, 0, 42);
+    let mut result = CommandResult::None;
+    for _ in 0..MAX_COMMANDS {
+        // errors are the expected outcome for the malformed code, only the panics are bugs
+        let Ok(command) = scripter.run(result) else {
+            break;
+        };
+        match command.execute_dummy() {
+            Some(r) => result = r,
+            None => break,
+        }
+    }
+    scripter
+}
@@ -0,0 +1,268 @@
+//! A simple reference interpreter for the arithmetic instructions, used for differential testing of the VM.
+//!
+//! It is written from the instruction documentation and deliberately computes everything in 64 bits, truncating the results, instead of using the wrapping operations like the VM does.
+//! The floating-point operations (`ATan2`, `Sin`, `Cos`, `Tan`) are not covered: the exact rounding isn't specified anyway.
+
+use arbitrary::Arbitrary;
+use shin_core::format::scenario::{
+    instruction_elements::{RegisterRepr, UntypedNumberSpec},
+    instructions::{
+        BinaryOperation, BinaryOperationType, ExpressionTerm, Instruction, UnaryOperationType,
+    },
+};
+
+use crate::{pick, BINARY_OPERATIONS, REGISTERS_COUNT, UNARY_OPERATIONS};
+
+/// A number that can only be a constant or one of the first [`REGISTERS_COUNT`] registers
+#[derive(Arbitrary, Debug, Copy, Clone)]
+pub enum ArithmeticNumber {
+    Constant(i32),
+    Register(u16),
+}
+
+impl From<ArithmeticNumber> for crate::FuzzNumber {
+    fn from(value: ArithmeticNumber) -> Self {
+        match value {
+            ArithmeticNumber::Constant(value) => crate::FuzzNumber::Constant(value),
+            ArithmeticNumber::Register(index) => crate::FuzzNumber::Register(index),
+        }
+    }
+}
+
+#[derive(Arbitrary, Debug, Clone)]
+pub enum ArithmeticInstruction {
+    Uo {
+        ty: u8,
+        destination: u16,
+        source: ArithmeticNumber,
+    },
+    Bo {
+        ty: u8,
+        destination: u16,
+        left: ArithmeticNumber,
+        right: ArithmeticNumber,
+    },
+    Exp {
+        destination: u16,
+        terms: Vec<(u8, ArithmeticNumber)>,
+        fill: ArithmeticNumber,
+    },
+}
+
+impl ArithmeticInstruction {
+    /// Converts to an instruction, or returns `None` if it uses an operation the reference interpreter doesn't support
+    pub fn to_instruction(&self) -> Option<Instruction> {
+        let instruction = match self {
+            &ArithmeticInstruction::Uo {
+                ty,
+                destination,
+                source,
+            } => crate::FuzzInstruction::Uo {
+                ty,
+                destination,
+                source: source.into(),
+            },
+            &ArithmeticInstruction::Bo {
+                ty,
+                destination,
+                left,
+                right,
+            } => crate::FuzzInstruction::Bo {
+                ty,
+                destination,
+                left: left.into(),
+                right: right.into(),
+            },
+            ArithmeticInstruction::Exp {
+                destination,
+                terms,
+                fill,
+            } => crate::FuzzInstruction::Exp {
+                destination: *destination,
+                terms: terms.iter().map(|&(op, v)| (op, v.into())).collect(),
+                fill: (*fill).into(),
+            },
+        };
+        let instruction = instruction.to_instruction(|_| unreachable!("No jumps expected"));
+
+        let supported = match &instruction {
+            Instruction::bo(BinaryOperation { ty, .. }) => !matches!(
+                ty,
+                BinaryOperationType::ATan2
+                    // TODO: the result for `L & (-1 << R) == 0` has not been checked against the game
+                    | BinaryOperationType::ACursedOperation
+            ),
+            Instruction::exp { expr, .. } => !expr.iter().any(|term| {
+                matches!(
+                    term,
+                    ExpressionTerm::Sin | ExpressionTerm::Cos | ExpressionTerm::Tan
+                )
+            }),
+            _ => true,
+        };
+
+        supported.then_some(instruction)
+    }
+}
+
+pub struct ReferenceInterpreter {
+    pub registers: [i32; REGISTERS_COUNT as usize],
+}
+
+impl ReferenceInterpreter {
+    pub fn new(init_val: i32) -> Self {
+        let mut registers = [0; REGISTERS_COUNT as usize];
+        registers[0] = init_val;
+        Self { registers }
+    }
+
+    fn number(&self, number: ArithmeticNumber) -> i64 {
+        match number {
+            ArithmeticNumber::Constant(value) => value as i64,
+            ArithmeticNumber::Register(index) => {
+                self.registers[(index % REGISTERS_COUNT) as usize] as i64
+            }
+        }
+    }
+
+    fn write(&mut self, destination: u16, value: i64) {
+        // the VM uses 32-bit registers, the results are truncated
+        self.registers[(destination % REGISTERS_COUNT) as usize] = value as i32;
+    }
+
+    pub fn execute(&mut self, instruction: &ArithmeticInstruction) {
+        match instruction {
+            &ArithmeticInstruction::Uo {
+                ty,
+                destination,
+                source,
+            } => {
+                let source = self.number(source);
+                let result = match pick(&UNARY_OPERATIONS, ty) {
+                    UnaryOperationType::Zero => 0,
+                    UnaryOperationType::Not16 => source ^ 0xffff,
+                    UnaryOperationType::Negate => -source,
+                    UnaryOperationType::Abs => source.abs(),
+                };
+                self.write(destination, result);
+            }
+            &ArithmeticInstruction::Bo {
+                ty,
+                destination,
+                left,
+                right,
+            } => {
+                let left = self.number(left);
+                let right = self.number(right);
+                let result = binary_operation(pick(&BINARY_OPERATIONS, ty), left, right);
+                self.write(destination, result);
+            }
+            ArithmeticInstruction::Exp { destination, .. } => {
+                let Some(Instruction::exp { expr, .. }) = instruction.to_instruction() else {
+                    unreachable!()
+                };
+                let mut stack = Vec::new();
+                for term in expr.iter() {
+                    let result = match *term {
+                        ExpressionTerm::Push(number) => match number.into_untyped() {
+                            UntypedNumberSpec::Constant(value) => value as i64,
+                            UntypedNumberSpec::Register(register) => {
+                                let RegisterRepr::Regular(index) = register.repr() else {
+                                    unreachable!("Only regular registers are generated")
+                                };
+                                self.registers[index as usize] as i64
+                            }
+                        },
+                        term => {
+                            let args = stack.split_off(stack.len() - term.argument_count());
+                            expression_term(term, &args)
+                        }
+                    };
+                    // the VM stack holds 32-bit values
+                    stack.push(result as i32 as i64);
+                }
+                assert_eq!(stack.len(), 1);
+                self.write(*destination, stack[0]);
+            }
+        }
+    }
+}
+
+fn unbool(value: bool) -> i64 {
+    if value {
+        -1
+    } else {
+        0
+    }
+}
+
+fn binary_operation(ty: BinaryOperationType, left: i64, right: i64) -> i64 {
+    match ty {
+        BinaryOperationType::MovRight => right,
+        BinaryOperationType::Zero => 0,
+        BinaryOperationType::Add => left + right,
+        BinaryOperationType::Subtract => left - right,
+        BinaryOperationType::Multiply => left * right,
+        BinaryOperationType::Divide if right == 0 => 0,
+        BinaryOperationType::Divide => left / right,
+        BinaryOperationType::Modulo if right == 0 => left,
+        BinaryOperationType::Modulo => left % right,
+        BinaryOperationType::BitwiseAnd => left & right,
+        BinaryOperationType::BitwiseOr => left | right,
+        BinaryOperationType::BitwiseXor => left ^ right,
+        BinaryOperationType::LeftShift => left << (right & 31),
+        BinaryOperationType::RightShift => left >> (right & 31),
+        BinaryOperationType::MultiplyReal => left * right / 1000,
+        BinaryOperationType::DivideReal if right == 0 => 0,
+        BinaryOperationType::DivideReal => left * 1000 / right,
+        BinaryOperationType::SetBit => left | 1 << (right & 31),
+        BinaryOperationType::ClearBit => left & !(1 << (right & 31)),
+        BinaryOperationType::ATan2 | BinaryOperationType::ACursedOperation => {
+            unreachable!("Unsupported operation {:?}", ty)
+        }
+    }
+}
+
+/// Computes an expression term, the `args` are in the order they were pushed
+fn expression_term(term: ExpressionTerm, args: &[i64]) -> i64 {
+    use BinaryOperationType as B;
+
+    match (term, args) {
+        (ExpressionTerm::Add, &[l, r]) => binary_operation(B::Add, l, r),
+        (ExpressionTerm::Subtract, &[l, r]) => binary_operation(B::Subtract, l, r),
+        (ExpressionTerm::Multiply, &[l, r]) => binary_operation(B::Multiply, l, r),
+        (ExpressionTerm::Divide, &[l, r]) => binary_operation(B::Divide, l, r),
+        (ExpressionTerm::Modulo, &[l, r]) => binary_operation(B::Modulo, l, r),
+        (ExpressionTerm::ShiftLeft, &[l, r]) => binary_operation(B::LeftShift, l, r),
+        (ExpressionTerm::ShiftRight, &[l, r]) => binary_operation(B::RightShift, l, r),
+        (ExpressionTerm::BitwiseAnd, &[l, r]) => l & r,
+        (ExpressionTerm::BitwiseOr, &[l, r]) => l | r,
+        (ExpressionTerm::BitwiseXor, &[l, r]) => l ^ r,
+        (ExpressionTerm::Negate, &[v]) => -v,
+        (ExpressionTerm::BitwiseNot, &[v]) => !v,
+        (ExpressionTerm::Abs, &[v]) => v.abs(),
+        (ExpressionTerm::CmpEqual, &[l, r]) => unbool(l == r),
+        (ExpressionTerm::CmpNotEqual, &[l, r]) => unbool(l != r),
+        (ExpressionTerm::CmpGreaterOrEqual, &[l, r]) => unbool(l >= r),
+        (ExpressionTerm::CmpGreater, &[l, r]) => unbool(l > r),
+        (ExpressionTerm::CmpLessOrEqual, &[l, r]) => unbool(l <= r),
+        (ExpressionTerm::CmpLess, &[l, r]) => unbool(l < r),
+        (ExpressionTerm::CmpZero, &[v]) => unbool(v == 0),
+        (ExpressionTerm::CmpNotZero, &[v]) => unbool(v != 0),
+        (ExpressionTerm::LogicalAnd, &[l, r]) => unbool(l != 0 && r != 0),
+        (ExpressionTerm::LogicalOr, &[l, r]) => unbool(l != 0 || r != 0),
+        // pushed in the order of false value, true value, condition
+        (ExpressionTerm::Select, &[f, t, c]) => {
+            if c != 0 {
+                t
+            } else {
+                f
+            }
+        }
+        (ExpressionTerm::MultiplyReal, &[l, r]) => binary_operation(B::MultiplyReal, l, r),
+        (ExpressionTerm::DivideReal, &[l, r]) => binary_operation(B::DivideReal, l, r),
+        (ExpressionTerm::Min, &[l, r]) => l.min(r),
+        (ExpressionTerm::Max, &[l, r]) => l.max(r),
+        (term, args) => unreachable!("Unsupported term {:?} with arguments {:?}", term, args),
+    }
+}
@@ -1,4 +1,5 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
+use tracing::warn;
 
 type Sample = (f32, f32);
 
@@ -112,11 +113,33 @@ pub trait AudioFrameSource {
     fn current_sample_position(&self) -> u32;
 }
 
+/// A region of the audio that is played repeatedly after the intro section
+///
+/// Both positions are in samples, not taking the pre-skip into account. The `end` is exclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoopRegion {
+    /// The first sample of the loop (the intro is everything before it)
+    pub start: u32,
+    /// The sample after the last one of the loop; when it's reached, the playback jumps back to `start`
+    pub end: u32,
+}
+
+impl LoopRegion {
+    pub fn len(&self) -> u32 {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start >= self.end
+    }
+}
+
 /// A wrapper around an [`AudioFrameSource`] that provides a sample-based interface
 pub struct AudioSource<S: AudioFrameSource> {
     source: S,
     reader: AudioBufferReader,
     skip_left: u32,
+    loop_region: Option<LoopRegion>,
 }
 
 impl<S: AudioFrameSource> AudioSource<S> {
@@ -128,9 +151,29 @@ impl<S: AudioFrameSource> AudioSource<S> {
             source,
             reader: AudioBufferReader::new(AudioBuffer::with_capacity(buffer_capacity)),
             skip_left: pre_skip,
+            loop_region: None,
         }
     }
 
+    /// Makes the source loop over the specified region
+    ///
+    /// When the end of the region is reached, the source seeks to its start without producing any gap, so `read_sample` only returns `None` if that seek fails.
+    ///
+    /// Passing `None` disables the looping, letting the source play until the end of the file. An empty region is rejected.
+    pub fn set_loop_region(&mut self, loop_region: Option<LoopRegion>) -> Result<()> {
+        if let Some(region) = loop_region {
+            if region.is_empty() {
+                bail!("Empty loop region {:?}", region);
+            }
+        }
+        self.loop_region = loop_region;
+        Ok(())
+    }
+
+    pub fn loop_region(&self) -> Option<LoopRegion> {
+        self.loop_region
+    }
+
     pub fn sample_rate(&self) -> u32 {
         self.source.sample_rate()
     }
@@ -154,6 +197,42 @@ impl<S: AudioFrameSource> AudioSource<S> {
     }
 
     pub fn read_sample(&mut self) -> Option<Sample> {
+        let Some(region) = self.loop_region else {
+            return self.read_sample_raw();
+        };
+
+        if self.current_samples_position() >= region.end && !self.seek_to_loop_start(region) {
+            return None;
+        }
+
+        match self.read_sample_raw() {
+            Some(sample) => Some(sample),
+            None => {
+                // the file ended before the loop end, wrap around anyway
+                if !self.seek_to_loop_start(region) {
+                    return None;
+                }
+                self.read_sample_raw()
+            }
+        }
+    }
+
+    /// Jumps back to the start of the loop; if that's not possible, the looping is stopped and `false` is returned
+    fn seek_to_loop_start(&mut self, region: LoopRegion) -> bool {
+        match self.samples_seek(region.start) {
+            Ok(()) => true,
+            Err(e) => {
+                warn!(
+                    "Could not seek to the loop start, stopping the playback: {:#}",
+                    e
+                );
+                self.loop_region = None;
+                false
+            }
+        }
+    }
+
+    fn read_sample_raw(&mut self) -> Option<Sample> {
         if self.skip_left > 0 {
             self.skip_left = self.reader.skip_samples(self.skip_left);
         }
@@ -165,7 +244,7 @@ impl<S: AudioFrameSource> AudioSource<S> {
                 if !self.source.read_frame(self.reader.inner_mut()) {
                     return None;
                 }
-                self.read_sample()
+                self.read_sample_raw()
             }
         }
     }
@@ -185,3 +264,142 @@ impl<S: AudioFrameSource> AudioSource<S> {
         &self.source
     }
 }
+
+/// Reads the samples one by one, decoding the frames as they are needed
+impl<S: AudioFrameSource> Iterator for AudioSource<S> {
+    type Item = Sample;
+
+    fn next(&mut self) -> Option<Sample> {
+        self.read_sample()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::{bail, Result};
+
+    use super::{AudioBuffer, AudioFrameSource, AudioSource, LoopRegion};
+
+    const FRAME_SIZE: u32 = 4;
+    const PRE_SKIP: u32 = 2;
+
+    /// A source producing `len` samples (after the pre-skip), each sample being its own position in the left channel
+    struct CountingSource {
+        len: u32,
+        next_frame: u32,
+    }
+
+    impl CountingSource {
+        fn new(len: u32) -> Self {
+            Self { len, next_frame: 0 }
+        }
+    }
+
+    impl AudioFrameSource for CountingSource {
+        fn max_frame_size(&self) -> usize {
+            FRAME_SIZE as usize
+        }
+
+        fn sample_rate(&self) -> u32 {
+            48000
+        }
+
+        fn pre_skip(&self) -> u32 {
+            PRE_SKIP
+        }
+
+        fn pre_roll(&self) -> u32 {
+            3
+        }
+
+        fn read_frame(&mut self, destination: &mut AudioBuffer) -> bool {
+            let start = self.next_frame * FRAME_SIZE;
+            let end = (start + FRAME_SIZE).min(self.len + PRE_SKIP);
+            if start >= end {
+                return false;
+            }
+            for raw in start..end {
+                destination.push((raw as f32 - PRE_SKIP as f32, 0.0));
+            }
+            self.next_frame += 1;
+            true
+        }
+
+        fn samples_seek(&mut self, sample_position: u32) -> Result<u32> {
+            if sample_position > self.len + PRE_SKIP {
+                bail!("Seek position {} is out of bounds", sample_position);
+            }
+            self.next_frame = sample_position / FRAME_SIZE;
+            Ok(sample_position % FRAME_SIZE)
+        }
+
+        fn current_sample_position(&self) -> u32 {
+            self.next_frame * FRAME_SIZE
+        }
+    }
+
+    fn read_positions(source: &mut AudioSource<CountingSource>, count: usize) -> Vec<u32> {
+        source.take(count).map(|(left, _)| left as u32).collect()
+    }
+
+    #[test]
+    fn plays_to_the_end_without_a_loop() {
+        let mut source = AudioSource::new(CountingSource::new(10));
+        assert_eq!(read_positions(&mut source, 20), (0..10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn plays_the_intro_once_then_loops() {
+        let mut source = AudioSource::new(CountingSource::new(20));
+        source
+            .set_loop_region(Some(LoopRegion { start: 5, end: 11 }))
+            .unwrap();
+
+        let expected = (0..11).chain(5..11).chain(5..11).collect::<Vec<_>>();
+        assert_eq!(read_positions(&mut source, expected.len()), expected);
+    }
+
+    #[test]
+    fn loop_ending_at_the_end_of_the_file_wraps() {
+        let mut source = AudioSource::new(CountingSource::new(10));
+        source
+            .set_loop_region(Some(LoopRegion { start: 3, end: 10 }))
+            .unwrap();
+
+        let expected = (0..10).chain(3..10).chain(3..6).collect::<Vec<_>>();
+        assert_eq!(read_positions(&mut source, expected.len()), expected);
+    }
+
+    #[test]
+    fn loop_past_the_end_of_the_file_wraps_at_the_end() {
+        // the header said the file is longer than it really is
+        let mut source = AudioSource::new(CountingSource::new(8));
+        source
+            .set_loop_region(Some(LoopRegion { start: 2, end: 12 }))
+            .unwrap();
+
+        let expected = (0..8).chain(2..8).chain(2..4).collect::<Vec<_>>();
+        assert_eq!(read_positions(&mut source, expected.len()), expected);
+    }
+
+    #[test]
+    fn rejects_an_empty_loop_region() {
+        let mut source = AudioSource::new(CountingSource::new(10));
+        assert!(source
+            .set_loop_region(Some(LoopRegion { start: 4, end: 4 }))
+            .is_err());
+        assert_eq!(source.loop_region(), None);
+    }
+
+    #[test]
+    fn failed_loop_seek_stops_the_playback() {
+        let mut source = AudioSource::new(CountingSource::new(10));
+        // the loop start is past the end of the file, so the seek fails
+        source
+            .set_loop_region(Some(LoopRegion { start: 20, end: 30 }))
+            .unwrap();
+
+        assert_eq!(read_positions(&mut source, 20), (0..10).collect::<Vec<_>>());
+        assert_eq!(source.loop_region(), None);
+    }
+}
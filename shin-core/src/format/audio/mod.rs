@@ -3,15 +3,40 @@
 //! It is a simple container storing opus frames mostly as-is. The only addition compared to usual opus formats are loop points.
 //!
 //! The header specifies loop start and loop end points in samples. When looping is enabled and loop end is reached, the decoder seeks to the loop start.
+//! Everything before the loop start is the intro, which is played only once.
+//!
+//! The frames can be read from a file loaded into memory ([`AudioFile`]) or streamed from a reader ([`AudioStreamReader`]).
+//! Either way, [`AudioDecoder`] decodes them one at a time, so only the compressed data is kept in memory (or none of it, when streaming).
 
 mod audio_source;
 
-use std::io::Read;
+use std::io::{self, Read, Seek, SeekFrom};
 
 use anyhow::{bail, Result};
-pub use audio_source::{AudioBuffer, AudioFrameSource, AudioSource};
+pub use audio_source::{AudioBuffer, AudioFrameSource, AudioSource, LoopRegion};
 use binrw::{BinRead, BinWrite};
 use opus::Channels;
+use snafu::{ensure, Snafu};
+use tracing::warn;
+
+/// Errors caused by malformed audio data
+#[derive(Debug, Snafu)]
+pub enum AudioError {
+    /// The file size in the header (`{expected}`) doesn't match the actual size (`{actual}`)
+    FileSizeMismatch { expected: u32, actual: usize },
+    /// Unsupported channel count `{channel_count}`, only mono and stereo are supported
+    UnsupportedChannelCount { channel_count: u16 },
+    /// Invalid frame size `{frame_size}` bytes, `{frame_samples}` samples
+    InvalidFrameSize { frame_size: u16, frame_samples: u16 },
+    /// The file has no samples
+    NoSamples,
+    /// Invalid loop points: start = `{loop_start}`, end = `{loop_end}`, the file is `{num_samples}` samples
+    InvalidLoopPoints {
+        loop_start: u32,
+        loop_end: u32,
+        num_samples: u32,
+    },
+}
 
 #[derive(BinRead, BinWrite, Debug)]
 #[brw(little, magic = b"NXA1")]
@@ -46,6 +71,27 @@ pub struct AudioInfo {
     pub loop_end: u32,
 }
 
+impl AudioInfo {
+    /// Returns the region that should be repeated when the audio is played in a loop
+    ///
+    /// If the file specifies an empty loop, the whole file is looped. Returns `None` when there's nothing to loop (the file has no samples).
+    pub fn loop_region(&self) -> Option<LoopRegion> {
+        let region = LoopRegion {
+            start: self.loop_start,
+            end: self.loop_end,
+        };
+        let region = if region.is_empty() {
+            LoopRegion {
+                start: 0,
+                end: self.num_samples,
+            }
+        } else {
+            region
+        };
+        (!region.is_empty()).then_some(region)
+    }
+}
+
 // A fully in-memory, but not yet decoded audio file
 pub struct AudioFile {
     info: AudioInfo,
@@ -57,7 +103,7 @@ impl AudioFile {
         &self.info
     }
 
-    pub fn decode(self) -> Result<AudioDecoder<Self>> {
+    pub fn decode(self) -> Result<AudioDecoder<AudioFileFrameReader<Self>>> {
         AudioDecoder::new(self)
     }
 
@@ -72,6 +118,16 @@ impl AsRef<AudioFile> for AudioFile {
     }
 }
 
+/// Provides the opus frames of an audio file to the [`AudioDecoder`]
+pub trait AudioFrames {
+    fn audio_info(&self) -> &AudioInfo;
+    /// Returns the next frame, or `None` at the end of the file
+    fn next_frame(&mut self) -> Option<&[u8]>;
+    /// Returns the index of the next frame
+    fn frames_position(&self) -> usize;
+    fn seek_to_frames(&mut self, new_frames_position: usize);
+}
+
 pub struct AudioFileFrameReader<F: AsRef<AudioFile>> {
     file: F,
     bytes_position: usize,
@@ -93,10 +149,6 @@ impl<F: AsRef<AudioFile>> AudioFileFrameReader<F> {
         self.audio_info().frame_size as usize
     }
 
-    fn frame_samples(&self) -> usize {
-        self.audio_info().frame_samples as usize
-    }
-
     pub fn frames_position(&self) -> usize {
         self.bytes_position / self.frame_size()
     }
@@ -111,7 +163,8 @@ impl<F: AsRef<AudioFile>> AudioFileFrameReader<F> {
             return None;
         }
 
-        let data = &data[self.bytes_position..][..self.frame_size()];
+        // a truncated frame at the end is ignored
+        let data = data.get(self.bytes_position..)?.get(..self.frame_size())?;
 
         self.bytes_position += self.frame_size();
 
@@ -123,27 +176,140 @@ impl<F: AsRef<AudioFile>> AudioFileFrameReader<F> {
     }
 }
 
-pub struct AudioDecoder<F: AsRef<AudioFile>> {
-    frame_iter: AudioFileFrameReader<F>,
+impl<F: AsRef<AudioFile>> AudioFrames for AudioFileFrameReader<F> {
+    fn audio_info(&self) -> &AudioInfo {
+        self.audio_info()
+    }
+
+    fn next_frame(&mut self) -> Option<&[u8]> {
+        self.get_next_frame()
+    }
+
+    fn frames_position(&self) -> usize {
+        self.frames_position()
+    }
+
+    fn seek_to_frames(&mut self, new_frames_position: usize) {
+        self.seek_to_frames(new_frames_position)
+    }
+}
+
+/// Reads the frames from an NXA file on demand, without loading the whole file into memory
+///
+/// Used for the long tracks, where even the compressed data is quite large.
+pub struct AudioStreamReader<R: Read + Seek> {
+    reader: R,
+    info: AudioInfo,
+    /// Where the frames start in the file
+    data_offset: u64,
+    data_size: u64,
+    bytes_position: u64,
+    /// Whether the reader has to be moved to `bytes_position` before reading the next frame
+    needs_seek: bool,
+    frame: Box<[u8]>,
+}
+
+impl<R: Read + Seek> AudioStreamReader<R> {
+    pub fn new(mut reader: R) -> Result<Self> {
+        let file_size = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(0))?;
+
+        let info = read_header(&mut reader, file_size)?;
+        let data_offset = reader.stream_position()?;
+
+        Ok(Self {
+            frame: vec![0; info.frame_size as usize].into_boxed_slice(),
+            reader,
+            info,
+            data_offset,
+            data_size: file_size - data_offset,
+            bytes_position: 0,
+            needs_seek: false,
+        })
+    }
+
+    fn frame_size(&self) -> u64 {
+        self.info.frame_size as u64
+    }
+
+    fn read_frame(&mut self) -> io::Result<()> {
+        if self.needs_seek {
+            self.reader
+                .seek(SeekFrom::Start(self.data_offset + self.bytes_position))?;
+            self.needs_seek = false;
+        }
+        self.reader.read_exact(&mut self.frame)
+    }
+}
+
+impl<R: Read + Seek> AudioFrames for AudioStreamReader<R> {
+    fn audio_info(&self) -> &AudioInfo {
+        &self.info
+    }
+
+    fn next_frame(&mut self) -> Option<&[u8]> {
+        // a truncated frame at the end is ignored
+        if self.bytes_position + self.frame_size() > self.data_size {
+            return None;
+        }
+
+        if let Err(e) = self.read_frame() {
+            // there is no way to report the error from the audio thread, treat it as the end of the file
+            warn!("Failed to read an audio frame: {}", e);
+            self.needs_seek = true;
+            return None;
+        }
+        self.bytes_position += self.frame_size();
+
+        Some(&self.frame)
+    }
+
+    fn frames_position(&self) -> usize {
+        (self.bytes_position / self.frame_size()) as usize
+    }
+
+    fn seek_to_frames(&mut self, new_frames_position: usize) {
+        self.bytes_position = self.frame_size() * new_frames_position as u64;
+        self.needs_seek = true;
+    }
+}
+
+pub struct AudioDecoder<R: AudioFrames> {
+    frame_iter: R,
     buffer: Box<[f32]>,
     decoder: opus::Decoder,
 }
 
-impl<F: AsRef<AudioFile>> AudioDecoder<F> {
+impl<F: AsRef<AudioFile>> AudioDecoder<AudioFileFrameReader<F>> {
     pub fn new(file: F) -> Result<Self> {
-        let info = &file.as_ref().info;
+        Self::from_frames(AudioFileFrameReader::new(file))
+    }
+}
+
+impl<R: Read + Seek> AudioDecoder<AudioStreamReader<R>> {
+    /// Decodes the audio streamed from the reader, see [`AudioStreamReader`]
+    pub fn from_reader(reader: R) -> Result<Self> {
+        Self::from_frames(AudioStreamReader::new(reader)?)
+    }
+}
+
+impl<R: AudioFrames> AudioDecoder<R> {
+    pub fn from_frames(frames: R) -> Result<Self> {
+        let info = frames.audio_info();
         let decoder = opus::Decoder::new(
             info.sample_rate,
             match info.channel_count {
                 1 => Channels::Mono,
                 2 => Channels::Stereo,
-                _ => panic!("Unsupported channel count"),
+                channel_count => {
+                    bail!(AudioError::UnsupportedChannelCount { channel_count })
+                }
             },
         )?;
         let buffer =
             vec![0.0; info.frame_samples as usize * info.channel_count as usize].into_boxed_slice();
         Ok(Self {
-            frame_iter: AudioFileFrameReader::new(file),
+            frame_iter: frames,
             buffer,
             decoder,
         })
@@ -154,11 +320,11 @@ impl<F: AsRef<AudioFile>> AudioDecoder<F> {
     }
 
     fn frame_samples(&self) -> usize {
-        self.frame_iter.frame_samples()
+        self.audio_info().frame_samples as usize
     }
 }
 
-impl<F: AsRef<AudioFile>> AudioFrameSource for AudioDecoder<F> {
+impl<R: AudioFrames> AudioFrameSource for AudioDecoder<R> {
     fn max_frame_size(&self) -> usize {
         self.audio_info().frame_samples as usize
     }
@@ -187,21 +353,21 @@ impl<F: AsRef<AudioFile>> AudioFrameSource for AudioDecoder<F> {
             ..
         } = self.audio_info();
 
-        let Some(data) = self.frame_iter.get_next_frame() else {
+        let Some(data) = self.frame_iter.next_frame() else {
             return false;
         };
 
-        assert_eq!(
-            self.decoder.get_nb_samples(data).unwrap(),
-            frame_samples as usize
-        );
-
-        let decoded = self
-            .decoder
-            .decode_float(data, &mut self.buffer, false)
-            .unwrap();
-
-        assert_eq!(decoded, frame_samples as usize);
+        match self.decoder.decode_float(data, &mut self.buffer, false) {
+            Ok(decoded) if decoded == frame_samples as usize => {}
+            result => {
+                // a corrupt frame is replaced with silence, so that the timing is preserved
+                warn!(
+                    "Failed to decode an audio frame (got {:?}, expected {} samples)",
+                    result, frame_samples
+                );
+                self.buffer.fill(0.0);
+            }
+        }
 
         match channels {
             1 => {
@@ -214,7 +380,7 @@ impl<F: AsRef<AudioFile>> AudioFrameSource for AudioDecoder<F> {
                     destination.push((sample[0], sample[1]));
                 }
             }
-            _ => panic!("Unsupported channel count: {}", channels),
+            _ => unreachable!("Unsupported channel count: {}", channels),
         }
 
         true
@@ -235,7 +401,7 @@ impl<F: AsRef<AudioFile>> AudioFrameSource for AudioDecoder<F> {
         let in_frame_position = samples_position % self.frame_samples();
 
         self.frame_iter.seek_to_frames(frames_position);
-        self.decoder.reset_state().unwrap();
+        self.decoder.reset_state()?;
 
         Ok(in_frame_position.try_into().unwrap())
     }
@@ -245,19 +411,156 @@ impl<F: AsRef<AudioFile>> AudioFrameSource for AudioDecoder<F> {
     }
 }
 
+/// Reads and validates the header, leaving the reader at the start of the frames
+///
+/// `file_size` is the actual size of the file, to check against the one in the header.
+fn read_header<R: Read + Seek>(reader: &mut R, file_size: u64) -> Result<AudioInfo> {
+    let header = NxaHeader::read_le(reader)?;
+
+    let info = header.info;
+    ensure!(
+        header.file_size as u64 == file_size,
+        FileSizeMismatchSnafu {
+            expected: header.file_size,
+            actual: file_size as usize,
+        }
+    );
+    ensure!(
+        matches!(info.channel_count, 1 | 2),
+        UnsupportedChannelCountSnafu {
+            channel_count: info.channel_count
+        }
+    );
+    ensure!(
+        info.frame_size != 0 && info.frame_samples != 0,
+        InvalidFrameSizeSnafu {
+            frame_size: info.frame_size,
+            frame_samples: info.frame_samples,
+        }
+    );
+    ensure!(info.num_samples != 0, NoSamplesSnafu);
+    // the loop end is usually at the end of the file, but some tracks have a tail after it
+    // it's only played when the audio is not looped
+    ensure!(
+        info.loop_start <= info.loop_end && info.loop_end <= info.num_samples,
+        InvalidLoopPointsSnafu {
+            loop_start: info.loop_start,
+            loop_end: info.loop_end,
+            num_samples: info.num_samples,
+        }
+    );
+
+    Ok(info)
+}
+
 pub fn read_audio(data: &[u8]) -> Result<AudioFile> {
     let mut cur = std::io::Cursor::new(data);
-    let header = NxaHeader::read_le(&mut cur)?;
-
-    assert_eq!(header.file_size, data.len() as u32);
-    // how are we supposed to loop when the loop end is not in the end of the file?
-    assert_eq!(header.info.loop_end, header.info.num_samples);
+    let info = read_header(&mut cur, data.len() as u64)?;
 
     let mut data = Vec::new();
     cur.read_to_end(&mut data)?;
 
-    Ok(AudioFile {
-        info: header.info,
-        data,
-    })
+    Ok(AudioFile { info, data })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use binrw::BinWrite;
+
+    use super::{read_audio, AudioFrames, AudioInfo, AudioStreamReader, LoopRegion, NxaHeader};
+
+    /// Builds an NXA file with the frames filled with their index (the contents are not valid opus, but the readers don't care)
+    fn build_file(frame_count: u8, frame_size: u16) -> Vec<u8> {
+        let mut header = NxaHeader {
+            version: 2,
+            file_size: 0,
+            info: AudioInfo {
+                sample_rate: 48000,
+                channel_count: 2,
+                frame_size,
+                frame_samples: 960,
+                pre_skip: 312,
+                num_samples: frame_count as u32 * 960,
+                loop_start: 0,
+                loop_end: 0,
+            },
+        };
+
+        let mut header_data = Cursor::new(Vec::new());
+        header.write(&mut header_data).unwrap();
+        header.file_size =
+            (header_data.get_ref().len() + frame_count as usize * frame_size as usize) as u32;
+
+        let mut data = Cursor::new(Vec::new());
+        header.write(&mut data).unwrap();
+        let mut data = data.into_inner();
+        for frame in 0..frame_count {
+            data.extend(std::iter::repeat(frame).take(frame_size as usize));
+        }
+        data
+    }
+
+    fn collect_frames(frames: &mut impl AudioFrames) -> Vec<Vec<u8>> {
+        std::iter::from_fn(|| frames.next_frame().map(|f| f.to_vec())).collect()
+    }
+
+    #[test]
+    fn stream_matches_in_memory() {
+        let data = build_file(5, 16);
+
+        let file = read_audio(&data).unwrap();
+        let mut in_memory = file.read_frames();
+        let mut stream = AudioStreamReader::new(Cursor::new(data)).unwrap();
+
+        let expected = collect_frames(&mut in_memory);
+        assert_eq!(expected.len(), 5);
+        assert_eq!(expected[3], vec![3; 16]);
+        assert_eq!(collect_frames(&mut stream), expected);
+
+        for frames in [&mut in_memory as &mut dyn AudioFrames, &mut stream] {
+            frames.seek_to_frames(2);
+            assert_eq!(frames.frames_position(), 2);
+            assert_eq!(frames.next_frame(), Some(&[2; 16][..]));
+            assert_eq!(frames.frames_position(), 3);
+        }
+    }
+
+    #[test]
+    fn rejects_files_without_samples() {
+        assert!(read_audio(&build_file(0, 16)).is_err());
+        assert!(AudioStreamReader::new(Cursor::new(build_file(0, 16))).is_err());
+    }
+
+    #[test]
+    fn loop_region() {
+        let info = |num_samples, loop_start, loop_end| AudioInfo {
+            sample_rate: 48000,
+            channel_count: 2,
+            frame_size: 16,
+            frame_samples: 960,
+            pre_skip: 312,
+            num_samples,
+            loop_start,
+            loop_end,
+        };
+
+        assert_eq!(
+            info(1000, 100, 900).loop_region(),
+            Some(LoopRegion {
+                start: 100,
+                end: 900
+            })
+        );
+        // an empty loop means the whole file
+        assert_eq!(
+            info(1000, 500, 500).loop_region(),
+            Some(LoopRegion {
+                start: 0,
+                end: 1000
+            })
+        );
+        assert_eq!(info(0, 0, 0).loop_region(), None);
+    }
 }
@@ -13,17 +13,28 @@
 
 use std::collections::HashMap;
 
-use anyhow::{bail, Result};
+use anyhow::Result;
 use binrw::{BinRead, BinWrite};
 use bitvec::bitbox;
 use image::RgbaImage;
-use shin_tasks::ParallelSlice;
+use snafu::{ensure, Snafu};
 
 use crate::format::{
+    par_map,
     picture::{read_picture_chunk, PictureChunk},
+    slice_checked,
     text::ZeroString,
 };
 
+/// Errors caused by malformed bustup data
+#[derive(Debug, Snafu)]
+pub enum BustupError {
+    /// The file size in the header (`{expected}`) doesn't match the actual size (`{actual}`)
+    FileSizeMismatch { expected: u32, actual: usize },
+    /// Two chunks have the same ID `{chunk_id}`, but different contents
+    ConflictingChunks { chunk_id: u32 },
+}
+
 #[derive(BinRead, BinWrite, Debug)]
 #[br(little, magic = b"BUP4")]
 #[br(assert(version == 4))]
@@ -108,8 +119,8 @@ fn cleanup_unused_areas(chunk: &mut PictureChunk) {
         .iter()
         .chain(chunk.transparent_vertices.iter())
     {
-        let clamp_y = |y: u16| std::cmp::min(y, chunk.data.height() as u16 - 1);
-        let clamp_x = |x: u16| std::cmp::min(x, chunk.data.width() as u16 - 1);
+        let clamp_y = |y: u16| std::cmp::min(y, (chunk.data.height() as u16).saturating_sub(1));
+        let clamp_x = |x: u16| std::cmp::min(x, (chunk.data.width() as u16).saturating_sub(1));
         for y in vertex.from_y.saturating_sub(0)..clamp_y(vertex.to_y) {
             for x in vertex.from_x.saturating_sub(0)..clamp_x(vertex.to_x) {
                 bitbox.set(coord_to_index(x as u32, y as u32), true);
@@ -130,16 +141,22 @@ pub fn read_bustup(source: &[u8]) -> Result<Bustup> {
 
     let header = BustupHeader::read(source)?;
 
-    if header.file_size != source.get_ref().len() as u32 {
-        bail!("File size mismatch");
-    }
+    ensure!(
+        header.file_size as usize == source.get_ref().len(),
+        FileSizeMismatchSnafu {
+            expected: header.file_size,
+            actual: source.get_ref().len(),
+        }
+    );
 
     let mut base_chunks = HashMap::new();
     for chunk in header.base_chunks.iter() {
         let e = base_chunks.entry(chunk.chunk_id).or_insert(*chunk);
-        assert_eq!(
-            e, chunk,
-            "Two chunks have the same ID, but different contents"
+        ensure!(
+            e == chunk,
+            ConflictingChunksSnafu {
+                chunk_id: chunk.chunk_id
+            }
         );
     }
     let base_chunks = base_chunks.into_iter().collect::<Vec<_>>();
@@ -147,9 +164,11 @@ pub fn read_bustup(source: &[u8]) -> Result<Bustup> {
     let mut additional_chunks = HashMap::new();
     for chunk in header.iter_additional_chunk_descs() {
         let e = additional_chunks.entry(chunk.chunk_id).or_insert(*chunk);
-        assert_eq!(
-            e, chunk,
-            "Two chunks have the same ID, but different contents"
+        ensure!(
+            e == chunk,
+            ConflictingChunksSnafu {
+                chunk_id: chunk.chunk_id
+            }
         );
     }
     let additional_chunks = additional_chunks.into_iter().collect::<Vec<_>>();
@@ -165,17 +184,12 @@ pub fn read_bustup(source: &[u8]) -> Result<Bustup> {
         chunks: Vec<(u32, BustupChunkDesc)>,
         source: &[u8],
     ) -> Vec<Result<(u32, PictureChunk)>> {
-        chunks.par_chunk_map(
-            shin_tasks::AsyncComputeTaskPool::get(),
-            1,
-            |chunk| -> Result<_> {
-                let &[(id, desc)] = chunk else { unreachable!() };
-                let data = &source[desc.offset as usize..(desc.offset + desc.size) as usize];
-                let mut chunk = read_picture_chunk(data)?;
-                cleanup_unused_areas(&mut chunk);
-                Ok((id, chunk))
-            },
-        )
+        par_map(&chunks, |&(id, desc)| -> Result<_> {
+            let data = slice_checked(source, desc.offset as usize, desc.size as usize)?;
+            let mut chunk = read_picture_chunk(data)?;
+            cleanup_unused_areas(&mut chunk);
+            Ok((id, chunk))
+        })
     }
 
     par_decode_chunks(base_chunks, source.get_ref())
@@ -4,16 +4,26 @@ use std::{
     borrow::Cow,
     collections::{hash_map::Entry, HashMap},
     io,
-    io::{Read, Seek, SeekFrom},
+    io::{Read, Seek, SeekFrom, Write},
 };
 
 use anyhow::anyhow;
 use binrw::{BinRead, BinResult, BinWrite, Endian, VecArgs};
 use glam::{vec2, Vec2};
 use image::GrayImage;
+use snafu::Snafu;
 use strum::EnumIter;
 
-use crate::format::lz77;
+use crate::format::lz77::{self, Lz77Error};
+
+/// Errors caused by malformed glyph data
+#[derive(Debug, Snafu)]
+pub enum GlyphError {
+    /// Failed to decompress the glyph data
+    Decompress { source: Lz77Error },
+    /// The glyph data is too short for the `{width}x{height}` mip level
+    TruncatedTexture { width: u8, height: u8 },
+}
 
 #[derive(BinRead, BinWrite, Debug)]
 #[brw(little, magic = b"FNT4")]
@@ -87,6 +97,22 @@ impl GlyphInfo {
     }
 }
 
+impl GlyphInfo {
+    fn to_header(self, compressed_size: u16) -> GlyphHeader {
+        GlyphHeader {
+            bearing_x: self.bearing_x,
+            bearing_y: self.bearing_y,
+            actual_width: self.actual_width,
+            actual_height: self.actual_height,
+            advance_width: self.advance_width,
+            unused: 0,
+            texture_width: self.texture_width,
+            texture_height: self.texture_height,
+            compressed_size,
+        }
+    }
+}
+
 impl From<GlyphHeader> for GlyphInfo {
     fn from(header: GlyphHeader) -> Self {
         Self {
@@ -128,45 +154,64 @@ pub struct LazyGlyph {
 }
 
 impl LazyGlyph {
-    fn data(&self) -> Cow<[u8]> {
-        match &self.data {
+    fn data(&self) -> Result<Cow<[u8]>, GlyphError> {
+        Ok(match &self.data {
             GlyphData::Raw(data) => Cow::Borrowed(data),
             GlyphData::Compressed(data) => Cow::Owned({
                 let mut result = Vec::new();
-                lz77::decompress::<10>(data, &mut result);
+                lz77::decompress::<10>(data, &mut result)
+                    .map_err(|source| GlyphError::Decompress { source })?;
                 result
             }),
-        }
+        })
+    }
+
+    /// Replaces the placement metrics of the glyph, leaving the bitmap as is
+    pub fn set_metrics(&mut self, bearing_x: i8, bearing_y: i8, advance_width: u8) {
+        self.info.bearing_x = bearing_x;
+        self.info.bearing_y = bearing_y;
+        self.info.advance_width = advance_width;
+    }
+
+    /// Size of the glyph when written to a FNT file, including the header
+    fn encoded_size(&self) -> usize {
+        let data = match &self.data {
+            GlyphData::Raw(data) | GlyphData::Compressed(data) => data,
+        };
+        10 + data.len()
     }
 
-    pub fn decompress(&self) -> Glyph {
-        let data = self.data();
+    pub fn decompress(&self) -> Result<Glyph, GlyphError> {
+        let data = self.data()?;
         let mut data = io::Cursor::new(data);
 
         fn read_texture(
             width: u8,
             height: u8,
             data: &mut io::Cursor<impl AsRef<[u8]>>,
-        ) -> GrayImage {
+        ) -> Result<GrayImage, GlyphError> {
             let mut image_data = vec![0u8; width as usize * height as usize];
             data.read_exact(&mut image_data)
-                .expect("Failed to read glyph texture");
+                .map_err(|_| GlyphError::TruncatedTexture { width, height })?;
 
-            GrayImage::from_raw(width as u32, height as u32, image_data).unwrap()
+            Ok(GrayImage::from_raw(width as u32, height as u32, image_data).unwrap())
         }
 
-        let mip_level_0 = read_texture(self.texture_size.0, self.texture_size.1, &mut data);
-        let mip_level_1 = read_texture(self.texture_size.0 / 2, self.texture_size.1 / 2, &mut data);
-        let mip_level_2 = read_texture(self.texture_size.0 / 4, self.texture_size.1 / 4, &mut data);
-        let mip_level_3 = read_texture(self.texture_size.0 / 8, self.texture_size.1 / 8, &mut data);
+        let mip_level_0 = read_texture(self.texture_size.0, self.texture_size.1, &mut data)?;
+        let mip_level_1 =
+            read_texture(self.texture_size.0 / 2, self.texture_size.1 / 2, &mut data)?;
+        let mip_level_2 =
+            read_texture(self.texture_size.0 / 4, self.texture_size.1 / 4, &mut data)?;
+        let mip_level_3 =
+            read_texture(self.texture_size.0 / 8, self.texture_size.1 / 8, &mut data)?;
 
-        Glyph {
+        Ok(Glyph {
             info: self.info,
             mip_level_0,
             mip_level_1,
             mip_level_2,
             mip_level_3,
-        }
+        })
     }
 }
 
@@ -212,6 +257,27 @@ impl BinRead for LazyGlyph {
     }
 }
 
+impl BinWrite for LazyGlyph {
+    type Args<'a> = ();
+
+    fn write_options<W: Write + Seek>(
+        &self,
+        writer: &mut W,
+        endian: Endian,
+        _: Self::Args<'_>,
+    ) -> BinResult<()> {
+        // the data is written back as it was read, so the compressed glyphs don't need to be recompressed
+        let (compressed_size, data) = match &self.data {
+            GlyphData::Raw(data) => (0, data),
+            GlyphData::Compressed(data) => (data.len() as u16, data),
+        };
+        self.info
+            .to_header(compressed_size)
+            .write_options(writer, endian, ())?;
+        data.write_options(writer, endian, ())
+    }
+}
+
 /// Glyph that has been decompressed
 pub struct Glyph {
     info: GlyphInfo,
@@ -240,8 +306,12 @@ impl BinRead for Glyph {
         endian: Endian,
         _: Self::Args<'_>,
     ) -> BinResult<Self> {
+        let pos = reader.stream_position()?;
         let glyph = LazyGlyph::read_options(reader, endian, ())?;
-        Ok(glyph.decompress())
+        glyph.decompress().map_err(|e| binrw::Error::Custom {
+            pos,
+            err: Box::new(e),
+        })
     }
 }
 
@@ -303,6 +373,30 @@ impl<G: GlyphTrait> Font<G> {
     pub fn get_glyphs(&self) -> &HashMap<GlyphId, G> {
         &self.glyphs
     }
+
+    pub fn get_glyph_mut(&mut self, glyph_id: GlyphId) -> Option<&mut G> {
+        self.glyphs.get_mut(&glyph_id)
+    }
+
+    /// Set the distance between the baseline and the top and the bottom of the font
+    pub fn set_ascent_descent(&mut self, ascent: u16, descent: u16) {
+        self.ascent = ascent;
+        self.descent = descent;
+    }
+
+    /// Make the `character` use an existing glyph
+    ///
+    /// # Panics
+    ///
+    /// If the font has no glyph with the `glyph_id`
+    pub fn set_character_glyph(&mut self, character: u16, glyph_id: GlyphId) {
+        assert!(
+            self.glyphs.contains_key(&glyph_id),
+            "The font has no glyph {}",
+            glyph_id.0
+        );
+        self.characters[character as usize] = glyph_id;
+    }
 }
 
 fn stream_size(reader: &mut impl Seek) -> BinResult<u64> {
@@ -388,6 +482,47 @@ impl<G: GlyphTrait> BinRead for Font<G> {
     }
 }
 
+/// Size of the header and the character table, the glyphs follow them
+const GLYPHS_OFFSET: usize = 16 + 4 * 0x10000;
+
+impl BinWrite for LazyFont {
+    type Args<'a> = ();
+
+    fn write_options<W: Write + Seek>(
+        &self,
+        writer: &mut W,
+        endian: Endian,
+        _: Self::Args<'_>,
+    ) -> BinResult<()> {
+        // the glyphs are laid out in the order of their ids
+        let mut glyph_ids = self.glyphs.keys().copied().collect::<Vec<_>>();
+        glyph_ids.sort();
+
+        let mut offsets = HashMap::new();
+        let mut offset = GLYPHS_OFFSET;
+        for glyph_id in &glyph_ids {
+            offsets.insert(*glyph_id, offset as u32);
+            offset += self.glyphs[glyph_id].encoded_size();
+        }
+
+        FontHeader {
+            version: 1,
+            size: offset as u32,
+            ascent: self.ascent,
+            descent: self.descent,
+        }
+        .write_options(writer, endian, ())?;
+        for glyph_id in self.characters.iter() {
+            offsets[glyph_id].write_options(writer, endian, ())?;
+        }
+        for glyph_id in &glyph_ids {
+            self.glyphs[glyph_id].write_options(writer, endian, ())?;
+        }
+
+        Ok(())
+    }
+}
+
 pub fn read_font<R: Read + Seek>(reader: &mut R) -> BinResult<Font> {
     Font::read_le(reader)
 }
@@ -395,3 +530,72 @@ pub fn read_font<R: Read + Seek>(reader: &mut R) -> BinResult<Font> {
 pub fn read_lazy_font<R: Read + Seek>(reader: &mut R) -> BinResult<LazyFont> {
     Font::read_le(reader)
 }
+
+pub fn write_lazy_font<W: Write + Seek>(font: &LazyFont, writer: &mut W) -> BinResult<()> {
+    font.write_le(writer)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    /// A font with every character mapped to one of two uncompressed 8x8 glyphs
+    fn test_font() -> Vec<u8> {
+        let glyph = |bearing_x: i8, fill: u8| {
+            let mut glyph = vec![bearing_x as u8, 6, 5, 7, 6, 0, 8, 8, 0, 0];
+            glyph.extend_from_slice(&[fill; 64]);
+            glyph
+        };
+        let (first, second) = (glyph(1, 0x00), glyph(-1, 0xff));
+        let size = GLYPHS_OFFSET + first.len() + second.len();
+
+        let mut data = b"FNT4".to_vec();
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(&(size as u32).to_le_bytes());
+        data.extend_from_slice(&[20, 0, 4, 0]);
+        for character in 0..0x10000 {
+            let offset = match character {
+                0x41 => GLYPHS_OFFSET + first.len(),
+                _ => GLYPHS_OFFSET,
+            };
+            data.extend_from_slice(&(offset as u32).to_le_bytes());
+        }
+        data.extend_from_slice(&first);
+        data.extend_from_slice(&second);
+        data
+    }
+
+    fn write(font: &LazyFont) -> Vec<u8> {
+        let mut data = Cursor::new(Vec::new());
+        write_lazy_font(font, &mut data).unwrap();
+        data.into_inner()
+    }
+
+    #[test]
+    fn test_write_unchanged() {
+        let data = test_font();
+        let font = read_lazy_font(&mut Cursor::new(&data)).unwrap();
+        assert_eq!(write(&font), data);
+    }
+
+    #[test]
+    fn test_write_modified() {
+        let mut font = read_lazy_font(&mut Cursor::new(test_font())).unwrap();
+        let a = font.get_character_mapping()[0x41];
+        font.set_ascent_descent(22, 5);
+        font.get_glyph_mut(a).unwrap().set_metrics(2, 7, 9);
+        font.set_character_glyph(0x42, a);
+
+        let font = read_lazy_font(&mut Cursor::new(write(&font))).unwrap();
+        assert_eq!((font.get_ascent(), font.get_descent()), (22, 5));
+        let info = font.get_glyph_for_character(0x42).get_info();
+        assert_eq!(
+            (info.bearing_x, info.bearing_y, info.advance_width),
+            (2, 7, 9)
+        );
+        assert_eq!(info.actual_size(), (5, 7));
+        assert_eq!(font.get_glyph_for_character(0x43).get_info().bearing_x, 1);
+    }
+}
@@ -33,34 +33,90 @@
 //! ```
 //! let compressed = [0b11000000, 0x48, 0x45, 0x4c, 0x4c, 0x4f, 0x20, 0x30, 0x05, 0x80, 0x0b];
 //! let mut decompressed = Vec::new();
-//! shin_core::format::lz77::decompress::<12>(&compressed, &mut decompressed);
+//! shin_core::format::lz77::decompress::<12>(&compressed, &mut decompressed).unwrap();
 //! assert_eq!(decompressed, b"HELLO HELLO HELLO HELLO");
 //! ```
 //!
+//! Corrupted data (a reference pointing before the start of the output or cut in the middle) is reported as an [`Lz77Error`]:
+//!
+//! ```
+//! let mut decompressed = Vec::new();
+//! assert!(shin_core::format::lz77::decompress::<12>(&[0b00000010, 0x48, 0x30, 0x05], &mut decompressed).is_err());
+//! assert!(shin_core::format::lz77::decompress::<12>(&[0b00000010, 0x48, 0x30], &mut decompressed).is_err());
+//! ```
+//!
 //! Encoding is (to be) implemented using a sliding window and a greedy algorithm.
 //! Theoretically the efficiency can be improved by using a bit of backtracking,
 //!     but it seems this improves compression ratio only by several percent (not worth the time).
 
-use std::io;
+use snafu::Snafu;
+
+#[derive(Debug, Snafu)]
+pub enum Lz77Error {
+    /// The compressed data ends in the middle of a back reference at `{position}`
+    TruncatedReference { position: u64 },
+    /// The back reference at `{position}` points `{back_offset}` bytes back, but only `{available}` bytes were decompressed so far
+    ReferenceOutOfBounds {
+        position: u64,
+        back_offset: usize,
+        available: usize,
+    },
+}
+
+/// Appends `len` bytes starting `back_offset` bytes before the end of the `output`
+#[inline(always)]
+fn copy_match(output: &mut Vec<u8>, back_offset: usize, len: usize) {
+    let start = output.len() - back_offset;
+    if back_offset >= len {
+        output.extend_from_within(start..start + len);
+    } else {
+        // the match overlaps with itself, so the last `back_offset` bytes are repeated
+        // copy them in doubling blocks, always a whole number of periods from `start`, instead of byte by byte
+        output.reserve(len);
+        let mut remaining = len;
+        while remaining > 0 {
+            let block = remaining.min(output.len() - start);
+            output.extend_from_within(start..start + block);
+            remaining -= block;
+        }
+    }
+}
+
+pub fn decompress<const OFFSET_BITS: u32>(
+    input: &[u8],
+    output: &mut Vec<u8>,
+) -> Result<(), Lz77Error> {
+    let mut position = 0;
 
-use bytes::Buf;
+    while position < input.len() {
+        let map = input[position];
+        position += 1;
 
-pub fn decompress<const OFFSET_BITS: u32>(input: &[u8], output: &mut Vec<u8>) {
-    let mut input = io::Cursor::new(input);
+        if map == 0 && input.len() - position >= 8 {
+            /* a whole block of literals */
+            output.extend_from_slice(&input[position..position + 8]);
+            position += 8;
+            continue;
+        }
 
-    while input.has_remaining() {
-        let map = input.get_u8();
         for i in 0..8 {
-            if !input.has_remaining() {
+            if position >= input.len() {
                 break;
             }
 
             if ((map >> i) & 1) == 0 {
                 /* literal value */
-                output.push(input.get_u8());
+                output.push(input[position]);
+                position += 1;
             } else {
                 /* back seek */
-                let backseek_spec = input.get_u16(); // big endian Oo
+                let Some(&[high, low]) = input.get(position..position + 2) else {
+                    return TruncatedReferenceSnafu {
+                        position: position as u64,
+                    }
+                    .fail();
+                };
+                let backseek_spec = u16::from_be_bytes([high, low]); // big endian Oo
 
                 /*  MSB  XXXXXXXX          YYYYYYYY    LSB
                     val  len               backOffset
@@ -69,16 +125,97 @@ pub fn decompress<const OFFSET_BITS: u32>(input: &[u8], output: &mut Vec<u8>) {
 
                 let back_offset_mask = (1 << OFFSET_BITS) - 1; // magic to get the last OFFSET_BITS bits
 
-                let len = (backseek_spec >> OFFSET_BITS) + 3;
-                let back_offset = (backseek_spec & back_offset_mask) + 1;
+                let len = (backseek_spec >> OFFSET_BITS) as usize + 3;
+                let back_offset = (backseek_spec & back_offset_mask) as usize + 1;
+
+                if back_offset > output.len() {
+                    return ReferenceOutOfBoundsSnafu {
+                        position: position as u64,
+                        back_offset,
+                        available: output.len(),
+                    }
+                    .fail();
+                }
+
+                copy_match(output, back_offset, len);
+                position += 2;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::decompress;
+
+    /// The straightforward byte by byte implementation
+    fn decompress_naive<const OFFSET_BITS: u32>(input: &[u8]) -> Vec<u8> {
+        let mut output = Vec::new();
+        let mut input = input.iter().copied();
+        while let Some(map) = input.next() {
+            for i in 0..8 {
+                if ((map >> i) & 1) == 0 {
+                    let Some(literal) = input.next() else { break };
+                    output.push(literal);
+                } else {
+                    let Some(high) = input.next() else { break };
+                    let spec = u16::from_be_bytes([high, input.next().unwrap()]);
+                    let len = (spec >> OFFSET_BITS) + 3;
+                    let back_offset = (spec & ((1 << OFFSET_BITS) - 1)) as usize + 1;
+                    for _ in 0..len {
+                        output.push(output[output.len() - back_offset]);
+                    }
+                }
+            }
+        }
+        output
+    }
 
-                for _ in 0..len {
-                    let last = output.len() - back_offset as usize;
-                    // TODO: make this fallible?
-                    // TODO: this might be optimized by stopping the bounds checking after we have enough data to guarantee that it's in bounds
-                    output.push(output[last]);
+    /// Generates a valid stream with a mix of literals, short and long, overlapping and not, references
+    fn random_stream<const OFFSET_BITS: u32>(rng: &mut StdRng, blocks: usize) -> Vec<u8> {
+        let mut stream = Vec::new();
+        let mut decompressed_len = 0usize;
+        for _ in 0..blocks {
+            let map_position = stream.len();
+            stream.push(0);
+            // make the all-literal blocks common to exercise the fast path
+            let literals_only = rng.gen_bool(0.3);
+            for i in 0..8 {
+                if literals_only || decompressed_len == 0 || rng.gen_bool(0.5) {
+                    stream.push(rng.gen());
+                    decompressed_len += 1;
+                } else {
+                    stream[map_position] |= 1 << i;
+                    let max_offset = decompressed_len.min(1 << OFFSET_BITS);
+                    let back_offset = rng.gen_range(1..=max_offset);
+                    let len = rng.gen_range(0..1u16 << (16 - OFFSET_BITS));
+                    let spec = (len << OFFSET_BITS) | (back_offset - 1) as u16;
+                    stream.extend_from_slice(&spec.to_be_bytes());
+                    decompressed_len += len as usize + 3;
                 }
             }
         }
+        stream
+    }
+
+    #[test]
+    fn matches_naive() {
+        let mut rng = StdRng::seed_from_u64(42);
+        for _ in 0..200 {
+            let blocks = rng.gen_range(1..64);
+            let stream = random_stream::<12>(&mut rng, blocks);
+            let mut output = Vec::new();
+            decompress::<12>(&stream, &mut output).unwrap();
+            assert_eq!(output, decompress_naive::<12>(&stream));
+
+            let stream = random_stream::<10>(&mut rng, blocks);
+            let mut output = Vec::new();
+            decompress::<10>(&stream, &mut output).unwrap();
+            assert_eq!(output, decompress_naive::<10>(&stream));
+        }
     }
 }
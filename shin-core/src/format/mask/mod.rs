@@ -6,6 +6,18 @@ use anyhow::Result;
 use binrw::{BinRead, BinWrite};
 use image::{GrayImage, Luma};
 use itertools::Itertools;
+use snafu::{ensure, Snafu};
+
+use crate::format::{lz77::Lz77Error, slice_checked};
+
+/// Errors caused by malformed mask data
+#[derive(Debug, Snafu)]
+pub enum MaskError {
+    /// Failed to decompress the texel data
+    Decompress { source: Lz77Error },
+    /// The texel data has `{actual}` bytes, `{expected}` were expected
+    TexelsSizeMismatch { expected: usize, actual: usize },
+}
 
 #[derive(BinRead, BinWrite)]
 #[brw(little, magic = b"MSK4")]
@@ -43,18 +55,26 @@ fn read_texels(texels_data: &[u8], width: u32, height: u32) -> Result<GrayImage>
     let data = if compressed_size != 0 {
         // need to decompress...
         let mut out_buffer = Vec::with_capacity(decompressed_size);
-        let compressed = &data[..compressed_size];
-        super::lz77::decompress::<12>(compressed, &mut out_buffer);
-
-        assert_eq!(out_buffer.len(), decompressed_size);
+        let compressed = slice_checked(data, 0, compressed_size)?;
+        super::lz77::decompress::<12>(compressed, &mut out_buffer)
+            .map_err(|source| MaskError::Decompress { source })?;
 
         Cow::Owned(out_buffer)
     } else {
-        assert_eq!(data.len(), decompressed_size);
         Cow::Borrowed(data)
     };
+    ensure!(
+        data.len() == decompressed_size,
+        TexelsSizeMismatchSnafu {
+            expected: decompressed_size,
+            actual: data.len(),
+        }
+    );
 
     let mut result = GrayImage::new(width, height);
+    if width == 0 || height == 0 {
+        return Ok(result);
+    }
 
     for (row_data, result_row) in data.chunks_exact(stride).zip_eq(result.rows_mut()) {
         for (src, dst) in row_data
@@ -110,9 +130,16 @@ pub fn read_mask(source: &[u8]) -> Result<MaskTexture> {
 
     let header = MskHeader::read(source)?;
 
-    let data = &source.get_ref()[header.data_offset as usize..][..header.data_size as usize];
-    let vertices_data =
-        &source.get_ref()[header.vertices_data as usize..][..header.vertices_size as usize];
+    let data = slice_checked(
+        source.get_ref(),
+        header.data_offset as usize,
+        header.data_size as usize,
+    )?;
+    let vertices_data = slice_checked(
+        source.get_ref(),
+        header.vertices_data as usize,
+        header.vertices_size as usize,
+    )?;
 
     let vertices = read_vertices(vertices_data)?;
     let texels = read_texels(data, header.width as u32, header.height as u32)?;
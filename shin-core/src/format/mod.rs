@@ -16,3 +16,58 @@ pub mod texture_archive;
 
 #[cfg(test)]
 mod test_util;
+
+use snafu::Snafu;
+
+/// A region referenced by the file headers lies outside of the file
+///
+/// Readers use [`slice_checked`] instead of indexing, so that a corrupt file produces this error instead of a panic.
+#[derive(Debug, Snafu)]
+#[snafu(display(
+    "Data at {offset:#x} of size {size:#x} is out of bounds ({available:#x} bytes available)"
+))]
+pub struct OutOfBoundsError {
+    pub offset: usize,
+    pub size: usize,
+    pub available: usize,
+}
+
+/// Gets a subslice of `size` bytes at `offset`, checking the bounds
+pub(crate) fn slice_checked(
+    data: &[u8],
+    offset: usize,
+    size: usize,
+) -> Result<&[u8], OutOfBoundsError> {
+    data.get(offset..)
+        .and_then(|data| data.get(..size))
+        .ok_or(OutOfBoundsError {
+            offset,
+            size,
+            available: data.len(),
+        })
+}
+
+/// Maps the items in parallel on the [`shin_tasks::AsyncComputeTaskPool`], used to decode the picture chunks and textures
+///
+/// The items are split in about as many tasks as there are threads instead of spawning a task per item, as there can be a lot of small chunks.
+/// A single item is mapped on the current thread.
+pub(crate) fn par_map<T, R, F>(items: &[T], f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send + 'static,
+    F: Fn(&T) -> R + Send + Sync,
+{
+    use shin_tasks::ParallelSlice;
+
+    if items.len() <= 1 {
+        return items.iter().map(f).collect();
+    }
+
+    items
+        .par_splat_map(shin_tasks::AsyncComputeTaskPool::get(), None, |chunk| {
+            chunk.iter().map(&f).collect::<Vec<_>>()
+        })
+        .into_iter()
+        .flatten()
+        .collect()
+}
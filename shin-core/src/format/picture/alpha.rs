@@ -0,0 +1,131 @@
+//! Straight and premultiplied alpha.
+//!
+//! The picture and bustup decoders produce images with straight (non-premultiplied) alpha, this is what the game's shaders expect.
+//! The fully transparent pixels are black though (the bustup decoder even clears the areas not covered by the vertices), which darkens the edges when the texture is sampled with linear filtering.
+//! [`bleed_transparent_colors`] fixes this for straight alpha textures, premultiplied ones don't have the problem at all.
+
+use image::RgbaImage;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum AlphaMode {
+    /// The color channels are independent of the alpha
+    Straight,
+    /// The color channels are multiplied by the alpha
+    Premultiplied,
+}
+
+pub fn premultiply_alpha(image: &mut RgbaImage) {
+    for pixel in image.pixels_mut() {
+        let alpha = pixel[3] as u32;
+        for channel in &mut pixel.0[..3] {
+            *channel = ((*channel as u32 * alpha + 127) / 255) as u8;
+        }
+    }
+}
+
+/// The color of the fully transparent pixels is lost and becomes black
+pub fn unpremultiply_alpha(image: &mut RgbaImage) {
+    for pixel in image.pixels_mut() {
+        let alpha = pixel[3] as u32;
+        for channel in &mut pixel.0[..3] {
+            *channel = if alpha == 0 {
+                0
+            } else {
+                ((*channel as u32 * 255 + alpha / 2) / alpha).min(255) as u8
+            };
+        }
+    }
+}
+
+pub fn convert_alpha(image: &mut RgbaImage, from: AlphaMode, to: AlphaMode) {
+    match (from, to) {
+        (AlphaMode::Straight, AlphaMode::Premultiplied) => premultiply_alpha(image),
+        (AlphaMode::Premultiplied, AlphaMode::Straight) => unpremultiply_alpha(image),
+        (AlphaMode::Straight, AlphaMode::Straight)
+        | (AlphaMode::Premultiplied, AlphaMode::Premultiplied) => {}
+    }
+}
+
+/// Gives the fully transparent pixels of a straight alpha image the average color of their visible neighbours
+///
+/// The pixels stay transparent, but the linear filtering no longer mixes black into the edges.
+/// A single pixel of bleeding is enough, as the filtering only looks at the adjacent pixels.
+pub fn bleed_transparent_colors(image: &mut RgbaImage) {
+    let source = image.clone();
+    let (width, height) = source.dimensions();
+
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        if pixel[3] != 0 {
+            continue;
+        }
+
+        let mut sum = [0u32; 3];
+        let mut count = 0;
+        for ny in y.saturating_sub(1)..=(y + 1).min(height - 1) {
+            for nx in x.saturating_sub(1)..=(x + 1).min(width - 1) {
+                let neighbour = source.get_pixel(nx, ny);
+                if neighbour[3] == 0 {
+                    continue;
+                }
+                for (sum, &channel) in sum.iter_mut().zip(&neighbour.0[..3]) {
+                    *sum += channel as u32;
+                }
+                count += 1;
+            }
+        }
+
+        if count > 0 {
+            for (channel, sum) in pixel.0[..3].iter_mut().zip(sum) {
+                *channel = (sum / count) as u8;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgba, RgbaImage};
+
+    use super::{bleed_transparent_colors, premultiply_alpha, unpremultiply_alpha};
+
+    #[test]
+    fn premultiply_roundtrip() {
+        let mut image = RgbaImage::from_fn(4, 1, |x, _| match x {
+            0 => Rgba([255, 128, 0, 255]),
+            1 => Rgba([255, 128, 0, 128]),
+            2 => Rgba([200, 100, 50, 0]),
+            _ => Rgba([10, 20, 30, 1]),
+        });
+
+        premultiply_alpha(&mut image);
+        assert_eq!(image.get_pixel(0, 0), &Rgba([255, 128, 0, 255]));
+        assert_eq!(image.get_pixel(1, 0), &Rgba([128, 64, 0, 128]));
+        assert_eq!(image.get_pixel(2, 0), &Rgba([0, 0, 0, 0]));
+        assert_eq!(image.get_pixel(3, 0), &Rgba([0, 0, 0, 1]));
+
+        unpremultiply_alpha(&mut image);
+        assert_eq!(image.get_pixel(0, 0), &Rgba([255, 128, 0, 255]));
+        assert_eq!(image.get_pixel(1, 0), &Rgba([255, 128, 0, 128]));
+        assert_eq!(image.get_pixel(2, 0), &Rgba([0, 0, 0, 0]));
+    }
+
+    #[test]
+    fn bleed() {
+        let mut image = RgbaImage::from_pixel(3, 3, Rgba([0, 0, 0, 0]));
+        image.put_pixel(0, 0, Rgba([100, 0, 0, 255]));
+        image.put_pixel(2, 0, Rgba([0, 100, 0, 10]));
+
+        bleed_transparent_colors(&mut image);
+
+        // the visible pixels are untouched
+        assert_eq!(image.get_pixel(0, 0), &Rgba([100, 0, 0, 255]));
+        assert_eq!(image.get_pixel(2, 0), &Rgba([0, 100, 0, 10]));
+        // neighbours of both
+        assert_eq!(image.get_pixel(1, 0), &Rgba([50, 50, 0, 0]));
+        assert_eq!(image.get_pixel(1, 1), &Rgba([50, 50, 0, 0]));
+        // neighbour of one
+        assert_eq!(image.get_pixel(0, 1), &Rgba([100, 0, 0, 0]));
+        // not adjacent to anything visible
+        assert_eq!(image.get_pixel(1, 2), &Rgba([0, 0, 0, 0]));
+    }
+}
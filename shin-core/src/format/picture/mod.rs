@@ -2,17 +2,47 @@
 //!
 //! The picture format splits the picture in chunks that are first separately transformed by using a dictionary or a differential encoding and an optional lz77 compression on top.
 //!
+//! The decoded images have straight alpha, see [`AlphaMode`].
+//!
 //! It also stores vertices for each chunk specifying which regions of the image have transparency and which don't. This potentially allows for a more efficient GPU rendering (this implementation doesn't do this yet).
 
-use std::{borrow::Cow, io, sync::Mutex};
+mod alpha;
+
+use std::{borrow::Cow, io};
 
-use anyhow::{bail, Context, Result};
+pub use alpha::{
+    bleed_transparent_colors, convert_alpha, premultiply_alpha, unpremultiply_alpha, AlphaMode,
+};
+use anyhow::{Context, Result};
 use binrw::{prelude::*, Endian};
 use bitflags::bitflags;
 use bytemuck::{Pod, Zeroable};
 use image::{ImageBuffer, RgbaImage};
 use itertools::Itertools;
-use shin_tasks::ParallelSlice;
+use snafu::{ensure, Snafu};
+
+use crate::format::{lz77::Lz77Error, par_map, slice_checked, OutOfBoundsError};
+
+/// Errors caused by malformed picture data
+#[derive(Debug, Snafu)]
+pub enum PictureError {
+    /// Unsupported picture format version `{version}`
+    UnsupportedVersion { version: u32 },
+    /// The file size in the header (`{expected}`) doesn't match the actual size (`{actual}`)
+    FileSizeMismatch { expected: u32, actual: usize },
+    /// Unknown value of the header field `{field}`: `{value}`
+    UnknownHeaderField { field: &'static str, value: u32 },
+    #[snafu(transparent)]
+    OutOfBounds { source: OutOfBoundsError },
+    /// Failed to decompress the texture data
+    Decompress { source: Lz77Error },
+    /// The texture data decompressed into `{actual}` bytes, `{expected}` were expected
+    DecompressedSizeMismatch { expected: usize, actual: usize },
+    /// The differential texture encoding is not supported
+    DifferentialEncodingUnsupported,
+    /// Chunk offsets are only supported in bustups, found `({offset_x}, {offset_y})`
+    ChunkOffsetUnsupported { offset_x: u32, offset_y: u32 },
+}
 
 #[derive(BinRead, BinWrite, Debug)]
 #[brw(little, magic = b"PIC4")]
@@ -222,8 +252,13 @@ impl<'a> PictureBuilder<'a> for SimpleMergedPicture {
     fn add_chunk(&mut self, (x, y): (u32, u32), chunk: PictureChunk) -> Result<()> {
         // I think those are used only in bustups
         // I am not sure how to handle them yet
-        assert_eq!(chunk.offset_x, 0);
-        assert_eq!(chunk.offset_y, 0);
+        ensure!(
+            chunk.offset_x == 0 && chunk.offset_y == 0,
+            ChunkOffsetUnsupportedSnafu {
+                offset_x: chunk.offset_x,
+                offset_y: chunk.offset_y,
+            }
+        );
 
         let chunk_image = chunk.data;
         image::imageops::replace(&mut self.image, &chunk_image, x as i64, y as i64);
@@ -285,31 +320,92 @@ fn decode_dict(
     width: usize,
     stride: usize,
 ) {
-    if let Some(alpha_data) = alpha_data {
-        assert_eq!(alpha_data.len(), encoded_data.len());
-
-        for ((row, alpha_row), dest_row) in encoded_data
-            .chunks(stride)
-            .zip(alpha_data.chunks(stride))
-            .zip_eq(image.rows_mut())
-        {
-            for ((index, alpha), dest_pixel) in row[..width]
-                .iter()
-                .cloned()
-                .zip(alpha_row[..width].iter().cloned())
-                .zip_eq(dest_row)
-            {
-                let mut val = dict[index as usize];
-                val.a = alpha;
-                *dest_pixel = val.into();
-            }
+    // little endian u32s, the alpha is in the high byte
+    let dict = dict.map(|v| u32::from_le_bytes([v.r, v.g, v.b, v.a]));
+
+    for (y, dest_row) in image.chunks_exact_mut(width * 4).enumerate() {
+        let row = &encoded_data[y * stride..][..width];
+        let alpha_row = alpha_data.map(|alpha_data| &alpha_data[y * stride..][..width]);
+        decode_dict_row(&dict, row, alpha_row, dest_row);
+    }
+}
+
+fn decode_dict_row(dict: &[u32; 0x100], row: &[u8], alpha_row: Option<&[u8]>, dest: &mut [u8]) {
+    #[allow(unused_mut)]
+    let mut decoded = 0;
+
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx2") {
+        // SAFETY: we have checked that the CPU supports AVX2
+        decoded = unsafe { simd::decode_dict_row_avx2(dict, row, alpha_row, dest) };
+    }
+
+    decode_dict_row_scalar(
+        dict,
+        &row[decoded..],
+        alpha_row.map(|alpha_row| &alpha_row[decoded..]),
+        &mut dest[decoded * 4..],
+    );
+}
+
+fn decode_dict_row_scalar(
+    dict: &[u32; 0x100],
+    row: &[u8],
+    alpha_row: Option<&[u8]>,
+    dest: &mut [u8],
+) {
+    let dest = dest.chunks_exact_mut(4);
+    if let Some(alpha_row) = alpha_row {
+        for ((&index, &alpha), dest_pixel) in row.iter().zip_eq(alpha_row).zip_eq(dest) {
+            let value = (dict[index as usize] & 0x00ffffff) | (alpha as u32) << 24;
+            dest_pixel.copy_from_slice(&value.to_le_bytes());
         }
     } else {
-        for (row, dest_row) in encoded_data.chunks(stride).zip_eq(image.rows_mut()) {
-            for (index, dest_pixel) in row[..width].iter().cloned().zip_eq(dest_row) {
-                *dest_pixel = dict[index as usize].into();
+        for (&index, dest_pixel) in row.iter().zip_eq(dest) {
+            dest_pixel.copy_from_slice(&dict[index as usize].to_le_bytes());
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod simd {
+    use std::arch::x86_64::*;
+
+    /// Decodes the pixels in groups of 8 with a gather from the dictionary, returns how many pixels were decoded
+    ///
+    /// The rest (less than 8 pixels) is left for the scalar code.
+    ///
+    /// # Safety
+    ///
+    /// The CPU must support AVX2
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn decode_dict_row_avx2(
+        dict: &[u32; 0x100],
+        row: &[u8],
+        alpha_row: Option<&[u8]>,
+        dest: &mut [u8],
+    ) -> usize {
+        let count = row.len() / 8 * 8;
+        assert!(dest.len() >= count * 4);
+        if let Some(alpha_row) = alpha_row {
+            assert!(alpha_row.len() >= count);
+        }
+
+        let color_mask = _mm256_set1_epi32(0x00ffffff);
+        for i in (0..count).step_by(8) {
+            let indices = _mm256_cvtepu8_epi32(_mm_loadl_epi64(row.as_ptr().add(i).cast()));
+            let mut pixels = _mm256_i32gather_epi32::<4>(dict.as_ptr().cast(), indices);
+            if let Some(alpha_row) = alpha_row {
+                let alpha = _mm256_cvtepu8_epi32(_mm_loadl_epi64(alpha_row.as_ptr().add(i).cast()));
+                pixels = _mm256_or_si256(
+                    _mm256_and_si256(pixels, color_mask),
+                    _mm256_slli_epi32::<24>(alpha),
+                );
             }
+            _mm256_storeu_si256(dest.as_mut_ptr().add(i * 4).cast(), pixels);
         }
+
+        count
     }
 }
 
@@ -319,35 +415,47 @@ pub fn read_texture(
     target_image: &mut RgbaImage,
     use_dict_encoding: bool,
     use_inline_alpha: bool,
-) {
+) -> Result<(), PictureError> {
     let width = target_image.width();
     let height = target_image.height();
 
+    if width == 0 || height == 0 {
+        return Ok(());
+    }
+
     // TODO: maybe replace this bit alignment magic with easier to understand operations?
     let differential_stride = ((width * 4 + 0xf) & 0xfffffff0) as usize;
     let dictionary_stride = ((width + 3) & 0xfffffffc) as usize;
 
+    let decompressed_size = if use_dict_encoding {
+        let mut out_size = dictionary_stride * height as usize;
+        if !use_inline_alpha {
+            out_size *= 2;
+        }
+        out_size += 0x400; // for the dictionary
+        out_size
+    } else {
+        differential_stride * height as usize
+    };
+
     let data = if compressed_size != 0 {
         // need to decompress...
-        let decompressed_size = if use_dict_encoding {
-            let mut out_size = dictionary_stride * height as usize;
-            if !use_inline_alpha {
-                out_size *= 2;
-            }
-            out_size += 0x400; // for the dictionary
-            out_size
-        } else {
-            differential_stride * height as usize
-        };
         let mut out_buffer = Vec::with_capacity(decompressed_size);
-        let compressed = &data[..compressed_size];
-        super::lz77::decompress::<12>(compressed, &mut out_buffer);
-
-        assert_eq!(decompressed_size, out_buffer.len());
+        let compressed = slice_checked(data, 0, compressed_size)?;
+        super::lz77::decompress::<12>(compressed, &mut out_buffer)
+            .map_err(|source| PictureError::Decompress { source })?;
+
+        ensure!(
+            decompressed_size == out_buffer.len(),
+            DecompressedSizeMismatchSnafu {
+                expected: decompressed_size,
+                actual: out_buffer.len(),
+            }
+        );
 
         Cow::Owned(out_buffer)
     } else {
-        Cow::Borrowed(data)
+        Cow::Borrowed(slice_checked(data, 0, decompressed_size)?)
     };
 
     if use_dict_encoding {
@@ -377,9 +485,11 @@ pub fn read_texture(
             alpha_data,
             width as usize,
             stride,
-        )
+        );
+
+        Ok(())
     } else {
-        todo!("decode differential")
+        DifferentialEncodingUnsupportedSnafu.fail()
     }
 }
 
@@ -421,13 +531,20 @@ pub fn read_picture_chunk(chunk_data: &[u8]) -> Result<PictureChunk> {
         transparent_vertices,
     );
 
+    let data_offset = reader.position() as usize;
+    let texture_data = slice_checked(
+        chunk_data,
+        data_offset,
+        chunk_data.len().saturating_sub(data_offset),
+    )?;
+
     read_texture(
-        &chunk_data[reader.position() as usize..],
+        texture_data,
         header.compressed_size as usize,
         &mut chunk.data,
         header.use_dict_encoding(),
         header.use_inline_alpha(),
-    );
+    )?;
 
     Ok(chunk)
 }
@@ -439,31 +556,46 @@ pub fn read_picture<'a, B: PictureBuilder<'a>>(
     let mut source = io::Cursor::new(source);
     let header = PicHeader::read(&mut source)?;
 
-    if header.version != 3 {
-        bail!("Unsupported picture format version {}", header.version);
-    }
-
-    if header.file_size != source.get_ref().len() as u32 {
-        bail!("File size mismatch");
-    }
-
-    if !matches!(header.field_20, 0 | 1) {
-        bail!("Unknown field_20 value {}", header.field_20);
-    }
-
-    if header.field_32 != 0x1000 {
-        bail!("Unknown field_32 value {}", header.field_32);
-    }
+    ensure!(
+        header.version == 3,
+        UnsupportedVersionSnafu {
+            version: header.version
+        }
+    );
+    ensure!(
+        header.file_size as usize == source.get_ref().len(),
+        FileSizeMismatchSnafu {
+            expected: header.file_size,
+            actual: source.get_ref().len(),
+        }
+    );
+    ensure!(
+        matches!(header.field_20, 0 | 1),
+        UnknownHeaderFieldSnafu {
+            field: "field_20",
+            value: header.field_20,
+        }
+    );
+    ensure!(
+        header.field_32 == 0x1000,
+        UnknownHeaderFieldSnafu {
+            field: "field_32",
+            value: header.field_32,
+        }
+    );
 
     let mut chunks = Vec::new();
     for _ in 0..header.chunk_count {
         let chunk_desc = PicChunkDesc::read(&mut source)?;
-        let chunk_data =
-            &source.get_ref()[chunk_desc.offset as usize..][..chunk_desc.size as usize];
+        let chunk_data = slice_checked(
+            source.get_ref(),
+            chunk_desc.offset as usize,
+            chunk_desc.size as usize,
+        )?;
         chunks.push(((chunk_desc.x as usize, chunk_desc.y as usize), chunk_data));
     }
 
-    let builder = B::new(
+    let mut builder = B::new(
         builder_args,
         header.effective_width as u32,
         header.effective_height as u32,
@@ -471,26 +603,37 @@ pub fn read_picture<'a, B: PictureBuilder<'a>>(
         header.origin_y as i32,
         header.picture_id,
     );
-    // TODO: how should be parallelize it in bevy?
-    // bevy doesn't use rayon, so using it here may be suboptimal
-    // ideally we want to be generic over the parallelization strategy
-    let builder = Mutex::new(builder);
-    chunks
-        .par_chunk_map(shin_tasks::AsyncComputeTaskPool::get(), 1, |chunk| {
-            let &[(pos, data)] = chunk else {
-                unreachable!()
-            };
-            (pos, read_picture_chunk(data))
-        })
+    par_map(&chunks, |&(pos, data)| (pos, read_picture_chunk(data)))
         .into_iter()
-        .try_for_each(|(pos, chunk)| {
-            builder
-                .lock()
-                .unwrap()
-                .add_chunk((pos.0 as u32, pos.1 as u32), chunk?)
-        })?;
+        .try_for_each(|(pos, chunk)| builder.add_chunk((pos.0 as u32, pos.1 as u32), chunk?))?;
 
-    let listener = builder.into_inner().unwrap();
+    builder.build()
+}
 
-    listener.build()
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::{decode_dict_row, decode_dict_row_scalar};
+
+    #[test]
+    fn decode_dict_row_matches_scalar() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut dict = [0u32; 0x100];
+        rng.fill(&mut dict[..]);
+
+        // cover both the SIMD groups and the scalar tails
+        for width in 0..40 {
+            let row = (0..width).map(|_| rng.gen()).collect::<Vec<u8>>();
+            let alpha_row = (0..width).map(|_| rng.gen()).collect::<Vec<u8>>();
+
+            for alpha_row in [None, Some(alpha_row.as_slice())] {
+                let mut expected = vec![0; width * 4];
+                decode_dict_row_scalar(&dict, &row, alpha_row, &mut expected);
+                let mut actual = vec![0; width * 4];
+                decode_dict_row(&dict, &row, alpha_row, &mut actual);
+                assert_eq!(actual, expected, "width {}", width);
+            }
+        }
+    }
 }
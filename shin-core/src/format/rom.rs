@@ -5,8 +5,12 @@
 //! This makes the implementation much simpler and file access much faster, but it increases startup time a bit.
 //!
 //! When using BufReader, the startup time with Umineko's rom is about 300 ms on my machine, so it's not a big deal.
+//!
+//! On the platforms supporting it, the ROM can also be memory mapped (see [`RomMap`]), so that the files can be accessed without copying them through an intermediate buffer.
 
 use std::{collections::BTreeMap, io, io::SeekFrom};
+#[cfg(not(target_arch = "wasm32"))]
+use std::{fs::File, ops::Deref, path::Path, sync::Arc};
 
 use anyhow::{anyhow, bail, Context, Result};
 use binrw::{BinRead, BinResult, BinWrite, Endian, NullString};
@@ -314,6 +318,149 @@ impl<S: io::Read + io::Seek> RomReader<S> {
             stack: vec![("", self.index.iter())],
         }
     }
+
+    /// Lists all the files in the ROM, skipping the directories
+    pub fn files(&self) -> impl Iterator<Item = (String, IndexFile)> + '_ {
+        self.traverse().filter_map(|(name, entry)| match entry {
+            IndexEntry::File(file) => Some((name, *file)),
+            IndexEntry::Directory(_) => None,
+        })
+    }
+
+    /// Computes the digests of all the files, reading them one by one
+    pub fn digests(&mut self) -> Result<BTreeMap<String, FileDigest>> {
+        let files = self.files().collect::<Vec<_>>();
+        let mut buffer = Vec::new();
+        files
+            .into_iter()
+            .map(|(name, file)| {
+                buffer.clear();
+                io::Read::read_to_end(&mut self.open_file(file)?, &mut buffer)
+                    .with_context(|| format!("Reading {}", name))?;
+                Ok((name, FileDigest::of(&buffer)))
+            })
+            .collect()
+    }
+}
+
+/// Size and checksum of a file, used to tell which files differ between two ROMs without comparing their contents
+///
+/// CRC32 is not collision resistant, but it's good enough to detect the modifications of the game files.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct FileDigest {
+    pub size: u32,
+    pub crc32: u32,
+}
+
+impl FileDigest {
+    pub fn of(data: &[u8]) -> Self {
+        Self {
+            size: data.len() as u32,
+            crc32: crc32fast::hash(data),
+        }
+    }
+}
+
+/// The contents of a memory mapped ROM file
+///
+/// It's cheap to clone, all the clones share the same mapping.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone)]
+pub struct RomMap(Arc<memmap2::Mmap>);
+
+#[cfg(not(target_arch = "wasm32"))]
+impl RomMap {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::open(path)?;
+        // SAFETY: the ROM is assumed to not change while it's open, the same way `RomReader` does
+        // modifying it from another process would still be UB, but there's no way to prevent that with mmap
+        let mmap = unsafe { memmap2::Mmap::map(&file)? };
+        Ok(Self(Arc::new(mmap)))
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl AsRef<[u8]> for RomMap {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// Data of a file in a memory mapped ROM
+///
+/// Holds a reference to the mapping, so it can outlive the [`RomReader`] and be sent to other threads.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone)]
+pub struct RomFileData {
+    map: RomMap,
+    offset: usize,
+    size: usize,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Deref for RomFileData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.map.as_ref()[self.offset..self.offset + self.size]
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl AsRef<[u8]> for RomFileData {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+/// A [`RomReader`] reading from a memory mapped ROM, allowing to access the files without copying
+#[cfg(not(target_arch = "wasm32"))]
+pub type MappedRomReader = RomReader<io::Cursor<RomMap>>;
+
+#[cfg(not(target_arch = "wasm32"))]
+impl RomReader<io::Cursor<RomMap>> {
+    pub fn open_mapped(path: impl AsRef<Path>) -> Result<Self> {
+        let map = RomMap::open(path).context("Mapping rom file")?;
+        Self::new(io::Cursor::new(map))
+    }
+
+    /// Returns the file data, without copying it
+    pub fn file_data(&self, file: IndexFile) -> Result<RomFileData> {
+        let map = self.reader.get_ref();
+        let offset = file.data_offset as usize;
+        let size = file.data_size as usize;
+        if offset
+            .checked_add(size)
+            .map_or(true, |end| end > map.as_ref().len())
+        {
+            bail!(
+                "File at {:#x} of size {:#x} is out of the rom bounds",
+                offset,
+                size
+            );
+        }
+
+        Ok(RomFileData {
+            map: map.clone(),
+            offset,
+            size,
+        })
+    }
+
+    /// Computes the digests of all the files in parallel, which is much faster than [`RomReader::digests`] for large ROMs
+    ///
+    /// Requires the task pools to be initialized (see [`crate::create_task_pools`]).
+    pub fn par_digests(&self) -> Result<BTreeMap<String, FileDigest>> {
+        let files = self.files().collect::<Vec<_>>();
+        crate::format::par_map(&files, |(name, file)| {
+            let data = self
+                .file_data(*file)
+                .with_context(|| format!("Reading {}", name))?;
+            Ok((name.clone(), FileDigest::of(&data)))
+        })
+        .into_iter()
+        .collect()
+    }
 }
 
 pub struct Traverse<'a> {
@@ -0,0 +1,145 @@
+//! Descriptions of the save layouts used by the different shin games
+//!
+//! All the games share the overall structure of the save, but differ in the obfuscation key, the number of slots and some details of the format.
+//!
+//! Only the umineko layout is verified against real saves, the others are based on it and may need adjustments.
+
+use anyhow::{bail, Result};
+use itertools::Itertools;
+
+use super::{crc32, obfuscation};
+
+/// How the integrity of the save is checked
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SaveChecksum {
+    /// The CRC32 of the data is appended to it and is used as the key of an inner obfuscation layer
+    Crc32,
+    /// Only the outer obfuscation layer is applied, the integrity is not checked
+    None,
+}
+
+#[derive(Debug)]
+pub struct SaveDescriptor {
+    /// Short name of the game, used to select the descriptor in the tools
+    pub game: &'static str,
+    /// The string the obfuscation key is derived from
+    pub key_seed: &'static str,
+    /// The value of the first byte of the save, bumped by the games when the layout changes
+    pub version: u8,
+    pub manual_slot_count: usize,
+    /// The persistent flags beyond this count are rejected when reading
+    pub max_persist_flags: usize,
+    pub checksum: SaveChecksum,
+}
+
+pub static UMINEKO: SaveDescriptor = SaveDescriptor {
+    game: "umineko",
+    key_seed: "うみねこのなく頃に咲",
+    version: 1,
+    manual_slot_count: 100,
+    // the length is stored as u16, no tighter limit is known
+    max_persist_flags: u16::MAX as usize,
+    checksum: SaveChecksum::Crc32,
+};
+
+pub static HIGURASHI: SaveDescriptor = SaveDescriptor {
+    game: "higurashi",
+    key_seed: "ひぐらしのなく頃に奉",
+    version: 1,
+    manual_slot_count: 100,
+    max_persist_flags: u16::MAX as usize,
+    checksum: SaveChecksum::Crc32,
+};
+
+pub static DC4: SaveDescriptor = SaveDescriptor {
+    game: "dc4",
+    key_seed: "D.C.4 ～ダ・カーポ4～",
+    version: 1,
+    manual_slot_count: 100,
+    max_persist_flags: u16::MAX as usize,
+    checksum: SaveChecksum::Crc32,
+};
+
+pub static KNOWN_GAMES: &[&SaveDescriptor] = &[&UMINEKO, &HIGURASHI, &DC4];
+
+impl SaveDescriptor {
+    pub fn by_game(game: &str) -> Result<&'static SaveDescriptor> {
+        match KNOWN_GAMES.iter().find(|d| d.game == game) {
+            Some(descriptor) => Ok(descriptor),
+            None => bail!(
+                "Unknown game {:?}, known games are: {}",
+                game,
+                KNOWN_GAMES.iter().map(|d| d.game).join(", ")
+            ),
+        }
+    }
+
+    /// Finds the game the save belongs to, by trying to deobfuscate it with the keys of all the known games
+    ///
+    /// Returns the descriptor along with the deobfuscated data. The version is not checked here, see [`SaveDescriptor::check_version`].
+    pub fn detect(data: &[u8]) -> Result<(&'static SaveDescriptor, Vec<u8>)> {
+        // the games without a checksum would accept any data, so they are tried last and only if their version matches
+        let checked = KNOWN_GAMES
+            .iter()
+            .filter(|d| d.checksum == SaveChecksum::Crc32)
+            .find_map(|d| d.deobfuscate(data).ok().map(|data| (*d, data)));
+        if let Some(result) = checked {
+            return Ok(result);
+        }
+
+        let unchecked = KNOWN_GAMES
+            .iter()
+            .filter(|d| d.checksum == SaveChecksum::None)
+            .find_map(|d| {
+                let data = d.deobfuscate(data).ok()?;
+                (data.first() == Some(&d.version)).then_some((*d, data))
+            });
+        match unchecked {
+            Some(result) => Ok(result),
+            None => bail!(
+                "Could not detect the game of the save: it can't be deobfuscated with the keys of any of the known games ({})",
+                KNOWN_GAMES.iter().map(|d| d.game).join(", ")
+            ),
+        }
+    }
+
+    pub fn obfuscation_key(&self) -> u32 {
+        crc32::crc32(self.key_seed.as_bytes(), 0)
+    }
+
+    pub fn deobfuscate(&self, data: &[u8]) -> Result<Vec<u8>> {
+        self.deobfuscate_with_key(data, self.obfuscation_key())
+    }
+
+    pub fn deobfuscate_with_key(&self, data: &[u8], key: u32) -> Result<Vec<u8>> {
+        match self.checksum {
+            SaveChecksum::Crc32 => obfuscation::decode(data, key),
+            SaveChecksum::None => Ok(obfuscation::decode_unchecked(data, key)),
+        }
+    }
+
+    pub fn obfuscate(&self, data: &[u8]) -> Vec<u8> {
+        self.obfuscate_with_key(data, self.obfuscation_key())
+    }
+
+    pub fn obfuscate_with_key(&self, data: &[u8], key: u32) -> Vec<u8> {
+        match self.checksum {
+            SaveChecksum::Crc32 => obfuscation::encode(data, key),
+            SaveChecksum::None => obfuscation::encode_unchecked(data, key),
+        }
+    }
+
+    /// Checks the version byte of the deobfuscated save
+    pub fn check_version(&self, version: u8) -> Result<()> {
+        match version {
+            0 => bail!("The {} save is not initialized (version 0)", self.game),
+            v if v == self.version => Ok(()),
+            v => bail!(
+                "Unsupported {} save version {} (only version {} is supported)",
+                self.game,
+                v,
+                self.version
+            ),
+        }
+    }
+}
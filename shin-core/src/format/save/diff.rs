@@ -0,0 +1,183 @@
+//! Comparison of two saves and merging of their read state, used to move the progress between the saves (like when switching platforms).
+
+use super::{GameData, SaveVectors, Savedata};
+
+/// One of the bitmask vectors of the [`SaveVectors`]
+///
+/// The bits are numbered from the least significant bit of the first word, the same way the [`PersistData`](super::PersistData) flags are numbered by their index.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SaveBitVector {
+    /// [`SaveVectors::seen_messages_mask`], the bit index is the message id
+    SeenMessages,
+    /// [`SaveVectors::vec2`], probably the seen choices
+    SeenChoices,
+    /// [`SaveVectors::vec4`], indexing into the picture info table
+    UnlockedPictures,
+    /// [`SaveVectors::vec5`], indexing into the BGM info table
+    UnlockedBgms,
+    /// [`SaveVectors::vec6`], probably indexing into the tips info table
+    UnlockedTips,
+}
+
+impl SaveBitVector {
+    pub const ALL: [SaveBitVector; 5] = [
+        SaveBitVector::SeenMessages,
+        SaveBitVector::SeenChoices,
+        SaveBitVector::UnlockedPictures,
+        SaveBitVector::UnlockedBgms,
+        SaveBitVector::UnlockedTips,
+    ];
+
+    pub fn words(self, vectors: &SaveVectors) -> &[u32] {
+        match self {
+            SaveBitVector::SeenMessages => &vectors.seen_messages_mask,
+            SaveBitVector::SeenChoices => &vectors.vec2,
+            SaveBitVector::UnlockedPictures => &vectors.vec4,
+            SaveBitVector::UnlockedBgms => &vectors.vec5,
+            SaveBitVector::UnlockedTips => &vectors.vec6,
+        }
+    }
+
+    fn words_mut(self, vectors: &mut SaveVectors) -> &mut Vec<u32> {
+        match self {
+            SaveBitVector::SeenMessages => &mut vectors.seen_messages_mask,
+            SaveBitVector::SeenChoices => &mut vectors.vec2,
+            SaveBitVector::UnlockedPictures => &mut vectors.vec4,
+            SaveBitVector::UnlockedBgms => &mut vectors.vec5,
+            SaveBitVector::UnlockedTips => &mut vectors.vec6,
+        }
+    }
+}
+
+/// A difference between two saves, as found by [`Savedata::diff`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum SaveChange {
+    PlaySeconds {
+        old: u32,
+        new: u32,
+    },
+    /// A persistent flag changed, the missing flags are treated as zero
+    PersistFlag {
+        index: usize,
+        old: i16,
+        new: i16,
+    },
+    /// A bit got set (`set` is `true`) or cleared
+    Bit {
+        vector: SaveBitVector,
+        index: usize,
+        set: bool,
+    },
+    /// An entry of [`SaveVectors::vec3`] changed, its meaning is not known
+    Vec3 {
+        index: usize,
+        old: Option<u8>,
+        new: Option<u8>,
+    },
+    Settings,
+    /// A save slot was written or cleared, `slot` is `None` for the auto save slot
+    Slot {
+        slot: Option<usize>,
+        old: Option<GameData>,
+        new: Option<GameData>,
+    },
+}
+
+fn bit(words: &[u32], index: usize) -> bool {
+    words
+        .get(index / 32)
+        .is_some_and(|word| word & (1 << (index % 32)) != 0)
+}
+
+fn diff_bits(vector: SaveBitVector, old: &[u32], new: &[u32], changes: &mut Vec<SaveChange>) {
+    for index in 0..old.len().max(new.len()) * 32 {
+        let (old, new) = (bit(old, index), bit(new, index));
+        if old != new {
+            changes.push(SaveChange::Bit {
+                vector,
+                index,
+                set: new,
+            });
+        }
+    }
+}
+
+impl Savedata {
+    /// Lists the differences from `self` to `new`, the changes describe the `new` state
+    pub fn diff(&self, new: &Savedata) -> Vec<SaveChange> {
+        let mut changes = Vec::new();
+
+        if self.play_seconds != new.play_seconds {
+            changes.push(SaveChange::PlaySeconds {
+                old: self.play_seconds,
+                new: new.play_seconds,
+            });
+        }
+
+        let (old_flags, new_flags) = (&self.persist_data.0, &new.persist_data.0);
+        for index in 0..old_flags.len().max(new_flags.len()) {
+            let old = old_flags.get(index).copied().unwrap_or(0);
+            let new = new_flags.get(index).copied().unwrap_or(0);
+            if old != new {
+                changes.push(SaveChange::PersistFlag { index, old, new });
+            }
+        }
+
+        for vector in SaveBitVector::ALL {
+            diff_bits(
+                vector,
+                vector.words(&self.save_vectors),
+                vector.words(&new.save_vectors),
+                &mut changes,
+            );
+        }
+
+        let (old_vec3, new_vec3) = (&self.save_vectors.vec3, &new.save_vectors.vec3);
+        for index in 0..old_vec3.len().max(new_vec3.len()) {
+            let old = old_vec3.get(index).copied();
+            let new = new_vec3.get(index).copied();
+            if old != new {
+                changes.push(SaveChange::Vec3 { index, old, new });
+            }
+        }
+
+        if self.settings != new.settings {
+            changes.push(SaveChange::Settings);
+        }
+
+        let old_slots = std::iter::once((None, &self.auto_save_slot)).chain(
+            self.manual_save_slots
+                .iter()
+                .enumerate()
+                .map(|(i, s)| (Some(i), s)),
+        );
+        let new_slots = std::iter::once(&new.auto_save_slot).chain(new.manual_save_slots.iter());
+        for ((slot, old), new) in old_slots.zip(new_slots) {
+            if old != new {
+                changes.push(SaveChange::Slot {
+                    slot,
+                    old: old.clone(),
+                    new: new.clone(),
+                });
+            }
+        }
+
+        changes
+    }
+
+    /// Adds the read state of the `other` save to this one: the read messages and choices, and the unlocked pictures, BGMs and tips
+    ///
+    /// Everything else, including the persistent flags, is kept as is, as the scenario might not expect their combinations.
+    pub fn merge_read_state(&mut self, other: &Savedata) {
+        for vector in SaveBitVector::ALL {
+            let words = vector.words_mut(&mut self.save_vectors);
+            let other = vector.words(&other.save_vectors);
+            if words.len() < other.len() {
+                words.resize(other.len(), 0);
+            }
+            for (word, other) in words.iter_mut().zip(other) {
+                *word |= other;
+            }
+        }
+    }
+}
@@ -1,22 +1,23 @@
 //! Support for decrypting and decoding save files.
 
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use bitbuffer::{BitRead, BitWrite, BitWriteStream, Endianness};
-use chrono::{NaiveDate, NaiveDateTime};
+use chrono::{Datelike, NaiveDate, NaiveDateTime, Timelike};
 use num_integer::Integer;
-use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
-use serde_big_array::BigArray;
 
 mod crc32;
+mod descriptor;
+mod diff;
 mod obfuscation;
 
+pub use descriptor::{SaveChecksum, SaveDescriptor, DC4, HIGURASHI, KNOWN_GAMES, UMINEKO};
+pub use diff::{SaveBitVector, SaveChange};
+
 type Endian = bitbuffer::BigEndian;
 const ENDIAN: Endian = bitbuffer::BigEndian;
 type BitReadStream<'a, E = Endian> = bitbuffer::BitReadStream<'a, E>;
 
-static GAME_KEY: Lazy<u32> = Lazy::new(|| crc32::crc32("うみねこのなく頃に咲".as_bytes(), 0));
-
 fn read_u8<E: Endianness>(reader: &mut BitReadStream<E>) -> bitbuffer::Result<u8> {
     reader.read_int(8)
 }
@@ -55,6 +56,38 @@ fn read_array<'a, T, E: Endianness, const N: usize>(
     Ok(res.map(|v| v.unwrap()))
 }
 
+fn write_vec<T, E: Endianness>(
+    writer: &mut BitWriteStream<E>,
+    items: &[T],
+    write: impl Fn(&mut BitWriteStream<E>, &T) -> bitbuffer::Result<()>,
+) -> bitbuffer::Result<()> {
+    writer.write_int(items.len() as u16, 16)?;
+    for item in items {
+        write(writer, item)?;
+    }
+    Ok(())
+}
+
+/// Pads the stream with zero bits to the byte boundary
+fn align<E: Endianness>(writer: &mut BitWriteStream<E>) -> bitbuffer::Result<()> {
+    let padding = (8 - writer.bit_len() % 8) % 8;
+    if padding > 0 {
+        writer.write_int(0u8, padding)?;
+    }
+    Ok(())
+}
+
+fn write_opt<T: BitWrite<E>, E: Endianness>(
+    writer: &mut BitWriteStream<E>,
+    value: &Option<T>,
+) -> bitbuffer::Result<()> {
+    writer.write_bool(value.is_some())?;
+    if let Some(value) = value {
+        writer.write(value)?;
+    }
+    Ok(())
+}
+
 fn parse_opt<'a, T, E: Endianness>(
     reader: &mut BitReadStream<'a, E>,
     parse: impl Fn(&mut BitReadStream<'a, E>) -> bitbuffer::Result<T>,
@@ -67,7 +100,7 @@ fn parse_opt<'a, T, E: Endianness>(
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Savedata {
     pub save_menu_position: u8,
     pub play_seconds: u32,
@@ -75,8 +108,8 @@ pub struct Savedata {
     pub save_vectors: SaveVectors,
     pub settings: Settings,
     pub auto_save_slot: Option<GameData>,
-    #[serde(with = "BigArray")]
-    pub manual_save_slots: [Option<GameData>; 100],
+    /// Has [`SaveDescriptor::manual_slot_count`] entries
+    pub manual_save_slots: Vec<Option<GameData>>,
 }
 
 impl Savedata {
@@ -84,9 +117,9 @@ impl Savedata {
         crc32::crc32(seed.as_bytes(), 0)
     }
 
-    /// Same as [Savedata::deobfuscate_with_key], but with fixed game key.
+    /// Same as [Savedata::deobfuscate_with_key], but detects the game by trying the keys of all the known games.
     pub fn deobfuscate(data: &[u8]) -> Result<Vec<u8>> {
-        Self::deobfuscate_with_key(data, *GAME_KEY)
+        Ok(SaveDescriptor::detect(data)?.1)
     }
 
     /// Decrypts the game data, returning the raw decrypted bytes.
@@ -95,9 +128,9 @@ impl Savedata {
         obfuscation::decode(data, key)
     }
 
-    /// Same as [Savedata::obfuscate_with_key], but with fixed game key.
+    /// Same as [Savedata::obfuscate_with_key], but with the umineko key.
     pub fn obfuscate(data: &[u8]) -> Vec<u8> {
-        Self::obfuscate_with_key(data, *GAME_KEY)
+        UMINEKO.obfuscate(data)
     }
 
     /// Encrypts the game data, returning the raw encrypted bytes.
@@ -105,40 +138,102 @@ impl Savedata {
         obfuscation::encode(data, key)
     }
 
-    /// Same as [Savedata::decode_with_key], but with fixed game key.
-    pub fn decode(data: &[u8]) -> Result<Self> {
-        Self::decode_with_key(data, *GAME_KEY)
+    /// Detects the game the save belongs to and decodes it with its layout.
+    pub fn decode(data: &[u8]) -> Result<(&'static SaveDescriptor, Self)> {
+        let (descriptor, data) = SaveDescriptor::detect(data)?;
+        Ok((descriptor, Self::read_raw(&data, descriptor)?))
+    }
+
+    /// Decodes the save of the specific game.
+    pub fn decode_as(data: &[u8], descriptor: &SaveDescriptor) -> Result<Self> {
+        let data = descriptor
+            .deobfuscate(data)
+            .with_context(|| format!("Deobfuscating the {} save", descriptor.game))?;
+        Self::read_raw(&data, descriptor)
     }
 
-    /// Decrypts & decodes the game data, returning the parsed data.
+    /// Decrypts & decodes the game data with the umineko layout, returning the parsed data.
     /// Can fail if the CRC check fails or the decoding fails.
     pub fn decode_with_key(data: &[u8], key: u32) -> Result<Self> {
-        let data = Self::deobfuscate_with_key(data, key)?;
-        let buffer = bitbuffer::BitReadBuffer::new(&data, ENDIAN);
+        let data = UMINEKO.deobfuscate_with_key(data, key)?;
+        Self::read_raw(&data, &UMINEKO)
+    }
+
+    /// Encodes & encrypts the save with the layout of the specific game.
+    pub fn encode_as(&self, descriptor: &SaveDescriptor) -> Result<Vec<u8>> {
+        Ok(descriptor.obfuscate(&self.write_raw(descriptor)?))
+    }
+
+    /// Decodes the deobfuscated save data
+    pub fn read_raw(data: &[u8], descriptor: &SaveDescriptor) -> Result<Self> {
+        let buffer = bitbuffer::BitReadBuffer::new(data, ENDIAN);
         let mut reader = BitReadStream::new(buffer);
-        Ok(Self::read(&mut reader)?)
+        Self::read(&mut reader, descriptor)
     }
-}
 
-impl<'a, E: Endianness> BitRead<'a, E> for Savedata {
-    fn read(reader: &mut BitReadStream<'a, E>) -> bitbuffer::Result<Self> {
-        let some_ctr: u32 = reader.read_int(8)?;
-        if some_ctr == 0 {
-            todo!("Construct default Savedata")
+    /// Encodes the save data, without obfuscating it
+    pub fn write_raw(&self, descriptor: &SaveDescriptor) -> Result<Vec<u8>> {
+        if self.manual_save_slots.len() != descriptor.manual_slot_count {
+            bail!(
+                "The {} save must have {} manual save slots, got {}",
+                descriptor.game,
+                descriptor.manual_slot_count,
+                self.manual_save_slots.len()
+            );
+        }
+        if self.persist_data.0.len() > descriptor.max_persist_flags {
+            bail!(
+                "Too many persistent flags for the {} save: {} (at most {})",
+                descriptor.game,
+                self.persist_data.0.len(),
+                descriptor.max_persist_flags
+            );
         }
-        if some_ctr > 1 {
-            panic!("Invalid Savedata: some_ctr > 1") // TODO: bitbuffer doesn't have a way to pass a custom error =(
+
+        let mut data = Vec::new();
+        let mut writer = BitWriteStream::new(&mut data, ENDIAN);
+        writer.write_int(descriptor.version, 8)?;
+        writer.write_int(self.save_menu_position, 7)?;
+        writer.write_int(self.play_seconds, 32)?;
+        align(&mut writer)?;
+
+        writer.write(&self.persist_data)?;
+        writer.write(&self.save_vectors)?;
+        writer.write(&self.settings)?;
+        write_opt(&mut writer, &self.auto_save_slot)?;
+        for slot in &self.manual_save_slots {
+            write_opt(&mut writer, slot)?;
         }
 
+        Ok(data)
+    }
+
+    fn read<E: Endianness>(
+        reader: &mut BitReadStream<E>,
+        descriptor: &SaveDescriptor,
+    ) -> Result<Self> {
+        let version: u8 = reader.read_int(8)?;
+        descriptor.check_version(version)?;
+
         let save_menu_position = reader.read_int(7)?;
         let play_seconds = reader.read_int(32)?;
         reader.align()?;
 
         let persist_data = PersistData::read(reader)?;
+        if persist_data.0.len() > descriptor.max_persist_flags {
+            bail!(
+                "Too many persistent flags for the {} save: {} (at most {})",
+                descriptor.game,
+                persist_data.0.len(),
+                descriptor.max_persist_flags
+            );
+        }
         let save_vectors = SaveVectors::read(reader)?;
         let settings = Settings::read(reader)?;
         let auto_save_slot = parse_opt(reader, GameData::read)?;
-        let manual_save_slots = read_array(reader, |r| parse_opt(r, GameData::read))?;
+        let manual_save_slots = (0..descriptor.manual_slot_count)
+            .map(|_| parse_opt(reader, GameData::read))
+            .collect::<bitbuffer::Result<Vec<_>>>()?;
 
         Ok(Self {
             save_menu_position,
@@ -154,7 +249,7 @@ impl<'a, E: Endianness> BitRead<'a, E> for Savedata {
 
 /// Stores the persistent variables used by the VM.
 /// They are independent of the save slots, used for stuff like global progression.
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct PersistData(pub Vec<i16>);
 
 impl PersistData {
@@ -186,7 +281,13 @@ impl<'a, E: Endianness> BitRead<'a, E> for PersistData {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl<E: Endianness> BitWrite<E> for PersistData {
+    fn write(&self, stream: &mut BitWriteStream<E>) -> bitbuffer::Result<()> {
+        write_vec(stream, &self.0, |w, &v| w.write_int(v, 16))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SaveVectors {
     pub seen_messages_mask: Vec<u32>,
     // seen choices?
@@ -216,8 +317,21 @@ impl<'a, E: Endianness> BitRead<'a, E> for SaveVectors {
     }
 }
 
+impl<E: Endianness> BitWrite<E> for SaveVectors {
+    fn write(&self, stream: &mut BitWriteStream<E>) -> bitbuffer::Result<()> {
+        align(stream)?;
+
+        write_vec(stream, &self.seen_messages_mask, |w, &v| w.write_int(v, 32))?;
+        write_vec(stream, &self.vec2, |w, &v| w.write_int(v, 32))?;
+        write_vec(stream, &self.vec3, |w, &v| w.write_int(v, 4))?;
+        write_vec(stream, &self.vec4, |w, &v| w.write_int(v, 32))?;
+        write_vec(stream, &self.vec5, |w, &v| w.write_int(v, 32))?;
+        write_vec(stream, &self.vec6, |w, &v| w.write_int(v, 32))
+    }
+}
+
 /// Stores game settings
-#[derive(Debug, Clone, Serialize, Deserialize, BitRead, BitWrite)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, BitRead, BitWrite)]
 pub struct Settings {
     #[size = 7]
     pub v0_bgmvol: u8,
@@ -252,12 +366,23 @@ pub struct Settings {
 }
 
 /// Stores minimal info necessary to load a save.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct GameData {
     date_time: NaiveDateTime,
     entry: GameDataEntry,
 }
 
+impl GameData {
+    /// When the game was saved
+    pub fn date_time(&self) -> NaiveDateTime {
+        self.date_time
+    }
+
+    pub fn entry(&self) -> &GameDataEntry {
+        &self.entry
+    }
+}
+
 impl<'a, E: Endianness> BitRead<'a, E> for GameData {
     fn read(reader: &mut BitReadStream<'a, E>) -> bitbuffer::Result<Self> {
         let date = parse_date_time(reader)?;
@@ -273,6 +398,14 @@ impl<'a, E: Endianness> BitRead<'a, E> for GameData {
     }
 }
 
+impl<E: Endianness> BitWrite<E> for GameData {
+    fn write(&self, writer: &mut BitWriteStream<E>) -> bitbuffer::Result<()> {
+        write_date_time(writer, &self.date_time)?;
+        writer.write_int(0u32, 1)?;
+        writer.write(&self.entry)
+    }
+}
+
 fn parse_date_time<E: Endianness>(
     reader: &mut BitReadStream<E>,
 ) -> bitbuffer::Result<NaiveDateTime> {
@@ -290,7 +423,19 @@ fn parse_date_time<E: Endianness>(
     Ok(datetime)
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, BitRead, BitWrite)]
+fn write_date_time<E: Endianness>(
+    writer: &mut BitWriteStream<E>,
+    date_time: &NaiveDateTime,
+) -> bitbuffer::Result<()> {
+    writer.write_int(date_time.year() as u32, 12)?;
+    writer.write_int(date_time.month(), 4)?;
+    writer.write_int(date_time.day(), 5)?;
+    writer.write_int(date_time.hour(), 5)?;
+    writer.write_int(date_time.minute(), 6)?;
+    writer.write_int(date_time.second(), 6)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, BitRead, BitWrite)]
 pub struct GameDataEntry {
     pub scenario_id: i32,
     pub random_seed: u32,
@@ -298,7 +443,7 @@ pub struct GameDataEntry {
     pub selection_data: SelectionData,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SelectionData(Vec<u8>);
 
 impl<'a, E: Endianness> BitRead<'a, E> for SelectionData {
@@ -308,7 +453,174 @@ impl<'a, E: Endianness> BitRead<'a, E> for SelectionData {
 }
 
 impl<E: Endianness> BitWrite<E> for SelectionData {
-    fn write(&self, _stream: &mut BitWriteStream<E>) -> bitbuffer::Result<()> {
-        todo!()
+    fn write(&self, stream: &mut BitWriteStream<E>) -> bitbuffer::Result<()> {
+        stream.write_int(self.0.len() as u32, 32)?;
+        for &v in &self.0 {
+            stream.write_int(v, 8)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::{
+        GameData, GameDataEntry, PersistData, SaveBitVector, SaveChange, SaveDescriptor,
+        SaveVectors, Savedata, SelectionData, Settings, KNOWN_GAMES, UMINEKO,
+    };
+
+    fn savedata(descriptor: &SaveDescriptor) -> Savedata {
+        let game_data = GameData {
+            date_time: NaiveDate::from_ymd_opt(2023, 10, 4)
+                .unwrap()
+                .and_hms_opt(12, 34, 56)
+                .unwrap(),
+            entry: GameDataEntry {
+                scenario_id: -1,
+                random_seed: 0xdeadbeef,
+                save_position: 1234,
+                selection_data: SelectionData(vec![1, 2, 3]),
+            },
+        };
+
+        let mut manual_save_slots = vec![None; descriptor.manual_slot_count];
+        manual_save_slots[3] = Some(game_data.clone());
+
+        Savedata {
+            save_menu_position: 5,
+            play_seconds: 3600,
+            persist_data: PersistData(vec![0, -1, 42]),
+            save_vectors: SaveVectors {
+                seen_messages_mask: vec![0xffff0000, 1],
+                vec2: vec![],
+                vec3: vec![1, 15, 7],
+                vec4: vec![2],
+                vec5: vec![3, 4],
+                vec6: vec![],
+            },
+            settings: Settings {
+                v0_bgmvol: 100,
+                v1_sfxvol: 90,
+                v2_voicevol: 80,
+                v3_sysvol: 70,
+                v4_voicefocus: true,
+                v5_voicepanapot: false,
+                v6: true,
+                v7: 2,
+                v8: 1,
+                v9_msgspeed: 50,
+                v10_skipspeed: 60,
+                v11_disallowskipunread: true,
+                v12: false,
+                v13_msgwinalpha: 75,
+                v14_showroutenavi: true,
+                v15: false,
+                v16_showtoucheffect: true,
+                v17_showscenetitle: false,
+                v18_showsongtitle: true,
+                v19: 0x12345678,
+            },
+            auto_save_slot: Some(game_data),
+            manual_save_slots,
+        }
+    }
+
+    #[test]
+    fn roundtrip() {
+        for &descriptor in KNOWN_GAMES {
+            let savedata = savedata(descriptor);
+            let encoded = savedata.encode_as(descriptor).unwrap();
+
+            let (detected, decoded) = Savedata::decode(&encoded).unwrap();
+            assert_eq!(detected.game, descriptor.game);
+            assert_eq!(decoded, savedata);
+        }
+    }
+
+    #[test]
+    fn unsupported_version() {
+        let mut raw = savedata(&UMINEKO).write_raw(&UMINEKO).unwrap();
+        raw[0] = 2;
+        let error = Savedata::decode(&UMINEKO.obfuscate(&raw)).unwrap_err();
+        assert!(error.to_string().contains("version 2"), "{}", error);
+
+        let error = Savedata::decode(b"definitely not a save").unwrap_err();
+        assert!(error.to_string().contains("detect"), "{}", error);
+    }
+
+    #[test]
+    fn diff() {
+        let old = savedata(&UMINEKO);
+        let mut new = old.clone();
+        new.play_seconds += 60;
+        new.persist_data.set(70, 3);
+        new.save_vectors.seen_messages_mask[1] = 0b101;
+        new.save_vectors.vec5.clear();
+        new.manual_save_slots[3] = None;
+
+        let changes = old.diff(&new);
+        assert_eq!(
+            changes[..4],
+            [
+                SaveChange::PlaySeconds {
+                    old: 3600,
+                    new: 3660
+                },
+                SaveChange::PersistFlag {
+                    index: 70,
+                    old: 0,
+                    new: 3
+                },
+                SaveChange::Bit {
+                    vector: SaveBitVector::SeenMessages,
+                    index: 34,
+                    set: true
+                },
+                SaveChange::Bit {
+                    vector: SaveBitVector::UnlockedBgms,
+                    index: 0,
+                    set: false
+                },
+            ]
+        );
+        // the other two bits of vec5, which was [3, 4], and the slot
+        assert_eq!(changes.len(), 7);
+        assert!(matches!(
+            changes.last().unwrap(),
+            SaveChange::Slot {
+                slot: Some(3),
+                old: Some(_),
+                new: None
+            }
+        ));
+
+        assert!(old.diff(&old).is_empty());
+    }
+
+    #[test]
+    fn merge_read_state() {
+        let mut merged = savedata(&UMINEKO);
+        let mut other = merged.clone();
+        other.persist_data.set(1, 5);
+        other.save_vectors.seen_messages_mask = vec![0x0000ffff, 0, 8];
+        other.save_vectors.vec4 = vec![];
+
+        merged.merge_read_state(&other);
+        assert_eq!(
+            merged.save_vectors.seen_messages_mask,
+            vec![0xffffffff, 1, 8]
+        );
+        assert_eq!(merged.save_vectors.vec4, vec![2]);
+        // the persistent flags are not merged
+        assert_eq!(merged.persist_data.get(1), -1);
+    }
+
+    #[test]
+    fn wrong_slot_count() {
+        let mut savedata = savedata(&UMINEKO);
+        savedata.manual_save_slots.pop();
+        assert!(savedata.encode_as(&UMINEKO).is_err());
     }
 }
@@ -48,6 +48,10 @@ fn encode_once(data: &mut [u8], key: u32) {
 }
 
 pub fn decode(data: &[u8], key: u32) -> Result<Vec<u8>> {
+    if data.len() < 4 {
+        bail!("save data is too short to contain a CRC")
+    }
+
     let mut stage1 = data.to_vec();
     decode_once(&mut stage1, key);
     let stage2_len = stage1.len() - 4;
@@ -74,6 +78,19 @@ pub fn encode(data: &[u8], key: u32) -> Vec<u8> {
     data
 }
 
+/// Decodes the data obfuscated without the inner CRC layer, nothing is checked
+pub fn decode_unchecked(data: &[u8], key: u32) -> Vec<u8> {
+    let mut data = data.to_vec();
+    decode_once(&mut data, key);
+    data
+}
+
+pub fn encode_unchecked(data: &[u8], key: u32) -> Vec<u8> {
+    let mut data = data.to_vec();
+    encode_once(&mut data, key);
+    data
+}
+
 #[cfg(test)]
 mod test {
     use insta::assert_debug_snapshot;
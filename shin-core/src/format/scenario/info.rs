@@ -27,6 +27,12 @@ pub struct MaskInfoItem {
 }
 pub type MaskInfo = Vec<MaskInfoItem>;
 
+impl MaskInfoItem {
+    pub fn path(&self) -> String {
+        format!("/mask/{}.msk", self.name.as_str().to_ascii_lowercase())
+    }
+}
+
 /// References a static picture (`.pic` file).
 ///
 /// See [`shin_core::format::picture`] for functionality to read the `.pic` file this struct references.
@@ -135,6 +141,11 @@ impl MovieInfoItem {
     pub fn path(&self) -> String {
         format!("/movie/{}.mp4", self.name.as_str().to_ascii_lowercase())
     }
+
+    /// The path of the subtitles for this movie. This is not a part of the original game data, the files can be added to the assets directory.
+    pub fn subtitles_path(&self) -> String {
+        format!("/movie/{}.srt", self.name.as_str().to_ascii_lowercase())
+    }
 }
 
 /// Matches a voice file to the lipsync character IDs for the characters speaking in the voice file, for lipsync purposes.
@@ -146,6 +157,38 @@ pub struct VoiceMappingInfoItem {
     /// List of character IDs for which a bustup with a matching lipsync character ID in its [`BustupInfoItem`] should have its lips animated if it is currently being displayed while a voice file matching the pattern is being played back.
     pub lipsync_character_ids: U8SmallList<u8>,
 }
+
+impl VoiceMappingInfoItem {
+    /// Whether the voice file `name` (without the `voice/` prefix and the extension) matches the pattern of this entry
+    ///
+    /// The matching is case-insensitive, `*` matches any (possibly empty) sequence of characters.
+    pub fn matches(&self, name: &str) -> bool {
+        let pattern = self.name_pattern.as_str().to_ascii_lowercase();
+        let name = name.to_ascii_lowercase();
+
+        let mut parts = pattern.split('*');
+        // there is always at least one part, even for an empty pattern
+        let first = parts.next().unwrap();
+        let Some(mut rest) = name.strip_prefix(first) else {
+            return false;
+        };
+
+        let mut parts = parts.collect::<Vec<_>>();
+        let Some(last) = parts.pop() else {
+            // no wildcards
+            return rest.is_empty();
+        };
+        for part in parts {
+            match rest.find(part) {
+                Some(index) => rest = &rest[index + part.len()..],
+                None => return false,
+            }
+        }
+
+        rest.ends_with(last)
+    }
+}
+
 pub type VoiceMappingInfo = Vec<VoiceMappingInfoItem>;
 
 /// An entry in the Picture Box (`cgmode`).
@@ -174,7 +217,7 @@ pub struct MusicBoxInfoItem {
 pub type MusicBoxInfo = Vec<MusicBoxInfoItem>;
 
 /// An individual instruction for building the data underlying the Character Box (`bupmode`).
-#[derive(Debug, BinRead, BinWrite)]
+#[derive(Debug, PartialEq, Eq, BinRead, BinWrite)]
 pub enum CharacterBoxSegment {
     /// Defines an individual background to be available for selection in the character box. The background will be shown behind the selected bustup.
     #[brw(magic = 0x0u8)]
@@ -208,7 +251,7 @@ pub enum CharacterBoxSegment {
 pub type CharacterBoxInfo = Vec<CharacterBoxSegment>;
 
 /// Defines how a `chars` grid portrait is displayed.
-#[derive(Debug, BinRead, BinWrite)]
+#[derive(Debug, PartialEq, Eq, BinRead, BinWrite)]
 #[brw(repr = u8)]
 pub enum CharsPortraitDisplayMode {
     /// Portrait will be shown in full color.
@@ -225,7 +268,7 @@ pub enum CharsPortraitDisplayMode {
 }
 
 /// An individual instruction for building the data underlying a character in the Characters screen (`chars`).
-#[derive(Debug, BinRead, BinWrite)]
+#[derive(Debug, PartialEq, Eq, BinRead, BinWrite)]
 pub enum CharsSpriteSegment {
     /// Begins a new character state. A character state is a combination of (sprite variants + name/description); multiple character states can be switched between using the “Execute”/“Resurrect” buttons below the selection grid. A character can have 1 to 4 defined states, however the game can display at most 3 states.
     #[brw(magic = 0x1u8)]
@@ -262,7 +305,7 @@ pub enum CharsSpriteSegment {
 }
 
 /// The data for a character in the Characters screen (`chars`)
-#[derive(Debug, BinRead, BinWrite)]
+#[derive(Debug, PartialEq, Eq, BinRead, BinWrite)]
 pub struct CharsSpriteInfoItem {
     /// The episode for which the character sprite and description is valid.
     pub episode: u8,
@@ -275,7 +318,7 @@ pub struct CharsSpriteInfoItem {
 pub type CharsSpriteInfo = Vec<CharsSpriteInfoItem>;
 
 /// The shape of an individual connector between portraits in the `chars` grid.
-#[derive(Debug, BinRead, BinWrite)]
+#[derive(Debug, PartialEq, Eq, BinRead, BinWrite)]
 #[brw(repr = u8)]
 pub enum CharsGridConnectorShape {
     /// No connector is displayed.
@@ -331,7 +374,7 @@ pub enum CharsGridConnectorShape {
 }
 
 /// The color of an individual connector between portraits in the `chars` grid.
-#[derive(Debug, BinRead, BinWrite)]
+#[derive(Debug, PartialEq, Eq, BinRead, BinWrite)]
 #[brw(repr = u8)]
 pub enum CharsGridConnectorColor {
     Red = 1,
@@ -340,7 +383,7 @@ pub enum CharsGridConnectorColor {
 }
 
 /// An individual instruction for building the data underlying the grid in the Characters screen (`chars`).
-#[derive(Debug, BinRead, BinWrite)]
+#[derive(Debug, PartialEq, Eq, BinRead, BinWrite)]
 pub enum CharsGridSegment {
     /// Defines a portrait on the grid, showing its full sprite, name, and description when selected.
     #[brw(magic = 0x1u8)]
@@ -387,7 +430,7 @@ pub enum CharsGridSegment {
 /// A grid for the Characters screen (`chars`). Contains portraits which can be selected to reveal additional information about the character, and connectors making up lines between the portraits to show relationships between the characters.
 ///
 /// The script can select a particular grid by ID to set it as the one that will be shown when opening `chars` from in-game. In addition, the first 8 grids are respectively the Episode 1-8 ones selectable from the main menu.
-#[derive(Debug, BinRead, BinWrite)]
+#[derive(Debug, PartialEq, Eq, BinRead, BinWrite)]
 pub struct CharsGridInfoItem {
     #[br(parse_with = parse_terminated_segment_list)]
     pub segments: Vec<CharsGridSegment>,
@@ -396,7 +439,7 @@ pub struct CharsGridInfoItem {
 pub type CharsGridInfo = Vec<CharsGridInfoItem>;
 
 /// An entry on the Tips screen (`tips`).
-#[derive(Debug, BinRead, BinWrite)]
+#[derive(Debug, PartialEq, Eq, BinRead, BinWrite)]
 pub struct TipsInfoItem {
     /// The episode this tip is for.
     pub episode: u8,
@@ -504,7 +547,7 @@ fn parse_sized_segment_list_ptr<R: Read + Seek, T: for<'a> BinRead<Args<'a> = ()
 }
 
 // parses the sections from offsets
-#[derive(Debug, BinRead)]
+#[derive(Debug, PartialEq, Eq, BinRead)]
 #[br(little)]
 pub struct ScenarioInfoTables {
     #[br(parse_with = parse_sized_section_ptr)]
@@ -554,4 +597,54 @@ impl ScenarioInfoTables {
     pub fn movie_info(&self, movie_id: i32) -> &MovieInfoItem {
         &self.movie_info[movie_id as usize]
     }
+
+    /// The lipsync character IDs of the characters speaking in the voice file `name`, taken from the first matching [`VoiceMappingInfoItem`]
+    pub fn voice_character_ids(&self, name: &str) -> &[u8] {
+        self.voice_mapping_info
+            .iter()
+            .find(|item| item.matches(name))
+            .map_or(&[], |item| item.lipsync_character_ids.0.as_slice())
+    }
+
+    /// All the lipsync character IDs mentioned in the voice mapping, sorted
+    pub fn voice_characters(&self) -> Vec<u8> {
+        let mut ids = self
+            .voice_mapping_info
+            .iter()
+            .flat_map(|item| item.lipsync_character_ids.0.iter().copied())
+            .collect::<Vec<_>>();
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::VoiceMappingInfoItem;
+    use crate::format::scenario::types::{U16String, U8SmallList};
+
+    fn item(pattern: &str) -> VoiceMappingInfoItem {
+        VoiceMappingInfoItem {
+            name_pattern: U16String::new(pattern),
+            lipsync_character_ids: U8SmallList::from_iter([1]),
+        }
+    }
+
+    #[test]
+    fn voice_mapping_pattern() {
+        assert!(item("00/10100001").matches("00/10100001"));
+        assert!(item("00/10100001").matches("00/10100001".to_ascii_uppercase().as_str()));
+        assert!(!item("00/10100001").matches("00/10100002"));
+        assert!(!item("00/1010000").matches("00/10100001"));
+
+        assert!(item("*/101*").matches("00/10100001"));
+        assert!(item("*/101*").matches("12/101"));
+        assert!(!item("*/101*").matches("00/10200001"));
+        assert!(item("00/*01").matches("00/10100001"));
+        assert!(!item("00/*01").matches("00/10100010"));
+        assert!(item("*").matches(""));
+        assert!(item("a*b*c").matches("aXbYc"));
+        assert!(!item("a*b*c").matches("aXcYb"));
+    }
 }
@@ -35,6 +35,22 @@ pub struct BitmaskNumberArray<
     NumberSpec<T8>,
 );
 
+impl<T1, T2, T3, T4, T5, T6, T7, T8> BitmaskNumberArray<T1, T2, T3, T4, T5, T6, T7, T8> {
+    /// Get the numbers without their types, in order
+    pub fn to_untyped(&self) -> [UntypedNumberSpec; 8] {
+        [
+            self.0.into_untyped(),
+            self.1.into_untyped(),
+            self.2.into_untyped(),
+            self.3.into_untyped(),
+            self.4.into_untyped(),
+            self.5.into_untyped(),
+            self.6.into_untyped(),
+            self.7.into_untyped(),
+        ]
+    }
+}
+
 impl<T1, T2, T3, T4, T5, T6, T7, T8> BinRead
     for BitmaskNumberArray<T1, T2, T3, T4, T5, T6, T7, T8>
 {
@@ -79,16 +95,7 @@ impl<T1, T2, T3, T4, T5, T6, T7, T8> BinWrite
         endian: Endian,
         _: Self::Args<'_>,
     ) -> BinResult<()> {
-        let untyped = [
-            self.0.into_untyped(),
-            self.1.into_untyped(),
-            self.2.into_untyped(),
-            self.3.into_untyped(),
-            self.4.into_untyped(),
-            self.5.into_untyped(),
-            self.6.into_untyped(),
-            self.7.into_untyped(),
-        ];
+        let untyped = self.to_untyped();
         let mut mask = 0;
         for (i, spec) in untyped.iter().enumerate() {
             if let UntypedNumberSpec::Constant(0) = spec {
@@ -11,7 +11,10 @@ use binrw::{BinRead, BinWrite};
 /// It can either refer to an argument register (to access args passed by a `call` instruction) or a regular global register.
 #[derive(BinRead, BinWrite, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
 // TODO: add a niche for `Option<Register>` to have an efficient representation
-pub struct Register(u16);
+pub struct Register(
+    // reject the addresses past the arguments when reading, so that a corrupt scenario doesn't crash the VM
+    #[br(assert(self_0 <= Register::ARGUMENTS_END, "Invalid register: {:#x}", self_0))] u16,
+);
 
 impl Register {
     /// Addresses larger than 0x1000 are treated as relative to the stack top (Aka mem3)
@@ -88,6 +88,13 @@ impl Scenario {
         self.entrypoint_address
     }
 
+    /// Finds the end of the code, the scenario files are padded with zeros
+    pub fn code_end(&self) -> CodeAddress {
+        let raw = self.raw();
+        let padding = raw.iter().rev().take_while(|&&b| b == 0).count();
+        CodeAddress((raw.len() - padding) as u32)
+    }
+
     pub fn instruction_reader(&self, offset: CodeAddress) -> InstructionReader {
         InstructionReader::new(self.raw_data.clone(), offset)
     }
@@ -115,8 +122,15 @@ impl InstructionReader {
         CodeAddress(self.cur.position().try_into().unwrap())
     }
 
-    pub fn set_position(&mut self, offset: CodeAddress) {
-        assert!(offset.0 as u64 <= self.cur.get_ref().len() as u64);
+    pub fn set_position(&mut self, offset: CodeAddress) -> Result<()> {
+        if offset.0 as u64 > self.cur.get_ref().len() as u64 {
+            bail!(
+                "Code address {} is out of bounds (scenario size is {:#x})",
+                offset,
+                self.cur.get_ref().len()
+            );
+        }
         self.cur.set_position(offset.0 as u64);
+        Ok(())
     }
 }
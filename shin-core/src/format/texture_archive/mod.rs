@@ -2,12 +2,19 @@
 
 use std::collections::HashMap;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use binrw::{BinRead, BinWrite};
 use image::RgbaImage;
-use shin_tasks::ParallelSlice;
+use snafu::{ensure, Snafu};
 
-use crate::format::text::ZeroString;
+use crate::format::{par_map, slice_checked, text::ZeroString};
+
+/// Errors caused by malformed texture archive data
+#[derive(Debug, Snafu)]
+pub enum TextureArchiveError {
+    /// The file size in the header (`{expected}`) doesn't match the actual size (`{actual}`)
+    FileSizeMismatch { expected: u32, actual: usize },
+}
 
 #[derive(BinRead, BinWrite, Debug)]
 #[brw(little, magic = b"TXA4")]
@@ -69,7 +76,8 @@ fn decode_texture(
         &mut image,
         use_dict_encoding,
         true,
-    );
+    )
+    .with_context(|| format!("Decoding texture {:?}", index_entry.name.0))?;
 
     Ok(image)
 }
@@ -80,25 +88,28 @@ pub fn read_texture_archive(source: &[u8]) -> Result<TextureArchive> {
 
     let header: TxaHeader = TxaHeader::read(source)?;
 
-    assert_eq!(header.file_size, source.get_ref().len() as u32);
+    ensure!(
+        header.file_size as usize == source.get_ref().len(),
+        FileSizeMismatchSnafu {
+            expected: header.file_size,
+            actual: source.get_ref().len(),
+        }
+    );
 
-    let textures = header
-        .index
-        .par_chunk_map(shin_tasks::AsyncComputeTaskPool::get(), 1, |chunk| {
-            let [v] = chunk else { unreachable!() };
-            let size = if v.data_compressed_size != 0 {
-                v.data_compressed_size
-            } else {
-                v.data_decompressed_size
-            } as usize;
-            decode_texture(
-                &source.get_ref()[v.data_offset as usize..][..size],
-                v,
-                header.use_dict_encoding != 0,
-            )
-        })
-        .into_iter()
-        .collect::<Result<Vec<_>>>()?;
+    let textures = par_map(&header.index, |v| -> Result<_> {
+        let size = if v.data_compressed_size != 0 {
+            v.data_compressed_size
+        } else {
+            v.data_decompressed_size
+        } as usize;
+        decode_texture(
+            slice_checked(source.get_ref(), v.data_offset as usize, size)?,
+            v,
+            header.use_dict_encoding != 0,
+        )
+    })
+    .into_iter()
+    .collect::<Result<Vec<_>>>()?;
 
     let name_to_index = header
         .index
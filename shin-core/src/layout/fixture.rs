@@ -0,0 +1,359 @@
+//! Layout fixtures: layouts recorded from the original engine, used to check the layouter against.
+//!
+//! The layout code is very finicky, and small differences in float computations are easy to miss when looking at the screen.
+//! A fixture stores the layout parameters, the message and the chars the game has produced for it, one record per line:
+//!
+//! ```text
+//! # comments and empty lines are ignored
+//! layout_width 1500
+//! character_name_layout_width 384
+//! base_font_height 50
+//! furigana_font_height 20
+//! font_horizontal_base_scale 0.9697
+//! text_layout left
+//! mode message
+//! direction horizontal
+//! message @rHello
+//! char 0048 0 0 90.625 23.27 47.5
+//! ```
+//!
+//! The `char` records are `codepoint (hex) time x y width height`, listed in the order the layouter outputs them (the character name first).
+//! The message is the rest of the line after `message `, it can't contain newlines (the layouter uses `@r` for them anyway).
+
+use std::io::{BufRead, Write};
+
+use anyhow::{bail, Context, Result};
+use glam::{vec2, Vec2};
+
+use crate::{
+    format::font::LazyFont,
+    layout::{
+        layout_text, LayoutParams, LayoutedChar, LayouterState, LayoutingMode, TextDirection,
+    },
+    vm::command::types::MessageTextLayout,
+};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct FixtureChar {
+    pub codepoint: u16,
+    pub time: f32,
+    pub position: Vec2,
+    pub size: Vec2,
+}
+
+impl FixtureChar {
+    fn from_layouted(c: &LayoutedChar) -> Self {
+        Self {
+            codepoint: c.codepoint,
+            time: c.time.as_f32(),
+            position: c.position,
+            size: c.size.size(),
+        }
+    }
+
+    fn differs(&self, other: &FixtureChar, tolerance: f32) -> bool {
+        let close = |a: f32, b: f32| (a - b).abs() <= tolerance;
+
+        self.codepoint != other.codepoint
+            || !close(self.time, other.time)
+            || !close(self.position.x, other.position.x)
+            || !close(self.position.y, other.position.y)
+            || !close(self.size.x, other.size.x)
+            || !close(self.size.y, other.size.y)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayoutFixture {
+    pub layout_width: f32,
+    pub character_name_layout_width: f32,
+    pub base_font_height: f32,
+    pub furigana_font_height: f32,
+    pub font_horizontal_base_scale: f32,
+    pub text_layout: MessageTextLayout,
+    pub mode: LayoutingMode,
+    pub direction: TextDirection,
+    pub message: String,
+    pub chars: Vec<FixtureChar>,
+}
+
+impl LayoutFixture {
+    /// Creates a fixture with the parameters of the message box and no chars
+    pub fn new(message: String) -> Self {
+        Self {
+            layout_width: 1500.0,
+            character_name_layout_width: 384.0,
+            base_font_height: 50.0,
+            furigana_font_height: 20.0,
+            font_horizontal_base_scale: 0.9697,
+            text_layout: MessageTextLayout::Left,
+            mode: LayoutingMode::MessageText,
+            direction: TextDirection::Horizontal,
+            message,
+            chars: Vec::new(),
+        }
+    }
+
+    pub fn layout_params<'a>(&self, font: &'a LazyFont) -> LayoutParams<'a> {
+        LayoutParams {
+            font,
+            layout_width: self.layout_width,
+            character_name_layout_width: self.character_name_layout_width,
+            base_font_height: self.base_font_height,
+            furigana_font_height: self.furigana_font_height,
+            font_horizontal_base_scale: self.font_horizontal_base_scale,
+            text_layout: self.text_layout,
+            default_state: LayouterState::default(),
+            has_character_name: matches!(self.mode, LayoutingMode::MessageText),
+            mode: self.mode,
+            line_breaking: Default::default(),
+            ruby: Default::default(),
+            direction: self.direction,
+        }
+    }
+
+    /// Lays out the message with our layouter, returning the chars in the fixture order
+    pub fn layout(&self, font: &LazyFont) -> Vec<FixtureChar> {
+        let message = layout_text(self.layout_params(font), &self.message);
+
+        message
+            .character_name_chars
+            .iter()
+            .flatten()
+            .chain(message.chars.iter())
+            .map(FixtureChar::from_layouted)
+            .collect()
+    }
+
+    /// Checks that our layouter produces the recorded chars, with the `tolerance` applied to all the times, positions and sizes
+    pub fn check(&self, font: &LazyFont, tolerance: f32) -> Result<()> {
+        /// Don't flood the output when everything is off
+        const MAX_REPORTED: usize = 8;
+
+        let actual = self.layout(font);
+
+        let mut mismatches = Vec::new();
+        if actual.len() != self.chars.len() {
+            mismatches.push(format!(
+                "expected {} chars, got {}",
+                self.chars.len(),
+                actual.len()
+            ));
+        }
+        for (index, (expected, actual)) in self.chars.iter().zip(actual.iter()).enumerate() {
+            if expected.differs(actual, tolerance) {
+                mismatches.push(format!(
+                    "char {}: expected {:?}, got {:?}",
+                    index, expected, actual
+                ));
+            }
+        }
+
+        if !mismatches.is_empty() {
+            let total = mismatches.len();
+            mismatches.truncate(MAX_REPORTED);
+            bail!(
+                "Layout of {:?} doesn't match the fixture ({} mismatches):\n{}",
+                self.message,
+                total,
+                mismatches.join("\n")
+            );
+        }
+
+        Ok(())
+    }
+
+    pub fn read(reader: impl BufRead) -> Result<Self> {
+        fn parse_float(value: Option<&str>) -> Result<f32> {
+            let value = value.context("Missing value")?;
+            value
+                .parse()
+                .with_context(|| format!("Invalid number: {:?}", value))
+        }
+
+        let mut result = Self::new(String::new());
+        let mut has_message = false;
+        for (line_number, line) in reader.lines().enumerate() {
+            let line = line?;
+            (|| {
+                if line.trim().is_empty() || line.starts_with('#') {
+                    return Ok(());
+                }
+
+                let (kind, rest) = line.split_once(' ').unwrap_or((line.as_str(), ""));
+                let mut parts = rest.split_whitespace();
+                match kind {
+                    "layout_width" => result.layout_width = parse_float(parts.next())?,
+                    "character_name_layout_width" => {
+                        result.character_name_layout_width = parse_float(parts.next())?
+                    }
+                    "base_font_height" => result.base_font_height = parse_float(parts.next())?,
+                    "furigana_font_height" => {
+                        result.furigana_font_height = parse_float(parts.next())?
+                    }
+                    "font_horizontal_base_scale" => {
+                        result.font_horizontal_base_scale = parse_float(parts.next())?
+                    }
+                    "text_layout" => {
+                        result.text_layout = match parts.next() {
+                            Some("left") => MessageTextLayout::Left,
+                            Some("layout1") => MessageTextLayout::Layout1,
+                            Some("center") => MessageTextLayout::Center,
+                            Some("right") => MessageTextLayout::Right,
+                            other => bail!("Unknown text layout: {:?}", other),
+                        }
+                    }
+                    "mode" => {
+                        result.mode = match parts.next() {
+                            Some("message") => LayoutingMode::MessageText,
+                            Some("log") => LayoutingMode::LogText,
+                            Some("generic") => LayoutingMode::GenericText,
+                            other => bail!("Unknown layouting mode: {:?}", other),
+                        }
+                    }
+                    "direction" => {
+                        result.direction = match parts.next() {
+                            Some("horizontal") => TextDirection::Horizontal,
+                            Some("vertical") => TextDirection::Vertical,
+                            other => bail!("Unknown text direction: {:?}", other),
+                        }
+                    }
+                    "message" => {
+                        result.message = rest.to_string();
+                        has_message = true;
+                    }
+                    "char" => {
+                        let codepoint = parts.next().context("Missing codepoint")?;
+                        let codepoint = u16::from_str_radix(codepoint, 16)
+                            .with_context(|| format!("Invalid codepoint: {:?}", codepoint))?;
+                        let time = parse_float(parts.next())?;
+                        let position = vec2(parse_float(parts.next())?, parse_float(parts.next())?);
+                        let size = vec2(parse_float(parts.next())?, parse_float(parts.next())?);
+                        result.chars.push(FixtureChar {
+                            codepoint,
+                            time,
+                            position,
+                            size,
+                        });
+                    }
+                    kind => bail!("Unknown record kind: {:?}", kind),
+                }
+                Ok(())
+            })()
+            .with_context(|| format!("Parsing line {}", line_number + 1))?;
+        }
+
+        if !has_message {
+            bail!("The fixture has no message");
+        }
+
+        Ok(result)
+    }
+
+    pub fn write(&self, mut writer: impl Write) -> std::io::Result<()> {
+        writeln!(writer, "layout_width {}", self.layout_width)?;
+        writeln!(
+            writer,
+            "character_name_layout_width {}",
+            self.character_name_layout_width
+        )?;
+        writeln!(writer, "base_font_height {}", self.base_font_height)?;
+        writeln!(writer, "furigana_font_height {}", self.furigana_font_height)?;
+        writeln!(
+            writer,
+            "font_horizontal_base_scale {}",
+            self.font_horizontal_base_scale
+        )?;
+        let text_layout = match self.text_layout {
+            MessageTextLayout::Left => "left",
+            MessageTextLayout::Layout1 => "layout1",
+            MessageTextLayout::Center => "center",
+            MessageTextLayout::Right => "right",
+        };
+        writeln!(writer, "text_layout {}", text_layout)?;
+        let mode = match self.mode {
+            LayoutingMode::MessageText => "message",
+            LayoutingMode::LogText => "log",
+            LayoutingMode::GenericText => "generic",
+        };
+        writeln!(writer, "mode {}", mode)?;
+        let direction = match self.direction {
+            TextDirection::Horizontal => "horizontal",
+            TextDirection::Vertical => "vertical",
+        };
+        writeln!(writer, "direction {}", direction)?;
+        writeln!(writer, "message {}", self.message)?;
+        for c in &self.chars {
+            writeln!(
+                writer,
+                "char {:04x} {} {} {} {} {}",
+                c.codepoint, c.time, c.position.x, c.position.y, c.size.x, c.size.y
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, io::BufReader, path::PathBuf};
+
+    use glam::vec2;
+
+    use super::{FixtureChar, LayoutFixture};
+    use crate::{
+        layout::{LayoutingMode, TextDirection},
+        vm::command::types::MessageTextLayout,
+    };
+
+    fn fixtures_dir() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/layout")
+    }
+
+    #[test]
+    fn roundtrip() {
+        let mut fixture = LayoutFixture::new("@rHello, @bworld.@<world@>!".to_string());
+        fixture.text_layout = MessageTextLayout::Center;
+        fixture.mode = LayoutingMode::LogText;
+        fixture.direction = TextDirection::Vertical;
+        fixture.chars.push(FixtureChar {
+            codepoint: 0x48,
+            time: 1.5,
+            position: vec2(0.0, 90.625),
+            size: vec2(23.27, 47.5),
+        });
+
+        let mut written = Vec::new();
+        fixture.write(&mut written).unwrap();
+        assert_eq!(LayoutFixture::read(written.as_slice()).unwrap(), fixture);
+
+        assert!(LayoutFixture::read("layout_width 1500\n".as_bytes()).is_err());
+        assert!(LayoutFixture::read("message @r\nchar zz 0 0 0 0 0\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn fixtures() {
+        let mut fixtures = std::fs::read_dir(fixtures_dir())
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|e| e == "txt"))
+            .collect::<Vec<_>>();
+        fixtures.sort();
+        if fixtures.is_empty() {
+            return;
+        }
+
+        // the fixtures are recorded with the game font, see the layouter tests
+        let font = File::open("../shin/assets/data/newrodin-medium.fnt").unwrap();
+        let font = crate::format::font::read_lazy_font(&mut BufReader::new(font)).unwrap();
+
+        let mut failures = Vec::new();
+        for path in fixtures {
+            let fixture = LayoutFixture::read(BufReader::new(File::open(&path).unwrap())).unwrap();
+            if let Err(e) = fixture.check(&font, 1e-3) {
+                failures.push(format!("{}: {:#}", path.display(), e));
+            }
+        }
+        assert!(failures.is_empty(), "{}", failures.join("\n\n"));
+    }
+}
@@ -1,8 +1,9 @@
-use std::iter::Peekable;
+use std::{iter::Peekable, ops::Range};
 
 use float_ord::FloatOrd;
 use glam::{vec2, Vec2, Vec3};
 use tracing::warn;
+use unicode_segmentation::UnicodeSegmentation;
 
 use crate::{
     format::font::{GlyphTrait, LazyFont},
@@ -19,6 +20,8 @@ pub struct LayoutedChar {
     pub size: GlyphSize,
     pub fade: f32,
     pub codepoint: u16,
+    /// Whether the glyph is rotated 90° clockwise around its pen position (non-CJK chars in vertical text)
+    pub rotated: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -72,6 +75,12 @@ pub struct GlyphSize {
     pub height: f32,
 }
 
+impl LayoutedChar {
+    fn char(&self) -> char {
+        char::from_u32(self.codepoint as u32).unwrap_or(char::REPLACEMENT_CHARACTER)
+    }
+}
+
 impl GlyphSize {
     pub fn size(&self) -> Vec2 {
         vec2(self.width, self.height)
@@ -86,7 +95,7 @@ impl GlyphSize {
 
 /// The environment for which the text should be layouted. This affects details like how the
 /// character name will be positioned
-#[derive(Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum LayoutingMode {
     /// Text in the message box: dialogue lines and narration, including character names
     MessageText,
@@ -96,6 +105,129 @@ pub enum LayoutingMode {
     GenericText,
 }
 
+/// Finds the places where a word can be hyphenated when it doesn't fit on a line
+pub trait Hyphenator {
+    /// Returns the offsets (in chars) inside the `word` where it can be broken, a hyphen is inserted before the break
+    fn hyphenation_points(&self, word: &str) -> Vec<usize>;
+}
+
+/// How the lines are broken when the text overflows the layout width
+#[derive(Copy, Clone, Default)]
+pub enum LineBreaking<'a> {
+    /// Break at any character, like the game does
+    ///
+    /// This is fine for Japanese, but splits Latin words in the middle.
+    #[default]
+    Character,
+    /// Break only at the Unicode word boundaries following a whitespace or adjacent to CJK characters
+    ///
+    /// Words not fitting on a line by themselves are hyphenated with the `hyphenator` (if any) or broken at any character.
+    Word {
+        hyphenator: Option<&'a dyn Hyphenator>,
+    },
+}
+
+/// Characters that should not start a line (a small subset of the kinsoku shori rules)
+const NO_LINE_START: &str =
+    "、。，．・：；？！ー…‥）」』】〕〉》”’ぁぃぅぇぉっゃゅょゎァィゥェォッャュョヮヵヶ,.:;?!)]}";
+/// Characters that should not end a line
+const NO_LINE_END: &str = "（「『【〔〈《“‘([{";
+
+fn is_cjk(c: char) -> bool {
+    // CJK punctuation, kana, ideographs and the fullwidth forms
+    matches!(c, '\u{2e80}'..='\u{9fff}' | '\u{f900}'..='\u{faff}' | '\u{ff00}'..='\u{ffef}')
+}
+
+/// Computes whether the line can be broken before each of the chars of the `text` (in [`LineBreaking::Word`] mode)
+fn break_opportunities(text: &str) -> Vec<bool> {
+    let mut result = vec![false; text.chars().count()];
+
+    let mut index = 0;
+    let mut previous: Option<&str> = None;
+    for segment in text.split_word_bounds() {
+        if let Some(previous) = previous {
+            let first = segment.chars().next().unwrap();
+            let last = previous.chars().next_back().unwrap();
+
+            let after_whitespace = previous.chars().all(char::is_whitespace);
+            result[index] = (after_whitespace || is_cjk(first) || is_cjk(last))
+                && !first.is_whitespace()
+                && !NO_LINE_START.contains(first)
+                && !NO_LINE_END.contains(last);
+        }
+        index += segment.chars().count();
+        previous = Some(segment);
+    }
+
+    result
+}
+
+/// How the ruby (furigana) is positioned over the base text
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RubyAlignment {
+    /// Start the ruby at the start of the base text
+    Start,
+    /// Center the ruby over the base text
+    Center,
+    /// Spread the ruby chars over the whole base text, leaving half of the spacing at the edges (the usual Japanese style)
+    Distribute,
+}
+
+/// Parameters of the ruby (furigana) layout
+///
+/// The defaults are tuned for Japanese furigana, [`RubyParams::LATIN`] is more suitable for glosses in translated scripts,
+/// which are usually much longer than the base text.
+#[derive(Debug, Copy, Clone)]
+pub struct RubyParams {
+    pub alignment: RubyAlignment,
+    /// Additional horizontal scale of the ruby glyphs, on top of the `font_horizontal_base_scale`
+    pub horizontal_scale: f32,
+    /// Minimum horizontal scale the ruby is squished to when it's wider than the base text
+    pub min_squish_scale: f32,
+    /// Whether to spread the base chars when the ruby is still wider than them after squishing
+    ///
+    /// Otherwise the ruby overhangs the base text.
+    pub widen_base: bool,
+}
+
+impl RubyParams {
+    pub const JAPANESE: RubyParams = RubyParams {
+        alignment: RubyAlignment::Distribute,
+        horizontal_scale: 1.0,
+        min_squish_scale: 0.8,
+        widen_base: true,
+    };
+
+    pub const LATIN: RubyParams = RubyParams {
+        alignment: RubyAlignment::Center,
+        horizontal_scale: 0.9,
+        min_squish_scale: 0.6,
+        widen_base: false,
+    };
+}
+
+impl Default for RubyParams {
+    fn default() -> Self {
+        Self::JAPANESE
+    }
+}
+
+/// Direction of the text lines
+///
+/// In the vertical mode the `layout_width` is the length of the columns, they go from right to left.
+/// The origin is at the top right corner of the text, so all the chars have negative x coordinates.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum TextDirection {
+    #[default]
+    Horizontal,
+    Vertical,
+}
+
+/// Chars rotated in the vertical text, in addition to all the non-CJK chars
+const ROTATED_IN_VERTICAL: &str = "ー－～〜…‥（）「」『』【】〔〕〈〉《》";
+/// Chars moved to the upper right corner of their em box in the vertical text
+const SHIFTED_IN_VERTICAL: &str = "、。，．";
+
 #[derive(Copy, Clone)]
 pub struct LayoutParams<'a> {
     pub font: &'a LazyFont,
@@ -108,13 +240,73 @@ pub struct LayoutParams<'a> {
     pub default_state: LayouterState,
     pub has_character_name: bool,
     pub mode: LayoutingMode,
+    pub line_breaking: LineBreaking<'a>,
+    pub ruby: RubyParams,
+    pub direction: TextDirection,
 }
 
 impl<'a> LayoutParams<'a> {
     fn glyph_size(&self, font_size: f32, codepoint: u16) -> GlyphSize {
-        let line_height = self.base_font_height * font_size;
+        self.glyph_size_for_height(self.base_font_height * font_size, 1.0, codepoint)
+    }
+
+    /// Adjusts the glyph advance for the text direction, returns whether the glyph is rotated
+    fn orient_glyph(&self, size: &mut GlyphSize, c: char) -> bool {
+        match self.direction {
+            TextDirection::Horizontal => false,
+            // rotated glyphs keep their horizontal advance, it goes down the column
+            TextDirection::Vertical if !is_cjk(c) || ROTATED_IN_VERTICAL.contains(c) => true,
+            TextDirection::Vertical => {
+                // the font has no vertical metrics, CJK glyphs are square anyway
+                size.advance_width = size.line_height;
+                false
+            }
+        }
+    }
+
+    /// Computes the pen position of a char in a vertical column
+    ///
+    /// `inline` is the position of the top of the char's em box along the column and `center` is the x coordinate of the column center line.
+    fn vertical_pen_position(&self, c: &LayoutedChar, inline: f32, center: f32) -> Vec2 {
+        let ascent = self.font.get_ascent() as f32 * c.size.scale;
+        let descent = self.font.get_descent() as f32 * c.size.scale;
+
+        if c.rotated {
+            // the baseline goes down the column, the em box is centered on it
+            vec2(center - (ascent - descent) / 2.0, inline)
+        } else if SHIFTED_IN_VERTICAL.contains(c.char()) {
+            // the glyph is in the lower left corner of the em box in horizontal text, move it by half of the em box
+            vec2(center, inline + ascent - c.size.line_height / 2.0)
+        } else {
+            // center the glyph on the column, compensating for the bearing added by the renderer
+            let bearing_x = self
+                .font
+                .get_glyph_for_character(c.codepoint)
+                .get_info()
+                .bearing_x;
+            vec2(
+                center - c.size.width / 2.0 - bearing_x as f32 * c.size.horizontal_scale,
+                inline + ascent,
+            )
+        }
+    }
+
+    fn ruby_glyph_size(&self, codepoint: u16) -> GlyphSize {
+        self.glyph_size_for_height(
+            self.furigana_font_height,
+            self.ruby.horizontal_scale,
+            codepoint,
+        )
+    }
+
+    fn glyph_size_for_height(
+        &self,
+        line_height: f32,
+        horizontal_scale: f32,
+        codepoint: u16,
+    ) -> GlyphSize {
         let scale = line_height / self.font.get_line_height() as f32;
-        let horizontal_scale = scale * self.font_horizontal_base_scale;
+        let horizontal_scale = scale * self.font_horizontal_base_scale * horizontal_scale;
 
         let glyph = self.font.get_glyph_for_character(codepoint).get_info();
         let height = glyph.actual_height as f32 * scale;
@@ -132,6 +324,12 @@ impl<'a> LayoutParams<'a> {
     }
 }
 
+/// Ruby chars laid out over a range of the pending chars
+struct RubyGroup {
+    base: Range<usize>,
+    chars: Vec<LayoutedChar>,
+}
+
 struct Layouter<'a> {
     parser: Peekable<LayouterParser<'a>>,
     params: LayoutParams<'a>,
@@ -139,6 +337,11 @@ struct Layouter<'a> {
     /// Layouted chars, grouped by line
     chars: Vec<Vec<LayoutedChar>>,
     pending_chars: Vec<LayoutedChar>,
+    pending_ruby: Vec<RubyGroup>,
+    /// Ruby text set by the `@b` command, waiting for the base text to end
+    ruby_text: Option<String>,
+    /// Index of the pending char starting the ruby base text
+    ruby_start: Option<usize>,
     position: Vec2,
     time: Ticks,
 }
@@ -148,7 +351,8 @@ impl<'a> Layouter<'a> {
         assert!((c as u32) < 0x10000);
         let codepoint = c as u16;
 
-        let size = self.params.glyph_size(self.state.font_size, codepoint);
+        let mut size = self.params.glyph_size(self.state.font_size, codepoint);
+        let rotated = self.params.orient_glyph(&mut size, c);
         let fade_time = if self.state.instant {
             0.0_f32
         } else {
@@ -156,8 +360,6 @@ impl<'a> Layouter<'a> {
         };
 
         // TODO: handle special cases for brackets
-        // TODO: handle furigana
-
         self.pending_chars.push(LayoutedChar {
             time: self.time,
             position: vec2(self.position.x, 0.0), // do not set y position yet, it will be set when we know which line this char is on
@@ -165,6 +367,7 @@ impl<'a> Layouter<'a> {
             size,
             fade: fade_time,
             codepoint,
+            rotated,
         });
 
         self.position.x += size.advance_width;
@@ -178,7 +381,102 @@ impl<'a> Layouter<'a> {
         // TODO: where are overflows handled? On the linefeed?
     }
 
-    fn finalize_line(&mut self, chars: &[LayoutedChar], last_line: bool, x_pos: f32) {
+    fn on_ruby_start(&mut self) {
+        self.ruby_start = Some(self.pending_chars.len());
+    }
+
+    fn on_ruby_end(&mut self) {
+        let (Some(text), Some(start)) = (self.ruby_text.take(), self.ruby_start.take()) else {
+            warn!("Ruby end without ruby text or start");
+            return;
+        };
+        let end = self.pending_chars.len();
+        if start == end {
+            warn!("Ruby {:?} has no base text", text);
+            return;
+        }
+
+        let ruby = self.params.ruby;
+        let base = &mut self.pending_chars[start..end];
+        let first = base[0];
+
+        let mut chars = text
+            .chars()
+            .filter(|&c| (c as u32) < 0x10000)
+            .map(|c| {
+                let mut size = self.params.ruby_glyph_size(c as u16);
+                let rotated = self.params.orient_glyph(&mut size, c);
+                LayoutedChar {
+                    // the ruby is shown together with the first base char
+                    time: first.time,
+                    position: vec2(0.0, 0.0),
+                    color: first.color,
+                    size,
+                    fade: first.fade,
+                    codepoint: c as u16,
+                    rotated,
+                }
+            })
+            .collect::<Vec<_>>();
+        if chars.is_empty() {
+            return;
+        }
+
+        let base_x = first.position.x;
+        let mut base_width = self.position.x - base_x;
+        let mut ruby_width = chars.iter().map(|c| c.size.advance_width).sum::<f32>();
+
+        // squish the ruby if it's too wide, but not too much to keep it readable
+        if ruby_width > base_width {
+            let scale = (base_width / ruby_width).max(ruby.min_squish_scale);
+            for c in &mut chars {
+                c.size.scale_horizontal(scale);
+            }
+            ruby_width *= scale;
+        }
+
+        if ruby.widen_base && ruby_width > base_width {
+            // spread the base chars, leaving half of the spacing at the edges
+            let spacing = (ruby_width - base_width) / base.len() as f32;
+            for (i, c) in base.iter_mut().enumerate() {
+                c.position.x += spacing * (i as f32 + 0.5);
+            }
+            self.position.x += ruby_width - base_width;
+            base_width = ruby_width;
+        }
+
+        // when the ruby is wider than the base text, this is negative and the ruby overhangs on both sides
+        let free_space = base_width - ruby_width;
+        let (offset, spacing) = match ruby.alignment {
+            RubyAlignment::Start => (0.0, 0.0),
+            RubyAlignment::Distribute if free_space > 0.0 => {
+                let spacing = free_space / chars.len() as f32;
+                (spacing / 2.0, spacing)
+            }
+            RubyAlignment::Center | RubyAlignment::Distribute => (free_space / 2.0, 0.0),
+        };
+
+        let mut x = base_x + offset;
+        for c in &mut chars {
+            c.position.x = x;
+            x += c.size.advance_width + spacing;
+        }
+
+        self.pending_ruby.push(RubyGroup {
+            base: start..end,
+            chars,
+        });
+    }
+
+    /// Finalizes a line made of the `chars`, starting with the pending char at `line_start`, along with the ruby over them
+    fn finalize_line(
+        &mut self,
+        chars: &[LayoutedChar],
+        ruby: &[RubyGroup],
+        line_start: usize,
+        last_line: bool,
+        x_pos: f32,
+    ) {
         // TODO: there are flags.... I think they have to do with difference between text alignment 0 & 1
 
         // Find the maximum height of a char in the line, or if there are no chars in the line, use the height a char
@@ -218,7 +516,12 @@ impl<'a> Layouter<'a> {
             // if we are not at the last line, the line should be full
             // and usually this means that it has overflowed
             // squish text a bit to make it fit (probably more visually pleasing?)
-            self.params.layout_width / width
+            let scale = self.params.layout_width / width;
+            match self.params.line_breaking {
+                LineBreaking::Character => scale,
+                // lines broken at word boundaries are usually not full, only squish the overflowing ones
+                LineBreaking::Word { .. } => scale.min(1.0),
+            }
         } else {
             1.0
         };
@@ -238,80 +541,197 @@ impl<'a> Layouter<'a> {
             MessageTextLayout::Right => self.params.layout_width - width,
         };
 
+        let ruby_ascent = (self.params.furigana_font_height / font.get_line_height() as f32)
+            * font.get_ascent() as f32;
+
+        let place = |mut c: LayoutedChar, is_ruby: bool| {
+            // align the text according to the layout params
+            c.position.x += x_offset;
+
+            // move the text to the beginning of the real line
+            // x might be larger than we want if an overflow happened
+            c.position.x -= x_pos;
+
+            if self.params.direction == TextDirection::Vertical {
+                // the x coordinate is the position along the column
+                let inline = c.position.x * fit_scale;
+                // the columns go from right to left, with the ruby on their right side
+                let column_right = -self.position.y;
+                let center = if is_ruby {
+                    column_right - furigana_height / 2.0
+                } else {
+                    column_right - furigana_height - max_line_height / 2.0
+                };
+                c.position = self.params.vertical_pen_position(&c, inline, center);
+                return c;
+            }
+
+            // move the glyph on its line y coordinate (previously it was zero)
+            c.position.y += self.position.y;
+            if is_ruby {
+                // the ruby goes to the space left above the line
+                c.position.y += ruby_ascent;
+            } else {
+                // make sure that the glyph is on the baseline (doing it here because font size might change on the line)
+                c.position.y += line_ascent;
+                // leave space for furigana
+                // TODO: we, obviously, should not do this when there is no furigana
+                c.position.y += furigana_height;
+            }
+
+            // if we are overflowing - make it fit by squishing the text
+            c.position.x *= fit_scale;
+            c.size.scale_horizontal(fit_scale);
+
+            // if needed - make the text fit by stretching it
+            if should_stretch {
+                // I don't get this formula...
+                // also it seems to do something strange
+                // TODO: figure this stuff out
+                // c.position.x = (self.params.layout_width - c.size.width)
+                //     * (self.position.x
+                //         / (self.position.x + (width - (self.position.x + c.size.width))));
+            }
+            c
+        };
+
+        let mut line = Vec::with_capacity(chars.len());
+        for (i, &c) in chars.iter().enumerate() {
+            line.push(place(c, false));
+            // the ruby chars go right after the first base char, they are shown at the same time
+            for group in ruby.iter().filter(|g| g.base.start == line_start + i) {
+                line.extend(group.chars.iter().map(|&c| place(c, true)));
+            }
+        }
+
         // Append line to chars
-        self.chars.push(
-            chars
-                .iter()
-                .cloned()
-                .map(|mut c| {
-                    // align the text according to the layout params
-                    c.position.x += x_offset;
-
-                    // move the text to the beginning of the real line
-                    // x might be larger than we want if an overflow happened
-                    c.position.x -= x_pos;
-
-                    // move the glyph on its line y coordinate (previously it was zero)
-                    c.position.y += self.position.y;
-                    // make sure that the glyph is on the baseline (doing it here because font size might change on the line)
-                    c.position.y += line_ascent;
-                    // leave space for furigana
-                    // TODO: we, obviously, should not do this when there is no furigana
-                    c.position.y += furigana_height;
-
-                    // if we are overflowing - make it fit by squishing the text
-                    c.position.x *= fit_scale;
-                    c.size.scale_horizontal(fit_scale);
-
-                    // if needed - make the text fit by stretching it
-                    if should_stretch {
-                        // I don't get this formula...
-                        // also it seems to do something strange
-                        // TODO: figure this stuff out
-                        // c.position.x = (self.params.layout_width - c.size.width)
-                        //     * (self.position.x
-                        //         / (self.position.x + (width - (self.position.x + c.size.width))));
-                    }
-                    c
-                })
-                .collect(),
-        );
+        self.chars.push(line);
 
         self.position.x = 0.0;
 
         self.position.y += max_line_height + furigana_height + 4.0 /* TODO: this is one of the many obscure line height-type parameters */;
     }
 
+    /// Checks whether the char doesn't fit on the line starting at `x_pos`
+    fn overflows(&self, c: &LayoutedChar, x_pos: f32) -> bool {
+        // if the start of the character is outside of the layout width
+        c.position.x - x_pos > self.params.layout_width
+            // or if the end of the character is outside of the layout width * 1.05
+            || c.position.x + c.size.width - x_pos > self.params.layout_width * 1.05
+        /* allow a bit of overflow, the chars will be rescaled */
+    }
+
+    /// Chooses where to break the line starting at `start` when the char at `overflow` doesn't fit
+    ///
+    /// Returns the chars of the finished line and the index of the char starting the next one.
+    fn break_line(
+        &self,
+        chars: &[LayoutedChar],
+        breaks: &[bool],
+        start: usize,
+        overflow: usize,
+    ) -> (Vec<LayoutedChar>, usize) {
+        let LineBreaking::Word { hyphenator } = self.params.line_breaking else {
+            return (chars[start..overflow].to_vec(), overflow);
+        };
+
+        // never break before the first char of the line, so that each line has at least one char
+        if let Some(next) = (start + 1..=overflow).rev().find(|&i| breaks[i]) {
+            // whitespace at the end of the line is not shown
+            let mut end = next;
+            while end > start + 1 && chars[end - 1].char().is_whitespace() {
+                end -= 1;
+            }
+            return (chars[start..end].to_vec(), next);
+        }
+
+        // the word doesn't fit on a line by itself, try to hyphenate it
+        if let Some(hyphenator) = hyphenator {
+            let word_end = (overflow + 1..chars.len())
+                .find(|&i| breaks[i])
+                .unwrap_or(chars.len());
+            let word = chars[start..word_end]
+                .iter()
+                .map(LayoutedChar::char)
+                .collect::<String>();
+
+            let x_pos = chars[start].position.x;
+            let mut points = hyphenator.hyphenation_points(&word);
+            points.sort_unstable();
+            for point in points.into_iter().rev() {
+                let next = start + point;
+                if next <= start || next > overflow {
+                    continue;
+                }
+
+                let last = chars[next - 1];
+                let font_size = last.size.line_height / self.params.base_font_height;
+                let hyphen = LayoutedChar {
+                    position: vec2(last.position.x + last.size.advance_width, 0.0),
+                    size: self.params.glyph_size(font_size, '-' as u16),
+                    codepoint: '-' as u16,
+                    rotated: self.params.direction == TextDirection::Vertical,
+                    ..last
+                };
+                if !self.overflows(&hyphen, x_pos) {
+                    let mut line = chars[start..next].to_vec();
+                    line.push(hyphen);
+                    return (line, next);
+                }
+            }
+        }
+
+        (chars[start..overflow].to_vec(), overflow)
+    }
+
     fn on_newline(&mut self, wrap: bool) {
         let chars = std::mem::take(&mut self.pending_chars);
+        let ruby = std::mem::take(&mut self.pending_ruby);
+        if self.ruby_start.take().is_some() {
+            warn!("Ruby base text is not closed before the end of the line");
+        }
+
+        // the ruby base text is never broken
+        let ruby_base_start = |i: usize| {
+            ruby.iter()
+                .find(|g| g.base.start < i && i < g.base.end)
+                .map(|g| g.base.start)
+        };
 
         let mut start = 0;
         let mut x_pos = 0.0;
 
         if wrap {
+            let mut breaks = match self.params.line_breaking {
+                LineBreaking::Character => Vec::new(),
+                LineBreaking::Word { .. } => {
+                    break_opportunities(&chars.iter().map(LayoutedChar::char).collect::<String>())
+                }
+            };
+            if !breaks.is_empty() {
+                for group in &ruby {
+                    breaks[group.base.start + 1..group.base.end].fill(false);
+                }
+            }
+
             // split into lines on overflows
-            // TODO: implement word wrapping?
             for (i, c) in chars.iter().enumerate() {
-                // if the start of the character is outside of the layout width
-                if c.position.x - x_pos > self.params.layout_width
-                    // or if the end of the character is outside of the layout width * 1.05
-                    || c.position.x + c.size.width - x_pos > self.params.layout_width * 1.05
-                /* allow a bit of overflow, the chars will be rescaled */
-                {
-                    self.finalize_line(&chars[start..i], false, x_pos);
-                    x_pos = c.position.x;
-                    start = i;
+                if self.overflows(c, x_pos) {
+                    let overflow = ruby_base_start(i).filter(|&s| s > start).unwrap_or(i);
+                    let (line, next) = self.break_line(&chars, &breaks, start, overflow);
+                    self.finalize_line(&line, &ruby, start, false, x_pos);
+                    x_pos = chars[next].position.x;
+                    start = next;
                 }
             }
         }
 
         // TODO: handle overflows
-        self.finalize_line(&chars[start..], true, x_pos);
+        self.finalize_line(&chars[start..], &ruby, start, true, x_pos);
         self.pending_chars.clear();
     }
 
     fn finalize(mut self) -> Vec<Vec<LayoutedChar>> {
-        // TODO: close furigana
         self.on_newline(true);
         self.chars
     }
@@ -448,6 +868,9 @@ pub fn layout_text(params: LayoutParams, text: &str) -> LayoutedMessage {
         state: params.default_state,
         chars: Vec::new(),
         pending_chars: Vec::new(),
+        pending_ruby: Vec::new(),
+        ruby_text: None,
+        ruby_start: None,
         position: vec2(0.0, 0.0),
         time: Ticks::ZERO,
     };
@@ -485,13 +908,9 @@ pub fn layout_text(params: LayoutParams, text: &str) -> LayoutedMessage {
                 ParsedCommand::DisableLipsync => {
                     actions_builder.action(layouter.time, ActionType::SetLipSync(false))
                 }
-                ParsedCommand::Furigana(_) => warn!("Furigana layout command is not implemented"),
-                ParsedCommand::FuriganaStart => {
-                    warn!("FuriganaStart layout command is not implemented")
-                }
-                ParsedCommand::FuriganaEnd => {
-                    warn!("FuriganaEnd layout command is not implemented")
-                }
+                ParsedCommand::Furigana(text) => layouter.ruby_text = Some(text),
+                ParsedCommand::FuriganaStart => layouter.on_ruby_start(),
+                ParsedCommand::FuriganaEnd => layouter.on_ruby_end(),
                 ParsedCommand::SetFade(fade) => layouter.state.fade = fade,
                 ParsedCommand::SetColor(color) => {
                     layouter.state.text_color = color.unwrap_or(Vec3::new(1.0, 1.0, 1.0))
@@ -581,6 +1000,13 @@ mod tests {
     }
 
     fn test_layout(text: &str) -> Vec<LayoutedChar> {
+        test_layout_with(text, |_| {})
+    }
+
+    fn test_layout_with(
+        text: &str,
+        configure: impl FnOnce(&mut LayoutParams),
+    ) -> Vec<LayoutedChar> {
         // NOTICE: here we need to use a font
         // it is an asset, so we need to load it from __somewhere__
         // having tests that depend on assets is not ideal
@@ -590,7 +1016,7 @@ mod tests {
         let mut font = BufReader::new(font);
         let font = shin_core::format::font::read_lazy_font(&mut font).unwrap();
 
-        let params = LayoutParams {
+        let mut params = LayoutParams {
             font: &font,
             layout_width: 1500.0,
             character_name_layout_width: 384.0,
@@ -601,7 +1027,11 @@ mod tests {
             default_state: LayouterState::default(),
             has_character_name: true,
             mode: LayoutingMode::MessageText,
+            line_breaking: LineBreaking::Character,
+            ruby: RubyParams::default(),
+            direction: TextDirection::Horizontal,
         };
+        configure(&mut params);
 
         let message = layout_text(params, text);
 
@@ -648,4 +1078,240 @@ mod tests {
         assert_eq!(c.codepoint, '姿' as u16);
         assert!(c.position.y > 40.625);
     }
+
+    /// Groups the layouted chars into lines by their baseline
+    fn lines(chars: &[LayoutedChar]) -> Vec<String> {
+        let mut lines: Vec<(f32, String)> = Vec::new();
+        for c in chars {
+            match lines.last_mut() {
+                Some((y, line)) if *y == c.position.y => line.push(c.char()),
+                _ => lines.push((c.position.y, c.char().to_string())),
+            }
+        }
+        lines.into_iter().map(|(_, line)| line).collect()
+    }
+
+    #[test]
+    fn break_opportunities_mixed() {
+        let text = "Hello, world! これは「テスト」です。";
+        let breaks = break_opportunities(text);
+        let allowed = text
+            .chars()
+            .zip(breaks)
+            .enumerate()
+            .filter(|(_, (_, b))| *b)
+            .map(|(i, (c, _))| (i, c))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            allowed,
+            vec![
+                // after the spaces
+                (7, 'w'),
+                (14, 'こ'),
+                // between the CJK chars, but not after the opening bracket or before the closing one and the full stop
+                (15, 'れ'),
+                (16, 'は'),
+                (17, '「'),
+                (22, 'で'),
+                (23, 'す'),
+            ]
+        );
+    }
+
+    #[test]
+    fn word_wrap_latin() {
+        let text = "It was a dark and stormy night; the rain fell in torrents, except at occasional intervals, when it was checked by a violent gust of wind which swept up the streets.";
+        let result = test_layout_with(&format!("@r{}", text), |p| {
+            p.line_breaking = LineBreaking::Word { hyphenator: None }
+        });
+
+        let lines = lines(&result);
+        assert!(lines.len() > 1);
+        // the lines are broken only at the spaces, which are dropped
+        assert_eq!(lines.join(" "), text);
+    }
+
+    #[test]
+    fn word_wrap_mixed() {
+        let text = "「Hello there」と彼は言った。The quick brown fox jumps over the lazy dog, それから狐は森の奥深くへと走り去っていった。Nobody has ever seen that fox again since then.";
+        let result = test_layout_with(&format!("@r{}", text), |p| {
+            p.line_breaking = LineBreaking::Word { hyphenator: None }
+        });
+
+        let lines = lines(&result);
+        assert!(lines.len() > 1);
+        for line in &lines {
+            assert!(
+                !NO_LINE_START.contains(line.chars().next().unwrap()),
+                "{:?}",
+                line
+            );
+            assert!(
+                !NO_LINE_END.contains(line.chars().next_back().unwrap()),
+                "{:?}",
+                line
+            );
+        }
+        // no Latin word is split between the lines
+        for word in text.split(|c: char| !c.is_ascii_alphabetic()) {
+            assert!(lines.iter().any(|line| line.contains(word)), "{:?}", word);
+        }
+    }
+
+    #[test]
+    fn word_wrap_hyphenation() {
+        /// Allows to hyphenate anywhere
+        struct AnywhereHyphenator;
+
+        impl Hyphenator for AnywhereHyphenator {
+            fn hyphenation_points(&self, word: &str) -> Vec<usize> {
+                (1..word.chars().count()).collect()
+            }
+        }
+
+        let word = "Supercalifragilisticexpialidocious".repeat(4);
+        let result = test_layout_with(&format!("@r{}", word), |p| {
+            p.line_breaking = LineBreaking::Word {
+                hyphenator: Some(&AnywhereHyphenator),
+            }
+        });
+
+        let lines = lines(&result);
+        assert!(lines.len() > 1);
+        assert!(lines[..lines.len() - 1]
+            .iter()
+            .all(|line| line.ends_with('-')));
+        assert_eq!(
+            lines
+                .iter()
+                .map(|line| line.trim_end_matches('-'))
+                .collect::<String>(),
+            word
+        );
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-3,
+            "{} is not close to {}",
+            actual,
+            expected
+        );
+    }
+
+    /// Splits the layouted chars into the base text and the ruby chars
+    fn split_ruby(chars: &[LayoutedChar], ruby: &str) -> (Vec<LayoutedChar>, Vec<LayoutedChar>) {
+        let ruby_codepoints = ruby.chars().map(|c| c as u16).collect::<Vec<_>>();
+        chars
+            .iter()
+            .copied()
+            .partition(|c| !ruby_codepoints.contains(&c.codepoint))
+    }
+
+    #[test]
+    fn ruby_distribute() {
+        let result = test_layout("@r@bかんじ.@<漢字@>です");
+        let (base, ruby) = split_ruby(&result, "かんじ");
+
+        // the ruby goes right after the first base char and is shown with it
+        assert_eq!(result[0].codepoint, '漢' as u16);
+        assert_eq!(result[1].codepoint, 'か' as u16);
+        assert!(ruby.iter().all(|c| c.time == base[0].time));
+        assert!(ruby.iter().all(|c| c.position.y < base[0].position.y));
+
+        // the ruby is narrower, so the base is not widened
+        assert_close(base[1].position.x, base[0].size.advance_width);
+
+        let base_width = base[2].position.x - base[0].position.x;
+        let ruby_width = ruby.iter().map(|c| c.size.advance_width).sum::<f32>();
+        let spacing = (base_width - ruby_width) / 3.0;
+        let mut x = base[0].position.x + spacing / 2.0;
+        for c in &ruby {
+            assert_close(c.position.x, x);
+            x += c.size.advance_width + spacing;
+        }
+        assert_close(x - spacing / 2.0, base[2].position.x);
+    }
+
+    #[test]
+    fn ruby_widen_base() {
+        let text = "とてもながいふりがなです";
+        let result = test_layout(&format!("@r@b{}.@<字@>だ", text));
+        let (base, ruby) = split_ruby(&result, text);
+
+        // squished down to the minimum scale
+        assert_close(
+            ruby[0].size.horizontal_scale,
+            ruby[0].size.scale * 0.9697 * 0.8,
+        );
+
+        // and the base is widened to match, with the ruby spanning the whole of it
+        let ruby_width = ruby.iter().map(|c| c.size.advance_width).sum::<f32>();
+        assert_close(base[1].position.x, ruby_width);
+        assert_close(ruby[0].position.x, 0.0);
+        let last = ruby.last().unwrap();
+        assert_close(
+            last.position.x + last.size.advance_width,
+            base[1].position.x,
+        );
+        // the base char is centered in the widened space
+        assert_close(
+            base[0].position.x * 2.0 + base[0].size.advance_width,
+            base[1].position.x,
+        );
+    }
+
+    #[test]
+    fn ruby_latin_overhang() {
+        let gloss = "the scholar of old";
+        let result = test_layout_with(&format!("@r@b{}.@<学者@>だ", gloss), |p| {
+            p.ruby = RubyParams::LATIN
+        });
+        let (base, ruby) = split_ruby(&result, gloss);
+
+        // the base is not widened
+        assert_close(base[1].position.x, base[0].size.advance_width);
+        assert_close(
+            base[2].position.x,
+            base[0].size.advance_width + base[1].size.advance_width,
+        );
+
+        // the ruby is squished and overhangs the base text evenly on both sides
+        assert!(ruby
+            .iter()
+            .all(|c| c.size.horizontal_scale < c.size.scale * 0.9697 * 0.9));
+        let last = ruby.last().unwrap();
+        let ruby_center = (ruby[0].position.x + last.position.x + last.size.advance_width) / 2.0;
+        assert_close(ruby_center, base[2].position.x / 2.0);
+        assert!(ruby[0].position.x < base[0].position.x);
+    }
+
+    #[test]
+    fn vertical() {
+        let text = "縦書きのテキスト、Latin。次の列に続く文章です。";
+        let result = test_layout_with(&format!("@r{}", text), |p| {
+            p.direction = TextDirection::Vertical;
+            p.layout_width = 500.0;
+        });
+        assert_eq!(result.len(), text.chars().count());
+
+        // upright glyphs advance by the line height down the column (the column is squished a bit to fit)
+        assert!(!result[0].rotated);
+        let advance = result[1].position.y - result[0].position.y;
+        assert!((45.0..=55.0).contains(&advance));
+        assert_close(result[2].position.y - result[1].position.y, advance);
+        // while the Latin ones are rotated
+        let latin = result.iter().filter(|c| c.rotated).collect::<Vec<_>>();
+        assert_eq!(latin.iter().map(|c| c.char()).collect::<String>(), "Latin");
+        assert!(latin
+            .windows(2)
+            .all(|w| w[1].position.y > w[0].position.y && w[1].position.x == w[0].position.x));
+
+        // the text doesn't fit into one column, the next ones go to the left
+        assert!(result.iter().all(|c| c.position.x < 0.0));
+        let last = result.last().unwrap();
+        assert!(last.position.x < result[0].position.x - 50.0);
+        assert!(last.position.y < result[0].position.y + 500.0);
+    }
 }
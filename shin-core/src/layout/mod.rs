@@ -1,8 +1,10 @@
+pub mod fixture;
 mod layouter;
 mod parser;
 
 pub use layouter::{
-    layout_text, Action, ActionType, Block, BlockExitCondition, LayoutParams, LayoutedChar,
-    LayoutedMessage, LayouterState, LayoutingMode,
+    layout_text, Action, ActionType, Block, BlockExitCondition, Hyphenator, LayoutParams,
+    LayoutedChar, LayoutedMessage, LayouterState, LayoutingMode, LineBreaking, RubyAlignment,
+    RubyParams, TextDirection,
 };
 pub use parser::{LayouterParser, ParsedCommand};
@@ -18,15 +18,41 @@ pub enum Easing {
     ///   of the [`Tween`] and then jump to the end value.
     Jump,
 
-    /// TODO: document
-    /// This is some weird one, it uses power functions instead of sine/cosine
+    /// Uses the power function of the given degree instead of sine/cosine.
+    ///
+    /// A positive degree starts slow and speeds up (like [`Easing::SineIn`]), a negative one mirrors the curve to start fast and slow down (like [`Easing::SineOut`]).
+    /// Zero is the same as [`Easing::Linear`].
     Power(i32),
+    /// Like [`Easing::Power`], but applied to both halves of the motion: starts slow, speeds up, and slows back down.
+    ///
+    /// The sign of the degree is ignored.
+    PowerInOut(i32),
 }
 
 const HALF_PI: f32 = PI / 2.0;
 
 impl Easing {
-    fn apply(&self, x: f32) -> f32 {
+    /// Decodes the easing of the `LAYERCTRL` command from the `easing` field of its flags and the `easing_param` (the degree of the power easings)
+    ///
+    /// Returns `None` for the unknown easing kinds.
+    pub fn from_layerctrl(kind: i32, param: i32) -> Option<Self> {
+        Some(match kind {
+            0 => Easing::Linear,
+            1 => Easing::SineIn,
+            2 => Easing::SineOut,
+            3 => Easing::SineInOut,
+            4 => Easing::Jump,
+            5 => Easing::Power(param),
+            6 => Easing::PowerInOut(param),
+            _ => return None,
+        })
+    }
+
+    /// Maps the progress of the motion (in `[0; 1]`) to the progress of the value
+    ///
+    /// The progress outside of the range is clamped.
+    pub fn apply(&self, x: f32) -> f32 {
+        let x = x.clamp(0.0, 1.0);
         match *self {
             Easing::Linear => x,
             Easing::SineIn => 1.0 - (x * HALF_PI).cos(),
@@ -48,6 +74,16 @@ impl Easing {
                     x
                 }
             }
+            Easing::PowerInOut(power) => {
+                let power = power.abs();
+                if power == 0 {
+                    x
+                } else if x < 0.5 {
+                    (2.0 * x).powi(power) / 2.0
+                } else {
+                    1.0 - (2.0 - 2.0 * x).powi(power) / 2.0
+                }
+            }
         }
     }
 }
@@ -85,3 +121,57 @@ impl Tween {
         self.easing.apply(x)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Easing;
+
+    #[test]
+    fn easing_values() {
+        #[rustfmt::skip]
+        let table = [
+            (Easing::Linear, [0.0, 0.25, 0.5, 0.75, 1.0]),
+            (Easing::SineIn, [0.0, 0.07612047, 0.29289323, 0.61731654, 1.0]),
+            (Easing::SineOut, [0.0, 0.38268343, 0.70710677, 0.9238795, 1.0]),
+            (Easing::SineInOut, [0.0, 0.14644662, 0.5, 0.8535534, 1.0]),
+            (Easing::Jump, [0.0, 0.0, 0.0, 0.0, 1.0]),
+            (Easing::Power(0), [0.0, 0.25, 0.5, 0.75, 1.0]),
+            (Easing::Power(2), [0.0, 0.0625, 0.25, 0.5625, 1.0]),
+            (Easing::Power(3), [0.0, 0.015625, 0.125, 0.421875, 1.0]),
+            (Easing::Power(-2), [0.0, 0.4375, 0.75, 0.9375, 1.0]),
+            (Easing::PowerInOut(2), [0.0, 0.125, 0.5, 0.875, 1.0]),
+            (Easing::PowerInOut(-3), [0.0, 0.0625, 0.5, 0.9375, 1.0]),
+            (Easing::PowerInOut(0), [0.0, 0.25, 0.5, 0.75, 1.0]),
+        ];
+
+        for (easing, expected) in table {
+            for (x, expected) in [0.0, 0.25, 0.5, 0.75, 1.0].into_iter().zip(expected) {
+                let actual = easing.apply(x);
+                assert!(
+                    (actual - expected).abs() < 1e-6,
+                    "{:?} at {}: expected {}, got {}",
+                    easing,
+                    x,
+                    expected,
+                    actual
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn easing_clamps_progress() {
+        for easing in [Easing::Linear, Easing::SineInOut, Easing::Power(-2)] {
+            assert_eq!(easing.apply(-0.5), 0.0, "{:?}", easing);
+            assert_eq!(easing.apply(1.5), 1.0, "{:?}", easing);
+        }
+    }
+
+    #[test]
+    fn layerctrl_easing() {
+        assert_eq!(Easing::from_layerctrl(0, 5), Some(Easing::Linear));
+        assert_eq!(Easing::from_layerctrl(5, 3), Some(Easing::Power(3)));
+        assert_eq!(Easing::from_layerctrl(6, -2), Some(Easing::PowerInOut(-2)));
+        assert_eq!(Easing::from_layerctrl(7, 0), None);
+    }
+}
@@ -1,11 +1,45 @@
 use std::collections::VecDeque;
 
+use glam::{Vec2, Vec3, Vec4};
+
 use crate::time::{Ticks, Tween};
 
-type Value = f32;
+/// A value that can be smoothly transitioned by a [`Tweener`].
+pub trait Tweenable: Copy {
+    /// Returns an linearly interpolated value between `a` and `b`.
+    ///
+    /// An amount of `0.0` should yield `a`, an amount of `1.0` should
+    /// yield `b`, and an amount of `0.5` should yield a value halfway
+    /// between `a` and `b`.
+    fn lerp(a: Self, b: Self, amount: f32) -> Self;
+}
+
+impl Tweenable for f32 {
+    fn lerp(a: Self, b: Self, amount: f32) -> Self {
+        a + (b - a) * amount
+    }
+}
+
+impl Tweenable for Vec2 {
+    fn lerp(a: Self, b: Self, amount: f32) -> Self {
+        a.lerp(b, amount)
+    }
+}
+
+impl Tweenable for Vec3 {
+    fn lerp(a: Self, b: Self, amount: f32) -> Self {
+        a.lerp(b, amount)
+    }
+}
+
+impl Tweenable for Vec4 {
+    fn lerp(a: Self, b: Self, amount: f32) -> Self {
+        a.lerp(b, amount)
+    }
+}
 
 #[derive(Debug, Clone, Copy)]
-enum State {
+enum State<Value> {
     Idle,
     Tweening {
         values: (Value, Value),
@@ -16,13 +50,15 @@ enum State {
 
 /// Holds a value and plays back tweens which smoothly
 /// adjust that value.
-pub struct Tweener {
+///
+/// The tweens are chained: the ones enqueued while another is playing start when it finishes.
+pub struct Tweener<Value: Tweenable = f32> {
     tween_queue: VecDeque<(Value, Tween)>,
-    state: State,
+    state: State<Value>,
     value: Value,
 }
 
-impl Tweener {
+impl<Value: Tweenable> Tweener<Value> {
     pub fn new(value: Value) -> Self {
         Self {
             tween_queue: VecDeque::new(),
@@ -35,12 +71,13 @@ impl Tweener {
         self.value
     }
 
+    /// The value the tweener ends up with after playing all the enqueued tweens
     pub fn target_value(&self) -> Value {
         match self.state {
             State::Idle => self.value,
             State::Tweening {
                 values: (_, value), ..
-            } => match self.tween_queue.front() {
+            } => match self.tween_queue.back() {
                 None => value,
                 Some(&(value, _)) => value,
             },
@@ -67,13 +104,11 @@ impl Tweener {
         }
     }
 
-    /// Returns an linearly interpolated value between `a` and `b`.
+    /// Enqueues a pause: the value stays at the previously enqueued one for the `duration`.
     ///
-    /// An amount of `0.0` should yield `a`, an amount of `1.0` should
-    /// yield `b`, and an amount of `0.5` should yield a value halfway
-    /// between `a` and `b`.
-    fn lerp(a: Value, b: Value, amount: f32) -> Value {
-        a + (b - a) * amount
+    /// Useful to delay the next tween in the chain.
+    pub fn enqueue_hold(&mut self, duration: Ticks) {
+        self.enqueue(self.target_value(), Tween::linear(duration));
     }
 
     fn next(&mut self, time: Ticks) {
@@ -88,8 +123,13 @@ impl Tweener {
         }
     }
 
-    pub fn update(&mut self, delta_time: Ticks) {
-        if let State::Tweening {
+    /// Advances the playing tweens by `delta_time`
+    ///
+    /// Returns `true` if the last tween in the chain has finished during this update, so the callers can react to the end of the motion.
+    pub fn update(&mut self, delta_time: Ticks) -> bool {
+        let mut delta_time = delta_time;
+        // a long frame can finish several short tweens at once
+        while let State::Tweening {
             values,
             time,
             tween,
@@ -99,16 +139,22 @@ impl Tweener {
             if *time >= tween.duration {
                 self.value = values.1;
                 let remaining_time = *time - tween.duration;
-                self.next(remaining_time);
+                self.next(Ticks::ZERO);
+                delta_time = remaining_time;
+                if self.is_idle() {
+                    return true;
+                }
             } else {
-                self.value = Self::lerp(values.0, values.1, tween.value(*time));
+                self.value = Value::lerp(values.0, values.1, tween.value(*time));
+                break;
             }
         }
+        false
     }
 
     /// Fast-forwards the tweener to the last enqueue value.
     pub fn fast_forward(&mut self) {
-        let last_queue_value = self.tween_queue.pop_front();
+        let last_queue_value = self.tween_queue.pop_back();
         self.tween_queue.clear();
 
         let value = match last_queue_value {
@@ -136,3 +182,50 @@ impl Tweener {
         self.enqueue(value, tween);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use glam::vec2;
+
+    use super::Tweener;
+    use crate::time::{Ticks, Tween};
+
+    #[test]
+    fn chained_tweens() {
+        let mut tweener = Tweener::new(0.0);
+        tweener.enqueue(1.0, Tween::linear(Ticks::from_f32(10.0)));
+        tweener.enqueue_hold(Ticks::from_f32(10.0));
+        tweener.enqueue(0.0, Tween::linear(Ticks::from_f32(10.0)));
+        assert_eq!(tweener.target_value(), 0.0);
+
+        assert!(!tweener.update(Ticks::from_f32(5.0)));
+        assert_eq!(tweener.value(), 0.5);
+        assert!(!tweener.update(Ticks::from_f32(10.0)));
+        assert_eq!(tweener.value(), 1.0);
+        assert!(!tweener.update(Ticks::from_f32(10.0)));
+        assert_eq!(tweener.value(), 0.5);
+        assert!(tweener.update(Ticks::from_f32(10.0)));
+        assert_eq!(tweener.value(), 0.0);
+        assert!(tweener.is_idle());
+        assert!(!tweener.update(Ticks::from_f32(10.0)));
+    }
+
+    #[test]
+    fn long_update_skips_short_tweens() {
+        let mut tweener = Tweener::new(0.0);
+        tweener.enqueue(1.0, Tween::linear(Ticks::from_f32(1.0)));
+        tweener.enqueue(2.0, Tween::linear(Ticks::from_f32(1.0)));
+        tweener.enqueue(4.0, Tween::linear(Ticks::from_f32(4.0)));
+
+        assert!(!tweener.update(Ticks::from_f32(4.0)));
+        assert_eq!(tweener.value(), 3.0);
+    }
+
+    #[test]
+    fn vector_values() {
+        let mut tweener = Tweener::new(vec2(0.0, 10.0));
+        tweener.enqueue(vec2(10.0, 0.0), Tween::linear(Ticks::from_f32(10.0)));
+        tweener.update(Ticks::from_f32(2.5));
+        assert_eq!(tweener.value(), vec2(2.5, 7.5));
+    }
+}
@@ -3,7 +3,7 @@
 use std::{
     collections::{hash_map::Entry, HashMap},
     sync::{
-        atomic::{AtomicU32, Ordering},
+        atomic::{AtomicBool, AtomicU32, Ordering},
         Arc, Weak,
     },
 };
@@ -12,12 +12,15 @@ use crate::format::scenario::instruction_elements::CodeAddress;
 
 pub(crate) struct Breakpoint {
     hit_count: AtomicU32,
+    /// Whether the VM should stop before executing the instruction (see [`Scripter::step`](super::Scripter::step))
+    pause: AtomicBool,
 }
 
 impl Breakpoint {
     pub fn new() -> Self {
         Self {
             hit_count: AtomicU32::new(0),
+            pause: AtomicBool::new(false),
         }
     }
 }
@@ -50,6 +53,14 @@ impl CodeBreakpointSet {
         }
     }
 
+    /// Checks whether there is a live breakpoint at the address that should pause the VM
+    pub fn pauses_at(&self, addr: CodeAddress) -> bool {
+        self.0
+            .get(&addr)
+            .and_then(|b| b.upgrade())
+            .is_some_and(|b| b.pause.load(Ordering::SeqCst))
+    }
+
     pub fn add_breakpoint(&mut self, address: CodeAddress) -> BreakpointHandle {
         match self.0.entry(address) {
             Entry::Occupied(mut e) => match e.get().upgrade() {
@@ -81,6 +92,18 @@ impl BreakpointHandle {
     pub fn hit_count(&self) -> u32 {
         self.0.hit_count.load(Ordering::SeqCst)
     }
+
+    /// Make the VM stop before executing the instruction at the breakpoint address
+    ///
+    /// The breakpoints at the same address are shared, so this applies to all the handles to it.
+    /// Only [`Scripter::step`](super::Scripter::step) and the methods built on it stop, [`Scripter::run`](super::Scripter::run) ignores this.
+    pub fn set_pause(&self, pause: bool) {
+        self.0.pause.store(pause, Ordering::SeqCst);
+    }
+
+    pub fn pauses(&self) -> bool {
+        self.0.pause.load(Ordering::SeqCst)
+    }
 }
 
 /// Combines handle to the breakpoint with a counter, allowing to check whether the BP was hit between [BreakpointObserver::update] calls
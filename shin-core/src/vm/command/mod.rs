@@ -5,7 +5,7 @@ pub mod types;
 use shin_derive::Command;
 use types::{
     AudioWaitStatus, LayerCtrlFlags, LayerId, LayerProperty, LayerType, MaskFlags, MessageboxStyle,
-    Pan, VLayerId, Volume,
+    Pan, VLayerId, Volume, WiperType,
 };
 
 use crate::{
@@ -281,8 +281,12 @@ pub enum Command {
         layer_id: NumberSpec<VLayerId>,
         wait_properties: U8SmallNumberList<LayerProperty>,
     },
+    /// Swap two layers of the current plane, including their properties
     #[cmd(opcode = 0xc5u8)]
-    LAYERSWAP { arg1: NumberSpec, arg2: NumberSpec },
+    LAYERSWAP {
+        layer_id1: NumberSpec<LayerId>,
+        layer_id2: NumberSpec<LayerId>,
+    },
     /// Select a subset of layers to perform batch operations
     /// (TODO: fact check) These can be used as layer_id = -4
     #[cmd(opcode = 0xc6u8)]
@@ -297,27 +301,37 @@ pub enum Command {
         target_status: NumberSpec,
     },
     // 0xc8 unused
+    /// Start a transition of the current plane from its currently displayed contents to the new ones
+    ///
+    /// The parameters are decoded into [WiperParams](types::WiperParams).
     #[cmd(opcode = 0xc9u8)]
     TRANSSET {
-        arg1: NumberSpec,
-        arg2: NumberSpec,
+        wiper_type: NumberSpec<WiperType>,
+        duration: NumberSpec<Ticks>,
         arg3: NumberSpec,
         params: BitmaskNumberArray,
     },
+    /// Wait for the transition of the current plane to finish
     #[cmd(opcode = 0xcau8)]
-    TRANSWAIT { arg: NumberSpec },
+    TRANSWAIT {
+        /// The meaning is not known yet, the engine ignores it and always waits for the whole transition
+        arg: NumberSpec,
+    },
     #[cmd(opcode = 0xcbu8)]
     PAGEBACK {},
     #[cmd(opcode = 0xccu8)]
     PLANESELECT { plane_id: NumberSpec },
     #[cmd(opcode = 0xcdu8)]
     PLANECLEAR {},
+    /// Load a mask for the current plane
     #[cmd(opcode = 0xceu8)]
     MASKLOAD {
         mask_data_id: NumberSpec,
         mask_flags: NumberSpec<MaskFlags>,
-        smth_smth_transition: NumberSpec<bool>,
+        /// If set, the mask is not applied right away, but is used by the next transition of the plane instead
+        transition: NumberSpec<bool>,
     },
+    /// Remove the mask of the current plane
     #[cmd(opcode = 0xcfu8)]
     MASKUNLOAD {},
 
@@ -35,6 +35,7 @@ bitflags! {
         const FLIP_X = 0x0001;
         const FLIP_Y = 0x0002;
         const UNK_4 = 0x0004;
+        /// Stretch the mask to the whole screen instead of using it at its native size
         const SCALE = 0x0010;
     }
 }
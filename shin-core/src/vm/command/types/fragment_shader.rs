@@ -0,0 +1,128 @@
+use glam::{vec3, Vec3, Vec4, Vec4Swizzles};
+use num_derive::FromPrimitive;
+
+/// Color effect applied when drawing a layer, selected by the [FragmentShader](super::LayerProperty::FragmentShader) property
+///
+/// The effects are parametrized by the `ShaderParamX`..`ShaderParamW` properties (divided by 1000).
+/// Unless stated otherwise, `xyz` is a color and `w` is the intensity of the effect.
+///
+/// [`LayerFragmentShader::evaluate`] is the CPU reference implementation of the effects, the GPU one must match it.
+#[derive(FromPrimitive, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum LayerFragmentShader {
+    Default = 0,
+    /// Convert to grayscale, tinted by the color
+    Mono = 1,
+    /// Replace the color
+    Fill = 2,
+    /// Add the color
+    Fill2 = 3,
+    /// Invert the color
+    Negative = 4,
+    /// Gamma-correct each channel, using `xyz` as gamma values (`w` is unused)
+    Gamma = 5,
+}
+
+/// Weights used to compute the luminance of a color (Rec. 601)
+pub const LUMA_WEIGHTS: Vec3 = vec3(0.299, 0.587, 0.114);
+
+impl LayerFragmentShader {
+    /// Applies the effect to a (non-premultiplied) texel, leaving the alpha as-is
+    pub fn evaluate(self, texel: Vec4, params: Vec4) -> Vec4 {
+        let color = texel.xyz();
+        let intensity = params.w;
+
+        let color = match self {
+            LayerFragmentShader::Default => color,
+            LayerFragmentShader::Mono => {
+                let gray = color.dot(LUMA_WEIGHTS);
+                color.lerp(params.xyz() * gray, intensity)
+            }
+            LayerFragmentShader::Fill => color.lerp(params.xyz(), intensity),
+            LayerFragmentShader::Fill2 => (color + params.xyz() * intensity).min(Vec3::ONE),
+            LayerFragmentShader::Negative => color.lerp(Vec3::ONE - color, intensity),
+            LayerFragmentShader::Gamma => {
+                let gamma = params.xyz().max(Vec3::splat(0.001));
+                vec3(
+                    color.x.powf(1.0 / gamma.x),
+                    color.y.powf(1.0 / gamma.y),
+                    color.z.powf(1.0 / gamma.z),
+                )
+            }
+        };
+
+        color.extend(texel.w)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{vec4, Vec4};
+
+    use super::LayerFragmentShader;
+
+    fn assert_close(actual: Vec4, expected: Vec4) {
+        assert!(
+            actual.abs_diff_eq(expected, 1e-4),
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    const TEXEL: Vec4 = vec4(0.2, 0.4, 0.8, 0.5);
+
+    #[test]
+    fn default_is_identity() {
+        assert_close(
+            LayerFragmentShader::Default.evaluate(TEXEL, vec4(0.1, 0.2, 0.3, 1.0)),
+            TEXEL,
+        );
+    }
+
+    #[test]
+    fn mono() {
+        let gray = 0.2 * 0.299 + 0.4 * 0.587 + 0.8 * 0.114;
+        assert_close(
+            LayerFragmentShader::Mono.evaluate(TEXEL, Vec4::ONE),
+            vec4(gray, gray, gray, 0.5),
+        );
+        // zero intensity leaves the color untouched
+        assert_close(
+            LayerFragmentShader::Mono.evaluate(TEXEL, vec4(1.0, 1.0, 1.0, 0.0)),
+            TEXEL,
+        );
+    }
+
+    #[test]
+    fn fill() {
+        assert_close(
+            LayerFragmentShader::Fill.evaluate(TEXEL, vec4(1.0, 0.0, 0.0, 0.5)),
+            vec4(0.6, 0.2, 0.4, 0.5),
+        );
+        assert_close(
+            LayerFragmentShader::Fill2.evaluate(TEXEL, vec4(1.0, 0.5, 0.0, 0.5)),
+            vec4(0.7, 0.65, 0.8, 0.5),
+        );
+        assert_close(
+            LayerFragmentShader::Fill2.evaluate(TEXEL, Vec4::ONE),
+            vec4(1.0, 1.0, 1.0, 0.5),
+        );
+    }
+
+    #[test]
+    fn negative() {
+        assert_close(
+            LayerFragmentShader::Negative.evaluate(TEXEL, Vec4::ONE),
+            vec4(0.8, 0.6, 0.2, 0.5),
+        );
+    }
+
+    #[test]
+    fn gamma() {
+        assert_close(LayerFragmentShader::Gamma.evaluate(TEXEL, Vec4::ONE), TEXEL);
+        assert_close(
+            LayerFragmentShader::Gamma.evaluate(TEXEL, vec4(0.5, 0.5, 0.5, 1.0)),
+            vec4(0.04, 0.16, 0.64, 0.5),
+        );
+    }
+}
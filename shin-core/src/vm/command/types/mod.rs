@@ -1,15 +1,19 @@
 //! Types used in commands.
 
 mod flags;
+mod fragment_shader;
 mod id;
 mod property;
+mod wiper;
 
 pub use flags::{AudioWaitStatus, LayerCtrlFlags, MaskFlags};
+pub use fragment_shader::{LayerFragmentShader, LUMA_WEIGHTS};
 pub use id::{
     LayerId, LayerIdOpt, VLayerId, VLayerIdRepr, LAYERBANKS_COUNT, LAYERS_COUNT, PLANES_COUNT,
 };
 use num_derive::FromPrimitive;
 pub use property::LayerProperty;
+pub use wiper::{WiperParams, WiperType};
 
 use crate::format::scenario::instruction_elements::FromNumber;
 
@@ -162,6 +162,24 @@ impl LayerProperty {
         }
     }
 
+    /// Clamps the value set by `LAYERCTRL` to the range that is meaningful for the property
+    ///
+    /// The flags and enum-like properties are limited to their known values, the ratios (with 1000 meaning 100%) can't go below zero.
+    /// Everything else (positions, scales, angles) is passed through, as negative values are meaningful for them.
+    pub fn clamp(self, value: i32) -> i32 {
+        use LayerProperty::*;
+        match self {
+            ShowLayer => value.clamp(0, 1),
+            // bit 0 flips horizontally, bit 1 flips vertically
+            Flip => value.clamp(0, 3),
+            GhostingAlpha | DissolveIntensity => value.clamp(0, 1000),
+            MosaicSize | RainIntensity | GhostingZoom => value.max(0),
+            WobbleXPeriod | WobbleYPeriod | WobbleAlphaPeriod | WobbleScaleXPeriod
+            | WobbleScaleYPeriod | WobbleRotationPeriod => value.max(0),
+            _ => value,
+        }
+    }
+
     pub fn is_implemented(&self) -> bool {
         use LayerProperty::*;
         matches!(
@@ -174,6 +192,8 @@ impl LayerProperty {
             TranslateX | TranslateY | TranslateX2 | TranslateY2 |
             WobbleXMode | WobbleXPeriod | WobbleXAmplitude | WobbleXBias |
             WobbleYMode | WobbleYPeriod | WobbleYAmplitude | WobbleYBias |
+            ShowLayer | Flip | FragmentShader |
+            ShaderParamX | ShaderParamY | ShaderParamZ | ShaderParamW |
 
             // this one is not, actually, implemented
             // everything seems to work fine, so ignoring it for now
@@ -0,0 +1,80 @@
+use num_derive::FromPrimitive;
+use tracing::warn;
+
+use crate::format::scenario::instruction_elements::{FromNumber, UntypedNumberArray};
+
+/// The program used to transition between the old and the new contents of a plane
+///
+/// Used in [TRANSSET](super::super::runtime::TRANSSET) command
+#[derive(FromPrimitive, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum WiperType {
+    /// Crossfade between the old and the new contents
+    Default = 0,
+    /// Reveal the new contents following the mask loaded with [MASKLOAD](super::super::runtime::MASKLOAD), darker parts first
+    Mask = 1,
+}
+
+impl FromNumber for WiperType {
+    fn from_number(number: i32) -> Self {
+        num_traits::FromPrimitive::from_i32(number).unwrap_or_else(|| {
+            warn!("Unsupported wiper type {}, using the default one", number);
+            WiperType::Default
+        })
+    }
+}
+
+/// Parameters of a wiper, decoded from the [BitmaskNumberArray](crate::format::scenario::instruction_elements::BitmaskNumberArray) of a transition command
+///
+/// Parameters not used by a wiper are ignored.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct WiperParams {
+    /// Width of the soft edge of the mask wipe, as a fraction of the mask range
+    pub fade_width: f32,
+    /// Reveal the lighter parts of the mask first
+    pub reverse: bool,
+}
+
+impl WiperParams {
+    /// Fade width used when the parameter is omitted (encoded as zero)
+    pub const DEFAULT_FADE_WIDTH: f32 = 0.1;
+}
+
+impl From<UntypedNumberArray> for WiperParams {
+    fn from((fade_width, reverse, _, _, _, _, _, _): UntypedNumberArray) -> Self {
+        Self {
+            fade_width: if fade_width == 0 {
+                Self::DEFAULT_FADE_WIDTH
+            } else {
+                (fade_width as f32 / 1000.0).clamp(0.001, 1.0)
+            },
+            reverse: reverse != 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WiperParams;
+
+    #[test]
+    fn decode_params() {
+        assert_eq!(
+            WiperParams::from((0, 0, 0, 0, 0, 0, 0, 0)),
+            WiperParams {
+                fade_width: WiperParams::DEFAULT_FADE_WIDTH,
+                reverse: false,
+            }
+        );
+        assert_eq!(
+            WiperParams::from((250, 1, 0, 0, 0, 0, 0, 0)),
+            WiperParams {
+                fade_width: 0.25,
+                reverse: true,
+            }
+        );
+        assert_eq!(
+            WiperParams::from((5000, 0, 0, 0, 0, 0, 0, 0)).fade_width,
+            1.0
+        );
+    }
+}
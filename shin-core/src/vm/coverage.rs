@@ -0,0 +1,129 @@
+//! Contains scenario coverage recording for the VM
+//!
+//! When enabled, the [`Scripter`](super::Scripter) records addresses of all the executed instructions and the outcomes of all the executed branches.
+//! This allows finding parts of the scenario that were never reached, for example to make sure all the routes were tested.
+//!
+//! The logs are stored in a simple line-based text format, so logs of multiple sessions can be merged just by concatenating them.
+
+use std::{
+    collections::BTreeSet,
+    io::{BufRead, Write},
+};
+
+use anyhow::{bail, Context, Result};
+
+use crate::format::scenario::instruction_elements::CodeAddress;
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CoverageLog {
+    executed: BTreeSet<CodeAddress>,
+    /// Pairs of (branch instruction address, address the execution continued at)
+    branches: BTreeSet<(CodeAddress, CodeAddress)>,
+}
+
+impl CoverageLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn record_instruction(&mut self, address: CodeAddress) {
+        self.executed.insert(address);
+    }
+
+    #[inline]
+    pub fn record_branch(&mut self, from: CodeAddress, to: CodeAddress) {
+        self.branches.insert((from, to));
+    }
+
+    pub fn is_executed(&self, address: CodeAddress) -> bool {
+        self.executed.contains(&address)
+    }
+
+    pub fn is_branch_taken(&self, from: CodeAddress, to: CodeAddress) -> bool {
+        self.branches.contains(&(from, to))
+    }
+
+    pub fn merge(&mut self, other: &CoverageLog) {
+        self.executed.extend(other.executed.iter().copied());
+        self.branches.extend(other.branches.iter().copied());
+    }
+
+    pub fn write(&self, mut writer: impl Write) -> std::io::Result<()> {
+        for address in &self.executed {
+            writeln!(writer, "i {:08x}", address.0)?;
+        }
+        for (from, to) in &self.branches {
+            writeln!(writer, "b {:08x} {:08x}", from.0, to.0)?;
+        }
+        Ok(())
+    }
+
+    /// Reads a log written with [`CoverageLog::write`], possibly multiple concatenated ones
+    pub fn read(reader: impl BufRead) -> Result<Self> {
+        fn parse_address(s: Option<&str>) -> Result<CodeAddress> {
+            let s = s.context("Missing address")?;
+            u32::from_str_radix(s, 16)
+                .map(CodeAddress)
+                .with_context(|| format!("Invalid address: {:?}", s))
+        }
+
+        let mut result = Self::new();
+        for (line_number, line) in reader.lines().enumerate() {
+            let line = line?;
+            let mut parts = line.split_whitespace();
+            (|| {
+                match parts.next() {
+                    None => {}
+                    Some("i") => result.record_instruction(parse_address(parts.next())?),
+                    Some("b") => {
+                        let from = parse_address(parts.next())?;
+                        let to = parse_address(parts.next())?;
+                        result.record_branch(from, to);
+                    }
+                    Some(kind) => bail!("Unknown record kind: {:?}", kind),
+                }
+                Ok(())
+            })()
+            .with_context(|| format!("Parsing line {}", line_number + 1))?;
+        }
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CoverageLog;
+    use crate::format::scenario::instruction_elements::CodeAddress;
+
+    #[test]
+    fn roundtrip() {
+        let mut log = CoverageLog::new();
+        log.record_instruction(CodeAddress(0x10));
+        log.record_instruction(CodeAddress(0x18));
+        log.record_branch(CodeAddress(0x18), CodeAddress(0x40));
+
+        let mut written = Vec::new();
+        log.write(&mut written).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&written).unwrap(),
+            "i 00000010\ni 00000018\nb 00000018 00000040\n"
+        );
+
+        assert_eq!(CoverageLog::read(written.as_slice()).unwrap(), log);
+    }
+
+    #[test]
+    fn merge_by_concatenation() {
+        let logs = "i 00000010\nb 00000010 00000020\n\ni 00000010\nb 00000010 00000014\n";
+        let log = CoverageLog::read(logs.as_bytes()).unwrap();
+
+        assert!(log.is_executed(CodeAddress(0x10)));
+        assert!(!log.is_executed(CodeAddress(0x14)));
+        assert!(log.is_branch_taken(CodeAddress(0x10), CodeAddress(0x20)));
+        assert!(log.is_branch_taken(CodeAddress(0x10), CodeAddress(0x14)));
+
+        assert!(CoverageLog::read("x 00000010\n".as_bytes()).is_err());
+    }
+}
@@ -1,5 +1,8 @@
 mod into_runtime_form;
 
+use std::collections::HashSet;
+
+use anyhow::{Context, Result};
 pub use into_runtime_form::*;
 use smallvec::SmallVec;
 use tracing::warn;
@@ -8,7 +11,9 @@ use crate::format::scenario::{
     instruction_elements::{
         CodeAddress, FromNumber, NumberSpec, Register, RegisterRepr, UntypedNumberSpec,
     },
-    instructions::{BinaryOperationType, Expression, ExpressionTerm, JumpCond, JumpCondType},
+    instructions::{
+        BinaryOperationType, Expression, ExpressionTerm, JumpCond, JumpCondType, UnaryOperationType,
+    },
 };
 
 /// Contains the full VM state
@@ -33,6 +38,37 @@ pub struct VmCtx {
     arguments_stack: Vec<SmallVec<i32, 6>>,
     /// PRNG state, updated on each instruction executed
     prng_state: u32,
+    /// Records the writes to the watched registers, only present when there are any (see [`VmCtx::watch_register`])
+    ///
+    /// It's not a part of the state, so it's not cloned.
+    observer: Option<Box<RegisterObserver>>,
+}
+
+impl Clone for VmCtx {
+    fn clone(&self) -> Self {
+        Self {
+            regular_registers: self.regular_registers,
+            call_stack: self.call_stack.clone(),
+            arguments_stack: self.arguments_stack.clone(),
+            prng_state: self.prng_state,
+            observer: None,
+        }
+    }
+}
+
+/// A write that changed the value of a watched register
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegisterWrite {
+    /// The index of the register in the main memory
+    pub index: u16,
+    pub old_value: i32,
+    pub new_value: i32,
+}
+
+#[derive(Default)]
+struct RegisterObserver {
+    watched: HashSet<u16>,
+    writes: Vec<RegisterWrite>,
 }
 
 #[inline]
@@ -59,6 +95,24 @@ fn unreal(v: f32) -> i32 {
     (v * 1000.0) as i32
 }
 
+/// Multiply two fixed-point numbers with 3 decimal digits, truncating the result towards zero
+#[inline]
+fn multiply_real(left: i32, right: i32) -> i32 {
+    (left as i64 * right as i64 / 1000) as i32
+}
+
+/// Divide two fixed-point numbers with 3 decimal digits, truncating the result towards zero
+///
+/// Division by zero gives zero, like the integer division
+#[inline]
+fn divide_real(left: i32, right: i32) -> i32 {
+    if right != 0 {
+        (left as i64 * 1000 / right as i64) as i32
+    } else {
+        0
+    }
+}
+
 #[inline]
 fn angle(v: i32) -> f32 {
     real(v) * std::f32::consts::PI * 2.0
@@ -79,25 +133,79 @@ impl VmCtx {
             call_stack: Vec::new(),
             arguments_stack: Vec::new(),
             prng_state: random_seed,
+            observer: None,
         }
     }
 
-    pub(super) fn get_prng_state(&self) -> u32 {
+    /// Re-create the context from the values returned by [`VmCtx::registers`], [`VmCtx::call_stack`], [`VmCtx::argument_frames`] and [`VmCtx::prng_state`]
+    pub fn from_parts(
+        registers: [i32; 0x1000],
+        call_stack: Vec<CodeAddress>,
+        argument_frames: Vec<SmallVec<i32, 6>>,
+        prng_state: u32,
+    ) -> Self {
+        Self {
+            regular_registers: registers,
+            call_stack,
+            arguments_stack: argument_frames,
+            prng_state,
+            observer: None,
+        }
+    }
+
+    /// Get the PRNG state, which determines the results of the following `rnd` instructions
+    pub fn prng_state(&self) -> u32 {
         self.prng_state
     }
 
+    /// Replace the PRNG state, e.g. to reproduce the random choices of a recorded run
+    pub fn set_prng_state(&mut self, state: u32) {
+        self.prng_state = state;
+    }
+
+    /// Get the main memory (mem1), the registers from `$v0` to `$v4095`
+    pub fn registers(&self) -> &[i32; 0x1000] {
+        &self.regular_registers
+    }
+
+    /// Get the call stack, the most recent entry being the last
+    ///
+    /// The entries are mostly the return addresses, but the values stored with [push](super::Instruction::push) end up here too
+    pub fn call_stack(&self) -> &[CodeAddress] {
+        &self.call_stack
+    }
+
+    /// Get the arguments of the active function calls (mem3), the innermost call being the last
+    pub fn argument_frames(&self) -> impl ExactSizeIterator<Item = &[i32]> + '_ {
+        self.arguments_stack.iter().map(|frame| frame.as_slice())
+    }
+
+    /// Empty the call and the data stacks, as if no function was called yet
+    pub fn clear_stacks(&mut self) {
+        self.call_stack.clear();
+        self.arguments_stack.clear();
+    }
+
     /// Get the value from memory
     ///
     /// The address can be a stack offset (mem3) or main memory address (mem1)
+    ///
+    /// Reading an argument that wasn't passed to the current function gives 0.
     #[inline]
     pub fn read_register(&self, register: Register) -> i32 {
         match register.repr() {
             RegisterRepr::Argument(index) => {
-                let frame = self
+                let value = self
                     .arguments_stack
                     .last()
-                    .expect("Attempt to read argument on empty stack");
-                frame[index as usize]
+                    .and_then(|frame| frame.get(index as usize));
+                match value {
+                    Some(&value) => value,
+                    None => {
+                        warn!("Attempt to read a missing argument {:?}", register);
+                        0
+                    }
+                }
             }
             RegisterRepr::Regular(index) => self.regular_registers[index as usize],
         }
@@ -106,20 +214,70 @@ impl VmCtx {
     /// Set a memory address to a value
     ///
     /// The address can be a stack offset (mem3) or main memory address (mem1)
+    ///
+    /// Writes to an argument that wasn't passed to the current function are ignored.
     #[inline]
     pub fn write_register(&mut self, register: Register, val: i32) {
         match register.repr() {
             RegisterRepr::Argument(index) => {
-                let frame = self
+                let value = self
                     .arguments_stack
                     .last_mut()
-                    .expect("Attempt to write argument on empty stack");
-                frame[index as usize] = val;
+                    .and_then(|frame| frame.get_mut(index as usize));
+                match value {
+                    Some(value) => *value = val,
+                    None => warn!("Attempt to write a missing argument {:?}", register),
+                }
+            }
+            RegisterRepr::Regular(index) => {
+                let old_value = std::mem::replace(&mut self.regular_registers[index as usize], val);
+                if let Some(observer) = &mut self.observer {
+                    if old_value != val && observer.watched.contains(&index) {
+                        observer.writes.push(RegisterWrite {
+                            index,
+                            old_value,
+                            new_value: val,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    /// Start recording the writes changing the main memory register at `index` (see [`VmCtx::take_register_writes`])
+    ///
+    /// Only the writes made by this context are recorded, the observer is not copied to the clones.
+    pub fn watch_register(&mut self, index: u16) {
+        self.observer
+            .get_or_insert_with(Default::default)
+            .watched
+            .insert(index);
+    }
+
+    /// Stop recording the writes to the register at `index`
+    pub fn unwatch_register(&mut self, index: u16) {
+        if let Some(observer) = &mut self.observer {
+            observer.watched.remove(&index);
+            if observer.watched.is_empty() && observer.writes.is_empty() {
+                // keep the write path fast when nothing is watched
+                self.observer = None;
             }
-            RegisterRepr::Regular(index) => self.regular_registers[index as usize] = val,
         }
     }
 
+    /// Take the recorded writes to the watched registers, in the order they were made
+    pub fn take_register_writes(&mut self) -> Vec<RegisterWrite> {
+        self.observer
+            .as_mut()
+            .map(|observer| std::mem::take(&mut observer.writes))
+            .unwrap_or_default()
+    }
+
+    /// Move the register watches to another context, like the one restored from a snapshot
+    pub(crate) fn move_observer_to(&mut self, other: &mut VmCtx) {
+        other.observer = self.observer.take();
+    }
+
     /// Read NumberSpec from memory (or return the constant value)
     #[inline]
     pub fn get_number<T: FromNumber>(&self, number: NumberSpec<T>) -> T {
@@ -140,7 +298,8 @@ impl VmCtx {
             JumpCondType::LessOrEqual => left <= right,
             JumpCondType::Less => left < right,
             JumpCondType::BitwiseAndNotZero => (left & right) != 0,
-            JumpCondType::BitSet => (left & (1 << (right % 32))) != 0,
+            // only the low 5 bits of the bit index are used, so it never overflows
+            JumpCondType::BitSet => (left & 1i32.wrapping_shl(right as u32)) != 0,
         };
 
         if cond.is_negated {
@@ -154,174 +313,198 @@ impl VmCtx {
         self.call_stack.push(addr);
     }
 
-    pub fn pop_code_stack(&mut self) -> CodeAddress {
-        self.call_stack.pop().unwrap()
+    pub fn pop_code_stack(&mut self) -> Result<CodeAddress> {
+        self.call_stack.pop().context("Call stack underflow")
+    }
+
+    /// Replace the addresses in the call stack, used when moving to another version of the scenario
+    pub(crate) fn relocate_code_stack(&mut self, mut f: impl FnMut(CodeAddress) -> CodeAddress) {
+        for address in &mut self.call_stack {
+            *address = f(*address);
+        }
     }
 
     pub fn push_data_stack_frame(&mut self, val: &[i32]) {
         self.arguments_stack.push(SmallVec::from_slice(val));
     }
 
-    pub fn pop_data_stack_frame(&mut self) {
-        self.arguments_stack.pop().unwrap();
+    pub fn pop_data_stack_frame(&mut self) -> Result<()> {
+        self.arguments_stack
+            .pop()
+            .context("Data stack underflow")
+            .map(drop)
     }
 
     /// Evaluate a RPN expression in this context
+    ///
+    /// All the arithmetic wraps around on overflow. Malformed expressions underflowing the stack read zeroes.
     pub fn evaluate_expression(&self, expr: &Expression) -> i32 {
+        fn pop(stack: &mut SmallVec<i32, 16>) -> i32 {
+            stack.pop().unwrap_or_else(|| {
+                warn!("Expression stack underflow");
+                0
+            })
+        }
+
         let mut stack = SmallVec::<i32, 16>::new();
         for term in expr.iter() {
             match term {
                 &ExpressionTerm::Push(v) => stack.push(self.get_number(v)),
                 ExpressionTerm::Add => {
-                    let right = stack.pop().unwrap();
-                    let left = stack.pop().unwrap();
-                    stack.push(left + right);
+                    let right = pop(&mut stack);
+                    let left = pop(&mut stack);
+                    stack.push(left.wrapping_add(right));
                 }
                 ExpressionTerm::Subtract => {
-                    let right = stack.pop().unwrap();
-                    let left = stack.pop().unwrap();
-                    stack.push(left - right);
+                    let right = pop(&mut stack);
+                    let left = pop(&mut stack);
+                    stack.push(left.wrapping_sub(right));
                 }
                 ExpressionTerm::Multiply => {
-                    let right = stack.pop().unwrap();
-                    let left = stack.pop().unwrap();
-                    stack.push(left * right);
+                    let right = pop(&mut stack);
+                    let left = pop(&mut stack);
+                    stack.push(left.wrapping_mul(right));
                 }
                 ExpressionTerm::Divide => {
-                    let right = stack.pop().unwrap();
-                    let left = stack.pop().unwrap();
-                    stack.push(if right != 0 { left / right } else { 0 });
+                    let right = pop(&mut stack);
+                    let left = pop(&mut stack);
+                    stack.push(if right != 0 {
+                        left.wrapping_div(right)
+                    } else {
+                        0
+                    });
                 }
                 ExpressionTerm::Modulo => {
-                    let right = stack.pop().unwrap();
-                    let left = stack.pop().unwrap();
-                    let div = if right != 0 { left / right } else { 0 };
-                    stack.push(left - div * right);
+                    let right = pop(&mut stack);
+                    let left = pop(&mut stack);
+                    let div = if right != 0 {
+                        left.wrapping_div(right)
+                    } else {
+                        0
+                    };
+                    stack.push(left.wrapping_sub(div.wrapping_mul(right)));
                 }
                 ExpressionTerm::ShiftLeft => {
-                    let right = stack.pop().unwrap();
-                    let left = stack.pop().unwrap();
-                    stack.push(left << right);
+                    let right = pop(&mut stack);
+                    let left = pop(&mut stack);
+                    stack.push(left.wrapping_shl(right as u32));
                 }
                 ExpressionTerm::ShiftRight => {
-                    let right = stack.pop().unwrap();
-                    let left = stack.pop().unwrap();
-                    stack.push(left >> right);
+                    let right = pop(&mut stack);
+                    let left = pop(&mut stack);
+                    stack.push(left.wrapping_shr(right as u32));
                 }
                 ExpressionTerm::BitwiseAnd => {
-                    let right = stack.pop().unwrap();
-                    let left = stack.pop().unwrap();
+                    let right = pop(&mut stack);
+                    let left = pop(&mut stack);
                     stack.push(left & right);
                 }
                 ExpressionTerm::BitwiseOr => {
-                    let right = stack.pop().unwrap();
-                    let left = stack.pop().unwrap();
+                    let right = pop(&mut stack);
+                    let left = pop(&mut stack);
                     stack.push(left | right);
                 }
                 ExpressionTerm::BitwiseXor => {
-                    let right = stack.pop().unwrap();
-                    let left = stack.pop().unwrap();
+                    let right = pop(&mut stack);
+                    let left = pop(&mut stack);
                     stack.push(left ^ right);
                 }
                 ExpressionTerm::Negate => {
-                    let val = stack.pop().unwrap();
-                    stack.push(-val);
+                    let val = pop(&mut stack);
+                    stack.push(val.wrapping_neg());
                 }
                 ExpressionTerm::BitwiseNot => {
-                    let val = stack.pop().unwrap();
+                    let val = pop(&mut stack);
                     stack.push(!val);
                 }
                 ExpressionTerm::Abs => {
-                    let val = stack.pop().unwrap();
-                    stack.push(val.abs());
+                    let val = pop(&mut stack);
+                    stack.push(val.wrapping_abs());
                 }
                 ExpressionTerm::CmpEqual => {
-                    let right = stack.pop().unwrap();
-                    let left = stack.pop().unwrap();
+                    let right = pop(&mut stack);
+                    let left = pop(&mut stack);
                     stack.push(unbool(left == right));
                 }
                 ExpressionTerm::CmpNotEqual => {
-                    let right = stack.pop().unwrap();
-                    let left = stack.pop().unwrap();
+                    let right = pop(&mut stack);
+                    let left = pop(&mut stack);
                     stack.push(unbool(left != right));
                 }
                 ExpressionTerm::CmpGreater => {
-                    let right = stack.pop().unwrap();
-                    let left = stack.pop().unwrap();
+                    let right = pop(&mut stack);
+                    let left = pop(&mut stack);
                     stack.push(unbool(left > right));
                 }
                 ExpressionTerm::CmpGreaterOrEqual => {
-                    let right = stack.pop().unwrap();
-                    let left = stack.pop().unwrap();
+                    let right = pop(&mut stack);
+                    let left = pop(&mut stack);
                     stack.push(unbool(left >= right));
                 }
                 ExpressionTerm::CmpLessOrEqual => {
-                    let right = stack.pop().unwrap();
-                    let left = stack.pop().unwrap();
+                    let right = pop(&mut stack);
+                    let left = pop(&mut stack);
                     stack.push(unbool(left <= right));
                 }
                 ExpressionTerm::CmpLess => {
-                    let right = stack.pop().unwrap();
-                    let left = stack.pop().unwrap();
+                    let right = pop(&mut stack);
+                    let left = pop(&mut stack);
                     stack.push(unbool(left < right));
                 }
                 ExpressionTerm::CmpZero => {
-                    let val = stack.pop().unwrap();
+                    let val = pop(&mut stack);
                     stack.push(unbool(val == 0));
                 }
                 ExpressionTerm::CmpNotZero => {
-                    let val = stack.pop().unwrap();
+                    let val = pop(&mut stack);
                     stack.push(unbool(val != 0));
                 }
                 ExpressionTerm::LogicalAnd => {
-                    let right = stack.pop().unwrap();
-                    let left = stack.pop().unwrap();
+                    let right = pop(&mut stack);
+                    let left = pop(&mut stack);
                     stack.push(unbool(bool(left) && bool(right)));
                 }
                 ExpressionTerm::LogicalOr => {
-                    let right = stack.pop().unwrap();
-                    let left = stack.pop().unwrap();
+                    let right = pop(&mut stack);
+                    let left = pop(&mut stack);
                     stack.push(unbool(bool(left) || bool(right)));
                 }
                 ExpressionTerm::Select => {
-                    let cond = stack.pop().unwrap();
-                    let true_val = stack.pop().unwrap();
-                    let false_val = stack.pop().unwrap();
+                    let cond = pop(&mut stack);
+                    let true_val = pop(&mut stack);
+                    let false_val = pop(&mut stack);
                     stack.push(if bool(cond) { true_val } else { false_val });
                 }
                 ExpressionTerm::MultiplyReal => {
-                    let right = stack.pop().unwrap();
-                    let left = stack.pop().unwrap();
-                    // TODO: figure out how negative values are handled
-                    assert!(left >= 0 && right >= 0); // not sure if this will behave correctly otherwise
-                    stack.push(left * right / 1000);
+                    let right = pop(&mut stack);
+                    let left = pop(&mut stack);
+                    stack.push(multiply_real(left, right));
                 }
                 ExpressionTerm::DivideReal => {
-                    let right = stack.pop().unwrap();
-                    let left = stack.pop().unwrap();
-                    assert!(left >= 0 && right >= 0); // not sure if this will behave correctly otherwise
-                    stack.push(left * 1000 / right);
+                    let right = pop(&mut stack);
+                    let left = pop(&mut stack);
+                    stack.push(divide_real(left, right));
                 }
                 ExpressionTerm::Sin => {
-                    let val = stack.pop().unwrap();
+                    let val = pop(&mut stack);
                     stack.push(unreal(angle(val).sin()));
                 }
                 ExpressionTerm::Cos => {
-                    let val = stack.pop().unwrap();
+                    let val = pop(&mut stack);
                     stack.push(unreal(angle(val).cos()));
                 }
                 ExpressionTerm::Tan => {
-                    let val = stack.pop().unwrap();
+                    let val = pop(&mut stack);
                     stack.push(unreal(angle(val).tan()));
                 }
                 ExpressionTerm::Min => {
-                    let right = stack.pop().unwrap();
-                    let left = stack.pop().unwrap();
+                    let right = pop(&mut stack);
+                    let left = pop(&mut stack);
                     stack.push(left.min(right));
                 }
                 ExpressionTerm::Max => {
-                    let right = stack.pop().unwrap();
-                    let left = stack.pop().unwrap();
+                    let right = pop(&mut stack);
+                    let left = pop(&mut stack);
                     stack.push(left.max(right));
                 }
             }
@@ -330,41 +513,54 @@ impl VmCtx {
             warn!("Expression did not evaluate to a single value");
         }
 
-        stack.pop().unwrap()
+        pop(&mut stack)
+    }
+
+    pub fn evaluate_unary_operation(&self, ty: UnaryOperationType, source: i32) -> i32 {
+        match ty {
+            UnaryOperationType::Zero => 0,
+            UnaryOperationType::Not16 => source ^ 0xffff,
+            UnaryOperationType::Negate => source.wrapping_neg(),
+            UnaryOperationType::Abs => source.wrapping_abs(),
+        }
     }
 
     pub fn evaluate_binary_operation(&self, ty: BinaryOperationType, left: i32, right: i32) -> i32 {
         match ty {
             BinaryOperationType::MovRight => right,
             BinaryOperationType::Zero => 0,
-            BinaryOperationType::Add => left + right,
-            BinaryOperationType::Subtract => left - right,
-            BinaryOperationType::Multiply => left * right,
+            BinaryOperationType::Add => left.wrapping_add(right),
+            BinaryOperationType::Subtract => left.wrapping_sub(right),
+            BinaryOperationType::Multiply => left.wrapping_mul(right),
             BinaryOperationType::Divide => {
                 if right != 0 {
-                    left / right
+                    left.wrapping_div(right)
                 } else {
                     0
                 }
             }
             BinaryOperationType::Modulo => {
-                let div = if right != 0 { left / right } else { 0 };
-                left - div * right
+                let div = if right != 0 {
+                    left.wrapping_div(right)
+                } else {
+                    0
+                };
+                left.wrapping_sub(div.wrapping_mul(right))
             }
             BinaryOperationType::BitwiseAnd => left & right,
             BinaryOperationType::BitwiseOr => left | right,
             BinaryOperationType::BitwiseXor => left ^ right,
-            BinaryOperationType::LeftShift => left << (right % 32),
-            BinaryOperationType::RightShift => left >> (right % 32),
-            BinaryOperationType::MultiplyReal => unreal(real(left) * real(right)),
-            BinaryOperationType::DivideReal => unreal(real(left) / real(right)),
+            // only the low 5 bits of the shift amounts and bit indices are used
+            BinaryOperationType::LeftShift => left.wrapping_shl(right as u32),
+            BinaryOperationType::RightShift => left.wrapping_shr(right as u32),
+            BinaryOperationType::MultiplyReal => multiply_real(left, right),
+            BinaryOperationType::DivideReal => divide_real(left, right),
             BinaryOperationType::ATan2 => unangle(f32::atan2(real(left), real(right))),
-            BinaryOperationType::SetBit => left | (1 << (right % 32)),
-            BinaryOperationType::ClearBit => left & !(1 << (right % 32)),
+            BinaryOperationType::SetBit => left | 1i32.wrapping_shl(right as u32),
+            BinaryOperationType::ClearBit => left & !1i32.wrapping_shl(right as u32),
             BinaryOperationType::ACursedOperation => {
                 // Defined as `ctz((0xffffffff << R) & L)`
-                let r = right % 32;
-                let l = left & (-1 << r);
+                let l = left & (-1i32).wrapping_shl(right as u32);
                 let l = if l == 0 { 32 } else { l };
                 let l = l.trailing_zeros();
                 l as i32
@@ -391,13 +587,192 @@ impl VmCtx {
         if a == b {
             a
         } else {
-            let useful_state = (state >> 8 & 0xffff) as i32;
-            let interval_size = (b - a).abs() + 1;
+            // computed in 64 bits, so that it doesn't overflow for huge intervals
+            let useful_state = (state >> 8 & 0xffff) as i64;
+            let interval_size = (b as i64 - a as i64).abs() + 1;
             let lower_bound = a.min(b);
 
             let amplitude = (useful_state * interval_size) >> 0x10;
 
-            lower_bound + amplitude
+            (lower_bound as i64 + amplitude) as i32
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::VmCtx;
+    use crate::format::scenario::{
+        instruction_elements::Register,
+        instructions::{BinaryOperationType, JumpCond, JumpCondType, UnaryOperationType},
+    };
+
+    #[test]
+    fn jump_conditions() {
+        use JumpCondType::*;
+
+        let ctx = VmCtx::new(0, 42);
+        #[rustfmt::skip]
+        let table = [
+            (Equal, 3, 3, true),
+            (Equal, 3, 4, false),
+            (NotEqual, 3, 4, true),
+            (NotEqual, 3, 3, false),
+            (GreaterOrEqual, 4, 3, true),
+            (GreaterOrEqual, 3, 3, true),
+            (GreaterOrEqual, -1, 3, false),
+            (Greater, 4, 3, true),
+            (Greater, 3, 3, false),
+            (LessOrEqual, 3, 3, true),
+            (LessOrEqual, 4, 3, false),
+            (Less, -1, 3, true),
+            (Less, 3, 3, false),
+            (BitwiseAndNotZero, 0b0110, 0b0010, true),
+            (BitwiseAndNotZero, 0b0110, 0b1001, false),
+            (BitSet, 0b0100, 2, true),
+            (BitSet, 0b0100, 1, false),
+            (BitSet, i32::MIN, 31, true),
+            // the bit index wraps around
+            (BitSet, 0b0100, 34, true),
+            (BitSet, 0b0001, -32, true),
+        ];
+
+        for (condition, left, right, expected) in table {
+            for is_negated in [false, true] {
+                let cond = JumpCond {
+                    is_negated,
+                    condition,
+                };
+                assert_eq!(
+                    ctx.compute_jump_condition(cond, left, right),
+                    expected != is_negated,
+                    "{:?} {} {}",
+                    cond,
+                    left,
+                    right
+                );
+            }
         }
     }
+
+    #[test]
+    fn prng_restore() {
+        let mut ctx = VmCtx::new(0, 42);
+        for _ in 0..10 {
+            ctx.update_prng();
+        }
+        let state = ctx.prng_state();
+
+        let run = |ctx: &mut VmCtx| {
+            (0..100)
+                .map(|_| {
+                    ctx.update_prng();
+                    ctx.run_prng(0, 1000)
+                })
+                .collect::<Vec<_>>()
+        };
+        let first = run(&mut ctx);
+        ctx.set_prng_state(state);
+        let second = run(&mut ctx);
+
+        assert_eq!(first, second);
+        assert_eq!(VmCtx::new(0, state).prng_state(), state);
+    }
+
+    #[test]
+    fn unary_operations() {
+        let ctx = VmCtx::new(0, 42);
+        let check = |ty, source, expected| {
+            assert_eq!(
+                ctx.evaluate_unary_operation(ty, source),
+                expected,
+                "{:?} {}",
+                ty,
+                source
+            );
+        };
+
+        check(UnaryOperationType::Zero, 123, 0);
+        check(UnaryOperationType::Not16, 0x1234, 0xedcb);
+        check(UnaryOperationType::Not16, -1, -1 ^ 0xffff);
+        check(UnaryOperationType::Negate, 5, -5);
+        check(UnaryOperationType::Negate, i32::MIN, i32::MIN);
+        check(UnaryOperationType::Abs, -7, 7);
+        check(UnaryOperationType::Abs, 7, 7);
+    }
+
+    #[test]
+    fn real_operations() {
+        let ctx = VmCtx::new(0, 42);
+        let check = |ty, left, right, expected| {
+            assert_eq!(
+                ctx.evaluate_binary_operation(ty, left, right),
+                expected,
+                "{:?} {} {}",
+                ty,
+                left,
+                right
+            );
+        };
+
+        check(BinaryOperationType::MultiplyReal, 1500, 1500, 2250);
+        check(BinaryOperationType::MultiplyReal, 333, 3000, 999);
+        check(BinaryOperationType::MultiplyReal, -1500, 1500, -2250);
+        // truncated towards zero
+        check(BinaryOperationType::MultiplyReal, -1, 1, 0);
+        // no intermediate overflow
+        check(
+            BinaryOperationType::MultiplyReal,
+            1_000_000,
+            1_000_000,
+            1_000_000_000,
+        );
+
+        check(BinaryOperationType::DivideReal, 1000, 3000, 333);
+        check(BinaryOperationType::DivideReal, -1000, 3000, -333);
+        check(BinaryOperationType::DivideReal, 2250, 1500, 1500);
+        check(BinaryOperationType::DivideReal, 1000, 0, 0);
+    }
+
+    #[test]
+    fn malformed_stack_usage() {
+        let mut ctx = VmCtx::new(0, 42);
+
+        assert!(ctx.pop_code_stack().is_err());
+        assert!(ctx.pop_data_stack_frame().is_err());
+
+        // arguments outside of a call read as zero and can't be written
+        let argument = Register::from_argument(1);
+        assert_eq!(ctx.read_register(argument), 0);
+        ctx.write_register(argument, 5);
+        assert_eq!(ctx.read_register(argument), 0);
+
+        ctx.push_data_stack_frame(&[7]);
+        assert_eq!(ctx.read_register(Register::from_argument(0)), 7);
+        assert_eq!(ctx.read_register(argument), 0);
+        assert!(ctx.pop_data_stack_frame().is_ok());
+    }
+
+    #[test]
+    fn wrapping_arithmetic() {
+        let ctx = VmCtx::new(0, 42);
+        let check = |ty, left, right, expected| {
+            assert_eq!(
+                ctx.evaluate_binary_operation(ty, left, right),
+                expected,
+                "{:?} {} {}",
+                ty,
+                left,
+                right
+            );
+        };
+
+        check(BinaryOperationType::Add, i32::MAX, 1, i32::MIN);
+        check(BinaryOperationType::Subtract, i32::MIN, 1, i32::MAX);
+        check(BinaryOperationType::Multiply, i32::MAX, 2, -2);
+        check(BinaryOperationType::Divide, i32::MIN, -1, i32::MIN);
+        check(BinaryOperationType::Modulo, i32::MIN, -1, 0);
+        check(BinaryOperationType::LeftShift, 1, 33, 2);
+        check(BinaryOperationType::RightShift, -8, 33, -4);
+    }
 }
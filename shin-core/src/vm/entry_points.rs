@@ -0,0 +1,133 @@
+//! Finds the places in the scenario code the VM can be started at, to jump to a scene directly when debugging
+//!
+//! The scenarios don't have any labels, so the entry points are taken from the code structure:
+//! - the scenario entrypoint
+//! - the targets of the jump tables, which is how the scenarios dispatch the episodes and the chapters
+//! - the starts of the functions (the `gosub`/`call` targets)
+//!
+//! Each entry point comes with the beginning of the first message found ahead of it (see [`lookahead`](crate::vm::lookahead)), to make it easier to tell the scenes apart.
+
+use std::collections::BTreeMap;
+
+use crate::{
+    format::scenario::{instruction_elements::CodeAddress, instructions::Instruction, Scenario},
+    vm::{command::CompiletimeCommand, lookahead::scan_ahead},
+};
+
+/// How far to look for the first message of an entry point
+const PREVIEW_INSTRUCTIONS: usize = 500;
+/// How many characters of the first message to keep
+const PREVIEW_LENGTH: usize = 40;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum EntryPointKind {
+    Entrypoint,
+    /// A target of the jump table at `table`, the `index`-th one
+    JumpTable {
+        table: CodeAddress,
+        index: usize,
+    },
+    /// A start of a function
+    Function,
+}
+
+#[derive(Debug, Clone)]
+pub struct EntryPoint {
+    pub address: CodeAddress,
+    pub kind: EntryPointKind,
+    /// The beginning of the first message shown after this entry point, if any was found
+    pub preview: Option<String>,
+}
+
+impl EntryPoint {
+    /// A short name for the entry point, like `jt@0x1234[5]`
+    pub fn name(&self) -> String {
+        match self.kind {
+            EntryPointKind::Entrypoint => "entrypoint".to_string(),
+            EntryPointKind::JumpTable { table, index } => format!("jt@{}[{}]", table, index),
+            EntryPointKind::Function => format!("fun@{}", self.address),
+        }
+    }
+}
+
+/// The text of the first message found ahead of `address`, shortened to [`PREVIEW_LENGTH`] characters
+fn message_preview(scenario: &Scenario, address: CodeAddress) -> Option<String> {
+    scan_ahead(scenario, address, PREVIEW_INSTRUCTIONS)
+        .commands
+        .into_iter()
+        .find_map(|command| match command.command {
+            CompiletimeCommand::MSGSET(msgset) => Some(msgset.text.0),
+            _ => None,
+        })
+        .map(|text| text.chars().take(PREVIEW_LENGTH).collect())
+}
+
+/// Lists the entry points of the scenario, sorted by their kinds and then by the addresses
+///
+/// An address reachable in several ways is only listed once, with the first kind in the order of [`EntryPointKind`].
+/// The code is read until the first instruction that can't be decoded, so the entry points in a broken scenario are still found.
+pub fn find_entry_points(scenario: &Scenario) -> Vec<EntryPoint> {
+    let mut kinds = BTreeMap::new();
+    kinds.insert(scenario.entrypoint_address(), EntryPointKind::Entrypoint);
+
+    let end = scenario.code_end();
+    let mut reader = scenario.instruction_reader(scenario.entrypoint_address());
+    while reader.position() < end {
+        let position = reader.position();
+        let Ok(instruction) = reader.read() else {
+            break;
+        };
+
+        match instruction {
+            Instruction::jt { table, .. } => {
+                for (index, &target) in table.0.iter().enumerate() {
+                    let kind = EntryPointKind::JumpTable {
+                        table: position,
+                        index,
+                    };
+                    kinds
+                        .entry(target)
+                        .and_modify(|k| *k = kind.min(*k))
+                        .or_insert(kind);
+                }
+            }
+            Instruction::gosub { target } | Instruction::call { target, .. } => {
+                kinds.entry(target).or_insert(EntryPointKind::Function);
+            }
+            _ => {}
+        }
+    }
+
+    let mut entry_points = kinds
+        .into_iter()
+        .map(|(address, kind)| EntryPoint {
+            address,
+            kind,
+            preview: message_preview(scenario, address),
+        })
+        .collect::<Vec<_>>();
+    entry_points.sort_by_key(|entry| (entry.kind, entry.address));
+    entry_points
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use super::*;
+
+    // the scenario from the `Scripter` example: a conditional jump, a DEBUGOUT command and an EXIT
+    const HELLO: &[u8] = b"SNR \xd8\x00\x00\x00\x00\x00\x00\x00\x06\x00\x00\x00\x13\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\xbc\x00\x00\x00X\x00\x00\x00`\x00\x00\x00h\x00\x00\x00p\x00\x00\x00x\x00\x00\x00\x80\x00\x00\x00\x88\x00\x00\x00\x90\x00\x00\x00\x94\x00\x00\x00\x98\x00\x00\x00\x9c\x00\x00\x00\xa4\x00\x00\x00\xa8\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00F\x02\xb0\x00\xc4\x00\x00\x00\xff\r\x00Hello world!\x00\x00\x00\x00\x00";
+
+    #[test]
+    fn test_entrypoint_only() {
+        let scenario = Scenario::new(Bytes::from_static(HELLO)).unwrap();
+        let entry_points = find_entry_points(&scenario);
+
+        assert_eq!(entry_points.len(), 1);
+        assert_eq!(entry_points[0].address, scenario.entrypoint_address());
+        assert_eq!(entry_points[0].kind, EntryPointKind::Entrypoint);
+        // DEBUGOUT is not a message
+        assert_eq!(entry_points[0].preview, None);
+    }
+}
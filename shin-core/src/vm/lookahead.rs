@@ -0,0 +1,319 @@
+//! Contains a static lookahead over the scenario code, finding the commands the VM is likely to execute soon
+//!
+//! The code is followed from the given address without executing it:
+//! - unconditional jumps and calls are followed (the returns go back to the call site)
+//! - conditional jumps are assumed not to be taken
+//! - the scan stops at the jump tables, at the returns from the routine it started in, and at the code it has already visited
+//!
+//! The result is only a guess, so it's suitable for hints like preloading the assets, but not for anything affecting the game state.
+
+use std::collections::HashMap;
+
+use crate::{
+    format::scenario::{instruction_elements::CodeAddress, instructions::Instruction, Scenario},
+    vm::command::CompiletimeCommand,
+};
+
+/// A command found ahead of the scan start
+#[derive(Debug)]
+pub struct LookaheadCommand {
+    pub address: CodeAddress,
+    /// How many instructions are (probably) executed before this one
+    pub distance: usize,
+    pub command: CompiletimeCommand,
+}
+
+/// The result of [`scan_ahead`]
+#[derive(Debug, Default)]
+pub struct Lookahead {
+    /// The commands found, in the order of the expected execution
+    pub commands: Vec<LookaheadCommand>,
+    /// Addresses of all the visited instructions, with their distances from the scan start
+    pub visited: HashMap<CodeAddress, usize>,
+}
+
+/// Follows the code starting at `start` for at most `max_instructions` instructions, collecting the commands
+pub fn scan_ahead(scenario: &Scenario, start: CodeAddress, max_instructions: usize) -> Lookahead {
+    let mut result = Lookahead::default();
+    let mut reader = scenario.instruction_reader(start);
+    let mut return_stack = Vec::new();
+
+    for distance in 0..max_instructions {
+        let address = reader.position();
+        if result.visited.insert(address, distance).is_some() {
+            break;
+        }
+        // the scenario might be cut off or the code might be data (if we guessed wrong), just stop
+        let Ok(instruction) = reader.read() else {
+            break;
+        };
+
+        let jump_to = match instruction {
+            Instruction::j { target } => Some(target),
+            Instruction::gosub { target } | Instruction::call { target, .. } => {
+                return_stack.push(reader.position());
+                Some(target)
+            }
+            Instruction::retsub {} | Instruction::r#return {} => match return_stack.pop() {
+                Some(target) => Some(target),
+                None => break,
+            },
+            Instruction::jt { .. } => break,
+            Instruction::Command(CompiletimeCommand::EXIT(exit)) if exit.arg1 == 0 => break,
+            Instruction::Command(command) => {
+                result.commands.push(LookaheadCommand {
+                    address,
+                    distance,
+                    command,
+                });
+                None
+            }
+            _ => None,
+        };
+
+        if let Some(target) = jump_to {
+            if reader.set_position(target).is_err() {
+                break;
+            }
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use binrw::BinWrite;
+    use bytes::Bytes;
+
+    use super::scan_ahead;
+    use crate::{
+        format::scenario::{
+            instruction_elements::{CodeAddress, NumberSpec, U8Bool, UntypedNumberSpec},
+            instructions::Instruction,
+            Scenario,
+        },
+        vm::command::{
+            compiletime::{EXIT, WAIT},
+            CompiletimeCommand,
+        },
+    };
+
+    /// Header and (empty) info tables of a scenario, the code follows right after
+    const SCENARIO_HEADER: &[u8] = b"SNR \xd8\x00\x00\x00\x00\x00\x00\x00\x06\x00\x00\x00\x13\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\xbc\x00\x00\x00X\x00\x00\x00`\x00\x00\x00h\x00\x00\x00p\x00\x00\x00x\x00\x00\x00\x80\x00\x00\x00\x88\x00\x00\x00\x90\x00\x00\x00\x94\x00\x00\x00\x98\x00\x00\x00\x9c\x00\x00\x00\xa4\x00\x00\x00\xa8\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00";
+
+    /// An instruction of a test scenario, the jump targets are the indices of the instructions
+    enum Op {
+        /// A command the lookahead should report
+        Command,
+        J(usize),
+        Gosub(usize),
+        Retsub,
+        Call(usize),
+        Return,
+        Jt(&'static [usize]),
+        Exit,
+    }
+
+    impl Op {
+        fn to_instruction(&self, resolve: &dyn Fn(usize) -> CodeAddress) -> Instruction {
+            match *self {
+                Op::Command => Instruction::Command(CompiletimeCommand::WAIT(WAIT {
+                    allow_interrupt: U8Bool(false),
+                    wait_amount: NumberSpec::new(UntypedNumberSpec::Constant(0)),
+                })),
+                Op::J(target) => Instruction::j {
+                    target: resolve(target),
+                },
+                Op::Gosub(target) => Instruction::gosub {
+                    target: resolve(target),
+                },
+                Op::Retsub => Instruction::retsub {},
+                Op::Call(target) => Instruction::call {
+                    target: resolve(target),
+                    args: [].into_iter().collect(),
+                },
+                Op::Return => Instruction::r#return {},
+                Op::Jt(targets) => Instruction::jt {
+                    index: NumberSpec::constant(0),
+                    table: targets.iter().map(|&target| resolve(target)).collect(),
+                },
+                Op::Exit => Instruction::Command(CompiletimeCommand::EXIT(EXIT {
+                    arg1: 0,
+                    arg2: NumberSpec::constant(0),
+                })),
+            }
+        }
+    }
+
+    fn encode(instructions: &[Instruction]) -> (Vec<u8>, Vec<CodeAddress>) {
+        let mut cursor = Cursor::new(Vec::new());
+        let mut positions = Vec::new();
+        for instruction in instructions {
+            positions.push(CodeAddress(
+                (SCENARIO_HEADER.len() as u64 + cursor.position()) as u32,
+            ));
+            instruction.write(&mut cursor).unwrap();
+        }
+        (cursor.into_inner(), positions)
+    }
+
+    /// Builds a scenario from the ops, returning it with the addresses of the ops
+    fn assemble(ops: &[Op]) -> (Scenario, Vec<CodeAddress>) {
+        let lower = |resolve: &dyn Fn(usize) -> CodeAddress| {
+            ops.iter()
+                .map(|op| op.to_instruction(resolve))
+                .collect::<Vec<_>>()
+        };
+
+        // the code addresses are always encoded with 4 bytes, so the layout doesn't depend on the values
+        let (_, positions) = encode(&lower(&|_| CodeAddress(0)));
+        let (code, _) = encode(&lower(&|index| positions[index]));
+
+        let mut data = SCENARIO_HEADER.to_vec();
+        data.extend_from_slice(&code);
+        let size = data.len() as u32;
+        data[4..8].copy_from_slice(&size.to_le_bytes());
+
+        (Scenario::new(Bytes::from(data)).unwrap(), positions)
+    }
+
+    #[test]
+    fn scan_ahead_cases() {
+        use Op::*;
+
+        struct Case {
+            name: &'static str,
+            ops: &'static [Op],
+            start: usize,
+            max_instructions: usize,
+            /// Indices of the commands found, in the order they are reported
+            expected: &'static [usize],
+        }
+
+        let cases = [
+            Case {
+                name: "straight code until the end",
+                ops: &[Command, Command, Command],
+                start: 0,
+                max_instructions: 100,
+                expected: &[0, 1, 2],
+            },
+            Case {
+                name: "instruction limit",
+                ops: &[Command, Command, Command],
+                start: 0,
+                max_instructions: 2,
+                expected: &[0, 1],
+            },
+            Case {
+                name: "unconditional jump",
+                ops: &[J(2), Command, Command],
+                start: 0,
+                max_instructions: 100,
+                expected: &[2],
+            },
+            Case {
+                name: "gosub returns to the call site",
+                ops: &[Gosub(3), Command, Exit, Command, Retsub],
+                start: 0,
+                max_instructions: 100,
+                expected: &[3, 1],
+            },
+            Case {
+                name: "call returns to the call site",
+                ops: &[Call(3), Command, Exit, Command, Return],
+                start: 0,
+                max_instructions: 100,
+                expected: &[3, 1],
+            },
+            Case {
+                name: "nested calls",
+                ops: &[
+                    Gosub(3),
+                    Command,
+                    Exit,
+                    Call(6),
+                    Command,
+                    Retsub,
+                    Command,
+                    Return,
+                ],
+                start: 0,
+                max_instructions: 100,
+                expected: &[6, 4, 1],
+            },
+            Case {
+                name: "return from the starting routine",
+                ops: &[Exit, Command, Retsub, Command],
+                start: 1,
+                max_instructions: 100,
+                expected: &[1],
+            },
+            Case {
+                name: "loop stops the scan",
+                ops: &[Command, Command, J(0), Command],
+                start: 0,
+                max_instructions: 100,
+                expected: &[0, 1],
+            },
+            Case {
+                name: "second call of a routine stops the scan",
+                ops: &[Gosub(4), Gosub(4), Command, Exit, Command, Retsub],
+                start: 0,
+                max_instructions: 100,
+                expected: &[4],
+            },
+            Case {
+                name: "jump table",
+                ops: &[Command, Jt(&[3, 4]), Command, Command, Command],
+                start: 0,
+                max_instructions: 100,
+                expected: &[0],
+            },
+            Case {
+                name: "exit",
+                ops: &[Command, Exit, Command],
+                start: 0,
+                max_instructions: 100,
+                expected: &[0],
+            },
+        ];
+
+        for case in cases {
+            let (scenario, positions) = assemble(case.ops);
+            let lookahead = scan_ahead(&scenario, positions[case.start], case.max_instructions);
+            let found = lookahead
+                .commands
+                .iter()
+                .map(|command| {
+                    positions
+                        .iter()
+                        .position(|&p| p == command.address)
+                        .unwrap()
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(found, case.expected, "{}", case.name);
+        }
+    }
+
+    #[test]
+    fn distances_count_the_followed_instructions() {
+        use Op::*;
+
+        let (scenario, positions) = assemble(&[Gosub(3), Command, Exit, Command, Retsub]);
+        let lookahead = scan_ahead(&scenario, positions[0], 100);
+
+        let distances = lookahead
+            .commands
+            .iter()
+            .map(|command| command.distance)
+            .collect::<Vec<_>>();
+        // gosub, command 3, retsub, command 1
+        assert_eq!(distances, [1, 3]);
+        assert_eq!(lookahead.visited[&positions[4]], 2);
+        assert_eq!(lookahead.visited[&positions[2]], 4);
+    }
+}
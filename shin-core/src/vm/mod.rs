@@ -18,29 +18,211 @@
 //!
 //! The [`Scripter`] struct is the main entry point for the VM. It reads a scenario, executes the instructions and returns commands for engine to execute.
 //!
+//! The game runs the VM with [`Scripter::run`], executing the returned commands over multiple frames.
+//! Tools that can handle the commands right away (like a dialogue previewer) can instead pass a [`CommandSink`] to [`Scripter::run_with`].
+//!
+//! # Debugging
+//!
+//! [`Scripter::step`] executes a single instruction, after which the state can be inspected: [`Scripter::position`] and [`Scripter::next_position`] give the program counter,
+//! and [`Scripter::ctx`] gives access to the registers and the stacks.
+//!
+//! Breakpoints ([`Scripter::add_breakpoint`]) count how many times an address was reached, and can also pause the VM before the instruction at it is executed (see [`BreakpointHandle::set_pause`]).
+//!
+//! None of the methods panic on malformed scenarios, the problems are reported as errors.
+//!
 
 pub mod breakpoint;
 pub mod command;
+pub mod coverage;
 mod ctx;
+pub mod entry_points;
+pub mod lookahead;
+pub mod relocate;
+
+use std::collections::BTreeMap;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 pub use ctx::*;
+use serde::{Deserialize, Serialize};
 use smallvec::SmallVec;
 use tracing::{instrument, trace};
 
 use crate::{
     format::scenario::{
         instruction_elements::CodeAddress,
-        instructions::{BinaryOperation, Instruction, UnaryOperation, UnaryOperationType},
+        instructions::{BinaryOperation, Instruction, UnaryOperation},
         InstructionReader, Scenario,
     },
     vm::{
         breakpoint::{BreakpointHandle, CodeBreakpointSet},
         command::{CommandResult, RuntimeCommand},
+        coverage::CoverageLog,
+        relocate::AddressMap,
     },
 };
 
-// TODO: add a listener trait that can be used to get notified of commands
+/// A copy of the [`Scripter`] state, allowing to resume the execution from the point it was taken at.
+///
+/// The snapshot includes the PRNG state, so the random choices after resuming are the same as they were the first time.
+/// Breakpoints and coverage are not part of the snapshot.
+///
+/// The snapshot can be serialized to be stored in the saves. The code addresses in it are only valid for the scenario it was taken in.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(into = "ScripterSnapshotRepr", try_from = "ScripterSnapshotRepr")]
+pub struct ScripterSnapshot {
+    ctx: VmCtx,
+    /// Address of the next instruction to read
+    resume_position: CodeAddress,
+    position: CodeAddress,
+}
+
+impl ScripterSnapshot {
+    /// Make a snapshot of a VM that starts at `address`, as if [`Scripter::new`] was jumped there with [`ScripterSnapshot::jump_to`]
+    ///
+    /// Useful to prepare the state to restore without a VM running the scenario.
+    pub fn new(init_val: i32, random_seed: u32, address: CodeAddress) -> Self {
+        Self {
+            ctx: VmCtx::new(init_val, random_seed),
+            resume_position: address,
+            position: address,
+        }
+    }
+
+    /// Get the position of the VM at the time the snapshot was taken (see [`Scripter::position`])
+    pub fn position(&self) -> CodeAddress {
+        self.position
+    }
+
+    /// Get the PRNG state at the time the snapshot was taken, restoring the snapshot restores it too
+    pub fn prng_state(&self) -> u32 {
+        self.ctx.prng_state()
+    }
+
+    /// Move the snapshot to another version of the scenario (see [`relocate`](crate::vm::relocate))
+    ///
+    /// The call stack entries that are not instruction addresses are kept as is, as they can be the values stored with [push](Instruction::push).
+    /// Returns `None` if the positions are not at instruction starts, meaning the map is not for the scenario the snapshot was taken in.
+    pub fn relocate(&self, map: &AddressMap) -> Option<Self> {
+        let mut ctx = self.ctx.clone();
+        ctx.relocate_code_stack(|address| map.map(address).unwrap_or(address));
+        Some(Self {
+            ctx,
+            resume_position: map.map(self.resume_position)?,
+            position: map.map(self.position)?,
+        })
+    }
+
+    /// Make the last executed instruction (usually the command the snapshot was taken after) run again after restoring
+    ///
+    /// This can be used to re-show a message in a different language. Note that the PRNG is advanced by the instruction again.
+    pub fn rewind(&mut self) {
+        self.resume_position = self.position;
+    }
+
+    /// Make the VM start over at `address` after restoring, with empty stacks
+    ///
+    /// The registers (holding the game flags) and the PRNG state are kept. This allows to jump to a scene directly (see [`entry_points`](crate::vm::entry_points)).
+    /// Restoring fails if the address is not in the scenario.
+    pub fn jump_to(&mut self, address: CodeAddress) {
+        self.ctx.clear_stacks();
+        self.resume_position = address;
+        self.position = address;
+    }
+}
+
+/// The serialized form of the [`ScripterSnapshot`]
+#[derive(Serialize, Deserialize)]
+struct ScripterSnapshotRepr {
+    /// Only the non-zero registers, most of them are unused
+    registers: BTreeMap<u16, i32>,
+    call_stack: Vec<u32>,
+    argument_frames: Vec<Vec<i32>>,
+    prng_state: u32,
+    resume_position: u32,
+    position: u32,
+}
+
+impl From<ScripterSnapshot> for ScripterSnapshotRepr {
+    fn from(snapshot: ScripterSnapshot) -> Self {
+        let ctx = &snapshot.ctx;
+        Self {
+            registers: ctx
+                .registers()
+                .iter()
+                .enumerate()
+                .filter(|&(_, &value)| value != 0)
+                .map(|(index, &value)| (index as u16, value))
+                .collect(),
+            call_stack: ctx.call_stack().iter().map(|address| address.0).collect(),
+            argument_frames: ctx.argument_frames().map(|frame| frame.to_vec()).collect(),
+            prng_state: ctx.prng_state(),
+            resume_position: snapshot.resume_position.0,
+            position: snapshot.position.0,
+        }
+    }
+}
+
+impl TryFrom<ScripterSnapshotRepr> for ScripterSnapshot {
+    type Error = String;
+
+    fn try_from(repr: ScripterSnapshotRepr) -> std::result::Result<Self, Self::Error> {
+        let mut registers = [0; 0x1000];
+        for (index, value) in repr.registers {
+            *registers
+                .get_mut(index as usize)
+                .ok_or_else(|| format!("Register index {} is out of range", index))? = value;
+        }
+
+        Ok(Self {
+            ctx: VmCtx::from_parts(
+                registers,
+                repr.call_stack.into_iter().map(CodeAddress).collect(),
+                repr.argument_frames
+                    .into_iter()
+                    .map(SmallVec::from_vec)
+                    .collect(),
+                repr.prng_state,
+            ),
+            resume_position: CodeAddress(repr.resume_position),
+            position: CodeAddress(repr.position),
+        })
+    }
+}
+
+/// What happened during a [`Scripter::step`]
+#[derive(Debug)]
+pub enum Step {
+    /// An instruction was executed
+    Executed,
+    /// A command was encountered, the engine should execute it and pass the result to the VM (see [`Scripter::set_command_result`])
+    Command(RuntimeCommand),
+    /// A pausing breakpoint was reached, nothing was executed
+    ///
+    /// The next step executes the instruction at the breakpoint instead of stopping again.
+    Breakpoint(CodeAddress),
+}
+
+/// Handles the commands issued by the VM immediately, see [`Scripter::run_with`]
+pub trait CommandSink {
+    /// Execute the command, returning its result, or `None` to stop the VM (for example, on [`EXIT`](command::runtime::EXIT))
+    fn handle(&mut self, command: RuntimeCommand) -> Option<CommandResult>;
+}
+
+impl<F: FnMut(RuntimeCommand) -> Option<CommandResult>> CommandSink for F {
+    fn handle(&mut self, command: RuntimeCommand) -> Option<CommandResult> {
+        self(command)
+    }
+}
+
+/// A [`CommandSink`] that finishes all the commands right away with the default results (see [`RuntimeCommand::execute_dummy`])
+pub struct DummyCommandSink;
+
+impl CommandSink for DummyCommandSink {
+    fn handle(&mut self, command: RuntimeCommand) -> Option<CommandResult> {
+        command.execute_dummy()
+    }
+}
+
 /// The scripter reads scenarios and issues commands.
 /// Those are usually handled by the Adv scene in the game (but you can do other stuff if you want to).
 ///
@@ -76,6 +258,9 @@ pub struct Scripter {
     instruction_reader: InstructionReader,
     position: CodeAddress,
     breakpoints: CodeBreakpointSet,
+    /// The address of the pausing breakpoint the VM has just stopped at, it's not stopped at again by the next step
+    paused_at: Option<CodeAddress>,
+    coverage: Option<CoverageLog>,
 }
 
 impl Scripter {
@@ -92,18 +277,22 @@ impl Scripter {
             instruction_reader: scenario.instruction_reader(scenario.entrypoint_address()),
             position: scenario.entrypoint_address(),
             breakpoints: CodeBreakpointSet::new(),
+            paused_at: None,
+            coverage: None,
         }
     }
 
     /// Execute one instruction
     /// pc is the program counter before the instruction was read
+    ///
+    /// Malformed scenarios (jumps out of bounds, stack underflows) produce an error instead of a panic.
     #[instrument(skip(self), level = "trace")]
     #[inline]
     fn run_instruction(
         &mut self,
         instruction: Instruction,
         pc: CodeAddress,
-    ) -> Option<RuntimeCommand> {
+    ) -> Result<Option<RuntimeCommand>> {
         self.ctx.update_prng();
         self.position = pc;
 
@@ -114,11 +303,7 @@ impl Scripter {
                 source,
             }) => {
                 let source = self.ctx.get_number(source);
-                let result = match ty {
-                    UnaryOperationType::Zero => 0,
-                    UnaryOperationType::Negate => -source,
-                    _ => todo!(),
-                };
+                let result = self.ctx.evaluate_unary_operation(ty, source);
 
                 trace!(?pc, ?ty, ?destination, ?source, ?result, "uo");
 
@@ -167,22 +352,22 @@ impl Scripter {
 
                 trace!(?pc, ?cond, ?left, ?right, ?target, "jc");
                 if cond {
-                    self.instruction_reader.set_position(target);
+                    self.instruction_reader.set_position(target)?;
                 }
             }
             Instruction::j { target } => {
                 trace!(?pc, ?target, "j");
-                self.instruction_reader.set_position(target);
+                self.instruction_reader.set_position(target)?;
             }
             Instruction::gosub { target } => {
                 trace!(?pc, ?target, "gosub");
                 self.ctx.push_code_stack(self.instruction_reader.position());
-                self.instruction_reader.set_position(target);
+                self.instruction_reader.set_position(target)?;
             }
             Instruction::retsub {} => {
-                let target = self.ctx.pop_code_stack();
+                let target = self.ctx.pop_code_stack()?;
                 trace!(?pc, ?target, "retsub");
-                self.instruction_reader.set_position(target);
+                self.instruction_reader.set_position(target)?;
             }
             Instruction::jt { index, table } => {
                 let index = self.ctx.get_number(index);
@@ -192,14 +377,14 @@ impl Scripter {
 
                 trace!(?pc, ?index, ?target, table_len = ?table.0.len(), "jt");
                 if let Some(target) = target {
-                    self.instruction_reader.set_position(target);
+                    self.instruction_reader.set_position(target)?;
                 }
             }
             Instruction::rnd { dest, min, max } => {
                 let min = self.ctx.get_number(min);
                 let max = self.ctx.get_number(max);
                 let result = self.ctx.run_prng(min, max);
-                trace!(?pc, ?dest, ?min, ?max, ?result, prng_state = ?self.ctx.get_prng_state(), "rnd");
+                trace!(?pc, ?dest, ?min, ?max, ?result, prng_state = ?self.ctx.prng_state(), "rnd");
                 self.ctx.write_register(dest, result);
             }
             Instruction::call { target, args } => {
@@ -212,7 +397,7 @@ impl Scripter {
 
                 self.ctx.push_code_stack(self.instruction_reader.position());
                 self.ctx.push_data_stack_frame(&args);
-                self.instruction_reader.set_position(target);
+                self.instruction_reader.set_position(target)?;
             }
             Instruction::push { values } => {
                 // unfortunately the game uses the call stack for both code addresses and sometimes data...
@@ -221,8 +406,14 @@ impl Scripter {
                 let values = values
                     .0
                     .into_iter()
-                    .map(|v| CodeAddress(self.ctx.get_number(v).try_into().unwrap()))
-                    .collect::<SmallVec<CodeAddress, 6>>();
+                    .map(|v| {
+                        let value = self.ctx.get_number(v);
+                        value
+                            .try_into()
+                            .map(CodeAddress)
+                            .with_context(|| format!("Pushing a negative value {}", value))
+                    })
+                    .collect::<Result<SmallVec<CodeAddress, 6>>>()?;
                 trace!(?pc, ?values, "push");
 
                 for value in values {
@@ -231,8 +422,14 @@ impl Scripter {
             }
             Instruction::pop { dest } => {
                 let values = (0..dest.0.len())
-                    .map(|_| self.ctx.pop_code_stack().0.try_into().unwrap())
-                    .collect::<SmallVec<i32, 6>>();
+                    .map(|_| {
+                        let value = self.ctx.pop_code_stack()?;
+                        value
+                            .0
+                            .try_into()
+                            .with_context(|| format!("Popping an out of range value {}", value))
+                    })
+                    .collect::<Result<SmallVec<i32, 6>>>()?;
                 trace!(?pc, ?values, "pop");
 
                 for (dest, value) in dest.0.iter().zip(values) {
@@ -240,53 +437,359 @@ impl Scripter {
                 }
             }
             Instruction::r#return {} => {
-                self.ctx.pop_data_stack_frame();
-                let target = self.ctx.pop_code_stack();
+                self.ctx.pop_data_stack_frame()?;
+                let target = self.ctx.pop_code_stack()?;
                 trace!(?pc, ?target, "return");
-                self.instruction_reader.set_position(target);
+                self.instruction_reader.set_position(target)?;
             }
             Instruction::Command(command) => {
                 let command = command.into_runtime_form(&self.ctx);
                 trace!(?pc, ?command, "command");
-                return Some(command);
+                return Ok(Some(command));
             }
         }
 
-        None
+        Ok(None)
     }
 
     /// Get the current position of the VM
     ///
-    /// This is the address of the next instruction to be executed
+    /// This is the address of the last executed instruction (the command returned by [`Scripter::run`] when called between the runs)
     #[inline]
     pub fn position(&self) -> CodeAddress {
         self.position
     }
 
-    /// Run the VM until a command is encountered
-    ///
-    /// You should pass the result of the previous command to this function (use `CommandResult::None` if the VM is just starting)
+    /// Get the address of the next instruction to be executed
     #[inline]
-    pub fn run(&mut self, prev_command_result: CommandResult) -> Result<RuntimeCommand> {
-        match prev_command_result {
+    pub fn next_position(&self) -> CodeAddress {
+        self.instruction_reader.position()
+    }
+
+    /// Pass the result of the command returned by the VM back to it
+    ///
+    /// [`Scripter::run`] does this by itself, this is needed only when driving the VM with [`Scripter::step`].
+    pub fn set_command_result(&mut self, result: CommandResult) {
+        match result {
             CommandResult::None => {}
             CommandResult::WriteMemory(addr, value) => {
                 self.ctx.write_register(addr, value);
             }
         }
+    }
+
+    fn step_impl(&mut self, honor_breakpoints: bool) -> Result<Step> {
+        let pc = self.instruction_reader.position();
+        let resuming = self.paused_at.take() == Some(pc);
+        if honor_breakpoints && !resuming && self.breakpoints.pauses_at(pc) {
+            self.paused_at = Some(pc);
+            return Ok(Step::Breakpoint(pc));
+        }
+
+        let instruction = self
+            .instruction_reader
+            .read()
+            .with_context(|| format!("Reading instruction at {}", pc))?;
+        self.breakpoints.visit_address(pc);
+
+        let is_branch = matches!(instruction, Instruction::jc { .. } | Instruction::jt { .. });
+        if let Some(coverage) = &mut self.coverage {
+            coverage.record_instruction(pc);
+        }
+
+        let command = self
+            .run_instruction(instruction, pc)
+            .with_context(|| format!("Executing instruction at {}", pc))?;
+
+        if let Some(coverage) = self.coverage.as_mut().filter(|_| is_branch) {
+            coverage.record_branch(pc, self.instruction_reader.position());
+        }
+
+        Ok(match command {
+            Some(command) => Step::Command(command),
+            None => Step::Executed,
+        })
+    }
+
+    /// Execute a single instruction, unless there is a pausing breakpoint at it
+    ///
+    /// When a command is returned, its result should be passed with [`Scripter::set_command_result`] before the next step.
+    pub fn step(&mut self) -> Result<Step> {
+        self.step_impl(true)
+    }
+
+    /// Run the VM until a command is encountered
+    ///
+    /// You should pass the result of the previous command to this function (use `CommandResult::None` if the VM is just starting)
+    ///
+    /// Pausing breakpoints are ignored, use [`Scripter::run_until_break`] to stop at them.
+    #[inline]
+    pub fn run(&mut self, prev_command_result: CommandResult) -> Result<RuntimeCommand> {
+        self.set_command_result(prev_command_result);
 
         loop {
-            let pc = self.instruction_reader.position();
-            let instruction = self.instruction_reader.read()?;
-            self.breakpoints.visit_address(pc);
-            if let Some(command) = self.run_instruction(instruction, pc) {
+            if let Step::Command(command) = self.step_impl(false)? {
                 return Ok(command);
             }
         }
     }
 
+    /// Same as [`Scripter::run`], but stops at the pausing breakpoints too
+    ///
+    /// Returns either [`Step::Command`] or [`Step::Breakpoint`]. When resuming after a breakpoint, pass `CommandResult::None`.
+    pub fn run_until_break(&mut self, prev_command_result: CommandResult) -> Result<Step> {
+        self.set_command_result(prev_command_result);
+
+        loop {
+            match self.step()? {
+                Step::Executed => {}
+                step => return Ok(step),
+            }
+        }
+    }
+
+    /// Run the VM, handling the commands with the `sink`, until it stops the VM or a pausing breakpoint is reached
+    ///
+    /// Returns the address of the breakpoint, or `None` if the sink has stopped the VM.
+    pub fn run_with(&mut self, sink: &mut impl CommandSink) -> Result<Option<CodeAddress>> {
+        let mut result = CommandResult::None;
+        loop {
+            match self.run_until_break(result)? {
+                Step::Command(command) => match sink.handle(command) {
+                    Some(command_result) => result = command_result,
+                    None => return Ok(None),
+                },
+                Step::Breakpoint(address) => return Ok(Some(address)),
+                Step::Executed => unreachable!(),
+            }
+        }
+    }
+
+    /// Take a snapshot of the VM state
+    ///
+    /// This is supposed to be called between the calls to [`Scripter::run`]. Resuming from the snapshot would continue right after the last command returned.
+    pub fn snapshot(&self) -> ScripterSnapshot {
+        ScripterSnapshot {
+            ctx: self.ctx.clone(),
+            resume_position: self.instruction_reader.position(),
+            position: self.position,
+        }
+    }
+
+    /// Restore the VM state from a snapshot
+    ///
+    /// The next [`Scripter::run`] call should be passed `CommandResult::None`, the result of the command the snapshot was taken after is not used.
+    ///
+    /// Fails without changing the state if the snapshot doesn't fit the scenario (it was taken from a different one).
+    pub fn restore(&mut self, snapshot: &ScripterSnapshot) -> Result<()> {
+        self.instruction_reader
+            .set_position(snapshot.resume_position)
+            .context("Snapshot taken from a different scenario")?;
+        let mut ctx = snapshot.ctx.clone();
+        self.ctx.move_observer_to(&mut ctx);
+        self.ctx = ctx;
+        self.position = snapshot.position;
+        self.paused_at = None;
+        Ok(())
+    }
+
+    /// Switch to another version of the scenario (see [`relocate`](crate::vm::relocate))
+    ///
+    /// The state has to be restored from a snapshot moved with [`ScripterSnapshot::relocate`] right after this.
+    /// The breakpoints and the coverage keep the addresses in the previous scenario.
+    pub fn set_scenario(&mut self, scenario: &Scenario) {
+        self.instruction_reader = scenario.instruction_reader(scenario.entrypoint_address());
+    }
+
+    /// Get the VM execution context, allowing to inspect the registers
+    pub fn ctx(&self) -> &VmCtx {
+        &self.ctx
+    }
+
+    /// Replace the PRNG state (see [`VmCtx::set_prng_state`])
+    pub fn set_prng_state(&mut self, state: u32) {
+        self.ctx.set_prng_state(state);
+    }
+
+    /// Start watching the main memory register at `index`, see [`Scripter::take_register_writes`]
+    ///
+    /// The watches survive restoring from snapshots.
+    pub fn watch_register(&mut self, index: u16) {
+        self.ctx.watch_register(index);
+    }
+
+    /// Stop watching the register at `index`
+    pub fn unwatch_register(&mut self, index: u16) {
+        self.ctx.unwatch_register(index);
+    }
+
+    /// Take the writes that changed the watched registers since the last call
+    ///
+    /// When stepping, the writes made by the last instruction (or by the last [`Scripter::set_command_result`]) are returned.
+    pub fn take_register_writes(&mut self) -> Vec<RegisterWrite> {
+        self.ctx.take_register_writes()
+    }
+
     /// Install a breakpoint at the given code address
+    ///
+    /// It only counts the hits, unless it's made to pause the VM with [`BreakpointHandle::set_pause`]
     pub fn add_breakpoint(&mut self, address: CodeAddress) -> BreakpointHandle {
         self.breakpoints.add_breakpoint(address)
     }
+
+    /// Start recording the executed instructions and branches (see [`coverage`](crate::vm::coverage))
+    pub fn enable_coverage(&mut self) {
+        self.coverage.get_or_insert_with(CoverageLog::new);
+    }
+
+    /// Get the coverage recorded so far, if enabled with [`Scripter::enable_coverage`]
+    pub fn coverage(&self) -> Option<&CoverageLog> {
+        self.coverage.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use super::*;
+
+    // the scenario from the `Scripter` example: a conditional jump, a DEBUGOUT command and an EXIT
+    const HELLO: &[u8] = b"SNR \xd8\x00\x00\x00\x00\x00\x00\x00\x06\x00\x00\x00\x13\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\xbc\x00\x00\x00X\x00\x00\x00`\x00\x00\x00h\x00\x00\x00p\x00\x00\x00x\x00\x00\x00\x80\x00\x00\x00\x88\x00\x00\x00\x90\x00\x00\x00\x94\x00\x00\x00\x98\x00\x00\x00\x9c\x00\x00\x00\xa4\x00\x00\x00\xa8\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00F\x02\xb0\x00\xc4\x00\x00\x00\xff\r\x00Hello world!\x00\x00\x00\x00\x00";
+
+    fn scenario() -> Scenario {
+        Scenario::new(Bytes::from_static(HELLO)).unwrap()
+    }
+
+    #[test]
+    fn test_run_with_sink() {
+        let scenario = scenario();
+        let mut scripter = Scripter::new(&scenario, 0, 42);
+
+        let mut commands = Vec::new();
+        let stopped_at = scripter
+            .run_with(&mut |command: RuntimeCommand| {
+                commands.push(command.name());
+                command.execute_dummy()
+            })
+            .unwrap();
+
+        assert_eq!(stopped_at, None);
+        assert_eq!(commands, ["DEBUGOUT", "EXIT"]);
+    }
+
+    #[test]
+    fn test_register_watch() {
+        let scenario = scenario();
+        let mut scripter = Scripter::new(&scenario, 0, 42);
+        scripter.watch_register(5);
+
+        scripter.set_command_result(CommandResult::WriteMemory("$v5".parse().unwrap(), 3));
+        scripter.set_command_result(CommandResult::WriteMemory("$v6".parse().unwrap(), 3));
+        // writing the same value is not a change
+        scripter.set_command_result(CommandResult::WriteMemory("$v5".parse().unwrap(), 3));
+        assert_eq!(
+            scripter.take_register_writes(),
+            [RegisterWrite {
+                index: 5,
+                old_value: 0,
+                new_value: 3
+            }]
+        );
+
+        // the watches are kept when restoring
+        let snapshot = scripter.snapshot();
+        scripter.restore(&snapshot).unwrap();
+        scripter.set_command_result(CommandResult::WriteMemory("$v5".parse().unwrap(), 4));
+        assert_eq!(scripter.take_register_writes().len(), 1);
+
+        scripter.unwatch_register(5);
+        scripter.set_command_result(CommandResult::WriteMemory("$v5".parse().unwrap(), 5));
+        assert!(scripter.take_register_writes().is_empty());
+    }
+
+    #[test]
+    fn test_snapshot_serialization() {
+        let scenario = scenario();
+        let mut scripter = Scripter::new(&scenario, 7, 42);
+        scripter.set_command_result(CommandResult::WriteMemory("$v5".parse().unwrap(), 3));
+        let snapshot = scripter.snapshot();
+
+        let json = serde_json::to_string(&snapshot).unwrap();
+        let decoded: ScripterSnapshot = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.position(), snapshot.position());
+        assert_eq!(decoded.prng_state(), 42);
+        assert_eq!(decoded.ctx.registers(), snapshot.ctx.registers());
+
+        let mut restored = Scripter::new(&scenario, 0, 0);
+        restored.restore(&decoded).unwrap();
+        assert_eq!(restored.ctx().registers()[0], 7);
+        assert_eq!(restored.ctx().registers()[5], 3);
+
+        assert!(serde_json::from_str::<ScripterSnapshot>(
+            r#"{"registers": {"4096": 1}, "call_stack": [], "argument_frames": [], "prng_state": 0, "resume_position": 0, "position": 0}"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_jump_to() {
+        let scenario = scenario();
+        let mut scripter = Scripter::new(&scenario, 0, 42);
+        scripter.run_with(&mut DummyCommandSink).unwrap();
+
+        let mut snapshot = scripter.snapshot();
+        snapshot.jump_to(scenario.entrypoint_address());
+        scripter.restore(&snapshot).unwrap();
+
+        let command = scripter.run(CommandResult::None).unwrap();
+        assert_eq!(command.name(), "DEBUGOUT");
+    }
+
+    #[test]
+    fn test_new_snapshot() {
+        let scenario = scenario();
+        let snapshot = ScripterSnapshot::new(7, 42, scenario.entrypoint_address());
+        assert_eq!(snapshot.position(), scenario.entrypoint_address());
+        assert_eq!(snapshot.prng_state(), 42);
+
+        let mut scripter = Scripter::new(&scenario, 0, 0);
+        scripter.restore(&snapshot).unwrap();
+        assert_eq!(scripter.ctx().registers()[0], 7);
+        let command = scripter.run(CommandResult::None).unwrap();
+        assert_eq!(command.name(), "DEBUGOUT");
+    }
+
+    #[test]
+    fn test_pausing_breakpoint() {
+        let scenario = scenario();
+        let entrypoint = scenario.entrypoint_address();
+        let mut scripter = Scripter::new(&scenario, 0, 42);
+
+        let breakpoint = scripter.add_breakpoint(entrypoint);
+        breakpoint.set_pause(true);
+
+        // the VM stops before the instruction, without executing it
+        assert!(matches!(scripter.step().unwrap(), Step::Breakpoint(a) if a == entrypoint));
+        assert_eq!(scripter.next_position(), entrypoint);
+        assert_eq!(breakpoint.hit_count(), 0);
+
+        // and executes it on the next step
+        assert!(matches!(scripter.step().unwrap(), Step::Executed));
+        assert_ne!(scripter.next_position(), entrypoint);
+        assert_eq!(breakpoint.hit_count(), 1);
+
+        assert_eq!(scripter.run_with(&mut DummyCommandSink).unwrap(), None);
+    }
+
+    #[test]
+    fn test_restore_foreign_snapshot() {
+        let scenario = scenario();
+        let mut scripter = Scripter::new(&scenario, 0, 42);
+        let mut snapshot = scripter.snapshot();
+        snapshot.resume_position = CodeAddress(0x10000);
+
+        assert!(scripter.restore(&snapshot).is_err());
+        assert_eq!(scripter.next_position(), scenario.entrypoint_address());
+    }
 }
@@ -0,0 +1,183 @@
+//! Contains the mapping of the code addresses between different versions of the same scenario
+//!
+//! The multi-language releases ship a scenario per language, compiled from the same source.
+//! They have the same instructions in the same order, but the strings have different lengths, so the code addresses don't match.
+//! [`AddressMap`] pairs the instructions of two such scenarios, which allows to move a VM snapshot from one to the other (see [`ScripterSnapshot::relocate`](super::ScripterSnapshot::relocate)).
+
+use std::collections::HashMap;
+
+use anyhow::{bail, Context, Result};
+
+use crate::format::scenario::{
+    instruction_elements::CodeAddress, instructions::Instruction, Scenario,
+};
+
+/// Checks that the instructions are the same apart from the operands, which can differ between the languages
+fn same_kind(from: &Instruction, to: &Instruction) -> bool {
+    match (from, to) {
+        (Instruction::Command(from), Instruction::Command(to)) => {
+            std::mem::discriminant(from) == std::mem::discriminant(to)
+        }
+        (from, to) => std::mem::discriminant(from) == std::mem::discriminant(to),
+    }
+}
+
+/// Lists the code addresses referenced by the instruction
+fn jump_targets(instruction: &Instruction) -> Vec<CodeAddress> {
+    match instruction {
+        Instruction::j { target }
+        | Instruction::jc { target, .. }
+        | Instruction::gosub { target }
+        | Instruction::call { target, .. } => vec![*target],
+        Instruction::jt { table, .. } => table.0.clone(),
+        _ => Vec::new(),
+    }
+}
+
+/// Maps the instruction addresses of one scenario to the addresses of the same instructions in another one
+#[derive(Debug)]
+pub struct AddressMap {
+    map: HashMap<CodeAddress, CodeAddress>,
+}
+
+impl AddressMap {
+    /// Pairs the instructions of the scenarios
+    ///
+    /// Fails if the scenarios don't have the same code structure: the instructions differ or the jumps go to different places.
+    pub fn new(from: &Scenario, to: &Scenario) -> Result<Self> {
+        let mut map = HashMap::new();
+        let mut jumps = Vec::new();
+
+        let (from_end, to_end) = (from.code_end(), to.code_end());
+        let mut from_reader = from.instruction_reader(from.entrypoint_address());
+        let mut to_reader = to.instruction_reader(to.entrypoint_address());
+        loop {
+            let (from_position, to_position) = (from_reader.position(), to_reader.position());
+            // the address after the last instruction is included too, the VM can be positioned there
+            map.insert(from_position, to_position);
+
+            match (from_position >= from_end, to_position >= to_end) {
+                (true, true) => break,
+                (false, false) => {}
+                _ => bail!(
+                    "The scenarios have different numbers of instructions (mismatch at {} and {})",
+                    from_position,
+                    to_position
+                ),
+            }
+
+            let from_instruction = from_reader
+                .read()
+                .with_context(|| format!("Reading instruction at {}", from_position))?;
+            let to_instruction = to_reader
+                .read()
+                .with_context(|| format!("Reading instruction at {}", to_position))?;
+            if !same_kind(&from_instruction, &to_instruction) {
+                bail!(
+                    "The scenarios have different instructions at {} and {}",
+                    from_position,
+                    to_position
+                );
+            }
+
+            let (from_targets, to_targets) = (
+                jump_targets(&from_instruction),
+                jump_targets(&to_instruction),
+            );
+            if from_targets.len() != to_targets.len() {
+                bail!(
+                    "The jump tables at {} and {} have different sizes",
+                    from_position,
+                    to_position
+                );
+            }
+            jumps.extend(
+                from_targets
+                    .into_iter()
+                    .zip(to_targets)
+                    .map(|targets| (from_position, targets)),
+            );
+        }
+
+        // the jumps can only be checked after all the addresses are known
+        for (position, (from_target, to_target)) in jumps {
+            if map.get(&from_target) != Some(&to_target) {
+                bail!(
+                    "The jump at {} goes to different places in the scenarios ({} and {})",
+                    position,
+                    from_target,
+                    to_target
+                );
+            }
+        }
+
+        Ok(Self { map })
+    }
+
+    /// Returns the address of the same instruction in the other scenario, `None` if the address is not at an instruction start
+    pub fn map(&self, address: CodeAddress) -> Option<CodeAddress> {
+        self.map.get(&address).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use super::*;
+
+    // the scenario from the `Scripter` example, showing a single message
+    const HELLO: &[u8] = b"SNR \xd8\x00\x00\x00\x00\x00\x00\x00\x06\x00\x00\x00\x13\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\xbc\x00\x00\x00X\x00\x00\x00`\x00\x00\x00h\x00\x00\x00p\x00\x00\x00x\x00\x00\x00\x80\x00\x00\x00\x88\x00\x00\x00\x90\x00\x00\x00\x94\x00\x00\x00\x98\x00\x00\x00\x9c\x00\x00\x00\xa4\x00\x00\x00\xa8\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00F\x02\xb0\x00\xc4\x00\x00\x00\xff\r\x00Hello world!\x00\x00\x00\x00\x00";
+    const HELLO_MESSAGE: &[u8] = b"\r\x00Hello world!\x00";
+
+    /// The same scenario with a shorter message, as if it was translated
+    fn translated() -> Vec<u8> {
+        let start = HELLO
+            .windows(HELLO_MESSAGE.len())
+            .position(|w| w == HELLO_MESSAGE)
+            .unwrap();
+        let mut data = HELLO[..start].to_vec();
+        data.extend_from_slice(b"\x04\x00Hi!\x00");
+        data.extend_from_slice(&HELLO[start + HELLO_MESSAGE.len()..]);
+
+        let size = data.len() as u32;
+        data[4..8].copy_from_slice(&size.to_le_bytes());
+        data
+    }
+
+    fn message_end(data: &[u8], message: &[u8]) -> CodeAddress {
+        let start = data
+            .windows(message.len())
+            .position(|w| w == message)
+            .unwrap();
+        CodeAddress((start + message.len()) as u32)
+    }
+
+    #[test]
+    fn test_identity() {
+        let scenario = Scenario::new(Bytes::from_static(HELLO)).unwrap();
+        let map = AddressMap::new(&scenario, &scenario).unwrap();
+
+        let entrypoint = scenario.entrypoint_address();
+        assert_eq!(map.map(entrypoint), Some(entrypoint));
+        assert_eq!(map.map(CodeAddress(entrypoint.0 + 1)), None);
+    }
+
+    #[test]
+    fn test_translated() {
+        let translated = translated();
+        let from = Scenario::new(Bytes::from_static(HELLO)).unwrap();
+        let to = Scenario::new(Bytes::from(translated.clone())).unwrap();
+        let map = AddressMap::new(&from, &to).unwrap();
+
+        assert_eq!(
+            map.map(from.entrypoint_address()),
+            Some(to.entrypoint_address())
+        );
+        // the address after the message is shifted by the difference in the lengths
+        assert_eq!(
+            map.map(message_end(HELLO, HELLO_MESSAGE)),
+            Some(message_end(&translated, b"\x04\x00Hi!\x00"))
+        );
+    }
+}
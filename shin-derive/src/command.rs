@@ -328,6 +328,15 @@ pub fn impl_command(input: Structure) -> TokenStream {
             }
         }
 
+        impl RuntimeCommand {
+            /// Get the name of the command, as used in the scenario
+            pub fn name(&self) -> &'static str {
+                match self {
+                    #(RuntimeCommand::#variant_names(_) => stringify!(#variant_names)),*
+                }
+            }
+        }
+
         impl std::fmt::Display for RuntimeCommand {
             fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
                 match self {
@@ -0,0 +1,591 @@
+//! Golden-image tests for the renderer.
+//!
+//! The scenes are rendered with a headless device (see [`shin_render::init::init_headless`]) and compared against the PNGs in the `golden` directory.
+//! A small per-channel difference is tolerated, as the results differ slightly between GPUs and drivers.
+//!
+//! The scenes are synthetic: the game assets can't be checked in, so they use generated images instead of the messagebox or bustup textures.
+//! The tests need a GPU adapter, so they are ignored by default: run them with `cargo test -p shin-render-snapshots -- --ignored`.
+//! They fail when there is no adapter, instead of passing without rendering anything.
+
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use glam::Mat4;
+use image::RgbaImage;
+use shin_render::{init::init_headless, GpuCommonResources, RenderTarget};
+
+/// Size of the rendered snapshots, kept small to keep the golden images small
+pub const SNAPSHOT_SIZE: (u32, u32) = (480, 270);
+
+/// Default per-channel tolerance used when comparing to the golden images
+pub const DEFAULT_TOLERANCE: u8 = 2;
+
+pub struct SnapshotRenderer {
+    resources: GpuCommonResources,
+    target: RenderTarget,
+}
+
+impl SnapshotRenderer {
+    /// Creates a renderer, failing if there is no GPU adapter to render with
+    pub fn new() -> Result<Self> {
+        let resources = pollster::block_on(init_headless(SNAPSHOT_SIZE))
+            .context("Creating a headless GPU device to render the snapshots")?;
+        let target = RenderTarget::new(&resources, SNAPSHOT_SIZE, Some("Snapshot RenderTarget"));
+
+        Ok(Self { resources, target })
+    }
+
+    pub fn resources(&self) -> &GpuCommonResources {
+        &self.resources
+    }
+
+    /// Begins a render pass into the cleared snapshot render target
+    pub fn begin_render_pass<'a>(
+        &'a self,
+        encoder: &'a mut wgpu::CommandEncoder,
+    ) -> wgpu::RenderPass<'a> {
+        self.target
+            .begin_srgb_render_pass(&self.resources, encoder, Some("Snapshot RenderPass"))
+    }
+
+    /// Projection matrix mapping the virtual screen to the whole snapshot
+    pub fn projection_matrix(&self) -> Mat4 {
+        self.target.projection_matrix()
+    }
+
+    /// Reads back the rendered snapshot, the render pass must be finished and submitted before calling this
+    pub fn read_pixels(&self) -> RgbaImage {
+        self.target.read_pixels(&self.resources)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ImageDifference {
+    pub differing_pixels: usize,
+    pub max_channel_difference: u8,
+}
+
+/// Compares two images, counting the pixels with any channel differing by more than `tolerance`
+///
+/// Returns `None` if the sizes don't match.
+pub fn compare_images(
+    expected: &RgbaImage,
+    actual: &RgbaImage,
+    tolerance: u8,
+) -> Option<ImageDifference> {
+    if expected.dimensions() != actual.dimensions() {
+        return None;
+    }
+
+    let mut result = ImageDifference {
+        differing_pixels: 0,
+        max_channel_difference: 0,
+    };
+    for (expected, actual) in expected.pixels().zip(actual.pixels()) {
+        let difference = expected
+            .0
+            .iter()
+            .zip(actual.0.iter())
+            .map(|(&e, &a)| e.abs_diff(a))
+            .max()
+            .unwrap();
+        result.max_channel_difference = result.max_channel_difference.max(difference);
+        if difference > tolerance {
+            result.differing_pixels += 1;
+        }
+    }
+
+    Some(result)
+}
+
+fn golden_path(name: &str, suffix: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("golden")
+        .join(format!("{}{}.png", name, suffix))
+}
+
+fn check_snapshot(name: &str, actual: &RgbaImage, tolerance: u8) -> Result<()> {
+    let path = golden_path(name, "");
+    let update = std::env::var_os("UPDATE_SNAPSHOTS").is_some();
+
+    if update {
+        actual
+            .save(&path)
+            .with_context(|| format!("Saving the golden image to {}", path.display()))?;
+        eprintln!("Recorded the golden image {}", path.display());
+        return Ok(());
+    }
+
+    let actual_path = golden_path(name, ".actual");
+    if !path.exists() {
+        actual
+            .save(&actual_path)
+            .with_context(|| format!("Saving the actual image to {}", actual_path.display()))?;
+        anyhow::bail!(
+            "Snapshot {} has no golden image {} (actual output saved to {})\nRerun with UPDATE_SNAPSHOTS=1 to record it",
+            name,
+            path.display(),
+            actual_path.display()
+        );
+    }
+
+    let expected = image::open(&path)
+        .with_context(|| format!("Loading the golden image {}", path.display()))?
+        .into_rgba8();
+
+    let difference = compare_images(&expected, actual, tolerance);
+    if difference.is_some_and(|d| d.differing_pixels == 0) {
+        return Ok(());
+    }
+
+    actual
+        .save(&actual_path)
+        .with_context(|| format!("Saving the actual image to {}", actual_path.display()))?;
+
+    match difference {
+        None => anyhow::bail!(
+            "Snapshot {} has size {:?}, but the golden image has size {:?} (actual output saved to {})",
+            name,
+            actual.dimensions(),
+            expected.dimensions(),
+            actual_path.display()
+        ),
+        Some(difference) => anyhow::bail!(
+            "Snapshot {} differs from the golden image in {} pixels (max channel difference {}, tolerance {}), actual output saved to {}\nRerun with UPDATE_SNAPSHOTS=1 if the change is intended",
+            name,
+            difference.differing_pixels,
+            difference.max_channel_difference,
+            tolerance,
+            actual_path.display()
+        ),
+    }
+}
+
+/// Compares the image with the golden image `golden/{name}.png`
+///
+/// A missing golden image is an error, it is only recorded (or re-recorded) when the `UPDATE_SNAPSHOTS` environment variable is set.
+#[track_caller]
+pub fn assert_snapshot(name: &str, actual: &RgbaImage, tolerance: u8) {
+    if let Err(e) = check_snapshot(name, actual, tolerance) {
+        panic!("{:#}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{vec2, vec3, vec4, Vec4};
+    use image::{GrayImage, Luma, Rgba, RgbaImage};
+    use shin_core::{time::Ticks, vm::command::types::LayerFragmentShader};
+    use shin_render::{
+        vertices::{ButtonVertex, TextVertex},
+        GpuTexture, NinePatch, SpriteVertexBuffer, VertexBuffer,
+    };
+
+    use super::{
+        assert_snapshot, compare_images, SnapshotRenderer, DEFAULT_TOLERANCE, SNAPSHOT_SIZE,
+    };
+
+    /// A colorful image with smooth gradients and hard edges
+    fn test_image() -> RgbaImage {
+        RgbaImage::from_fn(64, 36, |x, y| {
+            let r = (x * 255 / 63) as u8;
+            let g = (y * 255 / 35) as u8;
+            let b = if (x / 8 + y / 6) % 2 == 0 { 255 } else { 64 };
+            Rgba([r, g, b, 255])
+        })
+    }
+
+    /// A horizontal gradient mask, wiping from left to right
+    fn test_mask() -> GrayImage {
+        GrayImage::from_fn(64, 36, |x, _| Luma([(x * 255 / 63) as u8]))
+    }
+
+    /// A glyph atlas with four blocky glyphs, each in a 16x16 cell
+    fn test_glyphs() -> GrayImage {
+        GrayImage::from_fn(64, 16, |x, y| {
+            let (cell, x) = (x / 16, x % 16);
+            let inside = |from, to| (from..to).contains(&x) && (from..to).contains(&y);
+            let covered = match cell {
+                0 => inside(2, 14) && !inside(5, 11),
+                1 => (6..10).contains(&x) || (6..10).contains(&y),
+                2 => x.abs_diff(y) < 3,
+                _ => y % 5 < 2,
+            };
+            Luma([if covered { 255 } else { 0 }])
+        })
+    }
+
+    /// A character-like sprite: a head and a body on a transparent background
+    fn test_bustup() -> RgbaImage {
+        RgbaImage::from_fn(48, 64, |x, y| {
+            let (x, y) = (x as f32 + 0.5, y as f32 + 0.5);
+            let head = (x - 24.0).powi(2) + (y - 16.0).powi(2) < 100.0;
+            let body = y > 28.0 && (x - 24.0).abs() < (y - 28.0) * 0.5 + 8.0;
+            if head || body {
+                Rgba([(x * 5.0) as u8, 160, (y * 4.0) as u8, 255])
+            } else {
+                Rgba([0, 0, 0, 0])
+            }
+        })
+    }
+
+    #[test]
+    fn compare() {
+        let a = test_image();
+        let mut b = a.clone();
+        b.get_pixel_mut(3, 4).0[1] ^= 0x10;
+
+        let difference = compare_images(&a, &b, 0).unwrap();
+        assert_eq!(difference.differing_pixels, 1);
+        assert_eq!(difference.max_channel_difference, 0x10);
+        assert_eq!(compare_images(&a, &b, 0x10).unwrap().differing_pixels, 0);
+        assert_eq!(compare_images(&a, &RgbaImage::new(1, 1), 0), None);
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter"]
+    fn sprite() {
+        let renderer = SnapshotRenderer::new().unwrap();
+        let resources = renderer.resources();
+        let texture = GpuTexture::load(resources, &test_image(), Some("Test Image"));
+        let vertices = SpriteVertexBuffer::new_fullscreen(resources);
+
+        {
+            let mut encoder = resources.start_encoder();
+            let mut render_pass = renderer.begin_render_pass(&mut encoder);
+            resources.draw_sprite(
+                &mut render_pass,
+                vertices.vertex_source(),
+                &texture.bind_group,
+                renderer.projection_matrix(),
+            );
+        }
+        let image = renderer.read_pixels();
+
+        assert_snapshot("sprite", &image, DEFAULT_TOLERANCE);
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter"]
+    fn layer_mono() {
+        let renderer = SnapshotRenderer::new().unwrap();
+        let resources = renderer.resources();
+        let texture = GpuTexture::load(resources, &test_image(), Some("Test Image"));
+        let vertices = SpriteVertexBuffer::new_fullscreen(resources);
+
+        {
+            let mut encoder = resources.start_encoder();
+            let mut render_pass = renderer.begin_render_pass(&mut encoder);
+            resources.draw_layer(
+                &mut render_pass,
+                vertices.vertex_source(),
+                &texture.bind_group,
+                renderer.projection_matrix(),
+                LayerFragmentShader::Mono,
+                // sepia tone at full intensity
+                vec4(1.0, 0.8, 0.6, 1.0),
+            );
+        }
+        let image = renderer.read_pixels();
+
+        assert_snapshot("layer_mono", &image, DEFAULT_TOLERANCE);
+    }
+
+    fn srgb_to_linear(value: u8) -> f32 {
+        let value = value as f32 / 255.0;
+        if value <= 0.04045 {
+            value / 12.92
+        } else {
+            ((value + 0.055) / 1.055).powf(2.4)
+        }
+    }
+
+    fn linear_to_srgb(value: f32) -> u8 {
+        let value = value.clamp(0.0, 1.0);
+        let value = if value <= 0.0031308 {
+            value * 12.92
+        } else {
+            1.055 * value.powf(1.0 / 2.4) - 0.055
+        };
+        (value * 255.0).round() as u8
+    }
+
+    /// Renders each effect through the layer pipeline and compares it with [`LayerFragmentShader::evaluate`]
+    #[test]
+    #[ignore = "needs a GPU adapter"]
+    fn layer_matches_evaluate() {
+        let renderer = SnapshotRenderer::new().unwrap();
+        let resources = renderer.resources();
+        // the texels map to the pixels one to one, so the filtering doesn't change the colors
+        let (width, height) = SNAPSHOT_SIZE;
+        let source = RgbaImage::from_fn(width, height, |x, y| {
+            Rgba([
+                (x * 255 / (width - 1)) as u8,
+                (y * 255 / (height - 1)) as u8,
+                ((x + y) * 7 % 256) as u8,
+                255,
+            ])
+        });
+        let texture = GpuTexture::load(resources, &source, Some("Test Image"));
+        let vertices = SpriteVertexBuffer::new_fullscreen(resources);
+
+        let cases = [
+            (LayerFragmentShader::Default, vec4(0.3, 0.6, 0.9, 1.0)),
+            (LayerFragmentShader::Mono, vec4(1.0, 0.8, 0.6, 1.0)),
+            (LayerFragmentShader::Mono, vec4(1.0, 1.0, 1.0, 0.5)),
+            (LayerFragmentShader::Fill, vec4(1.0, 0.0, 0.0, 0.5)),
+            (LayerFragmentShader::Fill2, vec4(0.2, 0.5, 1.0, 0.5)),
+            (LayerFragmentShader::Negative, vec4(0.0, 0.0, 0.0, 1.0)),
+            (LayerFragmentShader::Negative, vec4(0.0, 0.0, 0.0, 0.25)),
+            (LayerFragmentShader::Gamma, vec4(0.5, 1.0, 2.2, 0.0)),
+        ];
+        for (fragment_shader, params) in cases {
+            {
+                let mut encoder = resources.start_encoder();
+                let mut render_pass = renderer.begin_render_pass(&mut encoder);
+                resources.draw_layer(
+                    &mut render_pass,
+                    vertices.vertex_source(),
+                    &texture.bind_group,
+                    renderer.projection_matrix(),
+                    fragment_shader,
+                    params,
+                );
+            }
+            let actual = renderer.read_pixels();
+
+            // the shader works with the linear colors, decoded from and encoded to sRGB by the textures
+            let expected = RgbaImage::from_fn(width, height, |x, y| {
+                let [r, g, b, a] = source.get_pixel(x, y).0;
+                let texel = Vec4::new(
+                    srgb_to_linear(r),
+                    srgb_to_linear(g),
+                    srgb_to_linear(b),
+                    a as f32 / 255.0,
+                );
+                let color = fragment_shader.evaluate(texel, params);
+                Rgba([
+                    linear_to_srgb(color.x),
+                    linear_to_srgb(color.y),
+                    linear_to_srgb(color.z),
+                    (color.w * 255.0).round() as u8,
+                ])
+            });
+
+            let difference = compare_images(&expected, &actual, DEFAULT_TOLERANCE).unwrap();
+            assert_eq!(
+                difference.differing_pixels, 0,
+                "{:?} {} differs from the CPU implementation (max channel difference {})",
+                fragment_shader, params, difference.max_channel_difference
+            );
+        }
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter"]
+    fn wiper_mask_mid_transition() {
+        let renderer = SnapshotRenderer::new().unwrap();
+        let resources = renderer.resources();
+        let old_texture = GpuTexture::load(
+            resources,
+            &RgbaImage::from_pixel(4, 4, Rgba([32, 64, 160, 255])),
+            Some("Old Image"),
+        );
+        let new_texture = GpuTexture::load(resources, &test_image(), Some("New Image"));
+        let mask = GpuTexture::load_mask(resources, &test_mask(), Some("Test Mask"));
+        let vertices = SpriteVertexBuffer::new_fullscreen(resources);
+
+        {
+            let mut encoder = resources.start_encoder();
+            let mut render_pass = renderer.begin_render_pass(&mut encoder);
+            let projection = renderer.projection_matrix();
+            resources.draw_sprite(
+                &mut render_pass,
+                vertices.vertex_source(),
+                &old_texture.bind_group,
+                projection,
+            );
+            // the mask stretched over the whole screen, halfway through a transition with the default fade width
+            resources.draw_wiper_mask(
+                &mut render_pass,
+                vertices.vertex_source(),
+                &new_texture.bind_group,
+                &mask.bind_group,
+                projection,
+                vec4(1.0, 1.0, 0.0, 0.0),
+                vec2(0.55, 0.45),
+            );
+        }
+        let image = renderer.read_pixels();
+
+        assert_snapshot("wiper_mask_mid_transition", &image, DEFAULT_TOLERANCE);
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter"]
+    fn window_nine_patch() {
+        let renderer = SnapshotRenderer::new().unwrap();
+        let resources = renderer.resources();
+        let texture = GpuTexture::load(resources, &test_image(), Some("Test Image"));
+        // a wide window and one smaller than its borders
+        let nine_patch = NinePatch::new(vec2(64.0, 36.0), (16.0, 12.0, 16.0, 12.0));
+        let mut vertices = nine_patch.vertices((-800.0, -400.0, 200.0, 300.0));
+        vertices.extend(nine_patch.vertices((400.0, -100.0, 420.0, 100.0)));
+        let vertices = VertexBuffer::new(resources, &vertices, Some("Window Vertices"));
+
+        {
+            let mut encoder = resources.start_encoder();
+            let mut render_pass = renderer.begin_render_pass(&mut encoder);
+            resources.draw_window(
+                &mut render_pass,
+                vertices.vertex_source(),
+                &texture.bind_group,
+                renderer.projection_matrix(),
+                vec4(1.0, 1.0, 1.0, 0.75),
+            );
+        }
+        let image = renderer.read_pixels();
+
+        assert_snapshot("window_nine_patch", &image, DEFAULT_TOLERANCE);
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter"]
+    fn button_half_flash() {
+        let renderer = SnapshotRenderer::new().unwrap();
+        let resources = renderer.resources();
+        let texture = GpuTexture::load(resources, &test_image(), Some("Test Image"));
+        // the left half of the texture is the normal look, the right half is the flashing one
+        let vertex = |x: f32, y: f32, u: f32, v: f32| ButtonVertex {
+            position: vec2(x, y),
+            texture_coordinate: vec2(u, v),
+            flash_texture_coordinate: vec2(u + 0.5, v),
+        };
+        let vertices = [
+            vertex(-600.0, -200.0, 0.0, 0.0),
+            vertex(600.0, -200.0, 0.5, 0.0),
+            vertex(-600.0, 200.0, 0.0, 1.0),
+            vertex(600.0, -200.0, 0.5, 0.0),
+            vertex(600.0, 200.0, 0.5, 1.0),
+            vertex(-600.0, 200.0, 0.0, 1.0),
+        ];
+        let vertices = VertexBuffer::new(resources, &vertices, Some("Button Vertices"));
+
+        {
+            let mut encoder = resources.start_encoder();
+            let mut render_pass = renderer.begin_render_pass(&mut encoder);
+            resources.draw_button(
+                &mut render_pass,
+                vertices.vertex_source(),
+                &texture.bind_group,
+                renderer.projection_matrix(),
+                vec4(1.0, 1.0, 1.0, 1.0),
+                0.5,
+            );
+        }
+        let image = renderer.read_pixels();
+
+        assert_snapshot("button_half_flash", &image, DEFAULT_TOLERANCE);
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter"]
+    fn messagebox() {
+        let renderer = SnapshotRenderer::new().unwrap();
+        let resources = renderer.resources();
+        let frame = GpuTexture::load(resources, &test_image(), Some("Messagebox Frame"));
+        let glyphs = GpuTexture::load_mask(resources, &test_glyphs(), Some("Glyph Atlas"));
+
+        let nine_patch = NinePatch::new(vec2(64.0, 36.0), (16.0, 12.0, 16.0, 12.0));
+        let window = VertexBuffer::new(
+            resources,
+            &nine_patch.vertices((-900.0, 200.0, 900.0, 520.0)),
+            Some("Messagebox Vertices"),
+        );
+
+        // a line of glyphs appearing one per tick, the last visible one is halfway through its fade-in
+        let glyph = |index: usize| {
+            let cell = (index % 4) as f32;
+            let origin = vec2(-820.0 + index as f32 * 72.0, 260.0);
+            let vertex = |x: f32, y: f32| TextVertex {
+                position: origin + vec2(x, y) * 64.0,
+                tex_position: vec2((cell + x) / 4.0, y),
+                color: vec3(1.0, 0.9, 0.6),
+                time: Ticks::from_f32(index as f32),
+                fade: 1.0,
+            };
+            [
+                vertex(0.0, 0.0),
+                vertex(1.0, 0.0),
+                vertex(0.0, 1.0),
+                vertex(1.0, 1.0),
+                vertex(0.0, 1.0),
+                vertex(1.0, 0.0),
+            ]
+        };
+        let text = (0..16).flat_map(glyph).collect::<Vec<_>>();
+        let text = VertexBuffer::new(resources, &text, Some("Text Vertices"));
+
+        {
+            let mut encoder = resources.start_encoder();
+            let mut render_pass = renderer.begin_render_pass(&mut encoder);
+            let projection = renderer.projection_matrix();
+            resources.draw_window(
+                &mut render_pass,
+                window.vertex_source(),
+                &frame.bind_group,
+                projection,
+                vec4(1.0, 1.0, 1.0, 0.75),
+            );
+            resources.draw_text(
+                &mut render_pass,
+                text.vertex_source(),
+                &glyphs.bind_group,
+                projection,
+                Ticks::from_f32(10.5),
+            );
+        }
+        let image = renderer.read_pixels();
+
+        assert_snapshot("messagebox", &image, DEFAULT_TOLERANCE);
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter"]
+    fn bustup_mono() {
+        let renderer = SnapshotRenderer::new().unwrap();
+        let resources = renderer.resources();
+        let background = GpuTexture::load(resources, &test_image(), Some("Background"));
+        let bustup = GpuTexture::load(resources, &test_bustup(), Some("Bustup"));
+        let background_vertices = SpriteVertexBuffer::new_fullscreen(resources);
+        let bustup_vertices = SpriteVertexBuffer::new(
+            resources,
+            (-360.0, -420.0, 360.0, 540.0),
+            vec4(1.0, 1.0, 1.0, 1.0),
+        );
+
+        {
+            let mut encoder = resources.start_encoder();
+            let mut render_pass = renderer.begin_render_pass(&mut encoder);
+            let projection = renderer.projection_matrix();
+            resources.draw_sprite(
+                &mut render_pass,
+                background_vertices.vertex_source(),
+                &background.bind_group,
+                projection,
+            );
+            // only the bustup is toned, the background shows through its transparent parts unchanged
+            resources.draw_layer(
+                &mut render_pass,
+                bustup_vertices.vertex_source(),
+                &bustup.bind_group,
+                projection,
+                LayerFragmentShader::Mono,
+                vec4(1.0, 0.8, 0.6, 1.0),
+            );
+        }
+        let image = renderer.read_pixels();
+
+        assert_snapshot("bustup_mono", &image, DEFAULT_TOLERANCE);
+    }
+}
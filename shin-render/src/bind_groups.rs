@@ -80,6 +80,10 @@ impl BindGroupLayouts {
     }
 }
 
+/// Bind group for a single texture with its sampler
+///
+/// It's created once together with the texture it refers to and reused for all the draws, so no bind groups are created per draw.
+/// Per-draw parameters are passed with push constants, so there are no uniform buffers to bind either.
 pub struct TextureBindGroup(pub wgpu::BindGroup);
 impl TextureBindGroup {
     pub fn new(
@@ -1,4 +1,4 @@
-use glam::{vec3, Mat4};
+use glam::{vec2, vec3, Mat4, Vec2};
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
@@ -9,27 +9,56 @@ struct CameraParams {
 pub const VIRTUAL_WIDTH: f32 = 1920.0;
 pub const VIRTUAL_HEIGHT: f32 = 1080.0;
 
+/// Render buffer can't be larger than this, as it's the texture size limit we request from the device
+const MAX_RENDER_BUFFER_WIDTH: f32 = 4096.0;
+
+/// How the 16:9 virtual screen is fitted into a window of a different aspect ratio
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum AspectMode {
+    /// Show the whole virtual screen, filling the rest of the window with black bars
+    #[default]
+    Letterbox,
+    /// Fill the whole window, cutting off the parts of the virtual screen that don't fit
+    Crop,
+}
+
 pub struct Camera {
     /// Projection matrix to draw onto the screen
     screen_projection_matrix: Mat4,
     render_buffer_size: (u32, u32),
+    render_scale: f32,
+    aspect_mode: AspectMode,
+    window_size: (u32, u32),
 }
 
 impl Camera {
     pub fn new(window_size: (u32, u32)) -> Self {
+        Self::with_render_scale(window_size, 1.0, AspectMode::Letterbox)
+    }
+
+    /// Creates a camera rendering at `render_scale` times the window resolution
+    ///
+    /// Values above 1.0 render the scene supersampled, which reduces aliasing when it's scaled down to a small window.
+    /// The render buffer is resolved to the window with linear filtering, so values above 2.0 would skip some of the rendered pixels.
+    pub fn with_render_scale(
+        window_size: (u32, u32),
+        render_scale: f32,
+        aspect_mode: AspectMode,
+    ) -> Self {
         let (window_width, window_height) = window_size;
 
         let w = window_width as f32 / VIRTUAL_WIDTH;
         let h = window_height as f32 / VIRTUAL_HEIGHT;
 
-        let scale = w.min(h);
-
-        let (viewport_width, viewport_height) = if w < h {
-            (VIRTUAL_WIDTH, VIRTUAL_HEIGHT * h / w)
-        } else {
-            (VIRTUAL_WIDTH * w / h, VIRTUAL_HEIGHT)
+        let scale = match aspect_mode {
+            AspectMode::Letterbox => w.min(h),
+            AspectMode::Crop => w.max(h),
         };
 
+        // the size of the window in the virtual pixels
+        let (viewport_width, viewport_height) =
+            (window_width as f32 / scale, window_height as f32 / scale);
+
         // It seems that we are basically one traslation away from matching the game output
         // TODO: figure out a proper way to move the coordinate space of smth
         // because this creates a strip of black pixels on the right and bottom
@@ -42,19 +71,51 @@ impl Camera {
         screen_projection.w_axis.w = 1.0;
         let screen_projection = screen_projection * translation;
 
+        let buffer_scale = (scale * render_scale).min(MAX_RENDER_BUFFER_WIDTH / VIRTUAL_WIDTH);
+        // a minimized window has a zero size, but the render buffer can't be empty
         let render_buffer_size = (
-            (VIRTUAL_WIDTH * scale) as u32,
-            (VIRTUAL_HEIGHT * scale) as u32,
+            ((VIRTUAL_WIDTH * buffer_scale) as u32).max(1),
+            ((VIRTUAL_HEIGHT * buffer_scale) as u32).max(1),
         );
 
         Self {
             screen_projection_matrix: screen_projection,
             render_buffer_size,
+            render_scale,
+            aspect_mode,
+            window_size,
         }
     }
 
     pub fn resize(&mut self, size: (u32, u32)) {
-        *self = Self::new(size);
+        *self = Self::with_render_scale(size, self.render_scale, self.aspect_mode);
+    }
+
+    /// Changes the render scale, keeping the window size and the aspect mode
+    pub fn set_render_scale(&mut self, render_scale: f32) {
+        *self = Self::with_render_scale(self.window_size, render_scale, self.aspect_mode);
+    }
+
+    pub fn render_scale(&self) -> f32 {
+        self.render_scale
+    }
+
+    pub fn aspect_mode(&self) -> AspectMode {
+        self.aspect_mode
+    }
+
+    /// Converts a position in the window (in physical pixels, like the cursor position) to the virtual screen coordinates
+    ///
+    /// The result is outside of the virtual screen when the position is on the black bars.
+    pub fn window_to_virtual(&self, position: Vec2) -> Vec2 {
+        let (window_width, window_height) = self.window_size;
+        let ndc = vec3(
+            position.x / window_width as f32 * 2.0 - 1.0,
+            1.0 - position.y / window_height as f32 * 2.0,
+            0.0,
+        );
+        let position = self.screen_projection_matrix.inverse().project_point3(ndc);
+        vec2(position.x, position.y)
     }
 
     pub fn render_buffer_size(&self) -> (u32, u32) {
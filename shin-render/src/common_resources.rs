@@ -1,12 +1,13 @@
 use std::sync::RwLock;
 
 use glam::{Mat4, Vec2, Vec4};
-use shin_core::time::Ticks;
+use shin_core::{time::Ticks, vm::command::types::LayerFragmentShader};
 
 use crate::{
     pipelines::Pipelines,
-    vertices::{PosColTexVertex, PosVertex, TextVertex, VertexSource},
-    BindGroupLayouts, SubmittingEncoder, TextureBindGroup, YuvTextureBindGroup,
+    vertices::{ButtonVertex, PosColTexVertex, PosVertex, TextVertex, VertexSource, WindowVertex},
+    BindGroupLayouts, GpuProfiler, PooledRenderTarget, RenderTargetPool, SamplerStore,
+    SubmittingEncoder, TextureBindGroup, YuvTextureBindGroup,
 };
 
 pub struct GpuCommonResources {
@@ -17,6 +18,9 @@ pub struct GpuCommonResources {
     pub render_buffer_size: RwLock<(u32, u32)>,
     pub pipelines: Pipelines,
     pub bind_group_layouts: BindGroupLayouts,
+    pub sampler_store: SamplerStore,
+    pub render_target_pool: RenderTargetPool,
+    pub gpu_profiler: GpuProfiler,
 }
 
 impl GpuCommonResources {
@@ -56,6 +60,76 @@ impl GpuCommonResources {
             .draw(render_pass, source, texture, transform);
     }
 
+    /// Draws a movie frame with the alpha mask packed into the bottom half of the frame
+    pub fn draw_yuva_sprite<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        source: VertexSource<'a, PosColTexVertex>,
+        texture: &'a YuvTextureBindGroup,
+        transform: Mat4,
+    ) {
+        self.pipelines
+            .yuva_sprite
+            .draw(render_pass, source, texture, transform);
+    }
+
+    /// Draws a composited layer, applying the color effect
+    pub fn draw_layer<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        source: VertexSource<'a, PosColTexVertex>,
+        texture: &'a TextureBindGroup,
+        transform: Mat4,
+        fragment_shader: LayerFragmentShader,
+        shader_params: Vec4,
+    ) {
+        self.pipelines.layer.draw(
+            render_pass,
+            source,
+            texture,
+            transform,
+            fragment_shader,
+            shader_params,
+        );
+    }
+
+    /// Draws a sprite with a uniform alpha multiplier, used for crossfades
+    pub fn draw_wiper_default<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        source: VertexSource<'a, PosColTexVertex>,
+        texture: &'a TextureBindGroup,
+        transform: Mat4,
+        alpha: f32,
+    ) {
+        self.pipelines
+            .wiper_default
+            .draw(render_pass, source, texture, transform, alpha);
+    }
+
+    /// Draws a sprite with the alpha determined by a mask texture, used for mask wipes
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_wiper_mask<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        source: VertexSource<'a, PosColTexVertex>,
+        texture: &'a TextureBindGroup,
+        mask: &'a TextureBindGroup,
+        transform: Mat4,
+        mask_transform: Vec4,
+        minmax: Vec2,
+    ) {
+        self.pipelines.wiper_mask.draw(
+            render_pass,
+            source,
+            texture,
+            mask,
+            transform,
+            mask_transform,
+            minmax,
+        );
+    }
+
     #[allow(unused)]
     pub fn draw_fill<'a>(
         &'a self,
@@ -96,7 +170,42 @@ impl GpuCommonResources {
             .draw(render_pass, source, texture, transform, time, distance);
     }
 
+    /// Draws a menu window, tinted with `color`
+    pub fn draw_window<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        source: VertexSource<'a, WindowVertex>,
+        texture: &'a TextureBindGroup,
+        transform: Mat4,
+        color: Vec4,
+    ) {
+        self.pipelines
+            .window
+            .draw(render_pass, source, texture, transform, color);
+    }
+
+    /// Draws a menu button, `flash` is the intensity of the flashing look from 0 to 1
+    pub fn draw_button<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        source: VertexSource<'a, ButtonVertex>,
+        texture: &'a TextureBindGroup,
+        transform: Mat4,
+        color: Vec4,
+        flash: f32,
+    ) {
+        self.pipelines
+            .button
+            .draw(render_pass, source, texture, transform, color, flash);
+    }
+
     pub fn current_render_buffer_size(&self) -> (u32, u32) {
         *self.render_buffer_size.read().unwrap()
     }
+
+    /// Acquires a render target of the current render buffer size from the pool
+    pub fn acquire_render_target(&self, label: Option<&str>) -> PooledRenderTarget {
+        self.render_target_pool
+            .acquire(self, self.current_render_buffer_size(), label)
+    }
 }
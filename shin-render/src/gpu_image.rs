@@ -1,12 +1,17 @@
-use std::borrow::Cow;
+use std::{
+    borrow::Cow,
+    sync::atomic::{AtomicU64, Ordering},
+};
 
 use glam::{vec4, Vec2};
-use image::RgbaImage;
+use image::{GrayImage, RgbaImage};
 use once_cell::sync::OnceCell;
+use shin_core::format::picture::{bleed_transparent_colors, convert_alpha, AlphaMode};
 
 use crate::{
     vertices::{PosColTexVertex, VertexSource},
-    GpuCommonResources, SpriteVertexBuffer, TextureBindGroup, SRGB_TEXTURE_FORMAT,
+    GpuCommonResources, SpriteVertexBuffer, TextureBindGroup, MASK_TEXTURE_FORMAT,
+    SRGB_TEXTURE_FORMAT,
 };
 
 pub struct LazyGpuImage {
@@ -54,6 +59,28 @@ impl LazyGpuTexture {
     }
 }
 
+/// Same as [`LazyGpuTexture`], but for single-channel mask textures
+pub struct LazyGpuMaskTexture {
+    image: GrayImage,
+    label: Option<String>,
+    gpu_texture: OnceCell<GpuTexture>,
+}
+
+impl LazyGpuMaskTexture {
+    pub fn new(image: GrayImage, label: Option<&str>) -> Self {
+        Self {
+            image,
+            label: label.map(|s| s.to_owned()),
+            gpu_texture: OnceCell::new(),
+        }
+    }
+
+    pub fn gpu_texture(&self, resources: &GpuCommonResources) -> &GpuTexture {
+        self.gpu_texture
+            .get_or_init(|| GpuTexture::load_mask(resources, &self.image, self.label.as_deref()))
+    }
+}
+
 /// Gpu picture, ready to be drawn
 /// Includes a texture, a sampler, a bind group, and a vertex buffer
 pub struct GpuImage {
@@ -103,6 +130,20 @@ impl GpuImage {
     }
 }
 
+static TEXTURES_CREATED: AtomicU64 = AtomicU64::new(0);
+static LIVE_TEXTURES: AtomicU64 = AtomicU64::new(0);
+static LIVE_TEXTURE_BYTES: AtomicU64 = AtomicU64::new(0);
+
+/// Counters of the [`GpuTexture`]s, shared by all the devices
+#[derive(Debug, Default, Copy, Clone)]
+pub struct GpuTextureStats {
+    /// Number of textures created since the start
+    pub created: u64,
+    pub live: u64,
+    /// Size of the pixel data of the live textures
+    pub live_bytes: u64,
+}
+
 /// Gpu texture
 /// Includes a texture, a sampler, and a bind group (no vertex buffer)
 pub struct GpuTexture {
@@ -111,33 +152,85 @@ pub struct GpuTexture {
     pub bind_group: TextureBindGroup,
     pub width: u32,
     pub height: u32,
+    size_in_bytes: u64,
 }
 
 impl GpuTexture {
+    /// Loads an image with straight alpha, as produced by the decoders
     pub fn load(resources: &GpuCommonResources, image: &RgbaImage, label: Option<&str>) -> Self {
+        Self::load_with_alpha_mode(resources, image, AlphaMode::Straight, label)
+    }
+
+    /// Loads an image, converting it to the straight alpha used by all the pipelines
+    ///
+    /// The colors are also bled into the fully transparent pixels, so that the linear filtering doesn't produce dark fringes around the edges.
+    pub fn load_with_alpha_mode(
+        resources: &GpuCommonResources,
+        image: &RgbaImage,
+        alpha_mode: AlphaMode,
+        label: Option<&str>,
+    ) -> Self {
+        assert_eq!(
+            SRGB_TEXTURE_FORMAT,
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+            "Only Rgba8UnormSrgb is supported for now"
+        );
+
+        let mut image = image.clone();
+        convert_alpha(&mut image, alpha_mode, AlphaMode::Straight);
+        bleed_transparent_colors(&mut image);
+
+        Self::load_raw(
+            resources,
+            &image,
+            (image.width(), image.height()),
+            SRGB_TEXTURE_FORMAT,
+            4,
+            label,
+        )
+    }
+
+    /// Loads a grayscale image as a single-channel linear texture, to be used as a mask
+    pub fn load_mask(
+        resources: &GpuCommonResources,
+        image: &GrayImage,
+        label: Option<&str>,
+    ) -> Self {
+        Self::load_raw(
+            resources,
+            image,
+            (image.width(), image.height()),
+            MASK_TEXTURE_FORMAT,
+            1,
+            label,
+        )
+    }
+
+    fn load_raw(
+        resources: &GpuCommonResources,
+        data: &[u8],
+        (width, height): (u32, u32),
+        format: wgpu::TextureFormat,
+        bytes_per_pixel: u32,
+        label: Option<&str>,
+    ) -> Self {
         let label = label
             .map(|s| Cow::from(s.to_owned()))
             .unwrap_or_else(|| Cow::from("Unnamed GpuTexture"));
 
         let size = wgpu::Extent3d {
-            width: image.width(),
-            height: image.height(),
+            width,
+            height,
             depth_or_array_layers: 1,
         };
 
-        assert_eq!(
-            SRGB_TEXTURE_FORMAT,
-            wgpu::TextureFormat::Rgba8UnormSrgb,
-            "Only Rgba8UnormSrgb is supported for now"
-        );
-
         let texture = resources.device.create_texture(&wgpu::TextureDescriptor {
             label: Some(&format!("{} Texture", label)),
             size,
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: SRGB_TEXTURE_FORMAT,
+            format,
             usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
             view_formats: &[],
         });
@@ -149,17 +242,13 @@ impl GpuTexture {
                 origin: Default::default(),
                 aspect: wgpu::TextureAspect::All,
             },
-            image,
+            data,
             wgpu::ImageDataLayout {
                 offset: 0,
-                bytes_per_row: Some(4 * image.width()),
-                rows_per_image: Some(image.height()),
-            },
-            wgpu::Extent3d {
-                width: image.width(),
-                height: image.height(),
-                depth_or_array_layers: 1,
+                bytes_per_row: Some(bytes_per_pixel * width),
+                rows_per_image: Some(height),
             },
+            size,
         );
 
         let sampler = resources.device.create_sampler(&wgpu::SamplerDescriptor {
@@ -182,16 +271,41 @@ impl GpuTexture {
             Some(&format!("{} BindGroup", label)),
         );
 
+        let size_in_bytes = bytes_per_pixel as u64 * width as u64 * height as u64;
+        TEXTURES_CREATED.fetch_add(1, Ordering::Relaxed);
+        LIVE_TEXTURES.fetch_add(1, Ordering::Relaxed);
+        LIVE_TEXTURE_BYTES.fetch_add(size_in_bytes, Ordering::Relaxed);
+
         Self {
             texture,
             sampler,
             bind_group,
-            width: image.width(),
-            height: image.height(),
+            width,
+            height,
+            size_in_bytes,
         }
     }
 
     pub fn bind_group(&self) -> &TextureBindGroup {
         &self.bind_group
     }
+
+    pub fn size_in_bytes(&self) -> u64 {
+        self.size_in_bytes
+    }
+
+    pub fn stats() -> GpuTextureStats {
+        GpuTextureStats {
+            created: TEXTURES_CREATED.load(Ordering::Relaxed),
+            live: LIVE_TEXTURES.load(Ordering::Relaxed),
+            live_bytes: LIVE_TEXTURE_BYTES.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Drop for GpuTexture {
+    fn drop(&mut self) {
+        LIVE_TEXTURES.fetch_sub(1, Ordering::Relaxed);
+        LIVE_TEXTURE_BYTES.fetch_sub(self.size_in_bytes, Ordering::Relaxed);
+    }
 }
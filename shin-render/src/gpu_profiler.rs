@@ -0,0 +1,186 @@
+//! Measurement of the GPU time spent in the render passes.
+//!
+//! The passes write timestamps at their start and end (see [`GpuProfiler::timestamp_writes`]), which are read back a couple of frames later, without stalling the GPU.
+//! The frames rendered while a readback is in flight are not measured.
+//! Needs [`wgpu::Features::TIMESTAMP_QUERY`], nothing is measured on the devices created without it.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// The passes after this many in a frame are not measured
+const MAX_PASSES: u32 = 128;
+const QUERY_COUNT: u32 = MAX_PASSES * 2;
+const BUFFER_SIZE: u64 = QUERY_COUNT as u64 * wgpu::QUERY_SIZE as u64;
+
+/// The GPU time of a render pass
+#[derive(Debug, Clone)]
+pub struct GpuPassTiming {
+    pub label: String,
+    /// Since the start of the first measured pass of the frame
+    pub start: Duration,
+    pub duration: Duration,
+}
+
+struct Queries {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    /// Nanoseconds per timestamp tick
+    period: f32,
+}
+
+#[derive(Default)]
+struct FrameState {
+    /// The labels of the passes measured in this frame, the pass `i` writes the queries `2i` and `2i + 1`
+    labels: Vec<String>,
+    /// The labels of the frame being read back, `None` if there is no readback in flight
+    readback_labels: Option<Vec<String>>,
+}
+
+pub struct GpuProfiler {
+    queries: Option<Queries>,
+    state: Mutex<FrameState>,
+    /// Set by the map callback, to whether the readback buffer was mapped successfully
+    map_result: Arc<Mutex<Option<bool>>>,
+}
+
+impl GpuProfiler {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let queries = device
+            .features()
+            .contains(wgpu::Features::TIMESTAMP_QUERY)
+            .then(|| Queries {
+                query_set: device.create_query_set(&wgpu::QuerySetDescriptor {
+                    label: Some("GpuProfiler QuerySet"),
+                    ty: wgpu::QueryType::Timestamp,
+                    count: QUERY_COUNT,
+                }),
+                resolve_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("GpuProfiler Resolve Buffer"),
+                    size: BUFFER_SIZE,
+                    usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                    mapped_at_creation: false,
+                }),
+                readback_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("GpuProfiler Readback Buffer"),
+                    size: BUFFER_SIZE,
+                    usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                }),
+                period: queue.get_timestamp_period(),
+            });
+
+        Self {
+            queries,
+            state: Mutex::new(FrameState::default()),
+            map_result: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Whether the device supports the timestamps
+    pub fn is_supported(&self) -> bool {
+        self.queries.is_some()
+    }
+
+    /// Returns the timestamp writes measuring a render pass, `None` if it's not measured
+    pub fn timestamp_writes(&self, label: Option<&str>) -> Option<wgpu::RenderPassTimestampWrites> {
+        let queries = self.queries.as_ref()?;
+        let mut state = self.state.lock().unwrap();
+        if state.readback_labels.is_some() || state.labels.len() >= MAX_PASSES as usize {
+            return None;
+        }
+
+        let index = state.labels.len() as u32;
+        state
+            .labels
+            .push(label.unwrap_or("Unnamed RenderPass").to_string());
+
+        Some(wgpu::RenderPassTimestampWrites {
+            query_set: &queries.query_set,
+            beginning_of_pass_write_index: Some(index * 2),
+            end_of_pass_write_index: Some(index * 2 + 1),
+        })
+    }
+
+    /// Starts reading back the measurements of this frame, returns the ones of an earlier frame when they are ready
+    ///
+    /// Must be called after all the passes of the frame were submitted.
+    pub fn end_frame(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Option<Vec<GpuPassTiming>> {
+        let queries = self.queries.as_ref()?;
+        let mut state = self.state.lock().unwrap();
+
+        let mut finished = None;
+        match self.map_result.lock().unwrap().take() {
+            Some(true) => {
+                let labels = state.readback_labels.take().unwrap_or_default();
+                let timestamps = bytemuck::cast_slice::<u8, u64>(
+                    &queries.readback_buffer.slice(..).get_mapped_range(),
+                )[..labels.len() * 2]
+                    .to_vec();
+                queries.readback_buffer.unmap();
+                finished = Some(timings(labels, &timestamps, queries.period));
+            }
+            // the measurements are lost, but the next frame can be measured
+            Some(false) => state.readback_labels = None,
+            None => {}
+        }
+
+        if state.readback_labels.is_none() && !state.labels.is_empty() {
+            let labels = std::mem::take(&mut state.labels);
+            let query_count = labels.len() as u32 * 2;
+
+            let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("GpuProfiler Resolve"),
+            });
+            encoder.resolve_query_set(
+                &queries.query_set,
+                0..query_count,
+                &queries.resolve_buffer,
+                0,
+            );
+            encoder.copy_buffer_to_buffer(
+                &queries.resolve_buffer,
+                0,
+                &queries.readback_buffer,
+                0,
+                query_count as u64 * wgpu::QUERY_SIZE as u64,
+            );
+            queue.submit(Some(encoder.finish()));
+
+            let map_result = self.map_result.clone();
+            queries
+                .readback_buffer
+                .slice(..)
+                .map_async(wgpu::MapMode::Read, move |result| {
+                    *map_result.lock().unwrap() = Some(result.is_ok());
+                });
+            state.readback_labels = Some(labels);
+        }
+
+        // lets the map callback run
+        device.poll(wgpu::Maintain::Poll);
+
+        finished
+    }
+}
+
+fn timings(labels: Vec<String>, timestamps: &[u64], period: f32) -> Vec<GpuPassTiming> {
+    let origin = timestamps.iter().step_by(2).copied().min().unwrap_or(0);
+    let to_duration = |ticks: u64| Duration::from_nanos((ticks as f64 * period as f64) as u64);
+
+    labels
+        .into_iter()
+        .zip(timestamps.chunks_exact(2))
+        .map(|(label, pair)| GpuPassTiming {
+            label,
+            start: to_duration(pair[0].saturating_sub(origin)),
+            duration: to_duration(pair[1].saturating_sub(pair[0])),
+        })
+        .collect()
+}
@@ -0,0 +1,128 @@
+//! Creation of the wgpu device and the common resources.
+
+use std::sync::{Arc, Mutex, RwLock};
+
+use anyhow::{Context, Result};
+use tracing::{error, info};
+
+use crate::{
+    BindGroupLayouts, GpuCommonResources, GpuProfiler, Pipelines, RenderTargetPool, SamplerStore,
+    SRGB_TEXTURE_FORMAT,
+};
+
+/// Describes the device features and limits required by the renderer
+pub fn device_descriptor() -> wgpu::DeviceDescriptor<'static> {
+    wgpu::DeviceDescriptor {
+        label: None,
+        required_features: wgpu::Features::PUSH_CONSTANTS,
+        // WebGL doesn't support all of wgpu's features, so if
+        // we're building for the web we'll have to disable some.
+        required_limits: wgpu::Limits {
+            max_texture_dimension_2d: 4096,
+            max_push_constant_size: 128,
+
+            ..wgpu::Limits::downlevel_webgl2_defaults()
+        },
+    }
+}
+
+/// Creates the common resources without a window or a surface
+///
+/// Everything is rendered into textures, which can be read back with [`RenderTarget::read_pixels`](crate::RenderTarget::read_pixels).
+/// This allows rendering in tests and CLI tools, without a display server.
+///
+/// The "screen" pipelines are created for the [`SRGB_TEXTURE_FORMAT`], so the final pass can also target a [`RenderTarget`](crate::RenderTarget).
+pub async fn init_headless(render_buffer_size: (u32, u32)) -> Result<GpuCommonResources> {
+    let backends = wgpu::util::backend_bits_from_env().unwrap_or(wgpu::Backends::all());
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends,
+        ..Default::default()
+    });
+    let adapter = wgpu::util::initialize_adapter_from_env_or_default(&instance, None)
+        .await
+        .context("Failed to find appropriate wgpu adapter")?;
+
+    info!("Selected an adapter {:?} (headless)", adapter.get_info());
+
+    let (device, queue) = adapter
+        .request_device(&device_descriptor(), None)
+        .await
+        .context("Failed to create wgpu device")?;
+
+    // the timestamp queries are not requested, so the GPU profiler doesn't measure anything
+    Ok(create_resources(
+        device,
+        queue,
+        SRGB_TEXTURE_FORMAT,
+        render_buffer_size,
+        RenderTargetPool::DEFAULT_BUDGET,
+    ))
+}
+
+/// Creates the common resources on the `device`, with the "screen" pipelines drawing to the `surface_texture_format`
+pub fn create_resources(
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    surface_texture_format: wgpu::TextureFormat,
+    render_buffer_size: (u32, u32),
+    render_target_budget: u64,
+) -> GpuCommonResources {
+    let bind_group_layouts = BindGroupLayouts::new(&device);
+    let sampler_store = SamplerStore::new(&device);
+    let pipelines = Pipelines::new(&device, &bind_group_layouts, surface_texture_format);
+    let gpu_profiler = GpuProfiler::new(&device, &queue);
+
+    GpuCommonResources {
+        device,
+        queue,
+        render_buffer_size: RwLock::new(render_buffer_size),
+        bind_group_layouts,
+        sampler_store,
+        pipelines,
+        render_target_pool: RenderTargetPool::new(render_target_budget),
+        gpu_profiler,
+    }
+}
+
+/// Picks the format of the window surface from the ones it supports
+///
+/// An sRGB format is preferred, so that the hardware does the gamma encoding.
+/// If there is none, the render target is copied to the surface without decoding the sRGB colors, which has the same effect.
+pub fn select_surface_format(formats: &[wgpu::TextureFormat]) -> Option<wgpu::TextureFormat> {
+    formats
+        .iter()
+        .copied()
+        .find(|format| format.is_srgb())
+        .or_else(|| formats.first().copied())
+}
+
+/// Set when the device is lost (on a driver crash or a GPU reset), so that it can be re-created
+///
+/// The device lost callback can be called on any thread, so the loss is only recorded here and handled once a frame.
+#[derive(Clone, Default)]
+pub struct DeviceLossFlag(Arc<Mutex<Option<String>>>);
+
+impl DeviceLossFlag {
+    /// Sets the device lost callback of the `device` to raise this flag
+    pub fn watch(device: &wgpu::Device) -> Self {
+        let flag = Self::default();
+        let lost = flag.0.clone();
+        device.set_device_lost_callback(move |reason, message| {
+            // dropping the device (including when it's being re-created) is not a loss
+            if matches!(
+                reason,
+                wgpu::DeviceLostReason::Dropped | wgpu::DeviceLostReason::ReplacedCallback
+            ) {
+                return;
+            }
+            error!("The GPU device was lost ({:?}): {}", reason, message);
+            *lost.lock().unwrap() = Some(format!("{:?}: {}", reason, message));
+        });
+        flag
+    }
+
+    /// Returns the reason if the device was lost since the last call
+    pub fn take(&self) -> Option<String> {
+        self.0.lock().unwrap().take()
+    }
+}
@@ -8,24 +8,38 @@ mod bind_groups;
 mod camera;
 mod common_resources;
 mod gpu_image;
+mod gpu_profiler;
+pub mod init;
 mod new_render;
+mod nine_patch;
 mod pillarbox;
 mod pipelines;
 mod render_target;
+mod render_target_pool;
+mod sampler_store;
 mod vertex_buffer;
 pub mod vertices;
 
 pub use bind_groups::{BindGroupLayouts, TextureBindGroup, YuvTextureBindGroup};
-pub use camera::{Camera, VIRTUAL_HEIGHT, VIRTUAL_WIDTH};
+pub use camera::{AspectMode, Camera, VIRTUAL_HEIGHT, VIRTUAL_WIDTH};
 pub use common_resources::GpuCommonResources;
-pub use gpu_image::{GpuImage, GpuTexture, LazyGpuImage, LazyGpuTexture};
+pub use gpu_image::{
+    GpuImage, GpuTexture, GpuTextureStats, LazyGpuImage, LazyGpuMaskTexture, LazyGpuTexture,
+};
+pub use gpu_profiler::{GpuPassTiming, GpuProfiler};
+pub use nine_patch::NinePatch;
 pub use pillarbox::Pillarbox;
-pub use pipelines::Pipelines;
+#[cfg(feature = "shader-hot-reload")]
+pub use pipelines::ShaderWatcher;
+pub use pipelines::{PipelineStats, Pipelines};
 pub use render_target::RenderTarget;
+pub use render_target_pool::{PooledRenderTarget, RenderTargetPool, RenderTargetPoolStats};
+pub use sampler_store::{SamplerMode, SamplerStore, TextureFilter, TextureWrap};
 pub use vertex_buffer::{IndexBuffer, PosVertexBuffer, SpriteVertexBuffer, Vertex, VertexBuffer};
 
 pub const SRGB_TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
 pub const RAW_TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+pub const MASK_TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Unorm;
 
 pub struct SubmittingEncoder<'q> {
     encoder: Option<wgpu::CommandEncoder>,
@@ -92,9 +92,21 @@ enum RenderProgramWithArguments {
     Sprite {},
     Font {},
     FontBorder {},
-    Button {},
+    Button {
+        // TODO: fix the trait impl
+        // vertices: VertexBufferSliceReference<ButtonVertex>,
+        transform: Mat4,
+        color: Vec4,
+        /// Intensity of the flashing look of the button, from 0 to 1
+        flash: f32,
+    },
     Blend {},
-    Window {},
+    Window {
+        // TODO: fix the trait impl
+        // vertices: VertexBufferSliceReference<WindowVertex>,
+        transform: Mat4,
+        color: Vec4,
+    },
 
     Layer {
         shape: LayerShaderShape,
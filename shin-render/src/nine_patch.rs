@@ -0,0 +1,78 @@
+use glam::{vec2, Vec2};
+
+use crate::vertices::WindowVertex;
+
+/// Describes how a menu window texture is stretched to an arbitrary size
+///
+/// The corners are drawn as-is, the edges are stretched along one axis and the center along both of them.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct NinePatch {
+    /// Size of the texture, in pixels
+    pub texture_size: Vec2,
+    /// Sizes of the left, top, right and bottom borders, in pixels
+    pub borders: (f32, f32, f32, f32),
+}
+
+impl NinePatch {
+    /// Number of vertices produced by [`NinePatch::vertices`]
+    pub const VERTEX_COUNT: usize = 9 * 6;
+
+    pub fn new(texture_size: Vec2, borders: (f32, f32, f32, f32)) -> Self {
+        Self {
+            texture_size,
+            borders,
+        }
+    }
+
+    /// Builds a triangle list covering the `(l, t, r, b)` rectangle
+    ///
+    /// When the rectangle is smaller than the borders, they are shrunk proportionally, so the window never turns inside out.
+    pub fn vertices(&self, (l, t, r, b): (f32, f32, f32, f32)) -> Vec<WindowVertex> {
+        let (border_l, border_t, border_r, border_b) = self.borders;
+
+        let shrink = |size: f32, start: f32, end: f32| {
+            if start + end > size && start + end > 0.0 {
+                size / (start + end)
+            } else {
+                1.0
+            }
+        };
+        let scale_x = shrink(r - l, border_l, border_r);
+        let scale_y = shrink(b - t, border_t, border_b);
+
+        let xs = [l, l + border_l * scale_x, r - border_r * scale_x, r];
+        let ys = [t, t + border_t * scale_y, b - border_b * scale_y, b];
+        let us = [
+            0.0,
+            border_l / self.texture_size.x,
+            1.0 - border_r / self.texture_size.x,
+            1.0,
+        ];
+        let vs = [
+            0.0,
+            border_t / self.texture_size.y,
+            1.0 - border_b / self.texture_size.y,
+            1.0,
+        ];
+
+        let vertex = |i: usize, j: usize| WindowVertex {
+            position: vec2(xs[i], ys[j]),
+            texture_coordinate: vec2(us[i], vs[j]),
+        };
+
+        let mut result = Vec::with_capacity(Self::VERTEX_COUNT);
+        for j in 0..3 {
+            for i in 0..3 {
+                result.extend([
+                    vertex(i, j),
+                    vertex(i + 1, j),
+                    vertex(i, j + 1),
+                    vertex(i + 1, j),
+                    vertex(i + 1, j + 1),
+                    vertex(i, j + 1),
+                ]);
+            }
+        }
+        result
+    }
+}
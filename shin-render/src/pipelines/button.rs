@@ -0,0 +1,86 @@
+use std::mem;
+
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat4, Vec4};
+
+use crate::{
+    pipelines,
+    vertices::{ButtonVertex, VertexSource},
+    BindGroupLayouts, TextureBindGroup,
+};
+
+#[derive(Pod, Zeroable, Copy, Clone, Debug)]
+#[repr(C)]
+struct ButtonParams {
+    pub transform: Mat4,
+    pub color: Vec4,
+    pub flash: f32,
+    pub _padding: [f32; 3],
+}
+
+/// Draws menu buttons, blending between the normal and the flashing look of the button
+pub struct ButtonPipeline(wgpu::RenderPipeline);
+
+impl ButtonPipeline {
+    pub fn new(
+        device: &wgpu::Device,
+        bind_group_layouts: &BindGroupLayouts,
+        texture_format: wgpu::TextureFormat,
+    ) -> Self {
+        let shader_module = pipelines::shader_module!(device, "button.wgsl");
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("ButtonPipeline Layout"),
+            bind_group_layouts: &[&bind_group_layouts.texture],
+            push_constant_ranges: &[wgpu::PushConstantRange {
+                stages: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                range: 0..(mem::size_of::<ButtonParams>() as u32),
+            }],
+        });
+
+        Self(pipelines::make_pipeline(
+            device,
+            texture_format,
+            shader_module,
+            layout,
+            ButtonVertex::desc(),
+            Some(wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::SrcAlpha,
+                    dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::OneMinusDstAlpha,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+            }),
+            "ButtonPipeline",
+        ))
+    }
+
+    pub fn draw<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        source: VertexSource<'a, ButtonVertex>,
+        texture: &'a TextureBindGroup,
+        transform: Mat4,
+        color: Vec4,
+        flash: f32,
+    ) {
+        render_pass.set_pipeline(&self.0);
+        render_pass.set_bind_group(0, &texture.0, &[]);
+        render_pass.set_push_constants(
+            wgpu::ShaderStages::VERTEX_FRAGMENT,
+            0,
+            bytemuck::cast_slice(&[ButtonParams {
+                transform,
+                color,
+                flash,
+                _padding: [0.0; 3],
+            }]),
+        );
+        source.draw(render_pass);
+    }
+}
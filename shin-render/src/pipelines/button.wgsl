@@ -0,0 +1,40 @@
+struct VertexIn {
+    @location(0) position: vec2<f32>,
+    @location(1) texture_coordinate: vec2<f32>,
+    @location(2) flash_texture_coordinate: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) texture_coordinate: vec2<f32>,
+    @location(1) flash_texture_coordinate: vec2<f32>,
+}
+
+@group(0) @binding(0)
+var button_texture: texture_2d<f32>;
+@group(0) @binding(1)
+var button_sampler: sampler;
+
+struct ButtonParams {
+    transform: mat4x4<f32>,
+    color: vec4<f32>,
+    flash: f32,
+}
+
+var<push_constant> params: ButtonParams;
+
+@vertex
+fn vertex_main(input: VertexIn) -> VertexOutput {
+    var output: VertexOutput;
+    output.position = params.transform * vec4<f32>(input.position, 0.0, 1.0);
+    output.texture_coordinate = input.texture_coordinate;
+    output.flash_texture_coordinate = input.flash_texture_coordinate;
+    return output;
+}
+
+@fragment
+fn fragment_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    let normal = textureSample(button_texture, button_sampler, input.texture_coordinate);
+    let flash = textureSample(button_texture, button_sampler, input.flash_texture_coordinate);
+    return mix(normal, flash, params.flash) * params.color;
+}
@@ -2,7 +2,6 @@ use std::mem;
 
 use bytemuck::{Pod, Zeroable};
 use glam::{Mat4, Vec4};
-use wgpu::include_wgsl;
 
 use crate::{
     pipelines,
@@ -25,7 +24,7 @@ impl FillPipeline {
         _bind_group_layouts: &BindGroupLayouts,
         texture_format: wgpu::TextureFormat,
     ) -> Self {
-        let shader_module = device.create_shader_module(include_wgsl!("fill.wgsl"));
+        let shader_module = pipelines::shader_module!(device, "fill.wgsl");
 
         let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("FillPipeline Layout"),
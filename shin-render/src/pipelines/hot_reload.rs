@@ -0,0 +1,112 @@
+//! Reloading of the shaders from the source tree, to iterate on them without rebuilding the engine.
+//!
+//! The sources are read from the directory the crate was built from, so this only works on the machine that built it.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use tracing::{error, info, warn};
+
+use super::Pipelines;
+use crate::BindGroupLayouts;
+
+const SHADER_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/pipelines");
+/// Checking the files every frame is wasteful, the changes don't need to be picked up that fast
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+pub(super) fn read_source(file: &str) -> Option<String> {
+    let path = Path::new(SHADER_DIR).join(file);
+    match std::fs::read_to_string(&path) {
+        Ok(source) => Some(source),
+        Err(e) => {
+            warn!(
+                "Failed to read {}, using the embedded shader: {}",
+                path.display(),
+                e
+            );
+            None
+        }
+    }
+}
+
+fn modification_times() -> HashMap<PathBuf, SystemTime> {
+    let Ok(entries) = std::fs::read_dir(SHADER_DIR) else {
+        return HashMap::new();
+    };
+
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "wgsl"))
+        .filter_map(|path| {
+            let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok()?;
+            Some((path, modified))
+        })
+        .collect()
+}
+
+/// Watches the shader sources for changes
+pub struct ShaderWatcher {
+    modified: HashMap<PathBuf, SystemTime>,
+    last_poll: Instant,
+    changed: bool,
+}
+
+impl ShaderWatcher {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        info!("Watching the shaders in {} for changes", SHADER_DIR);
+        Self {
+            modified: modification_times(),
+            last_poll: Instant::now(),
+            changed: false,
+        }
+    }
+
+    /// Returns `true` if the shaders have changed since the last [`ShaderWatcher::reset`]
+    pub fn poll(&mut self) -> bool {
+        if self.last_poll.elapsed() >= POLL_INTERVAL {
+            self.last_poll = Instant::now();
+            let modified = modification_times();
+            if modified != self.modified {
+                self.modified = modified;
+                self.changed = true;
+            }
+        }
+        self.changed
+    }
+
+    /// Marks the changes as handled
+    pub fn reset(&mut self) {
+        self.changed = false;
+    }
+}
+
+impl Pipelines {
+    /// Recompiles all the pipelines from the shader sources
+    ///
+    /// On a compilation error the error is logged and the current pipelines are kept, so a typo in a shader doesn't crash the game.
+    pub fn reload(
+        &mut self,
+        device: &wgpu::Device,
+        bind_group_layouts: &BindGroupLayouts,
+        surface_texture_format: wgpu::TextureFormat,
+    ) -> bool {
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let pipelines = Pipelines::new(device, bind_group_layouts, surface_texture_format);
+        match pollster::block_on(device.pop_error_scope()) {
+            None => {
+                *self = pipelines;
+                info!("Reloaded the shaders");
+                true
+            }
+            Some(e) => {
+                error!("Failed to reload the shaders, keeping the old ones:\n{}", e);
+                false
+            }
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use std::mem;
+
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat4, Vec4};
+use shin_core::vm::command::types::LayerFragmentShader;
+
+use crate::{
+    pipelines,
+    vertices::{PosColTexVertex, VertexSource},
+    BindGroupLayouts, TextureBindGroup,
+};
+
+#[derive(Pod, Zeroable, Copy, Clone, Debug)]
+#[repr(C)]
+struct LayerParams {
+    pub transform: Mat4,
+    pub shader_params: Vec4,
+    pub fragment_shader: u32,
+    pub _padding: [u32; 3],
+}
+
+/// Draws a composited layer, applying a [`LayerFragmentShader`] color effect
+///
+/// The shader must match [`LayerFragmentShader::evaluate`].
+pub struct LayerPipeline(wgpu::RenderPipeline);
+
+impl LayerPipeline {
+    pub fn new(
+        device: &wgpu::Device,
+        bind_group_layouts: &BindGroupLayouts,
+        texture_format: wgpu::TextureFormat,
+    ) -> Self {
+        let shader_module = pipelines::shader_module!(device, "layer.wgsl");
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("LayerPipeline Layout"),
+            bind_group_layouts: &[&bind_group_layouts.texture],
+            push_constant_ranges: &[wgpu::PushConstantRange {
+                stages: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                range: 0..(mem::size_of::<LayerParams>() as u32),
+            }],
+        });
+
+        Self(pipelines::make_pipeline(
+            device,
+            texture_format,
+            shader_module,
+            layout,
+            PosColTexVertex::desc(),
+            Some(wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::SrcAlpha,
+                    dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::OneMinusDstAlpha,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+            }),
+            "LayerPipeline",
+        ))
+    }
+
+    pub fn draw<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        source: VertexSource<'a, PosColTexVertex>,
+        texture: &'a TextureBindGroup,
+        transform: Mat4,
+        fragment_shader: LayerFragmentShader,
+        shader_params: Vec4,
+    ) {
+        render_pass.set_pipeline(&self.0);
+        render_pass.set_bind_group(0, &texture.0, &[]);
+        render_pass.set_push_constants(
+            wgpu::ShaderStages::VERTEX_FRAGMENT,
+            0,
+            bytemuck::cast_slice(&[LayerParams {
+                transform,
+                shader_params,
+                fragment_shader: fragment_shader as u32,
+                _padding: [0; 3],
+            }]),
+        );
+        source.draw(render_pass);
+    }
+}
@@ -0,0 +1,75 @@
+struct VertexIn {
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) texture_coordinate: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    @location(1) texture_coordinate: vec2<f32>,
+}
+
+@group(0) @binding(0)
+var sprite_texture: texture_2d<f32>;
+@group(0) @binding(1)
+var sprite_sampler: sampler;
+
+struct LayerParams {
+    transform: mat4x4<f32>,
+    shader_params: vec4<f32>,
+    fragment_shader: u32,
+}
+
+var<push_constant> params: LayerParams;
+
+// must match the values of shin_core::vm::command::types::LayerFragmentShader
+const SHADER_DEFAULT: u32 = 0u;
+const SHADER_MONO: u32 = 1u;
+const SHADER_FILL: u32 = 2u;
+const SHADER_FILL2: u32 = 3u;
+const SHADER_NEGATIVE: u32 = 4u;
+const SHADER_GAMMA: u32 = 5u;
+
+const LUMA_WEIGHTS: vec3<f32> = vec3<f32>(0.299, 0.587, 0.114);
+
+@vertex
+fn vertex_main(input: VertexIn) -> VertexOutput {
+    var output: VertexOutput;
+    output.position = params.transform * vec4<f32>(input.position, 1.0);
+    output.color = input.color;
+    output.texture_coordinate = input.texture_coordinate;
+    return output;
+}
+
+fn apply_fragment_shader(color: vec3<f32>) -> vec3<f32> {
+    let p = params.shader_params;
+    switch params.fragment_shader {
+        case SHADER_MONO: {
+            let gray = dot(color, LUMA_WEIGHTS);
+            return mix(color, p.xyz * gray, p.w);
+        }
+        case SHADER_FILL: {
+            return mix(color, p.xyz, p.w);
+        }
+        case SHADER_FILL2: {
+            return min(color + p.xyz * p.w, vec3<f32>(1.0));
+        }
+        case SHADER_NEGATIVE: {
+            return mix(color, vec3<f32>(1.0) - color, p.w);
+        }
+        case SHADER_GAMMA: {
+            let gamma = max(p.xyz, vec3<f32>(0.001));
+            return pow(color, vec3<f32>(1.0) / gamma);
+        }
+        default: {
+            return color;
+        }
+    }
+}
+
+@fragment
+fn fragment_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = textureSample(sprite_texture, sprite_sampler, input.texture_coordinate) * input.color;
+    return vec4<f32>(apply_fragment_shader(texel.rgb), texel.a);
+}
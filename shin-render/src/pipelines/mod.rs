@@ -1,17 +1,65 @@
+mod button;
 mod fill;
+#[cfg(feature = "shader-hot-reload")]
+mod hot_reload;
+mod layer;
 mod sprite;
 mod text;
 mod text_outline;
+mod window;
+mod wiper_default;
+mod wiper_mask;
 mod yuv_sprite;
+mod yuva_sprite;
 
+use std::{
+    borrow::Cow,
+    time::{Duration, Instant},
+};
+
+use button::ButtonPipeline;
 use fill::FillPipeline;
+use layer::LayerPipeline;
 use sprite::SpritePipeline;
 use text::TextPipeline;
 use text_outline::TextOutlinePipeline;
+use window::WindowPipeline;
+use wiper_default::WiperDefaultPipeline;
+use wiper_mask::WiperMaskPipeline;
 use yuv_sprite::YuvSpritePipeline;
+use yuva_sprite::YuvaSpritePipeline;
+
+#[cfg(feature = "shader-hot-reload")]
+pub use hot_reload::ShaderWatcher;
 
 use crate::{bind_groups::BindGroupLayouts, RAW_TEXTURE_FORMAT, SRGB_TEXTURE_FORMAT};
 
+/// Creates a shader module from a WGSL file in this directory, like [`wgpu::include_wgsl`]
+///
+/// With the `shader-hot-reload` feature the file is read from the source tree instead, so that the changes are picked up by [`Pipelines::reload`].
+macro_rules! shader_module {
+    ($device:expr, $file:literal) => {
+        $crate::pipelines::create_shader_module($device, $file, include_str!($file))
+    };
+}
+pub(crate) use shader_module;
+
+fn create_shader_module(
+    device: &wgpu::Device,
+    file: &'static str,
+    embedded: &'static str,
+) -> wgpu::ShaderModule {
+    #[cfg(feature = "shader-hot-reload")]
+    let source = hot_reload::read_source(file).map_or(Cow::Borrowed(embedded), Cow::Owned);
+    #[cfg(not(feature = "shader-hot-reload"))]
+    let source = Cow::Borrowed(embedded);
+
+    device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some(file),
+        source: wgpu::ShaderSource::Wgsl(source),
+    })
+}
+
 // TODO: make a builder?
 fn make_pipeline(
     device: &wgpu::Device,
@@ -56,33 +104,81 @@ fn make_pipeline(
     })
 }
 
+/// All the render pipelines, compiled eagerly on startup
 pub struct Pipelines {
     pub sprite: SpritePipeline,
     pub yuv_sprite: YuvSpritePipeline,
+    pub yuva_sprite: YuvaSpritePipeline,
     pub fill: FillPipeline,
+    pub layer: LayerPipeline,
     pub text: TextPipeline,
     pub text_outline: TextOutlinePipeline,
+    pub wiper_default: WiperDefaultPipeline,
+    pub wiper_mask: WiperMaskPipeline,
+    pub window: WindowPipeline,
+    pub button: ButtonPipeline,
     // those are pipelines using screen's texture format (not our preferred RGBA format)
     // they are only used for the final render pass
     pub sprite_screen: SpritePipeline,
     pub fill_screen: FillPipeline,
 }
 
+/// How long it took to build one of the [`Pipelines`], see [`Pipelines::new_with_stats`]
+#[derive(Debug, Clone)]
+pub struct PipelineStats {
+    pub name: &'static str,
+    pub texture_format: wgpu::TextureFormat,
+    pub build_time: Duration,
+}
+
 impl Pipelines {
     pub fn new(
         device: &wgpu::Device,
         bind_group_layouts: &BindGroupLayouts,
         surface_texture_format: wgpu::TextureFormat,
     ) -> Pipelines {
-        Pipelines {
-            sprite: SpritePipeline::new(device, bind_group_layouts, SRGB_TEXTURE_FORMAT),
-            yuv_sprite: YuvSpritePipeline::new(device, bind_group_layouts, RAW_TEXTURE_FORMAT),
-            fill: FillPipeline::new(device, bind_group_layouts, SRGB_TEXTURE_FORMAT),
-            text: TextPipeline::new(device, bind_group_layouts, SRGB_TEXTURE_FORMAT),
-            text_outline: TextOutlinePipeline::new(device, bind_group_layouts, SRGB_TEXTURE_FORMAT),
+        Self::new_with_stats(device, bind_group_layouts, surface_texture_format).0
+    }
 
-            sprite_screen: SpritePipeline::new(device, bind_group_layouts, surface_texture_format),
-            fill_screen: FillPipeline::new(device, bind_group_layouts, surface_texture_format),
+    /// Builds the pipelines like [`Pipelines::new`], measuring how long each of them took
+    ///
+    /// Every pipeline the engine can draw with is built here, so this is the full list of the pipeline variants.
+    pub fn new_with_stats(
+        device: &wgpu::Device,
+        bind_group_layouts: &BindGroupLayouts,
+        surface_texture_format: wgpu::TextureFormat,
+    ) -> (Pipelines, Vec<PipelineStats>) {
+        let mut stats = Vec::new();
+        macro_rules! timed {
+            ($name:literal, $pipeline:ident, $format:expr) => {{
+                let start = Instant::now();
+                let pipeline = $pipeline::new(device, bind_group_layouts, $format);
+                stats.push(PipelineStats {
+                    name: $name,
+                    texture_format: $format,
+                    build_time: start.elapsed(),
+                });
+                pipeline
+            }};
         }
+
+        let pipelines = Pipelines {
+            sprite: timed!("sprite", SpritePipeline, SRGB_TEXTURE_FORMAT),
+            yuv_sprite: timed!("yuv_sprite", YuvSpritePipeline, RAW_TEXTURE_FORMAT),
+            yuva_sprite: timed!("yuva_sprite", YuvaSpritePipeline, RAW_TEXTURE_FORMAT),
+            fill: timed!("fill", FillPipeline, SRGB_TEXTURE_FORMAT),
+            layer: timed!("layer", LayerPipeline, SRGB_TEXTURE_FORMAT),
+            text: timed!("text", TextPipeline, SRGB_TEXTURE_FORMAT),
+            text_outline: timed!("text_outline", TextOutlinePipeline, SRGB_TEXTURE_FORMAT),
+            wiper_default: timed!("wiper_default", WiperDefaultPipeline, SRGB_TEXTURE_FORMAT),
+            wiper_mask: timed!("wiper_mask", WiperMaskPipeline, SRGB_TEXTURE_FORMAT),
+            window: timed!("window", WindowPipeline, SRGB_TEXTURE_FORMAT),
+            button: timed!("button", ButtonPipeline, SRGB_TEXTURE_FORMAT),
+
+            sprite_screen: timed!("sprite_screen", SpritePipeline, surface_texture_format),
+            fill_screen: timed!("fill_screen", FillPipeline, surface_texture_format),
+        };
+
+        (pipelines, stats)
     }
 }
@@ -3,7 +3,6 @@ use std::mem;
 use bytemuck::{Pod, Zeroable};
 use glam::Mat4;
 use shin_core::time::Ticks;
-use wgpu::include_wgsl;
 
 use crate::{
     pipelines,
@@ -26,7 +25,7 @@ impl TextPipeline {
         bind_group_layouts: &BindGroupLayouts,
         texture_format: wgpu::TextureFormat,
     ) -> Self {
-        let shader_module = device.create_shader_module(include_wgsl!("text.wgsl"));
+        let shader_module = pipelines::shader_module!(device, "text.wgsl");
 
         let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("TextPipeline Layout"),
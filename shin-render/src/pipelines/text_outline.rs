@@ -3,7 +3,6 @@ use std::mem;
 use bytemuck::{Pod, Zeroable};
 use glam::{Mat4, Vec2};
 use shin_core::time::Ticks;
-use wgpu::include_wgsl;
 
 use crate::{
     pipelines,
@@ -27,7 +26,7 @@ impl TextOutlinePipeline {
         bind_group_layouts: &BindGroupLayouts,
         texture_format: wgpu::TextureFormat,
     ) -> Self {
-        let shader_module = device.create_shader_module(include_wgsl!("text_outline.wgsl"));
+        let shader_module = pipelines::shader_module!(device, "text_outline.wgsl");
 
         let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("TextOutlinePipeline Layout"),
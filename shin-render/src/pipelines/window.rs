@@ -0,0 +1,78 @@
+use std::mem;
+
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat4, Vec4};
+
+use crate::{
+    pipelines,
+    vertices::{VertexSource, WindowVertex},
+    BindGroupLayouts, TextureBindGroup,
+};
+
+#[derive(Pod, Zeroable, Copy, Clone, Debug)]
+#[repr(C)]
+struct WindowParams {
+    pub transform: Mat4,
+    pub color: Vec4,
+}
+
+/// Draws menu windows, tinting the window texture with a uniform color
+pub struct WindowPipeline(wgpu::RenderPipeline);
+
+impl WindowPipeline {
+    pub fn new(
+        device: &wgpu::Device,
+        bind_group_layouts: &BindGroupLayouts,
+        texture_format: wgpu::TextureFormat,
+    ) -> Self {
+        let shader_module = pipelines::shader_module!(device, "window.wgsl");
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("WindowPipeline Layout"),
+            bind_group_layouts: &[&bind_group_layouts.texture],
+            push_constant_ranges: &[wgpu::PushConstantRange {
+                stages: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                range: 0..(mem::size_of::<WindowParams>() as u32),
+            }],
+        });
+
+        Self(pipelines::make_pipeline(
+            device,
+            texture_format,
+            shader_module,
+            layout,
+            WindowVertex::desc(),
+            Some(wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::SrcAlpha,
+                    dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::OneMinusDstAlpha,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+            }),
+            "WindowPipeline",
+        ))
+    }
+
+    pub fn draw<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        source: VertexSource<'a, WindowVertex>,
+        texture: &'a TextureBindGroup,
+        transform: Mat4,
+        color: Vec4,
+    ) {
+        render_pass.set_pipeline(&self.0);
+        render_pass.set_bind_group(0, &texture.0, &[]);
+        render_pass.set_push_constants(
+            wgpu::ShaderStages::VERTEX_FRAGMENT,
+            0,
+            bytemuck::cast_slice(&[WindowParams { transform, color }]),
+        );
+        source.draw(render_pass);
+    }
+}
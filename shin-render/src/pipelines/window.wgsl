@@ -0,0 +1,34 @@
+struct VertexIn {
+    @location(0) position: vec2<f32>,
+    @location(1) texture_coordinate: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) texture_coordinate: vec2<f32>,
+}
+
+@group(0) @binding(0)
+var window_texture: texture_2d<f32>;
+@group(0) @binding(1)
+var window_sampler: sampler;
+
+struct WindowParams {
+    transform: mat4x4<f32>,
+    color: vec4<f32>,
+}
+
+var<push_constant> params: WindowParams;
+
+@vertex
+fn vertex_main(input: VertexIn) -> VertexOutput {
+    var output: VertexOutput;
+    output.position = params.transform * vec4<f32>(input.position, 0.0, 1.0);
+    output.texture_coordinate = input.texture_coordinate;
+    return output;
+}
+
+@fragment
+fn fragment_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(window_texture, window_sampler, input.texture_coordinate) * params.color;
+}
@@ -0,0 +1,83 @@
+use std::mem;
+
+use bytemuck::{Pod, Zeroable};
+use glam::Mat4;
+
+use crate::{
+    pipelines,
+    vertices::{PosColTexVertex, VertexSource},
+    BindGroupLayouts, TextureBindGroup,
+};
+
+#[derive(Pod, Zeroable, Copy, Clone, Debug)]
+#[repr(C)]
+struct WiperDefaultParams {
+    pub transform: Mat4,
+    pub alpha: f32,
+    pub _padding: [f32; 3],
+}
+
+/// Draws a texture with a uniform alpha multiplier, used for crossfades
+pub struct WiperDefaultPipeline(wgpu::RenderPipeline);
+
+impl WiperDefaultPipeline {
+    pub fn new(
+        device: &wgpu::Device,
+        bind_group_layouts: &BindGroupLayouts,
+        texture_format: wgpu::TextureFormat,
+    ) -> Self {
+        let shader_module = pipelines::shader_module!(device, "wiper_default.wgsl");
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("WiperDefaultPipeline Layout"),
+            bind_group_layouts: &[&bind_group_layouts.texture],
+            push_constant_ranges: &[wgpu::PushConstantRange {
+                stages: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                range: 0..(mem::size_of::<WiperDefaultParams>() as u32),
+            }],
+        });
+
+        Self(pipelines::make_pipeline(
+            device,
+            texture_format,
+            shader_module,
+            layout,
+            PosColTexVertex::desc(),
+            Some(wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::SrcAlpha,
+                    dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::OneMinusDstAlpha,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+            }),
+            "WiperDefaultPipeline",
+        ))
+    }
+
+    pub fn draw<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        source: VertexSource<'a, PosColTexVertex>,
+        texture: &'a TextureBindGroup,
+        transform: Mat4,
+        alpha: f32,
+    ) {
+        render_pass.set_pipeline(&self.0);
+        render_pass.set_bind_group(0, &texture.0, &[]);
+        render_pass.set_push_constants(
+            wgpu::ShaderStages::VERTEX_FRAGMENT,
+            0,
+            bytemuck::cast_slice(&[WiperDefaultParams {
+                transform,
+                alpha,
+                _padding: [0.0; 3],
+            }]),
+        );
+        source.draw(render_pass);
+    }
+}
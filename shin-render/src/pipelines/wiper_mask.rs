@@ -0,0 +1,93 @@
+use std::mem;
+
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat4, Vec2, Vec4};
+
+use crate::{
+    pipelines,
+    vertices::{PosColTexVertex, VertexSource},
+    BindGroupLayouts, TextureBindGroup,
+};
+
+#[derive(Pod, Zeroable, Copy, Clone, Debug)]
+#[repr(C)]
+struct WiperMaskParams {
+    pub transform: Mat4,
+    /// xy - scale, zw - offset applied to the texture coordinates to get the mask coordinates
+    pub mask_transform: Vec4,
+    pub minmax: Vec2,
+    pub _padding: Vec2,
+}
+
+/// Draws a texture, using the mask texture to compute the alpha
+///
+/// The alpha is computed as `clamp((mask - min) / (max - min), 0, 1)`.
+/// `min` can be larger than `max`, in which case the darker parts of the mask become opaque first.
+pub struct WiperMaskPipeline(wgpu::RenderPipeline);
+
+impl WiperMaskPipeline {
+    pub fn new(
+        device: &wgpu::Device,
+        bind_group_layouts: &BindGroupLayouts,
+        texture_format: wgpu::TextureFormat,
+    ) -> Self {
+        let shader_module = pipelines::shader_module!(device, "wiper_mask.wgsl");
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("WiperMaskPipeline Layout"),
+            bind_group_layouts: &[&bind_group_layouts.texture, &bind_group_layouts.texture],
+            push_constant_ranges: &[wgpu::PushConstantRange {
+                stages: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                range: 0..(mem::size_of::<WiperMaskParams>() as u32),
+            }],
+        });
+
+        Self(pipelines::make_pipeline(
+            device,
+            texture_format,
+            shader_module,
+            layout,
+            PosColTexVertex::desc(),
+            Some(wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::SrcAlpha,
+                    dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::OneMinusDstAlpha,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+            }),
+            "WiperMaskPipeline",
+        ))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        source: VertexSource<'a, PosColTexVertex>,
+        texture: &'a TextureBindGroup,
+        mask: &'a TextureBindGroup,
+        transform: Mat4,
+        mask_transform: Vec4,
+        minmax: Vec2,
+    ) {
+        render_pass.set_pipeline(&self.0);
+        render_pass.set_bind_group(0, &texture.0, &[]);
+        render_pass.set_bind_group(1, &mask.0, &[]);
+        render_pass.set_push_constants(
+            wgpu::ShaderStages::VERTEX_FRAGMENT,
+            0,
+            bytemuck::cast_slice(&[WiperMaskParams {
+                transform,
+                mask_transform,
+                minmax,
+                _padding: Vec2::ZERO,
+            }]),
+        );
+        source.draw(render_pass);
+    }
+}
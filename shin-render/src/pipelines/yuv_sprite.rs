@@ -2,7 +2,6 @@ use std::mem;
 
 use bytemuck::{Pod, Zeroable};
 use glam::Mat4;
-use wgpu::include_wgsl;
 
 use crate::{
     pipelines,
@@ -24,7 +23,7 @@ impl YuvSpritePipeline {
         bind_group_layouts: &BindGroupLayouts,
         texture_format: wgpu::TextureFormat,
     ) -> Self {
-        let shader_module = device.create_shader_module(include_wgsl!("yuv_sprite.wgsl"));
+        let shader_module = pipelines::shader_module!(device, "yuv_sprite.wgsl");
 
         let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("YuvSpritePipeline Layout"),
@@ -0,0 +1,57 @@
+// A variant of the yuv_sprite shader for movies with an alpha channel ("MovieAlpha" in the original engine)
+// The color is stored in the top half of the frame, and the alpha is stored in the luma of the bottom half
+
+struct VertexIn {
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) texture_coordinate: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    @location(1) texture_coordinate: vec2<f32>,
+}
+
+@group(0) @binding(0)
+var y_texture: texture_2d<f32>;
+@group(0) @binding(1)
+var u_texture: texture_2d<f32>;
+@group(0) @binding(2)
+var v_texture: texture_2d<f32>;
+@group(0) @binding(3)
+var sprite_sampler: sampler;
+
+struct YuvaSpriteParams {
+    transform: mat4x4<f32>,
+}
+
+var<push_constant> params: YuvaSpriteParams;
+
+@vertex
+fn vertex_main(input: VertexIn) -> VertexOutput {
+    var output: VertexOutput;
+    output.position = params.transform * vec4<f32>(input.position, 1.0);
+    output.color = input.color;
+    output.texture_coordinate = input.texture_coordinate;
+    return output;
+}
+
+@fragment
+fn fragment_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    let color_coordinate = vec2(input.texture_coordinate.x, input.texture_coordinate.y * 0.5);
+    let alpha_coordinate = vec2(input.texture_coordinate.x, 0.5 + input.texture_coordinate.y * 0.5);
+
+    let y = textureSample(y_texture, sprite_sampler, color_coordinate).r * 255.0;
+    let u = textureSample(u_texture, sprite_sampler, color_coordinate).r * 255.0;
+    let v = textureSample(v_texture, sprite_sampler, color_coordinate).r * 255.0;
+    let a = textureSample(y_texture, sprite_sampler, alpha_coordinate).r;
+
+    let rgb = vec3(
+        (y + 1.402 * (v - 128.0)),
+        (y - 0.344 * (u - 128.0) - 0.714 * (v - 128.0)),
+        (y + 1.772 * (u - 128.0)),
+    ) / 255.0;
+
+    return vec4(rgb, a);
+}
\ No newline at end of file
@@ -1,12 +1,13 @@
 use std::borrow::Cow;
 
 use glam::Mat4;
+use image::RgbaImage;
 
 use super::TextureBindGroup;
 use crate::{
     common_resources::GpuCommonResources,
     vertices::{PosColTexVertex, VertexSource},
-    SpriteVertexBuffer, VIRTUAL_HEIGHT, VIRTUAL_WIDTH,
+    SamplerMode, SpriteVertexBuffer, VIRTUAL_HEIGHT, VIRTUAL_WIDTH,
 };
 
 /// Describes a fullscreen intermediate render target.
@@ -14,8 +15,8 @@ pub struct RenderTarget {
     texture: wgpu::Texture,
     srgb_view: wgpu::TextureView,
     raw_view: wgpu::TextureView,
-    sampler: wgpu::Sampler,
     bind_group: TextureBindGroup,
+    raw_bind_group: TextureBindGroup,
     vertices: SpriteVertexBuffer,
     label: Cow<'static, str>,
 }
@@ -40,7 +41,10 @@ impl RenderTarget {
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             format: Self::SRGB_FORMAT,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::COPY_DST,
             view_formats: &[Self::RAW_FORMAT],
         });
         let srgb_view = texture.create_view(&wgpu::TextureViewDescriptor {
@@ -53,59 +57,104 @@ impl RenderTarget {
             format: Some(Self::RAW_FORMAT),
             ..Default::default()
         });
-        let sampler = resources.device.create_sampler(&wgpu::SamplerDescriptor {
-            label: Some(&format!("{} Sampler", label)),
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Linear,
-            mipmap_filter: wgpu::FilterMode::Nearest,
-            ..Default::default()
-        });
+        let sampler = resources.sampler_store.get(SamplerMode::default());
         let bind_group = TextureBindGroup::new(
             resources,
             &srgb_view,
-            &sampler,
+            sampler,
             Some(&format!("{} TextureBindGroup", label)),
         );
+        let raw_bind_group = TextureBindGroup::new(
+            resources,
+            &raw_view,
+            sampler,
+            Some(&format!("{} Raw TextureBindGroup", label)),
+        );
         let vertices = SpriteVertexBuffer::new_fullscreen(resources);
         Self {
             texture,
             srgb_view,
             raw_view,
-            sampler,
             bind_group,
+            raw_bind_group,
             vertices,
             label,
         }
     }
 
     pub fn resize(&mut self, resources: &GpuCommonResources, size: (u32, u32)) {
-        self.texture = resources.device.create_texture(&wgpu::TextureDescriptor {
-            label: Some(&format!("{} Texture", self.label)),
-            size: wgpu::Extent3d {
-                width: size.0,
-                height: size.1,
-                depth_or_array_layers: 1,
-            },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: Self::SRGB_FORMAT,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
-            view_formats: &[],
+        // recreate everything, so that the views and bind groups refer to the new texture
+        *self = Self::new(resources, size, Some(&self.label));
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        let size = self.texture.size();
+        (size.width, size.height)
+    }
+
+    /// Copies the contents of another render target into this one
+    ///
+    /// Both render targets must have the same size.
+    pub fn copy_from(&self, resources: &GpuCommonResources, source: &RenderTarget) {
+        let mut encoder = resources.start_encoder();
+        encoder.copy_texture_to_texture(
+            source.texture.as_image_copy(),
+            self.texture.as_image_copy(),
+            source.texture.size(),
+        );
+    }
+
+    /// Reads the contents of the render target back to the CPU, waiting for the GPU to finish
+    ///
+    /// The pixels are sRGB-encoded, as they are stored in the texture.
+    pub fn read_pixels(&self, resources: &GpuCommonResources) -> RgbaImage {
+        const BYTES_PER_PIXEL: u32 = 4;
+
+        let (width, height) = self.size();
+        let unpadded_bytes_per_row = width * BYTES_PER_PIXEL;
+        let padded_bytes_per_row = unpadded_bytes_per_row
+            .div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+        let buffer = resources.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(&format!("{} Readback Buffer", self.label)),
+            size: (padded_bytes_per_row * height) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
         });
-        self.srgb_view = self.texture.create_view(&wgpu::TextureViewDescriptor {
-            label: Some(&format!("{} TextureView", self.label)),
-            ..Default::default()
+
+        {
+            let mut encoder = resources.start_encoder();
+            encoder.copy_texture_to_buffer(
+                self.texture.as_image_copy(),
+                wgpu::ImageCopyBuffer {
+                    buffer: &buffer,
+                    layout: wgpu::ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: Some(padded_bytes_per_row),
+                        rows_per_image: Some(height),
+                    },
+                },
+                self.texture.size(),
+            );
+        }
+
+        let slice = buffer.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |result| {
+            result.expect("Failed to map the readback buffer")
         });
-        self.bind_group = TextureBindGroup::new(
-            resources,
-            &self.srgb_view,
-            &self.sampler,
-            Some(&format!("{} TextureBindGroup", self.label)),
-        );
+        resources.device.poll(wgpu::Maintain::Wait);
+
+        let mut pixels = Vec::with_capacity((unpadded_bytes_per_row * height) as usize);
+        for row in slice
+            .get_mapped_range()
+            .chunks_exact(padded_bytes_per_row as usize)
+        {
+            pixels.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+        }
+        buffer.unmap();
+
+        RgbaImage::from_raw(width, height, pixels).unwrap()
     }
 
     pub fn projection_matrix(&self) -> Mat4 {
@@ -124,6 +173,7 @@ impl RenderTarget {
 
     pub fn begin_srgb_render_pass<'a>(
         &'a self,
+        resources: &'a GpuCommonResources,
         encoder: &'a mut wgpu::CommandEncoder,
         label: Option<&str>,
     ) -> wgpu::RenderPass<'a> {
@@ -138,13 +188,14 @@ impl RenderTarget {
                 },
             })],
             depth_stencil_attachment: None,
-            timestamp_writes: None,
+            timestamp_writes: resources.gpu_profiler.timestamp_writes(label),
             occlusion_query_set: None,
         })
     }
 
     pub fn begin_raw_render_pass<'a>(
         &'a self,
+        resources: &'a GpuCommonResources,
         encoder: &'a mut wgpu::CommandEncoder,
         label: Option<&str>,
     ) -> wgpu::RenderPass<'a> {
@@ -159,12 +210,20 @@ impl RenderTarget {
                 },
             })],
             depth_stencil_attachment: None,
-            timestamp_writes: None,
+            timestamp_writes: resources.gpu_profiler.timestamp_writes(label),
             occlusion_query_set: None,
         })
     }
 
+    /// Bind group sampling the texture as sRGB, decoding the colors to linear space
     pub fn bind_group(&self) -> &TextureBindGroup {
         &self.bind_group
     }
+
+    /// Bind group sampling the texture without the sRGB decoding
+    ///
+    /// Drawing with it to a non-sRGB target copies the sRGB-encoded colors as-is, which is what we want when the surface is not sRGB.
+    pub fn raw_bind_group(&self) -> &TextureBindGroup {
+        &self.raw_bind_group
+    }
 }
@@ -0,0 +1,158 @@
+//! A pool of render targets, allowing the textures to be reused instead of allocating new ones.
+//!
+//! Render targets are returned to the pool when the [`PooledRenderTarget`] is dropped.
+//! They only become available for reuse after the end of the frame, as the GPU may still be using them.
+//! Free render targets exceeding the memory budget are deallocated at the end of the frame.
+
+use std::{
+    collections::VecDeque,
+    ops::Deref,
+    sync::{Arc, Mutex},
+};
+
+use crate::{GpuCommonResources, RenderTarget};
+
+fn size_in_bytes((width, height): (u32, u32)) -> u64 {
+    // all the render targets are RGBA8
+    width as u64 * height as u64 * 4
+}
+
+#[derive(Debug, Default, Copy, Clone)]
+pub struct RenderTargetPoolStats {
+    /// Number of render targets allocated since the start
+    pub allocations: u64,
+    /// Number of render targets reused from the pool since the start
+    pub reuses: u64,
+    pub in_use: usize,
+    pub free: usize,
+    pub bytes_in_use: u64,
+    pub bytes_free: u64,
+    pub budget: u64,
+}
+
+struct PoolState {
+    budget: u64,
+    /// Render targets available for reuse, oldest first
+    free: VecDeque<RenderTarget>,
+    /// Render targets released during the current frame
+    released: Vec<RenderTarget>,
+    stats: RenderTargetPoolStats,
+}
+
+pub struct RenderTargetPool {
+    state: Arc<Mutex<PoolState>>,
+}
+
+impl RenderTargetPool {
+    pub const DEFAULT_BUDGET: u64 = 256 * 1024 * 1024;
+
+    /// Creates a pool keeping at most `budget` bytes of render targets allocated (in use ones are not limited)
+    pub fn new(budget: u64) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(PoolState {
+                budget,
+                free: VecDeque::new(),
+                released: Vec::new(),
+                stats: RenderTargetPoolStats {
+                    budget,
+                    ..Default::default()
+                },
+            })),
+        }
+    }
+
+    pub fn acquire(
+        &self,
+        resources: &GpuCommonResources,
+        size: (u32, u32),
+        label: Option<&str>,
+    ) -> PooledRenderTarget {
+        let mut state = self.state.lock().unwrap();
+
+        let target = match state.free.iter().position(|t| t.size() == size) {
+            Some(index) => {
+                state.stats.reuses += 1;
+                state.stats.free -= 1;
+                state.stats.bytes_free -= size_in_bytes(size);
+                state.free.remove(index).unwrap()
+            }
+            None => {
+                state.stats.allocations += 1;
+                RenderTarget::new(resources, size, label)
+            }
+        };
+        state.stats.in_use += 1;
+        state.stats.bytes_in_use += size_in_bytes(size);
+
+        PooledRenderTarget {
+            target: Some(target),
+            pool: self.state.clone(),
+        }
+    }
+
+    /// Makes the render targets released during the frame available for reuse and frees the ones exceeding the budget
+    pub fn end_frame(&self) {
+        let mut state = self.state.lock().unwrap();
+        let state = &mut *state;
+
+        state.free.extend(state.released.drain(..));
+
+        while state.stats.bytes_in_use + state.stats.bytes_free > state.budget {
+            let Some(target) = state.free.pop_front() else {
+                break;
+            };
+            state.stats.free -= 1;
+            state.stats.bytes_free -= size_in_bytes(target.size());
+        }
+    }
+
+    pub fn set_budget(&self, budget: u64) {
+        let mut state = self.state.lock().unwrap();
+        state.budget = budget;
+        state.stats.budget = budget;
+    }
+
+    pub fn stats(&self) -> RenderTargetPoolStats {
+        self.state.lock().unwrap().stats
+    }
+}
+
+/// A render target borrowed from a [`RenderTargetPool`], returned to it on drop
+pub struct PooledRenderTarget {
+    target: Option<RenderTarget>,
+    pool: Arc<Mutex<PoolState>>,
+}
+
+impl PooledRenderTarget {
+    pub fn resize(&mut self, resources: &GpuCommonResources, size: (u32, u32)) {
+        let target = self.target.as_mut().unwrap();
+        let old_size = target.size();
+        target.resize(resources, size);
+
+        let mut state = self.pool.lock().unwrap();
+        state.stats.bytes_in_use =
+            state.stats.bytes_in_use - size_in_bytes(old_size) + size_in_bytes(size);
+    }
+}
+
+impl Deref for PooledRenderTarget {
+    type Target = RenderTarget;
+
+    fn deref(&self) -> &Self::Target {
+        self.target.as_ref().unwrap()
+    }
+}
+
+impl Drop for PooledRenderTarget {
+    fn drop(&mut self) {
+        let target = self.target.take().unwrap();
+        let bytes = size_in_bytes(target.size());
+
+        let mut state = self.pool.lock().unwrap();
+        state.stats.in_use -= 1;
+        state.stats.bytes_in_use -= bytes;
+        state.stats.free += 1;
+        state.stats.bytes_free += bytes;
+        state.released.push(target);
+    }
+}
@@ -0,0 +1,98 @@
+//! Samplers shared by all the textures.
+//!
+//! The textures created at runtime (the render targets, the movie frames and the dynamic atlas) take their sampler from the store when building their bind group, instead of creating a sampler each.
+
+/// How the texels are interpolated when a texture is sampled
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TextureFilter {
+    /// Sharp pixels, for the pictures drawn without scaling that shouldn't get blurry at fractional offsets
+    Nearest,
+    #[default]
+    Linear,
+}
+
+/// What is sampled outside of the `[0, 1]` texture coordinates
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TextureWrap {
+    /// The edge texels are repeated, used for everything drawn once, like the movie frames
+    #[default]
+    Clamp,
+    /// The texture is tiled, used for the scrolling backgrounds
+    Repeat,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SamplerMode {
+    pub filter: TextureFilter,
+    pub wrap: TextureWrap,
+}
+
+impl SamplerMode {
+    pub const COUNT: usize = 4;
+
+    pub const ALL: [SamplerMode; Self::COUNT] = [
+        SamplerMode::new(TextureFilter::Nearest, TextureWrap::Clamp),
+        SamplerMode::new(TextureFilter::Linear, TextureWrap::Clamp),
+        SamplerMode::new(TextureFilter::Nearest, TextureWrap::Repeat),
+        SamplerMode::new(TextureFilter::Linear, TextureWrap::Repeat),
+    ];
+
+    pub const fn new(filter: TextureFilter, wrap: TextureWrap) -> Self {
+        Self { filter, wrap }
+    }
+
+    /// The position of the mode in [`SamplerMode::ALL`]
+    fn index(self) -> usize {
+        let filter = match self.filter {
+            TextureFilter::Nearest => 0,
+            TextureFilter::Linear => 1,
+        };
+        let wrap = match self.wrap {
+            TextureWrap::Clamp => 0,
+            TextureWrap::Repeat => 2,
+        };
+        filter + wrap
+    }
+
+    fn descriptor(self, label: Option<&str>) -> wgpu::SamplerDescriptor {
+        let address_mode = match self.wrap {
+            TextureWrap::Clamp => wgpu::AddressMode::ClampToEdge,
+            TextureWrap::Repeat => wgpu::AddressMode::Repeat,
+        };
+        let filter = match self.filter {
+            TextureFilter::Nearest => wgpu::FilterMode::Nearest,
+            TextureFilter::Linear => wgpu::FilterMode::Linear,
+        };
+
+        wgpu::SamplerDescriptor {
+            label,
+            address_mode_u: address_mode,
+            address_mode_v: address_mode,
+            address_mode_w: address_mode,
+            mag_filter: filter,
+            min_filter: filter,
+            // none of the textures have mipmaps
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        }
+    }
+}
+
+/// Holds a sampler for each [`SamplerMode`], created once for the device
+pub struct SamplerStore {
+    samplers: [wgpu::Sampler; SamplerMode::COUNT],
+}
+
+impl SamplerStore {
+    pub fn new(device: &wgpu::Device) -> Self {
+        Self {
+            samplers: SamplerMode::ALL.map(|mode| {
+                device.create_sampler(&mode.descriptor(Some(&format!("{:?} Sampler", mode))))
+            }),
+        }
+    }
+
+    pub fn get(&self, mode: SamplerMode) -> &wgpu::Sampler {
+        &self.samplers[mode.index()]
+    }
+}
@@ -4,7 +4,7 @@ use glam::{vec2, vec3, vec4, Vec4};
 use wgpu::util::DeviceExt;
 
 use crate::{
-    vertices::{PosColTexVertex, PosVertex, TextVertex, VertexSource},
+    vertices::{ButtonVertex, PosColTexVertex, PosVertex, TextVertex, VertexSource, WindowVertex},
     GpuCommonResources, VIRTUAL_HEIGHT, VIRTUAL_WIDTH,
 };
 
@@ -28,6 +28,16 @@ impl Vertex for TextVertex {
         TextVertex::desc()
     }
 }
+impl Vertex for WindowVertex {
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        WindowVertex::desc()
+    }
+}
+impl Vertex for ButtonVertex {
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        ButtonVertex::desc()
+    }
+}
 
 pub struct VertexBuffer<T: Vertex> {
     buffer: wgpu::Buffer,
@@ -54,6 +54,30 @@ pub struct TextVertex {
     pub fade: f32,
 }
 
+/// Vertex of a menu window, drawn as a nine-patch (see [`NinePatch`](crate::NinePatch))
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Vertex, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct WindowVertex {
+    #[f32x2(0)]
+    pub position: Vec2,
+    #[f32x2(1)]
+    pub texture_coordinate: Vec2,
+}
+
+/// Vertex of a menu button
+///
+/// The button texture contains both the normal and the flashing (highlighted) look of the button, they are blended by the flash intensity.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Vertex, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ButtonVertex {
+    #[f32x2(0)]
+    pub position: Vec2,
+    #[f32x2(1)]
+    pub texture_coordinate: Vec2,
+    #[f32x2(2)]
+    pub flash_texture_coordinate: Vec2,
+}
+
 pub enum VertexSource<'a, T> {
     VertexBuffer {
         vertex_buffer: &'a wgpu::Buffer, // TODO: support multiple vertex buffers
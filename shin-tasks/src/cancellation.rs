@@ -0,0 +1,218 @@
+//! Cooperative cancellation of the spawned futures.
+//!
+//! A future wrapped with [`CancellationToken::run`] completes with [`Cancelled`] as soon as the token is cancelled, dropping the wrapped future at its next suspension point.
+//! [`TaskGroup`] ties the lifetime of a set of tasks to a value, so that the work nobody needs anymore can be abandoned by just dropping the group.
+
+use std::{
+    collections::HashMap,
+    fmt::{Display, Formatter},
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll, Waker},
+};
+
+use crate::TaskPool;
+
+/// The error returned by the futures that were cancelled before completing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl Display for Cancelled {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "the task was cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+#[derive(Default)]
+struct TokenState {
+    cancelled: AtomicBool,
+    next_waiter_id: AtomicU64,
+    /// Wakers of the [`Cancellable`] futures waiting on the token, by their ids
+    waiters: Mutex<HashMap<u64, Waker>>,
+}
+
+/// A flag that can be shared between the tasks, allowing to cancel all of them at once
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    state: Arc<TokenState>,
+}
+
+impl CancellationToken {
+    /// Creates a new, not cancelled, token
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels all the futures running with this token. They will complete with [`Cancelled`] the next time they are polled
+    pub fn cancel(&self) {
+        self.state.cancelled.store(true, Ordering::Release);
+        let waiters = std::mem::take(&mut *self.state.waiters.lock().unwrap());
+        for waker in waiters.into_values() {
+            waker.wake();
+        }
+    }
+
+    /// Returns `true` if [`CancellationToken::cancel`] was called on this token or one of its clones
+    pub fn is_cancelled(&self) -> bool {
+        self.state.cancelled.load(Ordering::Acquire)
+    }
+
+    /// Wraps the future, so that it's stopped when the token is cancelled
+    pub fn run<F: Future>(&self, future: F) -> Cancellable<F> {
+        Cancellable {
+            state: self.state.clone(),
+            id: self.state.next_waiter_id.fetch_add(1, Ordering::Relaxed),
+            future: Box::pin(future),
+        }
+    }
+}
+
+impl std::fmt::Debug for CancellationToken {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CancellationToken")
+            .field("cancelled", &self.is_cancelled())
+            .finish()
+    }
+}
+
+/// A future returned by [`CancellationToken::run`]
+pub struct Cancellable<F: Future> {
+    state: Arc<TokenState>,
+    id: u64,
+    future: Pin<Box<F>>,
+}
+
+impl<F: Future> Future for Cancellable<F> {
+    type Output = Result<F::Output, Cancelled>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // register before checking the flag, so that a cancellation happening in between is not missed
+        self.state
+            .waiters
+            .lock()
+            .unwrap()
+            .insert(self.id, cx.waker().clone());
+        if self.state.cancelled.load(Ordering::Acquire) {
+            return Poll::Ready(Err(Cancelled));
+        }
+
+        self.future.as_mut().poll(cx).map(Ok)
+    }
+}
+
+impl<F: Future> Drop for Cancellable<F> {
+    fn drop(&mut self) {
+        self.state.waiters.lock().unwrap().remove(&self.id);
+    }
+}
+
+/// A set of tasks cancelled together, either with [`TaskGroup::cancel`] or when the group is dropped
+#[derive(Debug, Default)]
+pub struct TaskGroup {
+    token: CancellationToken,
+}
+
+impl TaskGroup {
+    /// Creates an empty group
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The token cancelled together with the group, for the futures spawned in other ways
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+
+    /// Cancels all the tasks spawned in the group. The tasks spawned afterwards are cancelled immediately
+    pub fn cancel(&self) {
+        self.token.cancel();
+    }
+
+    /// Spawns a future on the `pool` as a part of the group
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn spawn<T>(
+        &self,
+        pool: &TaskPool,
+        future: impl Future<Output = T> + Send + 'static,
+    ) -> crate::Task<Result<T, Cancelled>>
+    where
+        T: Send + 'static,
+    {
+        pool.spawn(self.token.run(future))
+    }
+
+    /// Spawns a future on the `pool` as a part of the group
+    #[cfg(target_arch = "wasm32")]
+    pub fn spawn<T>(
+        &self,
+        pool: &TaskPool,
+        future: impl Future<Output = T> + 'static,
+    ) -> crate::Task<Result<T, Cancelled>>
+    where
+        T: 'static,
+    {
+        pool.spawn(self.token.run(future))
+    }
+}
+
+impl Drop for TaskGroup {
+    fn drop(&mut self) {
+        self.token.cancel();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_lite::future;
+
+    use super::*;
+
+    #[test]
+    fn test_not_cancelled() {
+        let token = CancellationToken::new();
+        assert_eq!(future::block_on(token.run(async { 42 })), Ok(42));
+    }
+
+    #[test]
+    fn test_cancel_pending() {
+        let pool = TaskPool::new();
+        let group = TaskGroup::new();
+        let (_tx, rx) = async_channel::bounded::<()>(1);
+
+        // never completes by itself, as nothing is ever sent
+        let task = group.spawn(&pool, async move { rx.recv().await });
+        group.cancel();
+
+        assert_eq!(future::block_on(task), Err(Cancelled));
+    }
+
+    #[test]
+    fn test_drop_group() {
+        let pool = TaskPool::new();
+        let group = TaskGroup::new();
+        let token = group.token().clone();
+        let task = group.spawn(&pool, future::pending::<()>());
+        drop(group);
+
+        assert!(token.is_cancelled());
+        assert_eq!(future::block_on(task), Err(Cancelled));
+    }
+
+    #[test]
+    fn test_spawn_after_cancel() {
+        let pool = TaskPool::new();
+        let group = TaskGroup::new();
+        group.cancel();
+
+        assert_eq!(
+            future::block_on(group.spawn(&pool, async { 42 })),
+            Err(Cancelled)
+        );
+    }
+}
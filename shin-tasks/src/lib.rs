@@ -7,6 +7,15 @@ pub use slice::{ParallelSlice, ParallelSliceMut};
 mod task;
 pub use task::Task;
 
+mod cancellation;
+pub use cancellation::{Cancellable, CancellationToken, Cancelled, TaskGroup};
+
+mod panic;
+pub use panic::TaskPanic;
+
+mod priority;
+pub use priority::TaskPriority;
+
 #[cfg(not(target_arch = "wasm32"))]
 mod task_pool;
 #[cfg(not(target_arch = "wasm32"))]
@@ -15,7 +24,9 @@ pub use task_pool::{Scope, TaskPool, TaskPoolBuilder};
 #[cfg(target_arch = "wasm32")]
 mod single_threaded_task_pool;
 #[cfg(target_arch = "wasm32")]
-pub use single_threaded_task_pool::{Scope, TaskPool, TaskPoolBuilder, ThreadExecutor};
+pub use single_threaded_task_pool::{
+    tick_global_task_pools_on_main_thread, Scope, TaskPool, TaskPoolBuilder, ThreadExecutor,
+};
 
 mod usages;
 #[cfg(not(target_arch = "wasm32"))]
@@ -0,0 +1,102 @@
+//! Capturing of the panics happening inside the spawned tasks.
+//!
+//! A task that panics is normally just canceled, and awaiting it panics again with a message that says nothing about the original problem.
+//! The tasks spawned with [`TaskPool::spawn_named`](crate::TaskPool::spawn_named) instead complete with a [`TaskPanic`] describing which task panicked and why.
+//! They also run inside a tracing span with the task name, so that the events they emit can be attributed.
+
+use std::{
+    any::Any,
+    borrow::Cow,
+    fmt::{Display, Formatter},
+    future::Future,
+    panic::AssertUnwindSafe,
+};
+
+use futures_lite::FutureExt;
+use tracing::{error, info_span, Instrument};
+
+/// The error returned by a named task that panicked
+#[derive(Debug, Clone)]
+pub struct TaskPanic {
+    /// The name the task was spawned with
+    pub task: Cow<'static, str>,
+    /// The panic message, if the payload was a string
+    pub message: Option<String>,
+}
+
+impl TaskPanic {
+    fn new(task: Cow<'static, str>, payload: Box<dyn Any + Send>) -> Self {
+        let message = match payload.downcast::<String>() {
+            Ok(message) => Some(*message),
+            Err(payload) => payload.downcast_ref::<&str>().map(|s| s.to_string()),
+        };
+        Self { task, message }
+    }
+}
+
+impl Display for TaskPanic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.message {
+            Some(message) => write!(f, "task {:?} panicked: {}", self.task, message),
+            None => write!(f, "task {:?} panicked", self.task),
+        }
+    }
+}
+
+impl std::error::Error for TaskPanic {}
+
+/// Runs the future in a span named after the task, turning its panics into [`TaskPanic`] errors
+pub(crate) async fn run_named<F: Future>(
+    name: Cow<'static, str>,
+    future: F,
+) -> Result<F::Output, TaskPanic> {
+    let span = info_span!("task", name = %name);
+    let result = AssertUnwindSafe(future)
+        .catch_unwind()
+        .instrument(span.clone())
+        .await;
+
+    result.map_err(|payload| {
+        let panic = TaskPanic::new(name, payload);
+        span.in_scope(|| error!("{}", panic));
+        panic
+    })
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use futures_lite::future;
+
+    use crate::TaskPool;
+
+    #[test]
+    fn test_named_task_ok() {
+        let pool = TaskPool::new();
+        let task = pool.spawn_named("answer", async { 42 });
+        assert_eq!(future::block_on(task).unwrap(), 42);
+    }
+
+    #[test]
+    fn test_named_task_panic() {
+        let pool = TaskPool::new();
+        let task = pool.spawn_named(format!("decode {}", "/bg/test.pic"), async {
+            if true {
+                panic!("invalid header");
+            }
+            42
+        });
+
+        let panic = future::block_on(task).unwrap_err();
+        assert_eq!(panic.task, "decode /bg/test.pic");
+        assert_eq!(panic.message.as_deref(), Some("invalid header"));
+    }
+
+    #[test]
+    fn test_pool_survives_panic() {
+        let pool = TaskPool::new();
+        for _ in 0..pool.thread_num() * 2 {
+            let _ = future::block_on(pool.spawn_named("panicking", async { panic!("oops") }));
+        }
+        assert_eq!(future::block_on(pool.spawn(async { 42 })), 42);
+    }
+}
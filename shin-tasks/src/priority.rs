@@ -0,0 +1,54 @@
+//! Priority classes of the spawned tasks.
+//!
+//! The executors don't order the tasks in any way, so the priority is implemented by limiting how many background tasks can run at the same time,
+//! which keeps some of the threads available for the interactive work.
+
+/// How urgently the result of a task is needed, see [`TaskPool::spawn_with_priority`](crate::TaskPool::spawn_with_priority)
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TaskPriority {
+    /// Something is waiting for the result right now, like a layer that has to be shown. This is what [`TaskPool::spawn`](crate::TaskPool::spawn) uses
+    #[default]
+    Interactive,
+    /// Speculative or long-running work, like the preloading of the assets that might be needed later
+    Background,
+}
+
+/// Limits the number of the background tasks that are started but not finished yet
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone)]
+pub(crate) struct BackgroundSlots {
+    // the slots are taken by sending to a bounded channel and freed by receiving from it
+    sender: async_channel::Sender<()>,
+    receiver: async_channel::Receiver<()>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl BackgroundSlots {
+    pub fn new(count: usize) -> Self {
+        let (sender, receiver) = async_channel::bounded(count.max(1));
+        Self { sender, receiver }
+    }
+
+    /// Waits for a free slot, it's taken until the returned guard is dropped
+    pub async fn acquire(&self) -> BackgroundSlotGuard {
+        self.sender
+            .send(())
+            .await
+            .expect("the receiver is owned by the slots");
+        BackgroundSlotGuard {
+            receiver: self.receiver.clone(),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) struct BackgroundSlotGuard {
+    receiver: async_channel::Receiver<()>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Drop for BackgroundSlotGuard {
+    fn drop(&mut self) {
+        let _ = self.receiver.try_recv();
+    }
+}
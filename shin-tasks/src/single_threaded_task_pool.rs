@@ -3,16 +3,20 @@ use std::{
     marker::PhantomData,
     mem,
     sync::{Arc, Mutex},
+    time::Duration,
 };
 
+use instant::Instant;
+
+use crate::Task;
+
 /// Used to create a TaskPool
 #[derive(Debug, Default, Clone)]
 pub struct TaskPoolBuilder {}
 
 /// This is a dummy struct for wasm support to provide the same api as with the multithreaded
 /// task pool. In the case of the multithreaded task pool this struct is used to spawn
-/// tasks on a specific thread. But the wasm task pool just calls
-/// [`wasm_bindgen_futures::spawn_local`] for spawning which just runs tasks on the main thread
+/// tasks on a specific thread. But the wasm task pool runs all the tasks on the main thread
 /// and so the [`ThreadExecutor`] does nothing.
 #[derive(Default)]
 pub struct ThreadExecutor<'a>(PhantomData<&'a ()>);
@@ -127,27 +131,44 @@ impl TaskPool {
             .collect()
     }
 
-    /// Spawns a static future onto the JS event loop. For now it is returning FakeTask
-    /// instance with no-op detach method. Returning real Task is possible here, but tricky:
-    /// future is running on JS event loop, Task is running on async_executor::LocalExecutor
-    /// so some proxy future is needed. Moreover currently we don't have long-living
-    /// LocalExecutor here (above `spawn` implementation creates temporary one)
-    /// But for typical use cases it seems that current implementation should be sufficient:
-    /// caller can spawn long-running future writing results to some channel / event queue
-    /// and simply call detach on returned Task (like AssetServer does) - spawned future
-    /// can write results to some channel / event queue.
-    pub fn spawn<T>(&self, future: impl Future<Output = T> + 'static) -> FakeTask
+    /// Spawns a static future onto the main thread executor. The returned Task is a future. It can
+    /// also be cancelled and "detached" allowing it to continue running without having to be
+    /// polled by the end-user.
+    ///
+    /// The tasks only make progress when [`tick_global_task_pools_on_main_thread`] is called.
+    pub fn spawn<T>(&self, future: impl Future<Output = T> + 'static) -> Task<T>
     where
         T: 'static,
     {
-        wasm_bindgen_futures::spawn_local(async move {
-            future.await;
-        });
-        FakeTask
+        Task::new(LOCAL_EXECUTOR.with(|executor| executor.spawn(future)))
     }
 
-    /// Spawns a static future on the JS event loop. This is exactly the same as [`TaskSpool::spawn`].
-    pub fn spawn_local<T>(&self, future: impl Future<Output = T> + 'static) -> FakeTask
+    /// Spawns a static future onto the main thread executor, in a tracing span with the `name` and catching its panics.
+    pub fn spawn_named<T>(
+        &self,
+        name: impl Into<std::borrow::Cow<'static, str>>,
+        future: impl Future<Output = T> + 'static,
+    ) -> Task<Result<T, crate::TaskPanic>>
+    where
+        T: 'static,
+    {
+        self.spawn(crate::panic::run_named(name.into(), future))
+    }
+
+    /// Spawns a static future onto the main thread executor. The priority is ignored, as everything runs on the main thread anyway.
+    pub fn spawn_with_priority<T>(
+        &self,
+        _priority: crate::TaskPriority,
+        future: impl Future<Output = T> + 'static,
+    ) -> Task<T>
+    where
+        T: 'static,
+    {
+        self.spawn(future)
+    }
+
+    /// Spawns a static future onto the main thread executor. This is exactly the same as [`TaskPool::spawn`].
+    pub fn spawn_local<T>(&self, future: impl Future<Output = T> + 'static) -> Task<T>
     where
         T: 'static,
     {
@@ -155,12 +176,22 @@ impl TaskPool {
     }
 }
 
-#[derive(Debug)]
-pub struct FakeTask;
+thread_local! {
+    /// Runs the tasks of all the pools, there is only one thread anyway
+    static LOCAL_EXECUTOR: async_executor::LocalExecutor<'static> = async_executor::LocalExecutor::new();
+}
 
-impl FakeTask {
-    /// No op on the single threaded task pool
-    pub fn detach(self) {}
+/// How long [`tick_global_task_pools_on_main_thread`] can run the tasks for, so that the frame is not delayed too much
+const TICK_BUDGET: Duration = Duration::from_millis(4);
+
+/// Runs the spawned tasks until none of them are ready to make progress or the time budget of a
+/// few milliseconds is used up.
+///
+/// Without threads this is the only place the tasks run, so it must be called every frame.
+/// A single poll of a task can't be interrupted, so the long CPU-heavy tasks can still exceed the budget.
+pub fn tick_global_task_pools_on_main_thread() {
+    let start = Instant::now();
+    LOCAL_EXECUTOR.with(|executor| while start.elapsed() < TICK_BUDGET && executor.try_tick() {});
 }
 
 /// A `TaskPool` scope for running one or more non-`'static` futures.
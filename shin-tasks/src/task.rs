@@ -12,6 +12,7 @@ use std::{
 /// more gracefully and wait until it stops running, use the [`cancel()`][Task::cancel()] method.
 ///
 /// Tasks that panic get immediately canceled. Awaiting a canceled task also causes a panic.
+/// Use [`TaskPool::spawn_named`](crate::TaskPool::spawn_named) to get the panic as an error instead.
 /// Wraps `async_executor::Task`
 #[derive(Debug)]
 #[must_use = "Tasks are canceled when dropped, use `.detach()` to run them in the background."]
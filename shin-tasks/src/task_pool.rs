@@ -1,4 +1,5 @@
 use std::{
+    borrow::Cow,
     future::Future,
     marker::PhantomData,
     mem,
@@ -12,8 +13,10 @@ use concurrent_queue::ConcurrentQueue;
 use futures_lite::{future, FutureExt};
 
 use crate::{
+    panic::run_named,
+    priority::BackgroundSlots,
     thread_executor::{ThreadExecutor, ThreadExecutorTicker},
-    Task,
+    Task, TaskPanic, TaskPriority,
 };
 
 struct CallOnDrop(Option<Arc<dyn Fn() + Send + Sync + 'static>>);
@@ -107,6 +110,8 @@ pub struct TaskPool {
     /// Inner state of the pool
     threads: Vec<JoinHandle<()>>,
     shutdown_tx: async_channel::Sender<()>,
+    /// Taken by the tasks spawned with [`TaskPriority::Background`], half of the threads
+    background_slots: BackgroundSlots,
 }
 
 impl TaskPool {
@@ -181,6 +186,7 @@ impl TaskPool {
             executor,
             threads,
             shutdown_tx,
+            background_slots: BackgroundSlots::new(num_threads / 2),
         }
     }
 
@@ -497,6 +503,44 @@ impl TaskPool {
         Task::new(self.executor.spawn(future))
     }
 
+    /// Same as [`TaskPool::spawn`], but the task runs in a tracing span with the `name`, and a
+    /// panic inside it is returned as a [`TaskPanic`] instead of canceling the task.
+    pub fn spawn_named<T>(
+        &self,
+        name: impl Into<Cow<'static, str>>,
+        future: impl Future<Output = T> + Send + 'static,
+    ) -> Task<Result<T, TaskPanic>>
+    where
+        T: Send + 'static,
+    {
+        self.spawn(run_named(name.into(), future))
+    }
+
+    /// Same as [`TaskPool::spawn`], but the [`TaskPriority::Background`] tasks wait until there are
+    /// less than a half of the pool threads worth of other background tasks running.
+    ///
+    /// The limit counts the tasks that are started and not finished, including the ones waiting for
+    /// IO, so it's best to keep the background tasks short.
+    pub fn spawn_with_priority<T>(
+        &self,
+        priority: TaskPriority,
+        future: impl Future<Output = T> + Send + 'static,
+    ) -> Task<T>
+    where
+        T: Send + 'static,
+    {
+        match priority {
+            TaskPriority::Interactive => self.spawn(future),
+            TaskPriority::Background => {
+                let slots = self.background_slots.clone();
+                self.spawn(async move {
+                    let _slot = slots.acquire().await;
+                    future.await
+                })
+            }
+        }
+    }
+
     /// Spawns a static future on the thread-local async executor for the current thread. The task
     /// will run entirely on the thread the task was spawned on.  The returned Task is a future.
     /// It can also be cancelled and "detached" allowing it to continue running without having
@@ -868,4 +912,39 @@ mod tests {
         assert!(!thread_check_failed.load(Ordering::Acquire));
         assert_eq!(count.load(Ordering::Acquire), 200);
     }
+
+    #[test]
+    fn test_background_priority_limit() {
+        let pool = TaskPoolBuilder::new().num_threads(4).build();
+        let running = Arc::new(AtomicI32::new(0));
+        let (gate_tx, gate_rx) = async_channel::unbounded::<()>();
+
+        let tasks = (0..4)
+            .map(|_| {
+                let running = running.clone();
+                let gate_rx = gate_rx.clone();
+                pool.spawn_with_priority(TaskPriority::Background, async move {
+                    running.fetch_add(1, Ordering::AcqRel);
+                    let _ = gate_rx.recv().await;
+                    running.fetch_sub(1, Ordering::AcqRel);
+                })
+            })
+            .collect::<Vec<_>>();
+
+        // half of the threads are available to the background tasks
+        while running.load(Ordering::Acquire) < 2 {
+            std::thread::yield_now();
+        }
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert_eq!(running.load(Ordering::Acquire), 2);
+
+        // an interactive task is not blocked by them
+        assert_eq!(future::block_on(pool.spawn(async { 42 })), 42);
+
+        drop(gate_tx);
+        for task in tasks {
+            future::block_on(task);
+        }
+        assert_eq!(running.load(Ordering::Acquire), 0);
+    }
 }
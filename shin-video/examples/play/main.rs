@@ -9,7 +9,8 @@ use glam::Mat4;
 use shin_audio::AudioManager;
 use shin_core::time::Ticks;
 use shin_render::{
-    BindGroupLayouts, Camera, GpuCommonResources, Pipelines, RenderTarget, Renderable,
+    BindGroupLayouts, Camera, GpuCommonResources, GpuProfiler, Pipelines, RenderTarget,
+    RenderTargetPool, Renderable, SamplerStore,
 };
 use shin_video::{mp4::Mp4, VideoPlayer};
 use winit::{
@@ -73,7 +74,9 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
     surface.configure(&device, &config);
 
     let bind_group_layouts = BindGroupLayouts::new(&device);
+    let sampler_store = SamplerStore::new(&device);
     let pipelines = Pipelines::new(&device, &bind_group_layouts, swapchain_format);
+    let gpu_profiler = GpuProfiler::new(&device, &queue);
 
     let window_size = (window.inner_size().width, window.inner_size().height);
     let mut camera = Camera::new(window_size);
@@ -83,7 +86,10 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
         queue,
         render_buffer_size: RwLock::new(camera.render_buffer_size()),
         bind_group_layouts,
+        sampler_store,
         pipelines,
+        render_target_pool: RenderTargetPool::new(RenderTargetPool::DEFAULT_BUDGET),
+        gpu_profiler,
     });
 
     let audio_manager = AudioManager::new();
@@ -91,7 +97,8 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
     // let file = File::open("ship1.mp4").unwrap();
     let file = File::open("op1.mp4").unwrap();
     let mp4 = Mp4::new(file).unwrap();
-    let mut video_player = VideoPlayer::new(&resources, &audio_manager, mp4).unwrap();
+    let mut video_player =
+        VideoPlayer::new(&resources, &audio_manager, mp4, Default::default()).unwrap();
 
     let render_target = RenderTarget::new(
         &resources,
@@ -147,16 +154,28 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
 
                     video_player.update(Ticks::from_duration(delta_time), &resources.queue);
 
-                    let frame = surface
-                        .get_current_texture()
-                        .expect("Failed to acquire next swap chain texture");
+                    let frame = match surface.get_current_texture() {
+                        Ok(frame) => frame,
+                        // reconfigure and skip the frame, the next one will be drawn on the new surface
+                        Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                            surface.configure(&resources.device, &config);
+                            window.request_redraw();
+                            return;
+                        }
+                        Err(wgpu::SurfaceError::Timeout) => {
+                            window.request_redraw();
+                            return;
+                        }
+                        Err(e) => panic!("Failed to acquire next swap chain texture: {:?}", e),
+                    };
                     let view = frame
                         .texture
                         .create_view(&wgpu::TextureViewDescriptor::default());
 
                     let mut encoder = resources.start_encoder();
                     {
-                        let mut rpass = render_target.begin_raw_render_pass(&mut encoder, None);
+                        let mut rpass =
+                            render_target.begin_raw_render_pass(&resources, &mut encoder, None);
                         let proj = render_target.projection_matrix();
 
                         video_player.render(&resources, &mut rpass, Mat4::IDENTITY, proj);
@@ -99,8 +99,13 @@ impl<S: Read + Seek> AudioFrameSource for AacFrameSource<S> {
         }
     }
 
-    fn samples_seek(&mut self, _sample_position: u32) -> anyhow::Result<u32> {
-        todo!("Seeking AAC stream")
+    /// Assumes the track timescale is the sample rate, which is how the AAC tracks are usually stored
+    fn samples_seek(&mut self, sample_position: u32) -> anyhow::Result<u32> {
+        let frame_start = self.track.seek_to_sync_sample(sample_position as u64) as u32;
+        self.decoder.reset();
+        self.samples_position = frame_start;
+
+        Ok(sample_position - frame_start)
     }
 
     fn current_sample_position(&self) -> u32 {
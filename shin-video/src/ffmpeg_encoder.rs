@@ -0,0 +1,132 @@
+//! This module implements encoding of the frames rendered by the engine to a video file, by piping them to an ffmpeg process.
+//!
+//! The frames are passed as raw RGBA, ffmpeg picks the container by the extension of the output file.
+
+use std::{
+    io::Write,
+    path::Path,
+    process::{Child, ChildStdin, Command, Stdio},
+};
+
+use anyhow::{bail, Context, Result};
+use tracing::debug;
+
+const FFMPEG_LOG_LEVEL: &str = "error";
+
+pub struct FfmpegVideoEncoder {
+    process: Child,
+    stdin: ChildStdin,
+    size: (u32, u32),
+}
+
+impl FfmpegVideoEncoder {
+    /// Starts encoding to `output`, overwriting it if it exists
+    ///
+    /// All the frames must have the `size`, they are shown for 1/`fps` seconds each.
+    pub fn new(output: &Path, size: (u32, u32), fps: u32) -> Result<Self> {
+        let ffmpeg = which::which("ffmpeg").context("Could not locate ffmpeg binary")?;
+
+        let (width, height) = size;
+        let mut process = Command::new(ffmpeg)
+            .arg("-hide_banner")
+            .arg("-loglevel")
+            .arg(FFMPEG_LOG_LEVEL)
+            .arg("-y")
+            .arg("-f")
+            .arg("rawvideo")
+            .arg("-pix_fmt")
+            .arg("rgba")
+            .arg("-s")
+            .arg(format!("{}x{}", width, height))
+            .arg("-framerate")
+            .arg(fps.to_string())
+            .arg("-i")
+            .arg("-")
+            // yuv420p needs even dimensions
+            .arg("-vf")
+            .arg("pad=ceil(iw/2)*2:ceil(ih/2)*2")
+            .arg("-pix_fmt")
+            .arg("yuv420p")
+            .arg(output)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .context("Could not spawn ffmpeg")?;
+        let stdin = process.stdin.take().unwrap();
+
+        debug!(
+            "Encoding {}x{} frames at {} fps to {}",
+            width,
+            height,
+            fps,
+            output.display()
+        );
+
+        Ok(Self {
+            process,
+            stdin,
+            size,
+        })
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    /// Writes a frame of RGBA pixels, row by row without padding
+    pub fn write_frame(&mut self, pixels: &[u8]) -> Result<()> {
+        let (width, height) = self.size;
+        assert_eq!(pixels.len(), (width * height * 4) as usize);
+
+        self.stdin
+            .write_all(pixels)
+            .context("Writing a frame to ffmpeg (did it exit?)")
+    }
+
+    /// Waits for ffmpeg to encode the remaining frames and to finalize the file
+    pub fn finish(self) -> Result<()> {
+        let Self {
+            mut process, stdin, ..
+        } = self;
+        // closing stdin signals the end of the stream
+        drop(stdin);
+
+        let status = process.wait().context("Waiting for ffmpeg")?;
+        if !status.success() {
+            bail!("ffmpeg exited with {}", status);
+        }
+        Ok(())
+    }
+}
+
+/// Combines the video stream of `video` with the audio of `audio` into `output`, without re-encoding the video
+pub fn mux_audio(video: &Path, audio: &Path, output: &Path) -> Result<()> {
+    let ffmpeg = which::which("ffmpeg").context("Could not locate ffmpeg binary")?;
+
+    let status = Command::new(ffmpeg)
+        .arg("-hide_banner")
+        .arg("-loglevel")
+        .arg(FFMPEG_LOG_LEVEL)
+        .arg("-y")
+        .arg("-i")
+        .arg(video)
+        .arg("-i")
+        .arg(audio)
+        .arg("-map")
+        .arg("0:v")
+        .arg("-map")
+        .arg("1:a")
+        .arg("-c:v")
+        .arg("copy")
+        .arg(output)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::inherit())
+        .status()
+        .context("Could not run ffmpeg")?;
+    if !status.success() {
+        bail!("ffmpeg exited with {}", status);
+    }
+    Ok(())
+}
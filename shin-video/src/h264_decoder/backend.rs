@@ -0,0 +1,167 @@
+//! Runtime selection of the h264 decoder backend.
+//!
+//! Which backends are compiled in depends on the crate features, but whether they actually work can only be found out at runtime
+//! (the ffmpeg binary might be missing, GStreamer might lack the h264 plugins, etc.).
+//! So each backend is probed before use, and the first one that works is picked.
+
+use std::{fmt::Display, str::FromStr, sync::Mutex};
+
+use anyhow::{bail, Context, Result};
+use once_cell::sync::Lazy;
+use tracing::{debug, info, warn};
+
+/// The environment variable that can be used to override the preferred h264 decoder backend
+pub const BACKEND_ENV_VARIABLE: &str = "SHIN_H264_DECODER";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum H264DecoderBackend {
+    /// Decodes using GStreamer's `decodebin`, which picks a hardware decoder if one is available
+    #[cfg(feature = "gstreamer")]
+    GStreamer,
+    /// Pipes the bitstream to an `ffmpeg` process
+    SpawnFfmpeg,
+}
+
+impl H264DecoderBackend {
+    /// All the backends compiled in, in the default order of preference
+    pub const ALL: &'static [H264DecoderBackend] = &[
+        #[cfg(feature = "gstreamer")]
+        H264DecoderBackend::GStreamer,
+        H264DecoderBackend::SpawnFfmpeg,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "gstreamer")]
+            H264DecoderBackend::GStreamer => "gstreamer",
+            H264DecoderBackend::SpawnFfmpeg => "ffmpeg",
+        }
+    }
+
+    /// Checks whether the backend can be used on this system
+    pub fn probe(&self) -> BackendProbe {
+        let (available, hardware_accelerated) = match self {
+            #[cfg(feature = "gstreamer")]
+            H264DecoderBackend::GStreamer => match super::gstreamer::probe() {
+                Ok(hardware_accelerated) => (Ok(()), hardware_accelerated),
+                Err(err) => (Err(format!("{:#}", err)), false),
+            },
+            H264DecoderBackend::SpawnFfmpeg => match super::spawn_ffmpeg::probe() {
+                Ok(()) => (Ok(()), false),
+                Err(err) => (Err(format!("{:#}", err)), false),
+            },
+        };
+
+        BackendProbe {
+            backend: *self,
+            available,
+            hardware_accelerated,
+        }
+    }
+
+    /// Returns the backends to try, in order: the preferred backend (if any) first, then all the others
+    pub fn candidates() -> Vec<H264DecoderBackend> {
+        let mut result = Vec::with_capacity(Self::ALL.len());
+        if let Some(preferred) = preferred_backend() {
+            result.push(preferred);
+        }
+        result.extend(Self::ALL.iter().filter(|&&b| !result.contains(&b)));
+        result
+    }
+}
+
+impl Display for H264DecoderBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for H264DecoderBackend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match Self::ALL.iter().find(|b| b.name().eq_ignore_ascii_case(s)) {
+            Some(&backend) => Ok(backend),
+            None => bail!(
+                "Unknown h264 decoder backend {:?}. Available backends: {}",
+                s,
+                Self::ALL
+                    .iter()
+                    .map(|b| b.name())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+}
+
+/// The result of checking whether a backend is usable
+#[derive(Debug, Clone)]
+pub struct BackendProbe {
+    pub backend: H264DecoderBackend,
+    /// `Err` contains a human-readable reason for why the backend can't be used
+    pub available: Result<(), String>,
+    pub hardware_accelerated: bool,
+}
+
+static PREFERRED_BACKEND: Lazy<Mutex<Option<H264DecoderBackend>>> = Lazy::new(|| {
+    let Some(value) = std::env::var_os(BACKEND_ENV_VARIABLE) else {
+        return Mutex::new(None);
+    };
+
+    let backend = value
+        .to_str()
+        .context("Value is not valid UTF-8")
+        .and_then(|v| v.parse());
+    match backend {
+        Ok(backend) => {
+            info!(
+                "Using h264 decoder backend {} from {}",
+                backend, BACKEND_ENV_VARIABLE
+            );
+            Mutex::new(Some(backend))
+        }
+        Err(err) => {
+            warn!("Ignoring {}: {:#}", BACKEND_ENV_VARIABLE, err);
+            Mutex::new(None)
+        }
+    }
+});
+
+/// Overrides the backend that is tried first (the `SHIN_H264_DECODER` environment variable is used by default).
+///
+/// Other backends are still used as a fallback if the preferred one fails.
+pub fn set_preferred_backend(backend: Option<H264DecoderBackend>) {
+    *PREFERRED_BACKEND.lock().unwrap() = backend;
+}
+
+fn preferred_backend() -> Option<H264DecoderBackend> {
+    *PREFERRED_BACKEND.lock().unwrap()
+}
+
+static PROBES: Lazy<Vec<BackendProbe>> = Lazy::new(|| {
+    H264DecoderBackend::ALL
+        .iter()
+        .map(|b| {
+            let probe = b.probe();
+            debug!("Probed h264 decoder backend: {:?}", probe);
+            probe
+        })
+        .collect()
+});
+
+/// Returns the probe results for all the backends, in the order they should be tried.
+///
+/// The probing is done only once, the results are cached.
+pub fn probe_backends() -> Vec<BackendProbe> {
+    H264DecoderBackend::candidates()
+        .into_iter()
+        .map(|backend| {
+            PROBES
+                .iter()
+                .find(|p| p.backend == backend)
+                .expect("All backends should be probed")
+                .clone()
+        })
+        .collect()
+}
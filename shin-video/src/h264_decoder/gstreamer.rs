@@ -24,6 +24,50 @@ fn init() {
     Lazy::force(&INIT_ONCE);
 }
 
+/// GStreamer elements decoding h264 in hardware. `decodebin` gives them a higher rank, so they will be picked when present
+const HARDWARE_DECODERS: &[&str] = &[
+    "vah264dec",
+    "vaapih264dec",
+    "nvh264dec",
+    "nvh264sldec",
+    "d3d11h264dec",
+    "d3d12h264dec",
+    "vtdec_hw",
+    "v4l2h264dec",
+    "v4l2slh264dec",
+];
+const SOFTWARE_DECODERS: &[&str] = &["avdec_h264", "openh264dec", "vtdec"];
+
+/// Checks that GStreamer can be initialized and has the elements we need.
+///
+/// Returns whether a hardware h264 decoder is available.
+pub fn probe() -> Result<bool> {
+    gst::init().context("Failed to initialize GStreamer")?;
+
+    for element in ["decodebin", "queue", "videoconvert", "appsrc", "appsink"] {
+        if gst::ElementFactory::find(element).is_none() {
+            bail!("GStreamer element {} is not available", element);
+        }
+    }
+
+    let find_any = |names: &[&str]| {
+        names
+            .iter()
+            .copied()
+            .find(|&name| gst::ElementFactory::find(name).is_some())
+    };
+
+    if let Some(decoder) = find_any(HARDWARE_DECODERS) {
+        debug!("Found hardware h264 decoder {}", decoder);
+        Ok(true)
+    } else if let Some(decoder) = find_any(SOFTWARE_DECODERS) {
+        debug!("Found software h264 decoder {}", decoder);
+        Ok(false)
+    } else {
+        bail!("No GStreamer h264 decoder found (install gst-libav or gst-plugins-bad)")
+    }
+}
+
 pub struct GStreamerH264Decoder {
     #[allow(dead_code)] // it's required to keep the pipeline alive
     pipeline: gst::Pipeline,
@@ -1,11 +1,19 @@
+mod backend;
+#[cfg(feature = "gstreamer")]
+mod gstreamer;
+mod spawn_ffmpeg;
 mod y4m;
 
 use std::io::{Read, Seek};
 
-use anyhow::Result;
-use cfg_if::cfg_if;
+use anyhow::{anyhow, Result};
+pub use backend::{probe_backends, set_preferred_backend, BackendProbe, H264DecoderBackend};
+use tracing::{info, warn};
 pub use y4m::{BitsPerSample, Colorspace, Frame, FrameSize, PlaneSize};
 
+#[cfg(feature = "gstreamer")]
+use self::gstreamer::GStreamerH264Decoder;
+use self::spawn_ffmpeg::SpawnFfmpegH264Decoder;
 use crate::mp4::Mp4TrackReader;
 
 pub trait H264DecoderTrait: Sized {
@@ -16,13 +24,89 @@ pub trait H264DecoderTrait: Sized {
     fn frame_size(&mut self) -> Result<FrameSize>;
 }
 
-cfg_if! {
-    if #[cfg(feature = "gstreamer")] {
-        mod gstreamer;
-        pub use self::gstreamer::GStreamerH264Decoder as H264Decoder;
-    } else {
-        mod spawn_ffmpeg;
-        pub use spawn_ffmpeg::SpawnFfmpegH264Decoder as H264Decoder;
+/// An H264 decoder using one of the backends available at runtime.
+///
+/// The backend is chosen by probing the available backends in the order of preference (see [`H264DecoderBackend::candidates`]).
+/// If a backend fails to initialize, the next one is tried.
+pub enum H264Decoder {
+    #[cfg(feature = "gstreamer")]
+    GStreamer(GStreamerH264Decoder),
+    SpawnFfmpeg(SpawnFfmpegH264Decoder),
+}
+
+impl H264Decoder {
+    /// Creates a decoder using the specified backend, without any fallback
+    pub fn with_backend<S: Read + Seek + Send + 'static>(
+        backend: H264DecoderBackend,
+        track: Mp4TrackReader<S>,
+    ) -> Result<Self> {
+        Ok(match backend {
+            #[cfg(feature = "gstreamer")]
+            H264DecoderBackend::GStreamer => Self::GStreamer(GStreamerH264Decoder::new(track)?),
+            H264DecoderBackend::SpawnFfmpeg => {
+                Self::SpawnFfmpeg(SpawnFfmpegH264Decoder::new(track)?)
+            }
+        })
+    }
+
+    /// Returns the backend used by this decoder
+    pub fn backend(&self) -> H264DecoderBackend {
+        match self {
+            #[cfg(feature = "gstreamer")]
+            Self::GStreamer(_) => H264DecoderBackend::GStreamer,
+            Self::SpawnFfmpeg(_) => H264DecoderBackend::SpawnFfmpeg,
+        }
+    }
+}
+
+impl H264DecoderTrait for H264Decoder {
+    fn new<S: Read + Seek + Send + 'static>(track: Mp4TrackReader<S>) -> Result<Self> {
+        let mut errors = Vec::new();
+
+        for probe in probe_backends() {
+            if let Err(err) = &probe.available {
+                errors.push(format!("{}: {}", probe.backend, err));
+                continue;
+            }
+
+            match Self::with_backend(probe.backend, track.clone()) {
+                Ok(decoder) => {
+                    info!(
+                        "Using {} h264 decoder (hardware accelerated: {})",
+                        probe.backend, probe.hardware_accelerated
+                    );
+                    return Ok(decoder);
+                }
+                Err(err) => {
+                    warn!(
+                        "Failed to initialize {} h264 decoder: {:?}. Trying the next one",
+                        probe.backend, err
+                    );
+                    errors.push(format!("{}: {:#}", probe.backend, err));
+                }
+            }
+        }
+
+        Err(anyhow!(
+            "No h264 decoder backend could be initialized:\n{}",
+            errors.join("\n")
+        ))
+    }
+
+    fn read_frame(&mut self) -> Result<Option<(FrameTiming, Frame)>> {
+        match self {
+            #[cfg(feature = "gstreamer")]
+            Self::GStreamer(decoder) => decoder.read_frame(),
+            Self::SpawnFfmpeg(decoder) => decoder.read_frame(),
+        }
+    }
+
+    fn frame_size(&mut self) -> Result<FrameSize> {
+        match self {
+            #[cfg(feature = "gstreamer")]
+            Self::GStreamer(decoder) => decoder.frame_size(),
+            Self::SpawnFfmpeg(decoder) => decoder.frame_size(),
+        }
     }
 }
 
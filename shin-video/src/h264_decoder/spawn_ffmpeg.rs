@@ -36,6 +36,33 @@ pub struct SpawnFfmpegH264Decoder {
     frame_size: Option<FrameSize>,
 }
 
+/// Checks that the ffmpeg binary can be found and that it supports h264 decoding
+pub fn probe() -> Result<()> {
+    let ffmpeg = which::which("ffmpeg").context("Could not locate ffmpeg binary")?;
+
+    let output = std::process::Command::new(&ffmpeg)
+        .arg("-hide_banner")
+        .arg("-decoders")
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .context("Could not run ffmpeg")?;
+    if !output.status.success() {
+        bail!("ffmpeg -decoders exited with {}", output.status);
+    }
+
+    // the lines look like " VFS..D h264                 H.264 / AVC / MPEG-4 AVC / MPEG-4 part 10"
+    let decoders = String::from_utf8_lossy(&output.stdout);
+    if !decoders
+        .lines()
+        .any(|line| line.split_whitespace().nth(1) == Some("h264"))
+    {
+        bail!("{} does not support h264 decoding", ffmpeg.display());
+    }
+
+    Ok(())
+}
+
 // const FFMPEG_LOG_LEVEL: &str = "debug";
 const FFMPEG_LOG_LEVEL: &str = "info";
 
@@ -1,12 +1,19 @@
 //! Glue together mp4 demuxing, h264 and aac decoding and `shin-render` APIs to implement video playback in `shin`.
 
 mod audio;
+mod ffmpeg_encoder;
 mod h264_decoder;
 pub mod mp4;
 mod mp4_bitstream_converter;
+pub mod subtitles;
 mod timer;
 mod video_player;
 mod yuv_texture;
 
-pub use video_player::VideoPlayer;
+pub use ffmpeg_encoder::{mux_audio, FfmpegVideoEncoder};
+pub use h264_decoder::{
+    probe_backends as probe_h264_decoder_backends,
+    set_preferred_backend as set_preferred_h264_decoder_backend, BackendProbe, H264DecoderBackend,
+};
+pub use video_player::{MovieAlphaMode, MovieThumbnail, VideoPlayer, VideoPlayerOptions};
 pub use yuv_texture::YuvTexture;
@@ -3,7 +3,7 @@ use std::{
     sync::{Arc, Mutex},
 };
 
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use mp4::{Mp4Sample, Mp4Track};
 
 pub type Mp4ReadStream = std::fs::File;
@@ -63,6 +63,41 @@ impl<S: Read + Seek> Mp4TrackReader<S> {
 
         Ok(Some(sample))
     }
+
+    /// Moves to the last sync sample (a keyframe) starting at or before `time`, returns the start time of that sample
+    ///
+    /// The times are in the track timescale. The sample start times are taken from the `stts` box, the composition offsets are not taken into account.
+    /// All the samples are sync samples if the track doesn't have an `stss` box (like the audio tracks).
+    pub fn seek_to_sync_sample(&mut self, time: u64) -> u64 {
+        let (sample_id, start_time) = self.get_mp4_track_info(|track| {
+            let stbl = &track.trak.mdia.minf.stbl;
+            let is_sync = |sample_id: u32| {
+                stbl.stss
+                    .as_ref()
+                    .map_or(true, |stss| stss.entries.binary_search(&sample_id).is_ok())
+            };
+
+            let mut found = (1, 0);
+            let mut sample_id = 1;
+            let mut start_time = 0;
+            for entry in &stbl.stts.entries {
+                for _ in 0..entry.sample_count {
+                    if start_time > time {
+                        return found;
+                    }
+                    if is_sync(sample_id) {
+                        found = (sample_id, start_time);
+                    }
+                    sample_id += 1;
+                    start_time += entry.sample_delta as u64;
+                }
+            }
+            found
+        });
+
+        self.samples_position = sample_id;
+        start_time
+    }
 }
 
 impl<S: Read + Seek> Clone for Mp4TrackReader<S> {
@@ -91,8 +126,10 @@ fn stream_len(stream: &mut impl Seek) -> Result<u64> {
 
 pub struct Mp4<S: Read + Seek> {
     pub reader: Mp4Reader<S>,
-    pub video_track: Mp4TrackReader<S>,
-    pub audio_track: Option<Mp4TrackReader<S>>,
+    /// The video track, if any. Some movies are audio-only
+    pub video_track: Option<Mp4TrackReader<S>>,
+    /// All the audio tracks, in the order they are stored in the file
+    pub audio_tracks: Vec<Mp4TrackReader<S>>,
 }
 
 impl<S: Read + Seek> Mp4<S> {
@@ -101,7 +138,7 @@ impl<S: Read + Seek> Mp4<S> {
         let mp4 =
             mp4::Mp4Reader::read_header(reader, size).context("Reading the MP4 file headers")?;
 
-        let tracks = mp4
+        let mut tracks = mp4
             .tracks()
             .iter()
             .map(|(_, track)| -> Result<_> {
@@ -109,33 +146,44 @@ impl<S: Read + Seek> Mp4<S> {
                 Ok((track.track_id(), ty))
             })
             .collect::<Result<Vec<_>>>()?;
+        // the tracks are stored in a HashMap, sort them to get a stable order
+        tracks.sort_by_key(|(id, _)| *id);
 
         let video_track_id = tracks
             .iter()
             .find(|(_, ty)| *ty == mp4::TrackType::Video)
-            .map(|(id, _)| *id)
-            .ok_or_else(|| anyhow::anyhow!("No video track found"))?;
+            .map(|(id, _)| *id);
 
-        let audio_track_id = tracks
+        let audio_track_ids = tracks
             .iter()
-            .find(|(_, ty)| *ty == mp4::TrackType::Audio)
-            .map(|(id, _)| *id);
+            .filter(|(_, ty)| *ty == mp4::TrackType::Audio)
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+
+        if video_track_id.is_none() && audio_track_ids.is_empty() {
+            bail!("No video or audio tracks found");
+        }
 
         let reader = Arc::new(Mutex::new(mp4));
 
-        let video_track = Mp4TrackReader::new(reader.clone(), video_track_id)
-            .context("Opening mp4 video track")?;
-        let audio_track = audio_track_id
-            .map(|audio_track_id| {
-                Mp4TrackReader::new(reader.clone(), audio_track_id)
+        let video_track = video_track_id
+            .map(|video_track_id| {
+                Mp4TrackReader::new(reader.clone(), video_track_id)
                     .context("Opening mp4 video track")
             })
             .transpose()?;
+        let audio_tracks = audio_track_ids
+            .into_iter()
+            .map(|audio_track_id| {
+                Mp4TrackReader::new(reader.clone(), audio_track_id)
+                    .context("Opening mp4 audio track")
+            })
+            .collect::<Result<Vec<_>>>()?;
 
         Ok(Self {
             reader,
             video_track,
-            audio_track,
+            audio_tracks,
         })
     }
 }
@@ -145,7 +193,7 @@ impl<S: Read + Seek> Clone for Mp4<S> {
         Self {
             reader: self.reader.clone(),
             video_track: self.video_track.clone(),
-            audio_track: self.audio_track.clone(),
+            audio_tracks: self.audio_tracks.clone(),
         }
     }
 }
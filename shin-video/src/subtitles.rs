@@ -0,0 +1,247 @@
+//! Subtitle tracks to be displayed over the movies.
+//!
+//! Only SubRip (`.srt`) is supported for now. Formatting tags (`<i>`, `<font ...>`, etc.) are stripped, as the styling is controlled by the renderer.
+
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubtitleCue {
+    pub start: Duration,
+    pub end: Duration,
+    /// The text of the cue, lines are separated by `\n`
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Subtitles {
+    /// Cues sorted by the start time
+    cues: Vec<SubtitleCue>,
+}
+
+impl Subtitles {
+    pub fn new(mut cues: Vec<SubtitleCue>) -> Self {
+        cues.sort_by_key(|c| c.start);
+        Self { cues }
+    }
+
+    pub fn parse_srt(source: &str) -> Result<Self> {
+        let source = source.strip_prefix('\u{feff}').unwrap_or(source);
+        let source = source.replace("\r\n", "\n");
+
+        let mut cues = Vec::new();
+        // cues are separated by blank lines
+        for (index, block) in source
+            .split("\n\n")
+            .map(|b| b.trim_matches('\n'))
+            .filter(|b| !b.trim().is_empty())
+            .enumerate()
+        {
+            let mut lines = block.lines();
+            let mut timing_line = lines.next().unwrap();
+            // the cue number is optional in practice
+            if !timing_line.contains("-->") {
+                timing_line = lines
+                    .next()
+                    .with_context(|| format!("Cue #{} has no timing line", index + 1))?;
+            }
+
+            let (start, end) = timing_line
+                .split_once("-->")
+                .with_context(|| format!("Invalid timing line: {:?}", timing_line))?;
+            let start = parse_srt_timestamp(start.trim())
+                .with_context(|| format!("Parsing start time of cue #{}", index + 1))?;
+            // there can be position info after the end time
+            let end = end.split_whitespace().next().unwrap_or("");
+            let end = parse_srt_timestamp(end)
+                .with_context(|| format!("Parsing end time of cue #{}", index + 1))?;
+
+            let text = lines.map(strip_tags).collect::<Vec<_>>().join("\n");
+
+            cues.push(SubtitleCue { start, end, text });
+        }
+
+        Ok(Self::new(cues))
+    }
+
+    pub fn cues(&self) -> &[SubtitleCue] {
+        &self.cues
+    }
+
+    /// Finds the cue that should be displayed at the specified time, returning its index and the cue itself.
+    ///
+    /// If multiple cues overlap, the one that started last is returned.
+    pub fn cue_at(&self, time: Duration) -> Option<(usize, &SubtitleCue)> {
+        let started = self.cues.partition_point(|c| c.start <= time);
+        self.cues[..started]
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|(_, c)| time < c.end)
+    }
+}
+
+/// Parses a timestamp in the `HH:MM:SS,mmm` format (a `.` is also accepted as the decimal separator)
+fn parse_srt_timestamp(s: &str) -> Result<Duration> {
+    let (hms, millis) = s
+        .split_once([',', '.'])
+        .with_context(|| format!("Invalid timestamp: {:?}", s))?;
+
+    let parts = hms
+        .split(':')
+        .map(|p| p.parse::<u64>())
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("Invalid timestamp: {:?}", s))?;
+    let [hours, minutes, seconds] = parts[..] else {
+        bail!("Invalid timestamp: {:?}", s);
+    };
+
+    // normalize to milliseconds, in case there are not exactly 3 digits
+    let millis = millis
+        .chars()
+        .chain(std::iter::repeat('0'))
+        .take(3)
+        .collect::<String>()
+        .parse::<u64>()
+        .with_context(|| format!("Invalid timestamp: {:?}", s))?;
+
+    // checked, so that absurdly large values in a broken file are an error instead of an overflow
+    hours
+        .checked_mul(60)
+        .and_then(|m| m.checked_add(minutes))
+        .and_then(|m| m.checked_mul(60))
+        .and_then(|s| s.checked_add(seconds))
+        .and_then(|s| s.checked_mul(1000))
+        .and_then(|ms| ms.checked_add(millis))
+        .map(Duration::from_millis)
+        .with_context(|| format!("Timestamp out of range: {:?}", s))
+}
+
+fn strip_tags(line: &str) -> String {
+    let mut result = String::with_capacity(line.len());
+    let mut in_tag = false;
+    for c in line.chars() {
+        match c {
+            '<' | '{' if !in_tag => in_tag = true,
+            '>' | '}' if in_tag => in_tag = false,
+            c if !in_tag => result.push(c),
+            _ => {}
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    fn cue(start: u64, end: u64, text: &str) -> SubtitleCue {
+        SubtitleCue {
+            start: ms(start),
+            end: ms(end),
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn parse_srt() {
+        let cases: &[(&str, &str, &[SubtitleCue])] = &[
+            (
+                "single cue",
+                "1\n00:00:01,000 --> 00:00:02,500\nHello\n",
+                &[cue(1000, 2500, "Hello")],
+            ),
+            (
+                "multi-line cues",
+                "1\n00:00:01,000 --> 00:00:02,000\nFirst line\nSecond line\n\n2\n00:00:03,000 --> 00:00:04,000\n<i>Styled</i> line\n",
+                &[
+                    cue(1000, 2000, "First line\nSecond line"),
+                    cue(3000, 4000, "Styled line"),
+                ],
+            ),
+            (
+                "CRLF line endings",
+                "1\r\n00:00:01,000 --> 00:00:02,000\r\nOne\r\nTwo\r\n\r\n2\r\n00:00:03,000 --> 00:00:04,000\r\nThree\r\n",
+                &[cue(1000, 2000, "One\nTwo"), cue(3000, 4000, "Three")],
+            ),
+            (
+                "byte order mark",
+                "\u{feff}1\n00:00:01,000 --> 00:00:02,000\nHello\n",
+                &[cue(1000, 2000, "Hello")],
+            ),
+            (
+                "no cue numbers, dot separator and position info",
+                "01:02:03.4 --> 01:02:04.56 X1:0 X2:10\nHello\n",
+                &[cue(3_723_400, 3_724_560, "Hello")],
+            ),
+            (
+                "cues out of order",
+                "2\n00:00:05,000 --> 00:00:06,000\nLater\n\n1\n00:00:01,000 --> 00:00:02,000\nEarlier\n",
+                &[cue(1000, 2000, "Earlier"), cue(5000, 6000, "Later")],
+            ),
+            ("empty file", "\n\n", &[]),
+        ];
+
+        for (name, source, expected) in cases {
+            let subtitles = Subtitles::parse_srt(source)
+                .unwrap_or_else(|e| panic!("{}: failed to parse: {:?}", name, e));
+            assert_eq!(subtitles.cues(), *expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn parse_srt_malformed() {
+        let cases = [
+            ("no timing line", "1\nHello\n"),
+            ("no arrow", "1\n00:00:01,000 00:00:02,000\nHello\n"),
+            ("no milliseconds", "1\n00:00:01 --> 00:00:02,000\nHello\n"),
+            ("too few fields", "1\n00:01,000 --> 00:00:02,000\nHello\n"),
+            (
+                "too many fields",
+                "1\n00:00:00:01,000 --> 00:00:02,000\nHello\n",
+            ),
+            ("not a number", "1\n00:xx:01,000 --> 00:00:02,000\nHello\n"),
+            (
+                "bad milliseconds",
+                "1\n00:00:01,0a0 --> 00:00:02,000\nHello\n",
+            ),
+            ("negative", "1\n-1:00:01,000 --> 00:00:02,000\nHello\n"),
+            ("missing end", "1\n00:00:01,000 -->\nHello\n"),
+            (
+                "overflow",
+                "1\n18446744073709551615:00:00,000 --> 00:00:02,000\nHello\n",
+            ),
+        ];
+
+        for (name, source) in cases {
+            assert!(
+                Subtitles::parse_srt(source).is_err(),
+                "{}: expected an error",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn overlapping_cues() {
+        let subtitles = Subtitles::parse_srt(
+            "1\n00:00:01,000 --> 00:00:05,000\nLong\n\n2\n00:00:02,000 --> 00:00:03,000\nShort\n",
+        )
+        .unwrap();
+
+        let text_at = |millis| subtitles.cue_at(ms(millis)).map(|(_, c)| c.text.as_str());
+        assert_eq!(text_at(500), None);
+        assert_eq!(text_at(1000), Some("Long"));
+        // the cue that started last wins
+        assert_eq!(text_at(2500), Some("Short"));
+        // and the earlier one is shown again after it ends
+        assert_eq!(text_at(3000), Some("Long"));
+        assert_eq!(text_at(5000), None);
+        assert_eq!(subtitles.cue_at(ms(2500)).unwrap().0, 1);
+    }
+}
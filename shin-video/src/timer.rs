@@ -1,5 +1,8 @@
 use shin_audio::AudioHandle;
-use shin_core::time::Ticks;
+use shin_core::{
+    time::{Ticks, Tween},
+    vm::command::types::AudioWaitStatus,
+};
 use tracing::warn;
 
 pub struct IndependentTimer {
@@ -21,30 +24,49 @@ impl IndependentTimer {
     pub fn time(&self) -> u64 {
         self.time
     }
+
+    pub fn seconds(&self) -> f64 {
+        self.time as f64 / self.time_base as f64
+    }
+
+    pub fn seek(&mut self, time: u64) {
+        self.time = time;
+    }
 }
 
 pub struct AudioTiedTimer {
     timer: IndependentTimer,
     audio_handle: AudioHandle,
+    /// How much later the sound is heard than the audio position says, in seconds
+    audio_offset: f64,
+    /// Set after a seek until the audio catches up with the timer, so that the timer is not pulled back to the old audio position
+    seeking: bool,
 }
 
 impl AudioTiedTimer {
     pub const MAX_DRIFT: f64 = 0.3;
 
-    pub fn new(time_base: u32, audio_handle: AudioHandle) -> AudioTiedTimer {
+    pub fn new(time_base: u32, audio_handle: AudioHandle, audio_offset: f64) -> AudioTiedTimer {
         AudioTiedTimer {
             timer: IndependentTimer::new(time_base),
             audio_handle,
+            audio_offset,
+            seeking: false,
         }
     }
 
     pub fn update(&mut self, delta_time: Ticks) {
         self.timer.update(delta_time);
 
-        let audio_secs = self.audio_handle.position().as_seconds() as f64;
+        // the timer stays at zero until the offset has passed (the cast saturates)
+        let audio_secs = self.audio_handle.position().as_seconds() as f64 - self.audio_offset;
         let timer_secs = self.timer.time() as f64 / self.timer.time_base as f64;
 
-        if (audio_secs - timer_secs).abs() > Self::MAX_DRIFT {
+        if self.seeking {
+            if (audio_secs - timer_secs).abs() <= Self::MAX_DRIFT {
+                self.seeking = false;
+            }
+        } else if (audio_secs - timer_secs).abs() > Self::MAX_DRIFT {
             warn!(
                 "Audio and timer are out of sync by {} seconds, resetting timer",
                 audio_secs - timer_secs
@@ -56,6 +78,48 @@ impl AudioTiedTimer {
     pub fn time(&self) -> u64 {
         self.timer.time()
     }
+
+    pub fn seconds(&self) -> f64 {
+        self.timer.seconds()
+    }
+
+    pub fn is_audio_finished(&self) -> bool {
+        self.audio_handle
+            .get_wait_status()
+            .contains(AudioWaitStatus::STOPPED)
+    }
+
+    pub fn seek(&mut self, time: u64) {
+        self.timer.seek(time);
+        self.seeking = true;
+
+        // the position is in the audio's own time, without the offset
+        let audio_secs = self.timer.seconds() + self.audio_offset;
+        if let Err(e) = self
+            .audio_handle
+            .seek(Ticks::from_seconds(audio_secs.max(0.0) as f32))
+        {
+            warn!("Could not seek the movie audio: {:?}", e);
+        }
+    }
+
+    pub fn stop(&mut self) {
+        if let Err(e) = self.audio_handle.stop(Tween::MS_15) {
+            warn!("Could not stop the movie audio: {:?}", e);
+        }
+    }
+
+    pub fn pause(&mut self) {
+        if let Err(e) = self.audio_handle.pause(Tween::MS_15) {
+            warn!("Could not pause the movie audio: {:?}", e);
+        }
+    }
+
+    pub fn resume(&mut self) {
+        if let Err(e) = self.audio_handle.resume(Tween::MS_15) {
+            warn!("Could not resume the movie audio: {:?}", e);
+        }
+    }
 }
 
 pub enum Timer {
@@ -68,8 +132,8 @@ impl Timer {
         Timer::Independent(IndependentTimer::new(time_base))
     }
 
-    pub fn new_audio_tied(time_base: u32, audio_handle: AudioHandle) -> Timer {
-        Timer::AudioTiedTimer(AudioTiedTimer::new(time_base, audio_handle))
+    pub fn new_audio_tied(time_base: u32, audio_handle: AudioHandle, audio_offset: f64) -> Timer {
+        Timer::AudioTiedTimer(AudioTiedTimer::new(time_base, audio_handle, audio_offset))
     }
 
     pub fn update(&mut self, delta_time: Ticks) {
@@ -85,4 +149,54 @@ impl Timer {
             Timer::AudioTiedTimer(timer) => timer.time(),
         }
     }
+
+    pub fn seconds(&self) -> f64 {
+        match self {
+            Timer::Independent(timer) => timer.seconds(),
+            Timer::AudioTiedTimer(timer) => timer.seconds(),
+        }
+    }
+
+    pub fn time_base(&self) -> u32 {
+        match self {
+            Timer::Independent(timer) => timer.time_base,
+            Timer::AudioTiedTimer(timer) => timer.timer.time_base,
+        }
+    }
+
+    /// Moves the timer to `time` (in the time base units), seeking the audio along with it
+    pub fn seek(&mut self, time: u64) {
+        match self {
+            Timer::Independent(timer) => timer.seek(time),
+            Timer::AudioTiedTimer(timer) => timer.seek(time),
+        }
+    }
+
+    /// Stops the audio, if there is any
+    pub fn stop(&mut self) {
+        if let Timer::AudioTiedTimer(timer) = self {
+            timer.stop();
+        }
+    }
+
+    /// Pauses the audio, the timer itself is paused by not updating it
+    pub fn pause(&mut self) {
+        if let Timer::AudioTiedTimer(timer) = self {
+            timer.pause();
+        }
+    }
+
+    pub fn resume(&mut self) {
+        if let Timer::AudioTiedTimer(timer) = self {
+            timer.resume();
+        }
+    }
+
+    /// Returns `true` if the audio the timer is tied to has finished playing (or if there is no audio at all)
+    pub fn is_audio_finished(&self) -> bool {
+        match self {
+            Timer::Independent(_) => true,
+            Timer::AudioTiedTimer(timer) => timer.is_audio_finished(),
+        }
+    }
 }
@@ -1,30 +1,136 @@
-use std::io::{Read, Seek};
+use std::{
+    io::{Read, Seek},
+    time::Duration,
+};
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use glam::Mat4;
 use kira::track::TrackId;
-use shin_audio::{AudioData, AudioManager, AudioSettings};
+use shin_audio::{AudioData, AudioManager, AudioSettings, DuckingRole};
 use shin_core::{
     time::{Ticks, Tween},
     vm::command::types::{Pan, Volume},
 };
 use shin_render::{GpuCommonResources, Renderable, SpriteVertexBuffer};
-use tracing::{error, info, trace, warn};
+use tracing::{debug, error, info, trace, warn};
 
 use crate::{
     audio::AacFrameSource,
-    h264_decoder::{Frame, FrameTiming, H264Decoder, H264DecoderTrait},
-    mp4::Mp4,
+    h264_decoder::{Frame, FrameTiming, H264Decoder, H264DecoderTrait, PlaneSize},
+    mp4::{Mp4, Mp4TrackReader},
     timer::Timer,
     YuvTexture,
 };
 
+/// Describes how the alpha channel of a movie is stored
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MovieAlphaMode {
+    /// Guess from the frame dimensions: a frame twice as tall as 16:9 is assumed to carry an alpha mask
+    #[default]
+    Auto,
+    /// The movie has no alpha channel
+    Opaque,
+    /// The top half of the frame stores the color, the bottom half stores the alpha mask (rendered with the `MovieAlpha` program)
+    Packed,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct VideoPlayerOptions {
+    /// Index of the audio track to play (in the order they are stored in the file), `None` to play without sound
+    ///
+    /// The first track is played if the movie doesn't have the requested one.
+    pub audio_track: Option<usize>,
+    pub alpha_mode: MovieAlphaMode,
+    pub volume: Volume,
+    /// How much later the sound comes out of the speakers than the audio backend reports, in seconds
+    ///
+    /// The video is delayed by this amount to stay in sync (or shown earlier, if it's negative). Has no effect on the movies played without sound.
+    pub audio_offset: f64,
+}
+
+impl Default for VideoPlayerOptions {
+    fn default() -> Self {
+        Self {
+            audio_track: Some(0),
+            alpha_mode: MovieAlphaMode::Auto,
+            volume: Volume::default(),
+            audio_offset: 0.0,
+        }
+    }
+}
+
+/// Creates a decoder starting at the last keyframe at or before the time, returns it with the time of the keyframe
+///
+/// Hides the type of the mp4 stream, so that the player doesn't have to be generic over it.
+type RestartDecoder = Box<dyn Fn(u64) -> Result<(u64, H264Decoder)> + Send>;
+
+struct VideoStream {
+    decoder: H264Decoder,
+    restart_decoder: RestartDecoder,
+    texture: YuvTexture,
+    has_alpha: bool,
+    pending_frame: Option<(FrameTiming, Frame)>,
+}
+
+impl VideoStream {
+    fn new<S: Read + Seek + Send + 'static>(
+        resources: &GpuCommonResources,
+        video_track: Mp4TrackReader<S>,
+        alpha_mode: MovieAlphaMode,
+    ) -> Result<Self> {
+        let start = std::time::Instant::now();
+        let mut decoder =
+            H264Decoder::new(video_track.clone()).context("Initializing H264Decoder")?;
+        let pending_frame = decoder.read_frame().context("Reading first frame")?;
+        let duration = start.elapsed();
+
+        info!(
+            "H264Decoder::new ({} backend) took {:?}",
+            decoder.backend(),
+            duration
+        );
+
+        let frame_size = decoder.frame_size().context("Getting H264 frame size")?;
+        let has_alpha = match alpha_mode {
+            MovieAlphaMode::Auto => {
+                let PlaneSize { width, height, .. } = frame_size.plane_sizes[0];
+                height * 16 == width * 9 * 2
+            }
+            MovieAlphaMode::Opaque => false,
+            MovieAlphaMode::Packed => true,
+        };
+        if has_alpha {
+            debug!("Playing the movie with packed alpha");
+        }
+
+        let texture = YuvTexture::new(resources, frame_size);
+
+        // the decoders for the seeks use the same backend, the first one didn't need any fallback
+        let backend = decoder.backend();
+        let restart_decoder: RestartDecoder = Box::new(move |time| {
+            let mut track = video_track.clone();
+            let keyframe_time = track.seek_to_sync_sample(time);
+            let decoder =
+                H264Decoder::with_backend(backend, track).context("Restarting H264Decoder")?;
+            Ok((keyframe_time, decoder))
+        });
+
+        Ok(Self {
+            decoder,
+            restart_decoder,
+            texture,
+            has_alpha,
+            pending_frame,
+        })
+    }
+}
+
 pub struct VideoPlayer {
     timer: Timer,
-    video_decoder: H264Decoder,
-    video_texture: YuvTexture,
+    video: Option<VideoStream>,
     vertex_buffer: SpriteVertexBuffer,
-    pending_frame: Option<(FrameTiming, Frame)>,
+    duration: Duration,
+    paused: bool,
 }
 
 impl VideoPlayer {
@@ -32,38 +138,78 @@ impl VideoPlayer {
         resources: &GpuCommonResources,
         audio_manager: &AudioManager,
         mp4: Mp4<S>,
+        options: VideoPlayerOptions,
     ) -> Result<VideoPlayer> {
-        let time_base = mp4
-            .video_track
-            .get_mp4_track_info(|track| track.timescale());
+        let audio_track = match options.audio_track {
+            Some(index) if index < mp4.audio_tracks.len() => Some(mp4.audio_tracks[index].clone()),
+            Some(index) => {
+                // the track is usually chosen for all the movies, but only some of them have the alternative tracks
+                warn!(
+                    "Requested audio track #{}, but the movie has only {} audio tracks. Playing the first one",
+                    index,
+                    mp4.audio_tracks.len()
+                );
+                mp4.audio_tracks.first().cloned()
+            }
+            None => None,
+        };
 
-        let start = std::time::Instant::now();
-        let mut video_decoder =
-            H264Decoder::new(mp4.video_track).context("Initializing H264Decoder")?;
-        let pending_frame = video_decoder.read_frame().context("Reading first frame")?;
-        let duration = start.elapsed();
+        if mp4.audio_tracks.len() > 1 {
+            info!(
+                "The movie has {} audio tracks, selected {:?}",
+                mp4.audio_tracks.len(),
+                options.audio_track
+            );
+        }
 
-        info!("H264Decoder::new took {:?}", duration);
+        // the longest track, the audio can be a bit longer than the video
+        let duration = mp4
+            .video_track
+            .iter()
+            .chain(audio_track.iter())
+            .map(|track| track.get_mp4_track_info(|track| track.duration()))
+            .max()
+            .unwrap_or_default();
 
-        let video_texture = YuvTexture::new(
-            resources,
-            video_decoder
-                .frame_size()
-                .context("Getting H264 frame size")?,
-        );
+        let (time_base, video) = match mp4.video_track {
+            Some(video_track) => {
+                let time_base = video_track.get_mp4_track_info(|track| track.timescale());
+
+                match VideoStream::new(resources, video_track, options.alpha_mode) {
+                    Ok(video) => (time_base, Some(video)),
+                    // no need to fail completely if we can still play the sound
+                    Err(err) if audio_track.is_some() => {
+                        error!(
+                            "Could not start video decoding: {:?}. Playing only the audio",
+                            err
+                        );
+                        (time_base, None)
+                    }
+                    Err(err) => return Err(err),
+                }
+            }
+            None => {
+                if audio_track.is_none() {
+                    bail!("The movie has no video track and no audio track is selected");
+                }
+                info!("Playing an audio-only movie");
+                // the timer is only used for video frames, so the time base doesn't matter much
+                (1000, None)
+            }
+        };
 
-        // TODO: use the audio track
         // if we are using audio the timer should be tracking the audio playback
-        let audio_handle = if let Some(track) = mp4.audio_track {
+        let audio_handle = if let Some(track) = audio_track {
             let frame_source = AacFrameSource::new(track).context("Initializing AacFrameSource")?;
             Some(audio_manager.play(AudioData {
                 source: frame_source,
                 settings: AudioSettings {
                     track: TrackId::Main,
                     fade_in: Tween::MS_15,
-                    loop_start: None,
-                    volume: Volume::default(),
+                    loop_region: None,
+                    volume: options.volume,
                     pan: Pan::default(),
+                    ducking: DuckingRole::None,
                 },
             }))
         } else {
@@ -71,7 +217,7 @@ impl VideoPlayer {
         };
 
         let timer = match audio_handle {
-            Some(handle) => Timer::new_audio_tied(time_base, handle),
+            Some(handle) => Timer::new_audio_tied(time_base, handle, options.audio_offset),
             None => Timer::new_independent(time_base),
         };
 
@@ -79,21 +225,28 @@ impl VideoPlayer {
 
         Ok(VideoPlayer {
             timer,
-            video_decoder,
-            video_texture,
+            video,
             vertex_buffer,
-            pending_frame,
+            duration,
+            paused: false,
         })
     }
 
     pub fn update(&mut self, delta_time: Ticks, queue: &wgpu::Queue) {
-        self.timer.update(delta_time);
+        // the frames are still updated while paused, to show the frame after a seek
+        if !self.paused {
+            self.timer.update(delta_time);
+        }
         let current_time = self.timer.time();
 
+        let Some(video) = &mut self.video else {
+            return;
+        };
+
         let mut skipped_frames = 0;
         // find the latest frame that is ready for display
         // this might be the currently pending frame, or any of the frames after it (shouldn't happen often I think)
-        while let Some((timing, ref frame)) = self.pending_frame {
+        while let Some((timing, ref frame)) = video.pending_frame {
             // if it's not time to display the frame yet - stop the loop
             if timing.start_time > current_time {
                 // very noisy
@@ -106,7 +259,7 @@ impl VideoPlayer {
             }
 
             // look at the frame after the pending one
-            let next_frame = match self.video_decoder.read_frame() {
+            let next_frame = match video.decoder.read_frame() {
                 Ok(frame) => frame,
                 Err(err) => {
                     error!("Error reading frame: {}. Stopping playback", err);
@@ -129,7 +282,7 @@ impl VideoPlayer {
                     timing.frame_number,
                     timing.start_time
                 );
-                self.video_texture.write_data(frame, queue);
+                video.texture.write_data(frame, queue);
                 // the loop will not enter again, so the pending frame will now be displayed
             } else {
                 skipped_frames += 1;
@@ -140,12 +293,75 @@ impl VideoPlayer {
                 info!("No more frames, stopping playback");
             }
 
-            self.pending_frame = next_frame;
+            video.pending_frame = next_frame;
         }
     }
 
+    /// Returns the current playback position, useful to synchronize other things (like subtitles) with the movie
+    pub fn position(&self) -> Duration {
+        Duration::from_secs_f64(self.timer.seconds())
+    }
+
+    /// The length of the movie, as stored in the mp4 file
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn pause(&mut self) {
+        if !self.paused {
+            self.paused = true;
+            self.timer.pause();
+        }
+    }
+
+    pub fn resume(&mut self) {
+        if self.paused {
+            self.paused = false;
+            self.timer.resume();
+        }
+    }
+
+    /// Stops the movie early, fading out the sound, the movie is finished afterwards
+    pub fn stop(&mut self) {
+        self.timer.stop();
+        if let Some(video) = &mut self.video {
+            video.pending_frame = None;
+        }
+    }
+
+    /// Moves the playback to the last keyframe at or before the `position`, returns the position of that keyframe
+    ///
+    /// The audio is moved to the keyframe too, so it stays in sync. The audio-only movies are moved to the `position` exactly.
+    pub fn seek(&mut self, position: Duration) -> Result<Duration> {
+        let time_base = self.timer.time_base();
+        let mut time = (position.as_secs_f64() * time_base as f64) as u64;
+
+        if let Some(video) = &mut self.video {
+            let (keyframe_time, mut decoder) = (video.restart_decoder)(time)?;
+            video.pending_frame = decoder
+                .read_frame()
+                .context("Reading the first frame after seeking")?;
+            // dropping the old decoder stops its backend
+            video.decoder = decoder;
+            time = keyframe_time;
+        }
+
+        debug!("Seeking the movie to {}/{}", time, time_base);
+        self.timer.seek(time);
+
+        Ok(Duration::from_secs_f64(time as f64 / time_base as f64))
+    }
+
     pub fn is_finished(&self) -> bool {
-        self.pending_frame.is_none()
+        match &self.video {
+            Some(video) => video.pending_frame.is_none(),
+            // audio-only movies end when the sound does
+            None => self.timer.is_audio_finished(),
+        }
     }
 }
 
@@ -159,12 +375,95 @@ impl Renderable for VideoPlayer {
         transform: Mat4,
         projection: Mat4,
     ) {
-        let total_transform = projection * transform;
+        let Some(video) = &self.video else {
+            return;
+        };
+
+        draw_frame(
+            resources,
+            render_pass,
+            &self.vertex_buffer,
+            &video.texture,
+            video.has_alpha,
+            projection * transform,
+        );
+    }
+
+    fn resize(&mut self, _resources: &GpuCommonResources) {}
+}
+
+fn draw_frame<'enc>(
+    resources: &'enc GpuCommonResources,
+    render_pass: &mut wgpu::RenderPass<'enc>,
+    vertex_buffer: &'enc SpriteVertexBuffer,
+    texture: &'enc YuvTexture,
+    has_alpha: bool,
+    transform: Mat4,
+) {
+    if has_alpha {
+        resources.draw_yuva_sprite(
+            render_pass,
+            vertex_buffer.vertex_source(),
+            texture.bind_group(),
+            transform,
+        );
+    } else {
         resources.draw_yuv_sprite(
             render_pass,
-            self.vertex_buffer.vertex_source(),
-            self.video_texture.bind_group(),
-            total_transform,
+            vertex_buffer.vertex_source(),
+            texture.bind_group(),
+            transform,
+        );
+    }
+}
+
+/// The first frame of a movie (always a keyframe), shown in the movie gallery
+///
+/// The decoder is only kept until the frame is read.
+pub struct MovieThumbnail {
+    texture: YuvTexture,
+    has_alpha: bool,
+    vertex_buffer: SpriteVertexBuffer,
+}
+
+impl MovieThumbnail {
+    pub fn new<S: Read + Seek + Send + 'static>(
+        resources: &GpuCommonResources,
+        mp4: Mp4<S>,
+        alpha_mode: MovieAlphaMode,
+    ) -> Result<Self> {
+        let video_track = mp4.video_track.context("The movie has no video track")?;
+        let mut video = VideoStream::new(resources, video_track, alpha_mode)?;
+        let (_, frame) = video
+            .pending_frame
+            .take()
+            .context("The movie has no frames")?;
+        video.texture.write_data(&frame, &resources.queue);
+
+        Ok(Self {
+            texture: video.texture,
+            has_alpha: video.has_alpha,
+            vertex_buffer: SpriteVertexBuffer::new_fullscreen(resources),
+        })
+    }
+}
+
+/// Drawn over the whole screen like the [`VideoPlayer`], scale it with the `transform` to make it smaller
+impl Renderable for MovieThumbnail {
+    fn render<'enc>(
+        &'enc self,
+        resources: &'enc GpuCommonResources,
+        render_pass: &mut wgpu::RenderPass<'enc>,
+        transform: Mat4,
+        projection: Mat4,
+    ) {
+        draw_frame(
+            resources,
+            render_pass,
+            &self.vertex_buffer,
+            &self.texture,
+            self.has_alpha,
+            projection * transform,
         );
     }
 
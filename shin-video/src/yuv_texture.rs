@@ -1,4 +1,6 @@
-use shin_render::{GpuCommonResources, YuvTextureBindGroup};
+use shin_render::{
+    GpuCommonResources, SamplerMode, TextureFilter, TextureWrap, YuvTextureBindGroup,
+};
 
 use crate::h264_decoder::{BitsPerSample, Colorspace, Frame, FrameSize, PlaneSize};
 
@@ -64,27 +66,17 @@ impl YuvTexture {
         let tex_u = create_texture(device, size.plane_sizes[1], "VideoRenderer U Texture");
         let tex_v = create_texture(device, size.plane_sizes[2], "VideoRenderer V Texture");
 
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            label: Some("VideoRenderer Sampler"),
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: Default::default(),
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Linear,
-            mipmap_filter: Default::default(),
-            lod_min_clamp: 0.0,
-            lod_max_clamp: 0.0,
-            compare: None,
-            anisotropy_clamp: 1,
-            border_color: None,
-        });
+        // the frames are scaled to the screen, clamping keeps the edges from bleeding into each other
+        let sampler = resources
+            .sampler_store
+            .get(SamplerMode::new(TextureFilter::Linear, TextureWrap::Clamp));
 
         let bind_group = YuvTextureBindGroup::new(
             resources,
             &tex_y.create_view(&Default::default()),
             &tex_u.create_view(&Default::default()),
             &tex_v.create_view(&Default::default()),
-            &sampler,
+            sampler,
             Some("VideoRenderer Bind Group"),
         );
 
@@ -0,0 +1,74 @@
+use std::{
+    io::{BufReader, BufWriter, Write},
+    path::PathBuf,
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use super::AchievementBackend;
+
+#[derive(Serialize, Deserialize)]
+struct AchievementsFile {
+    unlocked: Vec<i32>,
+}
+
+/// Stores the unlocked trophies in a JSON file
+pub struct JsonBackend {
+    path: PathBuf,
+}
+
+impl JsonBackend {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// `achievements.json` in the shin data directory, if there is one on this platform
+    pub fn default_path() -> Option<PathBuf> {
+        dirs_next::data_dir().map(|p| p.join("shin").join("achievements.json"))
+    }
+}
+
+impl AchievementBackend for JsonBackend {
+    fn name(&self) -> &'static str {
+        "json"
+    }
+
+    fn load(&mut self) -> Result<Vec<i32>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let file = std::fs::File::open(&self.path)
+            .with_context(|| format!("Opening {}", self.path.display()))?;
+        let file: AchievementsFile = serde_json::from_reader(BufReader::new(file))
+            .with_context(|| format!("Parsing {}", self.path.display()))?;
+
+        Ok(file.unlocked)
+    }
+
+    fn unlock(&mut self, _trophy_id: i32, unlocked: &[i32]) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Creating {}", parent.display()))?;
+        }
+
+        // write to a temporary file first, so that a crash doesn't lose the previous unlocks
+        let temp_path = self.path.with_extension("json.tmp");
+        let file = std::fs::File::create(&temp_path)
+            .with_context(|| format!("Creating {}", temp_path.display()))?;
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer_pretty(
+            &mut writer,
+            &AchievementsFile {
+                unlocked: unlocked.to_vec(),
+            },
+        )
+        .context("Writing the trophies")?;
+        writer.flush().context("Writing the trophies")?;
+        std::fs::rename(&temp_path, &self.path)
+            .with_context(|| format!("Replacing {}", self.path.display()))?;
+
+        Ok(())
+    }
+}
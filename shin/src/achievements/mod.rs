@@ -0,0 +1,98 @@
+//! Trophies (achievements) given by the scenario with TROPHY.
+//!
+//! The game only reports the trophies to the platform (they seem to be a PS4 thing), they are not a part of the game's savedata.
+//! So the unlocks are kept by an [`AchievementBackend`], which is responsible for persisting them:
+//! - [`NullBackend`] keeps them in memory only, they are lost on exit
+//! - [`JsonBackend`] stores them in a JSON file (by default in the shin data directory)
+//!
+//! A platform backend (like Steam) can be added by implementing the trait.
+//!
+//! The unlocked trophies are shown in the [`AchievementsScreen`], opened from the system menu.
+
+mod json;
+mod screen;
+
+use std::collections::BTreeSet;
+
+use anyhow::Result;
+pub use json::JsonBackend;
+pub use screen::AchievementsScreen;
+use tracing::{info, warn};
+
+pub trait AchievementBackend: Send {
+    /// A name for logs
+    fn name(&self) -> &'static str;
+
+    /// Returns the trophies unlocked in the previous runs
+    fn load(&mut self) -> Result<Vec<i32>>;
+
+    /// Records a newly unlocked trophy
+    ///
+    /// `unlocked` contains all the trophies unlocked so far, including this one.
+    fn unlock(&mut self, trophy_id: i32, unlocked: &[i32]) -> Result<()>;
+}
+
+/// A backend that doesn't persist anything
+pub struct NullBackend;
+
+impl AchievementBackend for NullBackend {
+    fn name(&self) -> &'static str {
+        "null"
+    }
+
+    fn load(&mut self) -> Result<Vec<i32>> {
+        Ok(Vec::new())
+    }
+
+    fn unlock(&mut self, _trophy_id: i32, _unlocked: &[i32]) -> Result<()> {
+        Ok(())
+    }
+}
+
+pub struct Achievements {
+    backend: Box<dyn AchievementBackend>,
+    unlocked: BTreeSet<i32>,
+}
+
+impl Achievements {
+    pub fn new(mut backend: Box<dyn AchievementBackend>) -> Self {
+        let unlocked = match backend.load() {
+            Ok(unlocked) => unlocked.into_iter().collect(),
+            Err(e) => {
+                warn!(
+                    "Failed to load the trophies from the {} backend: {:?}",
+                    backend.name(),
+                    e
+                );
+                BTreeSet::new()
+            }
+        };
+
+        Self { backend, unlocked }
+    }
+
+    /// Unlocks the trophy, returns `false` if it was already unlocked
+    pub fn unlock(&mut self, trophy_id: i32) -> bool {
+        if !self.unlocked.insert(trophy_id) {
+            return false;
+        }
+
+        info!("Unlocked trophy {}", trophy_id);
+        let unlocked = self.unlocked.iter().copied().collect::<Vec<_>>();
+        if let Err(e) = self.backend.unlock(trophy_id, &unlocked) {
+            warn!(
+                "Failed to record trophy {} with the {} backend: {:?}",
+                trophy_id,
+                self.backend.name(),
+                e
+            );
+        }
+
+        true
+    }
+
+    /// The unlocked trophies, in the ascending order
+    pub fn unlocked(&self) -> impl Iterator<Item = i32> + '_ {
+        self.unlocked.iter().copied()
+    }
+}
@@ -0,0 +1,187 @@
+use std::sync::Arc;
+
+use glam::{vec2, Mat4};
+use shin_core::time::{Easing, Ticks, Tween};
+use shin_render::{GpuCommonResources, LazyGpuTexture, Renderable};
+
+use crate::{
+    app::{Screen, ScreenTransition},
+    i18n::Localizer,
+    input::{actions::MenuAction, ActionState},
+    layer::FontAtlas,
+    render::overlay::{OverlayCollector, OverlayVisitable},
+    update::{Updatable, UpdateContext},
+    widget::{default_nine_patch, default_window_image, Label, Window},
+};
+
+const FADE: Tween = Tween {
+    duration: Ticks::from_f32(10.0),
+    easing: Easing::SineOut,
+};
+
+const WIDTH: f32 = 800.0;
+const TITLE_FONT_HEIGHT: f32 = 48.0;
+const LINE_FONT_HEIGHT: f32 = 36.0;
+const LINE_SPACING: f32 = 48.0;
+/// The lines after this are summarized as "and N more"
+const MAX_LINES: usize = 12;
+const WINDOW_PADDING: f32 = 40.0;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum State {
+    Opening,
+    Open,
+    Closing,
+}
+
+/// Lists the unlocked trophies, closed with any of the menu buttons
+///
+/// The trophy names are not known (they are stored on the platform side), so they are shown by their ids.
+pub struct AchievementsScreen {
+    window_texture: LazyGpuTexture,
+    window: Window,
+    labels: Vec<Label>,
+    state: State,
+    action_state: ActionState<MenuAction>,
+}
+
+impl AchievementsScreen {
+    pub fn new(
+        resources: &GpuCommonResources,
+        font_atlas: Arc<FontAtlas>,
+        i18n: &Localizer,
+        unlocked: impl Iterator<Item = i32>,
+    ) -> Self {
+        let unlocked = unlocked.collect::<Vec<_>>();
+
+        let mut lines = if unlocked.is_empty() {
+            vec![i18n.tr("achievements.empty")]
+        } else {
+            unlocked
+                .iter()
+                .take(MAX_LINES)
+                .map(|id| i18n.tr_args("achievements.trophy", &[("id", id)]))
+                .collect()
+        };
+        if unlocked.len() > MAX_LINES {
+            let count = unlocked.len() - MAX_LINES;
+            lines.push(i18n.tr_args("achievements.more", &[("count", &count)]));
+        }
+
+        let height = TITLE_FONT_HEIGHT + LINE_SPACING * (lines.len() as f32 + 0.5);
+        let top_left = vec2(-WIDTH / 2.0, -height / 2.0);
+
+        let mut window = Window::new(
+            resources,
+            default_nine_patch(),
+            (
+                top_left.x - WINDOW_PADDING,
+                top_left.y - WINDOW_PADDING,
+                -top_left.x + WINDOW_PADDING,
+                -top_left.y + WINDOW_PADDING,
+            ),
+        );
+
+        let mut labels = vec![Label::new(
+            resources,
+            font_atlas.clone(),
+            &i18n.tr("achievements.title"),
+            top_left,
+            WIDTH,
+            TITLE_FONT_HEIGHT,
+        )];
+        let lines_top = top_left.y + TITLE_FONT_HEIGHT + LINE_SPACING / 2.0;
+        labels.extend(lines.iter().enumerate().map(|(index, line)| {
+            Label::new(
+                resources,
+                font_atlas.clone(),
+                line,
+                vec2(top_left.x, lines_top + LINE_SPACING * index as f32),
+                WIDTH,
+                LINE_FONT_HEIGHT,
+            )
+        }));
+
+        window.show(FADE);
+
+        Self {
+            window_texture: LazyGpuTexture::new(
+                default_window_image(),
+                Some("Achievements Window"),
+            ),
+            window,
+            labels,
+            state: State::Opening,
+            action_state: ActionState::new(),
+        }
+    }
+}
+
+impl Screen for AchievementsScreen {
+    fn name(&self) -> &'static str {
+        "Achievements"
+    }
+
+    fn update(&mut self, context: &UpdateContext) -> ScreenTransition {
+        self.action_state.update(context.raw_input_state);
+        self.window.update(context);
+
+        match self.state {
+            State::Opening => {
+                if self.window.is_idle() {
+                    self.state = State::Open;
+                }
+            }
+            State::Open => {
+                if self.action_state.is_just_pressed(MenuAction::Activate)
+                    || self.action_state.is_just_pressed(MenuAction::Cancel)
+                {
+                    self.window.hide(FADE);
+                    self.state = State::Closing;
+                }
+            }
+            State::Closing => {
+                if self.window.is_idle() {
+                    return ScreenTransition::Pop;
+                }
+            }
+        }
+
+        ScreenTransition::None
+    }
+
+    fn is_opaque(&self) -> bool {
+        false
+    }
+}
+
+impl Renderable for AchievementsScreen {
+    fn render<'enc>(
+        &'enc self,
+        resources: &'enc GpuCommonResources,
+        render_pass: &mut wgpu::RenderPass<'enc>,
+        transform: Mat4,
+        projection: Mat4,
+    ) {
+        let transform = projection * transform;
+
+        render_pass.push_debug_group("AchievementsScreen");
+        self.window.render(
+            resources,
+            render_pass,
+            self.window_texture.gpu_texture(resources),
+            transform,
+        );
+        let opacity = self.window.opacity();
+        for label in &self.labels {
+            label.render(resources, render_pass, transform, opacity);
+        }
+        render_pass.pop_debug_group();
+    }
+
+    fn resize(&mut self, _resources: &GpuCommonResources) {}
+}
+
+impl OverlayVisitable for AchievementsScreen {
+    fn visit_overlay(&self, _collector: &mut OverlayCollector) {}
+}
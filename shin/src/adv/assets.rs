@@ -1,8 +1,9 @@
-use std::sync::Arc;
+use std::{collections::BTreeMap, sync::Arc};
 
 use anyhow::Result;
-use futures::try_join;
+use futures::{future::join_all, try_join};
 use shin_core::format::{font::LazyFont, scenario::Scenario};
+use tracing::warn;
 
 use crate::{
     asset::{asset_paths, AnyAssetServer},
@@ -12,11 +13,73 @@ use crate::{
 // TODO: this can be done with a macro
 #[derive(Clone)]
 pub struct AdvAssets {
-    pub scenario: Arc<Scenario>,
+    pub scenarios: ScenarioLanguages,
     pub fonts: AdvFonts,
     pub messagebox_textures: Arc<MessageboxTextures>,
 }
 
+/// The original scenario and its translations (see [`ScenarioConfig`](crate::config::ScenarioConfig))
+#[derive(Clone)]
+pub struct ScenarioLanguages {
+    pub original: Arc<Scenario>,
+    pub translations: BTreeMap<String, Arc<Scenario>>,
+}
+
+impl ScenarioLanguages {
+    /// Loads the translations from the asset `paths`, the ones failing to load are skipped
+    pub async fn load(
+        asset_server: &AnyAssetServer,
+        paths: &BTreeMap<String, String>,
+    ) -> Result<Self> {
+        let (original, translations) = futures::join!(
+            asset_server.load(asset_paths::SCENARIO),
+            join_all(paths.iter().map(|(language, path)| async move {
+                (language, path, asset_server.load::<Scenario, _>(path).await)
+            }))
+        );
+
+        let translations = translations
+            .into_iter()
+            .filter_map(|(language, path, scenario)| match scenario {
+                Ok(scenario) => Some((language.clone(), scenario)),
+                Err(e) => {
+                    warn!(
+                        "Failed to load the {:?} scenario from {}: {:?}",
+                        language, path, e
+                    );
+                    None
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            original: original?,
+            translations,
+        })
+    }
+
+    /// The scenario in the `language`, `None` for the original one
+    pub fn get(&self, language: Option<&str>) -> Option<&Arc<Scenario>> {
+        match language {
+            None => Some(&self.original),
+            Some(language) => self.translations.get(language),
+        }
+    }
+
+    /// The language of the `scenario`, `None` for the original one (or a scenario that is not one of these)
+    pub fn language_of(&self, scenario: &Arc<Scenario>) -> Option<&str> {
+        self.translations
+            .iter()
+            .find(|(_, translation)| Arc::ptr_eq(translation, scenario))
+            .map(|(language, _)| language.as_str())
+    }
+
+    /// The languages of the translations, sorted by their codes
+    pub fn languages(&self) -> Vec<String> {
+        self.translations.keys().cloned().collect()
+    }
+}
+
 #[derive(Clone)]
 pub struct AdvFonts {
     pub system_font: Arc<LazyFont>,
@@ -25,15 +88,18 @@ pub struct AdvFonts {
 }
 
 impl AdvAssets {
-    pub async fn load(asset_server: &AnyAssetServer) -> Result<Self> {
+    pub async fn load(
+        asset_server: &AnyAssetServer,
+        scenario_languages: &BTreeMap<String, String>,
+    ) -> Result<Self> {
         let result = try_join!(
-            asset_server.load(asset_paths::SCENARIO),
+            ScenarioLanguages::load(asset_server, scenario_languages),
             AdvFonts::load(asset_server),
             asset_server.load(asset_paths::MSGTEX),
         )?;
 
         Ok(Self {
-            scenario: result.0,
+            scenarios: result.0,
             fonts: result.1,
             messagebox_textures: result.2,
         })
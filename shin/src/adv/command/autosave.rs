@@ -12,7 +12,7 @@ impl StartableCommand for command::runtime::AUTOSAVE {
         _vm_state: &VmState,
         _adv_state: &mut AdvState,
     ) -> CommandStartResult {
-        warn!("TODO: AUTOSAVE: {:?}", self);
+        // the save itself is made by the Adv, it has the scripter state
         self.token.finish().into()
     }
 }
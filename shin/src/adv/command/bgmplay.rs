@@ -26,19 +26,20 @@ impl StartableCommand for command::runtime::BGMPLAY {
             linked_bgm_id: _,
         } = scenario.info_tables().bgm_info(self.bgm_data_id);
 
-        let audio = context
+        match context
             .asset_server
             // TODO: sync - bad!!
             .load_sync(bgm_info.path())
-            .expect("Failed to load BGM track");
-
-        adv_state.bgm_player.play(
-            audio,
-            display_name.as_str(),
-            !self.no_repeat,
-            self.volume,
-            Tween::linear(self.fade_in_time),
-        );
+        {
+            Ok(audio) => adv_state.bgm_player.play(
+                audio,
+                display_name.as_str(),
+                !self.no_repeat,
+                self.volume,
+                Tween::linear(self.fade_in_time),
+            ),
+            Err(e) => warn!("BGMPLAY: failed to load {}: {:?}", bgm_info.path(), e),
+        }
 
         self.token.finish().into()
     }
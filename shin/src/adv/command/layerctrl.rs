@@ -4,15 +4,20 @@ use super::prelude::*;
 
 impl StartableCommand for command::runtime::LAYERCTRL {
     fn apply_state(&self, state: &mut VmState) {
-        let (target_value, _time, _flags, _easing_param, ..) = self.params;
+        let (target_value, _time, flags, _easing_param, ..) = self.params;
 
         state
             .layers
             .get_vlayer_mut(self.layer_id)
             .for_each(|layer| {
+                let value = if flags.delta() {
+                    layer.properties.get_property(self.property_id) + target_value
+                } else {
+                    target_value
+                };
                 layer
                     .properties
-                    .set_property(self.property_id, target_value);
+                    .set_property(self.property_id, self.property_id.clamp(value));
             });
     }
 
@@ -32,10 +37,6 @@ impl StartableCommand for command::runtime::LAYERCTRL {
         if flags.scale_time() {
             warn!("LAYERCTRL: scale_time is set, but not supported");
         }
-        if flags.delta() {
-            // note: delta flag has a non-trivial interaction with queue clear flags
-            warn!("LAYERCTRL: delta is set, but not supported");
-        }
         if flags.ff_to_current() && flags.ff_to_target() {
             panic!("LAYERCTRL: both ff_to_current and ff_to_target flags are set");
         }
@@ -46,50 +47,53 @@ impl StartableCommand for command::runtime::LAYERCTRL {
             warn!("LAYERCTRL: ignore_wait is set, but not supported");
         }
 
-        let easing = match flags.easing() {
-            0 => Easing::Linear,
-            1 => Easing::SineIn,
-            2 => Easing::SineOut,
-            3 => Easing::SineInOut,
-            4 => Easing::Jump,
-            5 => Easing::Power(easing_param),
-            _ => panic!("LAYERCTRL: unknown easing function: {}", flags.easing()),
-        };
+        let easing = Easing::from_layerctrl(flags.easing(), easing_param).unwrap_or_else(|| {
+            warn!(
+                "LAYERCTRL: unknown easing function: {}, using linear",
+                flags.easing()
+            );
+            Easing::Linear
+        });
 
         let mut changed = false;
-        adv_state.get_vlayer_mut(vm_state, self.layer_id).for_each(|mut layer| {
-            let tweener = layer
-                .properties_mut()
-                .property_tweener_mut(self.property_id);
-
-            let from_value = tweener.target_value();
-            let to_value = target_value as f32;
-            let mut duration = duration;
-
-            if tweener.value() != to_value {
-                changed = true;
-            }
-
-            if flags.scale_time() {
-                // this flag makes "duration" actually mean change rate (in value per tick)
-                let change = (to_value - from_value).abs();
-                duration = Ticks::from_f32(change / duration.as_f32());
-            }
+        adv_state
+            .get_vlayer_mut(vm_state, self.layer_id)
+            .for_each(|mut layer| {
+                let tweener = layer
+                    .properties_mut()
+                    .property_tweener_mut(self.property_id);
+
+                let from_value = tweener.target_value();
+                // the delta is relative to the value the property is going to have after the queued tweens, like in `apply_state`
+                let target_value = if flags.delta() {
+                    from_value as i32 + target_value
+                } else {
+                    target_value
+                };
+                let target_value = self.property_id.clamp(target_value);
+                let to_value = target_value as f32;
+                let mut duration = duration;
+
+                if tweener.value() != to_value {
+                    changed = true;
+                }
 
-            if flags.ff_to_current() {
-                if flags.delta() {
-                    todo!("LAYERCTRL: ff_to_current and delta flags have an interaction that is not yet implemented");
+                if flags.scale_time() {
+                    // this flag makes "duration" actually mean change rate (in value per tick)
+                    let change = (to_value - from_value).abs();
+                    duration = Ticks::from_f32(change / duration.as_f32());
                 }
 
-                let current = tweener.value();
-                tweener.fast_forward_to(current);
-            }
-            if flags.ff_to_target() {
-                tweener.fast_forward();
-            }
+                if flags.ff_to_current() {
+                    let current = tweener.value();
+                    tweener.fast_forward_to(current);
+                }
+                if flags.ff_to_target() {
+                    tweener.fast_forward();
+                }
 
-            tweener.enqueue(target_value as f32, Tween { duration, easing })
-        });
+                tweener.enqueue(target_value as f32, Tween { duration, easing })
+            });
 
         if !self.property_id.is_implemented() && changed {
             warn!(
@@ -1,40 +1,37 @@
 use std::fmt::{Debug, Formatter};
 
 use pollster::FutureExt;
-use shin_tasks::{AsyncComputeTaskPool, Task};
+use shin_core::vm::command::types::LayerId;
+use shin_tasks::{AsyncComputeTaskPool, Task, TaskPanic};
+use tracing::error;
 
 use super::prelude::*;
-use crate::layer::UserLayer;
+use crate::layer::{NullLayer, UserLayer};
 
 pub struct LAYERLOAD {
     token: Option<command::token::LAYERLOAD>,
     layer_id: VLayerId,
-    load_task: Option<Task<UserLayer>>,
+    /// A layer is loaded for each of the ids when a selection is used
+    load_tasks: Vec<(LayerId, Task<Result<UserLayer, TaskPanic>>)>,
 }
 
 impl StartableCommand for command::runtime::LAYERLOAD {
     fn apply_state(&self, state: &mut VmState) {
         assert_eq!(self.leave_uninitialized, 0); // I __think__ this has to do with init props/leave them be, but I'm not sure
 
-        match self.layer_id.repr() {
-            VLayerIdRepr::RootLayerGroup
-            | VLayerIdRepr::ScreenLayer
-            | VLayerIdRepr::PageLayer
-            | VLayerIdRepr::PlaneLayerGroup => {
-                unreachable!("You can't load special layers")
-            }
-            VLayerIdRepr::Selected => {
-                todo!("LAYERLOAD: selected");
-            }
-            VLayerIdRepr::Layer(id) => {
-                // unwrap_or_else is unusable because of borrow checker
-                let layer = match state.layers.get_layer_mut(id) {
-                    None => state.layers.alloc(id),
-                    Some(v) => v,
-                };
+        // the special layers can't be loaded, get_vlayer_ids warns about them
+        let ids = state
+            .layers
+            .get_vlayer_ids(self.layer_id)
+            .collect::<Vec<_>>();
+        for id in ids {
+            // unwrap_or_else is unusable because of borrow checker
+            let layer = match state.layers.get_layer_mut(id) {
+                None => state.layers.alloc(id),
+                Some(v) => v,
+            };
 
-                layer.layerinit_params = Some((self.layer_type, self.params));
-            }
+            layer.layerinit_params = Some((self.layer_type, self.params));
         }
     }
 
@@ -42,32 +39,52 @@ impl StartableCommand for command::runtime::LAYERLOAD {
         self,
         context: &UpdateContext,
         scenario: &Arc<Scenario>,
-        _vm_state: &VmState,
+        vm_state: &VmState,
         adv_state: &mut AdvState,
     ) -> CommandStartResult {
-        // TODO: loading should be done async
-        let resources = context.gpu_resources.clone();
-        let asset_server = context.asset_server.clone();
-        let audio_manager = adv_state.audio_manager.clone();
-        let scenario = scenario.clone();
+        let font_atlas = adv_state
+            .root_layer_group
+            .message_layer()
+            .font_atlas()
+            .clone();
+        let movie_options = adv_state.settings.get().movie.video_player_options();
+
+        let load_tasks = vm_state
+            .layers
+            .get_vlayer_ids(self.layer_id)
+            .map(|id| {
+                let resources = context.gpu_resources.clone();
+                let asset_server = context.asset_server.clone();
+                let audio_manager = adv_state.audio_manager.clone();
+                let lip_sync = adv_state.voice_player.lip_sync().clone();
+                let font_atlas = font_atlas.clone();
+                let scenario = scenario.clone();
+                let (layer_type, params) = (self.layer_type, self.params);
 
-        let load_task = AsyncComputeTaskPool::get().spawn(async move {
-            UserLayer::load(
-                &resources,
-                &asset_server,
-                &audio_manager,
-                &scenario,
-                self.layer_type,
-                self.params,
-            )
-            .await
-        });
+                let name = format!("LAYERLOAD {:?} {:?} {:?}", id, layer_type, params);
+                let task = AsyncComputeTaskPool::get().spawn_named(name, async move {
+                    UserLayer::load(
+                        &resources,
+                        &asset_server,
+                        &audio_manager,
+                        &lip_sync,
+                        &font_atlas,
+                        &scenario,
+                        movie_options,
+                        layer_type,
+                        params,
+                    )
+                    .await
+                });
+                (id, task)
+            })
+            .collect();
 
         Yield(
             LAYERLOAD {
                 token: Some(self.token),
                 layer_id: self.layer_id,
-                load_task: Some(load_task),
+                load_tasks,
             }
             .into(),
         )
@@ -83,22 +100,16 @@ impl UpdatableCommand for LAYERLOAD {
         adv_state: &mut AdvState,
         _is_fast_forwarding: bool,
     ) -> Option<CommandResult> {
-        if self.load_task.as_ref().unwrap().is_finished() {
-            let layer = self.load_task.take().unwrap().block_on();
-
-            match self.layer_id.repr() {
-                VLayerIdRepr::RootLayerGroup
-                | VLayerIdRepr::ScreenLayer
-                | VLayerIdRepr::PageLayer
-                | VLayerIdRepr::PlaneLayerGroup => {
-                    panic!("You can't load special layers")
-                }
-                VLayerIdRepr::Selected => {
-                    todo!("LAYERLOAD: selected");
-                }
-                VLayerIdRepr::Layer(id) => adv_state
+        // the layers are added all at once, so that the selection doesn't show up partially loaded
+        if self.load_tasks.iter().all(|(_, task)| task.is_finished()) {
+            for (id, task) in self.load_tasks.drain(..) {
+                let layer = task.block_on().unwrap_or_else(|panic| {
+                    error!("{}, using a NullLayer instead", panic);
+                    NullLayer::new().into()
+                });
+                adv_state
                     .current_plane_layer_group_mut(vm_state)
-                    .add_layer(id, layer),
+                    .add_layer(id, layer);
             }
 
             return Some(self.token.take().unwrap().finish());
@@ -3,18 +3,15 @@ use crate::adv::vm_state::layers::LayerSelection;
 
 impl StartableCommand for command::runtime::LAYERSELECT {
     fn apply_state(&self, state: &mut VmState) {
-        let mut low = self.selection_start_id;
-        let mut high = self.selection_end_id;
-
-        if low > high {
+        let (start, end) = (self.selection_start_id, self.selection_end_id);
+        if start > end {
             warn!(
                 "LAYERSELECT: invalid selection range order: {:?} > {:?}",
-                low, high
+                start, end
             );
-            std::mem::swap(&mut low, &mut high);
         }
 
-        state.layers.layer_selection = Some(LayerSelection { low, high })
+        state.layers.layer_selection = Some(LayerSelection::new(start, end))
     }
 
     fn start(
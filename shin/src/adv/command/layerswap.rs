@@ -0,0 +1,22 @@
+use super::prelude::*;
+
+impl StartableCommand for command::runtime::LAYERSWAP {
+    fn apply_state(&self, state: &mut VmState) {
+        state.layers.planes[state.layers.current_plane as usize]
+            .swap(self.layer_id1, self.layer_id2);
+    }
+
+    fn start(
+        self,
+        _context: &UpdateContext,
+        _scenario: &Arc<Scenario>,
+        vm_state: &VmState,
+        adv_state: &mut AdvState,
+    ) -> CommandStartResult {
+        adv_state
+            .current_plane_layer_group_mut(vm_state)
+            .swap_layers(self.layer_id1, self.layer_id2);
+
+        self.token.finish().into()
+    }
+}
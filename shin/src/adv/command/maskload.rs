@@ -0,0 +1,38 @@
+use super::prelude::*;
+use crate::{adv::vm_state::layers::MaskState, asset::mask::Mask, layer::LayerGroupMask};
+
+impl StartableCommand for command::runtime::MASKLOAD {
+    fn apply_state(&self, state: &mut VmState) {
+        state.layers.planes[state.layers.current_plane as usize].mask = Some(MaskState {
+            mask_id: self.mask_data_id,
+            flags: self.mask_flags,
+            transition: self.transition,
+        });
+    }
+
+    fn start(
+        self,
+        context: &UpdateContext,
+        scenario: &Arc<Scenario>,
+        vm_state: &VmState,
+        adv_state: &mut AdvState,
+    ) -> CommandStartResult {
+        let mask_info = scenario.info_tables().mask_info(self.mask_data_id);
+        match context
+            .asset_server
+            // TODO: sync - bad!!
+            .load_sync::<Mask, _>(mask_info.path())
+        {
+            Ok(mask) => adv_state
+                .current_plane_layer_group_mut(vm_state)
+                .set_mask(Some(LayerGroupMask {
+                    mask,
+                    flags: self.mask_flags,
+                    transition: self.transition,
+                })),
+            Err(e) => warn!("MASKLOAD: failed to load {}: {:?}", mask_info.path(), e),
+        }
+
+        self.token.finish().into()
+    }
+}
@@ -0,0 +1,21 @@
+use super::prelude::*;
+
+impl StartableCommand for command::runtime::MASKUNLOAD {
+    fn apply_state(&self, state: &mut VmState) {
+        state.layers.planes[state.layers.current_plane as usize].mask = None;
+    }
+
+    fn start(
+        self,
+        _context: &UpdateContext,
+        _scenario: &Arc<Scenario>,
+        vm_state: &VmState,
+        adv_state: &mut AdvState,
+    ) -> CommandStartResult {
+        adv_state
+            .current_plane_layer_group_mut(vm_state)
+            .set_mask(None);
+
+        self.token.finish().into()
+    }
+}
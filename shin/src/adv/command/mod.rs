@@ -36,8 +36,11 @@ mod layerctrl;
 mod layerinit;
 mod layerload;
 mod layerselect;
+mod layerswap;
 mod layerunload;
 mod layerwait;
+mod maskload;
+mod maskunload;
 mod moviewait;
 mod msgclose;
 mod msginit;
@@ -48,6 +51,8 @@ mod notifyset;
 mod pageback;
 mod planeclear;
 mod planeselect;
+mod resume;
+mod resumeset;
 mod saveinfo;
 mod sepan;
 mod seplay;
@@ -58,10 +63,15 @@ mod sewait;
 mod sget;
 mod showchars;
 mod sset;
+mod syscall;
 mod tipsget;
+mod transset;
+mod transwait;
 mod trophy;
 mod unlock;
 mod voiceplay;
+mod voicestop;
+mod voicewait;
 mod wait;
 mod wipe;
 
@@ -79,6 +89,9 @@ use shin_core::{
     format::scenario::Scenario,
     vm::command::{CommandResult, RuntimeCommand},
 };
+use syscall::SYSCALL;
+use transwait::TRANSWAIT;
+use voicewait::VOICEWAIT;
 use wait::WAIT;
 
 use crate::{
@@ -120,6 +133,19 @@ pub enum ExecutingCommand {
     SEWAIT,
     #[derivative(Debug = "transparent")]
     MOVIEWAIT,
+    #[derivative(Debug = "transparent")]
+    TRANSWAIT,
+    #[derivative(Debug = "transparent")]
+    VOICEWAIT,
+    #[derivative(Debug = "transparent")]
+    SYSCALL,
+}
+
+impl ExecutingCommand {
+    /// Waits for the message re-displayed after a rollback to be read
+    pub fn restored_message() -> Self {
+        MSGSET::restored().into()
+    }
 }
 
 impl StartableCommand for RuntimeCommand {
@@ -151,16 +177,16 @@ impl StartableCommand for RuntimeCommand {
             RuntimeCommand::SEWAIT(v) => v.apply_state(state),
             // RuntimeCommand::SEONCE(v) => v.apply_state(state),
             RuntimeCommand::VOICEPLAY(v) => v.apply_state(state),
-            // RuntimeCommand::VOICESTOP(v) => v.apply_state(state),
-            // RuntimeCommand::VOICEWAIT(v) => v.apply_state(state),
+            RuntimeCommand::VOICESTOP(v) => v.apply_state(state),
+            RuntimeCommand::VOICEWAIT(v) => v.apply_state(state),
             // RuntimeCommand::SYSSE(v) => v.apply_state(state),
             RuntimeCommand::SAVEINFO(v) => v.apply_state(state),
             RuntimeCommand::AUTOSAVE(v) => v.apply_state(state),
             RuntimeCommand::EVBEGIN(v) => v.apply_state(state),
             RuntimeCommand::EVEND(v) => v.apply_state(state),
-            // RuntimeCommand::RESUMESET(v) => v.apply_state(state),
-            // RuntimeCommand::RESUME(v) => v.apply_state(state),
-            // RuntimeCommand::SYSCALL(v) => v.apply_state(state),
+            RuntimeCommand::RESUMESET(v) => v.apply_state(state),
+            RuntimeCommand::RESUME(v) => v.apply_state(state),
+            RuntimeCommand::SYSCALL(v) => v.apply_state(state),
             RuntimeCommand::TROPHY(v) => v.apply_state(state),
             RuntimeCommand::UNLOCK(v) => v.apply_state(state),
             RuntimeCommand::LAYERINIT(v) => v.apply_state(state),
@@ -168,16 +194,16 @@ impl StartableCommand for RuntimeCommand {
             RuntimeCommand::LAYERUNLOAD(v) => v.apply_state(state),
             RuntimeCommand::LAYERCTRL(v) => v.apply_state(state),
             RuntimeCommand::LAYERWAIT(v) => v.apply_state(state),
-            // RuntimeCommand::LAYERSWAP(v) => v.apply_state(state),
+            RuntimeCommand::LAYERSWAP(v) => v.apply_state(state),
             RuntimeCommand::LAYERSELECT(v) => v.apply_state(state),
             RuntimeCommand::MOVIEWAIT(v) => v.apply_state(state),
-            // RuntimeCommand::TRANSSET(v) => v.apply_state(state),
-            // RuntimeCommand::TRANSWAIT(v) => v.apply_state(state),
+            RuntimeCommand::TRANSSET(v) => v.apply_state(state),
+            RuntimeCommand::TRANSWAIT(v) => v.apply_state(state),
             RuntimeCommand::PAGEBACK(v) => v.apply_state(state),
             RuntimeCommand::PLANESELECT(v) => v.apply_state(state),
             RuntimeCommand::PLANECLEAR(v) => v.apply_state(state),
-            // RuntimeCommand::MASKLOAD(v) => v.apply_state(state),
-            // RuntimeCommand::MASKUNLOAD(v) => v.apply_state(state),
+            RuntimeCommand::MASKLOAD(v) => v.apply_state(state),
+            RuntimeCommand::MASKUNLOAD(v) => v.apply_state(state),
             RuntimeCommand::CHARS(v) => v.apply_state(state),
             RuntimeCommand::TIPSGET(v) => v.apply_state(state),
             // RuntimeCommand::QUIZ(v) => v.apply_state(state),
@@ -222,16 +248,16 @@ impl StartableCommand for RuntimeCommand {
             RuntimeCommand::SEWAIT(v) => v.start(context, scenario, vm_state, adv_state),
             // RuntimeCommand::SEONCE(v) => v.start(context, scenario, vm_state, adv_state),
             RuntimeCommand::VOICEPLAY(v) => v.start(context, scenario, vm_state, adv_state),
-            // RuntimeCommand::VOICESTOP(v) => v.start(context, scenario, vm_state, adv_state),
-            // RuntimeCommand::VOICEWAIT(v) => v.start(context, scenario, vm_state, adv_state),
+            RuntimeCommand::VOICESTOP(v) => v.start(context, scenario, vm_state, adv_state),
+            RuntimeCommand::VOICEWAIT(v) => v.start(context, scenario, vm_state, adv_state),
             // RuntimeCommand::SYSSE(v) => v.start(context, scenario, vm_state, adv_state),
             RuntimeCommand::SAVEINFO(v) => v.start(context, scenario, vm_state, adv_state),
             RuntimeCommand::AUTOSAVE(v) => v.start(context, scenario, vm_state, adv_state),
             RuntimeCommand::EVBEGIN(v) => v.start(context, scenario, vm_state, adv_state),
             RuntimeCommand::EVEND(v) => v.start(context, scenario, vm_state, adv_state),
-            // RuntimeCommand::RESUMESET(v) => v.start(context, scenario, vm_state, adv_state),
-            // RuntimeCommand::RESUME(v) => v.start(context, scenario, vm_state, adv_state),
-            // RuntimeCommand::SYSCALL(v) => v.start(context, scenario, vm_state, adv_state),
+            RuntimeCommand::RESUMESET(v) => v.start(context, scenario, vm_state, adv_state),
+            RuntimeCommand::RESUME(v) => v.start(context, scenario, vm_state, adv_state),
+            RuntimeCommand::SYSCALL(v) => v.start(context, scenario, vm_state, adv_state),
             RuntimeCommand::TROPHY(v) => v.start(context, scenario, vm_state, adv_state),
             RuntimeCommand::UNLOCK(v) => v.start(context, scenario, vm_state, adv_state),
             RuntimeCommand::LAYERINIT(v) => v.start(context, scenario, vm_state, adv_state),
@@ -239,16 +265,16 @@ impl StartableCommand for RuntimeCommand {
             RuntimeCommand::LAYERUNLOAD(v) => v.start(context, scenario, vm_state, adv_state),
             RuntimeCommand::LAYERCTRL(v) => v.start(context, scenario, vm_state, adv_state),
             RuntimeCommand::LAYERWAIT(v) => v.start(context, scenario, vm_state, adv_state),
-            // RuntimeCommand::LAYERSWAP(v) => v.start(context, scenario, vm_state, adv_state),
+            RuntimeCommand::LAYERSWAP(v) => v.start(context, scenario, vm_state, adv_state),
             RuntimeCommand::LAYERSELECT(v) => v.start(context, scenario, vm_state, adv_state),
             RuntimeCommand::MOVIEWAIT(v) => v.start(context, scenario, vm_state, adv_state),
-            // RuntimeCommand::TRANSSET(v) => v.start(context, scenario, vm_state, adv_state),
-            // RuntimeCommand::TRANSWAIT(v) => v.start(context, scenario, vm_state, adv_state),
+            RuntimeCommand::TRANSSET(v) => v.start(context, scenario, vm_state, adv_state),
+            RuntimeCommand::TRANSWAIT(v) => v.start(context, scenario, vm_state, adv_state),
             RuntimeCommand::PAGEBACK(v) => v.start(context, scenario, vm_state, adv_state),
             RuntimeCommand::PLANESELECT(v) => v.start(context, scenario, vm_state, adv_state),
             RuntimeCommand::PLANECLEAR(v) => v.start(context, scenario, vm_state, adv_state),
-            // RuntimeCommand::MASKLOAD(v) => v.start(context, scenario, vm_state, adv_state),
-            // RuntimeCommand::MASKUNLOAD(v) => v.start(context, scenario, vm_state, adv_state),
+            RuntimeCommand::MASKLOAD(v) => v.start(context, scenario, vm_state, adv_state),
+            RuntimeCommand::MASKUNLOAD(v) => v.start(context, scenario, vm_state, adv_state),
             RuntimeCommand::CHARS(v) => v.start(context, scenario, vm_state, adv_state),
             RuntimeCommand::TIPSGET(v) => v.start(context, scenario, vm_state, adv_state),
             // RuntimeCommand::QUIZ(v) => v.start(context, scenario, vm_state, adv_state),
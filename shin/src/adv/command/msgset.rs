@@ -3,10 +3,16 @@ use std::fmt::{Debug, Formatter};
 use super::prelude::*;
 
 pub struct MSGSET {
-    #[allow(unused)]
+    /// `None` for a message re-displayed after a rollback, which has no command to finish
     token: Option<command::token::MSGSET>,
 }
 
+impl MSGSET {
+    pub fn restored() -> Self {
+        Self { token: None }
+    }
+}
+
 impl StartableCommand for command::runtime::MSGSET {
     fn apply_state(&self, state: &mut VmState) {
         // TODO: think about async messages (those where you would use MSGWAIT)
@@ -51,7 +57,11 @@ impl UpdatableCommand for MSGSET {
         _is_fast_forwarding: bool,
     ) -> Option<CommandResult> {
         if adv_state.root_layer_group.message_layer().is_finished() {
-            Some(self.token.take().unwrap().finish())
+            Some(
+                self.token
+                    .take()
+                    .map_or(CommandResult::None, |token| token.finish()),
+            )
         } else {
             None
         }
@@ -10,9 +10,12 @@ impl StartableCommand for command::runtime::NOTIFYSET {
         _context: &UpdateContext,
         _scenario: &Arc<Scenario>,
         _vm_state: &VmState,
-        _adv_state: &mut AdvState,
+        adv_state: &mut AdvState,
     ) -> CommandStartResult {
+        // TODO: the argument is the notification type, but the texts for them are not known yet
         warn!("TODO: NOTIFYSET: {:?}", self);
+        let text = adv_state.i18n.tr("toast.menu_updated");
+        adv_state.toast_layer.push(&text);
         self.token.finish().into()
     }
 }
@@ -1,12 +1,8 @@
 use super::prelude::*;
 
+// PAGEBACK marks the start of a page for the rollback, it's handled in `Adv::update` (see `adv::rollback`)
 impl StartableCommand for command::runtime::PAGEBACK {
-    fn apply_state(&self, _state: &mut VmState) {
-        warn!("TODO: PAGEBACK state: {:?}", self);
-        // TODO: I __think__ we should have a way to store this in the state
-        // I am still not sure of the paradigm, lol
-        // ignore for now (along with WIPE)
-    }
+    fn apply_state(&self, _state: &mut VmState) {}
 
     fn start(
         self,
@@ -15,7 +11,6 @@ impl StartableCommand for command::runtime::PAGEBACK {
         _vm_state: &VmState,
         _adv_state: &mut AdvState,
     ) -> CommandStartResult {
-        warn!("TODO: PAGEBACK: {:?}", self);
         self.token.finish().into()
     }
 }
@@ -0,0 +1,16 @@
+use super::prelude::*;
+
+// RESUME restores the scene to the point recorded by RESUMESET, it's handled in `Adv::update`
+impl StartableCommand for command::runtime::RESUME {
+    fn apply_state(&self, _state: &mut VmState) {}
+
+    fn start(
+        self,
+        _context: &UpdateContext,
+        _scenario: &Arc<Scenario>,
+        _vm_state: &VmState,
+        _adv_state: &mut AdvState,
+    ) -> CommandStartResult {
+        self.token.finish().into()
+    }
+}
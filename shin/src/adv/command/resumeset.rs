@@ -0,0 +1,16 @@
+use super::prelude::*;
+
+// RESUMESET records the point RESUME returns to, it's handled in `Adv::update` (like PAGEBACK)
+impl StartableCommand for command::runtime::RESUMESET {
+    fn apply_state(&self, _state: &mut VmState) {}
+
+    fn start(
+        self,
+        _context: &UpdateContext,
+        _scenario: &Arc<Scenario>,
+        _vm_state: &VmState,
+        _adv_state: &mut AdvState,
+    ) -> CommandStartResult {
+        self.token.finish().into()
+    }
+}
@@ -10,8 +10,12 @@ impl StartableCommand for command::runtime::SAVEINFO {
         _context: &UpdateContext,
         _scenario: &Arc<Scenario>,
         _vm_state: &VmState,
-        _adv_state: &mut AdvState,
+        adv_state: &mut AdvState,
     ) -> CommandStartResult {
+        // the game shows the new save info for a while
+        if !self.info.is_empty() {
+            adv_state.toast_layer.push(&self.info);
+        }
         self.token.finish().into()
     }
 }
@@ -28,20 +28,21 @@ impl StartableCommand for command::runtime::SEPLAY {
 
         let se_info = scenario.info_tables().se_info(self.se_data_id);
 
-        let audio = context
+        match context
             .asset_server
             // TODO: sync - bad!!
             .load_sync(se_info.path())
-            .expect("Failed to load BGM track");
-
-        adv_state.se_player.play(
-            self.se_slot,
-            audio,
-            !self.no_repeat,
-            self.volume,
-            self.pan,
-            Tween::linear(self.fade_in_time),
-        );
+        {
+            Ok(audio) => adv_state.se_player.play(
+                self.se_slot,
+                audio,
+                !self.no_repeat,
+                self.volume,
+                self.pan,
+                Tween::linear(self.fade_in_time),
+            ),
+            Err(e) => warn!("SEPLAY: failed to load {}: {:?}", se_info.path(), e),
+        }
 
         self.token.finish().into()
     }
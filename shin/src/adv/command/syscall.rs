@@ -0,0 +1,57 @@
+use std::fmt::{Debug, Formatter};
+
+use super::prelude::*;
+
+/// Waits for the system screen opened by SYSCALL to be closed
+pub struct SYSCALL {
+    token: Option<command::token::SYSCALL>,
+}
+
+impl StartableCommand for command::runtime::SYSCALL {
+    fn apply_state(&self, _state: &mut VmState) {
+        // nothing to do
+    }
+
+    fn start(
+        self,
+        _context: &UpdateContext,
+        _scenario: &Arc<Scenario>,
+        _vm_state: &VmState,
+        adv_state: &mut AdvState,
+    ) -> CommandStartResult {
+        // TODO: the meaning of the arguments is not known yet, they probably select the system screen to open
+        // for now, just open the system menu, so that the player can choose
+        warn!("TODO: SYSCALL arguments: {:?}", self);
+        adv_state.open_system_menu();
+
+        Yield(
+            SYSCALL {
+                token: Some(self.token),
+            }
+            .into(),
+        )
+    }
+}
+
+impl UpdatableCommand for SYSCALL {
+    fn update(
+        &mut self,
+        _context: &UpdateContext,
+        _scenario: &Arc<Scenario>,
+        _vm_state: &VmState,
+        adv_state: &mut AdvState,
+        _is_fast_forwarding: bool,
+    ) -> Option<CommandResult> {
+        if adv_state.system_menu.is_open() {
+            None
+        } else {
+            Some(self.token.take().unwrap().finish())
+        }
+    }
+}
+
+impl Debug for SYSCALL {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("SYSCALL").finish()
+    }
+}
@@ -10,9 +10,11 @@ impl StartableCommand for command::runtime::TIPSGET {
         _context: &UpdateContext,
         _scenario: &Arc<Scenario>,
         _vm_state: &VmState,
-        _adv_state: &mut AdvState,
+        adv_state: &mut AdvState,
     ) -> CommandStartResult {
         warn!("TODO: TIPSGET: {:?}", self);
+        let text = adv_state.i18n.tr("toast.tips_updated");
+        adv_state.toast_layer.push(&text);
         self.token.finish().into()
     }
 }
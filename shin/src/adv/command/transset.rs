@@ -0,0 +1,32 @@
+use shin_core::vm::command::types::WiperParams;
+
+use super::prelude::*;
+
+impl StartableCommand for command::runtime::TRANSSET {
+    fn apply_state(&self, _state: &mut VmState) {
+        // transitions are purely visual, they do not exist in the VmState
+    }
+
+    fn start(
+        self,
+        context: &UpdateContext,
+        _scenario: &Arc<Scenario>,
+        vm_state: &VmState,
+        adv_state: &mut AdvState,
+    ) -> CommandStartResult {
+        if self.arg3 != 0 {
+            warn!("TRANSSET: unknown arg3 = {}, ignoring", self.arg3);
+        }
+
+        adv_state
+            .current_plane_layer_group_mut(vm_state)
+            .start_transition(
+                context.gpu_resources,
+                self.wiper_type,
+                WiperParams::from(self.params),
+                self.duration,
+            );
+
+        self.token.finish().into()
+    }
+}
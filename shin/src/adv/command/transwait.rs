@@ -0,0 +1,55 @@
+use std::fmt::{Debug, Formatter};
+
+use super::prelude::*;
+
+pub struct TRANSWAIT {
+    token: Option<command::token::TRANSWAIT>,
+}
+
+impl StartableCommand for command::runtime::TRANSWAIT {
+    fn apply_state(&self, _state: &mut VmState) {
+        // transitions do not exist in the VmState, no need to wait
+    }
+
+    fn start(
+        self,
+        _context: &UpdateContext,
+        _scenario: &Arc<Scenario>,
+        _vm_state: &VmState,
+        _adv_state: &mut AdvState,
+    ) -> CommandStartResult {
+        // `arg` is not understood yet, so the whole transition is waited for regardless of it
+        Yield(
+            TRANSWAIT {
+                token: Some(self.token),
+            }
+            .into(),
+        )
+    }
+}
+
+impl UpdatableCommand for TRANSWAIT {
+    fn update(
+        &mut self,
+        _context: &UpdateContext,
+        _scenario: &Arc<Scenario>,
+        vm_state: &VmState,
+        adv_state: &mut AdvState,
+        is_fast_forwarding: bool,
+    ) -> Option<CommandResult> {
+        let layer_group = adv_state.current_plane_layer_group_mut(vm_state);
+        if is_fast_forwarding {
+            layer_group.finish_transition();
+        }
+
+        layer_group
+            .is_transition_finished()
+            .then(|| self.token.take().unwrap().finish())
+    }
+}
+
+impl Debug for TRANSWAIT {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("TRANSWAIT").finish()
+    }
+}
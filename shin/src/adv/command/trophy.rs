@@ -2,7 +2,7 @@ use super::prelude::*;
 
 impl StartableCommand for command::runtime::TROPHY {
     fn apply_state(&self, _state: &mut VmState) {
-        warn!("TODO: TROPHY state: {:?}", self);
+        // the trophies are not a part of the scene, they are recorded in `start`
     }
 
     fn start(
@@ -10,9 +10,12 @@ impl StartableCommand for command::runtime::TROPHY {
         _context: &UpdateContext,
         _scenario: &Arc<Scenario>,
         _vm_state: &VmState,
-        _adv_state: &mut AdvState,
+        adv_state: &mut AdvState,
     ) -> CommandStartResult {
-        warn!("TODO: TROPHY: {:?}", self);
+        if adv_state.achievements.unlock(self.trophy_id) {
+            let text = adv_state.i18n.tr("toast.trophy_unlocked");
+            adv_state.toast_layer.push(&text);
+        }
         self.token.finish().into()
     }
 }
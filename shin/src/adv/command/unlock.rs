@@ -1,8 +1,9 @@
 use super::prelude::*;
+use crate::unlocks::UnlockType;
 
 impl StartableCommand for command::runtime::UNLOCK {
     fn apply_state(&self, _state: &mut VmState) {
-        warn!("TODO: UNLOCK state: {:?}", self);
+        // the unlocks are not a part of the scene, they are recorded in `start`
     }
 
     fn start(
@@ -10,9 +11,16 @@ impl StartableCommand for command::runtime::UNLOCK {
         _context: &UpdateContext,
         _scenario: &Arc<Scenario>,
         _vm_state: &VmState,
-        _adv_state: &mut AdvState,
+        adv_state: &mut AdvState,
     ) -> CommandStartResult {
-        warn!("TODO: UNLOCK: {:?}", self);
+        match UnlockType::from_raw(self.unlock_type) {
+            Some(ty) => {
+                for &index in &self.unlock_indices {
+                    adv_state.unlocks.unlock(ty, index);
+                }
+            }
+            None => warn!("UNLOCK of an unknown type: {:?}", self),
+        }
         self.token.finish().into()
     }
 }
@@ -2,17 +2,34 @@ use super::prelude::*;
 
 impl StartableCommand for command::runtime::VOICEPLAY {
     fn apply_state(&self, _state: &mut VmState) {
-        warn!("TODO: VOICEPLAY state: {:?}", self);
+        // voices are not restored when loading a save
     }
 
     fn start(
         self,
-        _context: &UpdateContext,
-        _scenario: &Arc<Scenario>,
+        context: &UpdateContext,
+        scenario: &Arc<Scenario>,
         _vm_state: &VmState,
-        _adv_state: &mut AdvState,
+        adv_state: &mut AdvState,
     ) -> CommandStartResult {
-        warn!("TODO: VOICEPLAY: {:?}", self);
+        if self.flags != 0 {
+            warn!("TODO: VOICEPLAY: ignoring flags={}", self.flags);
+        }
+
+        let path = format!("/voice/{}.nxa", self.name.to_ascii_lowercase());
+        let character_ids = scenario.info_tables().voice_character_ids(&self.name);
+
+        match context
+            .asset_server
+            // TODO: sync - bad!!
+            .load_sync(&path)
+        {
+            Ok(audio) => adv_state
+                .voice_player
+                .play(audio, character_ids, self.volume),
+            Err(e) => warn!("VOICEPLAY: failed to load {}: {:?}", path, e),
+        }
+
         self.token.finish().into()
     }
 }
@@ -0,0 +1,20 @@
+use shin_core::time::Tween;
+
+use super::prelude::*;
+
+impl StartableCommand for command::runtime::VOICESTOP {
+    fn apply_state(&self, _state: &mut VmState) {
+        // nothing to do
+    }
+
+    fn start(
+        self,
+        _context: &UpdateContext,
+        _scenario: &Arc<Scenario>,
+        _vm_state: &VmState,
+        adv_state: &mut AdvState,
+    ) -> CommandStartResult {
+        adv_state.voice_player.stop(Tween::MS_15);
+        self.token.finish().into()
+    }
+}
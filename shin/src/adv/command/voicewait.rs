@@ -0,0 +1,60 @@
+use std::fmt::{Debug, Formatter};
+
+use shin_core::vm::command::types::AudioWaitStatus;
+
+use super::prelude::*;
+
+pub struct VOICEWAIT {
+    token: Option<command::token::VOICEWAIT>,
+    target_status: AudioWaitStatus,
+}
+
+impl StartableCommand for command::runtime::VOICEWAIT {
+    fn apply_state(&self, _state: &mut VmState) {
+        // nothing to do
+    }
+
+    fn start(
+        self,
+        _context: &UpdateContext,
+        _scenario: &Arc<Scenario>,
+        _vm_state: &VmState,
+        _adv_state: &mut AdvState,
+    ) -> CommandStartResult {
+        Yield(
+            VOICEWAIT {
+                token: Some(self.token),
+                target_status: self.target_status,
+            }
+            .into(),
+        )
+    }
+}
+
+impl UpdatableCommand for VOICEWAIT {
+    fn update(
+        &mut self,
+        _context: &UpdateContext,
+        _scenario: &Arc<Scenario>,
+        _vm_state: &VmState,
+        adv_state: &mut AdvState,
+        _is_fast_forwarding: bool,
+    ) -> Option<CommandResult> {
+        let status = adv_state.voice_player.get_wait_status();
+        let finished = !(status & self.target_status).is_empty();
+
+        if finished {
+            Some(self.token.take().unwrap().finish())
+        } else {
+            None
+        }
+    }
+}
+
+impl Debug for VOICEWAIT {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("VOICEWAIT")
+            .field(&self.target_status)
+            .finish()
+    }
+}
@@ -0,0 +1,432 @@
+//! The scenario debugger: pausing the VM at scenario addresses or on commands, single-stepping it and resuming it from the overlay.
+//!
+//! The VM is only paused between the commands (or between the instructions when single-stepping), the command being executed always finishes first.
+//! A command matching one of the break-on names pauses the VM right after the VM issued it, before it's started.
+//! While paused, the scene keeps animating, only the scenario doesn't progress.
+//!
+//! Watched registers are shown with their values, highlighted for a moment after they change. A watch can also pause the VM right after the instruction changing the register.
+
+use std::{
+    fmt::Display,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use egui::{Color32, RichText, Window};
+use shin_core::{
+    format::scenario::instruction_elements::CodeAddress,
+    vm::{
+        breakpoint::BreakpointHandle,
+        command::{CommandResult, RuntimeCommand},
+        RegisterWrite, Scripter, Step,
+    },
+};
+use tracing::{info, warn};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PauseReason {
+    /// Paused from the overlay
+    Manual,
+    Breakpoint(CodeAddress),
+    Command(&'static str),
+    Watch(RegisterWrite),
+}
+
+impl Display for PauseReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PauseReason::Manual => write!(f, "paused"),
+            PauseReason::Breakpoint(address) => write!(f, "breakpoint at {}", address),
+            PauseReason::Command(name) => write!(f, "break on {}", name),
+            PauseReason::Watch(write) => write!(
+                f,
+                "$v{} changed from {} to {}",
+                write.index, write.old_value, write.new_value
+            ),
+        }
+    }
+}
+
+/// An action requested from the overlay, which only has shared access to the debugger
+#[derive(Debug)]
+enum Request {
+    Pause,
+    Resume,
+    Step,
+    AddBreakpoint(CodeAddress),
+    RemoveBreakpoint(CodeAddress),
+    BreakOn(String),
+    RemoveBreakOn(String),
+    AddWatch(u16),
+    RemoveWatch(u16),
+    SetWatchBreak(u16, bool),
+}
+
+/// How long a watched register is highlighted after it changes
+const CHANGE_HIGHLIGHT: Duration = Duration::from_secs(1);
+
+struct Watch {
+    index: u16,
+    break_on_change: bool,
+    /// The last change of the register, with the time it happened at
+    last_change: Option<(RegisterWrite, Instant)>,
+}
+
+#[derive(Default)]
+struct OverlayState {
+    requests: Vec<Request>,
+    breakpoint_input: String,
+    command_input: String,
+    watch_input: String,
+}
+
+pub struct VmDebugger {
+    breakpoints: Vec<(CodeAddress, BreakpointHandle)>,
+    /// Names of the commands to pause on, in upper case
+    break_on_commands: Vec<String>,
+    watches: Vec<Watch>,
+    paused: Option<PauseReason>,
+    /// Instructions allowed to execute while paused, requested with the step button
+    steps: u32,
+    /// The command that triggered a break-on-command pause, it's started when the VM is resumed
+    held_command: Option<RuntimeCommand>,
+    overlay_state: Mutex<OverlayState>,
+}
+
+impl VmDebugger {
+    pub fn new() -> Self {
+        Self {
+            breakpoints: Vec::new(),
+            break_on_commands: Vec::new(),
+            watches: Vec::new(),
+            paused: None,
+            steps: 0,
+            held_command: None,
+            overlay_state: Mutex::new(OverlayState::default()),
+        }
+    }
+
+    pub fn add_breakpoint(&mut self, scripter: &mut Scripter, address: CodeAddress) {
+        if self.breakpoints.iter().any(|(a, _)| *a == address) {
+            return;
+        }
+        let handle = scripter.add_breakpoint(address);
+        handle.set_pause(true);
+        self.breakpoints.push((address, handle));
+    }
+
+    fn remove_breakpoint(&mut self, address: CodeAddress) {
+        self.breakpoints.retain(|(a, handle)| {
+            if *a == address {
+                // other users of the same breakpoint (like the fast forward) shouldn't pause the VM
+                handle.set_pause(false);
+            }
+            *a != address
+        });
+    }
+
+    pub fn break_on_command(&mut self, name: &str) {
+        let name = name.to_ascii_uppercase();
+        if !self.break_on_commands.contains(&name) {
+            self.break_on_commands.push(name);
+        }
+    }
+
+    pub fn add_watch(&mut self, scripter: &mut Scripter, index: u16) {
+        if index >= 0x1000 {
+            warn!("Can't watch $v{}, there are only 4096 registers", index);
+            return;
+        }
+        if self.watches.iter().any(|w| w.index == index) {
+            return;
+        }
+        scripter.watch_register(index);
+        self.watches.push(Watch {
+            index,
+            break_on_change: false,
+            last_change: None,
+        });
+    }
+
+    fn remove_watch(&mut self, scripter: &mut Scripter, index: u16) {
+        scripter.unwatch_register(index);
+        self.watches.retain(|w| w.index != index);
+    }
+
+    /// Records the changes of the watched registers, returns the first one that should pause the VM
+    fn observe_writes(&mut self, scripter: &mut Scripter) -> Option<RegisterWrite> {
+        let now = Instant::now();
+        let mut break_write = None;
+        for write in scripter.take_register_writes() {
+            let Some(watch) = self.watches.iter_mut().find(|w| w.index == write.index) else {
+                continue;
+            };
+            watch.last_change = Some((write, now));
+            if watch.break_on_change && break_write.is_none() {
+                break_write = Some(write);
+            }
+        }
+        break_write
+    }
+
+    /// Forgets the command the VM was paused on, used when the VM state is replaced
+    pub fn drop_held_command(&mut self) {
+        self.held_command = None;
+    }
+
+    fn pause(&mut self, reason: PauseReason, scripter: &Scripter) {
+        info!(
+            "Scenario paused ({}), next instruction at {}",
+            reason,
+            scripter.next_position()
+        );
+        self.paused = Some(reason);
+        self.steps = 0;
+    }
+
+    /// Applies the actions requested from the overlay since the last update
+    pub fn update(&mut self, scripter: &mut Scripter) {
+        let requests = std::mem::take(&mut self.overlay_state.get_mut().unwrap().requests);
+        for request in requests {
+            match request {
+                Request::Pause => {
+                    if self.paused.is_none() {
+                        self.pause(PauseReason::Manual, scripter);
+                    }
+                }
+                Request::Resume => {
+                    self.paused = None;
+                    self.steps = 0;
+                }
+                Request::Step => {
+                    if self.paused.is_some() {
+                        self.steps += 1;
+                    }
+                }
+                Request::AddBreakpoint(address) => self.add_breakpoint(scripter, address),
+                Request::RemoveBreakpoint(address) => self.remove_breakpoint(address),
+                Request::BreakOn(name) => self.break_on_command(&name),
+                Request::RemoveBreakOn(name) => self.break_on_commands.retain(|n| *n != name),
+                Request::AddWatch(index) => self.add_watch(scripter, index),
+                Request::RemoveWatch(index) => self.remove_watch(scripter, index),
+                Request::SetWatchBreak(index, value) => {
+                    if let Some(watch) = self.watches.iter_mut().find(|w| w.index == index) {
+                        watch.break_on_change = value;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Uses up a step if the VM is paused
+    fn take_step(&mut self) {
+        if self.paused.is_some() {
+            self.steps = self.steps.saturating_sub(1);
+        }
+    }
+
+    /// Runs the VM until it issues a command, returns `None` if it is paused instead
+    ///
+    /// The `result` of the previous command is passed to the VM right away, even when paused.
+    pub fn next_command(
+        &mut self,
+        scripter: &mut Scripter,
+        result: CommandResult,
+    ) -> Result<Option<RuntimeCommand>> {
+        scripter.set_command_result(result);
+        if let Some(write) = self.observe_writes(scripter) {
+            if self.paused.is_none() {
+                self.pause(PauseReason::Watch(write), scripter);
+            }
+        }
+
+        if self.paused.is_none()
+            && self.held_command.is_none()
+            && self.breakpoints.is_empty()
+            && self.break_on_commands.is_empty()
+            && !self.watches.iter().any(|w| w.break_on_change)
+        {
+            // the fast path, nothing to stop at
+            let command = scripter.run(CommandResult::None)?;
+            self.observe_writes(scripter);
+            return Ok(Some(command));
+        }
+
+        loop {
+            if self.paused.is_some() && self.steps == 0 {
+                return Ok(None);
+            }
+            if let Some(command) = self.held_command.take() {
+                self.take_step();
+                return Ok(Some(command));
+            }
+
+            match scripter.step()? {
+                Step::Executed => {
+                    self.take_step();
+                    if let Some(write) = self.observe_writes(scripter) {
+                        if self.paused.is_none() {
+                            self.pause(PauseReason::Watch(write), scripter);
+                        }
+                    }
+                }
+                Step::Breakpoint(address) => self.pause(PauseReason::Breakpoint(address), scripter),
+                Step::Command(command) => {
+                    // when single-stepping, the commands are executed without stopping again
+                    if self.paused.is_none()
+                        && self
+                            .break_on_commands
+                            .iter()
+                            .any(|name| name == command.name())
+                    {
+                        self.pause(PauseReason::Command(command.name()), scripter);
+                        self.held_command = Some(command);
+                        continue;
+                    }
+                    self.take_step();
+                    return Ok(Some(command));
+                }
+            }
+        }
+    }
+
+    pub fn show_overlay(&self, ctx: &egui::Context, scripter: &Scripter) {
+        let mut state = self.overlay_state.lock().unwrap();
+        let state = &mut *state;
+
+        Window::new("VM Debugger").show(ctx, |ui| {
+            match self.paused {
+                None => ui.monospace("Running"),
+                Some(reason) => ui.monospace(format!("Paused: {}", reason)),
+            };
+            ui.monospace(format!(
+                "Last: {}  Next: {}",
+                scripter.position(),
+                scripter.next_position()
+            ));
+            if let Some(command) = &self.held_command {
+                ui.monospace(format!("Held: {:?}", command));
+            }
+            ui.monospace(format!(
+                "Call stack: {}",
+                scripter
+                    .ctx()
+                    .call_stack()
+                    .iter()
+                    .rev()
+                    .map(|a| a.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" <- ")
+            ));
+
+            ui.horizontal(|ui| {
+                if self.paused.is_some() {
+                    if ui.button("Resume").clicked() {
+                        state.requests.push(Request::Resume);
+                    }
+                    if ui.button("Step").clicked() {
+                        state.requests.push(Request::Step);
+                    }
+                } else if ui.button("Pause").clicked() {
+                    state.requests.push(Request::Pause);
+                }
+            });
+
+            ui.separator();
+            ui.monospace("Breakpoints:");
+            for (address, handle) in &self.breakpoints {
+                ui.horizontal(|ui| {
+                    ui.monospace(format!("{} (hit {} times)", address, handle.hit_count()));
+                    if ui.small_button("x").clicked() {
+                        state.requests.push(Request::RemoveBreakpoint(*address));
+                    }
+                });
+            }
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut state.breakpoint_input);
+                if ui.button("Add").clicked() {
+                    let input = state.breakpoint_input.trim();
+                    let input = input.strip_prefix("0x").unwrap_or(input);
+                    match u32::from_str_radix(input, 16) {
+                        Ok(address) => {
+                            state
+                                .requests
+                                .push(Request::AddBreakpoint(CodeAddress(address)));
+                            state.breakpoint_input.clear();
+                        }
+                        Err(_) => warn!("Invalid breakpoint address {:?}", input),
+                    }
+                }
+            });
+
+            ui.separator();
+            ui.monospace("Break on commands:");
+            for name in &self.break_on_commands {
+                ui.horizontal(|ui| {
+                    ui.monospace(name);
+                    if ui.small_button("x").clicked() {
+                        state.requests.push(Request::RemoveBreakOn(name.clone()));
+                    }
+                });
+            }
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut state.command_input);
+                if ui.button("Add").clicked() && !state.command_input.trim().is_empty() {
+                    let name = std::mem::take(&mut state.command_input);
+                    state
+                        .requests
+                        .push(Request::BreakOn(name.trim().to_string()));
+                }
+            });
+
+            ui.separator();
+            ui.monospace("Watches:");
+            let registers = scripter.ctx().registers();
+            for watch in &self.watches {
+                ui.horizontal(|ui| {
+                    let mut text =
+                        format!("$v{} = {}", watch.index, registers[watch.index as usize]);
+                    let mut recently_changed = false;
+                    if let Some((write, time)) = watch.last_change {
+                        text += &format!(" (was {})", write.old_value);
+                        recently_changed = time.elapsed() < CHANGE_HIGHLIGHT;
+                    }
+                    if recently_changed {
+                        ui.label(RichText::new(text).monospace().color(Color32::YELLOW));
+                    } else {
+                        ui.monospace(text);
+                    }
+
+                    let mut break_on_change = watch.break_on_change;
+                    if ui.checkbox(&mut break_on_change, "break").changed() {
+                        state
+                            .requests
+                            .push(Request::SetWatchBreak(watch.index, break_on_change));
+                    }
+                    if ui.small_button("x").clicked() {
+                        state.requests.push(Request::RemoveWatch(watch.index));
+                    }
+                });
+            }
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut state.watch_input);
+                if ui.button("Add").clicked() {
+                    let input = state.watch_input.trim();
+                    match parse_register_index(input) {
+                        Some(index) => {
+                            state.requests.push(Request::AddWatch(index));
+                            state.watch_input.clear();
+                        }
+                        None => warn!("Invalid register {:?}", input),
+                    }
+                }
+            });
+        });
+    }
+}
+
+/// Parses a main memory register, either as `$v12` or as just `12`
+fn parse_register_index(s: &str) -> Option<u16> {
+    s.strip_prefix("$v").unwrap_or(s).parse().ok()
+}
@@ -1,73 +1,419 @@
 pub mod assets;
 mod command;
-mod vm_state;
+mod debugger;
+mod prefetch;
+mod rollback;
+mod save;
+mod scene_jump;
+mod system_menu;
+pub mod vm_state;
 
-use std::{borrow::Cow, sync::Arc};
+use std::{borrow::Cow, path::PathBuf, sync::Arc};
 
+use anyhow::{Context, Result};
 pub use command::{CommandStartResult, ExecutingCommand, StartableCommand, UpdatableCommand};
 use egui::Window;
+use futures::try_join;
 use glam::Mat4;
 use itertools::Itertools;
+use pollster::FutureExt;
+pub use save::SaveStore;
 use shin_audio::AudioManager;
 use shin_core::{
     format::scenario::{instruction_elements::CodeAddress, Scenario},
+    layout::TextDirection,
     vm::{
         breakpoint::BreakpointObserver,
         command::{
             types::{LayerId, VLayerId, VLayerIdRepr, PLANES_COUNT},
-            CommandResult,
+            CommandResult, RuntimeCommand,
         },
+        relocate::AddressMap,
         Scripter,
     },
 };
 use shin_render::{GpuCommonResources, Renderable};
 use smallvec::{smallvec, SmallVec};
-use tracing::{debug, warn};
+use tracing::{debug, error, info, warn};
 use vm_state::layers::ITER_VLAYER_SMALL_VECTOR_SIZE;
 pub use vm_state::{layers::LayerSelection, VmState};
 
 use crate::{
-    adv::assets::AdvAssets,
-    audio::{BgmPlayer, SePlayer},
+    achievements::{Achievements, AchievementsScreen},
+    adv::{
+        assets::{AdvAssets, AdvFonts, ScenarioLanguages},
+        debugger::VmDebugger,
+        prefetch::Prefetcher,
+        rollback::{Checkpoint, RollbackHistory},
+        save::{GameSave, SaveSlot},
+        scene_jump::SceneJumpMenu,
+        system_menu::{SystemMenu, SystemMenuEntry},
+    },
+    app::{Screen, ScreenTransition},
+    asset::asset_paths,
+    audio::{show_levels, BgmPlayer, SePlayer, VoicePlayer},
+    i18n::Localizer,
     input::{actions::AdvMessageAction, ActionState},
     layer::{
-        AnyLayer, AnyLayerMut, LayerGroup, MessageLayer, RootLayerGroup, ScreenLayer, UserLayer,
+        AnyLayer, AnyLayerMut, FontAtlas, LayerGroup, MessageLayer, MessageboxTextures,
+        RootLayerGroup, ScreenLayer, ToastLayer, UserLayer,
     },
     render::overlay::{OverlayCollector, OverlayVisitable},
+    settings::{SettingsScreen, SettingsStore},
+    unlocks::{MovieGalleryScreen, UnlockType, Unlocks},
     update::{Updatable, UpdateContext},
 };
 
 pub struct Adv {
     scenario: Arc<Scenario>,
+    scenarios: ScenarioLanguages,
+    /// The scenario language from the settings the last time they were checked, `None` for the original scenario
+    scenario_language: Option<String>,
     scripter: Scripter,
     vm_state: VmState,
     adv_state: AdvState,
     action_state: ActionState<AdvMessageAction>,
     current_command: Option<ExecutingCommand>,
     fast_forward_to_bp: Option<BreakpointObserver>,
+    debugger: VmDebugger,
+    scene_jump: SceneJumpMenu,
+    rollback: RollbackHistory,
+    /// Checkpoint recorded by RESUMESET, to be restored by RESUME
+    resume_point: Option<Checkpoint>,
+    coverage_log: Option<PathBuf>,
+    /// Screen transition requested by the system menu, returned from the next [`Screen::update`]
+    pending_transition: Option<ScreenTransition>,
+    prefetcher: Prefetcher,
+    saves: SaveStore,
 }
 
 impl Adv {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         resources: &GpuCommonResources,
         audio_manager: Arc<AudioManager>,
         assets: AdvAssets,
+        settings: Arc<SettingsStore>,
+        i18n: Arc<Localizer>,
+        achievements: Achievements,
+        unlocks: Unlocks,
+        saves: SaveStore,
         init_val: i32,
         random_seed: u32,
     ) -> Self {
-        let scenario = assets.scenario.clone();
+        let scenarios = assets.scenarios.clone();
+        let scenario_language = settings.get().scenario_language.clone();
+        let scenario = match scenarios.get(scenario_language.as_deref()) {
+            Some(scenario) => scenario.clone(),
+            None => {
+                warn!(
+                    "The scenario in {:?} is not available, using the original one",
+                    scenario_language
+                );
+                scenarios.original.clone()
+            }
+        };
         let scripter = Scripter::new(&scenario, init_val, random_seed);
         let vm_state = VmState::new();
-        let adv_state = AdvState::new(resources, audio_manager, assets);
+        let adv_state = AdvState::new(
+            resources,
+            audio_manager,
+            assets,
+            settings,
+            i18n,
+            achievements,
+            unlocks,
+        );
 
         Self {
             scenario,
+            scenarios,
+            scenario_language,
             scripter,
             vm_state,
             adv_state,
             action_state: ActionState::new(),
             current_command: None,
             fast_forward_to_bp: None,
+            debugger: VmDebugger::new(),
+            scene_jump: SceneJumpMenu::new(),
+            rollback: RollbackHistory::new(),
+            resume_point: None,
+            coverage_log: None,
+            pending_transition: None,
+            prefetcher: Prefetcher::new(),
+            saves,
+        }
+    }
+
+    fn checkpoint(&self, at_message: bool) -> Checkpoint {
+        Checkpoint {
+            scripter: self.scripter.snapshot(),
+            vm_state: self.vm_state.clone(),
+            at_message,
+        }
+    }
+
+    /// A checkpoint to continue from the current message, for saving
+    ///
+    /// Before the first message, the command being executed is restarted instead.
+    fn current_message_checkpoint(&self) -> Checkpoint {
+        self.rollback.current().cloned().unwrap_or_else(|| {
+            let mut checkpoint = self.checkpoint(false);
+            if self.current_command.is_some() {
+                checkpoint.scripter.rewind();
+            }
+            checkpoint
+        })
+    }
+
+    fn make_save(&self, checkpoint: &Checkpoint) -> GameSave {
+        let language = self.scenarios.language_of(&self.scenario);
+        GameSave::new(checkpoint, language.map(str::to_string))
+    }
+
+    /// Saves the state at the current command to the autosave ring
+    fn autosave(&mut self) {
+        let save = self.make_save(&self.checkpoint(false));
+        if let Err(e) = self.saves.autosave(&save) {
+            error!("Failed to autosave: {:?}", e);
+        }
+    }
+
+    fn quick_save(&mut self) {
+        let save = self.make_save(&self.current_message_checkpoint());
+        let key = match self.saves.save(SaveSlot::Quick, &save) {
+            Ok(()) => "toast.quick_saved",
+            Err(e) => {
+                error!("Failed to quick save: {:?}", e);
+                "toast.save_failed"
+            }
+        };
+        self.adv_state
+            .toast_layer
+            .push(&self.adv_state.i18n.tr(key));
+    }
+
+    fn quick_load(&mut self, context: &UpdateContext) {
+        let key = match self.saves.load(SaveSlot::Quick) {
+            Ok(Some(save)) => match self.load_save(context, &save) {
+                Ok(()) => "toast.quick_loaded",
+                Err(e) => {
+                    error!("Failed to load the quick save: {:?}", e);
+                    "toast.load_failed"
+                }
+            },
+            Ok(None) => "toast.no_quick_save",
+            Err(e) => {
+                error!("Failed to read the quick save: {:?}", e);
+                "toast.load_failed"
+            }
+        };
+        self.adv_state
+            .toast_layer
+            .push(&self.adv_state.i18n.tr(key));
+    }
+
+    /// Continues the game from the save, the rollback history is reset
+    ///
+    /// A save made in another scenario language is moved to the current scenario.
+    fn load_save(&mut self, context: &UpdateContext, save: &GameSave) -> Result<()> {
+        let mut checkpoint = save.to_checkpoint()?;
+
+        let save_scenario = self
+            .scenarios
+            .get(save.scenario_language())
+            .with_context(|| {
+                format!(
+                    "The scenario in {:?} is not available",
+                    save.scenario_language()
+                )
+            })?;
+        if !Arc::ptr_eq(save_scenario, &self.scenario) {
+            let map = AddressMap::new(save_scenario, &self.scenario)
+                .context("The scenario of the save doesn't match the current one")?;
+            checkpoint = checkpoint
+                .relocate(&map)
+                .context("Could not find the save position in the current scenario")?;
+        }
+
+        info!("Loading a save at {}", checkpoint.scripter.position());
+        self.rollback = RollbackHistory::new();
+        self.resume_point = None;
+        self.prefetcher = Prefetcher::new();
+        self.restore_checkpoint(context, checkpoint);
+
+        Ok(())
+    }
+
+    /// Go back `n` messages, restoring the scene as it was back then
+    ///
+    /// Returns `false` if there is not enough messages in the history.
+    pub fn rollback(&mut self, context: &UpdateContext, n: usize) -> bool {
+        let Some(checkpoint) = self.rollback.rollback(n) else {
+            return false;
+        };
+        debug!(
+            "Rolling back {} messages to {}",
+            n,
+            checkpoint.scripter.position()
+        );
+
+        self.restore_checkpoint(context, checkpoint);
+
+        true
+    }
+
+    /// Restore the VM and rebuild the scene from the checkpoint
+    fn restore_checkpoint(&mut self, context: &UpdateContext, checkpoint: Checkpoint) {
+        // the checkpoints are taken in the current scenario, so this only fails on bugs
+        if let Err(e) = self.scripter.restore(&checkpoint.scripter) {
+            error!("Failed to restore the checkpoint: {:?}", e);
+            return;
+        }
+        self.current_command = None;
+        self.debugger.drop_held_command();
+        rollback::restore_adv_state(
+            context,
+            &self.scenario,
+            &mut self.adv_state,
+            &self.vm_state,
+            &checkpoint.vm_state,
+        );
+
+        // persistent data is not a part of the scene, it shouldn't be rolled back
+        let persist = std::mem::take(&mut self.vm_state.persist);
+        self.vm_state = checkpoint.vm_state;
+        self.vm_state.persist = persist;
+
+        if checkpoint.at_message {
+            self.current_command = Some(ExecutingCommand::restored_message());
+        }
+    }
+
+    /// Switches to the scenario in another language, showing the current message again in it
+    ///
+    /// The state is moved to the same position in the new scenario (see [`shin_core::vm::relocate`]).
+    /// If the scenarios don't match, the new language is only used after a restart.
+    fn switch_scenario_language(&mut self, context: &UpdateContext, language: Option<&str>) {
+        let Some(scenario) = self.scenarios.get(language).cloned() else {
+            warn!("The scenario in {:?} is not available", language);
+            return;
+        };
+        if Arc::ptr_eq(&scenario, &self.scenario) {
+            return;
+        }
+        let map = match AddressMap::new(&self.scenario, &scenario) {
+            Ok(map) => map,
+            Err(e) => {
+                warn!(
+                    "The scenario in {:?} doesn't match the current one, it will be used after a restart: {:?}",
+                    language, e
+                );
+                return;
+            }
+        };
+
+        // go back to the current message, executing its MSGSET again to show it in the new language
+        // before the first message, the command being executed is restarted instead
+        let checkpoint = match self.rollback.take_current() {
+            Some(checkpoint) => checkpoint.relocate(&map).map(|mut checkpoint| {
+                if checkpoint.at_message {
+                    checkpoint.scripter.rewind();
+                    checkpoint.at_message = false;
+                }
+                checkpoint
+            }),
+            None => self.checkpoint(false).relocate(&map).map(|mut checkpoint| {
+                if self.current_command.is_some() {
+                    checkpoint.scripter.rewind();
+                }
+                checkpoint
+            }),
+        };
+        let Some(checkpoint) = checkpoint else {
+            warn!(
+                "Could not find the current position in the {:?} scenario",
+                language
+            );
+            return;
+        };
+
+        info!(
+            "Switching the scenario language to {:?}, continuing from {}",
+            language,
+            checkpoint.scripter.position()
+        );
+        self.rollback.relocate(&map);
+        self.resume_point = self
+            .resume_point
+            .take()
+            .and_then(|checkpoint| checkpoint.relocate(&map));
+        self.prefetcher = Prefetcher::new();
+        self.scripter.set_scenario(&scenario);
+        self.scenario = scenario;
+        self.restore_checkpoint(context, checkpoint);
+    }
+
+    /// Starts the scenario over at `address` with a clean scene (see [`shin_core::vm::entry_points`])
+    ///
+    /// The game flags in the VM registers and the persistent data are kept, the layers, the audio and the rollback history are reset.
+    pub fn jump_to(&mut self, context: &UpdateContext, address: CodeAddress) {
+        info!("Jumping to {}", address);
+        let mut scripter = self.scripter.snapshot();
+        scripter.jump_to(address);
+        let checkpoint = Checkpoint {
+            scripter,
+            vm_state: VmState::new(),
+            at_message: false,
+        };
+
+        self.rollback = RollbackHistory::new();
+        self.resume_point = None;
+        self.prefetcher = Prefetcher::new();
+        self.restore_checkpoint(context, checkpoint);
+    }
+
+    fn handle_system_menu_entry(&mut self, context: &UpdateContext, entry: SystemMenuEntry) {
+        match entry {
+            SystemMenuEntry::Close => self.adv_state.close_system_menu(),
+            SystemMenuEntry::Config => {
+                let screen = SettingsScreen::new(
+                    context.gpu_resources,
+                    self.adv_state.font_atlas(),
+                    self.adv_state.settings.clone(),
+                    self.adv_state.i18n.clone(),
+                    &self.scenario.info_tables().voice_characters(),
+                );
+                self.pending_transition = Some(ScreenTransition::Push(Box::new(screen)));
+            }
+            SystemMenuEntry::Achievements => {
+                let screen = AchievementsScreen::new(
+                    context.gpu_resources,
+                    self.adv_state.font_atlas(),
+                    &self.adv_state.i18n,
+                    self.adv_state.achievements.unlocked(),
+                );
+                self.pending_transition = Some(ScreenTransition::Push(Box::new(screen)));
+            }
+            SystemMenuEntry::Movies => {
+                let screen = MovieGalleryScreen::new(
+                    context,
+                    self.adv_state.font_atlas(),
+                    &self.adv_state.i18n,
+                    self.adv_state.audio_manager.clone(),
+                    self.adv_state.settings.clone(),
+                    &self.scenario,
+                    self.adv_state.unlocks.unlocked(UnlockType::Movie),
+                );
+                self.pending_transition = Some(ScreenTransition::Push(Box::new(screen)));
+            }
+            SystemMenuEntry::Save
+            | SystemMenuEntry::Load
+            | SystemMenuEntry::Backlog
+            | SystemMenuEntry::Title => {
+                warn!("TODO: system menu entry {:?} is not implemented", entry);
+            }
         }
     }
 
@@ -75,16 +421,158 @@ impl Adv {
         assert!(self.fast_forward_to_bp.is_none());
         self.fast_forward_to_bp = Some(self.scripter.add_breakpoint(addr).into());
     }
+
+    /// Pause the scenario before the instruction at `addr` is executed, resumed from the VM Debugger overlay
+    pub fn add_breakpoint(&mut self, addr: CodeAddress) {
+        self.debugger.add_breakpoint(&mut self.scripter, addr);
+    }
+
+    /// Pause the scenario when the VM issues a command with this name (like `LAYERLOAD`), before it's started
+    pub fn break_on_command(&mut self, name: &str) {
+        self.debugger.break_on_command(name);
+    }
+
+    /// Show the register `$v<index>` in the VM Debugger overlay
+    pub fn watch_register(&mut self, index: u16) {
+        self.debugger.add_watch(&mut self.scripter, index);
+    }
+
+    pub fn set_novel_text_direction(&mut self, direction: TextDirection) {
+        self.adv_state
+            .root_layer_group
+            .message_layer_mut()
+            .set_novel_text_direction(direction);
+    }
+
+    /// Record the executed instructions, the log is written to `path` when the app exits
+    pub fn enable_coverage(&mut self, path: PathBuf) {
+        self.scripter.enable_coverage();
+        self.coverage_log = Some(path);
+    }
+
+    fn save_coverage(&self) {
+        let (Some(path), Some(coverage)) = (&self.coverage_log, self.scripter.coverage()) else {
+            return;
+        };
+
+        let result = std::fs::File::create(path)
+            .and_then(|file| coverage.write(std::io::BufWriter::new(file)));
+        match result {
+            Ok(()) => info!("Saved the coverage log to {}", path.display()),
+            Err(e) => warn!("Failed to save the coverage log: {}", e),
+        }
+    }
+}
+
+impl Screen for Adv {
+    fn name(&self) -> &'static str {
+        "ADV"
+    }
+
+    fn update(&mut self, context: &UpdateContext) -> ScreenTransition {
+        Updatable::update(self, context);
+        self.pending_transition
+            .take()
+            .unwrap_or(ScreenTransition::None)
+    }
+
+    fn on_exit(&mut self) {
+        self.save_coverage();
+    }
+
+    /// Rebuilds the scene on the new resources, continuing from the current message like after a rollback
+    fn recreate_gpu_state(&mut self, context: &UpdateContext) -> bool {
+        let assets = async {
+            try_join!(
+                AdvFonts::load(context.asset_server),
+                context
+                    .asset_server
+                    .load::<MessageboxTextures, _>(asset_paths::MSGTEX),
+            )
+        }
+        .block_on();
+        let (fonts, messagebox_textures) = match assets {
+            Ok(assets) => assets,
+            Err(e) => {
+                error!("Failed to reload the ADV assets: {:?}", e);
+                return false;
+            }
+        };
+        self.adv_state
+            .recreate_layers(context.gpu_resources, fonts, messagebox_textures);
+
+        // same as when switching the scenario language: restart the command being executed if there is no message yet
+        let checkpoint = self.rollback.take_current().unwrap_or_else(|| {
+            let mut checkpoint = self.checkpoint(false);
+            if self.current_command.is_some() {
+                checkpoint.scripter.rewind();
+            }
+            checkpoint
+        });
+        info!(
+            "Restoring the scene at {} on the new device",
+            checkpoint.scripter.position()
+        );
+        self.prefetcher = Prefetcher::new();
+        self.restore_checkpoint(context, checkpoint);
+
+        true
+    }
 }
 
 impl Updatable for Adv {
     fn update(&mut self, context: &UpdateContext) {
         self.action_state.update(context.raw_input_state);
 
+        if self.adv_state.system_menu.is_open() {
+            // the ADV is suspended while the menu is shown
+            if let Some(entry) = self.adv_state.system_menu.take_activated() {
+                self.handle_system_menu_entry(context, entry);
+            }
+            self.adv_state.update(context);
+            return;
+        }
+
+        if self
+            .action_state
+            .is_just_pressed(AdvMessageAction::SystemMenu)
+        {
+            self.adv_state.open_system_menu();
+            self.adv_state.update(context);
+            return;
+        }
+
+        let scenario_language = self.adv_state.settings.get().scenario_language.clone();
+        if scenario_language != self.scenario_language {
+            self.switch_scenario_language(context, scenario_language.as_deref());
+            self.scenario_language = scenario_language;
+        }
+
         let fast_forward_button_held = self
             .action_state
             .is_pressed(AdvMessageAction::HoldFastForward);
 
+        if self
+            .action_state
+            .is_just_pressed(AdvMessageAction::Rollback)
+            && !self.rollback(context, 1)
+        {
+            debug!("Nothing to roll back to");
+        }
+
+        if self
+            .action_state
+            .is_just_pressed(AdvMessageAction::QuickSave)
+        {
+            self.quick_save();
+        }
+        if self
+            .action_state
+            .is_just_pressed(AdvMessageAction::QuickLoad)
+        {
+            self.quick_load(context);
+        }
+
         if self.action_state.is_just_pressed(AdvMessageAction::Advance) {
             self.adv_state
                 .root_layer_group
@@ -99,6 +587,11 @@ impl Updatable for Adv {
                 .fast_forward();
         }
 
+        self.debugger.update(&mut self.scripter);
+        if let Some(address) = self.scene_jump.take_request() {
+            self.jump_to(context, address);
+        }
+
         let mut result = CommandResult::None;
         loop {
             // check the fast forward breakpoint; delete if hit
@@ -114,7 +607,7 @@ impl Updatable for Adv {
             let is_fast_forwarding = fast_forward_button_held || self.fast_forward_to_bp.is_some();
 
             // TODO: maybe yield if spent too much time in this loop?
-            let runtime_command = if let Some(command) = &mut self.current_command {
+            if let Some(command) = &mut self.current_command {
                 match command.update(
                     context,
                     &self.scenario,
@@ -123,17 +616,54 @@ impl Updatable for Adv {
                     is_fast_forwarding,
                 ) {
                     None => break,
-                    Some(result) => {
+                    Some(command_result) => {
                         self.current_command = None;
-                        self.scripter.run(result).expect("scripter run failed")
+                        result = command_result;
                     }
                 }
-            } else {
-                self.scripter.run(result).expect("scripter run failed")
+            }
+            let runtime_command = match self
+                .debugger
+                .next_command(&mut self.scripter, result)
+                .expect("scripter run failed")
+            {
+                Some(command) => command,
+                // paused by the debugger
+                None => break,
+            };
+
+            // autosave when the scenario moves to another chapter
+            let chapter_changed = match &runtime_command {
+                RuntimeCommand::SAVEINFO(cmd) => {
+                    (0..=1).contains(&cmd.level)
+                        && !cmd.info.is_empty()
+                        && self.vm_state.save_info.info[cmd.level as usize] != cmd.info
+                }
+                _ => false,
             };
 
             runtime_command.apply_state(&mut self.vm_state);
 
+            match &runtime_command {
+                RuntimeCommand::AUTOSAVE(_) => self.autosave(),
+                RuntimeCommand::SAVEINFO(_) if chapter_changed => self.autosave(),
+                RuntimeCommand::MSGSET(_) => self.rollback.push_message(self.checkpoint(true)),
+                RuntimeCommand::PAGEBACK(_) => {
+                    self.rollback.mark_page_start(self.checkpoint(false))
+                }
+                RuntimeCommand::RESUMESET(_) => self.resume_point = Some(self.checkpoint(false)),
+                RuntimeCommand::RESUME(_) => match self.resume_point.take() {
+                    Some(checkpoint) => {
+                        debug!("Resuming to {}", checkpoint.scripter.position());
+                        self.restore_checkpoint(context, checkpoint);
+                        result = CommandResult::None;
+                        continue;
+                    }
+                    None => warn!("RESUME without a RESUMESET"),
+                },
+                _ => {}
+            }
+
             match runtime_command.start(
                 context,
                 &self.scenario,
@@ -150,6 +680,13 @@ impl Updatable for Adv {
             }
         }
 
+        self.prefetcher.update(
+            context.asset_server,
+            &self.scenario,
+            self.scripter.ctx(),
+            self.scripter.position(),
+        );
+
         self.adv_state.update(context);
     }
 }
@@ -192,10 +729,38 @@ impl OverlayVisitable for Adv {
                     },
                     true,
                 );
+                collector.overlay(
+                    "VM Debugger",
+                    |ctx, _top_left| self.debugger.show_overlay(ctx, &self.scripter),
+                    false,
+                );
+                collector.overlay(
+                    "Scene Jump",
+                    |ctx, _top_left| self.scene_jump.show_overlay(ctx, &self.scenario),
+                    false,
+                );
                 self.adv_state
                     .root_layer_group
                     .message_layer()
                     .visit_overlay(collector);
+                collector.overlay(
+                    "Audio Levels",
+                    |ctx, _top_left| {
+                        let adv_state = &self.adv_state;
+                        Window::new("Audio Levels").show(ctx, |ui| {
+                            show_levels(
+                                ui,
+                                &[
+                                    ("Main", adv_state.audio_manager.main_meter()),
+                                    ("BGM", adv_state.bgm_player.meter()),
+                                    ("SE", adv_state.se_player.meter()),
+                                    ("Voice", adv_state.voice_player.meter()),
+                                ],
+                            );
+                        });
+                    },
+                    false,
+                );
                 collector.overlay(
                     "User Layers",
                     |ctx, _top_left| {
@@ -230,9 +795,16 @@ impl OverlayVisitable for Adv {
 
 pub struct AdvState {
     pub root_layer_group: RootLayerGroup,
+    pub toast_layer: ToastLayer,
+    pub system_menu: SystemMenu,
+    pub settings: Arc<SettingsStore>,
+    pub i18n: Arc<Localizer>,
+    pub achievements: Achievements,
+    pub unlocks: Unlocks,
     pub audio_manager: Arc<AudioManager>,
     pub bgm_player: BgmPlayer,
     pub se_player: SePlayer,
+    pub voice_player: VoicePlayer,
 }
 
 impl AdvState {
@@ -240,19 +812,85 @@ impl AdvState {
         resources: &GpuCommonResources,
         audio_manager: Arc<AudioManager>,
         assets: AdvAssets,
+        settings: Arc<SettingsStore>,
+        i18n: Arc<Localizer>,
+        achievements: Achievements,
+        unlocks: Unlocks,
     ) -> Self {
+        let (root_layer_group, toast_layer, system_menu) = Self::new_layers(
+            resources,
+            assets.fonts,
+            assets.messagebox_textures,
+            &settings,
+            &i18n,
+        );
+
         Self {
-            root_layer_group: RootLayerGroup::new(
-                resources,
-                ScreenLayer::new(resources),
-                MessageLayer::new(resources, assets.fonts, assets.messagebox_textures),
-            ),
+            root_layer_group,
+            toast_layer,
+            system_menu,
+            settings: settings.clone(),
+            i18n,
+            achievements,
+            unlocks,
             audio_manager: audio_manager.clone(),
             bgm_player: BgmPlayer::new(audio_manager.clone()),
-            se_player: SePlayer::new(audio_manager),
+            se_player: SePlayer::new(audio_manager.clone()),
+            voice_player: VoicePlayer::new(audio_manager, settings),
         }
     }
 
+    fn new_layers(
+        resources: &GpuCommonResources,
+        fonts: AdvFonts,
+        messagebox_textures: Arc<MessageboxTextures>,
+        settings: &Arc<SettingsStore>,
+        i18n: &Arc<Localizer>,
+    ) -> (RootLayerGroup, ToastLayer, SystemMenu) {
+        let root_layer_group = RootLayerGroup::new(
+            resources,
+            ScreenLayer::new(resources),
+            MessageLayer::new(resources, fonts, messagebox_textures, settings.clone()),
+        );
+        let font_atlas = root_layer_group.message_layer().font_atlas().clone();
+        let toast_layer = ToastLayer::new(font_atlas.clone());
+        let system_menu = SystemMenu::new(resources, font_atlas, i18n.clone());
+
+        (root_layer_group, toast_layer, system_menu)
+    }
+
+    /// Replaces the layers and the system menu with empty ones on the new GPU resources, keeping the audio
+    ///
+    /// The scene has to be rebuilt afterwards, see the [`Screen::recreate_gpu_state`] of [`Adv`].
+    fn recreate_layers(
+        &mut self,
+        resources: &GpuCommonResources,
+        fonts: AdvFonts,
+        messagebox_textures: Arc<MessageboxTextures>,
+    ) {
+        (self.root_layer_group, self.toast_layer, self.system_menu) = Self::new_layers(
+            resources,
+            fonts,
+            messagebox_textures,
+            &self.settings,
+            &self.i18n,
+        );
+    }
+
+    pub fn font_atlas(&self) -> Arc<FontAtlas> {
+        self.root_layer_group.message_layer().font_atlas().clone()
+    }
+
+    pub fn open_system_menu(&mut self) {
+        self.system_menu.open();
+        self.root_layer_group.message_layer_mut().set_modal(true);
+    }
+
+    pub fn close_system_menu(&mut self) {
+        self.system_menu.close();
+        self.root_layer_group.message_layer_mut().set_modal(false);
+    }
+
     pub fn current_plane_layer_group(&self, vm_state: &VmState) -> &LayerGroup {
         self.root_layer_group
             .screen_layer()
@@ -332,13 +970,13 @@ impl AdvState {
         match id.repr() {
             VLayerIdRepr::RootLayerGroup => smallvec![(&mut self.root_layer_group).into()],
             VLayerIdRepr::ScreenLayer => smallvec![self.root_layer_group.screen_layer_mut().into()],
-            VLayerIdRepr::PageLayer => {
-                warn!("Returning ScreenLayer for PageLayer");
-                smallvec![self.root_layer_group.screen_layer_mut().into()]
-            }
+            VLayerIdRepr::PageLayer => smallvec![self
+                .root_layer_group
+                .screen_layer_mut()
+                .page_layer_mut()
+                .into()],
             VLayerIdRepr::PlaneLayerGroup => {
-                warn!("Returning ScreenLayer for PlaneLayerGroup");
-                smallvec![self.root_layer_group.screen_layer_mut().into()]
+                smallvec![self.current_plane_layer_group_mut(vm_state).into()]
             }
             VLayerIdRepr::Selected => {
                 if let Some(selection) = vm_state.layers.layer_selection {
@@ -369,6 +1007,8 @@ impl AdvState {
 impl Updatable for AdvState {
     fn update(&mut self, context: &UpdateContext) {
         self.root_layer_group.update(context);
+        self.toast_layer.update(context);
+        self.system_menu.update(context);
     }
 }
 
@@ -382,9 +1022,14 @@ impl Renderable for AdvState {
     ) {
         self.root_layer_group
             .render(resources, render_pass, transform, projection);
+        self.toast_layer
+            .render(resources, render_pass, transform, projection);
+        self.system_menu
+            .render(resources, render_pass, projection * transform);
     }
 
     fn resize(&mut self, resources: &GpuCommonResources) {
         self.root_layer_group.resize(resources);
+        self.toast_layer.resize(resources);
     }
 }
@@ -0,0 +1,119 @@
+//! Preloading of the assets the scenario is about to use, so that the scene changes don't hitch on reading and decoding them.
+//!
+//! The code ahead of the VM is scanned with [`shin_core::vm::lookahead`] for the commands loading the assets, and they are requested from the [`AnyAssetServer`] with the priority given by how soon they are expected to be needed.
+
+use std::collections::HashMap;
+
+use num_traits::FromPrimitive;
+use shin_core::{
+    format::{
+        audio::AudioFile,
+        scenario::{
+            instruction_elements::{CodeAddress, NumberSpec},
+            Scenario,
+        },
+    },
+    vm::{
+        command::{types::LayerType, CompiletimeCommand},
+        lookahead::{scan_ahead, LookaheadCommand},
+        IntoRuntimeForm, VmCtx,
+    },
+};
+
+use crate::asset::{bustup::Bustup, picture::Picture, AnyAssetServer};
+
+/// How many instructions ahead of the VM are scanned
+const LOOKAHEAD_INSTRUCTIONS: usize = 512;
+
+pub struct Prefetcher {
+    /// Addresses covered by the last scan, with their distances from its start
+    scanned: HashMap<CodeAddress, usize>,
+}
+
+impl Prefetcher {
+    pub fn new() -> Self {
+        Self {
+            scanned: HashMap::new(),
+        }
+    }
+
+    /// Scans the code ahead of `position` when the VM is close to leaving the previously scanned part
+    ///
+    /// The numbers in the commands are resolved with the current register values, which may be different by the time they are executed, so some of the requests can be useless.
+    pub fn update(
+        &mut self,
+        asset_server: &AnyAssetServer,
+        scenario: &Scenario,
+        ctx: &VmCtx,
+        position: CodeAddress,
+    ) {
+        if self
+            .scanned
+            .get(&position)
+            .map_or(false, |&distance| distance < LOOKAHEAD_INSTRUCTIONS / 2)
+        {
+            return;
+        }
+
+        let lookahead = scan_ahead(scenario, position, LOOKAHEAD_INSTRUCTIONS);
+        asset_server.clear_prefetch_queue();
+        for command in &lookahead.commands {
+            request(asset_server, scenario, ctx, command);
+        }
+        self.scanned = lookahead.visited;
+    }
+}
+
+fn request(
+    asset_server: &AnyAssetServer,
+    scenario: &Scenario,
+    ctx: &VmCtx,
+    lookahead: &LookaheadCommand,
+) {
+    let tables = scenario.info_tables();
+    let priority = lookahead.distance;
+
+    match &lookahead.command {
+        CompiletimeCommand::LAYERLOAD(command) => {
+            // not using the LayerType conversion directly, as it panics on invalid values
+            let layer_type =
+                ctx.get_number(NumberSpec::<i32>::new(command.layer_type.into_untyped()));
+            let (id, ..) = command.params.into_runtime_form(ctx);
+            let Ok(id) = usize::try_from(id) else {
+                return;
+            };
+
+            // the movies are not prefetched, they are too large to be kept around just in case
+            match LayerType::from_i32(layer_type) {
+                Some(LayerType::Picture) => {
+                    if let Some(info) = tables.picture_info.get(id) {
+                        asset_server.prefetch::<Picture>(info.path(), priority);
+                    }
+                }
+                Some(LayerType::Bustup) => {
+                    if let Some(info) = tables.bustup_info.get(id) {
+                        asset_server.prefetch::<Bustup>(info.path(), priority);
+                    }
+                }
+                _ => {}
+            }
+        }
+        CompiletimeCommand::BGMPLAY(command) => {
+            let id = ctx.get_number(command.bgm_data_id);
+            if let Some(info) = usize::try_from(id)
+                .ok()
+                .and_then(|id| tables.bgm_info.get(id))
+            {
+                asset_server.prefetch::<AudioFile>(info.path(), priority);
+            }
+        }
+        CompiletimeCommand::VOICEPLAY(command) => {
+            let name = command.name.clone().into_runtime_form(ctx);
+            asset_server.prefetch::<AudioFile>(
+                format!("/voice/{}.nxa", name.to_ascii_lowercase()),
+                priority,
+            );
+        }
+        _ => {}
+    }
+}
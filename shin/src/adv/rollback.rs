@@ -0,0 +1,407 @@
+//! Rollback to the previously shown messages.
+//!
+//! A checkpoint is recorded for each message shown. It consists of a [`ScripterSnapshot`] and a copy of the [`VmState`].
+//! When rolling back, the VM is restored from the snapshot and the [`AdvState`] is rebuilt from the [`VmState`], just like it would be when loading a save.
+//!
+//! The scenario can use [PAGEBACK](shin_core::vm::command::runtime::PAGEBACK) to mark the start of a page:
+//! the next message checkpoint will then restore the state at the page start and replay the commands setting up the scene, instead of jumping straight to the message.
+
+use std::collections::VecDeque;
+
+use futures::future::join_all;
+use pollster::FutureExt;
+use shin_core::{
+    format::scenario::Scenario,
+    time::Tween,
+    vm::{command::types::PLANES_COUNT, relocate::AddressMap, ScripterSnapshot},
+};
+use tracing::{debug, warn};
+
+use crate::{
+    adv::{AdvState, VmState},
+    asset::mask::Mask,
+    layer::{Layer, LayerGroup, LayerGroupMask, UserLayer},
+    update::UpdateContext,
+};
+
+/// How many messages can be rolled back
+const HISTORY_CAPACITY: usize = 256;
+
+#[derive(Clone)]
+pub struct Checkpoint {
+    pub scripter: ScripterSnapshot,
+    pub vm_state: VmState,
+    /// Whether the checkpoint was taken right after a message was shown, so the message has to be re-displayed and waited for after restoring
+    pub at_message: bool,
+}
+
+impl Checkpoint {
+    /// Moves the checkpoint to another version of the scenario, see [`ScripterSnapshot::relocate`]
+    pub fn relocate(&self, map: &AddressMap) -> Option<Self> {
+        Some(Self {
+            scripter: self.scripter.relocate(map)?,
+            ..self.clone()
+        })
+    }
+}
+
+pub struct RollbackHistory {
+    checkpoints: VecDeque<Checkpoint>,
+    /// Checkpoint recorded by PAGEBACK, to be used for the next message
+    page_start: Option<Checkpoint>,
+}
+
+impl RollbackHistory {
+    pub fn new() -> Self {
+        Self {
+            checkpoints: VecDeque::new(),
+            page_start: None,
+        }
+    }
+
+    pub fn mark_page_start(&mut self, checkpoint: Checkpoint) {
+        self.page_start = Some(Checkpoint {
+            at_message: false,
+            ..checkpoint
+        });
+    }
+
+    pub fn push_message(&mut self, checkpoint: Checkpoint) {
+        let checkpoint = self.page_start.take().unwrap_or(checkpoint);
+
+        if self.checkpoints.len() == HISTORY_CAPACITY {
+            self.checkpoints.pop_front();
+        }
+        self.checkpoints.push_back(checkpoint);
+    }
+
+    /// Remove the last `n` messages from the history, returning the checkpoint to restore to show the earliest of them
+    ///
+    /// The current message is the last one in the history, so rolling back by one message requires at least two checkpoints.
+    pub fn rollback(&mut self, n: usize) -> Option<Checkpoint> {
+        if n == 0 || self.checkpoints.len() <= n {
+            return None;
+        }
+
+        self.checkpoints.truncate(self.checkpoints.len() - n);
+        self.take_current()
+    }
+
+    /// Remove the current message from the history, returning the checkpoint to restore to show it again
+    pub fn take_current(&mut self) -> Option<Checkpoint> {
+        // this message will be recorded again when it is shown after restoring
+        let checkpoint = self.checkpoints.pop_back()?;
+        // when resuming from a page start, PAGEBACK is not executed again, so mark it here
+        self.page_start = (!checkpoint.at_message).then(|| checkpoint.clone());
+
+        Some(checkpoint)
+    }
+
+    /// The checkpoint to restore to show the current message again, without removing it from the history
+    pub fn current(&self) -> Option<&Checkpoint> {
+        self.checkpoints.back()
+    }
+
+    /// Moves the history to another version of the scenario, the checkpoints that can't be moved are dropped
+    pub fn relocate(&mut self, map: &AddressMap) {
+        self.checkpoints = std::mem::take(&mut self.checkpoints)
+            .into_iter()
+            .filter_map(|checkpoint| checkpoint.relocate(map))
+            .collect();
+        self.page_start = self
+            .page_start
+            .take()
+            .and_then(|checkpoint| checkpoint.relocate(map));
+    }
+}
+
+fn restore_layer_group(
+    context: &UpdateContext,
+    scenario: &Scenario,
+    adv_state: &mut AdvState,
+    vm_state: &VmState,
+    plane: u32,
+) {
+    let audio_manager = adv_state.audio_manager.clone();
+    let lip_sync = adv_state.voice_player.lip_sync().clone();
+    let font_atlas = adv_state
+        .root_layer_group
+        .message_layer()
+        .font_atlas()
+        .clone();
+    let movie_options = adv_state.settings.get().movie.video_player_options();
+    let plane_state = &vm_state.layers.planes[plane as usize];
+    let layer_group: &mut LayerGroup = adv_state
+        .root_layer_group
+        .screen_layer_mut()
+        .page_layer_mut()
+        .plane_mut(plane);
+
+    layer_group
+        .properties_mut()
+        .restore_snapshot(&plane_state.group.properties);
+    layer_group.set_mask(plane_state.mask.and_then(|mask| {
+        let mask_info = scenario.info_tables().mask_info(mask.mask_id);
+        match context.asset_server.load_sync::<Mask, _>(mask_info.path()) {
+            Ok(asset) => Some(LayerGroupMask {
+                mask: asset,
+                flags: mask.flags,
+                transition: mask.transition,
+            }),
+            Err(e) => {
+                warn!("Failed to load mask {}: {:?}", mask_info.path(), e);
+                None
+            }
+        }
+    }));
+
+    for layer_id in layer_group.get_layer_ids().collect::<Vec<_>>() {
+        layer_group.remove_layer(layer_id);
+    }
+
+    let (audio_manager, lip_sync, font_atlas) = (&audio_manager, &lip_sync, &font_atlas);
+    // the layers are loaded concurrently, so the restore only waits for the slowest one
+    let layers = join_all(
+        plane_state
+            .layers
+            .iter()
+            .filter_map(|(&layer_id, layer_state)| {
+                let (layer_type, params) = layer_state.layerinit_params?;
+                Some(async move {
+                    let mut layer = UserLayer::load(
+                        &context.gpu_resources,
+                        &context.asset_server,
+                        audio_manager,
+                        lip_sync,
+                        font_atlas,
+                        scenario,
+                        movie_options,
+                        layer_type,
+                        params,
+                    )
+                    .await;
+                    layer
+                        .properties_mut()
+                        .restore_snapshot(&layer_state.properties);
+                    (layer_id, layer)
+                })
+            }),
+    )
+    .block_on();
+
+    for (layer_id, layer) in layers {
+        layer_group.add_layer(layer_id, layer);
+    }
+}
+
+/// Rebuild the scene from scratch to match the `vm_state`
+///
+/// `current_vm_state` is used to keep playing the BGM if it's the same track.
+pub fn restore_adv_state(
+    context: &UpdateContext,
+    scenario: &Scenario,
+    adv_state: &mut AdvState,
+    current_vm_state: &VmState,
+    vm_state: &VmState,
+) {
+    debug!("Restoring the ADV state");
+
+    for plane in 0..PLANES_COUNT as u32 {
+        restore_layer_group(context, scenario, adv_state, vm_state, plane);
+    }
+
+    let layers = &vm_state.layers;
+    let root_layer_group = &mut adv_state.root_layer_group;
+    root_layer_group
+        .properties_mut()
+        .restore_snapshot(&layers.root_layer_group.properties);
+    root_layer_group
+        .screen_layer_mut()
+        .properties_mut()
+        .restore_snapshot(&layers.screen_layer.properties);
+    root_layer_group
+        .screen_layer_mut()
+        .page_layer_mut()
+        .properties_mut()
+        .restore_snapshot(&layers.page_layer.properties);
+
+    let message_layer = root_layer_group.message_layer_mut();
+    message_layer.set_style(vm_state.messagebox_state.msginit);
+    match &vm_state.messagebox_state.text {
+        Some(text) if vm_state.messagebox_state.messagebox_shown => {
+            message_layer.set_message(context, text)
+        }
+        _ => message_layer.close(),
+    }
+
+    adv_state.voice_player.stop(Tween::MS_15);
+    // only the looping sounds are in the state, the one-shot ones are just stopped
+    for (slot, se) in vm_state.audio.se.iter().enumerate() {
+        let slot_id = slot as i32;
+        match se {
+            Some(se)
+                if current_vm_state.audio.se[slot]
+                    .is_some_and(|current| current.se_id == se.se_id) =>
+            {
+                adv_state
+                    .se_player
+                    .set_volume(slot_id, se.volume, Tween::MS_15);
+                adv_state
+                    .se_player
+                    .set_panning(slot_id, se.pan, Tween::MS_15);
+            }
+            Some(se) => {
+                let se_info = scenario.info_tables().se_info(se.se_id);
+                match context
+                    .asset_server
+                    // TODO: sync - bad!!
+                    .load_sync(se_info.path())
+                {
+                    Ok(audio) => adv_state.se_player.play(
+                        slot_id,
+                        audio,
+                        true,
+                        se.volume,
+                        se.pan,
+                        Tween::MS_15,
+                    ),
+                    Err(e) => warn!("Failed to load SE {}: {:?}", se_info.path(), e),
+                }
+            }
+            None => {
+                if adv_state.se_player.is_playing(slot_id) {
+                    adv_state.se_player.stop(slot_id, Tween::MS_15);
+                }
+            }
+        }
+    }
+    match vm_state.audio.bgm {
+        Some(bgm)
+            if current_vm_state
+                .audio
+                .bgm
+                .is_some_and(|current| current.bgm_id == bgm.bgm_id) =>
+        {
+            adv_state.bgm_player.set_volume(bgm.volume, Tween::MS_15);
+        }
+        Some(bgm) => {
+            let bgm_info = scenario.info_tables().bgm_info(bgm.bgm_id);
+            match context
+                .asset_server
+                // TODO: sync - bad!!
+                .load_sync(bgm_info.path())
+            {
+                Ok(audio) => adv_state.bgm_player.play(
+                    audio,
+                    bgm_info.display_name.as_str(),
+                    true,
+                    bgm.volume,
+                    Tween::MS_15,
+                ),
+                Err(e) => {
+                    warn!("Failed to load BGM track {}: {:?}", bgm_info.path(), e);
+                    adv_state.bgm_player.stop(Tween::MS_15);
+                }
+            }
+        }
+        None => adv_state.bgm_player.stop(Tween::MS_15),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use shin_core::format::scenario::instruction_elements::CodeAddress;
+
+    use super::*;
+
+    /// A checkpoint told apart by the position of its snapshot
+    fn checkpoint(position: u32, at_message: bool) -> Checkpoint {
+        Checkpoint {
+            scripter: ScripterSnapshot::new(0, 0, CodeAddress(position)),
+            vm_state: VmState::new(),
+            at_message,
+        }
+    }
+
+    fn position(checkpoint: &Checkpoint) -> u32 {
+        checkpoint.scripter.position().0
+    }
+
+    fn history(positions: impl IntoIterator<Item = u32>) -> RollbackHistory {
+        let mut history = RollbackHistory::new();
+        for position in positions {
+            history.push_message(checkpoint(position, true));
+        }
+        history
+    }
+
+    #[test]
+    fn capacity() {
+        let mut history = history(0..HISTORY_CAPACITY as u32 + 10);
+
+        // the oldest messages are evicted
+        assert_eq!(history.checkpoints.len(), HISTORY_CAPACITY);
+        assert_eq!(position(&history.checkpoints[0]), 10);
+        assert_eq!(
+            position(history.current().unwrap()),
+            HISTORY_CAPACITY as u32 + 9
+        );
+
+        // so they can't be rolled back to
+        assert!(history.rollback(HISTORY_CAPACITY).is_none());
+        let checkpoint = history.rollback(HISTORY_CAPACITY - 1).unwrap();
+        assert_eq!(position(&checkpoint), 10);
+        assert!(history.checkpoints.is_empty());
+    }
+
+    #[test]
+    fn page_start() {
+        let mut history = history([100]);
+
+        history.mark_page_start(checkpoint(200, true));
+        // the message is recorded with the state at the page start
+        history.push_message(checkpoint(300, true));
+        let current = history.current().unwrap();
+        assert_eq!(position(current), 200);
+        assert!(!current.at_message);
+
+        // the page start is used only once
+        history.push_message(checkpoint(400, true));
+        assert_eq!(position(history.current().unwrap()), 400);
+
+        // restoring to a page start marks it again, as PAGEBACK is not executed again
+        let checkpoint = history.rollback(1).unwrap();
+        assert_eq!(position(&checkpoint), 200);
+        history.push_message(self::checkpoint(300, true));
+        assert_eq!(position(history.current().unwrap()), 200);
+
+        // but restoring to a message doesn't
+        let checkpoint = history.rollback(1).unwrap();
+        assert_eq!(position(&checkpoint), 100);
+        assert!(checkpoint.at_message);
+        history.push_message(self::checkpoint(100, true));
+        assert_eq!(position(history.current().unwrap()), 100);
+    }
+
+    #[test]
+    fn rollback_bounds() {
+        let mut history = history([100, 200, 300]);
+
+        assert!(history.rollback(0).is_none());
+        // the current message can't be rolled back past
+        assert!(history.rollback(3).is_none());
+        assert!(history.rollback(10).is_none());
+        // the failed rollbacks leave the history as is
+        assert_eq!(history.checkpoints.len(), 3);
+
+        // the restored message is removed, it is recorded again when shown
+        let checkpoint = history.rollback(2).unwrap();
+        assert_eq!(position(&checkpoint), 100);
+        assert!(history.checkpoints.is_empty());
+
+        // a single message can't be rolled back
+        let mut history = self::history([100]);
+        assert!(history.rollback(1).is_none());
+        assert!(RollbackHistory::new().rollback(1).is_none());
+    }
+}
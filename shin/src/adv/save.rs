@@ -0,0 +1,331 @@
+//! The engine's own saves, stored as JSON files in the saves directory (by default in the shin data directory).
+//!
+//! The game's savedata format is not used, like for the settings. A save is a [`Checkpoint`] in the serializable form:
+//! the [`ScripterSnapshot`] plus the [`LayersSnapshot`] and the [`AudioSnapshot`] of the [`VmState`].
+//!
+//! The scenario saves automatically on AUTOSAVE and when the chapter in the SAVEINFO changes,
+//! the autosaves go to a ring of [`SavesConfig::autosave_slots`](crate::config::SavesConfig::autosave_slots) slots, replacing the oldest one.
+//! The player can also quick save and quick load to a separate slot.
+
+use std::{
+    io::{BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use num_traits::FromPrimitive;
+use serde::{Deserialize, Serialize};
+use shin_core::vm::{
+    command::types::{MessageTextLayout, MessageboxStyle, MessageboxType},
+    ScripterSnapshot,
+};
+use tracing::{debug, info};
+
+use crate::{
+    adv::{
+        rollback::Checkpoint,
+        vm_state::{MessageState, SaveInfo},
+        VmState,
+    },
+    audio::AudioSnapshot,
+    layer::LayersSnapshot,
+    snapshot::check_version,
+};
+
+/// Bumped on the incompatible changes to the save file layout, the snapshots inside it have their own versions
+const SAVE_VERSION: u32 = 1;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SaveSlot {
+    /// One of the autosave ring slots, starting from 0
+    Auto(u32),
+    Quick,
+}
+
+impl SaveSlot {
+    fn file_name(self) -> String {
+        match self {
+            SaveSlot::Auto(index) => format!("auto{:02}.json", index),
+            SaveSlot::Quick => "quick.json".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct MessageboxSnapshot {
+    messagebox_type: i32,
+    text_layout: i32,
+    shown: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    text: Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GameSave {
+    version: u32,
+    /// When the save was made, in seconds since the Unix epoch
+    timestamp: u64,
+    /// The scenario the code addresses are for, `None` for the original one
+    scenario_language: Option<String>,
+    /// The SAVEINFO strings, shown in the save list
+    save_info: [String; 4],
+    scripter: ScripterSnapshot,
+    messagebox: MessageboxSnapshot,
+    layers: LayersSnapshot,
+    audio: AudioSnapshot,
+}
+
+impl GameSave {
+    /// Makes a save continuing from the `checkpoint`
+    ///
+    /// A checkpoint taken at a message is saved to show the message again (by executing its MSGSET) after loading.
+    pub fn new(checkpoint: &Checkpoint, scenario_language: Option<String>) -> Self {
+        let mut scripter = checkpoint.scripter.clone();
+        if checkpoint.at_message {
+            scripter.rewind();
+        }
+        let vm_state = &checkpoint.vm_state;
+        let messagebox = &vm_state.messagebox_state;
+
+        Self {
+            version: SAVE_VERSION,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+            scenario_language,
+            save_info: vm_state.save_info.info.clone(),
+            scripter,
+            messagebox: MessageboxSnapshot {
+                messagebox_type: messagebox.msginit.messagebox_type as i32,
+                text_layout: messagebox.msginit.text_layout as i32,
+                shown: messagebox.messagebox_shown,
+                text: messagebox.text.clone(),
+            },
+            layers: LayersSnapshot::capture(&vm_state.layers),
+            audio: AudioSnapshot::capture(&vm_state.audio),
+        }
+    }
+
+    pub fn scenario_language(&self) -> Option<&str> {
+        self.scenario_language.as_deref()
+    }
+
+    /// Re-creates the checkpoint, failing if the save is from a newer engine or is corrupt
+    ///
+    /// The persistent data is not a part of the save, it's kept as is when restoring.
+    pub fn to_checkpoint(&self) -> Result<Checkpoint> {
+        check_version("save", self.version, SAVE_VERSION)?;
+
+        let mut vm_state = VmState::new();
+        vm_state.save_info = SaveInfo {
+            info: self.save_info.clone(),
+        };
+        vm_state.messagebox_state = MessageState {
+            msginit: MessageboxStyle {
+                messagebox_type: MessageboxType::from_i32(self.messagebox.messagebox_type)
+                    .with_context(|| {
+                        format!(
+                            "Unknown messagebox type {}",
+                            self.messagebox.messagebox_type
+                        )
+                    })?,
+                text_layout: MessageTextLayout::from_i32(self.messagebox.text_layout)
+                    .with_context(|| {
+                        format!("Unknown text layout {}", self.messagebox.text_layout)
+                    })?,
+            },
+            messagebox_shown: self.messagebox.shown,
+            text: self.messagebox.text.clone(),
+        };
+        vm_state.layers = self.layers.restore().context("Restoring the layers")?;
+        vm_state.audio = self.audio.restore().context("Restoring the audio")?;
+
+        Ok(Checkpoint {
+            scripter: self.scripter.clone(),
+            vm_state,
+            at_message: false,
+        })
+    }
+}
+
+/// Picks the autosave slot to write next: the first unused one, or the one written the longest time ago
+fn oldest_slot(modified: &[Option<SystemTime>]) -> u32 {
+    modified
+        .iter()
+        .position(|time| time.is_none())
+        .or_else(|| {
+            modified
+                .iter()
+                .enumerate()
+                .min_by_key(|&(_, time)| *time)
+                .map(|(index, _)| index)
+        })
+        .unwrap_or(0) as u32
+}
+
+/// Reads and writes the save files
+pub struct SaveStore {
+    /// `None` if the saves are not persisted
+    dir: Option<PathBuf>,
+    autosave_slots: u32,
+    /// The ring slot the next autosave is written to
+    next_autosave: u32,
+}
+
+impl SaveStore {
+    /// `saves` in the shin data directory, if there is one on this platform
+    pub fn default_dir() -> Option<PathBuf> {
+        dirs_next::data_dir().map(|p| p.join("shin").join("saves"))
+    }
+
+    /// `autosave_slots` can be 0 to disable the autosaves
+    pub fn new(dir: Option<PathBuf>, autosave_slots: u32) -> Self {
+        let next_autosave = match &dir {
+            Some(dir) => oldest_slot(
+                &(0..autosave_slots)
+                    .map(|index| {
+                        std::fs::metadata(dir.join(SaveSlot::Auto(index).file_name()))
+                            .and_then(|metadata| metadata.modified())
+                            .ok()
+                    })
+                    .collect::<Vec<_>>(),
+            ),
+            None => 0,
+        };
+
+        Self {
+            dir,
+            autosave_slots,
+            next_autosave,
+        }
+    }
+
+    /// Writes the save to the next autosave slot, overwriting the oldest one
+    pub fn autosave(&mut self, save: &GameSave) -> Result<()> {
+        if self.autosave_slots == 0 {
+            return Ok(());
+        }
+
+        let slot = SaveSlot::Auto(self.next_autosave);
+        self.next_autosave = (self.next_autosave + 1) % self.autosave_slots;
+        self.save(slot, save)
+    }
+
+    pub fn save(&self, slot: SaveSlot, save: &GameSave) -> Result<()> {
+        let Some(dir) = &self.dir else {
+            debug!("Not saving to {:?}, the saves are not persisted", slot);
+            return Ok(());
+        };
+
+        let path = dir.join(slot.file_name());
+        Self::write(&path, save)?;
+        info!("Saved to {}", path.display());
+
+        Ok(())
+    }
+
+    /// Reads the save in the `slot`, `None` if there is none
+    pub fn load(&self, slot: SaveSlot) -> Result<Option<GameSave>> {
+        let Some(dir) = &self.dir else {
+            return Ok(None);
+        };
+
+        let path = dir.join(slot.file_name());
+        if !path.exists() {
+            return Ok(None);
+        }
+        let file =
+            std::fs::File::open(&path).with_context(|| format!("Opening {}", path.display()))?;
+        let save = serde_json::from_reader(BufReader::new(file))
+            .with_context(|| format!("Parsing {}", path.display()))?;
+
+        Ok(Some(save))
+    }
+
+    fn write(path: &Path, save: &GameSave) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Creating {}", parent.display()))?;
+        }
+
+        // write to a temporary file first, so that a crash doesn't lose the previous save in the slot
+        let temp_path = path.with_extension("json.tmp");
+        let temp_file = std::fs::File::create(&temp_path)
+            .with_context(|| format!("Creating {}", temp_path.display()))?;
+        let mut writer = BufWriter::new(temp_file);
+        serde_json::to_writer(&mut writer, save).context("Writing the save")?;
+        writer.flush().context("Writing the save")?;
+        std::fs::rename(&temp_path, path)
+            .with_context(|| format!("Replacing {}", path.display()))?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use shin_core::{
+        format::scenario::instruction_elements::CodeAddress,
+        vm::command::types::{LayerId, LayerProperty, LayerType},
+    };
+
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let mut vm_state = VmState::new();
+        vm_state.save_info.set_save_info(1, "Chapter 1".to_string());
+        vm_state.messagebox_state.msginit.messagebox_type = MessageboxType::Novel;
+        vm_state.messagebox_state.messagebox_shown = true;
+        vm_state.messagebox_state.text = Some("Hello".to_string());
+        let layer = vm_state.layers.alloc(LayerId::new(3));
+        layer.layerinit_params = Some((LayerType::Picture, (5, 0, 0, 0, 0, 0, 0, 0)));
+        layer
+            .properties
+            .set_property(LayerProperty::TranslateY, -40);
+
+        let checkpoint = Checkpoint {
+            scripter: ScripterSnapshot::new(7, 42, CodeAddress(196)),
+            vm_state,
+            at_message: true,
+        };
+        let save = GameSave::new(&checkpoint, Some("en".to_string()));
+
+        let json = serde_json::to_string(&save).unwrap();
+        let save: GameSave = serde_json::from_str(&json).unwrap();
+        assert_eq!(save.scenario_language(), Some("en"));
+
+        let restored = save.to_checkpoint().unwrap();
+        // the message is shown again by executing its MSGSET
+        assert!(!restored.at_message);
+        assert_eq!(restored.scripter.position().0, 196);
+        assert_eq!(restored.vm_state.save_info.info[1], "Chapter 1");
+        assert_eq!(
+            restored.vm_state.messagebox_state.msginit.messagebox_type,
+            MessageboxType::Novel
+        );
+        assert_eq!(
+            restored.vm_state.messagebox_state.text.as_deref(),
+            Some("Hello")
+        );
+        let layer = restored.vm_state.layers.get_layer(LayerId::new(3)).unwrap();
+        assert_eq!(
+            layer.properties.get_property(LayerProperty::TranslateY),
+            -40
+        );
+    }
+
+    #[test]
+    fn autosave_ring() {
+        let time = |secs| Some(UNIX_EPOCH + Duration::from_secs(secs));
+
+        // the unused slots are filled first
+        assert_eq!(oldest_slot(&[time(10), None, time(5)]), 1);
+        // then the oldest one is replaced
+        assert_eq!(oldest_slot(&[time(10), time(20), time(5)]), 2);
+        assert_eq!(oldest_slot(&[]), 0);
+    }
+}
@@ -0,0 +1,77 @@
+//! The scene jump menu: lists the entry points of the scenario (see [`shin_core::vm::entry_points`]) and starts the scenario over at the chosen one.
+//!
+//! The entry points are only found when the menu is shown for the first time, as it takes a while for the full scenarios.
+
+use std::sync::{Arc, Mutex};
+
+use egui::{ScrollArea, Window};
+use shin_core::{
+    format::scenario::{instruction_elements::CodeAddress, Scenario},
+    vm::entry_points::{find_entry_points, EntryPoint},
+};
+
+#[derive(Default)]
+struct MenuState {
+    /// The entry points, with the scenario they were found in
+    entry_points: Option<(Arc<Scenario>, Vec<EntryPoint>)>,
+    filter: String,
+    requested: Option<CodeAddress>,
+}
+
+pub struct SceneJumpMenu {
+    state: Mutex<MenuState>,
+}
+
+impl SceneJumpMenu {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(MenuState::default()),
+        }
+    }
+
+    /// Takes the address the player chose to jump to since the last call
+    pub fn take_request(&mut self) -> Option<CodeAddress> {
+        self.state.get_mut().unwrap().requested.take()
+    }
+
+    pub fn show_overlay(&self, ctx: &egui::Context, scenario: &Arc<Scenario>) {
+        let mut state = self.state.lock().unwrap();
+        let state = &mut *state;
+
+        // the scenario changes when the language is switched
+        if !matches!(&state.entry_points, Some((found_in, _)) if Arc::ptr_eq(found_in, scenario)) {
+            state.entry_points = Some((scenario.clone(), find_entry_points(scenario)));
+        }
+        let (_, entry_points) = state.entry_points.as_ref().unwrap();
+
+        Window::new("Scene Jump").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Filter:");
+                ui.text_edit_singleline(&mut state.filter);
+            });
+            ui.separator();
+
+            let filter = state.filter.to_lowercase();
+            ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                for entry in entry_points {
+                    let name = entry.name();
+                    let preview = entry.preview.as_deref().unwrap_or("");
+                    if !filter.is_empty()
+                        && !name.to_lowercase().contains(&filter)
+                        && !preview.to_lowercase().contains(&filter)
+                    {
+                        continue;
+                    }
+
+                    ui.horizontal(|ui| {
+                        if ui.small_button("Jump").clicked() {
+                            state.requested = Some(entry.address);
+                        }
+                        ui.monospace(format!("{} {}", entry.address, name));
+                        ui.label(preview);
+                    });
+                }
+            });
+        });
+    }
+}
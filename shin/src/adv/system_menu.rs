@@ -0,0 +1,329 @@
+//! The system menu, opened over the ADV scene with the right mouse button or Escape (or by the scenario with SYSCALL).
+//!
+//! While the menu is open, the VM is suspended and the message window slides out of the way (see [`MessageLayer::set_modal`](crate::layer::MessageLayer::set_modal)).
+//! The menu only reports the activated entries, acting on them is up to [`Adv`](super::Adv).
+
+use std::sync::Arc;
+
+use glam::{vec2, Mat4, Vec2};
+use shin_core::time::{Easing, Ticks, Tween};
+use shin_render::{GpuCommonResources, LazyGpuTexture};
+
+use crate::{
+    i18n::Localizer,
+    input::{actions::MenuAction, ActionState},
+    layer::FontAtlas,
+    update::{Updatable, UpdateContext},
+    widget::{
+        default_button_image, default_button_sprite, default_nine_patch, default_window_image,
+        Button, Label, Window,
+    },
+};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SystemMenuEntry {
+    Save,
+    Load,
+    Config,
+    Backlog,
+    Achievements,
+    Movies,
+    Title,
+    Close,
+}
+
+impl SystemMenuEntry {
+    pub const ALL: [SystemMenuEntry; 8] = [
+        SystemMenuEntry::Save,
+        SystemMenuEntry::Load,
+        SystemMenuEntry::Config,
+        SystemMenuEntry::Backlog,
+        SystemMenuEntry::Achievements,
+        SystemMenuEntry::Movies,
+        SystemMenuEntry::Title,
+        SystemMenuEntry::Close,
+    ];
+
+    /// The key of the entry label in the UI strings
+    fn label_key(self) -> &'static str {
+        match self {
+            SystemMenuEntry::Save => "system_menu.save",
+            SystemMenuEntry::Load => "system_menu.load",
+            SystemMenuEntry::Config => "system_menu.config",
+            SystemMenuEntry::Backlog => "system_menu.backlog",
+            SystemMenuEntry::Achievements => "system_menu.achievements",
+            SystemMenuEntry::Movies => "system_menu.movies",
+            SystemMenuEntry::Title => "system_menu.title",
+            SystemMenuEntry::Close => "system_menu.close",
+        }
+    }
+}
+
+const FADE: Tween = Tween {
+    duration: Ticks::from_f32(10.0),
+    easing: Easing::SineOut,
+};
+
+const BUTTON_SIZE: Vec2 = vec2(480.0, 64.0);
+const BUTTON_SPACING: f32 = 80.0;
+const LABEL_FONT_HEIGHT: f32 = 40.0;
+const WINDOW_PADDING: f32 = 40.0;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum State {
+    Closed,
+    Opening,
+    Open,
+    /// Waiting for the flash of the activated entry to finish
+    Activating(usize),
+    Closing,
+}
+
+struct MenuButton {
+    entry: SystemMenuEntry,
+    button: Button,
+    label: Label,
+    position: Vec2,
+}
+
+impl MenuButton {
+    fn make_label(
+        resources: &GpuCommonResources,
+        font_atlas: Arc<FontAtlas>,
+        i18n: &Localizer,
+        entry: SystemMenuEntry,
+        position: Vec2,
+    ) -> Label {
+        Label::new(
+            resources,
+            font_atlas,
+            &i18n.tr(entry.label_key()),
+            position + vec2(0.0, (BUTTON_SIZE.y - LABEL_FONT_HEIGHT) / 2.0),
+            BUTTON_SIZE.x,
+            LABEL_FONT_HEIGHT,
+        )
+    }
+}
+
+pub struct SystemMenu {
+    font_atlas: Arc<FontAtlas>,
+    i18n: Arc<Localizer>,
+    /// The UI language the labels are in, they are rebuilt when it's changed in the settings
+    language: String,
+    window_texture: LazyGpuTexture,
+    button_texture: LazyGpuTexture,
+    window: Window,
+    buttons: Vec<MenuButton>,
+    selected: usize,
+    state: State,
+    action_state: ActionState<MenuAction>,
+    activated: Option<SystemMenuEntry>,
+}
+
+impl SystemMenu {
+    pub fn new(
+        resources: &GpuCommonResources,
+        font_atlas: Arc<FontAtlas>,
+        i18n: Arc<Localizer>,
+    ) -> Self {
+        let buttons_height =
+            BUTTON_SPACING * (SystemMenuEntry::ALL.len() - 1) as f32 + BUTTON_SIZE.y;
+        let top_left = vec2(-BUTTON_SIZE.x / 2.0, -buttons_height / 2.0);
+
+        let window = Window::new(
+            resources,
+            default_nine_patch(),
+            (
+                top_left.x - WINDOW_PADDING,
+                top_left.y - WINDOW_PADDING,
+                -top_left.x + WINDOW_PADDING,
+                -top_left.y + WINDOW_PADDING,
+            ),
+        );
+
+        let sprite = default_button_sprite(BUTTON_SIZE);
+        let buttons = SystemMenuEntry::ALL
+            .into_iter()
+            .enumerate()
+            .map(|(index, entry)| {
+                let position = top_left + vec2(0.0, BUTTON_SPACING * index as f32);
+                MenuButton {
+                    entry,
+                    button: Button::new(resources, sprite, position),
+                    label: MenuButton::make_label(
+                        resources,
+                        font_atlas.clone(),
+                        &i18n,
+                        entry,
+                        position,
+                    ),
+                    position,
+                }
+            })
+            .collect();
+
+        Self {
+            font_atlas,
+            language: i18n.language(),
+            i18n,
+            window_texture: LazyGpuTexture::new(default_window_image(), Some("System Menu Window")),
+            button_texture: LazyGpuTexture::new(
+                default_button_image(BUTTON_SIZE),
+                Some("System Menu Button"),
+            ),
+            window,
+            buttons,
+            selected: 0,
+            state: State::Closed,
+            action_state: ActionState::new(),
+            activated: None,
+        }
+    }
+
+    pub fn open(&mut self) {
+        if matches!(self.state, State::Opening | State::Open) {
+            return;
+        }
+
+        self.selected = 0;
+        for (index, button) in self.buttons.iter_mut().enumerate() {
+            button.button.set_highlighted(index == self.selected);
+        }
+        self.activated = None;
+        self.window.show(FADE);
+        self.state = State::Opening;
+    }
+
+    pub fn close(&mut self) {
+        if matches!(self.state, State::Closed | State::Closing) {
+            return;
+        }
+
+        self.window.hide(FADE);
+        self.state = State::Closing;
+    }
+
+    /// Whether the menu is shown, the ADV should be suspended in this case
+    pub fn is_open(&self) -> bool {
+        self.state != State::Closed
+    }
+
+    /// Returns the entry activated by the player since the last call
+    ///
+    /// Cancelling the menu is reported as [`SystemMenuEntry::Close`].
+    pub fn take_activated(&mut self) -> Option<SystemMenuEntry> {
+        self.activated.take()
+    }
+
+    fn refresh_labels(&mut self, resources: &GpuCommonResources) {
+        let language = self.i18n.language();
+        if language == self.language {
+            return;
+        }
+
+        for button in &mut self.buttons {
+            button.label = MenuButton::make_label(
+                resources,
+                self.font_atlas.clone(),
+                &self.i18n,
+                button.entry,
+                button.position,
+            );
+        }
+        self.language = language;
+    }
+
+    fn select(&mut self, index: usize) {
+        self.buttons[self.selected].button.set_highlighted(false);
+        self.selected = index;
+        self.buttons[self.selected].button.set_highlighted(true);
+    }
+
+    fn handle_input(&mut self) {
+        let count = self.buttons.len();
+        if self.action_state.is_just_pressed(MenuAction::Up) {
+            self.select((self.selected + count - 1) % count);
+        }
+        if self.action_state.is_just_pressed(MenuAction::Down) {
+            self.select((self.selected + 1) % count);
+        }
+        if self.action_state.is_just_pressed(MenuAction::Activate) {
+            self.buttons[self.selected].button.flash();
+            self.state = State::Activating(self.selected);
+        } else if self.action_state.is_just_pressed(MenuAction::Cancel) {
+            self.activated = Some(SystemMenuEntry::Close);
+        }
+    }
+
+    pub fn render<'enc>(
+        &'enc self,
+        resources: &'enc GpuCommonResources,
+        render_pass: &mut wgpu::RenderPass<'enc>,
+        transform: Mat4,
+    ) {
+        if self.state == State::Closed {
+            return;
+        }
+
+        render_pass.push_debug_group("SystemMenu");
+        self.window.render(
+            resources,
+            render_pass,
+            self.window_texture.gpu_texture(resources),
+            transform,
+        );
+        let opacity = self.window.opacity();
+        for button in &self.buttons {
+            button.button.render(
+                resources,
+                render_pass,
+                self.button_texture.gpu_texture(resources),
+                transform,
+                opacity,
+            );
+        }
+        for button in &self.buttons {
+            button
+                .label
+                .render(resources, render_pass, transform, opacity);
+        }
+        render_pass.pop_debug_group();
+    }
+}
+
+impl Updatable for SystemMenu {
+    fn update(&mut self, context: &UpdateContext) {
+        // keep the action state up to date even when closed, so that the press opening the menu is not seen as a new one
+        self.action_state.update(context.raw_input_state);
+
+        self.window.update(context);
+        for button in &mut self.buttons {
+            button.button.update(context);
+        }
+
+        if self.state != State::Closed {
+            self.refresh_labels(context.gpu_resources);
+        }
+
+        match self.state {
+            State::Closed => {}
+            State::Opening => {
+                if self.window.is_idle() {
+                    self.state = State::Open;
+                }
+            }
+            State::Open => self.handle_input(),
+            State::Activating(index) => {
+                if !self.buttons[index].button.is_animating() {
+                    self.activated = Some(self.buttons[index].entry);
+                    self.state = State::Open;
+                }
+            }
+            State::Closing => {
+                if self.window.is_idle() {
+                    self.state = State::Closed;
+                }
+            }
+        }
+    }
+}
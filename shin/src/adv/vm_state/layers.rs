@@ -1,16 +1,22 @@
 use bevy_utils::{hashbrown::hash_map::Entry, StableHashMap};
+use itertools::Itertools;
 use shin_core::{
     format::scenario::instruction_elements::UntypedNumberArray,
-    vm::command::types::{LayerId, LayerIdOpt, LayerType, VLayerId, VLayerIdRepr, PLANES_COUNT},
+    vm::command::types::{
+        LayerId, LayerIdOpt, LayerType, MaskFlags, VLayerId, VLayerIdRepr, PLANES_COUNT,
+    },
 };
 use smallvec::{smallvec, SmallVec};
 use tracing::warn;
 
 use crate::layer::LayerPropertiesSnapshot;
 
-#[derive(Debug, Copy, Clone)]
+/// A range of layer ids selected by LAYERSELECT, addressed by the [`VLayerIdRepr::Selected`] virtual id
+///
+/// The selection is just a range of ids, it doesn't matter whether the layers are loaded.
+/// The commands apply to the loaded layers in the range (except for LAYERLOAD and LAYERUNLOAD, which use all the ids).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct LayerSelection {
-    // TODO: enforce ordering?
     // TODO: do the layer plane changes affect the selection?
     // TODO: how to make an empty selection?
     pub low: LayerId,
@@ -18,6 +24,14 @@ pub struct LayerSelection {
 }
 
 impl LayerSelection {
+    /// Creates a selection of the layers between `a` and `b` (inclusive), in any order
+    pub fn new(a: LayerId, b: LayerId) -> Self {
+        Self {
+            low: a.min(b),
+            high: a.max(b),
+        }
+    }
+
     pub fn iter(&self) -> impl Iterator<Item = LayerId> {
         LayerSelectionIter {
             current: LayerIdOpt::some(self.low),
@@ -73,8 +87,18 @@ impl LayerState {
     }
 }
 
+#[derive(Debug, Copy, Clone)]
+pub struct MaskState {
+    pub mask_id: i32,
+    pub flags: MaskFlags,
+    pub transition: bool,
+}
+
 #[derive(Debug, Clone)]
 pub struct PlaneState {
+    /// State of the plane layer group itself
+    pub group: LayerState,
+    pub mask: Option<MaskState>,
     // TODO: allocations - bad?
     pub layers: StableHashMap<LayerId, LayerState>,
 }
@@ -82,6 +106,8 @@ pub struct PlaneState {
 impl PlaneState {
     pub fn new() -> Self {
         Self {
+            group: LayerState::new(),
+            mask: None,
             layers: StableHashMap::default(),
         }
     }
@@ -112,6 +138,17 @@ impl PlaneState {
             // warn!("LayerState::free: layer not allocated");
         }
     }
+
+    pub fn swap(&mut self, layer_id1: LayerId, layer_id2: LayerId) {
+        let layer1 = self.layers.remove(&layer_id1);
+        let layer2 = self.layers.remove(&layer_id2);
+        if let Some(layer1) = layer1 {
+            self.layers.insert(layer_id2, layer1);
+        }
+        if let Some(layer2) = layer2 {
+            self.layers.insert(layer_id1, layer2);
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -123,7 +160,6 @@ pub struct LayersState {
     pub root_layer_group: LayerState,
     pub screen_layer: LayerState,
     pub page_layer: LayerState,
-    pub plane_layer_group: LayerState,
 }
 
 /// can be whatever, just an optimization. Ideally, most selections made by the script should fit in
@@ -143,7 +179,6 @@ impl LayersState {
             root_layer_group: LayerState::new(),
             screen_layer: LayerState::new(),
             page_layer: LayerState::new(),
-            plane_layer_group: LayerState::new(),
         }
     }
 
@@ -170,13 +205,16 @@ impl LayersState {
             VLayerIdRepr::RootLayerGroup => smallvec![&self.root_layer_group],
             VLayerIdRepr::ScreenLayer => smallvec![&self.screen_layer],
             VLayerIdRepr::PageLayer => smallvec![&self.page_layer],
-            VLayerIdRepr::PlaneLayerGroup => smallvec![&self.plane_layer_group],
+            VLayerIdRepr::PlaneLayerGroup => {
+                smallvec![&self.planes[self.current_plane as usize].group]
+            }
             VLayerIdRepr::Selected => {
                 if let Some(selection) = self.layer_selection {
                     self.planes[self.current_plane as usize]
                         .layers
                         .iter()
                         .filter(move |(id, _)| selection.contains(**id))
+                        .sorted_by_key(|&(&id, _)| id)
                         .map(|(_, l)| l)
                         .collect::<SmallVec<&LayerState, { ITER_VLAYER_SMALL_VECTOR_SIZE }>>()
                 } else {
@@ -202,14 +240,18 @@ impl LayersState {
     ///
     /// Note that this can return layer ids for layers that are not loaded (in case of using a selection)
     ///
-    /// Attempt to get a layer id for a special layer panics (they have no "real" layer id)
+    /// The special layers have no "real" layer id, nothing is returned for them
     pub fn get_vlayer_ids(&self, vlayer_id: VLayerId) -> impl Iterator<Item = LayerId> {
         match vlayer_id.repr() {
             VLayerIdRepr::RootLayerGroup
             | VLayerIdRepr::ScreenLayer
             | VLayerIdRepr::PageLayer
             | VLayerIdRepr::PlaneLayerGroup => {
-                panic!("get_vlayer_ids: special layer do not have ids");
+                warn!(
+                    "get_vlayer_ids: special layers do not have ids: {:?}",
+                    vlayer_id
+                );
+                smallvec![].into_iter()
             }
             VLayerIdRepr::Selected => {
                 if let Some(selection) = self.layer_selection {
@@ -239,7 +281,9 @@ impl LayersState {
             VLayerIdRepr::RootLayerGroup => smallvec![&mut self.root_layer_group],
             VLayerIdRepr::ScreenLayer => smallvec![&mut self.screen_layer],
             VLayerIdRepr::PageLayer => smallvec![&mut self.page_layer],
-            VLayerIdRepr::PlaneLayerGroup => smallvec![&mut self.plane_layer_group],
+            VLayerIdRepr::PlaneLayerGroup => {
+                smallvec![&mut self.planes[self.current_plane as usize].group]
+            }
             VLayerIdRepr::Selected => {
                 // NOTE: usually, there are not that much layers present
                 // so it's okay to do an O(N) iteration here
@@ -248,6 +292,7 @@ impl LayersState {
                         .layers
                         .iter_mut()
                         .filter(|&(&id, _)| selection.contains(id))
+                        .sorted_by_key(|&(&id, _)| id)
                         .map(|(_, v)| v)
                         .collect::<SmallVec<&mut LayerState, { ITER_VLAYER_SMALL_VECTOR_SIZE }>>()
                 } else {
@@ -274,3 +319,125 @@ impl LayersState {
         self.planes[self.current_plane as usize].free(layer_id)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use shin_core::vm::command::types::{LayerId, LayerProperty, VLayerId, LAYERS_COUNT};
+
+    use super::*;
+
+    const SELECTED: i32 = -5;
+
+    fn id(id: u32) -> LayerId {
+        LayerId::new(id)
+    }
+
+    fn select(state: &mut LayersState, start: u32, end: u32) {
+        state.layer_selection = Some(LayerSelection::new(id(start), id(end)));
+    }
+
+    #[test]
+    fn selection_iteration() {
+        let ids = |selection: LayerSelection| selection.iter().map(|id| id.raw()).collect_vec();
+
+        assert_eq!(ids(LayerSelection::new(id(3), id(6))), vec![3, 4, 5, 6]);
+        // LAYERSELECT accepts the bounds in any order
+        assert_eq!(ids(LayerSelection::new(id(6), id(3))), vec![3, 4, 5, 6]);
+        assert_eq!(ids(LayerSelection::new(id(7), id(7))), vec![7]);
+        // the last id must not overflow the iterator
+        let last = LAYERS_COUNT - 1;
+        assert_eq!(
+            ids(LayerSelection::new(id(last - 1), id(last))),
+            vec![last - 1, last]
+        );
+    }
+
+    #[test]
+    fn selected_layers_ctrl() {
+        // LAYERLOAD 10, LAYERLOAD 12, LAYERLOAD 20, LAYERSELECT 10 15, LAYERCTRL -5 ...
+        let mut state = LayersState::new();
+        for layer in [10, 12, 20] {
+            state.alloc(id(layer));
+        }
+        select(&mut state, 10, 15);
+
+        for layer in state.get_vlayer_mut(VLayerId::new(SELECTED)) {
+            layer
+                .properties
+                .set_property(LayerProperty::TranslateX, 100);
+        }
+
+        let translate = |state: &LayersState, layer| {
+            state
+                .get_layer(id(layer))
+                .unwrap()
+                .properties
+                .get_property(LayerProperty::TranslateX)
+        };
+        assert_eq!(translate(&state, 10), 100);
+        assert_eq!(translate(&state, 12), 100);
+        // outside of the selection
+        assert_eq!(translate(&state, 20), 0);
+        assert_eq!(state.get_vlayer(VLayerId::new(SELECTED)).count(), 2);
+    }
+
+    #[test]
+    fn selected_layers_unload() {
+        // LAYERSELECT 10 15, LAYERUNLOAD -5 unloads all the selected layers, loaded or not
+        let mut state = LayersState::new();
+        for layer in [10, 12, 20] {
+            state.alloc(id(layer));
+        }
+        select(&mut state, 15, 10);
+
+        let ids = state.get_vlayer_ids(VLayerId::new(SELECTED)).collect_vec();
+        assert_eq!(ids.len(), 6);
+        for layer in ids {
+            state.free(layer);
+        }
+
+        assert!(state.get_layer(id(10)).is_none());
+        assert!(state.get_layer(id(12)).is_none());
+        assert!(state.get_layer(id(20)).is_some());
+    }
+
+    #[test]
+    fn special_layers() {
+        let mut state = LayersState::new();
+        select(&mut state, 0, 5);
+
+        for special in [-1, -2, -3, -4] {
+            let special = VLayerId::new(special);
+            assert_eq!(state.get_vlayer_mut(special).count(), 1);
+            // the special layers can't be loaded or unloaded
+            assert_eq!(state.get_vlayer_ids(special).count(), 0);
+        }
+
+        state
+            .get_vlayer_mut(VLayerId::new(-2))
+            .for_each(|l| l.properties.set_property(LayerProperty::Rotation, 250));
+        assert_eq!(
+            state
+                .screen_layer
+                .properties
+                .get_property(LayerProperty::Rotation),
+            250
+        );
+        assert_eq!(
+            state
+                .page_layer
+                .properties
+                .get_property(LayerProperty::Rotation),
+            0
+        );
+    }
+
+    #[test]
+    fn no_selection() {
+        let mut state = LayersState::new();
+        state.alloc(id(1));
+
+        assert_eq!(state.get_vlayer_mut(VLayerId::new(SELECTED)).count(), 0);
+        assert_eq!(state.get_vlayer_ids(VLayerId::new(SELECTED)).count(), 0);
+    }
+}
@@ -6,6 +6,7 @@ use shin_core::{format::save::PersistData, vm::command::types::MessageboxStyle};
 
 use crate::adv::vm_state::audio::AudioState;
 
+#[derive(Debug, Clone)]
 pub struct SaveInfo {
     pub info: [String; 4],
 }
@@ -21,7 +22,7 @@ impl SaveInfo {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct MessageState {
     pub msginit: MessageboxStyle,
     pub messagebox_shown: bool,
@@ -38,6 +39,10 @@ impl MessageState {
     }
 }
 
+/// The state of the scene as set by the commands, which is enough to restore it from scratch.
+///
+/// Used to implement saving and rollback.
+#[derive(Clone)]
 pub struct VmState {
     pub save_info: SaveInfo,
     pub messagebox_state: MessageState,
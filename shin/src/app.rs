@@ -0,0 +1,147 @@
+//! The screens of the app (the game itself and the menus over it, like the settings and the galleries), organized as a stack.
+//!
+//! Only the top screen is updated, so it's the only one receiving the input.
+//! The screens below it are frozen, but still rendered unless the top screen is opaque: this is how menus are layered over the game.
+//!
+//! Pushing and popping is instant, the pushed screens are expected to fade themselves in and out.
+
+use glam::Mat4;
+use shin_render::{GpuCommonResources, Renderable};
+use tracing::debug;
+
+use crate::{
+    render::overlay::{OverlayCollector, OverlayVisitable},
+    update::UpdateContext,
+};
+
+/// What the screen stack should do after updating the top screen
+pub enum ScreenTransition {
+    None,
+    /// Show a new screen over the current one
+    Push(Box<dyn Screen>),
+    /// Close the current screen, returning to the one below
+    Pop,
+}
+
+pub trait Screen: Renderable + OverlayVisitable {
+    /// A name for logs and the overlay
+    fn name(&self) -> &'static str;
+
+    /// Updates the screen, it's only called for the top screen
+    fn update(&mut self, context: &UpdateContext) -> ScreenTransition;
+
+    /// Whether the screen covers the whole screen, so the screens below don't need to be rendered
+    fn is_opaque(&self) -> bool {
+        true
+    }
+
+    /// Called when the screen becomes the top one again, after the screen above it has been popped
+    fn on_resume(&mut self) {}
+
+    /// Called for all the screens when the app exits
+    fn on_exit(&mut self) {}
+
+    /// Called after the GPU device was lost and re-created, with the new resources in the `context`
+    ///
+    /// The screen has to re-create all of its GPU resources. If it can't, it returns `false` and is closed.
+    fn recreate_gpu_state(&mut self, _context: &UpdateContext) -> bool {
+        false
+    }
+}
+
+pub struct ScreenStack {
+    screens: Vec<Box<dyn Screen>>,
+}
+
+impl ScreenStack {
+    pub fn new(initial: Box<dyn Screen>) -> Self {
+        Self {
+            screens: vec![initial],
+        }
+    }
+
+    fn apply(&mut self, transition: ScreenTransition) {
+        match transition {
+            ScreenTransition::None => {}
+            ScreenTransition::Push(screen) => {
+                debug!("Pushing screen {}", screen.name());
+                self.screens.push(screen);
+            }
+            ScreenTransition::Pop => {
+                if let Some(screen) = self.screens.pop() {
+                    debug!("Popping screen {}", screen.name());
+                }
+                if let Some(screen) = self.screens.last_mut() {
+                    screen.on_resume();
+                }
+            }
+        }
+    }
+
+    pub fn update(&mut self, context: &UpdateContext) {
+        let Some(top) = self.screens.last_mut() else {
+            return;
+        };
+        let transition = top.update(context);
+        self.apply(transition);
+    }
+
+    pub fn on_exit(&mut self) {
+        for screen in self.screens.iter_mut().rev() {
+            screen.on_exit();
+        }
+    }
+
+    /// Re-creates the GPU resources of the screens after the device was lost, closing the screens that can't do that
+    pub fn recreate_gpu_state(&mut self, context: &UpdateContext) {
+        self.screens.retain_mut(|screen| {
+            let recreated = screen.recreate_gpu_state(context);
+            if !recreated {
+                debug!("Closing screen {} after the device loss", screen.name());
+            }
+            recreated
+        });
+    }
+}
+
+impl Renderable for ScreenStack {
+    fn render<'enc>(
+        &'enc self,
+        resources: &'enc GpuCommonResources,
+        render_pass: &mut wgpu::RenderPass<'enc>,
+        transform: Mat4,
+        projection: Mat4,
+    ) {
+        // start from the topmost opaque screen, everything below it is hidden anyway
+        let first_visible = self
+            .screens
+            .iter()
+            .rposition(|s| s.is_opaque())
+            .unwrap_or(0);
+        for screen in &self.screens[first_visible..] {
+            screen.render(resources, render_pass, transform, projection);
+        }
+    }
+
+    fn resize(&mut self, resources: &GpuCommonResources) {
+        for screen in &mut self.screens {
+            screen.resize(resources);
+        }
+    }
+}
+
+impl OverlayVisitable for ScreenStack {
+    fn visit_overlay(&self, collector: &mut OverlayCollector) {
+        collector.overlay(
+            "Screens",
+            |_ctx, top_left| {
+                let names = self.screens.iter().map(|s| s.name()).collect::<Vec<_>>();
+                top_left.label(format!("Screens: {}", names.join(" > ")));
+            },
+            false,
+        );
+        for screen in &self.screens {
+            screen.visit_overlay(collector);
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use anyhow::{Context, Result};
+use anyhow::Result;
 use bevy_utils::HashMap;
 use glam::{vec2, Vec2};
 use shin_render::{GpuCommonResources, GpuImage, LazyGpuImage};
@@ -21,15 +21,18 @@ impl Bustup {
         self.base_picture.gpu_image(resources)
     }
 
+    pub fn has_emotion(&self, emotion: &str) -> bool {
+        self.emotions.contains_key(emotion)
+    }
+
+    /// Returns `None` if the emotion doesn't have a face or doesn't exist at all
     pub fn face_gpu_image(
         &self,
         resources: &GpuCommonResources,
         emotion: &str,
     ) -> Option<&GpuImage> {
         self.emotions
-            .get(emotion)
-            .with_context(|| format!("No emotion {} in bustup", emotion))
-            .unwrap()
+            .get(emotion)?
             .face_picture
             .as_ref()
             .map(|pic| pic.gpu_image(resources))
@@ -41,11 +44,7 @@ impl Bustup {
         emotion: &str,
         mouth_intensity: f32,
     ) -> Option<&GpuImage> {
-        let emotion = self
-            .emotions
-            .get(emotion)
-            .with_context(|| format!("No emotion {} in bustup", emotion))
-            .unwrap();
+        let emotion = self.emotions.get(emotion)?;
 
         if emotion.mouth_pictures.is_empty() {
             return None;
@@ -60,6 +59,9 @@ impl Bustup {
 }
 
 impl Asset for Bustup {
+    // there is a file per character and pose, and the scripts often bring back the same ones when the characters leave and return
+    const RETAINED_COUNT: usize = 16;
+
     fn load_from_bytes(data: Vec<u8>) -> Result<Self> {
         let bustup = shin_core::format::bustup::read_bustup(&data)?;
 
@@ -0,0 +1,25 @@
+use anyhow::Result;
+use shin_core::format::mask::read_mask;
+use shin_render::{GpuCommonResources, GpuTexture, LazyGpuMaskTexture};
+
+use crate::asset::Asset;
+
+/// A transition mask, uploaded to GPU on demand
+pub struct Mask {
+    texture: LazyGpuMaskTexture,
+}
+
+impl Mask {
+    pub fn gpu_texture(&self, resources: &GpuCommonResources) -> &GpuTexture {
+        self.texture.gpu_texture(resources)
+    }
+}
+
+impl Asset for Mask {
+    fn load_from_bytes(data: Vec<u8>) -> Result<Self> {
+        let mask = read_mask(&data)?;
+        let texture = LazyGpuMaskTexture::new(mask.texels, Some(&format!("Mask {:08x}", mask.id)));
+
+        Ok(Self { texture })
+    }
+}
@@ -2,6 +2,7 @@ mod audio;
 pub mod bustup;
 mod font;
 mod locate;
+pub mod mask;
 pub mod movie;
 pub mod picture;
 mod scenario;
@@ -18,5 +19,6 @@ pub mod asset_paths {
 
 pub use locate::locate_assets;
 pub use server::{
-    AnyAssetIo, AnyAssetServer, Asset, AssetIo, AssetServer, DirAssetIo, LayeredAssetIo, RomAssetIo,
+    AnyAssetIo, AnyAssetServer, Asset, AssetCategoryStats, AssetIo, AssetServer, DirAssetIo,
+    LayeredAssetIo, LoadedAssetInfo, RomAssetIo,
 };
@@ -3,7 +3,9 @@ use std::io::Cursor;
 use anyhow::{Context, Result};
 use shin_audio::AudioManager;
 use shin_render::GpuCommonResources;
-use shin_video::{mp4::Mp4, VideoPlayer};
+use shin_video::{
+    mp4::Mp4, subtitles::Subtitles, MovieAlphaMode, MovieThumbnail, VideoPlayer, VideoPlayerOptions,
+};
 
 use crate::asset::Asset;
 
@@ -26,7 +28,19 @@ impl Movie {
         &self,
         resources: &GpuCommonResources,
         audio_manager: &AudioManager,
+        options: VideoPlayerOptions,
     ) -> Result<VideoPlayer> {
-        VideoPlayer::new(resources, audio_manager, self.mp4.clone())
+        VideoPlayer::new(resources, audio_manager, self.mp4.clone(), options)
+    }
+
+    pub fn thumbnail(&self, resources: &GpuCommonResources) -> Result<MovieThumbnail> {
+        MovieThumbnail::new(resources, self.mp4.clone(), MovieAlphaMode::Auto)
+    }
+}
+
+impl Asset for Subtitles {
+    fn load_from_bytes(data: Vec<u8>) -> Result<Self> {
+        let source = String::from_utf8(data).context("Subtitles are not valid UTF-8")?;
+        Subtitles::parse_srt(&source).context("Parsing SRT subtitles")
     }
 }
@@ -1,11 +1,16 @@
 use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, VecDeque},
     fmt::Debug,
     fs::File,
     io,
     io::BufReader,
     ops::{Deref, DerefMut},
     path::{Path, PathBuf},
-    sync::{Arc, Mutex, RwLock, Weak},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, RwLock, Weak,
+    },
 };
 
 use anyhow::{anyhow, bail, Context, Result};
@@ -13,52 +18,274 @@ use async_trait::async_trait;
 use bevy_utils::HashMap;
 use derive_more::From;
 use pollster::FutureExt;
+#[cfg(not(target_arch = "wasm32"))]
+use shin_core::format::rom::MappedRomReader;
 use shin_core::format::rom::RomReader;
-use shin_tasks::{AsyncComputeTaskPool, IoTaskPool};
-use tracing::debug;
+use shin_render::GpuTexture;
+use shin_tasks::{AsyncComputeTaskPool, IoTaskPool, TaskGroup, TaskPriority};
+use tracing::{debug, warn};
+
+use crate::render::overlay::{OverlayCollector, OverlayVisitable};
 
 pub trait Asset: Send + Sync + Sized + 'static {
+    /// How many of the most recently used assets of this type are kept loaded when nothing references them anymore
+    ///
+    /// Useful for the assets that are often unloaded and loaded again shortly after, so that they don't have to be decoded each time.
+    const RETAINED_COUNT: usize = 0;
+
     fn load_from_bytes(data: Vec<u8>) -> Result<Self>;
 }
 
-struct AssetMap<T: Asset>(HashMap<String, Weak<T>>);
+struct AssetEntry<T> {
+    asset: Weak<T>,
+    /// Size of the file the asset was loaded from
+    file_size: usize,
+}
+
+struct AssetMap<T: Asset> {
+    assets: HashMap<String, AssetEntry<T>>,
+    /// Strong references to the last [`Asset::RETAINED_COUNT`] used assets, the most recent first
+    retained: VecDeque<Arc<T>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl<T: Asset> AssetMap<T> {
+    fn new() -> Self {
+        Self {
+            assets: HashMap::default(),
+            retained: VecDeque::new(),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    fn retain(&mut self, asset: &Arc<T>) {
+        if T::RETAINED_COUNT == 0 {
+            return;
+        }
+
+        self.retained.retain(|v| !Arc::ptr_eq(v, asset));
+        self.retained.push_front(asset.clone());
+        self.retained.truncate(T::RETAINED_COUNT);
+    }
+
+    fn live_entries(&self) -> impl Iterator<Item = (&String, &AssetEntry<T>)> {
+        self.assets
+            .iter()
+            .filter(|(_, entry)| entry.asset.strong_count() > 0)
+    }
+}
 
 impl<T: Asset> Deref for AssetMap<T> {
-    type Target = HashMap<String, Weak<T>>;
+    type Target = HashMap<String, AssetEntry<T>>;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.assets
     }
 }
 impl<T: Asset> DerefMut for AssetMap<T> {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
+        &mut self.assets
+    }
+}
+
+/// Cache statistics of one type of assets
+#[derive(Debug, Clone)]
+pub struct AssetCategoryStats {
+    pub name: &'static str,
+    /// Assets still referenced by someone, including the retained ones
+    pub live: usize,
+    /// Assets kept loaded only because of [`Asset::RETAINED_COUNT`]
+    pub retained: usize,
+    /// Size of the files the live assets were loaded from
+    pub live_file_bytes: u64,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct LoadedAssetInfo {
+    pub category: &'static str,
+    pub path: String,
+    pub file_size: usize,
+}
+
+/// Type-erased access to an [`AssetMap`], so that the statistics can be collected without knowing the asset types
+trait AssetMapStats {
+    fn stats(&self) -> AssetCategoryStats;
+    fn list(&self, out: &mut Vec<LoadedAssetInfo>);
+}
+
+fn category_name<T: Asset>() -> &'static str {
+    let name = std::any::type_name::<T>();
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+impl<T: Asset> AssetMapStats for AssetMap<T> {
+    fn stats(&self) -> AssetCategoryStats {
+        let (live, live_file_bytes) = self
+            .live_entries()
+            .fold((0, 0), |(count, bytes), (_, entry)| {
+                (count + 1, bytes + entry.file_size as u64)
+            });
+        let retained = self
+            .retained
+            .iter()
+            .filter(|asset| Arc::strong_count(asset) == 1)
+            .count();
+
+        AssetCategoryStats {
+            name: category_name::<T>(),
+            live,
+            retained,
+            live_file_bytes,
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    fn list(&self, out: &mut Vec<LoadedAssetInfo>) {
+        out.extend(self.live_entries().map(|(path, entry)| LoadedAssetInfo {
+            category: category_name::<T>(),
+            path: path.clone(),
+            file_size: entry.file_size,
+        }));
     }
 }
 
+type AnyMap = anymap::Map<dyn core::any::Any + Send + Sync>;
+
+fn map_stats<T: Asset>(maps: &AnyMap) -> &dyn AssetMapStats {
+    maps.get::<AssetMap<T>>().unwrap()
+}
+
+struct LoadedAssets {
+    maps: AnyMap,
+    /// Getters of the maps present in `maps`, in the order they were created (see [`map_stats`])
+    categories: Vec<fn(&AnyMap) -> &dyn AssetMapStats>,
+}
+
+impl LoadedAssets {
+    fn new() -> Self {
+        Self {
+            maps: AnyMap::new(),
+            categories: Vec::new(),
+        }
+    }
+
+    fn get<T: Asset>(&self) -> Option<&AssetMap<T>> {
+        self.maps.get::<AssetMap<T>>()
+    }
+
+    fn get_or_insert<T: Asset>(&mut self) -> &mut AssetMap<T> {
+        if !self.maps.contains::<AssetMap<T>>() {
+            self.categories.push(map_stats::<T>);
+        }
+        self.maps
+            .entry::<AssetMap<T>>()
+            .or_insert_with(AssetMap::new)
+    }
+
+    fn categories(&self) -> impl Iterator<Item = &dyn AssetMapStats> {
+        self.categories.iter().map(|get| get(&self.maps))
+    }
+}
+
+/// How many prefetch loads can run at the same time, so that they don't delay the loads needed right now
+const MAX_PREFETCHES_IN_FLIGHT: usize = 2;
+/// How many of the most recently prefetched assets are kept loaded, waiting to be used
+const PREFETCHED_RETAINED_COUNT: usize = 32;
+
+struct PrefetchRequest<Io: AssetIo> {
+    priority: usize,
+    /// Keeps the requests with the same priority in the order they were made
+    sequence: u64,
+    path: String,
+    spawn: fn(Arc<AssetServer<Io>>, String, &TaskGroup),
+}
+
+impl<Io: AssetIo> PartialEq for PrefetchRequest<Io> {
+    fn eq(&self, other: &Self) -> bool {
+        (self.priority, self.sequence) == (other.priority, other.sequence)
+    }
+}
+impl<Io: AssetIo> Eq for PrefetchRequest<Io> {}
+impl<Io: AssetIo> PartialOrd for PrefetchRequest<Io> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl<Io: AssetIo> Ord for PrefetchRequest<Io> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.priority, self.sequence).cmp(&(other.priority, other.sequence))
+    }
+}
+
+struct PrefetchQueue<Io: AssetIo> {
+    /// The lowest priority value is loaded first
+    pending: BinaryHeap<Reverse<PrefetchRequest<Io>>>,
+    next_sequence: u64,
+    in_flight: usize,
+    /// The started loads, cancelled when the queue is cleared
+    tasks: TaskGroup,
+    /// Strong references to the last [`PREFETCHED_RETAINED_COUNT`] prefetched assets, so that they stay in the cache until used
+    prefetched: VecDeque<Arc<dyn core::any::Any + Send + Sync>>,
+}
+
 pub struct AssetServer<Io: AssetIo> {
     io: Io,
-    loaded_assets: RwLock<anymap::Map<dyn core::any::Any + Send + Sync>>,
+    loaded_assets: RwLock<LoadedAssets>,
+    prefetch_queue: Mutex<PrefetchQueue<Io>>,
 }
 
 impl<Io: AssetIo> AssetServer<Io> {
     pub fn new(io: Io) -> Self {
         Self {
             io,
-            loaded_assets: RwLock::new(anymap::Map::new()),
+            loaded_assets: RwLock::new(LoadedAssets::new()),
+            prefetch_queue: Mutex::new(PrefetchQueue {
+                pending: BinaryHeap::new(),
+                next_sequence: 0,
+                in_flight: 0,
+                tasks: TaskGroup::new(),
+                prefetched: VecDeque::new(),
+            }),
         }
     }
 
+    fn is_loaded<T: Asset>(&self, path: &str) -> bool {
+        self.loaded_assets
+            .read()
+            .unwrap()
+            .get::<T>()
+            .and_then(|loaded| loaded.get(path))
+            .map_or(false, |entry| entry.asset.strong_count() > 0)
+    }
+
     pub async fn load<T: Asset, P: AsRef<str>>(&self, path: P) -> Result<Arc<T>> {
         let path = path.as_ref();
 
-        if let Some(loaded) = self.loaded_assets.read().unwrap().get::<AssetMap<T>>() {
-            if let Some(asset) = loaded.get(path) {
-                if let Some(asset) = asset.upgrade() {
-                    debug!("Loaded asset from cache: {}", path);
-                    return Ok(asset);
+        let cached = {
+            let loaded_assets = self.loaded_assets.read().unwrap();
+            loaded_assets.get::<T>().and_then(|loaded| {
+                let asset = loaded.get(path).and_then(|entry| entry.asset.upgrade());
+                if asset.is_some() {
+                    loaded.hits.fetch_add(1, Ordering::Relaxed);
                 }
+                asset
+            })
+        };
+        if let Some(asset) = cached {
+            debug!("Loaded asset from cache: {}", path);
+            if T::RETAINED_COUNT > 0 {
+                self.loaded_assets
+                    .write()
+                    .unwrap()
+                    .get_or_insert::<T>()
+                    .retain(&asset);
             }
+            return Ok(asset);
         }
 
         debug!("Loading asset: {}", path);
@@ -69,20 +296,48 @@ impl<Io: AssetIo> AssetServer<Io> {
             .read_file(path)
             .await
             .with_context(|| format!("Reading asset {:?}", path))?;
+        let file_size = data.len();
 
         let asset = AsyncComputeTaskPool::get()
-            .spawn(async move { T::load_from_bytes(data) })
-            .await?;
+            .spawn_named(format!("decode {}", path), async move {
+                T::load_from_bytes(data)
+            })
+            .await??;
         let asset = Arc::new(asset);
 
+        let mut loaded_assets = self.loaded_assets.write().unwrap();
+        let loaded = loaded_assets.get_or_insert::<T>();
+        loaded.misses.fetch_add(1, Ordering::Relaxed);
+        loaded.insert(
+            path.to_string(),
+            AssetEntry {
+                asset: Arc::downgrade(&asset),
+                file_size,
+            },
+        );
+        loaded.retain(&asset);
+
+        Ok(asset)
+    }
+
+    /// Returns the cache statistics for each type of assets that was loaded at least once
+    pub fn stats(&self) -> Vec<AssetCategoryStats> {
         self.loaded_assets
-            .write()
+            .read()
             .unwrap()
-            .entry::<AssetMap<T>>()
-            .or_insert_with(|| AssetMap(HashMap::default()))
-            .insert(path.to_string(), Arc::downgrade(&asset));
+            .categories()
+            .map(|category| category.stats())
+            .collect()
+    }
 
-        Ok(asset)
+    /// Lists the assets that are currently loaded, sorted by the type and the path
+    pub fn loaded_assets(&self) -> Vec<LoadedAssetInfo> {
+        let mut result = Vec::new();
+        for category in self.loaded_assets.read().unwrap().categories() {
+            category.list(&mut result);
+        }
+        result.sort_by(|a, b| (a.category, &a.path).cmp(&(b.category, &b.path)));
+        result
     }
 
     /// Load an asset synchronously. This is useful for assets not requiring much CPU time to load.
@@ -94,6 +349,119 @@ impl<Io: AssetIo> AssetServer<Io> {
     }
 }
 
+impl<Io: AssetIo + Send + Sync + 'static> AssetServer<Io> {
+    /// Requests an asset to be loaded in the background, so that it's already in the cache when it's needed
+    ///
+    /// The requests with the lower `priority` value are loaded first, they are started by [`AssetServer::update_prefetch`].
+    pub fn prefetch<T: Asset>(&self, path: impl Into<String>, priority: usize) {
+        let path = path.into();
+        if self.is_loaded::<T>(&path) {
+            return;
+        }
+
+        let mut queue = self.prefetch_queue.lock().unwrap();
+        let sequence = queue.next_sequence;
+        queue.next_sequence += 1;
+        queue.pending.push(Reverse(PrefetchRequest {
+            priority,
+            sequence,
+            path,
+            spawn: Self::spawn_prefetch::<T>,
+        }));
+    }
+
+    /// Drops the prefetch requests and cancels the loads already started, for when the predictions they were based on are outdated
+    pub fn clear_prefetch_queue(&self) {
+        let mut queue = self.prefetch_queue.lock().unwrap();
+        queue.pending.clear();
+        // dropping the old group cancels its tasks
+        queue.tasks = TaskGroup::new();
+    }
+
+    /// Forgets all the loaded assets, so that they are loaded again on the next request
+    ///
+    /// Used when the GPU device is re-created, as the cached assets can hold the textures of the old one.
+    pub fn clear(&self) {
+        self.clear_prefetch_queue();
+        self.prefetch_queue.lock().unwrap().prefetched.clear();
+        *self.loaded_assets.write().unwrap() = LoadedAssets::new();
+    }
+
+    /// Starts the queued prefetch loads, should be called every frame
+    pub fn update_prefetch(self: &Arc<Self>) {
+        let mut queue = self.prefetch_queue.lock().unwrap();
+        while queue.in_flight < MAX_PREFETCHES_IN_FLIGHT {
+            let Some(Reverse(request)) = queue.pending.pop() else {
+                break;
+            };
+            queue.in_flight += 1;
+            (request.spawn)(self.clone(), request.path, &queue.tasks);
+        }
+    }
+
+    fn spawn_prefetch<T: Asset>(server: Arc<Self>, path: String, tasks: &TaskGroup) {
+        // the load can be cancelled (even before it starts), so the counter is decremented when the future is dropped
+        let in_flight = PrefetchInFlight(server.clone());
+        let load = async move {
+            let result = server.load::<T, _>(&path).await;
+            drop(in_flight);
+
+            let mut queue = server.prefetch_queue.lock().unwrap();
+            match result {
+                Ok(asset) => {
+                    debug!("Prefetched asset: {}", path);
+                    queue.prefetched.push_front(asset);
+                    queue.prefetched.truncate(PREFETCHED_RETAINED_COUNT);
+                }
+                Err(e) => debug!("Failed to prefetch {}: {:#}", path, e),
+            }
+        };
+
+        IoTaskPool::get()
+            .spawn_with_priority(TaskPriority::Background, tasks.token().run(load))
+            .detach();
+    }
+}
+
+struct PrefetchInFlight<Io: AssetIo>(Arc<AssetServer<Io>>);
+
+impl<Io: AssetIo> Drop for PrefetchInFlight<Io> {
+    fn drop(&mut self) {
+        self.0.prefetch_queue.lock().unwrap().in_flight -= 1;
+    }
+}
+
+impl<Io: AssetIo> OverlayVisitable for AssetServer<Io> {
+    fn visit_overlay(&self, collector: &mut OverlayCollector) {
+        collector.overlay(
+            "Asset Cache",
+            |_ctx, top_left| {
+                let mib = |bytes: u64| bytes as f64 / (1024.0 * 1024.0);
+
+                let textures = GpuTexture::stats();
+                top_left.label(format!(
+                    "Textures: {} live ({:.1} MiB), {} created",
+                    textures.live,
+                    mib(textures.live_bytes),
+                    textures.created
+                ));
+                for category in self.stats() {
+                    top_left.label(format!(
+                        "{}: {} live ({} retained, {:.1} MiB of files), {} hits, {} misses",
+                        category.name,
+                        category.live,
+                        category.retained,
+                        mib(category.live_file_bytes),
+                        category.hits,
+                        category.misses
+                    ));
+                }
+            },
+            false,
+        );
+    }
+}
+
 pub type AnyAssetServer = AssetServer<AnyAssetIo>;
 
 impl AnyAssetServer {
@@ -192,10 +560,64 @@ impl<S: io::Read + io::Seek + Send + Sync + 'static> AssetIo for RomAssetIo<S> {
     }
 }
 
+/// Reads the assets from a memory mapped ROM
+///
+/// Unlike [`RomAssetIo`], the reads don't need to be serialized and the data is copied straight from the mapping.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct MappedRomAssetIo {
+    rom: Arc<MappedRomReader>,
+    label: Option<String>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Debug for MappedRomAssetIo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("MappedRomAssetIo")
+            .field(&self.label.as_deref().unwrap_or("unnamed"))
+            .finish()
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl MappedRomAssetIo {
+    pub fn new(rom: MappedRomReader, label: Option<&str>) -> Self {
+        Self {
+            rom: Arc::new(rom),
+            label: label.map(|s| s.to_string()),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[async_trait]
+impl AssetIo for MappedRomAssetIo {
+    async fn read_file(&self, path: &str) -> Result<Vec<u8>> {
+        let rom = self.rom.clone();
+        let path = path.to_string();
+
+        IoTaskPool::get()
+            .spawn(async move {
+                let file = rom
+                    .find_file(&path)
+                    .with_context(|| format!("Finding asset {:?}", path))?;
+                let data = rom
+                    .file_data(file)
+                    .with_context(|| format!("Reading asset {:?}", path))?;
+
+                // the page faults happen here, on the IO pool
+                Ok(data.to_vec())
+            })
+            .await
+    }
+}
+
 #[derive(Debug, From)]
 pub enum AnyAssetIo {
     Dir(DirAssetIo),
     RomFile(RomAssetIo<BufReader<File>>),
+    #[cfg(not(target_arch = "wasm32"))]
+    #[from(ignore)]
+    MappedRomFile(MappedRomAssetIo),
     Layered(LayeredAssetIo),
 }
 
@@ -204,14 +626,23 @@ impl AnyAssetIo {
         Self::Dir(DirAssetIo::new(root_path))
     }
 
+    /// Memory maps the ROM when the platform supports it, falling back to the buffered reads
     pub fn new_rom(rom_path: impl AsRef<Path>) -> Self {
         let rom_path = rom_path.as_ref();
+        let label = format!("{}", rom_path.display());
+
+        #[cfg(not(target_arch = "wasm32"))]
+        match MappedRomReader::open_mapped(rom_path) {
+            Ok(rom) => return Self::MappedRomFile(MappedRomAssetIo::new(rom, Some(&label))),
+            Err(e) => warn!(
+                "Could not memory map the rom {}, falling back to buffered reads: {:?}",
+                label, e
+            ),
+        }
+
         let rom =
             RomReader::new(BufReader::new(File::open(rom_path).unwrap())).expect("Opening rom");
-        Self::RomFile(RomAssetIo::new(
-            rom,
-            Some(&format!("{}", rom_path.display())),
-        ))
+        Self::RomFile(RomAssetIo::new(rom, Some(&label)))
     }
 }
 
@@ -221,6 +652,8 @@ impl AssetIo for AnyAssetIo {
         match self {
             Self::Dir(io) => io.read_file(path).await,
             Self::RomFile(io) => io.read_file(path).await,
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::MappedRomFile(io) => io.read_file(path).await,
             Self::Layered(io) => io.read_file(path).await,
         }
     }
@@ -297,3 +730,146 @@ impl AssetIo for LayeredAssetIo {
         ))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::Arc,
+        time::{Duration, Instant},
+    };
+
+    use anyhow::{bail, Result};
+    use async_trait::async_trait;
+
+    use super::{Asset, AssetIo, AssetServer};
+
+    /// The files contain their own paths, except for the ones starting with `missing`
+    struct PathIo;
+
+    #[async_trait]
+    impl AssetIo for PathIo {
+        async fn read_file(&self, path: &str) -> Result<Vec<u8>> {
+            if path.starts_with("missing") {
+                bail!("No such file: {}", path);
+            }
+            Ok(path.as_bytes().to_vec())
+        }
+    }
+
+    struct Text(String);
+
+    impl Asset for Text {
+        fn load_from_bytes(data: Vec<u8>) -> Result<Self> {
+            Ok(Self(String::from_utf8(data)?))
+        }
+    }
+
+    fn server() -> Arc<AssetServer<PathIo>> {
+        shin_tasks::create_task_pools();
+        Arc::new(AssetServer::new(PathIo))
+    }
+
+    /// The paths of the requests not started yet, in the order they will be started
+    fn pending(server: &AssetServer<PathIo>) -> Vec<String> {
+        let queue = server.prefetch_queue.lock().unwrap();
+        let mut pending = queue
+            .pending
+            .iter()
+            .map(|request| {
+                (
+                    request.0.priority,
+                    request.0.sequence,
+                    request.0.path.clone(),
+                )
+            })
+            .collect::<Vec<_>>();
+        pending.sort();
+        pending.into_iter().map(|(_, _, path)| path).collect()
+    }
+
+    /// Waits for the prefetch loads running on the task pool
+    fn wait_until(server: &AssetServer<PathIo>, condition: impl Fn(&AssetServer<PathIo>) -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !condition(server) {
+            assert!(
+                Instant::now() < deadline,
+                "Timed out waiting for the prefetch"
+            );
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    fn prefetched_count(server: &AssetServer<PathIo>) -> usize {
+        server.prefetch_queue.lock().unwrap().prefetched.len()
+    }
+
+    #[test]
+    fn prefetch_starts_by_priority_and_limits_the_loads() {
+        let server = server();
+        server.prefetch::<Text>("c", 2);
+        server.prefetch::<Text>("a", 1);
+        server.prefetch::<Text>("b", 1);
+        server.prefetch::<Text>("d", 0);
+        assert_eq!(pending(&server), ["d", "a", "b", "c"]);
+
+        // only `MAX_PREFETCHES_IN_FLIGHT` loads are started at once
+        server.update_prefetch();
+        assert_eq!(pending(&server), ["b", "c"]);
+
+        wait_until(&server, |server| prefetched_count(server) == 2);
+        assert!(server.is_loaded::<Text>("d"));
+        assert!(server.is_loaded::<Text>("a"));
+        assert!(!server.is_loaded::<Text>("b"));
+
+        server.update_prefetch();
+        assert!(pending(&server).is_empty());
+        wait_until(&server, |server| prefetched_count(server) == 4);
+        assert!(server.is_loaded::<Text>("b"));
+        assert!(server.is_loaded::<Text>("c"));
+    }
+
+    #[test]
+    fn failed_prefetch_frees_its_slot() {
+        let server = server();
+        server.prefetch::<Text>("missing1", 0);
+        server.prefetch::<Text>("missing2", 0);
+        server.prefetch::<Text>("a", 1);
+
+        server.update_prefetch();
+        assert_eq!(pending(&server), ["a"]);
+        wait_until(&server, |server| {
+            server.prefetch_queue.lock().unwrap().in_flight == 0
+        });
+
+        server.update_prefetch();
+        wait_until(&server, |server| server.is_loaded::<Text>("a"));
+        assert!(!server.is_loaded::<Text>("missing1"));
+    }
+
+    #[test]
+    fn prefetch_skips_loaded_assets() {
+        let server = server();
+        let asset = server.load_sync::<Text>("a").unwrap();
+        assert_eq!(asset.0, "a");
+
+        server.prefetch::<Text>("a", 0);
+        assert!(pending(&server).is_empty());
+
+        // nothing keeps it loaded anymore
+        drop(asset);
+        server.prefetch::<Text>("a", 0);
+        assert_eq!(pending(&server), ["a"]);
+    }
+
+    #[test]
+    fn clear_prefetch_queue_drops_the_requests() {
+        let server = server();
+        server.prefetch::<Text>("a", 0);
+        server.prefetch::<Text>("b", 0);
+
+        server.clear_prefetch_queue();
+        assert!(pending(&server).is_empty());
+        server.update_prefetch();
+        assert_eq!(server.prefetch_queue.lock().unwrap().in_flight, 0);
+    }
+}
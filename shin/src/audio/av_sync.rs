@@ -0,0 +1,177 @@
+//! The A/V sync calibration overlay, to find the [`MovieSettings::audio_offset_ms`](crate::settings::MovieSettings::audio_offset_ms) for the platform.
+//!
+//! It plays a beep every second and flashes a square when the movie player would show the frame of that beep.
+//! The offset is adjusted until the two happen at the same time, and then saved to the settings.
+
+use std::{f32::consts::PI, sync::Arc, sync::Mutex};
+
+use egui::{Color32, Sense, Slider, Window};
+use kira::track::TrackId;
+use shin_audio::{AudioData, AudioHandle, AudioManager, AudioSettings, DuckingRole};
+use shin_core::{
+    format::audio::{AudioBuffer, AudioFrameSource},
+    time::Tween,
+    vm::command::types::{AudioWaitStatus, Pan, Volume},
+};
+
+use crate::{
+    render::overlay::{OverlayCollector, OverlayVisitable},
+    settings::SettingsStore,
+};
+
+const SAMPLE_RATE: u32 = 48000;
+const FRAME_SIZE: u32 = 960;
+const BEEP_FREQUENCY: f32 = 1000.0;
+const BEEP_DURATION: f32 = 0.05;
+const BEEP_COUNT: u32 = 30;
+/// How long the square stays lit after each beep, in seconds
+const FLASH_DURATION: f64 = 0.1;
+
+/// A beep at the start of each second, for [`BEEP_COUNT`] seconds
+struct BeepSource {
+    position: u32,
+}
+
+impl AudioFrameSource for BeepSource {
+    fn max_frame_size(&self) -> usize {
+        FRAME_SIZE as usize
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn pre_skip(&self) -> u32 {
+        0
+    }
+
+    fn pre_roll(&self) -> u32 {
+        0
+    }
+
+    fn read_frame(&mut self, destination: &mut AudioBuffer) -> bool {
+        if self.position >= BEEP_COUNT * SAMPLE_RATE {
+            return false;
+        }
+
+        for i in self.position..self.position + FRAME_SIZE {
+            let time = i as f32 / SAMPLE_RATE as f32;
+            let sample = if time.fract() < BEEP_DURATION {
+                (2.0 * PI * BEEP_FREQUENCY * time).sin() * 0.5
+            } else {
+                0.0
+            };
+            destination.push((sample, sample));
+        }
+        self.position += FRAME_SIZE;
+
+        true
+    }
+
+    fn samples_seek(&mut self, sample_position: u32) -> anyhow::Result<u32> {
+        self.position = sample_position - sample_position % FRAME_SIZE;
+        Ok(sample_position % FRAME_SIZE)
+    }
+
+    fn current_sample_position(&self) -> u32 {
+        self.position
+    }
+}
+
+pub struct AvSyncCalibration {
+    audio_manager: Arc<AudioManager>,
+    settings: Arc<SettingsStore>,
+    /// The beeps being played, if the test is running
+    beeps: Mutex<Option<AudioHandle>>,
+}
+
+impl AvSyncCalibration {
+    pub fn new(audio_manager: Arc<AudioManager>, settings: Arc<SettingsStore>) -> Self {
+        Self {
+            audio_manager,
+            settings,
+            beeps: Mutex::new(None),
+        }
+    }
+
+    fn start(&self) -> AudioHandle {
+        self.audio_manager.play(AudioData {
+            source: BeepSource { position: 0 },
+            settings: AudioSettings {
+                track: TrackId::Main,
+                fade_in: Tween::IMMEDIATE,
+                loop_region: None,
+                volume: Volume::default(),
+                pan: Pan::default(),
+                ducking: DuckingRole::None,
+            },
+        })
+    }
+
+    fn show(&self, ctx: &egui::Context) {
+        let mut beeps = self.beeps.lock().unwrap();
+        if beeps
+            .as_ref()
+            .is_some_and(|h| h.get_wait_status().contains(AudioWaitStatus::STOPPED))
+        {
+            *beeps = None;
+        }
+
+        Window::new("A/V Sync Calibration").show(ctx, |ui| {
+            let mut offset_ms = self.settings.get().movie.audio_offset_ms;
+            ui.label("Adjust the offset until the square flashes together with the beep");
+            if ui
+                .add(Slider::new(&mut offset_ms, -500..=500).suffix(" ms"))
+                .changed()
+            {
+                self.settings
+                    .update(|settings| settings.movie.audio_offset_ms = offset_ms);
+            }
+
+            // the same time the movie player shows the frames at
+            let flash = beeps.as_ref().is_some_and(|handle| {
+                let time = handle.position().as_seconds() as f64 - offset_ms as f64 / 1000.0;
+                time >= 0.0 && time.fract() < FLASH_DURATION
+            });
+            let (rect, _) = ui.allocate_exact_size(egui::vec2(100.0, 100.0), Sense::hover());
+            ui.painter().rect_filled(
+                rect,
+                0.0,
+                if flash {
+                    Color32::WHITE
+                } else {
+                    Color32::DARK_GRAY
+                },
+            );
+
+            ui.horizontal(|ui| {
+                match &mut *beeps {
+                    Some(handle) => {
+                        if ui.button("Stop").clicked() {
+                            handle.stop(Tween::IMMEDIATE).ok();
+                            *beeps = None;
+                        }
+                    }
+                    None => {
+                        if ui.button("Start").clicked() {
+                            *beeps = Some(self.start());
+                        }
+                    }
+                }
+                if ui.button("Save").clicked() {
+                    self.settings.save();
+                }
+            });
+        });
+    }
+}
+
+impl OverlayVisitable for AvSyncCalibration {
+    fn visit_overlay(&self, collector: &mut OverlayCollector) {
+        collector.overlay(
+            "A/V Sync Calibration",
+            |ctx, _top_left| self.show(ctx),
+            false,
+        );
+    }
+}
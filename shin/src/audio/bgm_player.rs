@@ -1,7 +1,10 @@
 use std::sync::Arc;
 
 use kira::track::{TrackBuilder, TrackHandle, TrackId, TrackRoutes};
-use shin_audio::{AudioData, AudioFile, AudioHandle, AudioManager, AudioSettings};
+use shin_audio::{
+    AudioData, AudioFile, AudioHandle, AudioManager, AudioSettings, BusMeter, BusMeterBuilder,
+    DuckingRole,
+};
 use shin_core::{
     time::Tween,
     vm::command::types::{Pan, Volume},
@@ -11,23 +14,21 @@ use tracing::warn;
 pub struct BgmPlayer {
     audio_manager: Arc<AudioManager>,
     bgm_track: TrackHandle,
+    meter: BusMeter,
     // TODO: async track loading?
     current_bgm: Option<AudioHandle>,
 }
 
 impl BgmPlayer {
     pub fn new(audio_manager: Arc<AudioManager>) -> Self {
-        let mut manager = audio_manager.kira_manager().lock().unwrap();
-
-        let bgm_track = manager
-            .add_sub_track(TrackBuilder::new().routes(TrackRoutes::parent(TrackId::Main)))
-            .expect("Failed to create bgm track");
-
-        drop(manager);
+        let mut bgm_track = TrackBuilder::new().routes(TrackRoutes::parent(TrackId::Main));
+        let meter = bgm_track.add_effect(BusMeterBuilder::new());
+        let bgm_track = audio_manager.add_sub_track(bgm_track);
 
         Self {
             audio_manager,
             bgm_track,
+            meter,
             current_bgm: None,
         }
     }
@@ -40,15 +41,16 @@ impl BgmPlayer {
         volume: Volume,
         fade_in: Tween,
     ) {
-        let loop_start = repeat.then_some(bgm.info().loop_start);
+        let loop_region = repeat.then(|| bgm.info().loop_region()).flatten();
         let kira_data = AudioData::from_audio_file(
             bgm,
             AudioSettings {
                 track: self.bgm_track.id(),
                 fade_in,
-                loop_start,
+                loop_region,
                 volume,
                 pan: Pan::default(),
+                ducking: DuckingRole::Ducked(self.audio_manager.ducker().clone()),
             },
         );
 
@@ -61,6 +63,10 @@ impl BgmPlayer {
         self.current_bgm = Some(handle);
     }
 
+    pub fn meter(&self) -> &BusMeter {
+        &self.meter
+    }
+
     pub fn set_volume(&mut self, volume: Volume, tween: Tween) {
         if let Some(handle) = self.current_bgm.as_mut() {
             handle.set_volume(volume, tween).unwrap();
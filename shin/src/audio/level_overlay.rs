@@ -0,0 +1,84 @@
+//! Level meters of the audio buses for the debug overlay, to check the effect of the Volume and Pan commands.
+
+use egui::{pos2, vec2, Color32, Rect, Sense, Stroke, Ui};
+use shin_audio::{BusLevels, BusMeter};
+
+/// The quietest level shown, anything below is drawn as silence
+const MIN_DB: f32 = -60.0;
+/// The levels above it are drawn in yellow, to spot the buses that are too loud
+const HOT_DB: f32 = -6.0;
+const BAR_SIZE: egui::Vec2 = vec2(200.0, 6.0);
+
+fn to_db(amplitude: f32) -> f32 {
+    if amplitude > 0.0 {
+        20.0 * amplitude.log10()
+    } else {
+        f32::NEG_INFINITY
+    }
+}
+
+/// The position of the level on the meter, from 0 at [`MIN_DB`] to 1 at 0 dBFS
+fn meter_position(amplitude: f32) -> f32 {
+    ((to_db(amplitude) - MIN_DB) / -MIN_DB).clamp(0.0, 1.0)
+}
+
+fn level_color(amplitude: f32) -> Color32 {
+    let db = to_db(amplitude);
+    if db >= 0.0 {
+        Color32::RED
+    } else if db >= HOT_DB {
+        Color32::YELLOW
+    } else {
+        Color32::GREEN
+    }
+}
+
+/// Draws a bar with the RMS level filled and a line at the peak level
+fn level_bar(ui: &mut Ui, rms: f32, peak: f32) {
+    let (rect, _) = ui.allocate_exact_size(BAR_SIZE, Sense::hover());
+    let painter = ui.painter();
+
+    painter.rect_filled(rect, 0.0, Color32::from_gray(40));
+    let rms_width = rect.width() * meter_position(rms);
+    painter.rect_filled(
+        Rect::from_min_size(rect.min, vec2(rms_width, rect.height())),
+        0.0,
+        level_color(rms),
+    );
+    if peak > 0.0 {
+        let x = rect.min.x + rect.width() * meter_position(peak);
+        painter.line_segment(
+            [pos2(x, rect.min.y), pos2(x, rect.max.y)],
+            Stroke::new(1.0, level_color(peak)),
+        );
+    }
+}
+
+fn bus_row(ui: &mut Ui, name: &str, levels: BusLevels) {
+    ui.horizontal(|ui| {
+        ui.monospace(format!("{:<5}", name));
+        ui.vertical(|ui| {
+            ui.spacing_mut().item_spacing.y = 2.0;
+            level_bar(ui, levels.rms[0], levels.peak[0]);
+            level_bar(ui, levels.rms[1], levels.peak[1]);
+        });
+        let db = |amplitude: f32| match to_db(amplitude) {
+            db if db < MIN_DB => "  -inf".to_string(),
+            db => format!("{:>6.1}", db),
+        };
+        ui.monospace(format!(
+            "L {} / {}  R {} / {} dB",
+            db(levels.rms[0]),
+            db(levels.peak[0]),
+            db(levels.rms[1]),
+            db(levels.peak[1])
+        ));
+    });
+}
+
+/// Shows a meter for each of the `buses`: two bars for the left and right channels with the RMS / peak levels
+pub fn show_levels(ui: &mut Ui, buses: &[(&str, &BusMeter)]) {
+    for &(name, meter) in buses {
+        bus_row(ui, name, meter.levels());
+    }
+}
@@ -0,0 +1,56 @@
+use std::sync::{Arc, Mutex};
+
+use shin_audio::LevelMeter;
+
+/// The level below which the mouth is considered closed, to ignore the noise and the breathing
+const SILENCE_LEVEL: f32 = 0.02;
+/// The level at which the mouth is fully open
+const FULL_LEVEL: f32 = 0.35;
+
+struct LipSyncShared {
+    meter: LevelMeter,
+    /// The lipsync character IDs of the voice playing right now
+    speaking: Mutex<Vec<u8>>,
+}
+
+/// Tells the bustups how open the mouths of their characters should be, following the voice being played
+///
+/// The characters are matched by the lipsync character IDs (see [`shin_core::format::scenario::info::BustupInfoItem::lipsync_character_id`]).
+#[derive(Clone)]
+pub struct LipSync {
+    shared: Arc<LipSyncShared>,
+}
+
+impl LipSync {
+    pub fn new(meter: LevelMeter) -> Self {
+        Self {
+            shared: Arc::new(LipSyncShared {
+                meter,
+                speaking: Mutex::new(Vec::new()),
+            }),
+        }
+    }
+
+    pub(super) fn set_speaking(&self, character_ids: &[u8]) {
+        let mut speaking = self.shared.speaking.lock().unwrap();
+        speaking.clear();
+        speaking.extend_from_slice(character_ids);
+    }
+
+    /// Returns how open the mouth of the character should be, from `0.0` (closed) to `1.0`
+    pub fn mouth_openness(&self, character_id: u16) -> f32 {
+        let speaking = self
+            .shared
+            .speaking
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|&id| id as u16 == character_id);
+        if !speaking {
+            return 0.0;
+        }
+
+        let level = self.shared.meter.level();
+        ((level - SILENCE_LEVEL) / (FULL_LEVEL - SILENCE_LEVEL)).clamp(0.0, 1.0)
+    }
+}
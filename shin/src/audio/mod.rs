@@ -1,5 +1,15 @@
+mod av_sync;
 mod bgm_player;
+mod level_overlay;
+mod lip_sync;
 mod se_player;
+mod snapshot;
+mod voice_player;
 
+pub use av_sync::AvSyncCalibration;
 pub use bgm_player::BgmPlayer;
+pub use level_overlay::show_levels;
+pub use lip_sync::LipSync;
 pub use se_player::{SePlayer, SE_SLOT_COUNT};
+pub use snapshot::AudioSnapshot;
+pub use voice_player::VoicePlayer;
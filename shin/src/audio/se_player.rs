@@ -1,7 +1,10 @@
 use std::sync::Arc;
 
 use kira::track::{TrackBuilder, TrackHandle, TrackId, TrackRoutes};
-use shin_audio::{AudioData, AudioFile, AudioHandle, AudioManager, AudioSettings};
+use shin_audio::{
+    AudioData, AudioFile, AudioHandle, AudioManager, AudioSettings, BusMeter, BusMeterBuilder,
+    DuckingRole,
+};
 use shin_core::{
     time::Tween,
     vm::command::types::{AudioWaitStatus, Pan, Volume},
@@ -12,29 +15,36 @@ pub const SE_SLOT_COUNT: usize = 32;
 
 pub struct SePlayer {
     audio_manager: Arc<AudioManager>,
+    /// All the slot tracks go through it, to be metered together
+    _se_bus: TrackHandle,
+    meter: BusMeter,
     se_tracks: [TrackHandle; SE_SLOT_COUNT],
     se_slots: [Option<AudioHandle>; SE_SLOT_COUNT],
 }
 
 impl SePlayer {
     pub fn new(audio_manager: Arc<AudioManager>) -> Self {
-        let mut manager = audio_manager.kira_manager().lock().unwrap();
-
+        let mut se_bus = TrackBuilder::new().routes(TrackRoutes::parent(TrackId::Main));
+        let meter = se_bus.add_effect(BusMeterBuilder::new());
+        let se_bus = audio_manager.add_sub_track(se_bus);
         let se_tracks = [(); SE_SLOT_COUNT].map(|_| {
-            manager
-                .add_sub_track(TrackBuilder::new().routes(TrackRoutes::parent(TrackId::Main)))
-                .expect("Failed to create se track")
+            audio_manager
+                .add_sub_track(TrackBuilder::new().routes(TrackRoutes::parent(se_bus.id())))
         });
 
-        drop(manager);
-
         Self {
             audio_manager,
+            _se_bus: se_bus,
+            meter,
             se_tracks,
             se_slots: [(); SE_SLOT_COUNT].map(|_| None),
         }
     }
 
+    pub fn meter(&self) -> &BusMeter {
+        &self.meter
+    }
+
     pub fn play(
         &mut self,
         slot: i32,
@@ -46,15 +56,16 @@ impl SePlayer {
     ) {
         let slot = slot as usize;
 
-        let loop_start = repeat.then_some(se.info().loop_start);
+        let loop_region = repeat.then(|| se.info().loop_region()).flatten();
         let kira_data = AudioData::from_audio_file(
             se,
             AudioSettings {
                 track: self.se_tracks[slot].id(),
                 fade_in,
-                loop_start,
+                loop_region,
                 volume,
                 pan,
+                ducking: DuckingRole::None,
             },
         );
 
@@ -103,6 +114,11 @@ impl SePlayer {
         }
     }
 
+    /// Whether a sound was started in the slot and not stopped yet (it might have finished playing though)
+    pub fn is_playing(&self, slot: i32) -> bool {
+        self.se_slots[slot as usize].is_some()
+    }
+
     pub fn stop_all(&mut self, fade_out: Tween) {
         for slot in 0..SE_SLOT_COUNT {
             if self.se_slots[slot].is_some() {
@@ -0,0 +1,157 @@
+//! A serializable form of the audio state, to be stored in the saves next to the [`LayersSnapshot`](crate::layer::LayersSnapshot).
+//!
+//! Only the looping sounds are kept (the BGM and the looping SE slots), the one-shot sounds are never resumed.
+//! Like the original engine, the tracks are restarted from the beginning on load (and then loop in their loop regions),
+//! so the playback position is not stored.
+//!
+//! The schema is versioned by [`AUDIO_SNAPSHOT_VERSION`], see [`crate::snapshot`].
+
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use shin_core::vm::command::types::{Pan, Volume};
+
+use crate::{
+    adv::vm_state::audio::{AudioState, BgmState, SeState},
+    audio::SE_SLOT_COUNT,
+    snapshot::check_version,
+};
+
+/// Bumped on the incompatible changes to the stored sounds or the SE slot numbering
+pub const AUDIO_SNAPSHOT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct BgmSnapshot {
+    bgm_id: i32,
+    volume: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct SeSnapshot {
+    se_id: i32,
+    volume: f32,
+    pan: f32,
+    play_speed: f32,
+}
+
+/// The audio state in a form that is stable across the engine versions
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AudioSnapshot {
+    version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    bgm: Option<BgmSnapshot>,
+    /// The looping sounds by the SE slot
+    #[serde(default)]
+    se: BTreeMap<usize, SeSnapshot>,
+}
+
+impl AudioSnapshot {
+    pub fn capture(state: &AudioState) -> Self {
+        Self {
+            version: AUDIO_SNAPSHOT_VERSION,
+            bgm: state.bgm.map(|bgm| BgmSnapshot {
+                bgm_id: bgm.bgm_id,
+                volume: bgm.volume.0,
+            }),
+            se: state
+                .se
+                .iter()
+                .enumerate()
+                .filter_map(|(slot, se)| {
+                    se.map(|se| {
+                        (
+                            slot,
+                            SeSnapshot {
+                                se_id: se.se_id,
+                                volume: se.volume.0,
+                                pan: se.pan.0,
+                                play_speed: se.play_speed,
+                            },
+                        )
+                    })
+                })
+                .collect(),
+        }
+    }
+
+    /// Re-creates the audio state, failing if the snapshot is from a newer engine or is corrupt
+    pub fn restore(&self) -> Result<AudioState> {
+        check_version("audio snapshot", self.version, AUDIO_SNAPSHOT_VERSION)?;
+
+        let mut state = AudioState::new();
+        state.bgm = self.bgm.as_ref().map(|bgm| BgmState {
+            bgm_id: bgm.bgm_id,
+            volume: Volume(bgm.volume),
+        });
+        for (&slot, se) in &self.se {
+            if slot >= SE_SLOT_COUNT {
+                bail!("SE slot {} is out of range", slot);
+            }
+            state.se[slot] = Some(SeState {
+                se_id: se.se_id,
+                volume: Volume(se.volume),
+                pan: Pan(se.pan),
+                play_speed: se.play_speed,
+            });
+        }
+
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshot::json_roundtrip;
+
+    #[test]
+    fn roundtrip() {
+        let mut state = AudioState::new();
+        state.bgm = Some(BgmState {
+            bgm_id: 12,
+            volume: Volume(0.5),
+        });
+        state.se[3] = Some(SeState {
+            se_id: 40,
+            volume: Volume(1.0),
+            pan: Pan(-0.25),
+            play_speed: 1.0,
+        });
+
+        let restored = json_roundtrip(&AudioSnapshot::capture(&state))
+            .restore()
+            .unwrap();
+        let bgm = restored.bgm.unwrap();
+        assert_eq!(bgm.bgm_id, 12);
+        assert_eq!(bgm.volume.0, 0.5);
+        let se = restored.se[3].unwrap();
+        assert_eq!(se.se_id, 40);
+        assert_eq!(se.pan.0, -0.25);
+        assert!(restored
+            .se
+            .iter()
+            .enumerate()
+            .all(|(slot, se)| slot == 3 || se.is_none()));
+    }
+
+    #[test]
+    fn silence() {
+        let snapshot: AudioSnapshot = serde_json::from_str(r#"{"version": 1}"#).unwrap();
+        assert_eq!(snapshot, AudioSnapshot::capture(&AudioState::new()));
+
+        let state = snapshot.restore().unwrap();
+        assert!(state.bgm.is_none());
+        assert!(state.se.iter().all(|se| se.is_none()));
+    }
+
+    #[test]
+    fn rejects_out_of_range_slots() {
+        let bad_slot: AudioSnapshot = serde_json::from_str(&format!(
+            r#"{{"version": 1, "se": {{"{}": {{"se_id": 1, "volume": 1.0, "pan": 0.0, "play_speed": 1.0}}}}}}"#,
+            SE_SLOT_COUNT
+        ))
+        .unwrap();
+        assert!(bad_slot.restore().is_err());
+    }
+}
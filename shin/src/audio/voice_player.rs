@@ -0,0 +1,101 @@
+use std::sync::Arc;
+
+use kira::track::{TrackBuilder, TrackHandle, TrackId, TrackRoutes};
+use shin_audio::{
+    AudioData, AudioFile, AudioHandle, AudioManager, AudioSettings, BusMeter, BusMeterBuilder,
+    DuckingRole, LevelMeterBuilder,
+};
+use shin_core::{
+    time::Tween,
+    vm::command::types::{AudioWaitStatus, Pan, Volume},
+};
+use tracing::debug;
+
+use crate::{audio::LipSync, settings::SettingsStore};
+
+pub struct VoicePlayer {
+    audio_manager: Arc<AudioManager>,
+    settings: Arc<SettingsStore>,
+    voice_track: TrackHandle,
+    current_voice: Option<AudioHandle>,
+    lip_sync: LipSync,
+    meter: BusMeter,
+}
+
+impl VoicePlayer {
+    pub fn new(audio_manager: Arc<AudioManager>, settings: Arc<SettingsStore>) -> Self {
+        let mut voice_track = TrackBuilder::new().routes(TrackRoutes::parent(TrackId::Main));
+        let level_meter = voice_track.add_effect(LevelMeterBuilder::new());
+        let meter = voice_track.add_effect(BusMeterBuilder::new());
+        let voice_track = audio_manager.add_sub_track(voice_track);
+
+        Self {
+            audio_manager,
+            settings,
+            voice_track,
+            current_voice: None,
+            lip_sync: LipSync::new(level_meter),
+            meter,
+        }
+    }
+
+    /// Plays the voice, the `character_ids` are the characters speaking in it, used to apply the per-character voice settings
+    pub fn play(&mut self, voice: Arc<AudioFile>, character_ids: &[u8], volume: Volume) {
+        let character_volume = self.settings.get().voice.volume_for(character_ids);
+        if character_volume <= 0.0 {
+            debug!(
+                "Not playing a voice of muted characters {:?}",
+                character_ids
+            );
+            self.stop(Tween::MS_15);
+            return;
+        }
+        let volume = Volume(volume.0 * character_volume);
+
+        let kira_data = AudioData::from_audio_file(
+            voice,
+            AudioSettings {
+                track: self.voice_track.id(),
+                fade_in: Tween::IMMEDIATE,
+                loop_region: None,
+                volume,
+                pan: Pan::default(),
+                // voices lower the BGM while they are playing
+                ducking: DuckingRole::Voice(self.audio_manager.ducker().clone()),
+            },
+        );
+
+        let handle = self.audio_manager.play(kira_data);
+
+        if let Some(mut old_handle) = self.current_voice.take() {
+            old_handle.stop(Tween::MS_15).unwrap();
+        }
+
+        self.current_voice = Some(handle);
+        self.lip_sync.set_speaking(character_ids);
+    }
+
+    pub fn stop(&mut self, fade_out: Tween) {
+        if let Some(mut handle) = self.current_voice.take() {
+            handle.stop(fade_out).unwrap();
+        }
+        self.lip_sync.set_speaking(&[]);
+    }
+
+    /// The handle used by the bustups to move the mouths of the speaking characters
+    pub fn lip_sync(&self) -> &LipSync {
+        &self.lip_sync
+    }
+
+    pub fn meter(&self) -> &BusMeter {
+        &self.meter
+    }
+
+    pub fn get_wait_status(&self) -> AudioWaitStatus {
+        if let Some(handle) = self.current_voice.as_ref() {
+            handle.get_wait_status()
+        } else {
+            AudioWaitStatus::STOPPED
+        }
+    }
+}
@@ -0,0 +1,203 @@
+//! Auxiliary windows, opened next to the game window for tooling (inspectors, alternative views of the game)
+//!
+//! They share the device and the pipelines with the game window, but each has its own surface.
+//! The window events are routed to them by the window ID, and they are redrawn together with the game window.
+
+use std::sync::Arc;
+
+use anyhow::{bail, Context, Result};
+use glam::Mat4;
+use shin_render::{Camera, GpuCommonResources, RenderTarget};
+use tracing::debug;
+use winit::{
+    dpi::LogicalSize,
+    event::WindowEvent,
+    event_loop::EventLoopWindowTarget,
+    window::{Window, WindowBuilder, WindowId},
+};
+
+/// What the game window provides to draw the auxiliary windows
+pub struct AuxRenderContext<'a> {
+    pub resources: &'a GpuCommonResources,
+    /// The game scene, without the overlays
+    pub game_view: &'a RenderTarget,
+    /// Projection fitting the virtual screen into the auxiliary window (see [`Camera`])
+    pub projection: Mat4,
+    /// Whether the surface does the sRGB encoding (see [`RenderTarget::raw_bind_group`])
+    pub srgb_surface: bool,
+}
+
+/// The contents of an auxiliary window
+pub trait AuxWindowContent {
+    fn title(&self) -> String;
+
+    fn initial_size(&self) -> (u32, u32) {
+        (960, 540)
+    }
+
+    fn window_event(&mut self, _event: &WindowEvent) {}
+
+    fn render<'a>(&'a self, context: &AuxRenderContext<'a>, render_pass: &mut wgpu::RenderPass<'a>);
+}
+
+pub struct AuxWindow {
+    window: Arc<Window>,
+    surface: wgpu::Surface<'static>,
+    surface_config: wgpu::SurfaceConfiguration,
+    camera: Camera,
+    content: Box<dyn AuxWindowContent>,
+}
+
+impl AuxWindow {
+    /// Opens the window, its surface uses the same format as the game window so that the pipelines can be shared
+    pub fn new(
+        target: &EventLoopWindowTarget<()>,
+        instance: &wgpu::Instance,
+        adapter: &wgpu::Adapter,
+        resources: &GpuCommonResources,
+        surface_format: wgpu::TextureFormat,
+        content: Box<dyn AuxWindowContent>,
+    ) -> Result<Self> {
+        let (width, height) = content.initial_size();
+        let window = WindowBuilder::new()
+            .with_title(content.title())
+            .with_inner_size(LogicalSize::new(width, height))
+            .build(target)
+            .context("Creating the window")?;
+        let window = Arc::new(window);
+
+        let surface = instance
+            .create_surface(window.clone())
+            .context("Creating surface")?;
+        let capabilities = surface.get_capabilities(adapter);
+        if !capabilities.formats.contains(&surface_format) {
+            bail!(
+                "The surface doesn't support the format of the game window ({:?}, available: {:?})",
+                surface_format,
+                capabilities.formats
+            );
+        }
+
+        let size = window.inner_size();
+        let surface_config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: surface_format,
+            width: size.width.max(1),
+            height: size.height.max(1),
+            present_mode: wgpu::PresentMode::AutoVsync,
+            desired_maximum_frame_latency: 2,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            view_formats: vec![],
+        };
+        surface.configure(&resources.device, &surface_config);
+
+        debug!("Opened the auxiliary window {:?}", content.title());
+
+        Ok(Self {
+            camera: Camera::new((surface_config.width, surface_config.height)),
+            window,
+            surface,
+            surface_config,
+            content,
+        })
+    }
+
+    pub fn id(&self) -> WindowId {
+        self.window.id()
+    }
+
+    /// Returns `false` if the window should be closed
+    pub fn window_event(&mut self, resources: &GpuCommonResources, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::CloseRequested => return false,
+            WindowEvent::Resized(size) if size.width > 0 && size.height > 0 => {
+                self.surface_config.width = size.width;
+                self.surface_config.height = size.height;
+                self.surface
+                    .configure(&resources.device, &self.surface_config);
+                self.camera.resize((size.width, size.height));
+            }
+            _ => {}
+        }
+        self.content.window_event(event);
+        true
+    }
+
+    pub fn render(
+        &mut self,
+        resources: &GpuCommonResources,
+        game_view: &RenderTarget,
+    ) -> Result<(), wgpu::SurfaceError> {
+        let output = match self.surface.get_current_texture() {
+            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                self.surface
+                    .configure(&resources.device, &self.surface_config);
+                self.surface.get_current_texture()?
+            }
+            output => output?,
+        };
+        let view = output
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        let context = AuxRenderContext {
+            resources,
+            game_view,
+            projection: self.camera.screen_projection_matrix(),
+            srgb_surface: self.surface_config.format.is_srgb(),
+        };
+
+        {
+            let mut encoder = resources.start_encoder();
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Aux Window RenderPass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            self.content.render(&context, &mut render_pass);
+        }
+
+        output.present();
+
+        Ok(())
+    }
+}
+
+/// Shows the game scene without the overlays, scaled to the window
+///
+/// Useful for capturing the game while keeping the debug overlays in the main window.
+pub struct GameViewWindow;
+
+impl AuxWindowContent for GameViewWindow {
+    fn title(&self) -> String {
+        "shin: game view".to_string()
+    }
+
+    fn render<'a>(
+        &'a self,
+        context: &AuxRenderContext<'a>,
+        render_pass: &mut wgpu::RenderPass<'a>,
+    ) {
+        let bind_group = if context.srgb_surface {
+            context.game_view.bind_group()
+        } else {
+            context.game_view.raw_bind_group()
+        };
+        context.resources.pipelines.sprite_screen.draw(
+            render_pass,
+            context.game_view.vertex_source(),
+            bind_group,
+            context.projection,
+        );
+    }
+}
@@ -0,0 +1,336 @@
+//! Recording of the game to a video, for making trailers and for reporting rendering issues
+//!
+//! Every presented frame of the game scene (without the overlays) is read back from the render target, and the final audio mix is recorded along with it.
+//! The output is either a sequence of PNG images with a WAV file next to them, or a video encoded by ffmpeg if the output path has a video extension.
+//!
+//! The frames are read back synchronously, so the game may run slower while recording.
+//! The frame rate of the output is fixed: the frames are repeated or dropped to follow the wall clock, which keeps the video in sync with the audio.
+
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+    sync::mpsc::{sync_channel, Receiver, SyncSender},
+    thread::JoinHandle,
+    time::Instant,
+};
+
+use anyhow::{Context, Result};
+use image::{imageops, RgbaImage};
+use shin_audio::MixCapture;
+use shin_render::{GpuCommonResources, RenderTarget};
+use shin_video::FfmpegVideoEncoder;
+use tracing::{error, info, warn};
+
+use crate::config::CaptureConfig;
+
+/// The outputs with these extensions are encoded with ffmpeg, everything else is a directory for the PNG sequence
+const VIDEO_EXTENSIONS: &[&str] = &["mp4", "mkv", "webm", "mov"];
+/// How many frames can wait for the writer before the game is blocked
+const FRAME_QUEUE_SIZE: usize = 8;
+/// Used until the audio device reports the actual sample rate
+const FALLBACK_SAMPLE_RATE: u32 = 48000;
+
+fn is_video_path(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| VIDEO_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+}
+
+/// Appends the `index` to the file name, so that the recordings don't overwrite each other
+fn numbered_path(path: &Path, index: u32) -> PathBuf {
+    if index == 0 {
+        return path.to_path_buf();
+    }
+
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let name = match path.extension() {
+        Some(extension) => format!("{}-{}.{}", stem, index, extension.to_string_lossy()),
+        None => format!("{}-{}", stem, index),
+    };
+    path.with_file_name(name)
+}
+
+enum Message {
+    /// The frame is shown for `repeat` frames of the output
+    Frame {
+        image: RgbaImage,
+        repeat: u64,
+    },
+    Audio(Vec<kira::Frame>),
+}
+
+enum VideoSink {
+    Png {
+        directory: PathBuf,
+        next_index: u64,
+    },
+    Ffmpeg {
+        /// Started on the first frame, when the size is known
+        encoder: Option<FfmpegVideoEncoder>,
+        /// The video without the audio, muxed with the audio into the output when finished
+        video_path: PathBuf,
+        fps: u32,
+    },
+}
+
+impl VideoSink {
+    fn write(&mut self, image: &RgbaImage, repeat: u64) -> Result<()> {
+        match self {
+            VideoSink::Png {
+                directory,
+                next_index,
+            } => {
+                let first = directory.join(format!("frame_{:06}.png", next_index));
+                image
+                    .save(&first)
+                    .with_context(|| format!("Writing {}", first.display()))?;
+                // no need to encode the repeated frames again
+                for index in *next_index + 1..*next_index + repeat {
+                    let path = directory.join(format!("frame_{:06}.png", index));
+                    std::fs::copy(&first, &path)
+                        .with_context(|| format!("Writing {}", path.display()))?;
+                }
+                *next_index += repeat;
+            }
+            VideoSink::Ffmpeg {
+                encoder,
+                video_path,
+                fps,
+            } => {
+                let encoder = match encoder {
+                    Some(encoder) => encoder,
+                    None => encoder.insert(FfmpegVideoEncoder::new(
+                        video_path,
+                        image.dimensions(),
+                        *fps,
+                    )?),
+                };
+                for _ in 0..repeat {
+                    encoder.write_frame(image.as_raw())?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Runs on a separate thread, so that the encoding doesn't slow down the game
+fn write_recording(
+    output: PathBuf,
+    fps: u32,
+    sample_rate: u32,
+    receiver: Receiver<Message>,
+) -> Result<()> {
+    let (mut sink, audio_path) = if is_video_path(&output) {
+        let video_path = output.with_extension(format!(
+            "video.{}",
+            output.extension().unwrap().to_string_lossy()
+        ));
+        let sink = VideoSink::Ffmpeg {
+            encoder: None,
+            video_path,
+            fps,
+        };
+        (sink, output.with_extension("wav"))
+    } else {
+        std::fs::create_dir_all(&output)
+            .with_context(|| format!("Creating {}", output.display()))?;
+        let sink = VideoSink::Png {
+            directory: output.clone(),
+            next_index: 0,
+        };
+        (sink, output.join("audio.wav"))
+    };
+
+    let mut audio = hound::WavWriter::new(
+        BufWriter::new(
+            File::create(&audio_path)
+                .with_context(|| format!("Creating {}", audio_path.display()))?,
+        ),
+        hound::WavSpec {
+            channels: 2,
+            sample_rate,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        },
+    )?;
+
+    // the scene is rendered at the window resolution, the size of the first frame is kept if the window is resized
+    let mut size = None;
+    for message in receiver {
+        match message {
+            Message::Frame { mut image, repeat } => {
+                let size = *size.get_or_insert(image.dimensions());
+                if image.dimensions() != size {
+                    image =
+                        imageops::resize(&image, size.0, size.1, imageops::FilterType::Triangle);
+                }
+                // the parts of the scene not covered by anything are transparent, they should be black in the video
+                for pixel in image.pixels_mut() {
+                    pixel.0[3] = 255;
+                }
+                sink.write(&image, repeat)?;
+            }
+            Message::Audio(frames) => {
+                for frame in frames {
+                    audio.write_sample(frame.left)?;
+                    audio.write_sample(frame.right)?;
+                }
+            }
+        }
+    }
+
+    audio.finalize().context("Writing the audio")?;
+
+    if let VideoSink::Ffmpeg {
+        encoder,
+        video_path,
+        ..
+    } = sink
+    {
+        let Some(encoder) = encoder else {
+            warn!("No frames were recorded");
+            return Ok(());
+        };
+        encoder.finish().context("Encoding the video")?;
+        shin_video::mux_audio(&video_path, &audio_path, &output)
+            .context("Adding the audio to the video")?;
+        // keep the parts if muxing fails, so that the recording is not lost
+        std::fs::remove_file(&video_path)?;
+        std::fs::remove_file(&audio_path)?;
+    }
+
+    Ok(())
+}
+
+struct Recording {
+    output: PathBuf,
+    sender: SyncSender<Message>,
+    writer: JoinHandle<Result<()>>,
+    started: Instant,
+    /// Number of the output frames sent to the writer, including the repeats
+    frames_sent: u64,
+}
+
+/// Records the game while active, toggled with F8
+pub struct Recorder {
+    config: CaptureConfig,
+    recording: Option<Recording>,
+    /// Used to give each recording its own output path
+    recordings_started: u32,
+}
+
+impl Recorder {
+    pub fn new(config: CaptureConfig) -> Self {
+        Self {
+            config,
+            recording: None,
+            recordings_started: 0,
+        }
+    }
+
+    pub fn start(&mut self, audio: &MixCapture) -> Result<()> {
+        if self.recording.is_some() {
+            return Ok(());
+        }
+
+        let output = numbered_path(&self.config.output, self.recordings_started);
+        self.recordings_started += 1;
+
+        let fps = self.config.fps.max(1);
+        let sample_rate = match audio.sample_rate() {
+            0 => FALLBACK_SAMPLE_RATE,
+            sample_rate => sample_rate,
+        };
+
+        let (sender, receiver) = sync_channel(FRAME_QUEUE_SIZE);
+        let writer = std::thread::Builder::new()
+            .name("capture writer".to_string())
+            .spawn({
+                let output = output.clone();
+                move || write_recording(output, fps, sample_rate, receiver)
+            })
+            .context("Spawning the capture writer thread")?;
+
+        audio.start();
+        info!("Recording to {}", output.display());
+
+        self.recording = Some(Recording {
+            output,
+            sender,
+            writer,
+            started: Instant::now(),
+            frames_sent: 0,
+        });
+
+        Ok(())
+    }
+
+    /// Stops the recording and waits for the writer to finish the files
+    pub fn stop(&mut self, audio: &MixCapture) {
+        let Some(recording) = self.recording.take() else {
+            return;
+        };
+
+        audio.stop();
+        let mut frames = Vec::new();
+        audio.drain(&mut frames);
+        // if the writer is already gone, the error is reported by the join below
+        let _ = recording.sender.send(Message::Audio(frames));
+        drop(recording.sender);
+
+        match recording.writer.join() {
+            Ok(Ok(())) => info!("Recording saved to {}", recording.output.display()),
+            Ok(Err(e)) => error!(
+                "Failed to write the recording to {}: {:?}",
+                recording.output.display(),
+                e
+            ),
+            Err(_) => error!("The capture writer thread panicked"),
+        }
+    }
+
+    pub fn toggle(&mut self, audio: &MixCapture) {
+        if self.recording.is_some() {
+            self.stop(audio);
+        } else if let Err(e) = self.start(audio) {
+            error!("Failed to start recording: {:?}", e);
+        }
+    }
+
+    /// Records the frame that is about to be presented, does nothing when not recording
+    pub fn capture_frame(
+        &mut self,
+        resources: &GpuCommonResources,
+        render_target: &RenderTarget,
+        audio: &MixCapture,
+    ) {
+        let Some(recording) = &mut self.recording else {
+            return;
+        };
+
+        let due =
+            (recording.started.elapsed().as_secs_f64() * self.config.fps.max(1) as f64) as u64 + 1;
+        let repeat = due.saturating_sub(recording.frames_sent);
+
+        let mut frames = Vec::new();
+        audio.drain(&mut frames);
+        let mut result = recording.sender.send(Message::Audio(frames));
+
+        // the game renders faster than the output frame rate, this frame is dropped
+        if result.is_ok() && repeat > 0 {
+            let image = render_target.read_pixels(resources);
+            result = recording.sender.send(Message::Frame { image, repeat });
+            recording.frames_sent += repeat;
+        }
+
+        if result.is_err() {
+            // the writer failed, stop to report the error
+            self.stop(audio);
+        }
+    }
+}
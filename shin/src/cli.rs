@@ -2,18 +2,147 @@ use std::path::PathBuf;
 
 use clap::Parser;
 use clap_num::maybe_hex;
+use shin_video::H264DecoderBackend;
+
+use crate::config::{check_render_scale, AspectMode, WindowMode};
+
+fn parse_render_scale(s: &str) -> Result<f32, String> {
+    let scale = s.parse::<f32>().map_err(|e| e.to_string())?;
+    check_render_scale(scale)
+}
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 /// A visual novel engine
 pub struct Cli {
+    /// Read the options from this file instead of `config.toml` next to the executable
+    ///
+    /// The command line flags override the options from the config file and the environment.
+    #[clap(long)]
+    pub config: Option<PathBuf>,
+    /// Print the options after applying the config file, the environment and the command line, then exit
+    #[clap(long)]
+    pub print_config: bool,
     /// Search this directory for assets
     ///
     /// The directory must contain either a directory named "data" or a file named "data.rom".
     /// Consult the README for more information.
     #[clap(short, long)]
     pub assets_dir: Option<PathBuf>,
+    /// Default language of the engine UI, like `en` or `ja` (it can be changed in the settings)
+    #[clap(long)]
+    pub language: Option<String>,
+    /// Load the additional UI translations from the `<language>.toml` files in this directory
+    #[clap(long)]
+    pub locales_dir: Option<PathBuf>,
+    /// Initial width of the window, in logical pixels
+    #[clap(long)]
+    pub width: Option<u32>,
+    /// Initial height of the window, in logical pixels
+    #[clap(long)]
+    pub height: Option<u32>,
+    /// How the window is shown on start (fullscreen can be toggled with F11)
+    #[clap(long)]
+    pub window_mode: Option<WindowMode>,
+    /// Title of the window
+    #[clap(long)]
+    pub title: Option<String>,
+    /// PNG image used as the window icon
+    #[clap(long)]
+    pub icon: Option<PathBuf>,
+    /// Synchronize the presentation with the display refresh rate
+    #[clap(long, num_args = 0..=1, default_missing_value = "true")]
+    pub vsync: Option<bool>,
+    /// Frame rate limit while the window is not focused, 0 to not limit it [default: 10]
+    #[clap(long)]
+    pub background_fps: Option<u32>,
     /// Automatically fast-forward the scenario to the specified address (useful for debugging)
     #[clap(long, value_parser=maybe_hex::<u32>)]
     pub fast_forward_to: Option<u32>,
+    /// Pause the scenario at this address, can be repeated. The VM Debugger overlay resumes and single-steps it
+    #[clap(long, value_parser=maybe_hex::<u32>)]
+    pub break_at: Vec<u32>,
+    /// Pause the scenario when it issues a command with this name (like LAYERLOAD), can be repeated
+    #[clap(long)]
+    pub break_on: Vec<String>,
+    /// Watch the register $v<INDEX> in the VM Debugger overlay, can be repeated
+    #[clap(long, value_name = "INDEX")]
+    pub watch: Vec<u16>,
+    /// Initial state of the scenario PRNG, the same seed reproduces the same random choices [default: 42]
+    #[clap(long, value_parser=maybe_hex::<u32>)]
+    pub random_seed: Option<u32>,
+    /// Try this h264 decoder backend first when playing movies (other backends are used as a fallback)
+    ///
+    /// Can also be set with the SHIN_H264_DECODER environment variable.
+    #[clap(long)]
+    pub h264_decoder: Option<H264DecoderBackend>,
+    /// Record which parts of the scenario were executed and write the log to this file on exit
+    ///
+    /// Logs can be merged and analyzed with `sdu scenario coverage-report`.
+    #[clap(long)]
+    pub coverage_log: Option<PathBuf>,
+    /// Store the settings in this file
+    ///
+    /// Defaults to `settings.json` in the shin data directory. The settings are not persisted if there is no data directory on this platform.
+    #[clap(long)]
+    pub settings_file: Option<PathBuf>,
+    /// Store the unlocked trophies in this file
+    ///
+    /// Defaults to `achievements.json` in the shin data directory. The trophies are not persisted if there is no data directory on this platform.
+    #[clap(long)]
+    pub achievements_file: Option<PathBuf>,
+    /// Store the CGs, BGM tracks and movies unlocked by the scenario in this file
+    ///
+    /// Defaults to `unlocks.json` in the shin data directory. The unlocks are not persisted if there is no data directory on this platform.
+    #[clap(long)]
+    pub unlocks_file: Option<PathBuf>,
+    /// Store the saves in this directory
+    ///
+    /// Defaults to `saves` in the shin data directory. The game is not saved if there is no data directory on this platform.
+    #[clap(long)]
+    pub saves_dir: Option<PathBuf>,
+    /// How many autosaves are kept before the oldest one is replaced, 0 disables the autosaves [default: 5]
+    #[clap(long)]
+    pub autosave_slots: Option<u32>,
+    /// Maximum amount of memory (in MiB) kept allocated by unused render targets for reuse [default: 256]
+    #[clap(long)]
+    pub render_target_budget: Option<u64>,
+    /// Render the scene at this multiple of the window resolution [default: 1.0]
+    ///
+    /// Values above 1 supersample the scene, which reduces aliasing when the window is smaller than 1920x1080.
+    #[clap(long, value_parser = parse_render_scale)]
+    pub render_scale: Option<f32>,
+    /// How the game screen is fitted into a window that is not 16:9 [default: letterbox]
+    #[clap(long)]
+    pub aspect_mode: Option<AspectMode>,
+    /// Record the game from the start to this directory (as PNG images) or video file (encoded with ffmpeg)
+    ///
+    /// Without this flag, the recording can be toggled with F8, it's written to `capture` in the current directory by default.
+    #[clap(long)]
+    pub capture: Option<PathBuf>,
+    /// Frame rate of the recording [default: 60]
+    #[clap(long)]
+    pub capture_fps: Option<u32>,
+    /// Show a color test pattern instead of the game, to verify that the output colors are correct
+    #[clap(long, num_args = 0..=1, default_missing_value = "true")]
+    pub color_test_pattern: Option<bool>,
+    /// Render the audio mix to this WAV file instead of playing it (useful for debugging the audio timing)
+    ///
+    /// The mix is advanced by the game clock, so the timing of the sounds doesn't depend on the audio device.
+    #[clap(long)]
+    pub offline_audio: Option<PathBuf>,
+    /// Record a trace of the wgpu API calls into this directory, for reproducing the rendering issues
+    ///
+    /// Only works when shin is built with the `wgpu-trace` feature. To capture a single frame in RenderDoc instead, run the game under it and press F12.
+    #[clap(long)]
+    pub wgpu_trace: Option<PathBuf>,
+    /// Measure the CPU and GPU time of the frames, the results are shown in the overlay (F3)
+    #[clap(long, num_args = 0..=1, default_missing_value = "true")]
+    pub profiler: Option<bool>,
+    /// Write the profiler measurements to this file on exit, in the chrome trace format (open it in Perfetto)
+    #[clap(long)]
+    pub profile_trace: Option<PathBuf>,
+    /// Show the novel mode text vertically, written in columns from right to left
+    #[clap(long, num_args = 0..=1, default_missing_value = "true")]
+    pub vertical_novel_text: Option<bool>,
 }
@@ -0,0 +1,470 @@
+//! Runtime options of the engine
+//!
+//! The options are layered, each layer overriding the previous ones:
+//! 1. The defaults
+//! 2. `config.toml` next to the executable (or the file passed with `--config`)
+//! 3. The `SHIN_*` environment variables (see [`ENVIRONMENT`])
+//! 4. The command line flags
+//!
+//! `shin --print-config` prints the resulting options in the config file format, which is a good starting point for writing one.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::cli::Cli;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Default language of the engine UI (menus, settings), the game text comes from the assets
+    ///
+    /// The player can choose a different one in the settings.
+    pub language: String,
+    pub scenario: ScenarioConfig,
+    pub window: WindowConfig,
+    pub paths: PathsConfig,
+    pub saves: SavesConfig,
+    pub render: RenderConfig,
+    pub capture: CaptureConfig,
+    pub debug: DebugConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            language: "en".to_string(),
+            scenario: ScenarioConfig::default(),
+            window: WindowConfig::default(),
+            paths: PathsConfig::default(),
+            saves: SavesConfig::default(),
+            render: RenderConfig::default(),
+            capture: CaptureConfig::default(),
+            debug: DebugConfig::default(),
+        }
+    }
+}
+
+/// The scenario translations, for the releases shipping a scenario per language
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScenarioConfig {
+    /// Asset paths of the translated scenarios, by their language codes (like `en = "/main_en.snr"`)
+    ///
+    /// The player can switch between them and the original `/main.snr` in the settings.
+    /// The translations must be compiled from the same source as the original, only the strings can differ.
+    pub languages: BTreeMap<String, String>,
+}
+
+/// How the window is shown on start
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum WindowMode {
+    /// A regular window with the decorations
+    Windowed,
+    /// A window without the title bar and borders
+    Borderless,
+    /// A borderless window covering the whole monitor, without changing the video mode
+    BorderlessFullscreen,
+    /// Exclusive fullscreen in the best video mode of the monitor
+    ExclusiveFullscreen,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WindowConfig {
+    pub title: String,
+    /// PNG image used as the window icon
+    pub icon: Option<PathBuf>,
+    pub mode: WindowMode,
+    /// Size of the window when there is no remembered one, in logical pixels
+    pub width: u32,
+    pub height: u32,
+    /// Restore the size and position the window had when the game was closed (in the non-fullscreen modes)
+    pub remember_geometry: bool,
+    pub vsync: bool,
+    /// Frame rate limit while the window is not focused, 0 to not limit it
+    ///
+    /// While the window is minimized or occluded nothing is drawn, the game is only updated at this rate (or at 30 fps if it's 0).
+    pub background_fps: u32,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            title: "shin".to_string(),
+            icon: None,
+            mode: WindowMode::Windowed,
+            width: 1920,
+            height: 1080,
+            remember_geometry: true,
+            vsync: true,
+            background_fps: 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PathsConfig {
+    /// See [`crate::asset::locate_assets`] for where the assets are searched when it's not set
+    pub assets: Option<PathBuf>,
+    /// Defaults to `settings.json` in the shin data directory
+    pub settings_file: Option<PathBuf>,
+    /// Defaults to `achievements.json` in the shin data directory
+    pub achievements_file: Option<PathBuf>,
+    /// Defaults to `unlocks.json` in the shin data directory
+    pub unlocks_file: Option<PathBuf>,
+    /// Defaults to `saves` in the shin data directory
+    pub saves_dir: Option<PathBuf>,
+    /// Directory with `<language>.toml` files adding or overriding the UI translations (see [`crate::i18n`])
+    pub locales: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SavesConfig {
+    /// How many autosaves are kept, the oldest one is replaced by a new one; 0 disables the autosaves
+    pub autosave_slots: u32,
+}
+
+impl Default for SavesConfig {
+    fn default() -> Self {
+        Self { autosave_slots: 5 }
+    }
+}
+
+/// How the 16:9 game screen is fitted into a window of a different aspect ratio
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum AspectMode {
+    /// Show the whole screen with black bars on the sides
+    Letterbox,
+    /// Fill the window, cutting off the edges of the screen
+    Crop,
+}
+
+impl From<AspectMode> for shin_render::AspectMode {
+    fn from(mode: AspectMode) -> Self {
+        match mode {
+            AspectMode::Letterbox => shin_render::AspectMode::Letterbox,
+            AspectMode::Crop => shin_render::AspectMode::Crop,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RenderConfig {
+    /// Multiple of the window resolution the scene is rendered at, can be overridden in the settings
+    pub scale: f32,
+    pub aspect_mode: AspectMode,
+    /// Maximum amount of memory (in MiB) kept allocated by unused render targets for reuse
+    pub target_budget: u64,
+    /// Show the novel mode text vertically
+    pub vertical_novel_text: bool,
+}
+
+/// The smallest render scale accepted, anything lower would make the render buffer a few pixels in size
+pub const MIN_RENDER_SCALE: f32 = 0.1;
+
+/// The largest render scale accepted
+///
+/// The render buffer is resolved to the window with a single bilinear sample per pixel, so above 2x some of the rendered texels would be skipped entirely.
+pub const MAX_RENDER_SCALE: f32 = 2.0;
+
+/// Checks the render scale from the config, the command line or the settings, as zero, negative or NaN scales make an empty render buffer
+pub fn check_render_scale(scale: f32) -> Result<f32, String> {
+    if scale.is_finite() && (MIN_RENDER_SCALE..=MAX_RENDER_SCALE).contains(&scale) {
+        Ok(scale)
+    } else {
+        Err(format!(
+            "the render scale must be a number between {} and {}, got {}",
+            MIN_RENDER_SCALE, MAX_RENDER_SCALE, scale
+        ))
+    }
+}
+
+impl Default for RenderConfig {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            aspect_mode: AspectMode::Letterbox,
+            target_budget: 256,
+            vertical_novel_text: false,
+        }
+    }
+}
+
+/// Recording of the game, see [`crate::capture`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CaptureConfig {
+    /// A directory for a PNG sequence, or a video file (`.mp4`, `.mkv`, `.webm`, `.mov`) encoded with ffmpeg
+    ///
+    /// The recordings after the first one get a number appended to the name.
+    pub output: PathBuf,
+    /// Frame rate of the recording
+    pub fps: u32,
+    /// Start recording as soon as the game starts, otherwise the recording is toggled with F8
+    pub record_on_start: bool,
+}
+
+impl Default for CaptureConfig {
+    fn default() -> Self {
+        Self {
+            output: PathBuf::from("capture"),
+            fps: 60,
+            record_on_start: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DebugConfig {
+    /// Scenario address to fast-forward to on start
+    pub fast_forward_to: Option<u32>,
+    /// Scenario addresses to pause the VM at, it can be resumed from the VM Debugger overlay
+    pub break_at: Vec<u32>,
+    /// Names of the commands (like `LAYERLOAD`) to pause the VM on
+    pub break_on: Vec<String>,
+    /// Main memory registers (`$v` indices) to show in the VM Debugger overlay, highlighting the changes
+    pub watch: Vec<u16>,
+    /// Initial state of the scenario PRNG, the same seed gives the same random choices
+    pub random_seed: u32,
+    /// Write the scenario coverage log to this file on exit
+    pub coverage_log: Option<PathBuf>,
+    /// Show a color test pattern instead of the game
+    pub color_test_pattern: bool,
+    /// Render the audio mix to this WAV file instead of playing it, following the game clock
+    pub offline_audio: Option<PathBuf>,
+    /// Record a wgpu API trace into this directory, needs the `wgpu-trace` feature
+    pub wgpu_trace: Option<PathBuf>,
+    /// Measure the CPU and GPU time of the frames, shown in the overlay (see [`crate::profiler`])
+    pub profiler: bool,
+    /// Write the profiler measurements of the whole session to this chrome trace file on exit, enables the profiler
+    pub profile_trace: Option<PathBuf>,
+}
+
+impl DebugConfig {
+    /// Whether the frames are profiled
+    pub fn profiling(&self) -> bool {
+        self.profiler || self.profile_trace.is_some()
+    }
+}
+
+impl Default for DebugConfig {
+    fn default() -> Self {
+        Self {
+            fast_forward_to: None,
+            break_at: Vec::new(),
+            break_on: Vec::new(),
+            watch: Vec::new(),
+            random_seed: 42,
+            coverage_log: None,
+            color_test_pattern: false,
+            offline_audio: None,
+            wgpu_trace: None,
+            profiler: false,
+            profile_trace: None,
+        }
+    }
+}
+
+/// The environment variables overriding the config file, with the paths of the options they set
+///
+/// The values are parsed as TOML values, falling back to plain strings (so `SHIN_LANGUAGE=en` works without quotes).
+pub const ENVIRONMENT: &[(&str, &[&str])] = &[
+    ("SHIN_LANGUAGE", &["language"]),
+    ("SHIN_WINDOW_WIDTH", &["window", "width"]),
+    ("SHIN_WINDOW_HEIGHT", &["window", "height"]),
+    ("SHIN_WINDOW_MODE", &["window", "mode"]),
+    ("SHIN_VSYNC", &["window", "vsync"]),
+    ("SHIN_BACKGROUND_FPS", &["window", "background_fps"]),
+    ("SHIN_SETTINGS_FILE", &["paths", "settings_file"]),
+    ("SHIN_ACHIEVEMENTS_FILE", &["paths", "achievements_file"]),
+    ("SHIN_UNLOCKS_FILE", &["paths", "unlocks_file"]),
+    ("SHIN_SAVES_DIR", &["paths", "saves_dir"]),
+    ("SHIN_AUTOSAVE_SLOTS", &["saves", "autosave_slots"]),
+    ("SHIN_LOCALES_DIR", &["paths", "locales"]),
+    ("SHIN_RENDER_SCALE", &["render", "scale"]),
+    ("SHIN_ASPECT_MODE", &["render", "aspect_mode"]),
+    ("SHIN_RENDER_TARGET_BUDGET", &["render", "target_budget"]),
+    ("SHIN_CAPTURE_OUTPUT", &["capture", "output"]),
+    ("SHIN_CAPTURE_FPS", &["capture", "fps"]),
+    ("SHIN_RANDOM_SEED", &["debug", "random_seed"]),
+    ("SHIN_COLOR_TEST_PATTERN", &["debug", "color_test_pattern"]),
+];
+
+/// Merges the `overlay` into the `base`, the tables are merged recursively and everything else is replaced
+fn merge(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overlay)) => merge(base, overlay),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+fn parse_env_value(value: &str) -> toml::Value {
+    toml::from_str::<toml::Table>(&format!("value = {}", value))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(value.to_string()))
+}
+
+/// Sets the value at the `path`, creating the missing tables on the way
+///
+/// Fails if the path goes through a value that is not a table (e.g. `window = 1` in the config file).
+fn set_path(table: &mut toml::Table, path: &[&str], value: toml::Value) -> Result<()> {
+    let (last, parents) = path.split_last().expect("empty config path");
+    let mut table = table;
+    for (depth, &parent) in parents.iter().enumerate() {
+        table = match table
+            .entry(parent)
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+        {
+            toml::Value::Table(inner) => inner,
+            other => bail!("`{}` is {}, not a table", path[..=depth].join("."), other),
+        };
+    }
+    table.insert(last.to_string(), value);
+    Ok(())
+}
+
+fn default_config_path() -> Option<PathBuf> {
+    let exe = std::env::current_exe().ok()?;
+    Some(exe.parent()?.join("config.toml"))
+}
+
+fn read_config_file(path: &Path) -> Result<toml::Table> {
+    let config = std::fs::read_to_string(path)?;
+    toml::from_str(&config).context("Parsing the config file")
+}
+
+impl Config {
+    /// Layers the config file, the environment and the command line over the defaults
+    pub fn load(cli: &Cli) -> Result<Self> {
+        let mut table = toml::Table::try_from(Config::default()).expect("serializing the defaults");
+
+        match cli.config.clone().or_else(default_config_path) {
+            // a missing config file is fine, unless it was asked for explicitly
+            Some(path) if cli.config.is_some() || path.exists() => {
+                debug!("Reading the config from {}", path.display());
+                let file = read_config_file(&path)
+                    .with_context(|| format!("Reading {}", path.display()))?;
+                merge(&mut table, file);
+            }
+            _ => {}
+        }
+
+        for &(variable, path) in ENVIRONMENT {
+            if let Ok(value) = std::env::var(variable) {
+                debug!("Using {} from the environment", variable);
+                set_path(&mut table, path, parse_env_value(&value))
+                    .with_context(|| format!("Setting `{}` from {}", path.join("."), variable))?;
+            }
+        }
+
+        let mut config: Config = table.try_into().context(
+            "Invalid config (check the config file and the SHIN_* environment variables)",
+        )?;
+        config.apply_cli(cli);
+        check_render_scale(config.render.scale)
+            .map_err(anyhow::Error::msg)
+            .context("Invalid config (render.scale)")?;
+
+        Ok(config)
+    }
+
+    fn apply_cli(&mut self, cli: &Cli) {
+        fn set<T: Clone>(target: &mut T, value: &Option<T>) {
+            if let Some(value) = value {
+                *target = value.clone();
+            }
+        }
+        fn set_some<T: Clone>(target: &mut Option<T>, value: &Option<T>) {
+            if value.is_some() {
+                *target = value.clone();
+            }
+        }
+
+        set(&mut self.language, &cli.language);
+        set(&mut self.window.width, &cli.width);
+        set(&mut self.window.height, &cli.height);
+        set(&mut self.window.mode, &cli.window_mode);
+        set(&mut self.window.title, &cli.title);
+        set_some(&mut self.window.icon, &cli.icon);
+        set(&mut self.window.vsync, &cli.vsync);
+        set(&mut self.window.background_fps, &cli.background_fps);
+        set_some(&mut self.paths.assets, &cli.assets_dir);
+        set_some(&mut self.paths.settings_file, &cli.settings_file);
+        set_some(&mut self.paths.achievements_file, &cli.achievements_file);
+        set_some(&mut self.paths.unlocks_file, &cli.unlocks_file);
+        set_some(&mut self.paths.saves_dir, &cli.saves_dir);
+        set(&mut self.saves.autosave_slots, &cli.autosave_slots);
+        set_some(&mut self.paths.locales, &cli.locales_dir);
+        set(&mut self.render.scale, &cli.render_scale);
+        set(&mut self.render.aspect_mode, &cli.aspect_mode);
+        set(&mut self.render.target_budget, &cli.render_target_budget);
+        set(
+            &mut self.render.vertical_novel_text,
+            &cli.vertical_novel_text,
+        );
+        if let Some(output) = &cli.capture {
+            self.capture.output = output.clone();
+            self.capture.record_on_start = true;
+        }
+        set(&mut self.capture.fps, &cli.capture_fps);
+        set_some(&mut self.debug.fast_forward_to, &cli.fast_forward_to);
+        if !cli.break_at.is_empty() {
+            self.debug.break_at = cli.break_at.clone();
+        }
+        if !cli.break_on.is_empty() {
+            self.debug.break_on = cli.break_on.clone();
+        }
+        if !cli.watch.is_empty() {
+            self.debug.watch = cli.watch.clone();
+        }
+        set(&mut self.debug.random_seed, &cli.random_seed);
+        set_some(&mut self.debug.coverage_log, &cli.coverage_log);
+        set(&mut self.debug.color_test_pattern, &cli.color_test_pattern);
+        set_some(&mut self.debug.offline_audio, &cli.offline_audio);
+        set_some(&mut self.debug.wgpu_trace, &cli.wgpu_trace);
+        set(&mut self.debug.profiler, &cli.profiler);
+        set_some(&mut self.debug.profile_trace, &cli.profile_trace);
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string_pretty(self).expect("serializing the config")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::set_path;
+
+    #[test]
+    fn set_path_creates_tables() {
+        let mut table = toml::Table::new();
+        set_path(&mut table, &["window", "width"], toml::Value::Integer(1280)).unwrap();
+        assert_eq!(table["window"]["width"].as_integer(), Some(1280));
+    }
+
+    #[test]
+    fn set_path_through_a_value_fails() {
+        let mut table: toml::Table = toml::from_str("window = 1").unwrap();
+        let error = set_path(&mut table, &["window", "width"], toml::Value::Integer(1280))
+            .unwrap_err()
+            .to_string();
+        assert_eq!(error, "`window` is 1, not a table");
+    }
+}
@@ -0,0 +1,304 @@
+//! Translations of the engine-provided UI text (menus, settings, notifications).
+//!
+//! The game text comes from the scenario, this only covers the strings the engine shows by itself.
+//! Each language is a TOML file with the strings in (possibly nested) tables, addressed by the dotted keys (like `system_menu.save`).
+//! English and Japanese are built in, more languages can be added (or the built-in ones overridden) with `<language>.toml` files in the locales directory from the config.
+//!
+//! The UI language is chosen by the player in the settings, defaulting to the one from the config.
+//! The strings missing in it are looked up along a fallback chain, see [`Localizer::fallback_chain`].
+
+use std::{collections::BTreeMap, fmt::Display, path::Path, sync::Arc};
+
+use anyhow::{Context, Result};
+use tracing::{debug, warn};
+
+use crate::settings::SettingsStore;
+
+/// The language that has all the strings, the last one in all the fallback chains
+pub const BASE_LANGUAGE: &str = "en";
+
+const BUILT_IN: &[(&str, &str)] = &[
+    ("en", include_str!("en.toml")),
+    ("ja", include_str!("ja.toml")),
+];
+
+/// The strings of a single language
+#[derive(Debug, Clone, Default)]
+struct Locale {
+    /// The name of the language in itself, shown in the settings
+    name: Option<String>,
+    /// The language used for the missing strings before the default chain, like `es` for `gl`
+    fallback: Option<String>,
+    strings: BTreeMap<String, String>,
+}
+
+impl Locale {
+    fn parse(source: &str) -> Result<Self> {
+        let mut table: toml::Table = toml::from_str(source)?;
+
+        let mut locale = Locale::default();
+        if let Some(toml::Value::Table(meta)) = table.remove("locale") {
+            locale.name = meta
+                .get("name")
+                .and_then(|v| v.as_str())
+                .map(str::to_string);
+            locale.fallback = meta
+                .get("fallback")
+                .and_then(|v| v.as_str())
+                .map(str::to_string);
+        }
+        flatten(&mut locale.strings, "", table);
+
+        Ok(locale)
+    }
+
+    /// Adds the strings from the `other` locale, replacing the existing ones
+    fn merge(&mut self, other: Locale) {
+        if other.name.is_some() {
+            self.name = other.name;
+        }
+        if other.fallback.is_some() {
+            self.fallback = other.fallback;
+        }
+        self.strings.extend(other.strings);
+    }
+}
+
+fn flatten(strings: &mut BTreeMap<String, String>, prefix: &str, table: toml::Table) {
+    for (key, value) in table {
+        let key = if prefix.is_empty() {
+            key
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match value {
+            toml::Value::Table(table) => flatten(strings, &key, table),
+            toml::Value::String(value) => {
+                strings.insert(key, value);
+            }
+            value => warn!("Ignoring the non-string UI string {}: {}", key, value),
+        }
+    }
+}
+
+/// Replaces the `{name}` placeholders with the `args`, the unknown ones are left as is
+fn interpolate(template: &str, args: &[(&str, &dyn Display)]) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(index) = rest.find(['{', '}']) {
+        result.push_str(&rest[..index]);
+        rest = &rest[index..];
+
+        if rest.starts_with("{{") || rest.starts_with("}}") {
+            result.push_str(&rest[..1]);
+            rest = &rest[2..];
+            continue;
+        }
+
+        let placeholder = rest
+            .starts_with('{')
+            .then(|| rest.find('}'))
+            .flatten()
+            .and_then(|end| {
+                let name = &rest[1..end];
+                args.iter()
+                    .find(|(arg, _)| *arg == name)
+                    .map(|(_, value)| (end, value))
+            });
+        match placeholder {
+            Some((end, value)) => {
+                result.push_str(&value.to_string());
+                rest = &rest[end + 1..];
+            }
+            None => {
+                result.push_str(&rest[..1]);
+                rest = &rest[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+
+    result
+}
+
+/// Looks up the UI strings in the language chosen in the settings
+pub struct Localizer {
+    locales: BTreeMap<String, Locale>,
+    /// The language from the config, used when the player hasn't chosen one
+    default_language: String,
+    settings: Arc<SettingsStore>,
+}
+
+impl Localizer {
+    /// Loads the built-in languages and the ones from the `locales_dir`
+    pub fn new(
+        default_language: String,
+        locales_dir: Option<&Path>,
+        settings: Arc<SettingsStore>,
+    ) -> Self {
+        let mut locales = BTreeMap::new();
+        for &(language, source) in BUILT_IN {
+            let locale = Locale::parse(source).expect("parsing a built-in locale");
+            locales.insert(language.to_string(), locale);
+        }
+
+        if let Some(dir) = locales_dir {
+            if let Err(e) = Self::load_dir(&mut locales, dir) {
+                warn!("Failed to load the UI translations: {:?}", e);
+            }
+        }
+
+        let result = Self {
+            locales,
+            default_language,
+            settings,
+        };
+        if !result.languages().contains(&result.default_language) {
+            warn!(
+                "There is no UI translation for the language {:?}, falling back to {:?}",
+                result.default_language,
+                result.fallback_chain(&result.default_language)
+            );
+        }
+
+        result
+    }
+
+    fn load_dir(locales: &mut BTreeMap<String, Locale>, dir: &Path) -> Result<()> {
+        let entries =
+            std::fs::read_dir(dir).with_context(|| format!("Reading {}", dir.display()))?;
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().map_or(true, |ext| ext != "toml") {
+                continue;
+            }
+            let Some(language) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+
+            let source = std::fs::read_to_string(&path)
+                .with_context(|| format!("Reading {}", path.display()))?;
+            let locale =
+                Locale::parse(&source).with_context(|| format!("Parsing {}", path.display()))?;
+            debug!(
+                "Loaded {} UI strings for {:?} from {}",
+                locale.strings.len(),
+                language,
+                path.display()
+            );
+            locales
+                .entry(language.to_string())
+                .or_default()
+                .merge(locale);
+        }
+
+        Ok(())
+    }
+
+    /// All the languages that have translations, sorted by their codes
+    pub fn languages(&self) -> Vec<String> {
+        self.locales.keys().cloned().collect()
+    }
+
+    /// The name of the `language` in itself (like "日本語" for `ja`), or its code if it's not known
+    pub fn language_name(&self, language: &str) -> String {
+        self.locales
+            .get(language)
+            .and_then(|locale| locale.name.clone())
+            .unwrap_or_else(|| language.to_string())
+    }
+
+    /// The language chosen by the player, or the default one
+    pub fn language(&self) -> String {
+        self.settings
+            .get()
+            .language
+            .clone()
+            .unwrap_or_else(|| self.default_language.clone())
+    }
+
+    /// The languages the strings are looked up in, in order
+    ///
+    /// Each language is followed by its explicit fallback (if the locale file has one) or by the language with the last subtag removed (`pt-BR` is followed by `pt`).
+    /// After that come the default language chain and [`BASE_LANGUAGE`].
+    pub fn fallback_chain(&self, language: &str) -> Vec<String> {
+        let mut chain = Vec::new();
+        for start in [language, self.default_language.as_str(), BASE_LANGUAGE] {
+            let mut next = Some(start.to_string());
+            while let Some(language) = next.take() {
+                // stop on cycles made by the explicit fallbacks
+                if chain.contains(&language) {
+                    break;
+                }
+                next = self
+                    .locales
+                    .get(&language)
+                    .and_then(|locale| locale.fallback.clone())
+                    .or_else(|| language.rsplit_once('-').map(|(base, _)| base.to_string()));
+                chain.push(language);
+            }
+        }
+        chain
+    }
+
+    fn lookup(&self, key: &str) -> Option<&str> {
+        self.fallback_chain(&self.language())
+            .iter()
+            .filter_map(|language| self.locales.get(language))
+            .find_map(|locale| locale.strings.get(key))
+            .map(String::as_str)
+    }
+
+    /// The string with the `key` in the current language
+    ///
+    /// The key itself is returned if no language has it, so that the missing strings are easy to spot.
+    pub fn tr(&self, key: &str) -> String {
+        self.tr_args(key, &[])
+    }
+
+    /// Same as [`Localizer::tr`], with the `{name}` placeholders replaced by the `args`
+    pub fn tr_args(&self, key: &str, args: &[(&str, &dyn Display)]) -> String {
+        match self.lookup(key) {
+            Some(template) => interpolate(template, args),
+            None => {
+                warn!("Missing the UI string {:?}", key);
+                key.to_string()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interpolate() {
+        assert_eq!(
+            interpolate("Trophy {id} of {count}", &[("id", &3), ("count", &"many")]),
+            "Trophy 3 of many"
+        );
+        assert_eq!(
+            interpolate("{{id}} {unknown} }", &[("id", &3)]),
+            "{id} {unknown} }"
+        );
+    }
+
+    #[test]
+    fn test_fallback_chain() {
+        let mut localizer =
+            Localizer::new("ja".to_string(), None, Arc::new(SettingsStore::load(None)));
+        localizer.locales.insert(
+            "gl".to_string(),
+            Locale::parse("[locale]\nfallback = \"pt-PT\"").unwrap(),
+        );
+
+        assert_eq!(localizer.fallback_chain("en"), ["en", "ja"]);
+        assert_eq!(
+            localizer.fallback_chain("gl-ES"),
+            ["gl-ES", "gl", "pt-PT", "pt", "ja", "en"]
+        );
+        assert_eq!(localizer.language(), "ja");
+        assert_eq!(localizer.tr("system_menu.save"), "セーブ");
+    }
+}
@@ -89,6 +89,10 @@ where
         }
     }
 
+    pub fn action_map(&self) -> &ActionMap<T> {
+        &self.action_map
+    }
+
     pub fn is_just_pressed(&self, action: T) -> bool {
         self.action_data[action].state.just_pressed()
     }
@@ -138,6 +142,10 @@ where
         Self { action_map }
     }
 
+    pub fn inputs(&self, action: A) -> &InputSet {
+        &self.action_map[action]
+    }
+
     pub fn which_pressed(&self, input_state: &RawInputState) -> EnumMap<A, Option<f32>> {
         self.action_map.clone().map(|_action, inputs| {
             inputs
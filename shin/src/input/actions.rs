@@ -1,7 +1,7 @@
 use enum_map::{enum_map, Enum};
 
 use crate::input::{
-    inputs::{KeyCode, MouseButton},
+    inputs::{GamepadButtonType, KeyCode, MouseButton},
     Action, ActionMap, InputSet,
 };
 
@@ -14,6 +14,9 @@ pub enum AdvMessageAction {
     HoldFastForward,
     Backlog,
     Rollback,
+    SystemMenu,
+    QuickSave,
+    QuickLoad,
 }
 
 impl Action for AdvMessageAction {
@@ -24,14 +27,96 @@ impl Action for AdvMessageAction {
                     MouseButton::Left.into(),
                     KeyCode::Enter.into(),
                     KeyCode::Space.into(),
+                    GamepadButtonType::South.into(),
+                ]
+                .into_iter()
+                .collect(),
+                AdvMessageAction::HoldFastForward => [
+                    KeyCode::ControlLeft.into(),
+                    GamepadButtonType::RightTrigger2.into(),
                 ]
                 .into_iter()
                 .collect(),
-                AdvMessageAction::HoldFastForward => {
-                    [KeyCode::ControlLeft.into()].into_iter().collect()
-                }
                 AdvMessageAction::Backlog => [].into_iter().collect(),
-                AdvMessageAction::Rollback => [].into_iter().collect(),
+                AdvMessageAction::Rollback => [
+                    KeyCode::PageUp.into(),
+                    KeyCode::ArrowUp.into(),
+                    GamepadButtonType::DPadUp.into(),
+                ]
+                .into_iter()
+                .collect(),
+                AdvMessageAction::SystemMenu => [
+                    MouseButton::Right.into(),
+                    KeyCode::Escape.into(),
+                    GamepadButtonType::Start.into(),
+                ]
+                .into_iter()
+                .collect(),
+                AdvMessageAction::QuickSave => [KeyCode::F5.into()].into_iter().collect(),
+                AdvMessageAction::QuickLoad => [KeyCode::F6.into()].into_iter().collect(),
+            }
+        }
+
+        ActionMap::new(enum_map! { v => map(v) })
+    }
+}
+
+/// Actions available in the menus
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Enum)]
+pub enum MenuAction {
+    Up,
+    Down,
+    Left,
+    Right,
+    Activate,
+    Cancel,
+}
+
+impl Action for MenuAction {
+    fn default_action_map() -> ActionMap<Self> {
+        fn map(v: MenuAction) -> InputSet {
+            match v {
+                MenuAction::Up => [
+                    KeyCode::ArrowUp.into(),
+                    MouseButton::WheelUp.into(),
+                    GamepadButtonType::DPadUp.into(),
+                ]
+                .into_iter()
+                .collect(),
+                MenuAction::Down => [
+                    KeyCode::ArrowDown.into(),
+                    MouseButton::WheelDown.into(),
+                    GamepadButtonType::DPadDown.into(),
+                ]
+                .into_iter()
+                .collect(),
+                MenuAction::Left => [
+                    KeyCode::ArrowLeft.into(),
+                    GamepadButtonType::DPadLeft.into(),
+                ]
+                .into_iter()
+                .collect(),
+                MenuAction::Right => [
+                    KeyCode::ArrowRight.into(),
+                    GamepadButtonType::DPadRight.into(),
+                ]
+                .into_iter()
+                .collect(),
+                MenuAction::Activate => [
+                    MouseButton::Left.into(),
+                    KeyCode::Enter.into(),
+                    KeyCode::Space.into(),
+                    GamepadButtonType::South.into(),
+                ]
+                .into_iter()
+                .collect(),
+                MenuAction::Cancel => [
+                    MouseButton::Right.into(),
+                    KeyCode::Escape.into(),
+                    GamepadButtonType::East.into(),
+                ]
+                .into_iter()
+                .collect(),
             }
         }
 
@@ -0,0 +1,139 @@
+//! Gamepad input with gilrs
+//!
+//! All the connected gamepads are merged into the [`GamepadState`] of the [`RawInputState`], like the keyboards and the mice are merged by the OS.
+//! The kind of the gamepad used last selects the button prompts.
+
+use gilrs::{Axis, Button, Event, EventType, Gilrs};
+use tracing::{info, warn};
+
+use crate::input::{
+    inputs::{GamepadAxisType, GamepadButtonType},
+    prompts::{GamepadKind, InputDevice},
+    raw_input_state::GamepadState,
+    RawInputState,
+};
+
+/// The stick tilt that switches the button prompts to the gamepad, above the drift of a resting stick
+const ACTIVE_AXIS_THRESHOLD: f32 = 0.5;
+
+pub struct Gamepads {
+    /// `None` if the gamepads are not supported on this platform
+    gilrs: Option<Gilrs>,
+    /// Steam sets the `SteamDeck` variable when running on one
+    on_steam_deck: bool,
+}
+
+impl Gamepads {
+    pub fn new() -> Self {
+        let gilrs = match Gilrs::new() {
+            Ok(gilrs) => {
+                for (_, gamepad) in gilrs.gamepads() {
+                    info!("Gamepad connected: {}", gamepad.name());
+                }
+                Some(gilrs)
+            }
+            Err(e) => {
+                warn!("Gamepad input is not available: {}", e);
+                None
+            }
+        };
+
+        Self {
+            gilrs,
+            on_steam_deck: std::env::var_os("SteamDeck").is_some_and(|v| v == "1"),
+        }
+    }
+
+    /// Applies the gamepad events received since the last call to the `state`
+    pub fn poll(&mut self, state: &mut RawInputState) {
+        let Some(gilrs) = &mut self.gilrs else {
+            return;
+        };
+
+        while let Some(Event { id, event, .. }) = gilrs.next_event() {
+            let used = match event {
+                EventType::ButtonPressed(..) => true,
+                EventType::AxisChanged(_, value, _) => value.abs() > ACTIVE_AXIS_THRESHOLD,
+                _ => false,
+            };
+            if used {
+                let gamepad = gilrs.gamepad(id);
+                state.active_device = InputDevice::Gamepad(GamepadKind::detect(
+                    gamepad.vendor_id(),
+                    gamepad.product_id(),
+                    gamepad.name(),
+                    self.on_steam_deck,
+                ));
+            }
+
+            match event {
+                EventType::ButtonPressed(button, _) => {
+                    if let Some(button) = convert_gilrs_button(button) {
+                        state.gamepad.buttons[button] = true;
+                    }
+                }
+                EventType::ButtonReleased(button, _) => {
+                    if let Some(button) = convert_gilrs_button(button) {
+                        state.gamepad.buttons[button] = false;
+                    }
+                }
+                EventType::AxisChanged(axis, value, _) => {
+                    if let Some(axis) = convert_gilrs_axis(axis) {
+                        state.gamepad.axes[axis] = value;
+                    }
+                }
+                EventType::Connected => {
+                    info!("Gamepad connected: {}", gilrs.gamepad(id).name());
+                }
+                EventType::Disconnected => {
+                    info!("Gamepad disconnected: {}", gilrs.gamepad(id).name());
+                    // the buttons held on it would stay pressed otherwise
+                    state.gamepad = GamepadState::new();
+                }
+                _ => {
+                    // don't care about other events
+                }
+            }
+        }
+    }
+}
+
+#[inline]
+fn convert_gilrs_button(button: Button) -> Option<GamepadButtonType> {
+    Some(match button {
+        Button::South => GamepadButtonType::South,
+        Button::East => GamepadButtonType::East,
+        Button::North => GamepadButtonType::North,
+        Button::West => GamepadButtonType::West,
+        Button::C => GamepadButtonType::C,
+        Button::Z => GamepadButtonType::Z,
+        Button::LeftTrigger => GamepadButtonType::LeftTrigger,
+        Button::LeftTrigger2 => GamepadButtonType::LeftTrigger2,
+        Button::RightTrigger => GamepadButtonType::RightTrigger,
+        Button::RightTrigger2 => GamepadButtonType::RightTrigger2,
+        Button::Select => GamepadButtonType::Select,
+        Button::Start => GamepadButtonType::Start,
+        Button::Mode => GamepadButtonType::Mode,
+        Button::LeftThumb => GamepadButtonType::LeftThumb,
+        Button::RightThumb => GamepadButtonType::RightThumb,
+        Button::DPadUp => GamepadButtonType::DPadUp,
+        Button::DPadDown => GamepadButtonType::DPadDown,
+        Button::DPadLeft => GamepadButtonType::DPadLeft,
+        Button::DPadRight => GamepadButtonType::DPadRight,
+        Button::Unknown => return None,
+    })
+}
+
+#[inline]
+fn convert_gilrs_axis(axis: Axis) -> Option<GamepadAxisType> {
+    Some(match axis {
+        Axis::LeftStickX => GamepadAxisType::LeftStickX,
+        Axis::LeftStickY => GamepadAxisType::LeftStickY,
+        Axis::LeftZ => GamepadAxisType::LeftZ,
+        Axis::RightStickX => GamepadAxisType::RightStickX,
+        Axis::RightStickY => GamepadAxisType::RightStickY,
+        Axis::RightZ => GamepadAxisType::RightZ,
+        // the d-pad is handled as buttons
+        Axis::DPadX | Axis::DPadY | Axis::Unknown => return None,
+    })
+}
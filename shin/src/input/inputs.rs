@@ -1,8 +1,7 @@
 use enum_map::Enum;
 pub use winit::keyboard::KeyCode;
 
-#[allow(unused)] // It will be used... eventually
-#[derive(Debug, Hash, Ord, PartialOrd, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, Hash, Ord, PartialOrd, PartialEq, Eq, Clone, Copy, Enum)]
 pub enum GamepadAxisType {
     LeftStickX,
     LeftStickY,
@@ -13,8 +12,7 @@ pub enum GamepadAxisType {
     // Other(u8),
 }
 
-#[allow(unused)] // It will be used... eventually
-#[derive(Debug, Hash, Ord, PartialOrd, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, Hash, Ord, PartialOrd, PartialEq, Eq, Clone, Copy, Enum)]
 pub enum GamepadButtonType {
     South,
     East,
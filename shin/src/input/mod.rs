@@ -16,10 +16,15 @@ pub mod inputs;
 // The Shiny New Input System
 mod action;
 pub mod actions;
+mod gamepad;
+pub mod prompts;
 mod raw_input_state;
+mod virtual_cursor;
 
 pub use action::{Action, ActionMap, ActionState, InputSet, UserInput};
+pub use gamepad::Gamepads;
 pub use raw_input_state::RawInputState;
+pub use virtual_cursor::VirtualCursor;
 
 // Importing the derive macro
 // pub use leafwing_input_manager_macros::Actionlike;
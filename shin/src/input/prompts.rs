@@ -0,0 +1,259 @@
+//! Button prompts: the labels of the inputs bound to an action, to be shown in the UI (like "Press A to continue").
+//!
+//! The prompts follow the device the player used last, stored in [`RawInputState::active_device`](crate::input::RawInputState::active_device).
+//! The buttons are handled by their position (the south button is "A" on an Xbox controller and "×" on a PlayStation one),
+//! so a gamepad's labels depend on its kind, which is guessed from the USB ids and the name reported by gilrs.
+
+use std::borrow::Cow;
+
+use crate::input::{
+    inputs::{GamepadButtonType, KeyCode, MouseButton},
+    Action, ActionMap, UserInput,
+};
+
+const VENDOR_MICROSOFT: u16 = 0x045e;
+const VENDOR_SONY: u16 = 0x054c;
+const VENDOR_NINTENDO: u16 = 0x057e;
+const VENDOR_VALVE: u16 = 0x28de;
+const PRODUCT_STEAM_DECK: u16 = 0x1205;
+
+/// The button labels printed on a gamepad
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GamepadKind {
+    /// Also used for the gamepads that are not recognized, as most of them copy the Xbox layout
+    Xbox,
+    PlayStation,
+    /// The Switch Pro controller and the Joy-Cons, with A and B (and X and Y) swapped compared to Xbox
+    Nintendo,
+    SteamDeck,
+}
+
+impl GamepadKind {
+    /// Guesses the kind from the gamepad ids, falling back to the name
+    ///
+    /// Steam Input presents all the gamepads as a virtual Valve one, so `on_steam_deck` tells whether it's the built-in controls of a Steam Deck.
+    pub fn detect(
+        vendor_id: Option<u16>,
+        product_id: Option<u16>,
+        name: &str,
+        on_steam_deck: bool,
+    ) -> Self {
+        match vendor_id {
+            Some(VENDOR_MICROSOFT) => return GamepadKind::Xbox,
+            Some(VENDOR_SONY) => return GamepadKind::PlayStation,
+            Some(VENDOR_NINTENDO) => return GamepadKind::Nintendo,
+            Some(VENDOR_VALVE) if product_id == Some(PRODUCT_STEAM_DECK) || on_steam_deck => {
+                return GamepadKind::SteamDeck
+            }
+            _ => {}
+        }
+
+        let name = name.to_lowercase();
+        let name_has = |words: &[&str]| words.iter().any(|word| name.contains(word));
+        if name_has(&["steam deck"]) {
+            GamepadKind::SteamDeck
+        } else if name_has(&["playstation", "dualshock", "dualsense", "ps3", "ps4", "ps5"]) {
+            GamepadKind::PlayStation
+        } else if name_has(&["nintendo", "switch", "joy-con", "pro controller"]) {
+            GamepadKind::Nintendo
+        } else {
+            GamepadKind::Xbox
+        }
+    }
+
+    pub fn button_label(self, button: GamepadButtonType) -> &'static str {
+        use GamepadButtonType::*;
+        use GamepadKind::*;
+
+        match (self, button) {
+            (Xbox | SteamDeck, South) => "A",
+            (Xbox | SteamDeck, East) => "B",
+            (Xbox | SteamDeck, West) => "X",
+            (Xbox | SteamDeck, North) => "Y",
+            (PlayStation, South) => "×",
+            (PlayStation, East) => "○",
+            (PlayStation, West) => "□",
+            (PlayStation, North) => "△",
+            (Nintendo, South) => "B",
+            (Nintendo, East) => "A",
+            (Nintendo, West) => "Y",
+            (Nintendo, North) => "X",
+
+            (Xbox, LeftTrigger) => "LB",
+            (Xbox, RightTrigger) => "RB",
+            (Xbox, LeftTrigger2) => "LT",
+            (Xbox, RightTrigger2) => "RT",
+            (PlayStation | SteamDeck, LeftTrigger) => "L1",
+            (PlayStation | SteamDeck, RightTrigger) => "R1",
+            (PlayStation | SteamDeck, LeftTrigger2) => "L2",
+            (PlayStation | SteamDeck, RightTrigger2) => "R2",
+            (Nintendo, LeftTrigger) => "L",
+            (Nintendo, RightTrigger) => "R",
+            (Nintendo, LeftTrigger2) => "ZL",
+            (Nintendo, RightTrigger2) => "ZR",
+
+            (Xbox | SteamDeck, Select) => "View",
+            (Xbox | SteamDeck, Start) => "Menu",
+            (PlayStation, Select) => "Create",
+            (PlayStation, Start) => "Options",
+            (Nintendo, Select) => "−",
+            (Nintendo, Start) => "+",
+
+            (Xbox, Mode) => "Xbox",
+            (PlayStation, Mode) => "PS",
+            (Nintendo, Mode) => "HOME",
+            (SteamDeck, Mode) => "STEAM",
+
+            (PlayStation, LeftThumb) => "L3",
+            (PlayStation, RightThumb) => "R3",
+            (_, LeftThumb) => "LS",
+            (_, RightThumb) => "RS",
+
+            (_, C) => "C",
+            (_, Z) => "Z",
+            (_, DPadUp) => "↑",
+            (_, DPadDown) => "↓",
+            (_, DPadLeft) => "←",
+            (_, DPadRight) => "→",
+        }
+    }
+}
+
+/// The device the prompts are shown for
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum InputDevice {
+    KeyboardMouse,
+    Gamepad(GamepadKind),
+}
+
+impl InputDevice {
+    /// The label of the input bound to the `action` on this device, `None` if it's not bound to anything there
+    ///
+    /// With the keyboard and mouse, the keys are preferred, as the mouse buttons are mostly bound to the things you click anyway.
+    pub fn prompt<A: Action>(
+        self,
+        action_map: &ActionMap<A>,
+        action: A,
+    ) -> Option<Cow<'static, str>> {
+        let inputs = action_map.inputs(action);
+        match self {
+            InputDevice::KeyboardMouse => inputs
+                .iter()
+                .find_map(|input| match input {
+                    UserInput::Keyboard(key) => Some(key_label(*key)),
+                    _ => None,
+                })
+                .or_else(|| {
+                    inputs.iter().find_map(|input| match input {
+                        UserInput::MouseButton(button) => Some(mouse_label(*button).into()),
+                        _ => None,
+                    })
+                }),
+            InputDevice::Gamepad(kind) => inputs.iter().find_map(|input| match input {
+                UserInput::GamepadButton(button) => Some(kind.button_label(*button).into()),
+                _ => None,
+            }),
+        }
+    }
+}
+
+fn key_label(key: KeyCode) -> Cow<'static, str> {
+    match key {
+        KeyCode::ArrowUp => "↑".into(),
+        KeyCode::ArrowDown => "↓".into(),
+        KeyCode::ArrowLeft => "←".into(),
+        KeyCode::ArrowRight => "→".into(),
+        KeyCode::Escape => "Esc".into(),
+        KeyCode::PageUp => "PgUp".into(),
+        KeyCode::PageDown => "PgDn".into(),
+        KeyCode::ControlLeft | KeyCode::ControlRight => "Ctrl".into(),
+        KeyCode::ShiftLeft | KeyCode::ShiftRight => "Shift".into(),
+        KeyCode::AltLeft | KeyCode::AltRight => "Alt".into(),
+        key => {
+            // the rest is named well enough by the variant names, like `KeyA`, `Digit1` or `Enter`
+            let name = format!("{:?}", key);
+            match name
+                .strip_prefix("Key")
+                .or_else(|| name.strip_prefix("Digit"))
+            {
+                Some(name) => name.to_string().into(),
+                None => name.into(),
+            }
+        }
+    }
+}
+
+fn mouse_label(button: MouseButton) -> &'static str {
+    match button {
+        MouseButton::Left => "Left Click",
+        MouseButton::Right => "Right Click",
+        MouseButton::Middle => "Middle Click",
+        MouseButton::WheelUp => "Wheel ↑",
+        MouseButton::WheelDown => "Wheel ↓",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::actions::MenuAction;
+
+    #[test]
+    fn detect_gamepad_kind() {
+        let detect = |vendor, product, name| GamepadKind::detect(vendor, product, name, false);
+
+        assert_eq!(
+            detect(Some(VENDOR_SONY), Some(0x0ce6), "Wireless Controller"),
+            GamepadKind::PlayStation
+        );
+        assert_eq!(
+            detect(Some(VENDOR_VALVE), Some(PRODUCT_STEAM_DECK), "Steam Deck"),
+            GamepadKind::SteamDeck
+        );
+        // the virtual Steam Input gamepad
+        assert_eq!(
+            detect(Some(VENDOR_VALVE), Some(0x11ff), "Steam Virtual Gamepad"),
+            GamepadKind::Xbox
+        );
+        assert_eq!(
+            GamepadKind::detect(
+                Some(VENDOR_VALVE),
+                Some(0x11ff),
+                "Steam Virtual Gamepad",
+                true
+            ),
+            GamepadKind::SteamDeck
+        );
+        assert_eq!(
+            detect(None, None, "Nintendo Switch Pro Controller"),
+            GamepadKind::Nintendo
+        );
+        assert_eq!(detect(None, None, "Some Gamepad"), GamepadKind::Xbox);
+    }
+
+    #[test]
+    fn prompts() {
+        let map = MenuAction::default_action_map();
+
+        assert_eq!(
+            InputDevice::KeyboardMouse
+                .prompt(&map, MenuAction::Activate)
+                .as_deref(),
+            Some("Enter")
+        );
+        assert_eq!(
+            InputDevice::Gamepad(GamepadKind::Xbox)
+                .prompt(&map, MenuAction::Activate)
+                .as_deref(),
+            Some("A")
+        );
+        assert_eq!(
+            InputDevice::Gamepad(GamepadKind::Nintendo)
+                .prompt(&map, MenuAction::Cancel)
+                .as_deref(),
+            Some("A")
+        );
+        assert_eq!(key_label(KeyCode::KeyQ), "Q");
+        assert_eq!(key_label(KeyCode::F5), "F5");
+    }
+}
@@ -10,22 +10,46 @@ use winit::{
 };
 
 use crate::{
-    input::{action::UserInput, inputs::MouseButton},
+    input::{
+        action::UserInput,
+        inputs::{GamepadAxisType, GamepadButtonType, MouseButton},
+        prompts::InputDevice,
+    },
     render::overlay::OverlayVisitable,
 };
 
+/// The buttons and the axes of all the connected gamepads, merged together
+#[derive(Clone)]
+pub struct GamepadState {
+    pub buttons: EnumMap<GamepadButtonType, bool>,
+    /// In the `-1.0..=1.0` range, the Y axes of the sticks point up
+    pub axes: EnumMap<GamepadAxisType, f32>,
+}
+
+impl GamepadState {
+    pub fn new() -> Self {
+        Self {
+            buttons: enum_map! { _ => false },
+            axes: enum_map! { _ => 0.0 },
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct RawInputState {
     /// Keyboard state, set of pressed keys
     pub keyboard: PetitSet<KeyCode, 16>,
     /// Mouse buttons state, simple state of each button
     pub mouse_buttons: EnumMap<MouseButton, bool>,
+    /// Mouse position in the window, in physical pixels
     pub mouse_position: Vec2,
+    /// Mouse position in the virtual screen coordinates (see [`shin_render::Camera::window_to_virtual`]), set by the window before the update
+    pub virtual_mouse_position: Vec2,
     pub mouse_scroll_amount: f32,
-    #[allow(unused)] // TODO: implement gamepad input
-    gamepad: (),
-    // TODO: mouse position?
-    // How do we even handle mouse position?
+    /// Set by [`Gamepads::poll`](crate::input::Gamepads::poll)
+    pub gamepad: GamepadState,
+    /// The device the player used last, to show the button prompts for
+    pub active_device: InputDevice,
 }
 
 impl RawInputState {
@@ -34,8 +58,10 @@ impl RawInputState {
             keyboard: PetitSet::new(),
             mouse_buttons: enum_map! { _ => false },
             mouse_position: vec2(0.0, 0.0),
+            virtual_mouse_position: vec2(0.0, 0.0),
             mouse_scroll_amount: 0.0,
-            gamepad: (),
+            gamepad: GamepadState::new(),
+            active_device: InputDevice::KeyboardMouse,
         }
     }
 
@@ -44,7 +70,7 @@ impl RawInputState {
         match input {
             UserInput::Keyboard(key_code) => self.keyboard.contains(key_code).then_some(1.0),
             UserInput::MouseButton(button) => self.mouse_buttons[*button].then_some(1.0),
-            UserInput::GamepadButton(_) => None,
+            UserInput::GamepadButton(button) => self.gamepad.buttons[*button].then_some(1.0),
         }
     }
 
@@ -57,6 +83,7 @@ impl RawInputState {
                     match event.state {
                         ElementState::Pressed => {
                             self.keyboard.insert(keycode);
+                            self.active_device = InputDevice::KeyboardMouse;
                         }
                         ElementState::Released => {
                             self.keyboard.remove(&keycode);
@@ -81,11 +108,15 @@ impl RawInputState {
                     self.mouse_buttons[MouseButton::WheelDown] = true;
                 }
                 self.mouse_scroll_amount = amount;
+                self.active_device = InputDevice::KeyboardMouse;
             }
             &WindowEvent::MouseInput { button, state, .. } => {
                 if let Some(button) = convert_winit_mouse_button(button) {
                     self.mouse_buttons[button] = match state {
-                        ElementState::Pressed => true,
+                        ElementState::Pressed => {
+                            self.active_device = InputDevice::KeyboardMouse;
+                            true
+                        }
                         ElementState::Released => false,
                     }
                 }
@@ -120,6 +151,15 @@ impl Display for RawInputState {
                 .filter_map(|(but, state)| state.then(|| format!("{:?}", but)))
                 .join(", ")
         )?;
+        writeln!(
+            f,
+            "  gamepad_buttons: [{}]",
+            self.gamepad
+                .buttons
+                .iter()
+                .filter_map(|(but, state)| state.then(|| format!("{:?}", but)))
+                .join(", ")
+        )?;
         writeln!(f, "}}")?;
         Ok(())
     }
@@ -131,12 +171,19 @@ impl OverlayVisitable for RawInputState {
             "Input State",
             |_ctx, top_left| {
                 top_left.label(format!(
-                    "Input State: [{}] [{}]",
+                    "Input State: [{}] [{}] [{}] ({:.0}, {:.0})",
                     self.mouse_buttons
                         .iter()
                         .filter_map(|(but, state)| state.then(|| format!("{:?}", but)))
                         .join(", "),
-                    self.keyboard.iter().map(|v| format!("{:?}", v)).join(", ")
+                    self.keyboard.iter().map(|v| format!("{:?}", v)).join(", "),
+                    self.gamepad
+                        .buttons
+                        .iter()
+                        .filter_map(|(but, state)| state.then(|| format!("{:?}", but)))
+                        .join(", "),
+                    self.virtual_mouse_position.x,
+                    self.virtual_mouse_position.y,
                 ));
             },
             true,
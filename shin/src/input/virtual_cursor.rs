@@ -0,0 +1,84 @@
+//! A cursor moved with the left stick, for pointing at things (like the sliders) with a gamepad.
+//!
+//! While it's used, it takes over the virtual mouse position, and the south button acts as the left mouse button.
+//! This way the screens handle it just like the mouse. Moving the real mouse hides it.
+
+use glam::{vec2, Vec2};
+use shin_render::{VIRTUAL_HEIGHT, VIRTUAL_WIDTH};
+
+use crate::input::{
+    inputs::{GamepadAxisType, GamepadButtonType, MouseButton},
+    RawInputState,
+};
+
+/// The stick tilt below this is ignored, as the sticks rarely rest at exactly zero
+const DEADZONE: f32 = 0.15;
+/// The speed at the full tilt, in virtual pixels per second, when the stick is just tilted...
+const MIN_SPEED: f32 = 400.0;
+/// ...and after it's held for [`ACCELERATION_TIME`] seconds
+const MAX_SPEED: f32 = 1600.0;
+const ACCELERATION_TIME: f32 = 0.8;
+
+pub struct VirtualCursor {
+    /// In the virtual screen coordinates, `None` while the real mouse is used
+    position: Option<Vec2>,
+    /// To notice the real mouse moving
+    last_mouse_position: Vec2,
+    /// How long the stick has been tilted
+    held_time: f32,
+}
+
+impl VirtualCursor {
+    pub fn new() -> Self {
+        Self {
+            position: None,
+            last_mouse_position: vec2(0.0, 0.0),
+            held_time: 0.0,
+        }
+    }
+
+    /// The position to draw the cursor at, if it's shown
+    pub fn position(&self) -> Option<Vec2> {
+        self.position
+    }
+
+    /// Moves the cursor with the stick and applies it to the `input`
+    ///
+    /// Should be called after the virtual mouse position of the `input` is set.
+    pub fn update(&mut self, input: &mut RawInputState, delta_seconds: f32) {
+        if input.mouse_position != self.last_mouse_position {
+            self.last_mouse_position = input.mouse_position;
+            self.position = None;
+        }
+
+        let stick = vec2(
+            input.gamepad.axes[GamepadAxisType::LeftStickX],
+            -input.gamepad.axes[GamepadAxisType::LeftStickY],
+        );
+        let magnitude = stick.length();
+        if magnitude > DEADZONE {
+            self.held_time += delta_seconds;
+
+            // rescaled, so that the cursor starts moving slowly at the edge of the deadzone
+            let tilt = ((magnitude - DEADZONE) / (1.0 - DEADZONE)).min(1.0);
+            let acceleration = (self.held_time / ACCELERATION_TIME).min(1.0);
+            let speed = MIN_SPEED + (MAX_SPEED - MIN_SPEED) * acceleration;
+            // squaring the tilt makes the small movements more precise
+            let velocity = stick / magnitude * tilt * tilt * speed;
+
+            let half_screen = vec2(VIRTUAL_WIDTH, VIRTUAL_HEIGHT) / 2.0;
+            let position = self.position.unwrap_or(input.virtual_mouse_position);
+            self.position =
+                Some((position + velocity * delta_seconds).clamp(-half_screen, half_screen));
+        } else {
+            self.held_time = 0.0;
+        }
+
+        if let Some(position) = self.position {
+            input.virtual_mouse_position = position;
+            if input.gamepad.buttons[GamepadButtonType::South] {
+                input.mouse_buttons[MouseButton::Left] = true;
+            }
+        }
+    }
+}
@@ -1,19 +1,30 @@
-use std::{fmt::Debug, sync::Arc};
+use std::{fmt::Debug, sync::Arc, time::Duration};
 
 use glam::Mat4;
 use shin_render::{GpuCommonResources, GpuImage, Renderable};
+use tracing::warn;
 
 use crate::{
     asset::bustup::Bustup,
+    audio::LipSync,
     layer::{Layer, LayerProperties},
     update::{Updatable, UpdateContext},
 };
 
+/// How often the mouth can change its shape, the original game also animates the mouths at a low frame rate
+const MOUTH_FRAME_INTERVAL: Duration = Duration::from_millis(66);
+
 pub struct BustupLayer {
     bustup: Arc<Bustup>,
     bustup_name: Option<String>,
     emotion: String,
 
+    lipsync_character_id: u16,
+    lip_sync: LipSync,
+    /// The openness of the mouth being displayed, updated every [`MOUTH_FRAME_INTERVAL`]
+    mouth_openness: f32,
+    since_mouth_change: Duration,
+
     properties: LayerProperties,
 }
 
@@ -23,14 +34,24 @@ impl BustupLayer {
         bustup: Arc<Bustup>,
         bustup_name: Option<String>,
         emotion: &str,
+        lipsync_character_id: u16,
+        lip_sync: LipSync,
     ) -> Self {
         // ensure the picture is loaded to gpu
         bustup.base_gpu_image(resources);
+        if !bustup.has_emotion(emotion) {
+            // only the base picture will be drawn
+            warn!("No emotion {:?} in bustup {:?}", emotion, bustup_name);
+        }
 
         Self {
             bustup,
             bustup_name,
             emotion: emotion.to_owned(),
+            lipsync_character_id,
+            lip_sync,
+            mouth_openness: 0.0,
+            since_mouth_change: Duration::ZERO,
             properties: LayerProperties::new(),
         }
     }
@@ -64,7 +85,10 @@ impl Renderable for BustupLayer {
             draw_image(emotion_gpu_image);
         }
 
-        if let Some(mouth_gpu_image) = self.bustup.mouth_gpu_image(resources, &self.emotion, 0.0) {
+        if let Some(mouth_gpu_image) =
+            self.bustup
+                .mouth_gpu_image(resources, &self.emotion, self.mouth_openness)
+        {
             draw_image(mouth_gpu_image);
         }
     }
@@ -77,6 +101,12 @@ impl Renderable for BustupLayer {
 impl Updatable for BustupLayer {
     fn update(&mut self, ctx: &UpdateContext) {
         self.properties.update(ctx);
+
+        self.since_mouth_change += ctx.time_delta();
+        if self.since_mouth_change >= MOUTH_FRAME_INTERVAL {
+            self.since_mouth_change = Duration::ZERO;
+            self.mouth_openness = self.lip_sync.mouth_openness(self.lipsync_character_id);
+        }
     }
 }
 
@@ -1,36 +1,171 @@
+use std::sync::Arc;
+
 use bevy_utils::hashbrown::HashMap;
-use glam::Mat4;
+use glam::{vec2, vec4, Mat4, Vec2, Vec4};
 use itertools::Itertools;
-use shin_core::vm::command::types::LayerId;
-use shin_render::{GpuCommonResources, RenderTarget, Renderable};
+use shin_core::{
+    time::{Ticks, Tween, Tweener},
+    vm::command::types::{LayerId, MaskFlags, WiperParams, WiperType},
+};
+use shin_render::{
+    GpuCommonResources, PooledRenderTarget, RenderTarget, Renderable, VIRTUAL_HEIGHT, VIRTUAL_WIDTH,
+};
+use tracing::warn;
 
 use crate::{
     adv::LayerSelection,
+    asset::mask::Mask,
     layer::{Layer, LayerProperties, UserLayer},
     update::{Updatable, UpdateContext},
 };
 
+/// A mask loaded with MASKLOAD
+#[derive(Clone)]
+pub struct LayerGroupMask {
+    pub mask: Arc<Mask>,
+    pub flags: MaskFlags,
+    /// The mask is only used by the transitions, not when rendering the group normally
+    pub transition: bool,
+}
+
+impl LayerGroupMask {
+    /// Computes the scale (xy) and offset (zw) mapping the texture coordinates of the group to the mask texture coordinates
+    pub fn mask_transform(&self, resources: &GpuCommonResources) -> Vec4 {
+        let texture = self.mask.gpu_texture(resources);
+
+        let mut scale = if self.flags.contains(MaskFlags::SCALE) {
+            vec2(1.0, 1.0)
+        } else {
+            // use the mask at its native size, centered on the screen
+            vec2(
+                VIRTUAL_WIDTH / texture.width as f32,
+                VIRTUAL_HEIGHT / texture.height as f32,
+            )
+        };
+        let mut offset = (vec2(1.0, 1.0) - scale) / 2.0;
+
+        if self.flags.contains(MaskFlags::FLIP_X) {
+            scale.x = -scale.x;
+            offset.x = 1.0 - offset.x;
+        }
+        if self.flags.contains(MaskFlags::FLIP_Y) {
+            scale.y = -scale.y;
+            offset.y = 1.0 - offset.y;
+        }
+        // TODO: figure out what MaskFlags::UNK_4 does
+
+        vec4(scale.x, scale.y, offset.x, offset.y)
+    }
+}
+
+enum TransitionWiper {
+    Default,
+    Mask {
+        mask: LayerGroupMask,
+        params: WiperParams,
+    },
+}
+
+/// A transition started by TRANSSET
+///
+/// The contents of the group at the start of the transition are kept in a separate render target,
+/// while the new contents are rendered as usual. The old contents are drawn as-is, and the wiper then blends the new ones over them.
+struct LayerGroupTransition {
+    source: PooledRenderTarget,
+    wiper: TransitionWiper,
+    progress: Tweener,
+}
+
+impl LayerGroupTransition {
+    /// Computes the `minmax` of the mask wiper for the new contents
+    fn mask_minmax(progress: f32, params: &WiperParams) -> Vec2 {
+        let width = params.fade_width;
+        // the edge moves so that the whole fade range passes over the mask during the transition
+        let edge = progress * (1.0 + width) - width;
+        if params.reverse {
+            vec2(1.0 - edge - width, 1.0 - edge)
+        } else {
+            vec2(edge + width, edge)
+        }
+    }
+}
+
 pub struct LayerGroup {
     layers: HashMap<LayerId, UserLayer>,
-    render_target: RenderTarget,
+    render_target: PooledRenderTarget,
+    /// Holds the intermediate result when a transition or a mask is drawn together with the color effect
+    composite_target: PooledRenderTarget,
     properties: LayerProperties,
+    mask: Option<LayerGroupMask>,
+    transition: Option<LayerGroupTransition>,
 }
 
 impl LayerGroup {
     pub fn new(resources: &GpuCommonResources) -> Self {
-        let render_target = RenderTarget::new(
-            resources,
-            resources.current_render_buffer_size(),
-            Some("LayerGroup RenderTarget"),
-        );
+        let render_target = resources.acquire_render_target(Some("LayerGroup RenderTarget"));
+        let composite_target =
+            resources.acquire_render_target(Some("LayerGroup Composite RenderTarget"));
 
         Self {
             layers: HashMap::new(),
             render_target,
+            composite_target,
             properties: LayerProperties::new(),
+            mask: None,
+            transition: None,
         }
     }
 
+    pub fn set_mask(&mut self, mask: Option<LayerGroupMask>) {
+        self.mask = mask;
+    }
+
+    /// Starts a transition from the currently displayed contents of the group to whatever will be rendered next
+    pub fn start_transition(
+        &mut self,
+        resources: &GpuCommonResources,
+        wiper_type: WiperType,
+        params: WiperParams,
+        duration: Ticks,
+    ) {
+        let wiper = match (wiper_type, &self.mask) {
+            (WiperType::Default, _) => TransitionWiper::Default,
+            (WiperType::Mask, Some(mask)) => TransitionWiper::Mask {
+                mask: mask.clone(),
+                params,
+            },
+            (WiperType::Mask, None) => {
+                warn!("LayerGroup::start_transition: mask wiper used without a mask loaded, using the default wiper");
+                TransitionWiper::Default
+            }
+        };
+
+        // the render target still holds the previous frame
+        let source = resources.render_target_pool.acquire(
+            resources,
+            self.render_target.size(),
+            Some("LayerGroup Transition RenderTarget"),
+        );
+        source.copy_from(resources, &self.render_target);
+
+        let mut progress = Tweener::new(0.0);
+        progress.enqueue_now(1.0, Tween::linear(duration));
+
+        self.transition = Some(LayerGroupTransition {
+            source,
+            wiper,
+            progress,
+        });
+    }
+
+    pub fn is_transition_finished(&self) -> bool {
+        self.transition.is_none()
+    }
+
+    pub fn finish_transition(&mut self) {
+        self.transition = None;
+    }
+
     pub fn get_layer_ids(&self) -> impl Iterator<Item = LayerId> + '_ {
         self.layers.keys().cloned()
     }
@@ -47,14 +182,28 @@ impl LayerGroup {
         }
     }
 
+    /// Exchange the layers with the given ids, moving a layer to the other id if only one of them exists
+    pub fn swap_layers(&mut self, id1: LayerId, id2: LayerId) {
+        let layer1 = self.layers.remove(&id1);
+        let layer2 = self.layers.remove(&id2);
+        if let Some(layer1) = layer1 {
+            self.layers.insert(id2, layer1);
+        }
+        if let Some(layer2) = layer2 {
+            self.layers.insert(id1, layer2);
+        }
+    }
+
     pub fn get_layer(&self, id: LayerId) -> Option<&UserLayer> {
         self.layers.get(&id)
     }
 
+    /// Get the loaded layers in the selection, ordered by their ids
     pub fn get_layers(&self, selection: LayerSelection) -> impl Iterator<Item = &UserLayer> {
         self.layers
             .iter()
             .filter(move |&(&id, _)| selection.contains(id))
+            .sorted_by_key(|&(&id, _)| id)
             .map(|(_, v)| v)
     }
 
@@ -69,8 +218,98 @@ impl LayerGroup {
         self.layers
             .iter_mut()
             .filter(move |&(&id, _)| selection.contains(id))
+            .sorted_by_key(|&(&id, _)| id)
             .map(|(_, v)| v)
     }
+
+    fn draw_transition<'a>(
+        &'a self,
+        resources: &'a GpuCommonResources,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        projection: Mat4,
+        transition: &'a LayerGroupTransition,
+    ) {
+        let progress = transition.progress.value();
+        // fading the old contents out too would let the background show through mid-transition
+        resources.draw_wiper_default(
+            render_pass,
+            transition.source.vertex_source(),
+            transition.source.bind_group(),
+            projection,
+            1.0,
+        );
+        match &transition.wiper {
+            TransitionWiper::Default => {
+                render_pass.insert_debug_marker(&format!("Transition (progress {:.2})", progress));
+                resources.draw_wiper_default(
+                    render_pass,
+                    self.render_target.vertex_source(),
+                    self.render_target.bind_group(),
+                    projection,
+                    progress,
+                );
+            }
+            TransitionWiper::Mask { mask, params } => {
+                render_pass.insert_debug_marker(&format!(
+                    "Mask transition (progress {:.2}, {:?})",
+                    progress, params
+                ));
+                resources.draw_wiper_mask(
+                    render_pass,
+                    self.render_target.vertex_source(),
+                    self.render_target.bind_group(),
+                    mask.mask.gpu_texture(resources).bind_group(),
+                    projection,
+                    mask.mask_transform(resources),
+                    LayerGroupTransition::mask_minmax(progress, params),
+                );
+            }
+        }
+    }
+
+    fn draw_static_mask<'a>(
+        &'a self,
+        resources: &'a GpuCommonResources,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        source: &'a RenderTarget,
+        projection: Mat4,
+        mask: &'a LayerGroupMask,
+    ) {
+        render_pass.insert_debug_marker(&format!("Static mask ({:?})", mask.flags));
+        resources.draw_wiper_mask(
+            render_pass,
+            source.vertex_source(),
+            source.bind_group(),
+            mask.mask.gpu_texture(resources).bind_group(),
+            projection,
+            mask.mask_transform(resources),
+            // a static mask is used as-is, as the alpha channel
+            vec2(0.0, 1.0),
+        )
+    }
+
+    /// Draws the `source` with the color effect of the group
+    fn draw_effect<'a>(
+        &'a self,
+        resources: &'a GpuCommonResources,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        source: &'a RenderTarget,
+        projection: Mat4,
+    ) {
+        render_pass.insert_debug_marker(&format!(
+            "Effect {:?} {}",
+            self.properties.fragment_shader(),
+            self.properties.shader_params()
+        ));
+        resources.draw_layer(
+            render_pass,
+            source.vertex_source(),
+            source.bind_group(),
+            projection,
+            self.properties.fragment_shader(),
+            self.properties.shader_params(),
+        )
+    }
 }
 
 impl Updatable for LayerGroup {
@@ -79,6 +318,13 @@ impl Updatable for LayerGroup {
         for layer in self.layers.values_mut() {
             layer.update(context);
         }
+
+        if let Some(transition) = &mut self.transition {
+            transition.progress.update(context.time_delta_ticks());
+            if transition.progress.is_idle() {
+                self.transition = None;
+            }
+        }
     }
 }
 
@@ -92,9 +338,11 @@ impl Renderable for LayerGroup {
     ) {
         {
             let mut encoder = resources.start_encoder();
-            let mut render_pass = self
-                .render_target
-                .begin_srgb_render_pass(&mut encoder, Some("LayerGroup RenderPass"));
+            let mut render_pass = self.render_target.begin_srgb_render_pass(
+                resources,
+                &mut encoder,
+                Some("LayerGroup RenderPass"),
+            );
 
             let ordered_layers = self
                 .layers
@@ -109,26 +357,71 @@ impl Renderable for LayerGroup {
             let projection = self.render_target.projection_matrix();
 
             for (id, l) in ordered_layers {
-                render_pass.push_debug_group(&format!("Layer {:?}", id));
+                if !l.properties().is_visible() {
+                    continue;
+                }
+                render_pass.push_debug_group(&format!("Layer {:?} {:?}", id, l));
                 l.render(resources, &mut render_pass, transform, projection);
                 render_pass.pop_debug_group();
             }
         }
 
         render_pass.push_debug_group("LayerGroup Render");
-        // TODO use layer pseudo-pipeline
-        resources.draw_sprite(
-            render_pass,
-            self.render_target.vertex_source(),
-            self.render_target.bind_group(),
-            projection,
-        );
+        let static_mask = self.mask.as_ref().filter(|mask| !mask.transition);
+        match (&self.transition, static_mask) {
+            (Some(transition), _) => {
+                // the old and the new contents are blended first, so that the color effect applies to both
+                {
+                    let mut encoder = resources.start_encoder();
+                    let mut composite_pass = self.composite_target.begin_srgb_render_pass(
+                        resources,
+                        &mut encoder,
+                        Some("LayerGroup Transition RenderPass"),
+                    );
+                    let projection = self.composite_target.projection_matrix();
+                    self.draw_transition(resources, &mut composite_pass, projection, transition);
+                }
+                self.draw_effect(resources, render_pass, &self.composite_target, projection);
+            }
+            (None, Some(mask)) => {
+                // the mask is applied last, so that its alpha is not blended twice
+                {
+                    let mut encoder = resources.start_encoder();
+                    let mut composite_pass = self.composite_target.begin_srgb_render_pass(
+                        resources,
+                        &mut encoder,
+                        Some("LayerGroup Effect RenderPass"),
+                    );
+                    let projection = self.composite_target.projection_matrix();
+                    self.draw_effect(
+                        resources,
+                        &mut composite_pass,
+                        &self.render_target,
+                        projection,
+                    );
+                }
+                self.draw_static_mask(
+                    resources,
+                    render_pass,
+                    &self.composite_target,
+                    projection,
+                    mask,
+                );
+            }
+            (None, None) => {
+                self.draw_effect(resources, render_pass, &self.render_target, projection);
+            }
+        }
         render_pass.pop_debug_group();
     }
 
     fn resize(&mut self, resources: &GpuCommonResources) {
         self.render_target
             .resize(resources, resources.current_render_buffer_size());
+        self.composite_target
+            .resize(resources, resources.current_render_buffer_size());
+        // the snapshot of the old contents is lost anyway
+        self.transition = None;
     }
 }
 
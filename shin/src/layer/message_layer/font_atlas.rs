@@ -3,6 +3,7 @@ use std::sync::Arc;
 use shin_core::format::font::{GlyphId, GlyphMipLevel, GlyphTrait, LazyFont};
 use shin_render::{GpuCommonResources, TextureBindGroup};
 use strum::IntoEnumIterator;
+use tracing::error;
 use wgpu::TextureFormat;
 
 use crate::render::{
@@ -22,12 +23,22 @@ impl ImageProvider for FontImageProvider {
     fn get_image(&self, id: Self::Id) -> (Vec<Vec<u8>>, (u32, u32)) {
         let glyph = self.font.get_glyph(id).unwrap();
         let size = glyph.get_info().texture_size();
-        let glyph = glyph.decompress();
 
         let mut result = Vec::new();
-        for mip_level in GlyphMipLevel::iter() {
-            let image = glyph.get_image(mip_level);
-            result.push(image.to_vec());
+        match glyph.decompress() {
+            Ok(glyph) => {
+                for mip_level in GlyphMipLevel::iter() {
+                    let image = glyph.get_image(mip_level);
+                    result.push(image.to_vec());
+                }
+            }
+            Err(e) => {
+                // draw a blank glyph instead of taking the whole game down
+                error!("Failed to decompress glyph {:?}: {}", id, e);
+                for level in 0..Self::MIPMAP_LEVELS {
+                    result.push(vec![0; ((size.0 >> level) * (size.1 >> level)) as usize]);
+                }
+            }
         }
 
         (result, size)
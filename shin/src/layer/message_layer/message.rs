@@ -1,12 +1,13 @@
 use std::sync::Arc;
 
-use glam::{vec2, Mat4, Vec2};
+use glam::{vec2, vec3, Mat4, Vec2};
 use shin_core::{
     format::font::GlyphTrait,
     layout::{
-        Action, ActionType, Block, BlockExitCondition, LayoutedChar, LayoutedMessage, LayoutingMode,
+        Action, ActionType, Block, BlockExitCondition, LayoutedChar, LayoutedMessage,
+        LayoutingMode, TextDirection,
     },
-    time::Ticks,
+    time::{Easing, Ticks, Tween, Tweener},
     vm::command::types::MessageTextLayout,
 };
 use shin_render::{vertices::TextVertex, GpuCommonResources, Renderable, VertexBuffer};
@@ -18,6 +19,13 @@ use crate::{
     update::{Updatable, UpdateContext},
 };
 
+/// Space kept visible past the baseline of the last shown line (or past the last column in vertical text) when scrolling
+const SCROLL_MARGIN: f32 = 24.0;
+const SCROLL_TWEEN: Tween = Tween {
+    duration: Ticks::from_f32(15.0),
+    easing: Easing::SineInOut,
+};
+
 /// Calculated global metrics for a message. Used to adjust the sizes of individual parts of
 /// the message box, such that it fits the character name and the entire height of the message
 #[derive(Copy, Clone)]
@@ -26,6 +34,91 @@ pub struct MessageMetrics {
     pub height: f32,
 }
 
+/// Generates the vertices for the layouted chars, loading the glyphs into the font atlas.
+///
+/// The codepoints of the loaded glyphs are appended to `used_codepoints`, they should be freed with [`FontAtlas::free_glyph`] when the text is no longer displayed.
+pub fn build_text_vertices(
+    resources: &GpuCommonResources,
+    font_atlas: &FontAtlas,
+    base_position: Vec2,
+    chars: impl IntoIterator<Item = LayoutedChar>,
+    used_codepoints: &mut Vec<u16>,
+) -> Vec<TextVertex> {
+    let mut vertices = Vec::new();
+    for char in chars {
+        // TODO: support for BOLD font
+        let glyph_info = font_atlas
+            .get_font()
+            .get_glyph_for_character(char.codepoint)
+            .get_info();
+
+        let atlas_size = font_atlas.texture_size();
+        let atlas_size = vec2(atlas_size.0 as f32, atlas_size.1 as f32);
+
+        let AtlasImage {
+            position: tex_position,
+            size: _, // the atlas size is not to be trusted, as it can be larger than the actual texture (even larger than the power of 2 padded texture...)
+        } = font_atlas.get_glyph(resources, char.codepoint);
+        // save the codepoint to free it from the atlas later
+        used_codepoints.push(char.codepoint);
+
+        // just use the actual size of the glyph
+        let tex_size = glyph_info.actual_size();
+        let tex_size = vec2(tex_size.0 as f32, tex_size.1 as f32);
+
+        // scale texture coordinates to the size of the texture
+        let tex_position = tex_position / atlas_size;
+        let tex_size = tex_size / atlas_size;
+
+        let pen_position = base_position + char.position;
+        let bearing = vec2(
+            glyph_info.bearing_x as f32 * char.size.horizontal_scale,
+            -glyph_info.bearing_y as f32 * char.size.scale,
+        );
+        let size = char.size.size();
+        // rotated glyphs (in vertical text) are turned 90° clockwise around the pen position
+        let orient = |offset: Vec2| {
+            if char.rotated {
+                vec2(-offset.y, offset.x)
+            } else {
+                offset
+            }
+        };
+
+        let time = char.time;
+        let fade = char.fade;
+        let color = char.color;
+
+        // TODO: do the fade calculation here
+
+        // helper macro to reduce vertex creation boilerplate
+        macro_rules! v {
+            (($x:expr, $y:expr), ($tex_x:expr, $tex_y:expr)) => {
+                TextVertex {
+                    position: pen_position + orient(bearing + vec2($x, $y) * size),
+                    tex_position: tex_position + vec2($tex_x, $tex_y) * tex_size,
+                    color,
+                    time,
+                    fade,
+                }
+            };
+        }
+
+        vertices.extend([
+            // Top left triangle
+            v!((0.0, 0.0), (0.0, 0.0)),
+            v!((1.0, 0.0), (1.0, 0.0)),
+            v!((0.0, 1.0), (0.0, 1.0)),
+            // Bottom right triangle
+            v!((1.0, 1.0), (1.0, 1.0)),
+            v!((0.0, 1.0), (0.0, 1.0)),
+            v!((1.0, 0.0), (1.0, 0.0)),
+        ]);
+    }
+
+    vertices
+}
+
 pub struct Message {
     time: Ticks,
     font_atlas: Arc<FontAtlas>,
@@ -37,6 +130,16 @@ pub struct Message {
     received_signals: u32,
     completed_blocks: u32,
     metrics: MessageMetrics,
+    direction: TextDirection,
+    /// The region outside which the text is cut (left, top, right, bottom), in the message layer coordinates
+    clip: Option<(f32, f32, f32, f32)>,
+    /// For every char in the order of appearance: the time it's shown and how far it reaches in the direction the text grows (down or left)
+    reaches: Vec<(Ticks, f32)>,
+    /// How many entries of `reaches` are already shown
+    shown_chars: usize,
+    shown_reach: f32,
+    /// How far the text is moved back to keep the last shown line inside the clip region
+    scroll: Tweener,
 }
 
 pub enum MessageStatus {
@@ -52,13 +155,18 @@ impl Message {
         font_atlas: Arc<FontAtlas>,
         base_position: Vec2,
         show_character_name: bool,
+        direction: TextDirection,
         message: &str,
     ) -> Self {
         // let mut font_atlas_guard = font_atlas.lock().unwrap();
 
         let layout_params = shin_core::layout::LayoutParams {
             font: font_atlas.get_font(),
-            layout_width: 1500.0,
+            layout_width: match direction {
+                TextDirection::Horizontal => 1500.0,
+                // the column length, fitting the novel mode page
+                TextDirection::Vertical => 900.0,
+            },
             character_name_layout_width: 384.0,
             base_font_height: 50.0,
             furigana_font_height: 20.0,
@@ -67,6 +175,9 @@ impl Message {
             default_state: Default::default(),
             has_character_name: true,
             mode: LayoutingMode::MessageText,
+            line_breaking: Default::default(),
+            ruby: Default::default(),
+            direction,
         };
 
         let LayoutedMessage {
@@ -135,71 +246,30 @@ impl Message {
             })
             .chain(chars);
 
-        let mut used_codepoints = Vec::new();
-        let mut vertices = Vec::new();
-        for char in all_chars_iter {
-            // TODO: support for BOLD font
-            let glyph_info = font_atlas
-                .get_font()
-                .get_glyph_for_character(char.codepoint)
-                .get_info();
-
-            let atlas_size = font_atlas.texture_size();
-            let atlas_size = vec2(atlas_size.0 as f32, atlas_size.1 as f32);
-
-            let AtlasImage {
-                position: tex_position,
-                size: _, // the atlas size is not to be trusted, as it can be larger than the actual texture (even larger than the power of 2 padded texture...)
-            } = font_atlas.get_glyph(context.gpu_resources, char.codepoint);
-            // save the codepoint to free it from the atlas later
-            used_codepoints.push(char.codepoint);
-
-            // just use the actual size of the glyph
-            let tex_size = glyph_info.actual_size();
-            let tex_size = vec2(tex_size.0 as f32, tex_size.1 as f32);
-
-            // scale texture coordinates to the size of the texture
-            let tex_position = tex_position / atlas_size;
-            let tex_size = tex_size / atlas_size;
-
-            let position = base_position
-                + char.position
-                + vec2(
-                    glyph_info.bearing_x as f32 * char.size.horizontal_scale,
-                    -glyph_info.bearing_y as f32 * char.size.scale,
-                );
-            let size = char.size.size();
-
-            let time = char.time;
-            let fade = char.fade;
-            let color = char.color;
-
-            // TODO: do the fade calculation here
-
-            // helper macro to reduce vertex creation boilerplate
-            macro_rules! v {
-                (($x:expr, $y:expr), ($tex_x:expr, $tex_y:expr)) => {
-                    TextVertex {
-                        position: position + vec2($x, $y) * size,
-                        tex_position: tex_position + vec2($tex_x, $tex_y) * tex_size,
-                        color,
-                        time,
-                        fade,
-                    }
+        let all_chars = all_chars_iter.collect::<Vec<_>>();
+
+        let mut reaches = all_chars
+            .iter()
+            .map(|c| {
+                let position = base_position + c.position;
+                let reach = match direction {
+                    TextDirection::Horizontal => position.y,
+                    // the columns go from right to left
+                    TextDirection::Vertical => -position.x + c.size.size().x / 2.0,
                 };
-            }
+                (c.time, reach + SCROLL_MARGIN)
+            })
+            .collect::<Vec<_>>();
+        reaches.sort_by_key(|&(time, _)| time);
 
-            vertices.extend([
-                // Top left triangle
-                v!((0.0, 0.0), (0.0, 0.0)),
-                v!((1.0, 0.0), (1.0, 0.0)),
-                v!((0.0, 1.0), (0.0, 1.0)),
-                // Bottom right triangle
-                v!((1.0, 1.0), (1.0, 1.0)),
-                v!((0.0, 1.0), (0.0, 1.0)),
-                v!((1.0, 0.0), (1.0, 0.0)),
-            ]);
-        }
+        let mut used_codepoints = Vec::new();
+        let vertices = build_text_vertices(
+            context.gpu_resources,
+            &font_atlas,
+            base_position,
+            all_chars,
+            &mut used_codepoints,
+        );
 
         let vertex_buffer = VertexBuffer::new(
             context.gpu_resources,
@@ -218,9 +288,43 @@ impl Message {
             received_signals: 0,
             completed_blocks: 0,
             metrics,
+            direction,
+            clip: None,
+            reaches,
+            shown_chars: 0,
+            shown_reach: f32::NEG_INFINITY,
+            scroll: Tweener::new(0.0),
         }
     }
 
+    /// Cuts the text to the region (left, top, right, bottom), scrolling it when the shown text grows past the region
+    pub fn set_clip(&mut self, clip: Option<(f32, f32, f32, f32)>) {
+        self.clip = clip;
+    }
+
+    fn update_scroll(&mut self, context: &UpdateContext) {
+        while let Some(&(time, reach)) = self.reaches.get(self.shown_chars) {
+            if time > self.time {
+                break;
+            }
+            self.shown_reach = self.shown_reach.max(reach);
+            self.shown_chars += 1;
+        }
+
+        if let Some((left, _, _, bottom)) = self.clip {
+            let edge = match self.direction {
+                TextDirection::Horizontal => bottom,
+                TextDirection::Vertical => -left,
+            };
+            let target = (self.shown_reach - edge).max(0.0);
+            if target > self.scroll.target_value() {
+                self.scroll.enqueue_now(target, SCROLL_TWEEN);
+            }
+        }
+
+        self.scroll.update(context.time_delta_ticks());
+    }
+
     pub fn is_complete(&self) -> bool {
         self.blocks.is_empty()
     }
@@ -349,9 +453,48 @@ impl Updatable for Message {
             }
             self.execute_actions();
         }
+
+        self.update_scroll(context);
     }
 }
 
+/// Converts a region in the coordinates of the `transform` to a scissor rectangle (x, y, width, height) on the render target
+///
+/// Returns `None` if nothing of the region is on the target.
+fn scissor_rect(
+    resources: &GpuCommonResources,
+    transform: Mat4,
+    (left, top, right, bottom): (f32, f32, f32, f32),
+) -> Option<(u32, u32, u32, u32)> {
+    let (width, height) = resources.current_render_buffer_size();
+
+    let corners = [
+        vec3(left, top, 0.0),
+        vec3(right, top, 0.0),
+        vec3(left, bottom, 0.0),
+        vec3(right, bottom, 0.0),
+    ]
+    .map(|corner| {
+        let ndc = transform.project_point3(corner);
+        vec2(
+            (ndc.x + 1.0) / 2.0 * width as f32,
+            (1.0 - ndc.y) / 2.0 * height as f32,
+        )
+    });
+    let min = corners
+        .iter()
+        .fold(Vec2::splat(f32::INFINITY), |a, &b| a.min(b))
+        .max(Vec2::ZERO);
+    let max = corners
+        .iter()
+        .fold(Vec2::splat(f32::NEG_INFINITY), |a, &b| a.max(b))
+        .min(vec2(width as f32, height as f32));
+
+    let (x, y) = (min.x.floor() as u32, min.y.floor() as u32);
+    let (x_end, y_end) = (max.x.ceil() as u32, max.y.ceil() as u32);
+    (x_end > x && y_end > y).then(|| (x, y, x_end - x, y_end - y))
+}
+
 impl Renderable for Message {
     fn render<'enc>(
         &'enc self,
@@ -362,12 +505,33 @@ impl Renderable for Message {
     ) {
         const OUTLINE_DISTANCE: f32 = 3.5;
 
-        let total_transform = projection * transform;
+        let region_transform = projection * transform;
+        let scroll = self.scroll.value();
+        let total_transform = region_transform
+            * Mat4::from_translation(match self.direction {
+                TextDirection::Horizontal => vec3(0.0, -scroll, 0.0),
+                TextDirection::Vertical => vec3(scroll, 0.0, 0.0),
+            });
+
+        let scissor = match self.clip {
+            Some(clip) => match scissor_rect(resources, region_transform, clip) {
+                Some(scissor) => Some(scissor),
+                // the region is off the screen, nothing to draw
+                None => return,
+            },
+            None => None,
+        };
 
         let atlas_size = self.font_atlas.texture_size();
         let scaled_distance = OUTLINE_DISTANCE / vec2(atlas_size.0 as f32, atlas_size.1 as f32);
 
+        // all the characters share the font atlas texture and a single vertex buffer,
+        // so the whole message is drawn with just two draw calls
+        // the outlines are drawn in a separate pass before the fills, so that the outline of a character never covers the neighbouring character
         render_pass.push_debug_group("Message");
+        if let Some((x, y, width, height)) = scissor {
+            render_pass.set_scissor_rect(x, y, width, height);
+        }
         resources.draw_text_outline(
             render_pass,
             self.vertex_buffer.vertex_source(),
@@ -384,6 +548,10 @@ impl Renderable for Message {
             total_transform,
             self.time,
         );
+        if scissor.is_some() {
+            let (width, height) = resources.current_render_buffer_size();
+            render_pass.set_scissor_rect(0, 0, width, height);
+        }
         render_pass.pop_debug_group();
     }
 
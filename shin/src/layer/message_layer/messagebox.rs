@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use glam::{vec2, vec3, vec4, Mat4, Vec2};
+use glam::{vec2, vec3, vec4, Mat4, Vec2, Vec4};
 use shin_core::vm::command::types::MessageboxType;
 use shin_render::{
     vertices::PosColTexVertex, GpuCommonResources, LazyGpuTexture, PosVertexBuffer, Renderable,
@@ -10,6 +10,7 @@ use shin_render::{
 use crate::{
     asset::texture_archive::TextureArchive,
     layer::message_layer::message::MessageMetrics,
+    settings::{MessageboxSettings, SettingsStore},
     update::{Updatable, UpdateContext},
 };
 
@@ -37,6 +38,10 @@ pub struct MessageboxTextures {
 
 const MAX_VERTEX_COUNT: usize = 120;
 const TEX_SIZE: Vec2 = vec2(1648.0, 288.0);
+/// The opacity of the message window with the default settings
+const WINDOW_ALPHA: f32 = 0.85;
+/// The opacity of the darkening behind the novel mode text with the default settings
+const NOVEL_FILL_ALPHA: f32 = 0.7;
 
 // https://stackoverflow.com/a/34324856
 macro_rules! count {
@@ -45,25 +50,26 @@ macro_rules! count {
 }
 
 macro_rules! make_vertices {
-    ($r:expr, $([$x:expr, $y:expr, $x_tex:expr, $y_tex:expr]),*) => {
+    ($r:expr, $color:expr, $([$x:expr, $y:expr, $x_tex:expr, $y_tex:expr]),*) => {
         $r.reserve(count!($($x)*));
         $(
             $r.push(PosColTexVertex {
                 position: vec3($x, $y, 1.0),
-                color: vec4(1.0, 1.0, 1.0, 0.85),
+                color: $color,
                 texture_coordinate: vec2($x_tex / TEX_SIZE.x, $y_tex / TEX_SIZE.y),
             });
         )*
     };
 }
 
-fn build_message_header_buffer(character_name_width: f32) -> Vec<PosColTexVertex> {
+fn build_message_header_buffer(character_name_width: f32, color: Vec4) -> Vec<PosColTexVertex> {
     let mut result = Vec::new();
 
     if character_name_width == 0.0 {
         // Draw the header part without a character name box
         make_vertices!(
             result,
+            color,
             [130.0, -32.0, 0.0, 144.0],
             [130.0, 80.0, 0.0, 256.0],
             [178.0, -32.0, 48.0, 144.0],
@@ -77,6 +83,7 @@ fn build_message_header_buffer(character_name_width: f32) -> Vec<PosColTexVertex
         // Draw the header part with a character name box
         make_vertices!(
             result,
+            color,
             [130.0, -32.0, 0.0, 0.0],
             [130.0, 80.0, 0.0, 112.0],
             [178.0, -32.0, 48.0, 0.0],
@@ -95,7 +102,7 @@ fn build_message_header_buffer(character_name_width: f32) -> Vec<PosColTexVertex
     result
 }
 
-fn build_message_body_vertices(height: f32) -> Vec<PosColTexVertex> {
+fn build_message_body_vertices(height: f32, color: Vec4) -> Vec<PosColTexVertex> {
     let mut result = Vec::new();
 
     let mid = height + 32.0 - 256.0;
@@ -103,6 +110,7 @@ fn build_message_body_vertices(height: f32) -> Vec<PosColTexVertex> {
 
     make_vertices!(
         result,
+        color,
         [130.0, 80.0, 240.0, 16.0],
         [130.0, mid, 240.0, 32.0],
         [178.0, 80.0, 288.0, 16.0],
@@ -134,19 +142,22 @@ fn unwrap_triangle_strip(strip: &[PosColTexVertex], output: &mut Vec<PosColTexVe
     }
 }
 
-fn build_vertex_buffer(character_name_width: f32, height: f32) -> Vec<PosColTexVertex> {
+/// `color` is multiplied with the window texture, it's how the opacity and the tint settings are applied
+fn build_vertex_buffer(
+    character_name_width: f32,
+    height: f32,
+    color: Vec4,
+) -> Vec<PosColTexVertex> {
     let mut result = Vec::new();
     result.reserve(MAX_VERTEX_COUNT);
 
-    // TODO: take opacity into account
-
     unwrap_triangle_strip(
-        &build_message_header_buffer(character_name_width),
+        &build_message_header_buffer(character_name_width, color),
         &mut result,
     );
     // let header = 0..result.len() as u32;
 
-    unwrap_triangle_strip(&build_message_body_vertices(height), &mut result);
+    unwrap_triangle_strip(&build_message_body_vertices(height, color), &mut result);
     // let body = header.end..result.len() as u32;
 
     assert!(result.len() < MAX_VERTEX_COUNT);
@@ -162,10 +173,18 @@ pub struct Messagebox {
     visible: bool,
     metrics: MessageMetrics,
     dynamic_height: f32,
+    settings: Arc<SettingsStore>,
+    /// A copy of the settings, refreshed on update
+    appearance: MessageboxSettings,
 }
 
 impl Messagebox {
-    pub fn new(textures: Arc<MessageboxTextures>, resources: &GpuCommonResources) -> Self {
+    pub fn new(
+        textures: Arc<MessageboxTextures>,
+        resources: &GpuCommonResources,
+        settings: Arc<SettingsStore>,
+    ) -> Self {
+        let appearance = settings.get().messagebox;
         Self {
             textures,
             // TODO: reduce the capacity of the vertex buffer
@@ -182,12 +201,17 @@ impl Messagebox {
                 height: 360.0, // Static height: maximum height the message will ever have
             },
             dynamic_height: 360.0, // Dynamic height: potentially changes as the player clicks through the message
+            settings,
+            appearance,
         }
     }
 }
 
 impl Updatable for Messagebox {
-    fn update(&mut self, _context: &UpdateContext) {}
+    fn update(&mut self, _context: &UpdateContext) {
+        // the settings can be changed while the message window is shown
+        self.appearance = self.settings.get().messagebox;
+    }
 }
 
 impl Renderable for Messagebox {
@@ -215,8 +239,16 @@ impl Renderable for Messagebox {
                     ));
 
                 // TODO: do not upload the vertices if they haven't changed
-                let vertices =
-                    build_vertex_buffer(self.metrics.character_name_width, self.dynamic_height);
+                let color = self
+                    .appearance
+                    .tint
+                    .color()
+                    .extend(WINDOW_ALPHA * self.appearance.opacity);
+                let vertices = build_vertex_buffer(
+                    self.metrics.character_name_width,
+                    self.dynamic_height,
+                    color,
+                );
                 self.tex_vertex_buffer.write(&resources.queue, &vertices);
 
                 let texture = match self.messagebox_type {
@@ -242,7 +274,7 @@ impl Renderable for Messagebox {
                     render_pass,
                     self.fill_vertex_buffer.vertex_source(),
                     projection * transform,
-                    vec4(0.0, 0.0, 0.0, 0.7),
+                    vec4(0.0, 0.0, 0.0, NOVEL_FILL_ALPHA * self.appearance.opacity),
                 );
             }
         }
@@ -4,31 +4,46 @@ mod messagebox;
 
 use std::sync::Arc;
 
-use glam::{vec2, Mat4};
+pub use font_atlas::FontAtlas;
+use glam::{vec2, vec3, Mat4};
+pub use message::build_text_vertices;
 use message::{Message, MessageStatus};
 pub use messagebox::MessageboxTextures;
 use shin_core::{
-    time::Ticks,
+    layout::TextDirection,
+    time::{Easing, Ticks, Tween, Tweener},
     vm::command::types::{MessageboxStyle, MessageboxType},
 };
 use shin_render::{GpuCommonResources, Renderable};
 
 use crate::{
     adv::assets::AdvFonts,
-    layer::{
-        message_layer::{font_atlas::FontAtlas, messagebox::Messagebox},
-        Layer, LayerProperties,
-    },
+    layer::{message_layer::messagebox::Messagebox, Layer, LayerProperties},
     render::overlay::{OverlayCollector, OverlayVisitable},
+    settings::SettingsStore,
     update::{Updatable, UpdateContext},
 };
 
+/// How far down the message window slides when a modal is shown, enough to move it out of the screen
+const MODAL_SLIDE_DISTANCE: f32 = 600.0;
+const MODAL_SLIDE_TWEEN: Tween = Tween {
+    duration: Ticks::from_f32(12.0),
+    easing: Easing::SineInOut,
+};
+
+/// The part of the screen the novel mode text is shown in, the text growing past it is scrolled
+const NOVEL_TEXT_CLIP: (f32, f32, f32, f32) = (-840.0, -500.0, 840.0, 500.0);
+
 pub struct MessageLayer {
     props: LayerProperties,
     style: MessageboxStyle,
+    /// Direction of the text in the novel mode, the other message boxes always use horizontal text
+    novel_text_direction: TextDirection,
     font_atlas: Arc<FontAtlas>,
     message: Option<Message>,
     messagebox: Messagebox,
+    /// Slides the message window out while a modal (like the system menu) is shown, from 0 (in place) to 1 (out of the screen)
+    modal_slide: Tweener,
 }
 
 impl MessageLayer {
@@ -36,45 +51,92 @@ impl MessageLayer {
         resources: &GpuCommonResources,
         fonts: AdvFonts,
         textures: Arc<MessageboxTextures>,
+        settings: Arc<SettingsStore>,
     ) -> Self {
         Self {
             props: LayerProperties::new(),
             style: MessageboxStyle::default(),
+            novel_text_direction: TextDirection::Horizontal,
             font_atlas: Arc::new(FontAtlas::new(resources, fonts.medium_font)),
             message: None,
-            messagebox: Messagebox::new(textures, resources),
+            messagebox: Messagebox::new(textures, resources, settings),
+            modal_slide: Tweener::new(0.0),
         }
     }
 
+    /// The font atlas is shared with other text displayed in the game (e.g. the movie subtitles)
+    pub fn font_atlas(&self) -> &Arc<FontAtlas> {
+        &self.font_atlas
+    }
+
     pub fn set_style(&mut self, style: MessageboxStyle) {
         self.style = style;
 
         self.messagebox.set_messagebox_type(style.messagebox_type);
     }
 
+    pub fn set_novel_text_direction(&mut self, direction: TextDirection) {
+        self.novel_text_direction = direction;
+    }
+
+    /// Slides the message window out of the way of a modal, or back when the modal is closed
+    ///
+    /// The message is suspended while the modal is shown.
+    pub fn set_modal(&mut self, modal: bool) {
+        let target = if modal { 1.0 } else { 0.0 };
+        if self.modal_slide.target_value() != target {
+            self.modal_slide.enqueue_now(target, MODAL_SLIDE_TWEEN);
+        }
+    }
+
+    pub fn is_modal(&self) -> bool {
+        self.modal_slide.target_value() > 0.0
+    }
+
     pub fn set_message(&mut self, context: &UpdateContext, text: &str) {
         self.messagebox.set_visible(true);
 
         // TODO: devise a better [ositioning scheme maybe?
-        let (base_position, show_character_name) = match self.style.messagebox_type {
+        let (base_position, show_character_name, direction) = match self.style.messagebox_type {
             MessageboxType::Neutral
             | MessageboxType::WitchSpace
             | MessageboxType::Ushiromiya
-            | MessageboxType::Transparent => (vec2(-740.0 - 10.0, 300.0 - 156.0), true),
-            MessageboxType::Novel => (vec2(-740.0 - 10.0, 300.0 - 156.0 - 450.0), false),
+            | MessageboxType::Transparent => (
+                vec2(-740.0 - 10.0, 300.0 - 156.0),
+                true,
+                TextDirection::Horizontal,
+            ),
+            MessageboxType::Novel => match self.novel_text_direction {
+                TextDirection::Horizontal => (
+                    vec2(-740.0 - 10.0, 300.0 - 156.0 - 450.0),
+                    false,
+                    TextDirection::Horizontal,
+                ),
+                // vertical text starts at the top right corner
+                TextDirection::Vertical => (
+                    vec2(740.0 + 10.0, 300.0 - 156.0 - 600.0),
+                    false,
+                    TextDirection::Vertical,
+                ),
+            },
             MessageboxType::NoText => {
                 todo!()
             }
         };
 
-        let message = Message::new(
+        let mut message = Message::new(
             context,
             self.font_atlas.clone(),
             base_position,
             show_character_name,
+            direction,
             text,
         );
 
+        if self.style.messagebox_type == MessageboxType::Novel {
+            message.set_clip(Some(NOVEL_TEXT_CLIP));
+        }
+
         self.messagebox.set_metrics(message.metrics());
         self.message = Some(message);
     }
@@ -125,7 +187,12 @@ impl Renderable for MessageLayer {
         transform: Mat4,
         projection: Mat4,
     ) {
-        let transform = self.props.compute_transform(transform);
+        let transform = self.props.compute_transform(transform)
+            * Mat4::from_translation(vec3(
+                0.0,
+                self.modal_slide.value() * MODAL_SLIDE_DISTANCE,
+                0.0,
+            ));
         self.messagebox
             .render(resources, render_pass, transform, projection);
         if let Some(message) = &self.message {
@@ -140,7 +207,11 @@ impl Renderable for MessageLayer {
 
 impl Updatable for MessageLayer {
     fn update(&mut self, ctx: &UpdateContext) {
+        self.modal_slide.update(ctx.time_delta_ticks());
         self.messagebox.update(ctx);
+        if self.is_modal() {
+            return;
+        }
         if let Some(message) = &mut self.message {
             message.update(ctx);
         }
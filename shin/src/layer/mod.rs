@@ -7,20 +7,22 @@ mod page_layer;
 mod picture_layer;
 mod root_layer_group;
 mod screen_layer;
+mod snapshot;
 mod tile_layer;
+mod toast_layer;
 mod wobbler;
 
-use std::f32::consts::PI;
+use std::{f32::consts::PI, sync::Arc};
 
 pub use bustup_layer::BustupLayer;
 use derivative::Derivative;
 use derive_more::From;
 use enum_dispatch::enum_dispatch;
 use enum_map::{enum_map, EnumMap};
-use glam::{vec3, Mat4};
-pub use layer_group::LayerGroup;
-pub use message_layer::{MessageLayer, MessageboxTextures};
-pub use movie_layer::MovieLayer;
+use glam::{vec3, vec4, Mat4, Vec4};
+pub use layer_group::{LayerGroup, LayerGroupMask};
+pub use message_layer::{build_text_vertices, FontAtlas, MessageLayer, MessageboxTextures};
+pub use movie_layer::{MovieLayer, SubtitleRenderer, SubtitleStyle};
 pub use null_layer::NullLayer;
 pub use page_layer::PageLayer;
 pub use picture_layer::PictureLayer;
@@ -34,14 +36,18 @@ use shin_core::{
         Scenario,
     },
     time::{Ticks, Tweener},
-    vm::command::types::{LayerProperty, LayerType},
+    vm::command::types::{LayerFragmentShader, LayerProperty, LayerType},
 };
 use shin_render::{GpuCommonResources, Renderable};
+use shin_video::{subtitles::Subtitles, VideoPlayerOptions};
+pub use snapshot::LayersSnapshot;
 pub use tile_layer::TileLayer;
-use tracing::{debug, warn};
+pub use toast_layer::ToastLayer;
+use tracing::{debug, error, warn};
 
 use crate::{
     asset::{bustup::Bustup, movie::Movie, picture::Picture, AnyAssetServer},
+    audio::LipSync,
     layer::wobbler::Wobbler,
     update::{Updatable, UpdateContext},
 };
@@ -93,6 +99,32 @@ impl LayerProperties {
         }
     }
 
+    /// Jump straight to the values stored in the snapshot, skipping all the tweens in progress
+    pub fn restore_snapshot(&mut self, snapshot: &LayerPropertiesSnapshot) {
+        for (prop, &val) in snapshot.properties.iter() {
+            self.properties[prop].fast_forward_to(val as f32);
+        }
+    }
+
+    /// Hidden layers are skipped when rendering, but are still updated
+    pub fn is_visible(&self) -> bool {
+        self.get_property_value(LayerProperty::ShowLayer) != 0.0
+    }
+
+    pub fn fragment_shader(&self) -> LayerFragmentShader {
+        let value = self.get_property_value(LayerProperty::FragmentShader) as i32;
+        num_traits::FromPrimitive::from_i32(value).unwrap_or(LayerFragmentShader::Default)
+    }
+
+    pub fn shader_params(&self) -> Vec4 {
+        vec4(
+            self.get_property_value(LayerProperty::ShaderParamX),
+            self.get_property_value(LayerProperty::ShaderParamY),
+            self.get_property_value(LayerProperty::ShaderParamZ),
+            self.get_property_value(LayerProperty::ShaderParamW),
+        ) / 1000.0
+    }
+
     pub fn compute_transform(&self, base_transform: Mat4) -> Mat4 {
         macro_rules! get {
             (Zero) => {
@@ -114,7 +146,16 @@ impl LayerProperties {
 
         // TODO: actually use all the properties
 
+        let flip = self.get_property_value(LayerProperty::Flip) as i32;
+        let flip_scale = vec3(
+            if flip & 1 != 0 { -1.0 } else { 1.0 },
+            if flip & 2 != 0 { -1.0 } else { 1.0 },
+            1.0,
+        );
+
         let transforms = [
+            // apply flip, around the origin of the layer
+            Mat4::from_scale(flip_scale),
             // apply scale
             Mat4::from_translation(-get!(ScaleOriginX, ScaleOriginY, Zero)),
             Mat4::from_scale(vec3(
@@ -214,7 +255,6 @@ impl LayerPropertiesSnapshot {
         self.properties = initial_values();
     }
 
-    #[allow(unused)]
     pub fn get_property(&self, property: LayerProperty) -> i32 {
         self.properties[property]
     }
@@ -222,6 +262,10 @@ impl LayerPropertiesSnapshot {
     pub fn set_property(&mut self, property: LayerProperty, value: i32) {
         self.properties[property] = value;
     }
+
+    pub fn iter(&self) -> impl Iterator<Item = (LayerProperty, i32)> + '_ {
+        self.properties.iter().map(|(prop, &val)| (prop, val))
+    }
 }
 
 #[enum_dispatch]
@@ -248,11 +292,24 @@ pub enum UserLayer {
 }
 
 impl UserLayer {
+    /// A corrupt or missing asset shouldn't crash the game, so the layer is replaced with an empty one
+    fn load_failed(path: &str, error: anyhow::Error) -> Self {
+        error!(
+            "Failed to load {}, using a NullLayer instead: {:?}",
+            path, error
+        );
+        NullLayer::new().into()
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub async fn load(
         resources: &GpuCommonResources,
         asset_server: &AnyAssetServer,
         audio_manager: &AudioManager,
+        lip_sync: &LipSync,
+        font_atlas: &Arc<FontAtlas>,
         scenario: &Scenario,
+        movie_options: VideoPlayerOptions,
         layer_ty: LayerType,
         params: UntypedNumberArray,
     ) -> Self {
@@ -269,10 +326,10 @@ impl UserLayer {
                 let pic_info @ PictureInfoItem { name, linked_cg_id } =
                     scenario.info_tables().picture_info(pic_id);
                 debug!("Load picture: {} -> {} {}", pic_id, name, linked_cg_id);
-                let pic = asset_server
-                    .load::<Picture, _>(pic_info.path())
-                    .await
-                    .expect("Failed to load picture");
+                let pic = match asset_server.load::<Picture, _>(pic_info.path()).await {
+                    Ok(pic) => pic,
+                    Err(e) => return Self::load_failed(&pic_info.path(), e),
+                };
                 PictureLayer::new(resources, pic, Some(name.to_string())).into()
             }
             LayerType::Bustup => {
@@ -286,12 +343,20 @@ impl UserLayer {
                     "Load bustup: {} -> {} {} {}",
                     bup_id, name, emotion, lipsync_character_id
                 );
-                let bup = asset_server
-                    .load::<Bustup, _>(bup_info.path())
-                    .await
-                    .expect("Failed to load bustup");
-
-                BustupLayer::new(resources, bup, Some(name.to_string()), emotion.as_str()).into()
+                let bup = match asset_server.load::<Bustup, _>(bup_info.path()).await {
+                    Ok(bup) => bup,
+                    Err(e) => return Self::load_failed(&bup_info.path(), e),
+                };
+
+                BustupLayer::new(
+                    resources,
+                    bup,
+                    Some(name.to_string()),
+                    emotion.as_str(),
+                    *lipsync_character_id,
+                    lip_sync.clone(),
+                )
+                .into()
             }
             LayerType::Movie => {
                 let (movie_id, _volume, _flags, ..) = params;
@@ -305,12 +370,36 @@ impl UserLayer {
                     "Load movie: {} -> {} {} {} {}",
                     movie_id, name, linked_picture_id, flags, linked_bgm_id
                 );
-                let movie = asset_server
-                    .load::<Movie, _>(movie_info.path())
-                    .await
-                    .expect("Failed to load movie");
+                let movie = match asset_server.load::<Movie, _>(movie_info.path()).await {
+                    Ok(movie) => movie,
+                    Err(e) => return Self::load_failed(&movie_info.path(), e),
+                };
+
+                let mut layer = MovieLayer::new(
+                    resources,
+                    audio_manager,
+                    movie,
+                    Some(name.to_string()),
+                    movie_options,
+                );
 
-                MovieLayer::new(resources, audio_manager, movie, Some(name.to_string())).into()
+                // subtitles are optional, most movies don't have them
+                match asset_server
+                    .load::<Subtitles, _>(movie_info.subtitles_path())
+                    .await
+                {
+                    Ok(subtitles) => {
+                        debug!("Loaded {} subtitle cues", subtitles.cues().len());
+                        layer.set_subtitles(Some(SubtitleRenderer::new(
+                            subtitles,
+                            font_atlas.clone(),
+                            SubtitleStyle::default(),
+                        )));
+                    }
+                    Err(err) => debug!("Not loading subtitles: {:#}", err),
+                }
+
+                layer.into()
             }
             LayerType::Rain => {
                 let (_always_zero, _min_distance, _max_distance, ..) = params;
@@ -1,9 +1,12 @@
+mod subtitles;
+
 use std::{fmt::Debug, sync::Arc};
 
 use glam::Mat4;
 use shin_audio::AudioManager;
-use shin_render::{GpuCommonResources, RenderTarget, Renderable};
-use shin_video::VideoPlayer;
+use shin_render::{GpuCommonResources, PooledRenderTarget, Renderable};
+use shin_video::{VideoPlayer, VideoPlayerOptions};
+pub use subtitles::{SubtitleRenderer, SubtitleStyle};
 
 use crate::{
     asset::movie::Movie,
@@ -14,7 +17,8 @@ use crate::{
 pub struct MovieLayer {
     props: LayerProperties,
     video_player: VideoPlayer,
-    render_target: RenderTarget,
+    render_target: PooledRenderTarget,
+    subtitles: Option<SubtitleRenderer>,
     movie_name: Option<String>,
 }
 
@@ -24,21 +28,23 @@ impl MovieLayer {
         audio_manager: &AudioManager,
         movie: Arc<Movie>,
         movie_name: Option<String>,
+        options: VideoPlayerOptions,
     ) -> Self {
         Self {
             props: LayerProperties::new(),
             video_player: movie
-                .play(resources, audio_manager)
+                .play(resources, audio_manager, options)
                 .expect("Failed to play movie"),
-            render_target: RenderTarget::new(
-                resources,
-                resources.current_render_buffer_size(),
-                Some("MovieLayer RenderTarget"),
-            ),
+            render_target: resources.acquire_render_target(Some("MovieLayer RenderTarget")),
+            subtitles: None,
             movie_name,
         }
     }
 
+    pub fn set_subtitles(&mut self, subtitles: Option<SubtitleRenderer>) {
+        self.subtitles = subtitles;
+    }
+
     pub fn is_finished(&self) -> bool {
         self.video_player.is_finished()
     }
@@ -56,9 +62,11 @@ impl Renderable for MovieLayer {
         // TODO: I believe this will be changed, so we can remove this extra render pass
         {
             let mut encoder = resources.start_encoder();
-            let mut render_pass = self
-                .render_target
-                .begin_raw_render_pass(&mut encoder, Some("MovieLayer RenderPass"));
+            let mut render_pass = self.render_target.begin_raw_render_pass(
+                resources,
+                &mut encoder,
+                Some("MovieLayer RenderPass"),
+            );
 
             self.video_player.render(
                 resources,
@@ -74,6 +82,10 @@ impl Renderable for MovieLayer {
             self.render_target.bind_group(),
             projection,
         );
+
+        if let Some(subtitles) = &self.subtitles {
+            subtitles.render(resources, render_pass, projection * transform);
+        }
     }
 
     fn resize(&mut self, resources: &GpuCommonResources) {
@@ -86,6 +98,14 @@ impl Updatable for MovieLayer {
     fn update(&mut self, ctx: &UpdateContext) {
         self.video_player
             .update(ctx.time_delta_ticks(), &ctx.gpu_resources.queue);
+
+        if let Some(subtitles) = &mut self.subtitles {
+            subtitles.update(
+                ctx.gpu_resources,
+                self.video_player.position(),
+                ctx.time_delta_ticks(),
+            );
+        }
     }
 }
 
@@ -0,0 +1,188 @@
+use std::{sync::Arc, time::Duration};
+
+use glam::{vec2, vec3, Mat4, Vec3};
+use shin_core::{
+    layout::{LayoutParams, LayoutedMessage, LayouterState, LayoutingMode, LineBreaking},
+    time::Ticks,
+    vm::command::types::MessageTextLayout,
+};
+use shin_render::{vertices::TextVertex, GpuCommonResources, VertexBuffer};
+use shin_video::subtitles::Subtitles;
+
+use crate::layer::message_layer::{build_text_vertices, FontAtlas};
+
+/// Controls how the subtitles are displayed
+#[derive(Debug, Clone, Copy)]
+pub struct SubtitleStyle {
+    /// Height of a line of text, in virtual pixels
+    pub font_height: f32,
+    pub color: Vec3,
+    pub outline: bool,
+    /// Width of the area in which the text is wrapped
+    pub layout_width: f32,
+    pub text_layout: MessageTextLayout,
+    /// Distance between the last line of the subtitle and the bottom edge of the screen
+    pub bottom_margin: f32,
+}
+
+impl Default for SubtitleStyle {
+    fn default() -> Self {
+        Self {
+            font_height: 50.0,
+            color: vec3(1.0, 1.0, 1.0),
+            outline: true,
+            layout_width: 1600.0,
+            text_layout: MessageTextLayout::Center,
+            bottom_margin: 60.0,
+        }
+    }
+}
+
+struct ShownCue {
+    index: usize,
+    /// Time since the cue was shown, used to fade it in
+    time: Ticks,
+    vertex_buffer: VertexBuffer<TextVertex>,
+    used_codepoints: Vec<u16>,
+    font_atlas: Arc<FontAtlas>,
+}
+
+impl Drop for ShownCue {
+    fn drop(&mut self) {
+        for &codepoint in self.used_codepoints.iter() {
+            self.font_atlas.free_glyph(codepoint);
+        }
+    }
+}
+
+/// Displays the subtitle cues synchronized to the movie position
+pub struct SubtitleRenderer {
+    subtitles: Arc<Subtitles>,
+    font_atlas: Arc<FontAtlas>,
+    style: SubtitleStyle,
+    shown_cue: Option<ShownCue>,
+}
+
+impl SubtitleRenderer {
+    pub fn new(
+        subtitles: Arc<Subtitles>,
+        font_atlas: Arc<FontAtlas>,
+        style: SubtitleStyle,
+    ) -> Self {
+        Self {
+            subtitles,
+            font_atlas,
+            style,
+            shown_cue: None,
+        }
+    }
+
+    pub fn update(&mut self, resources: &GpuCommonResources, position: Duration, delta: Ticks) {
+        let cue = self.subtitles.cue_at(position);
+
+        match cue {
+            Some((index, _)) if self.shown_cue.as_ref().is_some_and(|c| c.index == index) => {
+                self.shown_cue.as_mut().unwrap().time += delta;
+            }
+            Some((index, cue)) => {
+                // drop the old cue first, so that its glyphs can be freed before the new ones are allocated
+                self.shown_cue = None;
+                self.shown_cue = Some(self.layout_cue(resources, index, &cue.text));
+            }
+            None => self.shown_cue = None,
+        }
+    }
+
+    fn layout_cue(&self, resources: &GpuCommonResources, index: usize, text: &str) -> ShownCue {
+        // convert the plain text to the layouter markup (the font only covers the BMP)
+        let text = text
+            .chars()
+            .filter(|&c| (c as u32) < 0x10000)
+            .collect::<String>()
+            .replace('@', "＠")
+            .replace('\n', "@r");
+
+        let params = LayoutParams {
+            font: self.font_atlas.get_font(),
+            layout_width: self.style.layout_width,
+            character_name_layout_width: 0.0,
+            base_font_height: self.style.font_height,
+            furigana_font_height: self.style.font_height * 0.4,
+            font_horizontal_base_scale: 0.9697,
+            text_layout: self.style.text_layout,
+            default_state: LayouterState {
+                text_color: self.style.color,
+                instant: true,
+                ..Default::default()
+            },
+            has_character_name: false,
+            mode: LayoutingMode::GenericText,
+            // subtitles are often translations, break the lines between words
+            line_breaking: LineBreaking::Word { hyphenator: None },
+            ruby: Default::default(),
+            direction: Default::default(),
+        };
+
+        let LayoutedMessage { chars, .. } = shin_core::layout::layout_text(params, &text);
+
+        // anchor the last line to the bottom of the screen
+        let last_baseline = chars.iter().map(|c| c.position.y).fold(0.0, f32::max);
+        let base_position = vec2(
+            -self.style.layout_width / 2.0,
+            540.0 - self.style.bottom_margin - last_baseline,
+        );
+
+        let mut used_codepoints = Vec::new();
+        let vertices = build_text_vertices(
+            resources,
+            &self.font_atlas,
+            base_position,
+            chars,
+            &mut used_codepoints,
+        );
+
+        ShownCue {
+            index,
+            time: Ticks::ZERO,
+            vertex_buffer: VertexBuffer::new(resources, &vertices, Some("Subtitle VertexBuffer")),
+            used_codepoints,
+            font_atlas: self.font_atlas.clone(),
+        }
+    }
+
+    pub fn render<'enc>(
+        &'enc self,
+        resources: &'enc GpuCommonResources,
+        render_pass: &mut wgpu::RenderPass<'enc>,
+        transform: Mat4,
+    ) {
+        const OUTLINE_DISTANCE: f32 = 3.5;
+
+        let Some(cue) = &self.shown_cue else {
+            return;
+        };
+
+        render_pass.push_debug_group("Subtitle");
+        if self.style.outline {
+            let atlas_size = self.font_atlas.texture_size();
+            let scaled_distance = OUTLINE_DISTANCE / vec2(atlas_size.0 as f32, atlas_size.1 as f32);
+
+            resources.draw_text_outline(
+                render_pass,
+                cue.vertex_buffer.vertex_source(),
+                self.font_atlas.texture_bind_group(),
+                transform,
+                cue.time,
+                scaled_distance,
+            );
+        }
+        resources.draw_text(
+            render_pass,
+            cue.vertex_buffer.vertex_source(),
+            self.font_atlas.texture_bind_group(),
+            transform,
+            cue.time,
+        );
+        render_pass.pop_debug_group();
+    }
+}
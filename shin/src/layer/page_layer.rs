@@ -1,6 +1,6 @@
 use glam::Mat4;
 use shin_core::vm::command::types::PLANES_COUNT;
-use shin_render::{GpuCommonResources, RenderTarget, Renderable};
+use shin_render::{GpuCommonResources, PooledRenderTarget, Renderable};
 
 use crate::{
     layer::{Layer, LayerGroup, LayerProperties},
@@ -10,16 +10,12 @@ use crate::{
 pub struct PageLayer {
     planes: [LayerGroup; PLANES_COUNT],
     properties: LayerProperties,
-    render_target: RenderTarget,
+    render_target: PooledRenderTarget,
 }
 
 impl PageLayer {
     pub fn new(resources: &GpuCommonResources) -> Self {
-        let render_target = RenderTarget::new(
-            resources,
-            resources.current_render_buffer_size(),
-            Some("LayerGroup RenderTarget"),
-        );
+        let render_target = resources.acquire_render_target(Some("LayerGroup RenderTarget"));
 
         Self {
             planes: [
@@ -44,6 +40,7 @@ impl PageLayer {
 
 impl Updatable for PageLayer {
     fn update(&mut self, context: &UpdateContext) {
+        self.properties.update(context);
         for plane in self.planes.iter_mut() {
             plane.update(context);
         }
@@ -60,9 +57,11 @@ impl Renderable for PageLayer {
     ) {
         {
             let mut encoder = resources.start_encoder();
-            let mut render_pass = self
-                .render_target
-                .begin_srgb_render_pass(&mut encoder, Some("PageLayer RenderPass"));
+            let mut render_pass = self.render_target.begin_srgb_render_pass(
+                resources,
+                &mut encoder,
+                Some("PageLayer RenderPass"),
+            );
 
             let transform = self.properties.compute_transform(transform);
             let projection = self.render_target.projection_matrix();
@@ -77,12 +76,13 @@ impl Renderable for PageLayer {
         }
 
         render_pass.push_debug_group("PageLayer Render");
-        // TODO use layer pseudo-pipeline
-        resources.draw_sprite(
+        resources.draw_layer(
             render_pass,
             self.render_target.vertex_source(),
             self.render_target.bind_group(),
             projection,
+            self.properties.fragment_shader(),
+            self.properties.shader_params(),
         );
         render_pass.pop_debug_group();
     }
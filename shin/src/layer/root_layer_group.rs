@@ -1,5 +1,5 @@
 use glam::Mat4;
-use shin_render::{GpuCommonResources, RenderTarget, Renderable};
+use shin_render::{GpuCommonResources, PooledRenderTarget, Renderable};
 
 use crate::{
     layer::{screen_layer::ScreenLayer, Layer, LayerProperties, MessageLayer},
@@ -9,7 +9,7 @@ use crate::{
 pub struct RootLayerGroup {
     screen_layer: ScreenLayer,
     message_layer: MessageLayer,
-    render_target: RenderTarget,
+    render_target: PooledRenderTarget,
     properties: LayerProperties,
 }
 
@@ -19,11 +19,7 @@ impl RootLayerGroup {
         screen_layer: ScreenLayer,
         message_layer: MessageLayer,
     ) -> Self {
-        let render_target = RenderTarget::new(
-            resources,
-            resources.current_render_buffer_size(),
-            Some("LayerGroup RenderTarget"),
-        );
+        let render_target = resources.acquire_render_target(Some("LayerGroup RenderTarget"));
 
         Self {
             screen_layer,
@@ -68,9 +64,11 @@ impl Renderable for RootLayerGroup {
     ) {
         {
             let mut encoder = resources.start_encoder();
-            let mut render_pass = self
-                .render_target
-                .begin_srgb_render_pass(&mut encoder, Some("RootLayerGroup RenderPass"));
+            let mut render_pass = self.render_target.begin_srgb_render_pass(
+                resources,
+                &mut encoder,
+                Some("RootLayerGroup RenderPass"),
+            );
 
             let transform = self.properties.compute_transform(transform);
             let projection = self.render_target.projection_matrix();
@@ -87,12 +85,13 @@ impl Renderable for RootLayerGroup {
         }
 
         render_pass.push_debug_group("RootLayerGroup Render");
-        // TODO use layer pseudo-pipeline
-        resources.draw_sprite(
+        resources.draw_layer(
             render_pass,
             self.render_target.vertex_source(),
             self.render_target.bind_group(),
             projection,
+            self.properties.fragment_shader(),
+            self.properties.shader_params(),
         );
         render_pass.pop_debug_group();
     }
@@ -1,5 +1,5 @@
 use glam::Mat4;
-use shin_render::{GpuCommonResources, RenderTarget, Renderable};
+use shin_render::{GpuCommonResources, PooledRenderTarget, Renderable};
 
 use crate::{
     layer::{page_layer::PageLayer, Layer, LayerProperties},
@@ -9,7 +9,7 @@ use crate::{
 pub struct ScreenLayer {
     page_layer: PageLayer,
     properties: LayerProperties,
-    render_target: RenderTarget,
+    render_target: PooledRenderTarget,
     // TODO: a TransitionLayer (two kinds??) should be here
 }
 
@@ -18,11 +18,7 @@ impl ScreenLayer {
         Self {
             page_layer: PageLayer::new(resources),
             properties: LayerProperties::new(),
-            render_target: RenderTarget::new(
-                resources,
-                resources.current_render_buffer_size(),
-                Some("ScreenLayer RenderTarget"),
-            ),
+            render_target: resources.acquire_render_target(Some("ScreenLayer RenderTarget")),
         }
     }
 
@@ -52,9 +48,11 @@ impl Renderable for ScreenLayer {
     ) {
         {
             let mut encoder = resources.start_encoder();
-            let mut render_pass = self
-                .render_target
-                .begin_srgb_render_pass(&mut encoder, Some("PageLayer RenderPass"));
+            let mut render_pass = self.render_target.begin_srgb_render_pass(
+                resources,
+                &mut encoder,
+                Some("ScreenLayer RenderPass"),
+            );
 
             let transform = self.properties.compute_transform(transform);
             let projection = self.render_target.projection_matrix();
@@ -64,12 +62,13 @@ impl Renderable for ScreenLayer {
         }
 
         render_pass.push_debug_group("ScreenLayer Render");
-        // TODO use layer pseudo-pipeline
-        resources.draw_sprite(
+        resources.draw_layer(
             render_pass,
             self.render_target.vertex_source(),
             self.render_target.bind_group(),
             projection,
+            self.properties.fragment_shader(),
+            self.properties.shader_params(),
         );
         render_pass.pop_debug_group();
     }
@@ -0,0 +1,337 @@
+//! A serializable form of the layers state, to be stored in the saves and the rollback history.
+//!
+//! The snapshot is built from the [`LayersState`] kept by the VM state, not from the layers themselves:
+//! it's enough to re-create all the layers (the layer types and the LAYERLOAD parameters select the loaded assets).
+//! Like the rollback, the snapshot only keeps the target property values, the tweens and the mask transitions in progress are jumped to the end when restoring.
+//!
+//! To keep the old saves loadable, the schema only uses the numbers defined by the game (layer types, property ids, mask flags) instead of the engine's enums.
+//! The properties left at their initial values are not stored, so the properties added later just get their initial values.
+//! The schema is versioned by [`LAYERS_SNAPSHOT_VERSION`], see [`crate::snapshot`].
+
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Context, Result};
+use num_traits::FromPrimitive;
+use serde::{Deserialize, Serialize};
+use shin_core::vm::command::types::{
+    LayerId, LayerProperty, LayerType, MaskFlags, LAYERS_COUNT, PLANES_COUNT,
+};
+use tracing::warn;
+
+use crate::{
+    adv::vm_state::layers::{LayerSelection, LayerState, LayersState, MaskState, PlaneState},
+    snapshot::check_version,
+};
+
+/// Bumped on the incompatible changes to the layer numbering or the property storage
+pub const LAYERS_SNAPSHOT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct LayerInitSnapshot {
+    layer_type: i32,
+    params: [i32; 8],
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+struct LayerSnapshot {
+    /// `None` for the layer groups and the layers not initialized yet
+    #[serde(skip_serializing_if = "Option::is_none")]
+    init: Option<LayerInitSnapshot>,
+    /// Property id to value, only the ones that differ from the initial values
+    properties: BTreeMap<i32, i32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct MaskSnapshot {
+    mask_id: i32,
+    flags: i32,
+    transition: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+struct PlaneSnapshot {
+    group: LayerSnapshot,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mask: Option<MaskSnapshot>,
+    layers: BTreeMap<u32, LayerSnapshot>,
+}
+
+/// The layers state in a form that is stable across the engine versions
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LayersSnapshot {
+    version: u32,
+    #[serde(default)]
+    current_plane: u32,
+    /// The LAYERSELECT range, inclusive
+    #[serde(default)]
+    layer_selection: Option<(u32, u32)>,
+    #[serde(default)]
+    planes: Vec<PlaneSnapshot>,
+    #[serde(default)]
+    root_layer_group: LayerSnapshot,
+    #[serde(default)]
+    screen_layer: LayerSnapshot,
+    #[serde(default)]
+    page_layer: LayerSnapshot,
+}
+
+impl LayerSnapshot {
+    fn capture(state: &LayerState) -> Self {
+        Self {
+            init: state
+                .layerinit_params
+                .map(|(layer_type, params)| LayerInitSnapshot {
+                    layer_type: layer_type as i32,
+                    params: params.into(),
+                }),
+            properties: state
+                .properties
+                .iter()
+                .filter(|&(prop, value)| value != prop.initial_value())
+                .map(|(prop, value)| (prop as i32, value))
+                .collect(),
+        }
+    }
+
+    fn restore(&self) -> Result<LayerState> {
+        let mut state = LayerState::new();
+
+        if let Some(init) = &self.init {
+            let layer_type = LayerType::from_i32(init.layer_type)
+                .with_context(|| format!("Unknown layer type {}", init.layer_type))?;
+            state.layerinit_params = Some((layer_type, init.params.into()));
+        }
+        for (&id, &value) in &self.properties {
+            match LayerProperty::from_i32(id) {
+                Some(prop) => state.properties.set_property(prop, value),
+                // might be a property of a newer engine version, the layer will still look mostly right
+                None => warn!("Ignoring an unknown layer property {} in the snapshot", id),
+            }
+        }
+
+        Ok(state)
+    }
+}
+
+impl PlaneSnapshot {
+    fn capture(state: &PlaneState) -> Self {
+        Self {
+            group: LayerSnapshot::capture(&state.group),
+            mask: state.mask.map(|mask| MaskSnapshot {
+                mask_id: mask.mask_id,
+                flags: mask.flags.bits(),
+                transition: mask.transition,
+            }),
+            layers: state
+                .layers
+                .iter()
+                .map(|(id, layer)| (id.raw(), LayerSnapshot::capture(layer)))
+                .collect(),
+        }
+    }
+
+    fn restore(&self) -> Result<PlaneState> {
+        let mut state = PlaneState::new();
+
+        state.group = self.group.restore().context("Restoring the plane group")?;
+        state.mask = self.mask.as_ref().map(|mask| MaskState {
+            mask_id: mask.mask_id,
+            flags: MaskFlags::from_bits_retain(mask.flags),
+            transition: mask.transition,
+        });
+        for (&id, layer) in &self.layers {
+            if id >= LAYERS_COUNT {
+                bail!("Layer id {} is out of range", id);
+            }
+            let layer = layer
+                .restore()
+                .with_context(|| format!("Restoring layer {}", id))?;
+            state.layers.insert(LayerId::new(id), layer);
+        }
+
+        Ok(state)
+    }
+}
+
+impl LayersSnapshot {
+    pub fn capture(state: &LayersState) -> Self {
+        Self {
+            version: LAYERS_SNAPSHOT_VERSION,
+            current_plane: state.current_plane,
+            layer_selection: state
+                .layer_selection
+                .map(|selection| (selection.low.raw(), selection.high.raw())),
+            planes: state.planes.iter().map(PlaneSnapshot::capture).collect(),
+            root_layer_group: LayerSnapshot::capture(&state.root_layer_group),
+            screen_layer: LayerSnapshot::capture(&state.screen_layer),
+            page_layer: LayerSnapshot::capture(&state.page_layer),
+        }
+    }
+
+    /// Re-creates the layers state, failing if the snapshot is from a newer engine or is corrupt
+    pub fn restore(&self) -> Result<LayersState> {
+        check_version("layers snapshot", self.version, LAYERS_SNAPSHOT_VERSION)?;
+        if self.current_plane as usize >= PLANES_COUNT {
+            bail!("Plane {} is out of range", self.current_plane);
+        }
+        if self.planes.len() > PLANES_COUNT {
+            bail!("Too many planes: {}", self.planes.len());
+        }
+
+        let mut state = LayersState::new();
+        state.current_plane = self.current_plane;
+        state.layer_selection = match self.layer_selection {
+            Some((low, high)) => {
+                if low >= LAYERS_COUNT || high >= LAYERS_COUNT {
+                    bail!("Layer selection {}..={} is out of range", low, high);
+                }
+                Some(LayerSelection::new(LayerId::new(low), LayerId::new(high)))
+            }
+            None => None,
+        };
+        for (index, plane) in self.planes.iter().enumerate() {
+            state.planes[index] = plane
+                .restore()
+                .with_context(|| format!("Restoring plane {}", index))?;
+        }
+        state.root_layer_group = self
+            .root_layer_group
+            .restore()
+            .context("Restoring the root layer group")?;
+        state.screen_layer = self
+            .screen_layer
+            .restore()
+            .context("Restoring the screen layer")?;
+        state.page_layer = self
+            .page_layer
+            .restore()
+            .context("Restoring the page layer")?;
+
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshot::json_roundtrip;
+
+    fn sample_state() -> LayersState {
+        let mut state = LayersState::new();
+
+        let layer = state.alloc(LayerId::new(10));
+        layer.layerinit_params = Some((LayerType::Picture, (42, 0, 0, 0, 0, 0, 0, 0)));
+        layer
+            .properties
+            .set_property(LayerProperty::TranslateX, 100);
+        layer.properties.set_property(LayerProperty::ScaleX, 500);
+        state.alloc(LayerId::new(12));
+
+        state.current_plane = 2;
+        let layer = state.alloc(LayerId::new(3));
+        layer.layerinit_params = Some((LayerType::Bustup, (1, 2, 3, 4, 5, 6, 7, 8)));
+        state.planes[2].mask = Some(MaskState {
+            mask_id: 7,
+            flags: MaskFlags::FLIP_X,
+            transition: true,
+        });
+        state.layer_selection = Some(LayerSelection::new(LayerId::new(5), LayerId::new(1)));
+
+        state
+            .screen_layer
+            .properties
+            .set_property(LayerProperty::MosaicSize, 30);
+
+        state
+    }
+
+    #[test]
+    fn roundtrip() {
+        let state = sample_state();
+        let snapshot = LayersSnapshot::capture(&state);
+
+        let restored = json_roundtrip(&snapshot).restore().unwrap();
+        // the restored state must produce the same snapshot
+        assert_eq!(LayersSnapshot::capture(&restored), snapshot);
+
+        assert_eq!(restored.current_plane, 2);
+        assert_eq!(
+            restored.layer_selection,
+            Some(LayerSelection::new(LayerId::new(1), LayerId::new(5)))
+        );
+        let layer = restored.planes[0].get_layer(LayerId::new(10)).unwrap();
+        assert_eq!(
+            layer.layerinit_params,
+            Some((LayerType::Picture, (42, 0, 0, 0, 0, 0, 0, 0)))
+        );
+        assert_eq!(
+            layer.properties.get_property(LayerProperty::TranslateX),
+            100
+        );
+        assert!(restored.planes[0].get_layer(LayerId::new(12)).is_some());
+        let mask = restored.planes[2].mask.unwrap();
+        assert_eq!(mask.mask_id, 7);
+        assert_eq!(mask.flags, MaskFlags::FLIP_X);
+        assert!(mask.transition);
+    }
+
+    #[test]
+    fn initial_properties_are_not_stored() {
+        let snapshot = LayersSnapshot::capture(&LayersState::new());
+        let json = serde_json::to_value(&snapshot).unwrap();
+
+        assert_eq!(
+            json["root_layer_group"]["properties"],
+            serde_json::json!({})
+        );
+        assert_eq!(json["planes"][0]["layers"], serde_json::json!({}));
+    }
+
+    #[test]
+    fn missing_fields_use_defaults() {
+        // a minimal snapshot, like an older schema lacking the newer fields would look
+        let snapshot: LayersSnapshot = serde_json::from_str(
+            r#"{"version": 1, "planes": [{"layers": {"4": {"init": {"layer_type": 0, "params": [0, 0, 0, 0, 0, 0, 0, 0]}}}}]}"#,
+        )
+        .unwrap();
+        let state = snapshot.restore().unwrap();
+
+        let layer = state.planes[0].get_layer(LayerId::new(4)).unwrap();
+        assert_eq!(
+            layer.layerinit_params,
+            Some((LayerType::Null, (0, 0, 0, 0, 0, 0, 0, 0)))
+        );
+        assert_eq!(
+            layer.properties.get_property(LayerProperty::ScaleX),
+            LayerProperty::ScaleX.initial_value()
+        );
+    }
+
+    #[test]
+    fn unknown_property_is_ignored() {
+        let snapshot: LayersSnapshot =
+            serde_json::from_str(r#"{"version": 1, "page_layer": {"properties": {"9999": 5}}}"#)
+                .unwrap();
+        let state = snapshot.restore().unwrap();
+        assert!(state
+            .page_layer
+            .properties
+            .iter()
+            .all(|(prop, value)| value == prop.initial_value()));
+    }
+
+    #[test]
+    fn rejects_unknown_layers() {
+        let bad_layer: LayersSnapshot =
+            serde_json::from_str(r#"{"version": 1, "planes": [{"layers": {"256": {}}}]}"#).unwrap();
+        assert!(bad_layer.restore().is_err());
+
+        let bad_type: LayersSnapshot = serde_json::from_str(
+            r#"{"version": 1, "screen_layer": {"init": {"layer_type": 100, "params": [0, 0, 0, 0, 0, 0, 0, 0]}}}"#,
+        )
+        .unwrap();
+        assert!(bad_type.restore().is_err());
+    }
+}
@@ -0,0 +1,179 @@
+//! Notification toasts, shown in the bottom left corner of the screen.
+//!
+//! The game uses them to tell the player about the save info changes, the new tips and the unlocked extras.
+//! The toasts are queued and shown one at a time, sliding in from the left edge.
+
+use std::{collections::VecDeque, sync::Arc};
+
+use glam::{vec2, vec3, Mat4};
+use shin_core::time::{Easing, Ticks, Tween, Tweener};
+use shin_render::{GpuCommonResources, LazyGpuTexture, Renderable};
+
+use crate::{
+    layer::{FontAtlas, Layer, LayerProperties},
+    update::{Updatable, UpdateContext},
+    widget::{default_nine_patch, default_window_image, Label, Window},
+};
+
+const TOAST_WIDTH: f32 = 640.0;
+const TOAST_HEIGHT: f32 = 72.0;
+const TOAST_MARGIN: f32 = 32.0;
+const TOAST_PADDING: f32 = 24.0;
+const FONT_HEIGHT: f32 = 36.0;
+/// How far to the left the toast starts sliding in from
+const SLIDE_DISTANCE: f32 = 120.0;
+const SLIDE: Tween = Tween {
+    duration: Ticks::from_f32(15.0),
+    easing: Easing::SineOut,
+};
+/// How long a toast stays on the screen, not counting the slide in
+const SHOW_DURATION: Ticks = Ticks::from_f32(180.0);
+
+struct ShownToast {
+    window: Window,
+    label: Label,
+    /// 1 when out of the screen, 0 when in place
+    ///
+    /// Slides in and holds for [`SHOW_DURATION`], then slides out.
+    slide: Tweener,
+    hiding: bool,
+}
+
+pub struct ToastLayer {
+    props: LayerProperties,
+    font_atlas: Arc<FontAtlas>,
+    window_texture: LazyGpuTexture,
+    queue: VecDeque<String>,
+    shown: Option<ShownToast>,
+}
+
+impl ToastLayer {
+    pub fn new(font_atlas: Arc<FontAtlas>) -> Self {
+        Self {
+            props: LayerProperties::new(),
+            font_atlas,
+            window_texture: LazyGpuTexture::new(default_window_image(), Some("Toast Window")),
+            queue: VecDeque::new(),
+            shown: None,
+        }
+    }
+
+    /// Queues a toast with a plain `text` (not the layouter markup)
+    pub fn push(&mut self, text: &str) {
+        // the font only covers the BMP
+        let text = text
+            .chars()
+            .filter(|&c| (c as u32) < 0x10000)
+            .collect::<String>()
+            .replace('@', "＠");
+
+        // the scenario can send the same notification several times in a row, no need to repeat it
+        if self.queue.back() == Some(&text) {
+            return;
+        }
+        self.queue.push_back(text);
+    }
+
+    fn show(&self, resources: &GpuCommonResources, text: &str) -> ShownToast {
+        let left = -960.0 + TOAST_MARGIN;
+        let bottom = 540.0 - TOAST_MARGIN;
+        let top = bottom - TOAST_HEIGHT;
+
+        let mut window = Window::new(
+            resources,
+            default_nine_patch(),
+            (left, top, left + TOAST_WIDTH, bottom),
+        );
+        window.show(SLIDE);
+        let label = Label::new(
+            resources,
+            self.font_atlas.clone(),
+            text,
+            vec2(
+                left + TOAST_PADDING,
+                top + (TOAST_HEIGHT - FONT_HEIGHT) / 2.0,
+            ),
+            TOAST_WIDTH - TOAST_PADDING * 2.0,
+            FONT_HEIGHT,
+        );
+        let mut slide = Tweener::new(1.0);
+        slide.enqueue(0.0, SLIDE);
+        slide.enqueue_hold(SHOW_DURATION);
+
+        ShownToast {
+            window,
+            label,
+            slide,
+            hiding: false,
+        }
+    }
+}
+
+impl Updatable for ToastLayer {
+    fn update(&mut self, context: &UpdateContext) {
+        self.props.update(context);
+
+        if self.shown.is_none() {
+            if let Some(text) = self.queue.pop_front() {
+                self.shown = Some(self.show(context.gpu_resources, &text));
+            }
+        }
+
+        let Some(toast) = &mut self.shown else {
+            return;
+        };
+        toast.window.update(context);
+        let finished = toast.slide.update(context.time_delta_ticks());
+
+        if finished && !toast.hiding {
+            toast.hiding = true;
+            toast.window.hide(SLIDE);
+            toast.slide.enqueue(1.0, SLIDE);
+        }
+        if toast.hiding && toast.slide.is_idle() && toast.window.is_idle() {
+            self.shown = None;
+        }
+    }
+}
+
+impl Renderable for ToastLayer {
+    fn render<'enc>(
+        &'enc self,
+        resources: &'enc GpuCommonResources,
+        render_pass: &mut wgpu::RenderPass<'enc>,
+        transform: Mat4,
+        projection: Mat4,
+    ) {
+        let Some(toast) = &self.shown else {
+            return;
+        };
+
+        let transform = projection
+            * self.props.compute_transform(transform)
+            * Mat4::from_translation(vec3(-toast.slide.value() * SLIDE_DISTANCE, 0.0, 0.0));
+
+        render_pass.push_debug_group("Toast");
+        toast.window.render(
+            resources,
+            render_pass,
+            self.window_texture.gpu_texture(resources),
+            transform,
+        );
+        toast
+            .label
+            .render(resources, render_pass, transform, toast.window.opacity());
+        render_pass.pop_debug_group();
+    }
+
+    fn resize(&mut self, _resources: &GpuCommonResources) {}
+}
+
+impl Layer for ToastLayer {
+    fn properties(&self) -> &LayerProperties {
+        &self.props
+    }
+
+    fn properties_mut(&mut self) -> &mut LayerProperties {
+        &mut self.props
+    }
+}
@@ -5,21 +5,43 @@ extern crate self as shin;
 
 use clap::Parser;
 
+mod achievements;
 mod asset;
 // mod camera;
 mod adv;
+mod app;
 mod audio;
+mod aux_window;
+mod capture;
 mod cli;
+mod config;
 mod fps_counter;
+mod i18n;
 mod input;
 mod layer;
+mod profiler;
 mod render;
+mod settings;
+mod snapshot;
 mod time;
+mod unlocks;
 mod update;
+mod widget;
 mod window;
 
 fn main() {
     let cli = cli::Cli::parse();
+    let config = match config::Config::load(&cli) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{:#}", err);
+            std::process::exit(1);
+        }
+    };
+    if cli.print_config {
+        print!("{}", config.to_toml());
+        return;
+    }
 
-    pollster::block_on(window::run(cli));
+    pollster::block_on(window::run(cli, config));
 }
@@ -0,0 +1,204 @@
+//! Frame profiler, measuring the CPU time of the update and render stages and the GPU time of the render passes.
+//!
+//! The last measured frame is shown in the overlay. The whole session can also be recorded as a chrome trace (open it in `chrome://tracing` or Perfetto), which is written when the game exits.
+//!
+//! The GPU timings arrive a couple of frames late and the GPU clock is not synchronized with the CPU one, so in the trace they are placed at the start of the frame they were read back in.
+
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+use shin_render::GpuPassTiming;
+use tracing::{info, warn};
+
+use crate::render::overlay::{OverlayCollector, OverlayVisitable};
+
+/// The CPU time of a stage of the frame
+#[derive(Debug, Clone)]
+pub struct CpuSpan {
+    pub name: &'static str,
+    /// Since the start of the frame
+    pub start: Duration,
+    pub duration: Duration,
+}
+
+/// An event in the chrome trace format, with the times in microseconds
+#[derive(Serialize)]
+struct TraceEvent {
+    name: String,
+    ph: &'static str,
+    ts: f64,
+    dur: f64,
+    pid: u32,
+    tid: u32,
+}
+
+const CPU_THREAD: u32 = 0;
+const GPU_THREAD: u32 = 1;
+
+struct Trace {
+    path: PathBuf,
+    events: Vec<TraceEvent>,
+}
+
+pub struct Profiler {
+    enabled: bool,
+    session_start: Instant,
+    frame_start: Instant,
+    current_frame: Vec<CpuSpan>,
+    last_frame: Vec<CpuSpan>,
+    last_frame_time: Duration,
+    last_gpu_frame: Vec<GpuPassTiming>,
+    trace: Option<Trace>,
+}
+
+impl Profiler {
+    /// A disabled profiler doesn't measure anything, `trace_path` enables it
+    pub fn new(enabled: bool, trace_path: Option<PathBuf>) -> Self {
+        let now = Instant::now();
+        Self {
+            enabled: enabled || trace_path.is_some(),
+            session_start: now,
+            frame_start: now,
+            current_frame: Vec::new(),
+            last_frame: Vec::new(),
+            last_frame_time: Duration::ZERO,
+            last_gpu_frame: Vec::new(),
+            trace: trace_path.map(|path| Trace {
+                path,
+                events: Vec::new(),
+            }),
+        }
+    }
+
+    pub fn begin_frame(&mut self) {
+        if !self.enabled {
+            return;
+        }
+        self.frame_start = Instant::now();
+        self.current_frame.clear();
+    }
+
+    /// Returns the time to pass to [`Profiler::end_stage`]
+    pub fn begin_stage(&self) -> Instant {
+        Instant::now()
+    }
+
+    pub fn end_stage(&mut self, name: &'static str, start: Instant) {
+        if !self.enabled {
+            return;
+        }
+        self.current_frame.push(CpuSpan {
+            name,
+            start: start.saturating_duration_since(self.frame_start),
+            duration: start.elapsed(),
+        });
+    }
+
+    /// Finishes the frame, `gpu_timings` are the GPU timings that were read back during it (see [`shin_render::GpuProfiler::end_frame`])
+    pub fn end_frame(&mut self, gpu_timings: Option<Vec<GpuPassTiming>>) {
+        if !self.enabled {
+            return;
+        }
+
+        self.last_frame_time = self.frame_start.elapsed();
+        self.last_frame = std::mem::take(&mut self.current_frame);
+        let gpu_timings_read = gpu_timings.is_some();
+        if let Some(gpu_timings) = gpu_timings {
+            self.last_gpu_frame = gpu_timings;
+        }
+
+        if let Some(trace) = &mut self.trace {
+            let frame_ts = self
+                .frame_start
+                .saturating_duration_since(self.session_start);
+            let us = |d: Duration| d.as_secs_f64() * 1_000_000.0;
+
+            trace.events.push(TraceEvent {
+                name: "Frame".to_string(),
+                ph: "X",
+                ts: us(frame_ts),
+                dur: us(self.last_frame_time),
+                pid: 0,
+                tid: CPU_THREAD,
+            });
+            trace
+                .events
+                .extend(self.last_frame.iter().map(|span| TraceEvent {
+                    name: span.name.to_string(),
+                    ph: "X",
+                    ts: us(frame_ts + span.start),
+                    dur: us(span.duration),
+                    pid: 0,
+                    tid: CPU_THREAD,
+                }));
+            if gpu_timings_read {
+                trace
+                    .events
+                    .extend(self.last_gpu_frame.iter().map(|pass| TraceEvent {
+                        name: pass.label.clone(),
+                        ph: "X",
+                        ts: us(frame_ts + pass.start),
+                        dur: us(pass.duration),
+                        pid: 0,
+                        tid: GPU_THREAD,
+                    }));
+            }
+        }
+    }
+
+    /// Writes the chrome trace, if it was recorded
+    pub fn finish(&mut self) {
+        let Some(trace) = self.trace.take() else {
+            return;
+        };
+
+        let result = std::fs::File::create(&trace.path)
+            .map_err(anyhow::Error::from)
+            .and_then(|file| {
+                serde_json::to_writer(std::io::BufWriter::new(file), &trace.events)
+                    .map_err(anyhow::Error::from)
+            });
+        match result {
+            Ok(()) => info!("Saved the profiler trace to {}", trace.path.display()),
+            Err(e) => warn!("Failed to save the profiler trace: {}", e),
+        }
+    }
+}
+
+impl OverlayVisitable for Profiler {
+    fn visit_overlay(&self, collector: &mut OverlayCollector) {
+        if !self.enabled {
+            return;
+        }
+
+        collector.overlay(
+            "Profiler",
+            |_ctx, top_left| {
+                let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+
+                top_left.label(format!("CPU frame: {:.2} ms", ms(self.last_frame_time)));
+                for span in &self.last_frame {
+                    top_left.label(format!("  {}: {:.2} ms", span.name, ms(span.duration)));
+                }
+
+                if self.last_gpu_frame.is_empty() {
+                    top_left.label("GPU: no timestamps (the device may not support them)");
+                } else {
+                    let total = self
+                        .last_gpu_frame
+                        .iter()
+                        .map(|pass| pass.duration)
+                        .sum::<Duration>();
+                    top_left.label(format!("GPU passes: {:.2} ms", ms(total)));
+                    for pass in &self.last_gpu_frame {
+                        top_left.label(format!("  {}: {:.3} ms", pass.label, ms(pass.duration)));
+                    }
+                }
+            },
+            false,
+        );
+    }
+}
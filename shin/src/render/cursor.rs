@@ -0,0 +1,35 @@
+//! The image of the [`VirtualCursor`](crate::input::VirtualCursor), a white dot with a dark outline, visible on any background.
+
+use glam::vec2;
+use image::{Rgba, RgbaImage};
+use shin_render::{GpuCommonResources, GpuImage};
+
+const SIZE: u32 = 36;
+const OUTLINE_WIDTH: f32 = 4.0;
+
+fn make_image() -> RgbaImage {
+    let center = SIZE as f32 / 2.0;
+    let radius = center - 1.0;
+
+    RgbaImage::from_fn(SIZE, SIZE, |x, y| {
+        let distance = vec2(x as f32 + 0.5 - center, y as f32 + 0.5 - center).length();
+        // one pixel of antialiasing at the edge
+        let alpha = (radius - distance + 0.5).clamp(0.0, 1.0);
+        let value = if distance < radius - OUTLINE_WIDTH {
+            255
+        } else {
+            32
+        };
+        Rgba([value, value, value, (alpha * 255.0) as u8])
+    })
+}
+
+/// The image is centered at the cursor position
+pub fn load(resources: &GpuCommonResources) -> GpuImage {
+    GpuImage::load(
+        resources,
+        &make_image(),
+        vec2(SIZE as f32 / 2.0, SIZE as f32 / 2.0),
+        Some("Virtual Cursor"),
+    )
+}
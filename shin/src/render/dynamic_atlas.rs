@@ -1,12 +1,15 @@
 use std::{
     ops::Deref,
-    sync::{Mutex, RwLock},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, RwLock,
+    },
 };
 
 use bevy_utils::{Entry, HashMap};
 use glam::{vec2, Vec2};
-use shin_render::{GpuCommonResources, TextureBindGroup};
-use tracing::info;
+use shin_render::{GpuCommonResources, SamplerMode, TextureBindGroup};
+use tracing::debug;
 use usvg::{tiny_skia_path, NodeKind, NormalizedF32, TreeParsing};
 
 use crate::render::overlay::{OverlayCollector, OverlayVisitable};
@@ -36,6 +39,13 @@ impl AtlasAllocation {
     }
 }
 
+/// An image that is still in the atlas, but is not used anymore
+struct UnusedAllocation {
+    allocation: etagere::Allocation,
+    /// Value of the atlas use counter when the image was freed, used to evict the least recently used images first
+    last_used: u64,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct AtlasImage {
     pub position: Vec2,
@@ -58,7 +68,23 @@ pub struct DynamicAtlas<P: ImageProvider> {
     /// These are the images that are currently in the atlas and cannot be evicted.
     active_allocations: RwLock<HashMap<P::Id, AtlasAllocation>>,
     /// These are images still in the atlas, but can be evicted.
-    eviction_ready: Mutex<HashMap<P::Id, etagere::Allocation>>,
+    eviction_ready: Mutex<HashMap<P::Id, UnusedAllocation>>,
+    /// Incremented each time an image is freed, serves as a clock for the LRU eviction
+    use_counter: AtomicU64,
+}
+
+fn new_allocator(texture_size: (u32, u32)) -> etagere::BucketedAtlasAllocator {
+    etagere::BucketedAtlasAllocator::with_options(
+        etagere::Size::new(
+            texture_size.0.try_into().unwrap(),
+            texture_size.1.try_into().unwrap(),
+        ),
+        &etagere::AllocatorOptions {
+            alignment: etagere::Size::new(8, 8), // TODO: make this configurable
+            vertical_shelves: false,
+            num_columns: 1,
+        },
+    )
 }
 
 impl<P: ImageProvider> DynamicAtlas<P> {
@@ -87,45 +113,23 @@ impl<P: ImageProvider> DynamicAtlas<P> {
             view_formats: &[],
         });
         let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-        // TODO: make sampler configurable
-        let texture_sampler = resources.device.create_sampler(&wgpu::SamplerDescriptor {
-            label: Some(&format!("{} Sampler", label)),
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Linear,
-            mipmap_filter: wgpu::FilterMode::Linear,
-            ..Default::default()
-        });
         let texture_bind_group = TextureBindGroup::new(
             resources,
             &texture_view,
-            &texture_sampler,
+            resources.sampler_store.get(SamplerMode::default()),
             Some(&format!("{} TextureBindGroup", label)),
         );
 
-        let allocator = etagere::BucketedAtlasAllocator::with_options(
-            etagere::Size::new(
-                texture_size.0.try_into().unwrap(),
-                texture_size.1.try_into().unwrap(),
-            ),
-            &etagere::AllocatorOptions {
-                alignment: etagere::Size::new(8, 8), // TODO: make this configurable
-                vertical_shelves: false,
-                num_columns: 1,
-            },
-        );
-
         Self {
             image_provider,
             label,
             texture,
             texture_bind_group,
             texture_size,
-            allocator: Mutex::new(allocator),
+            allocator: Mutex::new(new_allocator(texture_size)),
             active_allocations: RwLock::new(HashMap::default()),
             eviction_ready: Mutex::new(HashMap::default()),
+            use_counter: AtomicU64::new(0),
         }
     }
 
@@ -152,10 +156,10 @@ impl<P: ImageProvider> DynamicAtlas<P> {
             }
             Entry::Vacant(entry) => {
                 let mut eviction_ready = self.eviction_ready.lock().unwrap();
-                if let Some(allocation) = eviction_ready.remove(&id) {
+                if let Some(unused) = eviction_ready.remove(&id) {
                     // The image is already allocated, but not in use, so we can restore it
                     entry.insert(AtlasAllocation {
-                        allocation,
+                        allocation: unused.allocation,
                         ref_count: 1,
                     })
                 } else {
@@ -169,37 +173,21 @@ impl<P: ImageProvider> DynamicAtlas<P> {
                     assert_eq!(P::IMAGE_FORMAT.block_dimensions(), (1, 1));
                     let block_size = P::IMAGE_FORMAT.block_size(None).unwrap();
 
+                    let size =
+                        etagere::Size::new(width.try_into().unwrap(), height.try_into().unwrap());
                     let allocation = {
                         let mut allocator = self.allocator.lock().unwrap();
-                        if let Some(alloc) = allocator.allocate(etagere::Size::new(
-                            width.try_into().unwrap(),
-                            height.try_into().unwrap(),
-                        )) {
-                            alloc
-                        } else {
+                        match allocator.allocate(size) {
+                            Some(alloc) => alloc,
                             // seems like we are out of space
                             // we can evict unused images to make space
-                            for (_id, alloc) in eviction_ready.drain() {
-                                allocator.deallocate(alloc.id);
-                            }
-                            info!(
-                                label = self.label,
-                                "Evicted all atlas images to make space for new ones, free space: {:.2}%", 
-                                100.0 * allocator.free_space() as f32 / allocator.size().area() as f32
-                            );
-
-                            // allocator
-                            //     .dump_svg(&mut std::fs::File::create("atlas_dump.svg").unwrap())
-                            //     .unwrap();
-
-                            if let Some(alloc) = allocator.allocate(etagere::Size::new(
-                                width.try_into().unwrap(),
-                                height.try_into().unwrap(),
-                            )) {
-                                alloc
-                            } else {
-                                panic!("Failed to allocate atlas space for image, even after evicting all unused images");
-                            }
+                            None => Self::evict_and_allocate(
+                                &self.label,
+                                &mut allocator,
+                                &mut eviction_ready,
+                                size,
+                            )
+                            .expect("Failed to allocate atlas space for image, even after evicting all unused images"),
                         }
                     };
 
@@ -253,6 +241,46 @@ impl<P: ImageProvider> DynamicAtlas<P> {
         Some(allocation.as_atlas_image())
     }
 
+    /// Evicts the unused images in the least recently used order until the image of `size` fits
+    fn evict_and_allocate(
+        label: &str,
+        allocator: &mut etagere::BucketedAtlasAllocator,
+        eviction_ready: &mut HashMap<P::Id, UnusedAllocation>,
+        size: etagere::Size,
+    ) -> Option<etagere::Allocation> {
+        let mut candidates = eviction_ready
+            .iter()
+            .map(|(&id, unused)| (unused.last_used, id))
+            .collect::<Vec<_>>();
+        candidates.sort_unstable_by_key(|&(last_used, _)| last_used);
+
+        let mut evicted = 0;
+        let mut result = None;
+        for (_, id) in candidates {
+            let unused = eviction_ready.remove(&id).unwrap();
+            allocator.deallocate(unused.allocation.id);
+            evicted += 1;
+
+            if let Some(alloc) = allocator.allocate(size) {
+                result = Some(alloc);
+                break;
+            }
+        }
+
+        debug!(
+            label,
+            "Evicted {} least recently used atlas images to make space for a new one, free space: {:.2}%",
+            evicted,
+            100.0 * allocator.free_space() as f32 / allocator.size().area() as f32
+        );
+
+        // allocator
+        //     .dump_svg(&mut std::fs::File::create("atlas_dump.svg").unwrap())
+        //     .unwrap();
+
+        result
+    }
+
     #[allow(unused)]
     pub fn peek_image(&self, id: P::Id) -> Option<AtlasImage> {
         Some(
@@ -274,10 +302,13 @@ impl<P: ImageProvider> DynamicAtlas<P> {
         allocation.ref_count -= 1;
 
         if allocation.ref_count == 0 {
-            self.eviction_ready
-                .lock()
-                .unwrap()
-                .insert(id, allocation.allocation);
+            self.eviction_ready.lock().unwrap().insert(
+                id,
+                UnusedAllocation {
+                    allocation: allocation.allocation,
+                    last_used: self.use_counter.fetch_add(1, Ordering::Relaxed),
+                },
+            );
             active_allocations.remove(&id);
         }
     }
@@ -513,3 +544,100 @@ impl<P: ImageProvider> OverlayVisitable for DynamicAtlas<P> {
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy_utils::HashMap;
+
+    use super::{new_allocator, DynamicAtlas, ImageProvider, UnusedAllocation};
+
+    struct NoImages;
+
+    impl ImageProvider for NoImages {
+        const IMAGE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Unorm;
+        const MIPMAP_LEVELS: u32 = 1;
+        type Id = u32;
+
+        fn get_image(&self, _id: u32) -> (Vec<Vec<u8>>, (u32, u32)) {
+            unreachable!("the eviction doesn't upload images")
+        }
+    }
+
+    type Atlas = DynamicAtlas<NoImages>;
+
+    const IMAGE_SIZE: etagere::Size = etagere::Size::new(32, 32);
+
+    /// Fills a 128x128 atlas with unused 32x32 images, the images allocated later were used earlier
+    fn full_atlas() -> (
+        etagere::BucketedAtlasAllocator,
+        HashMap<u32, UnusedAllocation>,
+    ) {
+        let mut allocator = new_allocator((128, 128));
+        let allocations = std::iter::from_fn(|| allocator.allocate(IMAGE_SIZE)).collect::<Vec<_>>();
+        assert!(allocations.len() > 1);
+
+        let count = allocations.len() as u64;
+        let eviction_ready = allocations
+            .into_iter()
+            .enumerate()
+            .map(|(id, allocation)| {
+                let last_used = count - 1 - id as u64;
+                (
+                    id as u32,
+                    UnusedAllocation {
+                        allocation,
+                        last_used,
+                    },
+                )
+            })
+            .collect();
+        (allocator, eviction_ready)
+    }
+
+    fn last_used(eviction_ready: &HashMap<u32, UnusedAllocation>) -> Vec<u64> {
+        let mut last_used = eviction_ready
+            .values()
+            .map(|unused| unused.last_used)
+            .collect::<Vec<_>>();
+        last_used.sort_unstable();
+        last_used
+    }
+
+    #[test]
+    fn evicts_least_recently_used_first() {
+        let (mut allocator, mut eviction_ready) = full_atlas();
+        let count = eviction_ready.len() as u64;
+
+        Atlas::evict_and_allocate("test", &mut allocator, &mut eviction_ready, IMAGE_SIZE)
+            .expect("no space after the eviction");
+
+        // only the oldest images are gone, and only as many as needed
+        let kept = last_used(&eviction_ready);
+        let evicted = count - kept.len() as u64;
+        assert!(evicted >= 1 && evicted < count);
+        assert_eq!(kept, (evicted..count).collect::<Vec<_>>());
+
+        // the next eviction continues from the oldest remaining image
+        let before = kept.len();
+        Atlas::evict_and_allocate("test", &mut allocator, &mut eviction_ready, IMAGE_SIZE)
+            .expect("no space after the eviction");
+        let kept = last_used(&eviction_ready);
+        assert!(kept.len() < before);
+        assert_eq!(kept, (count - kept.len() as u64..count).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn too_large_image_evicts_everything() {
+        let (mut allocator, mut eviction_ready) = full_atlas();
+
+        let result = Atlas::evict_and_allocate(
+            "test",
+            &mut allocator,
+            &mut eviction_ready,
+            etagere::Size::new(256, 256),
+        );
+
+        assert!(result.is_none());
+        assert!(eviction_ready.is_empty());
+    }
+}
@@ -1,2 +1,4 @@
+pub mod cursor;
 pub mod dynamic_atlas;
 pub mod overlay;
+pub mod test_pattern;
@@ -8,7 +8,7 @@ use egui::{
 };
 use egui_wgpu::{Renderer, ScreenDescriptor};
 use glam::vec2;
-use shin_render::GpuCommonResources;
+use shin_render::{GpuCommonResources, RenderTargetPool};
 
 use crate::{
     input::{actions::OverlayManagerAction, inputs::MouseButton, ActionState, RawInputState},
@@ -393,3 +393,26 @@ impl<'a, 'top_left, 'ctx> OverlayCollector<'a, 'top_left, 'ctx> {
 pub trait OverlayVisitable {
     fn visit_overlay(&self, collector: &mut OverlayCollector);
 }
+
+impl OverlayVisitable for RenderTargetPool {
+    fn visit_overlay(&self, collector: &mut OverlayCollector) {
+        collector.overlay(
+            "Render Target Pool",
+            |_ctx, top_left| {
+                let stats = self.stats();
+                let mib = |bytes: u64| bytes as f64 / (1024.0 * 1024.0);
+                top_left.label(format!(
+                    "Render Targets: {} in use ({:.1} MiB), {} free ({:.1} MiB / {:.1} MiB budget), {} allocated, {} reused",
+                    stats.in_use,
+                    mib(stats.bytes_in_use),
+                    stats.free,
+                    mib(stats.bytes_free),
+                    mib(stats.budget),
+                    stats.allocations,
+                    stats.reuses
+                ));
+            },
+            false,
+        );
+    }
+}
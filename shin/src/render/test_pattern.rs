@@ -0,0 +1,56 @@
+//! A test pattern to verify the color space handling of the output.
+//!
+//! The pattern is drawn instead of the game when `--color-test-pattern` is passed.
+//! With correct sRGB handling, the gray ramp steps look perceptually even and the 1px black-and-white checkerboard looks as bright as the gray patch next to it when viewed from a distance.
+
+use glam::vec2;
+use image::{Rgba, RgbaImage};
+use shin_render::{GpuCommonResources, GpuImage, VIRTUAL_HEIGHT, VIRTUAL_WIDTH};
+
+/// sRGB value of the gray that matches the average brightness of a black-and-white checkerboard
+const HALF_INTENSITY_GRAY: u8 = 188;
+const GRAY_STEPS: u32 = 16;
+
+const COLOR_BARS: [[u8; 3]; 7] = [
+    [255, 255, 255],
+    [255, 255, 0],
+    [0, 255, 255],
+    [0, 255, 0],
+    [255, 0, 255],
+    [255, 0, 0],
+    [0, 0, 255],
+];
+
+fn make_image() -> RgbaImage {
+    let width = VIRTUAL_WIDTH as u32;
+    let height = VIRTUAL_HEIGHT as u32;
+
+    RgbaImage::from_fn(width, height, |x, y| {
+        let [r, g, b] = match y * 3 / height {
+            // the color bars
+            0 => COLOR_BARS[(x * COLOR_BARS.len() as u32 / width) as usize],
+            // the gray ramp
+            1 => {
+                let step = x * GRAY_STEPS / width;
+                let value = (step * 255 / (GRAY_STEPS - 1)) as u8;
+                [value; 3]
+            }
+            // checkerboard on the left, the matching gray on the right
+            _ if x < width / 2 => {
+                let value = if (x + y) % 2 == 0 { 255 } else { 0 };
+                [value; 3]
+            }
+            _ => [HALF_INTENSITY_GRAY; 3],
+        };
+        Rgba([r, g, b, 255])
+    })
+}
+
+pub fn load(resources: &GpuCommonResources) -> GpuImage {
+    GpuImage::load(
+        resources,
+        &make_image(),
+        vec2(VIRTUAL_WIDTH / 2.0, VIRTUAL_HEIGHT / 2.0),
+        Some("Color Test Pattern"),
+    )
+}
@@ -0,0 +1,371 @@
+//! User settings, stored in a JSON file (by default in the shin data directory).
+//!
+//! These are the engine's own settings, the settings block in the game's savedata is not used.
+//! The settings are shared with [`SettingsStore`] and changed by the player in the [`SettingsScreen`].
+
+mod screen;
+
+use std::{
+    collections::BTreeMap,
+    io::{BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{RwLock, RwLockReadGuard},
+};
+
+use anyhow::{Context, Result};
+use glam::{vec3, Vec3};
+pub use screen::SettingsScreen;
+use serde::{Deserialize, Serialize};
+use shin_audio::DuckingSettings;
+use shin_core::vm::command::types::Volume;
+use shin_video::VideoPlayerOptions;
+use tracing::{debug, warn};
+
+use crate::config::check_render_scale;
+
+/// Volume and muting of a single character's voice
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CharacterVoice {
+    /// Multiplier for the volume set by the scenario, in `[0.0, 1.0]`
+    pub volume: f32,
+    pub muted: bool,
+}
+
+impl Default for CharacterVoice {
+    fn default() -> Self {
+        Self {
+            volume: 1.0,
+            muted: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VoiceSettings {
+    /// Keyed by the lipsync character IDs from the voice mapping (see [`shin_core::format::scenario::info::VoiceMappingInfoItem`])
+    ///
+    /// Only the characters with non-default settings are stored.
+    pub characters: BTreeMap<u8, CharacterVoice>,
+}
+
+impl VoiceSettings {
+    pub fn character(&self, character_id: u8) -> CharacterVoice {
+        self.characters
+            .get(&character_id)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn set_character(&mut self, character_id: u8, voice: CharacterVoice) {
+        if voice == CharacterVoice::default() {
+            self.characters.remove(&character_id);
+        } else {
+            self.characters.insert(character_id, voice);
+        }
+    }
+
+    /// The volume multiplier for a voice file spoken by the `character_ids`
+    ///
+    /// When several characters speak in the same file, the loudest one wins, so the voice is only muted when all of them are.
+    /// Voices without known speakers are not affected.
+    pub fn volume_for(&self, character_ids: &[u8]) -> f32 {
+        if character_ids.is_empty() {
+            return 1.0;
+        }
+
+        character_ids
+            .iter()
+            .map(|&id| self.character(id))
+            .map(|voice| if voice.muted { 0.0 } else { voice.volume })
+            .fold(0.0, f32::max)
+    }
+}
+
+/// Lowering of the BGM while a voice is playing ("voice focus" in the original engine settings)
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VoiceFocusSettings {
+    pub enabled: bool,
+    /// Multiplier for the BGM volume while a voice is playing, in `[0.0, 1.0]`
+    pub volume: f32,
+}
+
+impl Default for VoiceFocusSettings {
+    fn default() -> Self {
+        let ducking = DuckingSettings::default();
+        Self {
+            enabled: ducking.enabled,
+            volume: ducking.volume.0,
+        }
+    }
+}
+
+impl VoiceFocusSettings {
+    pub fn ducking_settings(&self) -> DuckingSettings {
+        DuckingSettings {
+            enabled: self.enabled,
+            volume: Volume(self.volume),
+            ..DuckingSettings::default()
+        }
+    }
+}
+
+/// A color the message window is tinted with
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum MessageboxTint {
+    /// The window texture as is
+    #[default]
+    Default,
+    Dark,
+    Sepia,
+    Blue,
+}
+
+impl MessageboxTint {
+    pub const ALL: [MessageboxTint; 4] = [
+        MessageboxTint::Default,
+        MessageboxTint::Dark,
+        MessageboxTint::Sepia,
+        MessageboxTint::Blue,
+    ];
+
+    /// The key of the tint name in the UI strings
+    pub fn name_key(self) -> &'static str {
+        match self {
+            MessageboxTint::Default => "settings.tint.default",
+            MessageboxTint::Dark => "settings.tint.dark",
+            MessageboxTint::Sepia => "settings.tint.sepia",
+            MessageboxTint::Blue => "settings.tint.blue",
+        }
+    }
+
+    /// The multiplier for the window color
+    pub fn color(self) -> Vec3 {
+        match self {
+            MessageboxTint::Default => vec3(1.0, 1.0, 1.0),
+            MessageboxTint::Dark => vec3(0.45, 0.45, 0.45),
+            MessageboxTint::Sepia => vec3(1.0, 0.85, 0.65),
+            MessageboxTint::Blue => vec3(0.7, 0.8, 1.0),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MessageboxSettings {
+    /// Multiplier for the message window opacity, in `[0.0, 1.0]`
+    ///
+    /// The text itself is not affected.
+    pub opacity: f32,
+    pub tint: MessageboxTint,
+}
+
+impl Default for MessageboxSettings {
+    fn default() -> Self {
+        Self {
+            opacity: 1.0,
+            tint: MessageboxTint::Default,
+        }
+    }
+}
+
+/// Size and position of the window, in physical pixels
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WindowGeometry {
+    /// Position of the top-left corner of the window decorations
+    pub x: i32,
+    pub y: i32,
+    /// Size of the window contents
+    pub width: u32,
+    pub height: u32,
+}
+
+/// An exclusive fullscreen mode of a monitor
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DisplayMode {
+    pub width: u32,
+    pub height: u32,
+    pub refresh_rate_millihertz: u32,
+}
+
+impl DisplayMode {
+    pub fn name(&self) -> String {
+        format!(
+            "{}x{} @ {:.2} Hz",
+            self.width,
+            self.height,
+            self.refresh_rate_millihertz as f32 / 1000.0
+        )
+    }
+}
+
+/// A monitor the game can be shown on, as enumerated by the window on start
+#[derive(Debug, Clone)]
+pub struct MonitorInfo {
+    pub name: String,
+    pub primary: bool,
+    /// Sorted from the best to the worst, by the resolution and then by the refresh rate
+    pub video_modes: Vec<DisplayMode>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DisplaySettings {
+    /// Name of the monitor the fullscreen game is shown on, `None` for the primary one
+    ///
+    /// Falls back to the primary monitor if the monitor is not connected.
+    pub monitor: Option<String>,
+    /// The mode used in the exclusive fullscreen, `None` for the best one
+    ///
+    /// Falls back to the best refresh rate with the same resolution, and then to the best mode, if the monitor doesn't support it.
+    pub video_mode: Option<DisplayMode>,
+}
+
+#[derive(Debug, Copy, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MovieSettings {
+    /// How much later the sound is heard than the engine thinks it is, in milliseconds, found with the A/V sync calibration overlay
+    ///
+    /// The movie frames are delayed by this amount, negative values show them earlier.
+    pub audio_offset_ms: i32,
+    /// Index of the audio track of the movies with several ones, like the openings with a karaoke track
+    ///
+    /// The movies with a single track play it regardless of this.
+    pub audio_track: usize,
+}
+
+impl MovieSettings {
+    pub fn video_player_options(&self) -> VideoPlayerOptions {
+        VideoPlayerOptions {
+            audio_offset: self.audio_offset_ms as f64 / 1000.0,
+            audio_track: Some(self.audio_track),
+            ..VideoPlayerOptions::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Language of the engine UI, `None` to use the one from the config (see [`crate::i18n`])
+    pub language: Option<String>,
+    /// Language of the scenario, one of the [`ScenarioConfig::languages`](crate::config::ScenarioConfig::languages), `None` for the original one
+    pub scenario_language: Option<String>,
+    pub voice: VoiceSettings,
+    pub voice_focus: VoiceFocusSettings,
+    pub messagebox: MessageboxSettings,
+    pub display: DisplaySettings,
+    /// Multiple of the window resolution the scene is rendered at, `None` to use the one from the config (see [`RenderConfig::scale`](crate::config::RenderConfig::scale))
+    pub render_scale: Option<f32>,
+    pub movie: MovieSettings,
+    /// The window geometry when the game was last closed, `None` if it was never closed in a windowed mode
+    pub window: Option<WindowGeometry>,
+}
+
+/// Holds the current settings and writes them back to the file
+pub struct SettingsStore {
+    /// `None` if the settings are not persisted
+    path: Option<PathBuf>,
+    settings: RwLock<Settings>,
+    /// The monitors to choose from in the settings, not persisted
+    monitors: RwLock<Vec<MonitorInfo>>,
+    /// The scenario translations to choose from in the settings, not persisted
+    scenario_languages: RwLock<Vec<String>>,
+}
+
+impl SettingsStore {
+    /// `settings.json` in the shin data directory, if there is one on this platform
+    pub fn default_path() -> Option<PathBuf> {
+        dirs_next::data_dir().map(|p| p.join("shin").join("settings.json"))
+    }
+
+    /// Loads the settings from the `path`, falling back to the defaults if the file doesn't exist or is broken
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let mut settings = match &path {
+            Some(path) if path.exists() => match Self::read(path) {
+                Ok(settings) => {
+                    debug!("Loaded the settings from {}", path.display());
+                    settings
+                }
+                Err(e) => {
+                    warn!("Failed to load the settings, using the defaults: {:?}", e);
+                    Settings::default()
+                }
+            },
+            _ => Settings::default(),
+        };
+        if let Some(scale) = settings.render_scale {
+            if let Err(e) = check_render_scale(scale) {
+                warn!("Ignoring the render scale from the settings: {}", e);
+                settings.render_scale = None;
+            }
+        }
+
+        Self {
+            path,
+            settings: RwLock::new(settings),
+            monitors: RwLock::new(Vec::new()),
+            scenario_languages: RwLock::new(Vec::new()),
+        }
+    }
+
+    fn read(path: &Path) -> Result<Settings> {
+        let file =
+            std::fs::File::open(path).with_context(|| format!("Opening {}", path.display()))?;
+        serde_json::from_reader(BufReader::new(file))
+            .with_context(|| format!("Parsing {}", path.display()))
+    }
+
+    pub fn get(&self) -> RwLockReadGuard<Settings> {
+        self.settings.read().unwrap()
+    }
+
+    pub fn monitors(&self) -> RwLockReadGuard<Vec<MonitorInfo>> {
+        self.monitors.read().unwrap()
+    }
+
+    /// Sets the monitors available for the [`DisplaySettings`], there are none on the platforms without the monitor enumeration
+    pub fn set_monitors(&self, monitors: Vec<MonitorInfo>) {
+        *self.monitors.write().unwrap() = monitors;
+    }
+
+    pub fn scenario_languages(&self) -> RwLockReadGuard<Vec<String>> {
+        self.scenario_languages.read().unwrap()
+    }
+
+    /// Sets the languages available for [`Settings::scenario_language`], there are none when the game has a single scenario
+    pub fn set_scenario_languages(&self, languages: Vec<String>) {
+        *self.scenario_languages.write().unwrap() = languages;
+    }
+
+    /// Changes the settings in memory, call [`SettingsStore::save`] to persist them
+    pub fn update(&self, f: impl FnOnce(&mut Settings)) {
+        f(&mut self.settings.write().unwrap());
+    }
+
+    pub fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+
+        if let Err(e) = self.write(path) {
+            warn!("Failed to save the settings: {:?}", e);
+        }
+    }
+
+    fn write(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Creating {}", parent.display()))?;
+        }
+
+        let file =
+            std::fs::File::create(path).with_context(|| format!("Creating {}", path.display()))?;
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer_pretty(&mut writer, &*self.get()).context("Writing the settings")?;
+        writer.flush().context("Writing the settings")?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,621 @@
+use std::sync::Arc;
+
+use glam::{vec2, Mat4, Vec2};
+use shin_core::time::{Easing, Ticks, Tween};
+use shin_render::{GpuCommonResources, LazyGpuTexture, Renderable};
+
+use super::{CharacterVoice, DisplaySettings, MessageboxTint, MonitorInfo, SettingsStore};
+use crate::{
+    app::{Screen, ScreenTransition},
+    i18n::Localizer,
+    input::{actions::MenuAction, prompts::InputDevice, ActionState},
+    layer::FontAtlas,
+    render::overlay::{OverlayCollector, OverlayVisitable},
+    update::{Updatable, UpdateContext},
+    widget::{
+        default_button_image, default_button_sprite, default_nine_patch, default_window_image,
+        Button, Label, Window,
+    },
+};
+
+const FADE: Tween = Tween {
+    duration: Ticks::from_f32(10.0),
+    easing: Easing::SineOut,
+};
+
+const ROW_SIZE: Vec2 = vec2(720.0, 56.0);
+const ROW_SPACING: f32 = 68.0;
+/// The rows that don't fit are scrolled into view when selected
+const VISIBLE_ROWS: usize = 8;
+const TITLE_FONT_HEIGHT: f32 = 48.0;
+const LABEL_FONT_HEIGHT: f32 = 36.0;
+const HINT_FONT_HEIGHT: f32 = 28.0;
+const HINT_SPACING: f32 = 48.0;
+const WINDOW_PADDING: f32 = 40.0;
+/// How much the volumes and the opacity change with a single press of left or right
+const STEP: f32 = 0.1;
+/// The render scales to choose from, up to [`MAX_RENDER_SCALE`](crate::config::MAX_RENDER_SCALE)
+const RENDER_SCALES: [f32; 5] = [0.5, 0.75, 1.0, 1.5, 2.0];
+/// The movies have at most two audio tracks: the main one and the karaoke one in some openings
+const MOVIE_AUDIO_TRACKS: usize = 2;
+
+/// Moves the `value` in `[0.0, 1.0]` by a step in the `direction`
+fn step(value: f32, direction: f32) -> f32 {
+    let value = (value + direction * STEP).clamp(0.0, 1.0);
+    // round to the steps to not accumulate the float errors
+    (value / STEP).round() * STEP
+}
+
+fn percent(value: f32) -> f32 {
+    (value * 100.0).round()
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum State {
+    Opening,
+    Open,
+    Closing,
+}
+
+/// A single adjustable setting
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Row {
+    Language,
+    /// Applied when the settings are closed, see [`Adv`](crate::adv::Adv)
+    ScenarioLanguage,
+    MessageboxOpacity,
+    MessageboxTint,
+    /// The display settings are applied when the fullscreen is entered
+    Monitor,
+    VideoMode,
+    /// Applied right away, see [`crate::window`]
+    RenderScale,
+    MovieAudioTrack,
+    /// Applied to the audio right away, see [`crate::window`]
+    VoiceFocus,
+    CharacterVoice(u8),
+}
+
+impl Row {
+    fn text(self, store: &SettingsStore, i18n: &Localizer) -> String {
+        // the localizer reads the settings too, so they can't stay locked while it's used
+        let settings = store.get().clone();
+        let language = i18n.language_name(&i18n.language());
+        match self {
+            Row::Language => {
+                let language = match settings.language {
+                    Some(_) => language,
+                    None => i18n.tr_args("settings.language_default", &[("language", &language)]),
+                };
+                i18n.tr_args("settings.language", &[("language", &language)])
+            }
+            Row::ScenarioLanguage => {
+                let language = match &settings.scenario_language {
+                    Some(language) => i18n.language_name(language),
+                    None => i18n.tr("settings.scenario_language_original"),
+                };
+                i18n.tr_args("settings.scenario_language", &[("language", &language)])
+            }
+            Row::MessageboxOpacity => i18n.tr_args(
+                "settings.messagebox_opacity",
+                &[("percent", &percent(settings.messagebox.opacity))],
+            ),
+            Row::MessageboxTint => i18n.tr_args(
+                "settings.messagebox_tint",
+                &[("tint", &i18n.tr(settings.messagebox.tint.name_key()))],
+            ),
+            Row::Monitor => {
+                let monitor = match &settings.display.monitor {
+                    Some(monitor) => monitor.clone(),
+                    None => i18n.tr("settings.monitor_primary"),
+                };
+                i18n.tr_args("settings.monitor", &[("monitor", &monitor)])
+            }
+            Row::VideoMode => {
+                let mode = match settings.display.video_mode {
+                    Some(mode) => mode.name(),
+                    None => i18n.tr("settings.video_mode_best"),
+                };
+                i18n.tr_args("settings.video_mode", &[("mode", &mode)])
+            }
+            Row::RenderScale => {
+                let scale = match settings.render_scale {
+                    Some(scale) => format!("{}%", percent(scale)),
+                    None => i18n.tr("settings.render_scale_default"),
+                };
+                i18n.tr_args("settings.render_scale", &[("scale", &scale)])
+            }
+            Row::MovieAudioTrack => i18n.tr_args(
+                "settings.movie_audio_track",
+                &[("track", &(settings.movie.audio_track + 1))],
+            ),
+            Row::VoiceFocus => {
+                if settings.voice_focus.enabled {
+                    i18n.tr_args(
+                        "settings.voice_focus",
+                        &[("percent", &percent(settings.voice_focus.volume))],
+                    )
+                } else {
+                    i18n.tr("settings.voice_focus_off")
+                }
+            }
+            Row::CharacterVoice(id) => {
+                // the character names are not known, the lipsync IDs are all we have
+                let voice = settings.voice.character(id);
+                if voice.muted {
+                    i18n.tr_args("settings.character_voice_muted", &[("id", &id)])
+                } else {
+                    i18n.tr_args(
+                        "settings.character_voice",
+                        &[("id", &id), ("percent", &percent(voice.volume))],
+                    )
+                }
+            }
+        }
+    }
+
+    /// Left and right
+    fn adjust(self, store: &SettingsStore, i18n: &Localizer, direction: f32) {
+        let monitors = store.monitors();
+        let scenario_languages = store.scenario_languages();
+        let languages = i18n.languages();
+        store.update(|settings| match self {
+            Row::Language => {
+                settings.language = cycle_option(&settings.language, &languages, direction)
+            }
+            Row::ScenarioLanguage => {
+                settings.scenario_language =
+                    cycle_option(&settings.scenario_language, &scenario_languages, direction)
+            }
+            Row::MessageboxOpacity => {
+                settings.messagebox.opacity = step(settings.messagebox.opacity, direction)
+            }
+            Row::MessageboxTint => {
+                settings.messagebox.tint = cycle_tint(settings.messagebox.tint, direction)
+            }
+            Row::Monitor => {
+                let names = monitors
+                    .iter()
+                    .map(|monitor| monitor.name.clone())
+                    .collect::<Vec<_>>();
+                settings.display.monitor =
+                    cycle_option(&settings.display.monitor, &names, direction);
+                // the modes of the previous monitor are unlikely to be supported
+                settings.display.video_mode = None;
+            }
+            Row::VideoMode => {
+                let modes = current_monitor(&monitors, &settings.display)
+                    .map(|monitor| monitor.video_modes.as_slice())
+                    .unwrap_or_default();
+                settings.display.video_mode =
+                    cycle_option(&settings.display.video_mode, modes, direction);
+            }
+            Row::RenderScale => {
+                settings.render_scale =
+                    cycle_option(&settings.render_scale, &RENDER_SCALES, direction)
+            }
+            Row::MovieAudioTrack => {
+                let track = settings.movie.audio_track.min(MOVIE_AUDIO_TRACKS - 1);
+                settings.movie.audio_track = if direction < 0.0 {
+                    (track + MOVIE_AUDIO_TRACKS - 1) % MOVIE_AUDIO_TRACKS
+                } else {
+                    (track + 1) % MOVIE_AUDIO_TRACKS
+                };
+            }
+            Row::VoiceFocus => {
+                settings.voice_focus.volume = step(settings.voice_focus.volume, direction);
+                settings.voice_focus.enabled = true;
+            }
+            Row::CharacterVoice(id) => {
+                let voice = settings.voice.character(id);
+                settings.voice.set_character(
+                    id,
+                    CharacterVoice {
+                        volume: step(voice.volume, direction),
+                        muted: false,
+                    },
+                );
+            }
+        })
+    }
+
+    /// Activate
+    fn toggle(self, store: &SettingsStore, i18n: &Localizer) {
+        if matches!(
+            self,
+            Row::Language
+                | Row::ScenarioLanguage
+                | Row::Monitor
+                | Row::VideoMode
+                | Row::RenderScale
+                | Row::MovieAudioTrack
+        ) {
+            return self.adjust(store, i18n, 1.0);
+        }
+
+        store.update(|settings| match self {
+            Row::MessageboxOpacity => {
+                // toggle between fully transparent and opaque
+                settings.messagebox.opacity = if settings.messagebox.opacity > 0.0 {
+                    0.0
+                } else {
+                    1.0
+                }
+            }
+            Row::MessageboxTint => {
+                settings.messagebox.tint = cycle_tint(settings.messagebox.tint, 1.0)
+            }
+            Row::Language
+            | Row::ScenarioLanguage
+            | Row::Monitor
+            | Row::VideoMode
+            | Row::RenderScale
+            | Row::MovieAudioTrack => unreachable!(),
+            Row::VoiceFocus => settings.voice_focus.enabled = !settings.voice_focus.enabled,
+            Row::CharacterVoice(id) => {
+                let voice = settings.voice.character(id);
+                settings.voice.set_character(
+                    id,
+                    CharacterVoice {
+                        muted: !voice.muted,
+                        ..voice
+                    },
+                );
+            }
+        })
+    }
+}
+
+fn cycle_tint(tint: MessageboxTint, direction: f32) -> MessageboxTint {
+    let count = MessageboxTint::ALL.len();
+    let index = MessageboxTint::ALL
+        .iter()
+        .position(|&t| t == tint)
+        .unwrap_or(0);
+    let index = if direction < 0.0 {
+        (index + count - 1) % count
+    } else {
+        (index + 1) % count
+    };
+    MessageboxTint::ALL[index]
+}
+
+/// The monitor the display settings apply to: the chosen one, or the primary one
+fn current_monitor<'a>(
+    monitors: &'a [MonitorInfo],
+    display: &DisplaySettings,
+) -> Option<&'a MonitorInfo> {
+    display
+        .monitor
+        .as_ref()
+        .and_then(|name| monitors.iter().find(|monitor| &monitor.name == name))
+        .or_else(|| monitors.iter().find(|monitor| monitor.primary))
+}
+
+/// Cycles through `None` (the default) followed by the `options`
+fn cycle_option<T: Clone + PartialEq>(
+    current: &Option<T>,
+    options: &[T],
+    direction: f32,
+) -> Option<T> {
+    let count = options.len() + 1;
+    let index = current
+        .as_ref()
+        .and_then(|current| options.iter().position(|option| option == current))
+        .map_or(0, |index| index + 1);
+    let index = if direction < 0.0 {
+        (index + count - 1) % count
+    } else {
+        (index + 1) % count
+    };
+    index.checked_sub(1).map(|index| options[index].clone())
+}
+
+struct RowSlot {
+    button: Button,
+    label: Option<Label>,
+    position: Vec2,
+}
+
+/// Lets the player change the settings, they are saved when the screen is closed
+pub struct SettingsScreen {
+    store: Arc<SettingsStore>,
+    i18n: Arc<Localizer>,
+    font_atlas: Arc<FontAtlas>,
+    window_texture: LazyGpuTexture,
+    button_texture: LazyGpuTexture,
+    window: Window,
+    /// Rebuilt together with the rows, as the language can be changed
+    title: Label,
+    title_position: Vec2,
+    /// The button prompts under the rows, rebuilt when the player switches between the keyboard and a gamepad
+    hint: Option<Label>,
+    hint_position: Vec2,
+    prompt_device: InputDevice,
+    rows: Vec<Row>,
+    /// The buttons showing the rows from `scroll` to `scroll + VISIBLE_ROWS`
+    slots: Vec<RowSlot>,
+    selected: usize,
+    scroll: usize,
+    state: State,
+    action_state: ActionState<MenuAction>,
+}
+
+impl SettingsScreen {
+    /// `voice_characters` are the characters from the voice mapping that can have their voice adjusted, they are listed after the message window, the display, the render scale, the movie and the voice focus settings
+    pub fn new(
+        resources: &GpuCommonResources,
+        font_atlas: Arc<FontAtlas>,
+        store: Arc<SettingsStore>,
+        i18n: Arc<Localizer>,
+        voice_characters: &[u8],
+    ) -> Self {
+        // there is nothing to choose from when the game has a single scenario
+        let scenario_rows = if store.scenario_languages().is_empty() {
+            &[][..]
+        } else {
+            &[Row::ScenarioLanguage][..]
+        };
+        // there is nothing to choose from on the platforms without the monitor enumeration
+        let display_rows = if store.monitors().is_empty() {
+            &[][..]
+        } else {
+            &[Row::Monitor, Row::VideoMode][..]
+        };
+        let rows = [Row::Language]
+            .into_iter()
+            .chain(scenario_rows.iter().copied())
+            .chain([Row::MessageboxOpacity, Row::MessageboxTint])
+            .chain(display_rows.iter().copied())
+            .chain([Row::RenderScale, Row::MovieAudioTrack, Row::VoiceFocus])
+            .chain(voice_characters.iter().map(|&id| Row::CharacterVoice(id)))
+            .collect::<Vec<_>>();
+
+        let slot_count = rows.len().min(VISIBLE_ROWS);
+        let height = TITLE_FONT_HEIGHT + ROW_SPACING * slot_count as f32 + HINT_SPACING;
+        let top_left = vec2(-ROW_SIZE.x / 2.0, -height / 2.0);
+
+        let mut window = Window::new(
+            resources,
+            default_nine_patch(),
+            (
+                top_left.x - WINDOW_PADDING,
+                top_left.y - WINDOW_PADDING,
+                -top_left.x + WINDOW_PADDING,
+                -top_left.y + WINDOW_PADDING,
+            ),
+        );
+        window.show(FADE);
+
+        let title = Label::new(
+            resources,
+            font_atlas.clone(),
+            &i18n.tr("settings.title"),
+            top_left,
+            ROW_SIZE.x,
+            TITLE_FONT_HEIGHT,
+        );
+
+        let sprite = default_button_sprite(ROW_SIZE);
+        let slots = (0..slot_count)
+            .map(|index| {
+                let position = top_left
+                    + vec2(
+                        0.0,
+                        TITLE_FONT_HEIGHT + (ROW_SPACING - ROW_SIZE.y) + ROW_SPACING * index as f32,
+                    );
+                RowSlot {
+                    button: Button::new(resources, sprite, position),
+                    label: None,
+                    position,
+                }
+            })
+            .collect();
+
+        let mut result = Self {
+            store,
+            i18n,
+            font_atlas,
+            window_texture: LazyGpuTexture::new(default_window_image(), Some("Settings Window")),
+            button_texture: LazyGpuTexture::new(
+                default_button_image(ROW_SIZE),
+                Some("Settings Button"),
+            ),
+            window,
+            title,
+            title_position: top_left,
+            hint: None,
+            hint_position: top_left + vec2(0.0, height - HINT_FONT_HEIGHT),
+            prompt_device: InputDevice::KeyboardMouse,
+            rows,
+            slots,
+            selected: 0,
+            scroll: 0,
+            state: State::Opening,
+            action_state: ActionState::new(),
+        };
+        result.refresh_slots(resources);
+        result
+    }
+
+    /// Rebuilds the labels and the highlight after the selection, the scroll or the settings have changed
+    fn refresh_slots(&mut self, resources: &GpuCommonResources) {
+        self.title = Label::new(
+            resources,
+            self.font_atlas.clone(),
+            &self.i18n.tr("settings.title"),
+            self.title_position,
+            ROW_SIZE.x,
+            TITLE_FONT_HEIGHT,
+        );
+        self.hint = Some(Label::new(
+            resources,
+            self.font_atlas.clone(),
+            &self.hint_text(),
+            self.hint_position,
+            ROW_SIZE.x,
+            HINT_FONT_HEIGHT,
+        ));
+        for (index, slot) in self.slots.iter_mut().enumerate() {
+            let row_index = self.scroll + index;
+            let row = self.rows[row_index];
+            slot.button.set_highlighted(row_index == self.selected);
+            slot.label = Some(Label::new(
+                resources,
+                self.font_atlas.clone(),
+                &row.text(&self.store, &self.i18n),
+                slot.position + vec2(0.0, (ROW_SIZE.y - LABEL_FONT_HEIGHT) / 2.0),
+                ROW_SIZE.x,
+                LABEL_FONT_HEIGHT,
+            ));
+        }
+    }
+
+    fn hint_text(&self) -> String {
+        let action_map = self.action_state.action_map();
+        let prompt = |action| {
+            self.prompt_device
+                .prompt(action_map, action)
+                .unwrap_or_default()
+        };
+        self.i18n.tr_args(
+            "settings.hint",
+            &[
+                ("activate", &prompt(MenuAction::Activate)),
+                ("cancel", &prompt(MenuAction::Cancel)),
+            ],
+        )
+    }
+
+    fn select(&mut self, index: usize) {
+        self.selected = index;
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + VISIBLE_ROWS {
+            self.scroll = self.selected + 1 - VISIBLE_ROWS;
+        }
+    }
+
+    fn selected_slot(&mut self) -> &mut RowSlot {
+        &mut self.slots[self.selected - self.scroll]
+    }
+
+    /// Returns `true` if the screen needs to be refreshed
+    fn handle_input(&mut self) -> bool {
+        if self.action_state.is_just_pressed(MenuAction::Cancel) {
+            self.store.save();
+            self.window.hide(FADE);
+            self.state = State::Closing;
+            return false;
+        }
+
+        let count = self.rows.len();
+
+        let row = self.rows[self.selected];
+        if self.action_state.is_just_pressed(MenuAction::Up) {
+            self.select((self.selected + count - 1) % count);
+        } else if self.action_state.is_just_pressed(MenuAction::Down) {
+            self.select((self.selected + 1) % count);
+        } else if self.action_state.is_just_pressed(MenuAction::Left) {
+            row.adjust(&self.store, &self.i18n, -1.0);
+        } else if self.action_state.is_just_pressed(MenuAction::Right) {
+            row.adjust(&self.store, &self.i18n, 1.0);
+        } else if self.action_state.is_just_pressed(MenuAction::Activate) {
+            row.toggle(&self.store, &self.i18n);
+            self.selected_slot().button.flash();
+        } else {
+            return false;
+        }
+
+        true
+    }
+}
+
+impl Screen for SettingsScreen {
+    fn name(&self) -> &'static str {
+        "Settings"
+    }
+
+    fn update(&mut self, context: &UpdateContext) -> ScreenTransition {
+        self.action_state.update(context.raw_input_state);
+        self.window.update(context);
+        for slot in &mut self.slots {
+            slot.button.update(context);
+        }
+        if context.raw_input_state.active_device != self.prompt_device {
+            self.prompt_device = context.raw_input_state.active_device;
+            self.refresh_slots(context.gpu_resources);
+        }
+
+        match self.state {
+            State::Opening => {
+                if self.window.is_idle() {
+                    self.state = State::Open;
+                }
+            }
+            State::Open => {
+                if self.handle_input() {
+                    self.refresh_slots(context.gpu_resources);
+                }
+            }
+            State::Closing => {
+                if self.window.is_idle() {
+                    return ScreenTransition::Pop;
+                }
+            }
+        }
+
+        ScreenTransition::None
+    }
+
+    fn is_opaque(&self) -> bool {
+        false
+    }
+}
+
+impl Renderable for SettingsScreen {
+    fn render<'enc>(
+        &'enc self,
+        resources: &'enc GpuCommonResources,
+        render_pass: &mut wgpu::RenderPass<'enc>,
+        transform: Mat4,
+        projection: Mat4,
+    ) {
+        let transform = projection * transform;
+
+        render_pass.push_debug_group("SettingsScreen");
+        self.window.render(
+            resources,
+            render_pass,
+            self.window_texture.gpu_texture(resources),
+            transform,
+        );
+        let opacity = self.window.opacity();
+        self.title
+            .render(resources, render_pass, transform, opacity);
+        for slot in &self.slots {
+            slot.button.render(
+                resources,
+                render_pass,
+                self.button_texture.gpu_texture(resources),
+                transform,
+                opacity,
+            );
+        }
+        for slot in &self.slots {
+            if let Some(label) = &slot.label {
+                label.render(resources, render_pass, transform, opacity);
+            }
+        }
+        if let Some(hint) = &self.hint {
+            hint.render(resources, render_pass, transform, opacity);
+        }
+        render_pass.pop_debug_group();
+    }
+
+    fn resize(&mut self, _resources: &GpuCommonResources) {}
+}
+
+impl OverlayVisitable for SettingsScreen {
+    fn visit_overlay(&self, _collector: &mut OverlayCollector) {}
+}
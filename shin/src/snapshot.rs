@@ -0,0 +1,47 @@
+//! Helpers shared by the serializable snapshots of the engine state: the [`LayersSnapshot`](crate::layer::LayersSnapshot), the [`AudioSnapshot`](crate::audio::AudioSnapshot) and the saves containing them.
+//!
+//! Each schema stores its own version, which has to be bumped on any incompatible change to it.
+//! The snapshots written by the older engine versions are still accepted, the fields added since then are filled with `#[serde(default)]`.
+
+use anyhow::{bail, Result};
+
+/// Fails if the snapshot was written by a newer engine version, as its schema could be misinterpreted
+pub fn check_version(what: &str, version: u32, supported: u32) -> Result<()> {
+    if version > supported {
+        bail!(
+            "The {} version {} is newer than the supported {}",
+            what,
+            version,
+            supported
+        );
+    }
+
+    Ok(())
+}
+
+/// Serializes the snapshot to JSON and back, checking that nothing is lost on the way
+#[cfg(test)]
+pub fn json_roundtrip<T>(snapshot: &T) -> T
+where
+    T: serde::Serialize + serde::de::DeserializeOwned + PartialEq + std::fmt::Debug,
+{
+    let json = serde_json::to_string(snapshot).unwrap();
+    let decoded: T = serde_json::from_str(&json).unwrap();
+    assert_eq!(&decoded, snapshot, "{}", json);
+    decoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::check_version;
+
+    #[test]
+    fn accepts_older_versions_only() {
+        assert!(check_version("test", 1, 2).is_ok());
+        assert!(check_version("test", 2, 2).is_ok());
+        assert_eq!(
+            check_version("test", 3, 2).unwrap_err().to_string(),
+            "The test version 3 is newer than the supported 2"
+        );
+    }
+}
@@ -0,0 +1,148 @@
+//! The CGs, BGM tracks and movies unlocked by the scenario with UNLOCK, shown in the galleries.
+//!
+//! The game keeps the unlocked CGs and BGMs in its global savedata, which is not used by the engine yet.
+//! So the unlocks are stored in a JSON file of their own (by default in the shin data directory), like the trophies.
+//!
+//! The unlocked movies are shown in the [`MovieGalleryScreen`], opened from the system menu.
+
+mod movie_gallery;
+
+use std::{
+    collections::BTreeSet,
+    io::{BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+pub use movie_gallery::MovieGalleryScreen;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+/// What an UNLOCK command unlocks
+///
+/// The values are in the order of the command's description (CG, BGM or MOVIE), they were not confirmed against the original engine.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum UnlockType {
+    /// Indexing into the picture info table
+    Picture,
+    /// Indexing into the BGM info table
+    Bgm,
+    /// Indexing into the movie info table
+    Movie,
+}
+
+impl UnlockType {
+    pub fn from_raw(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(UnlockType::Picture),
+            1 => Some(UnlockType::Bgm),
+            2 => Some(UnlockType::Movie),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct UnlocksFile {
+    pictures: BTreeSet<i32>,
+    bgms: BTreeSet<i32>,
+    movies: BTreeSet<i32>,
+}
+
+impl UnlocksFile {
+    fn set(&self, ty: UnlockType) -> &BTreeSet<i32> {
+        match ty {
+            UnlockType::Picture => &self.pictures,
+            UnlockType::Bgm => &self.bgms,
+            UnlockType::Movie => &self.movies,
+        }
+    }
+
+    fn set_mut(&mut self, ty: UnlockType) -> &mut BTreeSet<i32> {
+        match ty {
+            UnlockType::Picture => &mut self.pictures,
+            UnlockType::Bgm => &mut self.bgms,
+            UnlockType::Movie => &mut self.movies,
+        }
+    }
+}
+
+pub struct Unlocks {
+    /// `None` if the unlocks are not persisted
+    path: Option<PathBuf>,
+    file: UnlocksFile,
+}
+
+impl Unlocks {
+    /// `unlocks.json` in the shin data directory, if there is one on this platform
+    pub fn default_path() -> Option<PathBuf> {
+        dirs_next::data_dir().map(|p| p.join("shin").join("unlocks.json"))
+    }
+
+    /// Loads the unlocks from the `path`, starting with nothing unlocked if the file doesn't exist or is broken
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let file = match &path {
+            Some(path) if path.exists() => match Self::read(path) {
+                Ok(file) => {
+                    debug!("Loaded the unlocks from {}", path.display());
+                    file
+                }
+                Err(e) => {
+                    warn!("Failed to load the unlocks: {:?}", e);
+                    UnlocksFile::default()
+                }
+            },
+            _ => UnlocksFile::default(),
+        };
+
+        Self { path, file }
+    }
+
+    fn read(path: &Path) -> Result<UnlocksFile> {
+        let file =
+            std::fs::File::open(path).with_context(|| format!("Opening {}", path.display()))?;
+        serde_json::from_reader(BufReader::new(file))
+            .with_context(|| format!("Parsing {}", path.display()))
+    }
+
+    /// Unlocks the item and saves the unlocks, returns `false` if it was already unlocked
+    pub fn unlock(&mut self, ty: UnlockType, index: i32) -> bool {
+        if !self.file.set_mut(ty).insert(index) {
+            return false;
+        }
+
+        info!("Unlocked {:?} {}", ty, index);
+        if let Some(path) = &self.path {
+            if let Err(e) = Self::write(path, &self.file) {
+                warn!("Failed to save the unlocks: {:?}", e);
+            }
+        }
+
+        true
+    }
+
+    /// The unlocked items of the type, in the ascending order
+    pub fn unlocked(&self, ty: UnlockType) -> impl Iterator<Item = i32> + '_ {
+        self.file.set(ty).iter().copied()
+    }
+
+    fn write(path: &Path, file: &UnlocksFile) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Creating {}", parent.display()))?;
+        }
+
+        // write to a temporary file first, so that a crash doesn't lose the previous unlocks
+        let temp_path = path.with_extension("json.tmp");
+        let temp_file = std::fs::File::create(&temp_path)
+            .with_context(|| format!("Creating {}", temp_path.display()))?;
+        let mut writer = BufWriter::new(temp_file);
+        serde_json::to_writer_pretty(&mut writer, file).context("Writing the unlocks")?;
+        writer.flush().context("Writing the unlocks")?;
+        std::fs::rename(&temp_path, path)
+            .with_context(|| format!("Replacing {}", path.display()))?;
+
+        Ok(())
+    }
+}
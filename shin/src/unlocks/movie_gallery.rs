@@ -0,0 +1,479 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Result;
+use glam::{vec2, vec3, Mat4, Vec2};
+use pollster::FutureExt;
+use shin_audio::AudioManager;
+use shin_core::{
+    format::scenario::Scenario,
+    time::{Easing, Ticks, Tween},
+};
+use shin_render::{GpuCommonResources, LazyGpuTexture, PooledRenderTarget, Renderable};
+use shin_tasks::{AsyncComputeTaskPool, Task, TaskPanic};
+use shin_video::{MovieThumbnail, VideoPlayer};
+use tracing::{error, warn};
+
+use crate::{
+    app::{Screen, ScreenTransition},
+    asset::{movie::Movie, AnyAssetServer},
+    i18n::Localizer,
+    input::{actions::MenuAction, ActionState},
+    layer::FontAtlas,
+    render::overlay::{OverlayCollector, OverlayVisitable},
+    settings::SettingsStore,
+    update::{Updatable, UpdateContext},
+    widget::{default_nine_patch, default_window_image, Label, Window},
+};
+
+const FADE: Tween = Tween {
+    duration: Ticks::from_f32(10.0),
+    easing: Easing::SineOut,
+};
+
+const COLUMNS: usize = 4;
+const ROWS: usize = 3;
+const PAGE_SIZE: usize = COLUMNS * ROWS;
+const CELL_WIDTH: f32 = 420.0;
+const CELL_HEIGHT: f32 = 270.0;
+/// The thumbnails are the full screen movie frames scaled down
+const THUMBNAIL_SCALE: f32 = 0.18;
+const THUMBNAIL_WIDTH: f32 = 1920.0 * THUMBNAIL_SCALE;
+const THUMBNAIL_HEIGHT: f32 = 1080.0 * THUMBNAIL_SCALE;
+const TITLE_FONT_HEIGHT: f32 = 48.0;
+const NAME_FONT_HEIGHT: f32 = 28.0;
+const WINDOW_PADDING: f32 = 40.0;
+const HIGHLIGHT_PADDING: f32 = 12.0;
+/// How far left and right seek while playing
+const SEEK_STEP: Duration = Duration::from_secs(10);
+
+type LoadTask<T> = Task<Result<Result<T>, TaskPanic>>;
+
+enum State {
+    Opening,
+    Browsing,
+    /// Waiting for the selected movie to be loaded
+    Loading(LoadTask<Arc<Movie>>),
+    Playing(VideoPlayer),
+    Closing,
+}
+
+struct Entry {
+    path: String,
+    name: Label,
+    thumbnail: Option<MovieThumbnail>,
+    /// Extracting the first frame takes a while, so it's done in the background
+    thumbnail_task: Option<LoadTask<MovieThumbnail>>,
+}
+
+/// Lists the unlocked movies with their first frames, and plays the selected one
+///
+/// While playing, activating pauses and resumes the movie, left and right seek and cancelling returns to the list.
+pub struct MovieGalleryScreen {
+    asset_server: Arc<AnyAssetServer>,
+    audio_manager: Arc<AudioManager>,
+    settings: Arc<SettingsStore>,
+    window_texture: LazyGpuTexture,
+    window: Window,
+    /// Drawn around the selected movie
+    highlight: Window,
+    title: Label,
+    entries: Vec<Entry>,
+    selected: usize,
+    /// The thumbnails or the movie are drawn here first, see [`crate::layer::MovieLayer`]
+    render_target: PooledRenderTarget,
+    state: State,
+    action_state: ActionState<MenuAction>,
+}
+
+/// The center of the thumbnail in the `slot` of the page
+fn slot_center(slot: usize) -> Vec2 {
+    let column = slot % COLUMNS;
+    let row = slot / COLUMNS;
+    vec2(
+        CELL_WIDTH * (column as f32 + 0.5 - COLUMNS as f32 / 2.0),
+        TITLE_FONT_HEIGHT / 2.0 + CELL_HEIGHT * (row as f32 + 0.5 - ROWS as f32 / 2.0),
+    )
+}
+
+fn highlight_rect(slot: usize) -> (f32, f32, f32, f32) {
+    let center = slot_center(slot);
+    (
+        center.x - THUMBNAIL_WIDTH / 2.0 - HIGHLIGHT_PADDING,
+        center.y - THUMBNAIL_HEIGHT / 2.0 - HIGHLIGHT_PADDING,
+        center.x + THUMBNAIL_WIDTH / 2.0 + HIGHLIGHT_PADDING,
+        center.y + THUMBNAIL_HEIGHT / 2.0 + NAME_FONT_HEIGHT + HIGHLIGHT_PADDING,
+    )
+}
+
+impl MovieGalleryScreen {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        context: &UpdateContext,
+        font_atlas: Arc<FontAtlas>,
+        i18n: &Localizer,
+        audio_manager: Arc<AudioManager>,
+        settings: Arc<SettingsStore>,
+        scenario: &Scenario,
+        unlocked: impl Iterator<Item = i32>,
+    ) -> Self {
+        let resources = context.gpu_resources;
+        let movie_info = &scenario.info_tables().movie_info;
+
+        let entries = unlocked
+            .filter_map(|movie_id| {
+                let Some(info) = movie_info.get(movie_id as usize) else {
+                    warn!("Unlocked movie {} is not in the movie info", movie_id);
+                    return None;
+                };
+                Some(info)
+            })
+            .enumerate()
+            .map(|(index, info)| {
+                let center = slot_center(index % PAGE_SIZE);
+                let path = info.path();
+
+                let thumbnail_task = {
+                    let resources = resources.clone();
+                    let asset_server = context.asset_server.clone();
+                    let path = path.clone();
+                    AsyncComputeTaskPool::get().spawn_named(
+                        format!("Movie thumbnail {}", path),
+                        async move {
+                            let movie = asset_server.load::<Movie, _>(&path).await?;
+                            movie.thumbnail(&resources)
+                        },
+                    )
+                };
+
+                Entry {
+                    path,
+                    name: Label::new(
+                        resources,
+                        font_atlas.clone(),
+                        info.name.as_str(),
+                        vec2(
+                            center.x - CELL_WIDTH / 2.0,
+                            center.y + THUMBNAIL_HEIGHT / 2.0,
+                        ),
+                        CELL_WIDTH,
+                        NAME_FONT_HEIGHT,
+                    ),
+                    thumbnail: None,
+                    thumbnail_task: Some(thumbnail_task),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let half_width = CELL_WIDTH * COLUMNS as f32 / 2.0;
+        let half_height = (CELL_HEIGHT * ROWS as f32 + TITLE_FONT_HEIGHT) / 2.0;
+        let mut window = Window::new(
+            resources,
+            default_nine_patch(),
+            (
+                -half_width - WINDOW_PADDING,
+                -half_height - WINDOW_PADDING,
+                half_width + WINDOW_PADDING,
+                half_height + WINDOW_PADDING,
+            ),
+        );
+        window.show(FADE);
+
+        let mut highlight = Window::new(resources, default_nine_patch(), highlight_rect(0));
+        if !entries.is_empty() {
+            highlight.show(FADE);
+        }
+
+        let title = if entries.is_empty() {
+            i18n.tr("movie_gallery.empty")
+        } else {
+            i18n.tr("movie_gallery.title")
+        };
+
+        Self {
+            asset_server: context.asset_server.clone(),
+            audio_manager,
+            settings,
+            window_texture: LazyGpuTexture::new(
+                default_window_image(),
+                Some("MovieGallery Window"),
+            ),
+            window,
+            highlight,
+            title: Label::new(
+                resources,
+                font_atlas,
+                &title,
+                vec2(-half_width, -half_height),
+                half_width * 2.0,
+                TITLE_FONT_HEIGHT,
+            ),
+            entries,
+            selected: 0,
+            render_target: resources.acquire_render_target(Some("MovieGallery RenderTarget")),
+            state: State::Opening,
+            action_state: ActionState::new(),
+        }
+    }
+
+    fn page(&self) -> usize {
+        self.selected / PAGE_SIZE
+    }
+
+    fn select(&mut self, resources: &GpuCommonResources, index: usize) {
+        self.selected = index;
+        self.highlight
+            .set_rect(resources, highlight_rect(index % PAGE_SIZE));
+    }
+
+    /// Moves the selection by the arrows, wrapping around the list
+    fn navigate(&mut self, resources: &GpuCommonResources) {
+        let count = self.entries.len();
+        if count == 0 {
+            return;
+        }
+
+        let step = if self.action_state.is_just_pressed(MenuAction::Left) {
+            count - 1
+        } else if self.action_state.is_just_pressed(MenuAction::Right) {
+            1
+        } else if self.action_state.is_just_pressed(MenuAction::Up) {
+            count - COLUMNS % count
+        } else if self.action_state.is_just_pressed(MenuAction::Down) {
+            COLUMNS % count
+        } else {
+            return;
+        };
+        self.select(resources, (self.selected + step) % count);
+    }
+
+    fn poll_thumbnails(&mut self) {
+        for entry in &mut self.entries {
+            if !entry
+                .thumbnail_task
+                .as_ref()
+                .is_some_and(|task| task.is_finished())
+            {
+                continue;
+            }
+
+            match entry.thumbnail_task.take().unwrap().block_on() {
+                Ok(Ok(thumbnail)) => entry.thumbnail = Some(thumbnail),
+                Ok(Err(e)) => warn!("Could not make a thumbnail of {}: {:?}", entry.path, e),
+                Err(panic) => error!("{}", panic),
+            }
+        }
+    }
+
+    fn start_loading(&self) -> LoadTask<Arc<Movie>> {
+        let asset_server = self.asset_server.clone();
+        let path = self.entries[self.selected].path.clone();
+        AsyncComputeTaskPool::get().spawn_named(format!("Movie gallery {}", path), async move {
+            asset_server.load::<Movie, _>(&path).await
+        })
+    }
+
+    fn update_playing(&mut self, context: &UpdateContext, player: &mut VideoPlayer) -> bool {
+        player.update(context.time_delta_ticks(), &context.gpu_resources.queue);
+
+        if self.action_state.is_just_pressed(MenuAction::Cancel) {
+            player.stop();
+            return false;
+        }
+        if self.action_state.is_just_pressed(MenuAction::Activate) {
+            if player.is_paused() {
+                player.resume();
+            } else {
+                player.pause();
+            }
+        }
+
+        let seek_to = if self.action_state.is_just_pressed(MenuAction::Left) {
+            Some(player.position().saturating_sub(SEEK_STEP))
+        } else if self.action_state.is_just_pressed(MenuAction::Right) {
+            Some((player.position() + SEEK_STEP).min(player.duration()))
+        } else {
+            None
+        };
+        if let Some(position) = seek_to {
+            if let Err(e) = player.seek(position) {
+                warn!("Could not seek the movie: {:?}", e);
+            }
+        }
+
+        !player.is_finished()
+    }
+}
+
+impl Screen for MovieGalleryScreen {
+    fn name(&self) -> &'static str {
+        "MovieGallery"
+    }
+
+    fn update(&mut self, context: &UpdateContext) -> ScreenTransition {
+        self.action_state.update(context.raw_input_state);
+        self.window.update(context);
+        self.highlight.update(context);
+        self.poll_thumbnails();
+
+        self.state = match std::mem::replace(&mut self.state, State::Closing) {
+            State::Opening if self.window.is_idle() => State::Browsing,
+            State::Browsing => {
+                self.navigate(context.gpu_resources);
+                if self.action_state.is_just_pressed(MenuAction::Cancel) {
+                    self.window.hide(FADE);
+                    self.highlight.hide(FADE);
+                    State::Closing
+                } else if self.action_state.is_just_pressed(MenuAction::Activate)
+                    && !self.entries.is_empty()
+                {
+                    State::Loading(self.start_loading())
+                } else {
+                    State::Browsing
+                }
+            }
+            State::Loading(task) if task.is_finished() => {
+                let options = self.settings.get().movie.video_player_options();
+                match task.block_on() {
+                    Ok(Ok(movie)) => {
+                        match movie.play(context.gpu_resources, &self.audio_manager, options) {
+                            Ok(player) => State::Playing(player),
+                            Err(e) => {
+                                error!("Could not play the movie: {:?}", e);
+                                State::Browsing
+                            }
+                        }
+                    }
+                    Ok(Err(e)) => {
+                        error!("Could not load the movie: {:?}", e);
+                        State::Browsing
+                    }
+                    Err(panic) => {
+                        error!("{}", panic);
+                        State::Browsing
+                    }
+                }
+            }
+            State::Playing(mut player) => {
+                if self.update_playing(context, &mut player) {
+                    State::Playing(player)
+                } else {
+                    State::Browsing
+                }
+            }
+            State::Closing if self.window.is_idle() => return ScreenTransition::Pop,
+            state => state,
+        };
+
+        ScreenTransition::None
+    }
+
+    fn is_opaque(&self) -> bool {
+        matches!(self.state, State::Playing(_))
+    }
+}
+
+impl Renderable for MovieGalleryScreen {
+    fn render<'enc>(
+        &'enc self,
+        resources: &'enc GpuCommonResources,
+        render_pass: &mut wgpu::RenderPass<'enc>,
+        transform: Mat4,
+        projection: Mat4,
+    ) {
+        render_pass.push_debug_group("MovieGalleryScreen");
+
+        if let State::Playing(player) = &self.state {
+            {
+                let mut encoder = resources.start_encoder();
+                let mut target_pass = self.render_target.begin_raw_render_pass(
+                    resources,
+                    &mut encoder,
+                    Some("MovieGallery RenderPass"),
+                );
+                player.render(
+                    resources,
+                    &mut target_pass,
+                    transform,
+                    self.render_target.projection_matrix(),
+                );
+            }
+            resources.draw_sprite(
+                render_pass,
+                self.render_target.vertex_source(),
+                self.render_target.bind_group(),
+                projection,
+            );
+            render_pass.pop_debug_group();
+            return;
+        }
+
+        let total_transform = projection * transform;
+        let window_texture = self.window_texture.gpu_texture(resources);
+        self.window
+            .render(resources, render_pass, window_texture, total_transform);
+        self.highlight
+            .render(resources, render_pass, window_texture, total_transform);
+
+        let opacity = self.window.opacity();
+        self.title
+            .render(resources, render_pass, total_transform, opacity);
+
+        let page_start = self.page() * PAGE_SIZE;
+        let page = self
+            .entries
+            .iter()
+            .enumerate()
+            .skip(page_start)
+            .take(PAGE_SIZE);
+
+        // the thumbnails can't fade, so they are only shown when the window is fully open
+        if matches!(self.state, State::Browsing | State::Loading(_)) {
+            {
+                let mut encoder = resources.start_encoder();
+                let mut target_pass = self.render_target.begin_raw_render_pass(
+                    resources,
+                    &mut encoder,
+                    Some("MovieGallery Thumbnails RenderPass"),
+                );
+                for (index, entry) in page.clone() {
+                    let Some(thumbnail) = &entry.thumbnail else {
+                        continue;
+                    };
+                    let center = slot_center(index % PAGE_SIZE);
+                    let thumbnail_transform = transform
+                        * Mat4::from_translation(vec3(center.x, center.y, 0.0))
+                        * Mat4::from_scale(vec3(THUMBNAIL_SCALE, THUMBNAIL_SCALE, 1.0));
+                    thumbnail.render(
+                        resources,
+                        &mut target_pass,
+                        thumbnail_transform,
+                        self.render_target.projection_matrix(),
+                    );
+                }
+            }
+            resources.draw_sprite(
+                render_pass,
+                self.render_target.vertex_source(),
+                self.render_target.bind_group(),
+                projection,
+            );
+        }
+
+        for (_, entry) in page {
+            entry
+                .name
+                .render(resources, render_pass, total_transform, opacity);
+        }
+
+        render_pass.pop_debug_group();
+    }
+
+    fn resize(&mut self, resources: &GpuCommonResources) {
+        self.render_target
+            .resize(resources, resources.current_render_buffer_size());
+    }
+}
+
+impl OverlayVisitable for MovieGalleryScreen {
+    fn visit_overlay(&self, _collector: &mut OverlayCollector) {}
+}
@@ -0,0 +1,160 @@
+use glam::{vec2, vec4, Mat4, Vec2};
+use image::{Rgba, RgbaImage};
+use shin_core::time::{Easing, Ticks, Tween, Tweener};
+use shin_render::{vertices::ButtonVertex, GpuCommonResources, GpuTexture, VertexBuffer};
+
+use crate::update::{Updatable, UpdateContext};
+
+/// How many times a button blinks when activated
+const FLASH_COUNT: usize = 3;
+/// Duration of a single fade of the flash (in or out)
+const FLASH_FADE: Tween = Tween {
+    duration: Ticks::from_f32(4.0),
+    easing: Easing::Linear,
+};
+/// Duration of the transition when the button gets or loses focus
+const HIGHLIGHT_FADE: Tween = Tween {
+    duration: Ticks::from_f32(8.0),
+    easing: Easing::SineOut,
+};
+
+/// Where the button is in the texture, in pixels
+///
+/// The texture contains the normal and the flashing look of the button side by side.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ButtonSprite {
+    pub texture_size: Vec2,
+    pub size: Vec2,
+    /// Top-left corner of the normal look
+    pub normal: Vec2,
+    /// Top-left corner of the flashing look
+    pub flash: Vec2,
+}
+
+impl ButtonSprite {
+    /// Builds the vertices to draw the button with the top-left corner at `position`
+    fn vertices(&self, position: Vec2) -> [ButtonVertex; 6] {
+        let vertex = |corner: Vec2| ButtonVertex {
+            position: position + corner * self.size,
+            texture_coordinate: (self.normal + corner * self.size) / self.texture_size,
+            flash_texture_coordinate: (self.flash + corner * self.size) / self.texture_size,
+        };
+
+        [
+            vertex(vec2(0.0, 0.0)),
+            vertex(vec2(1.0, 0.0)),
+            vertex(vec2(0.0, 1.0)),
+            vertex(vec2(1.0, 0.0)),
+            vertex(vec2(1.0, 1.0)),
+            vertex(vec2(0.0, 1.0)),
+        ]
+    }
+}
+
+/// A plain button texture of the given size, with the normal look on the left and the flashing one on the right
+///
+/// The game's menu textures are not figured out yet, so the menus are drawn with this one.
+pub fn default_button_image(size: Vec2) -> RgbaImage {
+    let (width, height) = (size.x as u32, size.y as u32);
+    RgbaImage::from_fn(width * 2, height, |x, y| {
+        let flash = x >= width;
+        let x = x % width;
+        let border = x == 0 || y == 0 || x == width - 1 || y == height - 1;
+        match (flash, border) {
+            (false, true) => Rgba([255, 255, 255, 64]),
+            (false, false) => Rgba([255, 255, 255, 16]),
+            (true, true) => Rgba([255, 240, 200, 255]),
+            (true, false) => Rgba([255, 220, 150, 96]),
+        }
+    })
+}
+
+/// The sprite to draw [`default_button_image`] with
+pub fn default_button_sprite(size: Vec2) -> ButtonSprite {
+    ButtonSprite {
+        texture_size: vec2(size.x * 2.0, size.y),
+        size,
+        normal: vec2(0.0, 0.0),
+        flash: vec2(size.x, 0.0),
+    }
+}
+
+/// A menu button
+///
+/// The highlighted (focused) button is drawn with its flashing look, [`Button::flash`] blinks it to acknowledge the activation.
+pub struct Button {
+    vertex_buffer: VertexBuffer<ButtonVertex>,
+    highlighted: bool,
+    flash: Tweener,
+}
+
+impl Button {
+    /// Creates a button with the top-left corner at `position`, in virtual screen coordinates
+    pub fn new(resources: &GpuCommonResources, sprite: ButtonSprite, position: Vec2) -> Self {
+        let vertex_buffer = VertexBuffer::new(
+            resources,
+            &sprite.vertices(position),
+            Some("Button VertexBuffer"),
+        );
+
+        Self {
+            vertex_buffer,
+            highlighted: false,
+            flash: Tweener::new(0.0),
+        }
+    }
+
+    pub fn set_highlighted(&mut self, highlighted: bool) {
+        if highlighted == self.highlighted {
+            return;
+        }
+        self.highlighted = highlighted;
+        self.flash
+            .enqueue_now(if highlighted { 1.0 } else { 0.0 }, HIGHLIGHT_FADE);
+    }
+
+    /// Blinks the button, then returns it to the highlighted or the normal look
+    pub fn flash(&mut self) {
+        self.flash.enqueue_now(1.0, FLASH_FADE);
+        for _ in 0..FLASH_COUNT {
+            self.flash.enqueue(0.0, FLASH_FADE);
+            self.flash.enqueue(1.0, FLASH_FADE);
+        }
+        if !self.highlighted {
+            self.flash.enqueue(0.0, HIGHLIGHT_FADE);
+        }
+    }
+
+    /// Whether the button is still animating, the menus wait for the flash to finish before acting on the activation
+    pub fn is_animating(&self) -> bool {
+        !self.flash.is_idle()
+    }
+
+    pub fn render<'enc>(
+        &'enc self,
+        resources: &'enc GpuCommonResources,
+        render_pass: &mut wgpu::RenderPass<'enc>,
+        texture: &'enc GpuTexture,
+        transform: Mat4,
+        opacity: f32,
+    ) {
+        if opacity <= 0.0 {
+            return;
+        }
+
+        resources.draw_button(
+            render_pass,
+            self.vertex_buffer.vertex_source(),
+            texture.bind_group(),
+            transform,
+            vec4(1.0, 1.0, 1.0, opacity),
+            self.flash.value(),
+        );
+    }
+}
+
+impl Updatable for Button {
+    fn update(&mut self, context: &UpdateContext) {
+        self.flash.update(context.time_delta_ticks());
+    }
+}
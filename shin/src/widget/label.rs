@@ -0,0 +1,93 @@
+use std::sync::Arc;
+
+use glam::{vec3, Mat4, Vec2};
+use shin_core::{
+    layout::{LayoutParams, LayoutedMessage, LayouterState, LayoutingMode},
+    time::Ticks,
+    vm::command::types::MessageTextLayout,
+};
+use shin_render::{vertices::TextVertex, GpuCommonResources, VertexBuffer};
+
+use crate::layer::{build_text_vertices, FontAtlas};
+
+/// A static text, laid out with the game font
+pub struct Label {
+    vertex_buffer: VertexBuffer<TextVertex>,
+    used_codepoints: Vec<u16>,
+    font_atlas: Arc<FontAtlas>,
+}
+
+impl Label {
+    /// Lays out the `text` (in the layouter markup) centered in a `width` wide area starting at `position`
+    pub fn new(
+        resources: &GpuCommonResources,
+        font_atlas: Arc<FontAtlas>,
+        text: &str,
+        position: Vec2,
+        width: f32,
+        font_height: f32,
+    ) -> Self {
+        let params = LayoutParams {
+            font: font_atlas.get_font(),
+            layout_width: width,
+            character_name_layout_width: 0.0,
+            base_font_height: font_height,
+            furigana_font_height: font_height * 0.4,
+            font_horizontal_base_scale: 0.9697,
+            text_layout: MessageTextLayout::Center,
+            default_state: LayouterState {
+                text_color: vec3(1.0, 1.0, 1.0),
+                instant: true,
+                ..Default::default()
+            },
+            has_character_name: false,
+            mode: LayoutingMode::GenericText,
+            line_breaking: Default::default(),
+            ruby: Default::default(),
+            direction: Default::default(),
+        };
+
+        let LayoutedMessage { chars, .. } = shin_core::layout::layout_text(params, text);
+
+        let mut used_codepoints = Vec::new();
+        let vertices = build_text_vertices(
+            resources,
+            &font_atlas,
+            position,
+            chars,
+            &mut used_codepoints,
+        );
+
+        Self {
+            vertex_buffer: VertexBuffer::new(resources, &vertices, Some("Label VertexBuffer")),
+            used_codepoints,
+            font_atlas,
+        }
+    }
+
+    pub fn render<'enc>(
+        &'enc self,
+        resources: &'enc GpuCommonResources,
+        render_pass: &mut wgpu::RenderPass<'enc>,
+        transform: Mat4,
+        opacity: f32,
+    ) {
+        // all the chars are shown instantly at time 0, and the text shader fades the chars in during the first tick after their time
+        // so passing the opacity as the time fades the whole label
+        resources.draw_text(
+            render_pass,
+            self.vertex_buffer.vertex_source(),
+            self.font_atlas.texture_bind_group(),
+            transform,
+            Ticks::from_f32(opacity.clamp(0.0, 1.0)),
+        );
+    }
+}
+
+impl Drop for Label {
+    fn drop(&mut self) {
+        for &codepoint in self.used_codepoints.iter() {
+            self.font_atlas.free_glyph(codepoint);
+        }
+    }
+}
@@ -0,0 +1,12 @@
+//! Building blocks of the menu screens (the system menu, save/load, settings)
+//!
+//! Unlike the layers, the widgets don't own their textures: all the widgets of a screen usually come from a single texture archive, which is owned by the screen.
+//! The texture is passed to `render` instead.
+
+mod button;
+mod label;
+mod window;
+
+pub use button::{default_button_image, default_button_sprite, Button, ButtonSprite};
+pub use label::Label;
+pub use window::{default_nine_patch, default_window_image, Window};
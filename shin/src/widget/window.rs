@@ -0,0 +1,119 @@
+use glam::{vec2, vec4, Mat4};
+use image::{Rgba, RgbaImage};
+use shin_core::time::{Tween, Tweener};
+use shin_render::{
+    vertices::WindowVertex, GpuCommonResources, GpuTexture, NinePatch, VertexBuffer,
+};
+
+use crate::update::{Updatable, UpdateContext};
+
+/// Size of the borders of [`default_window_image`]
+const DEFAULT_WINDOW_BORDER: u32 = 16;
+
+/// A plain window texture
+///
+/// The game's menu textures are not figured out yet, so the menus are drawn with this one.
+pub fn default_window_image() -> RgbaImage {
+    let size = DEFAULT_WINDOW_BORDER * 3;
+    RgbaImage::from_fn(size, size, |x, y| {
+        let edge_distance = x.min(y).min(size - 1 - x).min(size - 1 - y);
+        if edge_distance < 2 {
+            Rgba([200, 200, 220, 255])
+        } else {
+            Rgba([16, 16, 32, 220])
+        }
+    })
+}
+
+/// The nine-patch to draw [`default_window_image`] with
+pub fn default_nine_patch() -> NinePatch {
+    let size = (DEFAULT_WINDOW_BORDER * 3) as f32;
+    let border = DEFAULT_WINDOW_BORDER as f32;
+    NinePatch::new(vec2(size, size), (border, border, border, border))
+}
+
+/// A menu window, drawn from a nine-patch texture
+///
+/// The window starts hidden, [`Window::show`] and [`Window::hide`] fade it in and out.
+pub struct Window {
+    nine_patch: NinePatch,
+    rect: (f32, f32, f32, f32),
+    vertex_buffer: VertexBuffer<WindowVertex>,
+    opacity: Tweener,
+}
+
+impl Window {
+    /// Creates a window covering the `(l, t, r, b)` rectangle, in virtual screen coordinates
+    pub fn new(
+        resources: &GpuCommonResources,
+        nine_patch: NinePatch,
+        rect: (f32, f32, f32, f32),
+    ) -> Self {
+        let vertex_buffer = VertexBuffer::new_updatable(
+            resources,
+            NinePatch::VERTEX_COUNT as u32,
+            Some("Window VertexBuffer"),
+        );
+        vertex_buffer.write(&resources.queue, &nine_patch.vertices(rect));
+
+        Self {
+            nine_patch,
+            rect,
+            vertex_buffer,
+            opacity: Tweener::new(0.0),
+        }
+    }
+
+    pub fn set_rect(&mut self, resources: &GpuCommonResources, rect: (f32, f32, f32, f32)) {
+        if rect == self.rect {
+            return;
+        }
+        self.rect = rect;
+        self.vertex_buffer
+            .write(&resources.queue, &self.nine_patch.vertices(rect));
+    }
+
+    pub fn show(&mut self, tween: Tween) {
+        self.opacity.enqueue_now(1.0, tween);
+    }
+
+    pub fn hide(&mut self, tween: Tween) {
+        self.opacity.enqueue_now(0.0, tween);
+    }
+
+    pub fn opacity(&self) -> f32 {
+        self.opacity.value()
+    }
+
+    /// Whether the fade in or out has finished
+    pub fn is_idle(&self) -> bool {
+        self.opacity.is_idle()
+    }
+
+    pub fn render<'enc>(
+        &'enc self,
+        resources: &'enc GpuCommonResources,
+        render_pass: &mut wgpu::RenderPass<'enc>,
+        texture: &'enc GpuTexture,
+        transform: Mat4,
+    ) {
+        let opacity = self.opacity.value();
+        if opacity <= 0.0 {
+            return;
+        }
+
+        resources.draw_window(
+            render_pass,
+            self.vertex_buffer.vertex_source(),
+            texture.bind_group(),
+            transform,
+            vec4(1.0, 1.0, 1.0, opacity),
+        );
+    }
+}
+
+impl Updatable for Window {
+    fn update(&mut self, context: &UpdateContext) {
+        self.opacity.update(context.time_delta_ticks());
+    }
+}
@@ -1,52 +1,162 @@
-use std::sync::{Arc, RwLock};
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    path::Path,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use anyhow::{Context, Result};
 use glam::Mat4;
-use shin_audio::AudioManager;
-use shin_core::format::scenario::instruction_elements::CodeAddress;
+use shin_audio::{AudioManager, DuckingSettings};
+use shin_core::{format::scenario::instruction_elements::CodeAddress, layout::TextDirection};
 use shin_render::{
-    BindGroupLayouts, Camera, GpuCommonResources, Pillarbox, Pipelines, RenderTarget, Renderable,
+    init::{create_resources, select_surface_format, DeviceLossFlag},
+    AspectMode, Camera, GpuCommonResources, GpuImage, Pillarbox, PooledRenderTarget, Renderable,
 };
-use tracing::{debug, info, warn};
+use tracing::{debug, error, info, warn};
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
 use winit::{
-    dpi::{LogicalPosition, LogicalSize, PhysicalSize},
+    dpi::{LogicalSize, PhysicalPosition, PhysicalSize},
     event::*,
-    event_loop::{ControlFlow, EventLoop},
+    event_loop::{ControlFlow, EventLoop, EventLoopWindowTarget},
     keyboard::{KeyCode, PhysicalKey},
-    window::{Fullscreen, Window, WindowBuilder},
+    monitor::{MonitorHandle, VideoMode},
+    window::{Fullscreen, Icon, Window, WindowBuilder, WindowId},
 };
 
 use crate::{
-    adv::{assets::AdvAssets, Adv},
+    achievements::{AchievementBackend, Achievements, JsonBackend, NullBackend},
+    adv::{assets::AdvAssets, Adv, SaveStore},
+    app::ScreenStack,
     asset::{locate_assets, AnyAssetServer},
+    audio::AvSyncCalibration,
+    aux_window::{AuxWindow, AuxWindowContent, GameViewWindow},
+    capture::Recorder,
     cli::Cli,
+    config::{Config, WindowMode},
     fps_counter::FpsCounter,
-    input::RawInputState,
-    render::overlay::{OverlayManager, OverlayVisitable},
+    i18n::Localizer,
+    input::{Gamepads, RawInputState, VirtualCursor},
+    profiler::Profiler,
+    render::{
+        cursor,
+        overlay::{OverlayManager, OverlayVisitable},
+        test_pattern,
+    },
+    settings::{DisplayMode, MonitorInfo, SettingsStore, WindowGeometry},
     time::Time,
+    unlocks::Unlocks,
     update::{Updatable, UpdateContext},
 };
 
+const OFFLINE_AUDIO_SAMPLE_RATE: u32 = 48000;
+/// The update rate while the window is hidden, when the background frame rate is not limited
+const HIDDEN_UPDATE_FPS: u32 = 30;
+
+/// Decides when the next frame is drawn, depending on whether the window is focused and visible
+///
+/// In the foreground the frames are drawn back to back (limited by the vsync).
+/// In the background they are limited to [`WindowConfig::background_fps`](crate::config::WindowConfig::background_fps).
+/// While the window is minimized or occluded nothing is drawn, but the game is still updated, so that the audio and the scenario keep going.
+struct FramePacing {
+    background_fps: u32,
+    focused: bool,
+    occluded: bool,
+    minimized: bool,
+}
+
+impl FramePacing {
+    fn new(background_fps: u32) -> Self {
+        Self {
+            background_fps,
+            focused: true,
+            occluded: false,
+            minimized: false,
+        }
+    }
+
+    /// Whether the window can't be seen, so there is no point in drawing
+    fn is_hidden(&self) -> bool {
+        self.occluded || self.minimized
+    }
+
+    /// The time between the frames, `None` to draw them back to back
+    fn frame_interval(&self) -> Option<Duration> {
+        let fps = if self.is_hidden() {
+            match self.background_fps {
+                0 => HIDDEN_UPDATE_FPS,
+                fps => fps,
+            }
+        } else if !self.focused && self.background_fps > 0 {
+            self.background_fps
+        } else {
+            return None;
+        };
+        Some(Duration::from_secs(1) / fps)
+    }
+
+    /// Requests the next frame, or sets up the event loop to wake up when it's time for it
+    fn schedule_next_frame(&self, target: &EventLoopWindowTarget<()>, window: &Window) {
+        match self.frame_interval() {
+            None => {
+                target.set_control_flow(ControlFlow::Wait);
+                window.request_redraw();
+            }
+            Some(interval) => {
+                target.set_control_flow(ControlFlow::WaitUntil(Instant::now() + interval))
+            }
+        }
+    }
+}
+
 struct State<'window> {
+    instance: wgpu::Instance,
+    adapter: wgpu::Adapter,
     surface: wgpu::Surface<'window>,
     surface_config: wgpu::SurfaceConfiguration,
     window_size: (u32, u32),
     resources: Arc<GpuCommonResources>,
+    device_loss: DeviceLossFlag,
     camera: Camera,
     time: Time,
-    render_target: RenderTarget,
+    render_target: PooledRenderTarget,
     pillarbox: Pillarbox,
     asset_server: Arc<AnyAssetServer>,
     input: RawInputState,
+    gamepads: Gamepads,
+    virtual_cursor: VirtualCursor,
+    cursor_image: GpuImage,
     overlay_manager: OverlayManager,
     fps_counter: FpsCounter,
-    adv: Adv,
+    screens: ScreenStack,
+    /// Drawn instead of the game when set
+    color_test_pattern: Option<GpuImage>,
+    aux_windows: HashMap<WindowId, AuxWindow>,
+    /// Opened on the next event loop iteration, as opening a window needs the event loop
+    pending_aux_windows: Vec<Box<dyn AuxWindowContent>>,
+    audio_manager: Arc<AudioManager>,
+    settings: Arc<SettingsStore>,
+    /// The ducking settings given to the audio manager, to apply the changes made in the settings screen
+    ducking_settings: DuckingSettings,
+    /// The render scale from the config, used unless the settings override it
+    default_render_scale: f32,
+    av_sync: AvSyncCalibration,
+    recorder: Recorder,
+    /// Wrap the next frame into a graphics debugger capture
+    capture_next_frame: bool,
+    profiler: Profiler,
+    #[cfg(feature = "shader-hot-reload")]
+    shader_watcher: shin_render::ShaderWatcher,
 }
 
 impl<'state> State<'state> {
-    async fn new(window: &'state Window, cli: &Cli) -> Result<Self> {
+    async fn new(
+        window: &'state Window,
+        config: &Config,
+        settings: Arc<SettingsStore>,
+    ) -> Result<Self> {
         let window_size = window.inner_size();
         let window_size = (window_size.width, window_size.height);
 
@@ -69,108 +179,369 @@ impl<'state> State<'state> {
         .await
         .context("Failed to find appropriate wgpu adapter")?;
 
-        info!("Selected an adapter {:?}", adapter.get_info(),);
-        debug!("Adapter limits: {:?}", adapter.limits());
-
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    label: None,
-                    required_features: wgpu::Features::PUSH_CONSTANTS,
-                    // WebGL doesn't support all of wgpu's features, so if
-                    // we're building for the web we'll have to disable some.
-                    required_limits: wgpu::Limits {
-                        max_texture_dimension_2d: 4096,
-                        max_push_constant_size: 128,
-
-                        ..wgpu::Limits::downlevel_webgl2_defaults()
-                    },
-                },
-                // Some(&std::path::Path::new("trace")), // Trace path
-                None,
-            )
-            .await
-            .context("Failed to create wgpu device")?;
-
-        // TODO: make a better selection?
-        // TODO: rn we don't really support switching this
-        // it may be worth to add one more pass to convert from internal (Rgba8) to the preferred output format
-        // or support having everything in the preferred format? (sounds hard)
-        let surface_texture_format = surface.get_capabilities(&adapter).formats[0];
-
-        let config = wgpu::SurfaceConfiguration {
+        let (device, queue) = request_device(&adapter, config).await?;
+
+        let surface_formats = surface.get_capabilities(&adapter).formats;
+        let surface_texture_format = select_surface_format(&surface_formats)
+            .context("The surface is not supported by the adapter")?;
+        info!(
+            "Selected surface format {:?} (available: {:?})",
+            surface_texture_format, surface_formats
+        );
+
+        let surface_config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format: surface_texture_format,
             width: window_size.0,
             height: window_size.1,
-            present_mode: wgpu::PresentMode::Fifo,
+            present_mode: if config.window.vsync {
+                wgpu::PresentMode::Fifo
+            } else {
+                wgpu::PresentMode::AutoNoVsync
+            },
             desired_maximum_frame_latency: 2,
             alpha_mode: wgpu::CompositeAlphaMode::Auto,
             view_formats: vec![],
         };
-        surface.configure(&device, &config);
-
-        let bind_group_layouts = BindGroupLayouts::new(&device);
-        let pipelines = Pipelines::new(&device, &bind_group_layouts, surface_texture_format);
+        surface.configure(&device, &surface_config);
+        let device_loss = DeviceLossFlag::watch(&device);
 
-        let camera = Camera::new(window_size);
+        let camera = Camera::with_render_scale(
+            window_size,
+            render_scale(&settings, config.render.scale),
+            config.render.aspect_mode.into(),
+        );
 
-        let resources = Arc::new(GpuCommonResources {
+        let resources = Arc::new(create_resources(
             device,
             queue,
-            render_buffer_size: RwLock::new(camera.render_buffer_size()),
-            bind_group_layouts,
-            pipelines,
-        });
+            surface_texture_format,
+            camera.render_buffer_size(),
+            config.render.target_budget * 1024 * 1024,
+        ));
 
         let overlay = OverlayManager::new(&resources, surface_texture_format);
 
-        let render_target = RenderTarget::new(
-            &resources,
-            camera.render_buffer_size(),
-            Some("Window RenderTarget"),
-        );
+        let render_target = resources.acquire_render_target(Some("Window RenderTarget"));
 
         let pillarbox = Pillarbox::new(&resources);
+        let cursor_image = cursor::load(&resources);
+
+        let color_test_pattern = config
+            .debug
+            .color_test_pattern
+            .then(|| test_pattern::load(&resources));
+
+        let audio_manager = Arc::new(match &config.debug.offline_audio {
+            Some(path) => {
+                info!("Rendering the audio to {}", path.display());
+                AudioManager::new_offline(path, OFFLINE_AUDIO_SAMPLE_RATE)
+                    .context("Creating the offline audio manager")?
+            }
+            None => AudioManager::new(),
+        });
+        let ducking_settings = settings.get().voice_focus.ducking_settings();
+        audio_manager.set_ducking_settings(ducking_settings);
+        let av_sync = AvSyncCalibration::new(audio_manager.clone(), settings.clone());
 
-        let audio_manager = Arc::new(AudioManager::new());
+        for probe in shin_video::probe_h264_decoder_backends() {
+            match &probe.available {
+                Ok(()) => info!(
+                    "h264 decoder backend {}: available (hardware accelerated: {})",
+                    probe.backend, probe.hardware_accelerated
+                ),
+                Err(err) => warn!(
+                    "h264 decoder backend {}: unavailable: {}",
+                    probe.backend, err
+                ),
+            }
+        }
 
-        let asset_io = locate_assets(cli.assets_dir.as_deref()).context("Failed to locate assets. Consult the README for instructions on how to set up the game.")?;
+        let asset_io = locate_assets(config.paths.assets.as_deref()).context("Failed to locate assets. Consult the README for instructions on how to set up the game.")?;
 
         debug!("Asset IO: {:#?}", asset_io);
 
         let asset_server = Arc::new(AnyAssetServer::new(asset_io.into()));
 
         let adv_assets =
-            pollster::block_on(AdvAssets::load(&asset_server)).expect("Loading assets failed");
+            pollster::block_on(AdvAssets::load(&asset_server, &config.scenario.languages))
+                .context("Loading assets failed")?;
+        settings.set_scenario_languages(adv_assets.scenarios.languages());
+
+        let achievements_backend: Box<dyn AchievementBackend> = match config
+            .paths
+            .achievements_file
+            .clone()
+            .or_else(JsonBackend::default_path)
+        {
+            Some(path) => {
+                debug!("Storing the trophies in {}", path.display());
+                Box::new(JsonBackend::new(path))
+            }
+            None => {
+                warn!("No data directory found, the trophies will not be persisted");
+                Box::new(NullBackend)
+            }
+        };
+
+        let unlocks_path = config
+            .paths
+            .unlocks_file
+            .clone()
+            .or_else(Unlocks::default_path);
+        if unlocks_path.is_none() {
+            warn!("No data directory found, the unlocks will not be persisted");
+        }
+
+        let saves_dir = config
+            .paths
+            .saves_dir
+            .clone()
+            .or_else(SaveStore::default_dir);
+        match &saves_dir {
+            Some(dir) => debug!("Storing the saves in {}", dir.display()),
+            None => warn!("No data directory found, the game will not be saved"),
+        }
 
-        let mut adv = Adv::new(&resources, audio_manager, adv_assets, 0, 42);
+        let i18n = Arc::new(Localizer::new(
+            config.language.clone(),
+            config.paths.locales.as_deref(),
+            settings.clone(),
+        ));
 
-        if let Some(addr) = cli.fast_forward_to {
+        let mut adv = Adv::new(
+            &resources,
+            audio_manager.clone(),
+            adv_assets,
+            settings.clone(),
+            i18n,
+            Achievements::new(achievements_backend),
+            Unlocks::load(unlocks_path),
+            SaveStore::new(saves_dir, config.saves.autosave_slots),
+            0,
+            config.debug.random_seed,
+        );
+
+        if let Some(addr) = config.debug.fast_forward_to {
             debug!("Fast forwarding to {}", addr);
             adv.fast_forward_to(CodeAddress(addr));
         }
+        for &addr in &config.debug.break_at {
+            adv.add_breakpoint(CodeAddress(addr));
+        }
+        for name in &config.debug.break_on {
+            adv.break_on_command(name);
+        }
+        for &index in &config.debug.watch {
+            adv.watch_register(index);
+        }
+        if let Some(path) = config.debug.coverage_log.clone() {
+            adv.enable_coverage(path);
+        }
+        if config.render.vertical_novel_text {
+            adv.set_novel_text_direction(TextDirection::Vertical);
+        }
+
+        let mut recorder = Recorder::new(config.capture.clone());
+        if config.capture.record_on_start {
+            recorder
+                .start(audio_manager.mix_capture())
+                .context("Starting the recording")?;
+        }
 
         Ok(Self {
+            instance,
+            adapter,
             surface,
-            surface_config: config,
+            surface_config,
             window_size,
             resources,
+            device_loss,
             camera,
             time: Time::default(),
             render_target,
             pillarbox,
             asset_server,
             input: RawInputState::new(),
+            gamepads: Gamepads::new(),
+            virtual_cursor: VirtualCursor::new(),
+            cursor_image,
             overlay_manager: overlay,
             fps_counter: FpsCounter::new(),
-            adv,
+            screens: ScreenStack::new(Box::new(adv)),
+            color_test_pattern,
+            aux_windows: HashMap::new(),
+            pending_aux_windows: Vec::new(),
+            audio_manager,
+            settings,
+            ducking_settings,
+            default_render_scale: config.render.scale,
+            av_sync,
+            recorder,
+            capture_next_frame: false,
+            profiler: Profiler::new(config.debug.profiler, config.debug.profile_trace.clone()),
+            #[cfg(feature = "shader-hot-reload")]
+            shader_watcher: shin_render::ShaderWatcher::new(),
         })
     }
 
-    fn reconfigure_surface(&mut self) {
+    fn open_aux_window(&mut self, content: Box<dyn AuxWindowContent>) {
+        self.pending_aux_windows.push(content);
+    }
+
+    fn open_pending_aux_windows(&mut self, target: &EventLoopWindowTarget<()>) {
+        for content in std::mem::take(&mut self.pending_aux_windows) {
+            let title = content.title();
+            match AuxWindow::new(
+                target,
+                &self.instance,
+                &self.adapter,
+                &self.resources,
+                self.surface_config.format,
+                content,
+            ) {
+                Ok(window) => {
+                    self.aux_windows.insert(window.id(), window);
+                }
+                Err(e) => warn!("Failed to open the window {:?}: {:?}", title, e),
+            }
+        }
+    }
+
+    /// Returns `false` if the event is not for an auxiliary window
+    fn aux_window_event(&mut self, window_id: WindowId, event: &WindowEvent) -> bool {
+        let Some(window) = self.aux_windows.get_mut(&window_id) else {
+            return false;
+        };
+        if !window.window_event(&self.resources, event) {
+            self.aux_windows.remove(&window_id);
+        }
+        true
+    }
+
+    fn toggle_recording(&mut self) {
+        self.recorder.toggle(self.audio_manager.mix_capture());
+    }
+
+    /// Asks the attached graphics debugger (like RenderDoc) to capture the next frame
+    ///
+    /// Does nothing if the game is not running under one.
+    fn capture_frame(&mut self) {
+        info!("Capturing the next frame in the graphics debugger");
+        self.capture_next_frame = true;
+    }
+
+    fn dump_loaded_assets(&self) {
+        let assets = self.asset_server.loaded_assets();
+        info!("{} assets are loaded:", assets.len());
+        for asset in assets {
+            info!(
+                "  {} {} ({} bytes)",
+                asset.category, asset.path, asset.file_size
+            );
+        }
+    }
+
+    fn exit(&mut self) {
+        self.profiler.finish();
+        self.recorder.stop(self.audio_manager.mix_capture());
+        self.screens.on_exit();
+        if let Err(e) = self.audio_manager.finish() {
+            warn!("Failed to finish the offline audio: {:?}", e);
+        }
+    }
+
+    /// Configures the surface again after it was lost or got outdated (like on alt-tab from the exclusive fullscreen)
+    ///
+    /// The surface could have changed to support different formats (like when the window is moved to another monitor).
+    /// The final pass is built for the surface format, so then all the GPU resources are re-created with the new one.
+    fn reconfigure_surface(&mut self, config: &Config) -> Result<()> {
+        let surface_formats = self.surface.get_capabilities(&self.adapter).formats;
+        if surface_formats.is_empty() {
+            // happens while the window is minimized on some platforms, try again on the next frame
+            debug!("The surface has no supported formats, skipping the reconfiguration");
+            return Ok(());
+        }
+        if !surface_formats.contains(&self.surface_config.format) {
+            warn!(
+                "The surface format {:?} is not supported anymore (available: {:?})",
+                self.surface_config.format, surface_formats
+            );
+            return self.recreate_gpu_state(config);
+        }
+
         self.surface
             .configure(&self.resources.device, &self.surface_config);
+        Ok(())
+    }
+
+    /// Returns the reason if the device was lost since the last call
+    fn take_device_loss(&self) -> Option<String> {
+        self.device_loss.take()
+    }
+
+    /// Re-creates the device and everything on it, after the device was lost (on a driver crash or a GPU reset)
+    ///
+    /// The audio and the state of the game are kept: the screens rebuild their scenes on the new device (see [`Screen::recreate_gpu_state`](crate::app::Screen::recreate_gpu_state)).
+    /// The auxiliary windows are closed.
+    fn recreate_gpu_state(&mut self, config: &Config) -> Result<()> {
+        info!("Re-creating the GPU device and resources");
+
+        // the old adapter could be gone with the device (like when the GPU is reset)
+        let adapter = pollster::block_on(wgpu::util::initialize_adapter_from_env_or_default(
+            &self.instance,
+            Some(&self.surface),
+        ))
+        .context("Failed to find appropriate wgpu adapter")?;
+        let (device, queue) = pollster::block_on(request_device(&adapter, config))?;
+
+        let surface_formats = self.surface.get_capabilities(&adapter).formats;
+        let surface_texture_format = select_surface_format(&surface_formats)
+            .context("The surface is not supported by the adapter")?;
+        if surface_texture_format != self.surface_config.format {
+            info!(
+                "Selected surface format {:?} (available: {:?})",
+                surface_texture_format, surface_formats
+            );
+        }
+        self.surface_config.format = surface_texture_format;
+        self.surface.configure(&device, &self.surface_config);
+
+        self.device_loss = DeviceLossFlag::watch(&device);
+        self.adapter = adapter;
+        self.resources = Arc::new(create_resources(
+            device,
+            queue,
+            surface_texture_format,
+            self.camera.render_buffer_size(),
+            config.render.target_budget * 1024 * 1024,
+        ));
+
+        let resources = &self.resources;
+        self.overlay_manager = OverlayManager::new(resources, surface_texture_format);
+        self.render_target = resources.acquire_render_target(Some("Window RenderTarget"));
+        self.pillarbox = Pillarbox::new(resources);
+        self.cursor_image = cursor::load(resources);
+        if self.color_test_pattern.is_some() {
+            self.color_test_pattern = Some(test_pattern::load(resources));
+        }
+        if !self.aux_windows.is_empty() {
+            info!("Closing the auxiliary windows, they were rendered with the lost device");
+            self.aux_windows.clear();
+        }
+
+        // the cached assets can hold the textures of the old device
+        self.asset_server.clear();
+
+        let context = UpdateContext {
+            time: &self.time,
+            gpu_resources: &self.resources,
+            asset_server: &self.asset_server,
+            raw_input_state: &self.input,
+        };
+        self.screens.recreate_gpu_state(&context);
+
+        Ok(())
     }
 
     pub fn resize(&mut self, new_size: (u32, u32)) {
@@ -182,20 +553,24 @@ impl<'state> State<'state> {
                 .configure(&self.resources.device, &self.surface_config);
 
             self.camera.resize(new_size);
-            self.render_target
-                .resize(&self.resources, self.camera.render_buffer_size());
-
             debug!(
                 "Window resized to {:?}, new render buffer size is {:?}",
                 new_size,
                 self.camera.render_buffer_size()
             );
+            self.resize_render_buffer();
+        }
+    }
 
-            *self.resources.render_buffer_size.write().unwrap() = self.camera.render_buffer_size();
+    /// Applies the render buffer size of the camera to the render target and everything sized after it
+    fn resize_render_buffer(&mut self) {
+        self.render_target
+            .resize(&self.resources, self.camera.render_buffer_size());
 
-            self.pillarbox.resize(&self.resources);
-            self.adv.resize(&self.resources);
-        }
+        *self.resources.render_buffer_size.write().unwrap() = self.camera.render_buffer_size();
+
+        self.pillarbox.resize(&self.resources);
+        self.screens.resize(&self.resources);
     }
 
     #[allow(unused_variables)]
@@ -204,20 +579,57 @@ impl<'state> State<'state> {
         false
     }
 
+    /// Recompiles the pipelines if the shader sources have changed
+    #[cfg(feature = "shader-hot-reload")]
+    fn reload_shaders(&mut self) {
+        if !self.shader_watcher.poll() {
+            return;
+        }
+        // the resources are shared with the layers being loaded, try again on the next frame when they are done
+        let Some(resources) = Arc::get_mut(&mut self.resources) else {
+            return;
+        };
+
+        resources.pipelines.reload(
+            &resources.device,
+            &resources.bind_group_layouts,
+            self.surface_config.format,
+        );
+        self.shader_watcher.reset();
+    }
+
     fn update(&mut self) {
+        #[cfg(feature = "shader-hot-reload")]
+        self.reload_shaders();
+
+        self.profiler.begin_frame();
         self.time.update();
 
+        // there are no worker threads on the web, the tasks run between the frames
+        #[cfg(target_arch = "wasm32")]
+        shin_tasks::tick_global_task_pools_on_main_thread();
+
+        self.gamepads.poll(&mut self.input);
         let mut input = self.input.clone();
+        input.virtual_mouse_position = self.camera.window_to_virtual(input.mouse_position);
+        self.virtual_cursor
+            .update(&mut input, self.time.delta_seconds());
 
+        let stage = self.profiler.begin_stage();
         self.overlay_manager
             .start_update(&self.time, &input, self.window_size);
         self.overlay_manager.visit_overlays(|collector| {
             self.fps_counter.visit_overlay(collector);
+            self.profiler.visit_overlay(collector);
+            self.resources.render_target_pool.visit_overlay(collector);
+            self.asset_server.visit_overlay(collector);
             input.visit_overlay(collector);
-            self.adv.visit_overlay(collector);
+            self.av_sync.visit_overlay(collector);
+            self.screens.visit_overlay(collector);
         });
         self.overlay_manager
             .finish_update(&self.resources, &mut input);
+        self.profiler.end_stage("Overlay update", stage);
 
         let update_context = UpdateContext {
             time: &self.time,
@@ -226,30 +638,109 @@ impl<'state> State<'state> {
             raw_input_state: &input,
         };
 
-        self.adv.update(&update_context);
+        let stage = self.profiler.begin_stage();
+        self.screens.update(&update_context);
         self.fps_counter.update(&update_context);
+        self.asset_server.update_prefetch();
+        self.profiler.end_stage("Update", stage);
+
+        let ducking_settings = self.settings.get().voice_focus.ducking_settings();
+        if ducking_settings != self.ducking_settings {
+            self.audio_manager.set_ducking_settings(ducking_settings);
+            self.ducking_settings = ducking_settings;
+        }
+
+        let render_scale = render_scale(&self.settings, self.default_render_scale);
+        if render_scale != self.camera.render_scale() {
+            self.camera.set_render_scale(render_scale);
+            debug!(
+                "Render scale changed to {}, new render buffer size is {:?}",
+                render_scale,
+                self.camera.render_buffer_size()
+            );
+            self.resize_render_buffer();
+        }
+
+        // the offline audio follows the game clock, including the pauses and the speed changes
+        let stage = self.profiler.begin_stage();
+        if let Err(e) = self.audio_manager.advance(self.time.delta()) {
+            warn!("Failed to render the offline audio: {:?}", e);
+        }
+        self.profiler.end_stage("Offline audio", stage);
 
         // NOTE: it's important that the input is updated after everything else, as it clears some state after it should have been handled
         self.input.update();
     }
 
     fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
+        let capture = std::mem::take(&mut self.capture_next_frame);
+        if capture {
+            self.resources.device.start_capture();
+        }
+        let result = self.render_frame();
+        if capture {
+            self.resources.device.stop_capture();
+        }
+
+        let gpu_timings = self
+            .resources
+            .gpu_profiler
+            .end_frame(&self.resources.device, &self.resources.queue);
+        self.profiler.end_frame(gpu_timings);
+
+        result
+    }
+
+    fn render_frame(&mut self) -> Result<(), wgpu::SurfaceError> {
         // render everything to the render target
+        let stage = self.profiler.begin_stage();
         {
             let mut encoder = self.resources.start_encoder();
-            let mut render_pass = self
-                .render_target
-                .begin_srgb_render_pass(&mut encoder, Some("Screen RenderPass"));
-
-            self.adv.render(
+            let mut render_pass = self.render_target.begin_srgb_render_pass(
                 &self.resources,
-                &mut render_pass,
-                Mat4::IDENTITY,
-                self.render_target.projection_matrix(),
+                &mut encoder,
+                Some("Screen RenderPass"),
             );
+
+            if let Some(test_pattern) = &self.color_test_pattern {
+                self.resources.draw_sprite(
+                    &mut render_pass,
+                    test_pattern.vertex_source(),
+                    test_pattern.bind_group(),
+                    self.render_target.projection_matrix(),
+                );
+            } else {
+                self.screens.render(
+                    &self.resources,
+                    &mut render_pass,
+                    Mat4::IDENTITY,
+                    self.render_target.projection_matrix(),
+                );
+            }
         }
 
-        let output = self.surface.get_current_texture()?;
+        self.profiler.end_stage("Render scene", stage);
+
+        let stage = self.profiler.begin_stage();
+        self.recorder.capture_frame(
+            &self.resources,
+            &self.render_target,
+            self.audio_manager.mix_capture(),
+        );
+        self.profiler.end_stage("Recording", stage);
+
+        let stage = self.profiler.begin_stage();
+        let output = match self.surface.get_current_texture() {
+            // reconfigure with the same settings and try again, so that the frame is not lost
+            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                self.surface
+                    .configure(&self.resources.device, &self.surface_config);
+                self.surface.get_current_texture()?
+            }
+            output => output?,
+        };
+        // it can still be presented, but it's better to reconfigure the surface for the next frame
+        let suboptimal = output.suboptimal;
         let view = output
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
@@ -267,35 +758,285 @@ impl<'state> State<'state> {
                     },
                 })],
                 depth_stencil_attachment: None,
-                timestamp_writes: None,
+                timestamp_writes: self
+                    .resources
+                    .gpu_profiler
+                    .timestamp_writes(Some("Final RenderPass")),
                 occlusion_query_set: None,
             });
 
+            let render_target_bind_group = if self.surface_config.format.is_srgb() {
+                self.render_target.bind_group()
+            } else {
+                self.render_target.raw_bind_group()
+            };
             self.resources.pipelines.sprite_screen.draw(
                 &mut render_pass,
                 self.render_target.vertex_source(),
-                self.render_target.bind_group(),
-                self.camera.screen_projection_matrix(),
-            );
-            self.pillarbox.render(
-                &self.resources,
-                &mut render_pass,
-                Mat4::IDENTITY,
+                render_target_bind_group,
                 self.camera.screen_projection_matrix(),
             );
+            if self.camera.aspect_mode() == AspectMode::Letterbox {
+                self.pillarbox.render(
+                    &self.resources,
+                    &mut render_pass,
+                    Mat4::IDENTITY,
+                    self.camera.screen_projection_matrix(),
+                );
+            }
+            // drawn over the game, so that it's not recorded
+            if let Some(position) = self.virtual_cursor.position() {
+                self.resources.pipelines.sprite_screen.draw(
+                    &mut render_pass,
+                    self.cursor_image.vertex_source(),
+                    self.cursor_image.bind_group(),
+                    self.camera.screen_projection_matrix()
+                        * Mat4::from_translation(position.extend(0.0)),
+                );
+            }
 
             self.overlay_manager
                 .render(&self.resources, &mut render_pass);
         }
 
         output.present();
+        if suboptimal {
+            debug!("The surface is suboptimal, reconfiguring");
+            self.surface
+                .configure(&self.resources.device, &self.surface_config);
+        }
+        self.profiler.end_stage("Final pass and present", stage);
+
+        let stage = self.profiler.begin_stage();
+        for window in self.aux_windows.values_mut() {
+            if let Err(e) = window.render(&self.resources, &self.render_target) {
+                warn!("Failed to render an auxiliary window: {:?}", e);
+            }
+        }
+        self.profiler.end_stage("Auxiliary windows", stage);
+        self.resources.render_target_pool.end_frame();
 
         Ok(())
     }
 }
 
+async fn request_device(
+    adapter: &wgpu::Adapter,
+    config: &Config,
+) -> Result<(wgpu::Device, wgpu::Queue)> {
+    info!("Selected an adapter {:?}", adapter.get_info(),);
+    debug!("Adapter limits: {:?}", adapter.limits());
+
+    let mut device_descriptor = shin_render::init::device_descriptor();
+    if config.debug.profiling() {
+        if adapter.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            device_descriptor.required_features |= wgpu::Features::TIMESTAMP_QUERY;
+        } else {
+            warn!("The adapter doesn't support timestamp queries, the GPU time won't be profiled");
+        }
+    }
+
+    adapter
+        .request_device(&device_descriptor, config.debug.wgpu_trace.as_deref())
+        .await
+        .context("Failed to create wgpu device")
+}
+
+fn load_icon(path: &Path) -> Result<Icon> {
+    let image = image::load_from_memory(
+        &std::fs::read(path).with_context(|| format!("Reading {}", path.display()))?,
+    )
+    .with_context(|| format!("Decoding {}", path.display()))?
+    .into_rgba8();
+    let (width, height) = image.dimensions();
+    Icon::from_rgba(image.into_raw(), width, height).context("Creating the window icon")
+}
+
+fn build_window(
+    event_loop: &EventLoop<()>,
+    config: &Config,
+    settings: &SettingsStore,
+) -> Result<Window> {
+    let window_config = &config.window;
+
+    let mut builder = WindowBuilder::new()
+        .with_title(&window_config.title)
+        .with_decorations(window_config.mode == WindowMode::Windowed)
+        .with_maximized(false);
+
+    builder = match settings.get().window {
+        Some(geometry) if window_config.remember_geometry => builder
+            .with_inner_size(PhysicalSize::new(geometry.width, geometry.height))
+            .with_position(PhysicalPosition::new(geometry.x, geometry.y)),
+        _ => builder.with_inner_size(LogicalSize::new(window_config.width, window_config.height)),
+    };
+
+    if let Some(icon) = &window_config.icon {
+        match load_icon(icon) {
+            Ok(icon) => builder = builder.with_window_icon(Some(icon)),
+            Err(e) => warn!("Failed to load the window icon: {:?}", e),
+        }
+    }
+
+    let window = builder.build(event_loop).context("Creating the window")?;
+    // the monitors are enumerated through the window, so the fullscreen is entered after it's created
+    window.set_fullscreen(fullscreen_for(window_config.mode, &window, settings));
+
+    Ok(window)
+}
+
+fn display_mode(video_mode: &VideoMode) -> DisplayMode {
+    let size = video_mode.size();
+    DisplayMode {
+        width: size.width,
+        height: size.height,
+        refresh_rate_millihertz: video_mode.refresh_rate_millihertz(),
+    }
+}
+
+fn monitor_name(monitor: &MonitorHandle) -> String {
+    monitor
+        .name()
+        .unwrap_or_else(|| "Unknown monitor".to_string())
+}
+
+/// Lists the monitors and their modes to choose from in the settings
+fn enumerate_monitors(window: &Window) -> Vec<MonitorInfo> {
+    let primary = window.primary_monitor();
+    window
+        .available_monitors()
+        .map(|monitor| {
+            let mut video_modes = monitor
+                .video_modes()
+                .map(|mode| display_mode(&mode))
+                .collect::<Vec<_>>();
+            // the modes differing only in the bit depth look the same to the player
+            video_modes.sort_by_key(|mode| {
+                (
+                    Reverse(mode.width * mode.height),
+                    Reverse(mode.refresh_rate_millihertz),
+                    mode.width,
+                )
+            });
+            video_modes.dedup();
+
+            MonitorInfo {
+                name: monitor_name(&monitor),
+                primary: primary.as_ref() == Some(&monitor),
+                video_modes,
+            }
+        })
+        .collect()
+}
+
+/// The render scale chosen in the settings, falling back to the one from the config
+fn render_scale(settings: &SettingsStore, default: f32) -> f32 {
+    settings.get().render_scale.unwrap_or(default)
+}
+
+/// The monitor chosen in the settings, falling back to the primary one
+fn select_monitor(window: &Window, settings: &SettingsStore) -> Option<MonitorHandle> {
+    if let Some(name) = &settings.get().display.monitor {
+        if let Some(monitor) = window
+            .available_monitors()
+            .find(|monitor| &monitor_name(monitor) == name)
+        {
+            return Some(monitor);
+        }
+        warn!("Monitor {:?} is not connected, using the primary one", name);
+    }
+
+    window
+        .primary_monitor()
+        .or_else(|| window.available_monitors().next())
+}
+
+/// The video mode chosen in the settings, falling back to the closest one the monitor supports
+fn select_video_mode(monitor: &MonitorHandle, settings: &SettingsStore) -> Option<VideoMode> {
+    // the highest resolution, then the highest refresh rate
+    let quality = |mode: &VideoMode| {
+        let size = mode.size();
+        (size.width * size.height, mode.refresh_rate_millihertz())
+    };
+
+    if let Some(wanted) = settings.get().display.video_mode {
+        if let Some(mode) = monitor
+            .video_modes()
+            .find(|mode| display_mode(mode) == wanted)
+        {
+            return Some(mode);
+        }
+
+        warn!(
+            "{} doesn't support {}, using the closest mode",
+            monitor_name(monitor),
+            wanted.name()
+        );
+        let same_size = monitor
+            .video_modes()
+            .filter(|mode| mode.size() == PhysicalSize::new(wanted.width, wanted.height))
+            .max_by_key(quality);
+        if same_size.is_some() {
+            return same_size;
+        }
+    }
+
+    monitor.video_modes().max_by_key(quality)
+}
+
+/// The fullscreen state of the window in the `mode`, on the monitor chosen in the settings
+fn fullscreen_for(
+    mode: WindowMode,
+    window: &Window,
+    settings: &SettingsStore,
+) -> Option<Fullscreen> {
+    match mode {
+        WindowMode::Windowed | WindowMode::Borderless => None,
+        WindowMode::BorderlessFullscreen => {
+            Some(Fullscreen::Borderless(select_monitor(window, settings)))
+        }
+        WindowMode::ExclusiveFullscreen => {
+            let monitor = select_monitor(window, settings);
+            match monitor
+                .as_ref()
+                .and_then(|monitor| select_video_mode(monitor, settings))
+            {
+                Some(video_mode) => Some(Fullscreen::Exclusive(video_mode)),
+                None => {
+                    warn!("No video modes available, falling back to borderless fullscreen");
+                    Some(Fullscreen::Borderless(monitor))
+                }
+            }
+        }
+    }
+}
+
+/// Stores the window geometry in the settings, they are saved on exit
+fn remember_geometry(window: &Window, settings: &SettingsStore) {
+    if window.fullscreen().is_some() {
+        return;
+    }
+    let Ok(position) = window.outer_position() else {
+        return;
+    };
+    let size = window.inner_size();
+    if size.width == 0 || size.height == 0 {
+        // minimized
+        return;
+    }
+
+    settings.update(|settings| {
+        settings.window = Some(WindowGeometry {
+            x: position.x,
+            y: position.y,
+            width: size.width,
+            height: size.height,
+        })
+    });
+}
+
 #[cfg_attr(target_arch = "wasm32", wasm_bindgen(start))]
-pub async fn run(cli: Cli) {
+pub async fn run(cli: Cli, config: Config) {
     cfg_if::cfg_if! {
         if #[cfg(target_arch = "wasm32")] {
             std::panic::set_hook(Box::new(console_error_panic_hook::hook));
@@ -307,13 +1048,21 @@ pub async fn run(cli: Cli) {
 
     shin_tasks::create_task_pools();
 
+    if let Some(backend) = cli.h264_decoder {
+        shin_video::set_preferred_h264_decoder_backend(Some(backend));
+    }
+
+    let settings = Arc::new(SettingsStore::load(
+        config
+            .paths
+            .settings_file
+            .clone()
+            .or_else(SettingsStore::default_path),
+    ));
+
     let event_loop = EventLoop::new().unwrap();
-    let window = WindowBuilder::new()
-        .with_inner_size(LogicalSize::new(1920, 1080))
-        .with_maximized(false)
-        .with_position(LogicalPosition::new(1080, 0))
-        .build(&event_loop)
-        .unwrap();
+    let window = build_window(&event_loop, &config, &settings).unwrap();
+    settings.set_monitors(enumerate_monitors(&window));
 
     #[cfg(target_arch = "wasm32")]
     {
@@ -335,16 +1084,27 @@ pub async fn run(cli: Cli) {
     }
 
     // State::new uses async code, so we're going to wait for it to finish
-    let mut state = State::new(&window, &cli)
+    let mut state = State::new(&window, &config, settings.clone())
         .await
         .expect("Failed to initialize the game"); // TODO: report error in a better way
 
     // don't move it pls
     let window = &window;
 
+    let mut pacing = FramePacing::new(config.window.background_fps);
+
     event_loop
         .run(move |event, target| {
             match event {
+                Event::NewEvents(StartCause::ResumeTimeReached { .. }) => {
+                    if pacing.is_hidden() {
+                        // the window is not redrawn while hidden, so only update the game
+                        state.update();
+                        pacing.schedule_next_frame(target, window);
+                    } else {
+                        window.request_redraw();
+                    }
+                }
                 Event::WindowEvent {
                     ref event,
                     window_id,
@@ -357,8 +1117,7 @@ pub async fn run(cli: Cli) {
                                 event:
                                     KeyEvent {
                                         state: ElementState::Pressed,
-                                        physical_key:
-                                            PhysicalKey::Code(KeyCode::Escape | KeyCode::KeyQ),
+                                        physical_key: PhysicalKey::Code(KeyCode::KeyQ),
                                         ..
                                     },
                                 ..
@@ -372,12 +1131,64 @@ pub async fn run(cli: Cli) {
                                     },
                                 ..
                             } => {
-                                window.set_fullscreen(
-                                    window.fullscreen().map_or_else(
-                                        || Some(Fullscreen::Borderless(None)),
-                                        |_| None,
-                                    ),
-                                );
+                                // the monitor and the mode could have been changed in the settings
+                                let mode = match config.window.mode {
+                                    WindowMode::ExclusiveFullscreen => {
+                                        WindowMode::ExclusiveFullscreen
+                                    }
+                                    _ => WindowMode::BorderlessFullscreen,
+                                };
+                                window.set_fullscreen(match window.fullscreen() {
+                                    Some(_) => None,
+                                    None => fullscreen_for(mode, window, &settings),
+                                });
+                            }
+                            WindowEvent::KeyboardInput {
+                                event:
+                                    KeyEvent {
+                                        state: ElementState::Pressed,
+                                        physical_key: PhysicalKey::Code(KeyCode::F9),
+                                        ..
+                                    },
+                                ..
+                            } => {
+                                state.open_aux_window(Box::new(GameViewWindow));
+                            }
+                            WindowEvent::KeyboardInput {
+                                event:
+                                    KeyEvent {
+                                        state: ElementState::Pressed,
+                                        physical_key: PhysicalKey::Code(KeyCode::F8),
+                                        repeat: false,
+                                        ..
+                                    },
+                                ..
+                            } => {
+                                state.toggle_recording();
+                            }
+                            WindowEvent::KeyboardInput {
+                                event:
+                                    KeyEvent {
+                                        state: ElementState::Pressed,
+                                        physical_key: PhysicalKey::Code(KeyCode::F12),
+                                        repeat: false,
+                                        ..
+                                    },
+                                ..
+                            } => {
+                                state.capture_frame();
+                            }
+                            WindowEvent::KeyboardInput {
+                                event:
+                                    KeyEvent {
+                                        state: ElementState::Pressed,
+                                        physical_key: PhysicalKey::Code(KeyCode::F7),
+                                        repeat: false,
+                                        ..
+                                    },
+                                ..
+                            } => {
+                                state.dump_loaded_assets();
                             }
                             WindowEvent::KeyboardInput {
                                 event:
@@ -394,31 +1205,85 @@ pub async fn run(cli: Cli) {
                                     state.resize(new_size.into());
                                 }
                             }
+                            WindowEvent::Focused(focused) => {
+                                pacing.focused = *focused;
+                                pacing.schedule_next_frame(target, window);
+                            }
+                            WindowEvent::Occluded(occluded) => {
+                                debug!("Window occluded: {}", occluded);
+                                pacing.occluded = *occluded;
+                                pacing.schedule_next_frame(target, window);
+                            }
                             WindowEvent::Resized(physical_size) => {
+                                // the window is resized to zero when minimized on some platforms
+                                let minimized =
+                                    physical_size.width == 0 || physical_size.height == 0;
+                                if minimized != pacing.minimized {
+                                    debug!("Window minimized: {}", minimized);
+                                    pacing.minimized = minimized;
+                                    pacing.schedule_next_frame(target, window);
+                                }
                                 state.resize((*physical_size).into());
+                                if config.window.remember_geometry {
+                                    remember_geometry(window, &settings);
+                                }
+                            }
+                            WindowEvent::Moved(_) if config.window.remember_geometry => {
+                                remember_geometry(window, &settings);
                             }
                             WindowEvent::RedrawRequested => {
+                                if let Some(reason) = state.take_device_loss() {
+                                    warn!("Recovering from the device loss: {}", reason);
+                                    if let Err(e) = state.recreate_gpu_state(&config) {
+                                        error!("Failed to recover from the device loss: {:?}", e);
+                                        target.exit();
+                                        return;
+                                    }
+                                }
+
                                 state.update();
-                                match state.render() {
-                                    Ok(_) => {}
-                                    // Reconfigure the surface if it's lost or outdated
+                                let result = match state.render() {
+                                    Ok(_) => Ok(()),
+                                    // Still lost or outdated after reconfiguring it once, the surface could have changed
                                     Err(
                                         wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated,
-                                    ) => {
-                                        state.reconfigure_surface();
-                                    }
+                                    ) => state.reconfigure_surface(&config),
                                     // The system is out of memory, we should probably quit
-                                    Err(wgpu::SurfaceError::OutOfMemory) => target.exit(),
-
-                                    Err(wgpu::SurfaceError::Timeout) => warn!("Surface timeout"),
+                                    Err(wgpu::SurfaceError::OutOfMemory) => {
+                                        error!("Out of memory while acquiring the surface texture");
+                                        target.exit();
+                                        Ok(())
+                                    }
+                                    // The frame is skipped
+                                    Err(wgpu::SurfaceError::Timeout) => {
+                                        warn!("Surface timeout");
+                                        Ok(())
+                                    }
+                                };
+                                if let Err(e) = result {
+                                    error!("Failed to reconfigure the surface: {:?}", e);
+                                    target.exit();
                                 }
 
-                                window.request_redraw();
+                                pacing.schedule_next_frame(target, window);
                             }
                             _ => {}
                         }
                     }
                 }
+                Event::WindowEvent {
+                    ref event,
+                    window_id,
+                } => {
+                    state.aux_window_event(window_id, event);
+                }
+                Event::AboutToWait => state.open_pending_aux_windows(target),
+                Event::LoopExiting => {
+                    state.exit();
+                    if config.window.remember_geometry {
+                        settings.save();
+                    }
+                }
                 _ => {}
             }
         })